@@ -0,0 +1,167 @@
+use crate::{Action, Operation, Program};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Who-talks-to-whom graph built from Emit/Receive actions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActorNetwork {
+    pub actors: Vec<ActorStats>,
+    pub edges: Vec<Interaction>,
+}
+
+/// Messages flowing from one actor to another
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Interaction {
+    pub from: String,
+    pub to: String,
+    /// Channels (targets) the messages went over
+    pub channels: Vec<String>,
+    pub messages: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActorStats {
+    pub actor: String,
+    /// Distinct actors this one sends to
+    pub out_degree: usize,
+    /// Distinct actors this one hears from
+    pub in_degree: usize,
+    pub sent: usize,
+    pub received: usize,
+    /// Share of the other actors it is directly connected to (0..=1)
+    pub centrality: f64,
+}
+
+pub(super) use crate::simulator::channels::channel;
+
+fn param<'a>(action: &'a Action, key: &str) -> Option<&'a str> {
+    action.params.as_ref().and_then(|p| p.get(key)).and_then(|v| v.as_str())
+}
+
+/// Pair every Emit with every Receive on the same channel by another actor,
+/// plus explicit `destination`/`source` params
+pub fn actor_network(program: &Program) -> ActorNetwork {
+    let mut emits: Vec<(String, String)> = Vec::new();
+    let mut receives: Vec<(String, String)> = Vec::new();
+    let mut explicit: Vec<(String, String, String)> = Vec::new();
+
+    program.walk(&mut |action, _| match action.op {
+        Operation::Emit => match param(action, "destination") {
+            Some(to) => explicit.push((action.actor.clone(), to.to_string(), channel(action))),
+            None => emits.push((action.actor.clone(), channel(action))),
+        },
+        Operation::Receive => match param(action, "source") {
+            Some(from) => explicit.push((from.to_string(), action.actor.clone(), channel(action))),
+            None => receives.push((action.actor.clone(), channel(action))),
+        },
+        _ => {}
+    });
+
+    let mut links: BTreeMap<(String, String), (BTreeSet<String>, usize)> = BTreeMap::new();
+    let mut add = |from: &str, to: &str, channel: &str| {
+        if from != to {
+            let link = links.entry((from.to_string(), to.to_string())).or_default();
+            link.0.insert(channel.to_string());
+            link.1 += 1;
+        }
+    };
+
+    for (sender, emitted) in &emits {
+        for (receiver, heard) in &receives {
+            if emitted == heard {
+                add(sender, receiver, emitted);
+            }
+        }
+    }
+    for (from, to, channel) in &explicit {
+        add(from, to, channel);
+    }
+
+    let edges: Vec<Interaction> = links.into_iter()
+        .map(|((from, to), (channels, messages))| Interaction {
+            from,
+            to,
+            channels: channels.into_iter().collect(),
+            messages,
+        })
+        .collect();
+
+    let names: BTreeSet<&str> = emits.iter().chain(&receives).map(|(actor, _)| actor.as_str())
+        .chain(edges.iter().flat_map(|e| [e.from.as_str(), e.to.as_str()]))
+        .collect();
+    let others = names.len().saturating_sub(1).max(1) as f64;
+
+    let actors = names.iter()
+        .map(|&name| {
+            let outgoing: Vec<&Interaction> = edges.iter().filter(|e| e.from == name).collect();
+            let incoming: Vec<&Interaction> = edges.iter().filter(|e| e.to == name).collect();
+            let neighbours: BTreeSet<&str> = outgoing.iter().map(|e| e.to.as_str())
+                .chain(incoming.iter().map(|e| e.from.as_str()))
+                .collect();
+
+            ActorStats {
+                actor: name.to_string(),
+                out_degree: outgoing.len(),
+                in_degree: incoming.len(),
+                sent: outgoing.iter().map(|e| e.messages).sum(),
+                received: incoming.iter().map(|e| e.messages).sum(),
+                centrality: neighbours.len() as f64 / others,
+            }
+        })
+        .collect();
+
+    ActorNetwork { actors, edges }
+}
+
+impl ActorNetwork {
+    /// Graphviz digraph, edges labelled with their channels
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph actors {\n    rankdir=LR;\n");
+        for actor in &self.actors {
+            dot.push_str(&format!("    \"{}\";\n", escape(&actor.actor)));
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                escape(&edge.from), escape(&edge.to), escape(&edge.channels.join(", "))
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Actors ordered from most to least connected
+    pub fn by_centrality(&self) -> Vec<&ActorStats> {
+        let mut actors: Vec<&ActorStats> = self.actors.iter().collect();
+        actors.sort_by(|a, b| b.centrality.total_cmp(&a.centrality).then(a.actor.cmp(&b.actor)));
+        actors
+    }
+}
+
+pub(super) fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_pairs_emit_and_receive() {
+        let json = r#"{"actions": [
+            {"actor": "Alice", "op": "Emit", "target": "greeting"},
+            {"actor": "Bob", "op": "Receive", "target": "greeting"},
+            {"actor": "Carol", "op": "Receive", "target": "greeting"},
+            {"actor": "VM", "op": "DefineFunction", "target": "answer", "params": {"args": [], "body": [
+                {"actor": "Bob", "op": "Emit", "target": "reply", "params": {"destination": "Alice"}}
+            ]}}
+        ]}"#;
+        let network = actor_network(&Program::from_json(json).unwrap());
+
+        assert_eq!(network.edges.len(), 3);
+        let alice = network.actors.iter().find(|a| a.actor == "Alice").unwrap();
+        assert_eq!((alice.out_degree, alice.in_degree), (2, 1));
+        assert_eq!(alice.centrality, 1.0);
+        assert!(network.to_dot().contains("\"Bob\" -> \"Alice\" [label=\"reply\"]"));
+    }
+}
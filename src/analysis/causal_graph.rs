@@ -0,0 +1,233 @@
+use crate::analysis::actor_network::{channel, escape};
+use crate::analysis::{reads, writes};
+use crate::{Action, Operation, Program};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Which actions produce the values later actions consume
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CausalGraph {
+    pub nodes: Vec<CausalNode>,
+    pub edges: Vec<CausalEdge>,
+}
+
+/// One action, nested ones included, numbered in [`Program::walk`] order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CausalNode {
+    pub id: usize,
+    /// Where the action is, e.g. `actions[2].then[0]`
+    pub path: String,
+    pub actor: String,
+    /// `Bind x`
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// A `{"var"}`, `*_register` param or `For` bound reads a variable
+    Variable,
+    /// A `Read` of a target something `Write`s or stores into
+    Memory,
+    /// A `Receive` on a channel (its `channel` param, else its target)
+    /// something `Emit`s on
+    Message,
+    /// An id named in the action's `caused_by`
+    Cause,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CausalEdge {
+    pub from: usize,
+    pub to: usize,
+    /// The variable, target or channel the value passes through
+    pub via: String,
+    pub kind: EdgeKind,
+}
+
+/// Link each consumer to the most recent earlier action, in walk order, that
+/// produced what it consumes, and each action to those its `caused_by` names
+///
+/// Walk order is program order for straight-line code; a value written in a
+/// branch or loop links to later readers whether or not the branch runs.
+pub fn causal_graph(program: &Program) -> CausalGraph {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    let mut producers: HashMap<String, usize> = HashMap::new();
+    let mut emitters: HashMap<String, usize> = HashMap::new();
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    let mut next = 0;
+    program.walk(&mut |action, _| {
+        if let Some(id) = &action.id {
+            ids.entry(id.clone()).or_insert(next);
+        }
+        next += 1;
+    });
+
+    program.walk(&mut |action, path| {
+        let id = nodes.len();
+        nodes.push(CausalNode {
+            id,
+            path: path.to_string(),
+            actor: action.actor.clone(),
+            label: format!("{} {}", op_name(&action.op), action.target),
+        });
+
+        let mut consumed: Vec<(String, EdgeKind)> = variables_read(action).into_iter().map(|name| (name, EdgeKind::Variable)).collect();
+        match action.op {
+            Operation::Read => consumed.push((action.target.clone(), EdgeKind::Memory)),
+            Operation::Receive => consumed.push((channel(action), EdgeKind::Message)),
+            _ => {}
+        }
+        consumed.extend(action.caused_by.iter().flatten().map(|cause| (cause.clone(), EdgeKind::Cause)));
+
+        for (name, kind) in consumed {
+            let source = match kind {
+                EdgeKind::Message => emitters.get(&name),
+                EdgeKind::Cause => ids.get(&name),
+                _ => producers.get(&name),
+            };
+            if let Some(&from) = source {
+                let edge = CausalEdge { from, to: id, via: name, kind };
+                if from != id && !edges.contains(&edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+
+        for name in writes(action) {
+            producers.insert(name, id);
+        }
+        if let Some(variable) = &action.loop_var {
+            producers.insert(variable.clone(), id);
+        }
+        if action.op == Operation::Emit {
+            emitters.insert(channel(action), id);
+        }
+    });
+
+    CausalGraph { nodes, edges }
+}
+
+fn op_name(op: &Operation) -> String {
+    match op {
+        Operation::Custom(name) => name.clone(),
+        op => format!("{:?}", op),
+    }
+}
+
+/// Variables an action reads, including in a `For`'s bounds; a function
+/// definition reads nothing itself, its body's actions do
+fn variables_read(action: &Action) -> Vec<String> {
+    if action.op == Operation::DefineFunction {
+        return Vec::new();
+    }
+    let mut names = reads(action);
+    for bound in [&action.from_expr, &action.to_expr, &action.step_expr].into_iter().flatten() {
+        if let Ok(value) = serde_json::to_value(bound) {
+            super::collect_vars(&value, &mut names);
+        }
+    }
+    names.dedup();
+    names
+}
+
+impl CausalGraph {
+    /// Graphviz digraph, edges labelled with what flows along them
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph causality {\n    rankdir=TB;\n    node [shape=box];\n");
+        for node in &self.nodes {
+            dot.push_str(&format!("    n{} [label=\"{}\\n{}\"];\n", node.id, escape(&node.label), escape(&node.actor)));
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Variable => "",
+                EdgeKind::Memory => ", style=dashed",
+                EdgeKind::Message => ", style=dotted",
+                EdgeKind::Cause => ", style=bold",
+            };
+            dot.push_str(&format!("    n{} -> n{} [label=\"{}\"{}];\n", edge.from, edge.to, escape(&edge.via), style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Mermaid flowchart, for Markdown that renders diagrams
+    pub fn to_mermaid(&self) -> String {
+        let text = |s: &str| s.replace('"', "#quot;");
+        let mut mermaid = String::from("flowchart TD\n");
+        for node in &self.nodes {
+            mermaid.push_str(&format!("    n{}[\"{}<br/>{}\"]\n", node.id, text(&node.label), text(&node.actor)));
+        }
+        for edge in &self.edges {
+            let arrow = match edge.kind {
+                EdgeKind::Variable => "-->",
+                EdgeKind::Memory | EdgeKind::Message => "-.->",
+                EdgeKind::Cause => "==>",
+            };
+            mermaid.push_str(&format!("    n{} {}|\"{}\"| n{}\n", edge.from, arrow, text(&edge.via), edge.to));
+        }
+        mermaid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> CausalGraph {
+        causal_graph(&Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "n", "params": {"value": 3}},
+            {"actor": "VM", "op": "Write", "target": "cell", "params": {"value": 1}},
+            {"actor": "VM", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": {"var": "n"}, "body": [
+                {"actor": "VM", "op": "Bind", "target": "sq", "params": {"value": {"expr": {"op": "*", "left": {"var": "i"}, "right": {"var": "i"}}}}}
+            ]},
+            {"actor": "Alice", "op": "Emit", "target": "news", "params": {"content": {"var": "sq"}}},
+            {"actor": "Bob", "op": "Receive", "target": "news", "id": "heard"},
+            {"actor": "VM", "op": "Read", "target": "cell", "caused_by": ["heard"]}
+        ]}"#).unwrap())
+    }
+
+    #[test]
+    fn test_edges_follow_values() {
+        let graph = graph();
+        assert_eq!(graph.nodes.len(), 7);
+        assert_eq!(graph.nodes[3].path, "actions[2].body[0]");
+
+        let edges: Vec<(usize, usize, &str, EdgeKind)> = graph.edges.iter().map(|e| (e.from, e.to, e.via.as_str(), e.kind)).collect();
+        assert_eq!(edges, vec![
+            (0, 2, "n", EdgeKind::Variable),
+            (2, 3, "i", EdgeKind::Variable),
+            (3, 4, "sq", EdgeKind::Variable),
+            (4, 5, "news", EdgeKind::Message),
+            (1, 6, "cell", EdgeKind::Memory),
+            (5, 6, "heard", EdgeKind::Cause),
+        ]);
+    }
+
+    #[test]
+    fn test_call_out_produces_its_variable() {
+        let graph = causal_graph(&Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Call", "target": "double", "params": {"n": 21, "out": "res"}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "res"}}}
+        ]}"#).unwrap());
+        assert!(graph.edges.iter().any(|e| (e.from, e.to, e.via.as_str()) == (2, 3, "res")), "{:?}", graph.edges);
+    }
+
+    #[test]
+    fn test_exports() {
+        let graph = graph();
+        let dot = graph.to_dot();
+        assert!(dot.contains("n0 [label=\"Bind n\\nVM\"];"));
+        assert!(dot.contains("n4 -> n5 [label=\"news\", style=dotted];"));
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("    n0 -->|\"n\"| n2\n"));
+        assert!(mermaid.contains("    n1 -.->|\"cell\"| n6\n"));
+        assert!(mermaid.contains("    n5 ==>|\"heard\"| n6\n"));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::Program;
+use crate::analysis::reads;
+use std::collections::HashMap;
+
+/// Timing of one top-level action in the dependency graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledAction {
+    pub index: usize,
+    /// Indices of the actions this one has to wait for
+    pub depends_on: Vec<usize>,
+    pub earliest_start: f64,
+    pub earliest_finish: f64,
+    pub latest_start: f64,
+    /// How far the action can slip without delaying the whole program
+    pub slack: f64,
+}
+
+/// Critical-path schedule of a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub actions: Vec<ScheduledAction>,
+    /// Time from the first start to the last finish
+    pub makespan: f64,
+    /// Zero-slack chain of actions that determines the makespan
+    pub critical_path: Vec<usize>,
+    /// Independent actions that could run at the same time, by dependency depth
+    pub parallel_groups: Vec<Vec<usize>>,
+}
+
+/// How busy one actor is in a [`Schedule`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActorLoad {
+    pub actor: String,
+    /// Total duration of the actor's actions
+    pub busy: f64,
+    /// `busy` as a fraction of the makespan
+    pub utilization: f64,
+    /// `(from, to)` stretches between the actor's first start and last
+    /// finish where it does nothing
+    pub idle: Vec<(f64, f64)>,
+}
+
+const EPSILON: f64 = 1e-9;
+
+/// Direct dependencies of each top-level action
+///
+/// An action waits for:
+/// - an earlier action whose `post` matches its `pre`
+/// - the previous action by the same actor (actors do one thing at a time)
+/// - an earlier action whose target it reads (`{"var": ...}` or a `*_register` param)
+/// - an earlier action named in its `caused_by`, or a nested action's
+///
+/// Actions in the same simultaneous group (consecutive, same `t`) only wait
+/// for each other to read a target written earlier in the group, since the
+/// simulators run the group in order.
+pub fn dependencies(program: &Program) -> Vec<Vec<usize>> {
+    let mut last_by_actor: HashMap<&str, usize> = HashMap::new();
+    let mut last_writer: HashMap<&str, usize> = HashMap::new();
+    let causes = crate::links::top_level_causes(program);
+    let mut deps = Vec::with_capacity(program.actions.len());
+
+    for group in program.simultaneous_groups() {
+        for action in &program.actions[group.clone()] {
+            let mut mine = Vec::new();
+
+            if let Some(pre) = &action.pre {
+                mine.extend(program.actions[..group.start].iter().enumerate()
+                    .filter(|(_, earlier)| earlier.post.as_ref().is_some_and(|post| post.eq_ignore_ascii_case(pre)))
+                    .map(|(i, _)| i));
+            }
+
+            if let Some(&previous) = last_by_actor.get(action.actor.as_str()) {
+                mine.push(previous);
+            }
+
+            for name in reads(action) {
+                if let Some(&writer) = last_writer.get(name.as_str()) {
+                    mine.push(writer);
+                }
+            }
+            mine.extend(causes[deps.len()].iter().filter(|&&cause| cause < group.start));
+
+            mine.sort_unstable();
+            mine.dedup();
+            last_writer.insert(&action.target, deps.len());
+            deps.push(mine);
+        }
+
+        for index in group {
+            last_by_actor.insert(&program.actions[index].actor, index);
+        }
+    }
+
+    deps
+}
+
+/// Schedule every top-level action as early as its dependencies and its `t`
+/// allow, then work backwards to find slack and the critical path
+///
+/// Actions without `dur` take no time.
+pub fn critical_path(program: &Program) -> Schedule {
+    let deps = dependencies(program);
+    let actions = &program.actions;
+    let duration = |i: usize| actions[i].dur.unwrap_or(0.0);
+
+    // Forward pass; dependencies always point backwards, so program order is topological
+    let mut earliest_start = vec![0.0; actions.len()];
+    let mut earliest_finish = vec![0.0; actions.len()];
+    for i in 0..actions.len() {
+        let ready = deps[i].iter().map(|&d| earliest_finish[d]).fold(0.0, f64::max);
+        earliest_start[i] = actions[i].t.map_or(ready, |t| t.max(ready));
+        earliest_finish[i] = earliest_start[i] + duration(i);
+    }
+
+    let start = earliest_start.iter().copied().fold(f64::INFINITY, f64::min);
+    let end = earliest_finish.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let makespan = if actions.is_empty() { 0.0 } else { end - start };
+
+    // Backward pass
+    let mut latest_finish = vec![end; actions.len()];
+    for i in (0..actions.len()).rev() {
+        let latest_start = latest_finish[i] - duration(i);
+        for &d in &deps[i] {
+            latest_finish[d] = latest_finish[d].min(latest_start);
+        }
+    }
+
+    let scheduled: Vec<ScheduledAction> = (0..actions.len())
+        .map(|i| {
+            let latest_start = latest_finish[i] - duration(i);
+            ScheduledAction {
+                index: i,
+                depends_on: deps[i].clone(),
+                earliest_start: earliest_start[i],
+                earliest_finish: earliest_finish[i],
+                latest_start,
+                slack: (latest_start - earliest_start[i]).max(0.0),
+            }
+        })
+        .collect();
+
+    Schedule {
+        critical_path: trace_critical_path(&scheduled, end),
+        parallel_groups: parallel_groups(&deps),
+        actions: scheduled,
+        makespan,
+    }
+}
+
+/// Busy time, utilization and idle gaps of each actor when the program runs
+/// as `schedule` lays it out, in order of first appearance
+pub fn actor_loads(program: &Program, schedule: &Schedule) -> Vec<ActorLoad> {
+    let mut by_actor: Vec<(&str, Vec<&ScheduledAction>)> = Vec::new();
+    for scheduled in &schedule.actions {
+        let actor = program.actions[scheduled.index].actor.as_str();
+        match by_actor.iter_mut().find(|(name, _)| *name == actor) {
+            Some((_, actions)) => actions.push(scheduled),
+            None => by_actor.push((actor, vec![scheduled])),
+        }
+    }
+
+    by_actor.into_iter().map(|(actor, mut actions)| {
+        actions.sort_by(|a, b| a.earliest_start.total_cmp(&b.earliest_start));
+        let busy: f64 = actions.iter().map(|a| a.earliest_finish - a.earliest_start).sum();
+
+        let mut idle = Vec::new();
+        let mut free_from = actions[0].earliest_start;
+        for action in &actions {
+            if action.earliest_start - free_from > EPSILON {
+                idle.push((free_from, action.earliest_start));
+            }
+            free_from = free_from.max(action.earliest_finish);
+        }
+
+        ActorLoad {
+            actor: actor.to_string(),
+            busy,
+            utilization: if schedule.makespan > 0.0 { busy / schedule.makespan } else { 0.0 },
+            idle,
+        }
+    }).collect()
+}
+
+/// Walk back from the action that finishes last through zero-slack
+/// predecessors that finish exactly when it starts
+fn trace_critical_path(actions: &[ScheduledAction], end: f64) -> Vec<usize> {
+    let mut path = Vec::new();
+    let mut current = actions.iter()
+        .rev()
+        .find(|a| a.slack < EPSILON && (a.earliest_finish - end).abs() < EPSILON);
+
+    while let Some(action) = current {
+        path.push(action.index);
+        current = action.depends_on.iter()
+            .rev()
+            .map(|&d| &actions[d])
+            .find(|d| d.slack < EPSILON && (d.earliest_finish - action.earliest_start).abs() < EPSILON);
+    }
+
+    path.reverse();
+    path
+}
+
+/// Group actions by dependency depth, keeping only groups with more than one
+fn parallel_groups(deps: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut groups = crate::dag::waves(deps);
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_critical_path_and_slack() {
+        // a (2) → c (3) is critical; b (1) runs alongside with 4 units of slack
+        let json = r#"{"actions": [
+            {"actor": "A", "op": "Heat", "target": "water", "dur": 2, "post": "hot water"},
+            {"actor": "B", "op": "Gather", "target": "cup", "dur": 1},
+            {"actor": "C", "op": "Pour", "target": "tea", "dur": 3, "pre": "hot water"}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let schedule = critical_path(&program);
+
+        assert_eq!(schedule.makespan, 5.0);
+        assert_eq!(schedule.critical_path, vec![0, 2]);
+        assert_eq!(schedule.actions[1].slack, 4.0);
+        assert_eq!(schedule.actions[2].depends_on, vec![0]);
+        assert_eq!(schedule.parallel_groups, vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_actor_loads() {
+        // The cook waits for the kettle between gathering and pouring
+        let json = r#"{"actions": [
+            {"actor": "cook", "op": "Gather", "target": "cup", "dur": 1},
+            {"actor": "kettle", "op": "Heat", "target": "water", "dur": 4, "post": "boiled"},
+            {"actor": "cook", "op": "Pour", "target": "water", "dur": 1, "pre": "boiled"}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let loads = actor_loads(&program, &critical_path(&program));
+        assert_eq!(loads, vec![
+            ActorLoad { actor: "cook".to_string(), busy: 2.0, utilization: 0.4, idle: vec![(1.0, 4.0)] },
+            ActorLoad { actor: "kettle".to_string(), busy: 4.0, utilization: 0.8, idle: vec![] },
+        ]);
+    }
+
+    #[test]
+    fn test_register_reads_create_dependencies() {
+        let json = r#"{"actions": [
+            {"actor": "VM1", "op": "GenRandomInt", "target": "n"},
+            {"actor": "VM2", "op": "Write", "target": "m", "params": {"lhs_register": "n", "rhs": 2}}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        assert_eq!(dependencies(&program), vec![vec![], vec![0]]);
+    }
+
+    #[test]
+    fn test_caused_by_creates_dependencies() {
+        let json = r#"{"actions": [
+            {"id": "order", "actor": "Buyer", "op": "Emit", "target": "order"},
+            {"actor": "Bank", "op": "Write", "target": "ledger"},
+            {"actor": "Seller", "op": "If", "target": "stock", "condition": {"type": "comparison", "op": ">", "left": 1, "right": 0},
+             "then": [{"actor": "Seller", "op": "Emit", "target": "goods", "caused_by": ["order"]}]}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        assert_eq!(dependencies(&program), vec![vec![], vec![], vec![0]]);
+    }
+
+    #[test]
+    fn test_simultaneous_actions_run_in_parallel() {
+        // A chord: three notes by one player at t=0, then a note that reads nothing
+        let json = r#"{"actions": [
+            {"actor": "Piano", "op": "Emit", "target": "C4", "t": 0, "dur": 1},
+            {"actor": "Piano", "op": "Emit", "target": "E4", "t": 0, "dur": 1},
+            {"actor": "Piano", "op": "Emit", "target": "G4", "t": 0, "dur": 1},
+            {"actor": "Piano", "op": "Emit", "target": "F4", "t": 1, "dur": 1}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        assert_eq!(dependencies(&program), vec![vec![], vec![], vec![], vec![2]]);
+
+        let schedule = critical_path(&program);
+        assert_eq!(schedule.parallel_groups, vec![vec![0, 1, 2]]);
+        assert_eq!(schedule.makespan, 2.0);
+    }
+
+    #[test]
+    fn test_reads_within_a_simultaneous_group_wait() {
+        let json = r#"{"actions": [
+            {"actor": "a", "op": "Bind", "target": "x", "t": 0, "params": {"value": 1}},
+            {"actor": "b", "op": "Bind", "target": "y", "t": 0, "params": {"value": {"var": "x"}}},
+            {"actor": "c", "op": "Bind", "target": "z", "t": 0, "params": {"value": 2}}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        assert_eq!(dependencies(&program), vec![vec![], vec![0], vec![]]);
+
+        let mut brain = crate::simulator::BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state().beliefs["y"], serde_json::json!(1));
+    }
+}
@@ -0,0 +1,267 @@
+//! Where variables and functions are used before they exist
+//!
+//! Follows the program in order, through both branches of every `If`, and
+//! only counts a name as bound after a branch or loop if every way through
+//! binds it. Function bodies run when called, so they may read their args,
+//! anything the program binds and any function it defines. A `then` branch
+//! may read whatever its condition checks `exists`.
+
+use crate::analysis::{collect_vars, reads, writes};
+use crate::{Action, Condition, Operation, Program};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionIssueKind {
+    /// A variable read where it may not be bound yet
+    ReadBeforeBind,
+    /// A `{"call"}` to a function not defined yet
+    CalledBeforeDefined,
+    /// A `For` whose loop variable overwrites a variable bound earlier
+    LoopShadowsVariable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefinitionIssue {
+    /// Where the action is, e.g. `actions[2].then[0]`
+    pub path: String,
+    pub kind: DefinitionIssueKind,
+    pub name: String,
+}
+
+impl DefinitionIssue {
+    pub fn display(&self) -> String {
+        let message = match self.kind {
+            DefinitionIssueKind::ReadBeforeBind => format!("'{}' may be read before it is bound", self.name),
+            DefinitionIssueKind::CalledBeforeDefined => format!("'{}' is called before it is defined", self.name),
+            DefinitionIssueKind::LoopShadowsVariable => format!("loop variable '{}' overwrites a variable bound earlier", self.name),
+        };
+        format!("{}: {}", self.path, message)
+    }
+}
+
+/// Names bound at one point of the program
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    vars: HashSet<String>,
+    functions: HashSet<String>,
+}
+
+struct Checker {
+    /// Everything the program binds anywhere, visible to function bodies
+    globals: HashSet<String>,
+    functions: HashSet<String>,
+    issues: Vec<DefinitionIssue>,
+}
+
+/// Reads of unbound variables, calls to undefined functions and loop
+/// variables that overwrite other variables, in program order
+pub fn check_definitions(program: &Program) -> Vec<DefinitionIssue> {
+    let mut checker = Checker { globals: HashSet::new(), functions: HashSet::new(), issues: Vec::new() };
+    program.walk(&mut |action, _| {
+        checker.globals.extend(writes(action));
+        if action.op == Operation::DefineFunction {
+            checker.functions.insert(action.target.clone());
+        }
+        checker.globals.extend(action.loop_var.clone());
+    });
+
+    checker.block(&program.actions, "actions", Scope::default(), false);
+    checker.issues
+}
+
+impl Checker {
+    /// Check a list of actions starting from `scope`, returning what's bound
+    /// after it
+    fn block(&mut self, actions: &[Action], path: &str, mut scope: Scope, in_function: bool) -> Scope {
+        for (index, action) in actions.iter().enumerate() {
+            let location = format!("{}[{}]", path, index);
+            self.uses(action, &location, &scope, in_function);
+
+            if let Some(var) = &action.loop_var {
+                if action.op == Operation::For && scope.vars.contains(var) {
+                    self.issue(&location, DefinitionIssueKind::LoopShadowsVariable, var);
+                }
+            }
+
+            let mut branches = Vec::new();
+            action.visit_children(&mut |name, nested| {
+                let nested_path = format!("{}.{}", location, name);
+                let after = if name == "params.body" && action.op == Operation::DefineFunction {
+                    let mut body = Scope { vars: self.globals.clone(), functions: self.functions.clone() };
+                    body.vars.extend(function_args(action));
+                    self.block(nested, &nested_path, body, true)
+                } else {
+                    let mut inner = scope.clone();
+                    inner.vars.extend(action.loop_var.clone());
+                    if name == "then" {
+                        inner.vars.extend(action.condition.iter().flat_map(proven));
+                    }
+                    self.block(nested, &nested_path, inner, in_function)
+                };
+                branches.push(after);
+            });
+
+            // Only an If with both branches binds anything for sure
+            if action.op == Operation::If && branches.len() == 2 {
+                let (then, otherwise) = (&branches[0], &branches[1]);
+                scope.vars.extend(then.vars.intersection(&otherwise.vars).cloned());
+                scope.functions.extend(then.functions.intersection(&otherwise.functions).cloned());
+            }
+            scope.vars.extend(writes(action));
+            if action.op == Operation::DefineFunction {
+                scope.functions.insert(action.target.clone());
+            }
+        }
+        scope
+    }
+
+    fn uses(&mut self, action: &Action, location: &str, scope: &Scope, in_function: bool) {
+        // A function's body is checked when the walk reaches it
+        let mut names = if action.op == Operation::DefineFunction { Vec::new() } else { reads(action) };
+        for expression in [&action.from_expr, &action.to_expr, &action.step_expr].into_iter().flatten() {
+            if let Ok(value) = serde_json::to_value(expression) {
+                collect_vars(&value, &mut names);
+            }
+        }
+        // Probing with an `exists` condition is how to read something safely
+        let mut seen: HashSet<String> = HashSet::new();
+        for value in action.params.iter().flat_map(|p| p.values()).chain(action.condition.as_ref().and_then(|c| serde_json::to_value(c).ok()).as_ref()) {
+            collect_probes(value, &mut seen);
+        }
+        for name in names {
+            if !scope.vars.contains(&name) && seen.insert(name.clone()) {
+                self.issue(location, DefinitionIssueKind::ReadBeforeBind, &name);
+            }
+        }
+
+        let mut calls = Vec::new();
+        for (key, value) in action.params.iter().flatten() {
+            if !(action.op == Operation::DefineFunction && key == "body") {
+                collect_calls(value, &mut calls);
+            }
+        }
+        if let Some(value) = action.condition.as_ref().and_then(|c| serde_json::to_value(c).ok()) {
+            collect_calls(&value, &mut calls);
+        }
+        let defined = if in_function { &self.functions } else { &scope.functions };
+        let missing: Vec<String> = calls.into_iter().filter(|f| !defined.contains(f)).collect();
+        for name in missing {
+            self.issue(location, DefinitionIssueKind::CalledBeforeDefined, &name);
+        }
+    }
+
+    fn issue(&mut self, path: &str, kind: DefinitionIssueKind, name: &str) {
+        self.issues.push(DefinitionIssue { path: path.to_string(), kind, name: name.to_string() });
+    }
+}
+
+fn function_args(action: &Action) -> Vec<String> {
+    action.params.as_ref()
+        .and_then(|p| p.get("args"))
+        .and_then(|args| args.as_array())
+        .map(|args| args.iter().filter_map(|a| a.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+/// Names a condition only holds for if they exist
+fn proven(condition: &Condition) -> Vec<String> {
+    match condition {
+        Condition::Exists { var } => vec![var.clone()],
+        Condition::And { operands } => operands.iter().flat_map(proven).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Names `{"type": "exists"}` conditions in `value` probe
+fn collect_probes(value: &serde_json::Value, probes: &mut HashSet<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if map.get("type").and_then(|t| t.as_str()) == Some("exists") {
+                probes.extend(map.get("var").and_then(|v| v.as_str()).map(str::to_string));
+            }
+            map.values().for_each(|v| collect_probes(v, probes));
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|v| collect_probes(v, probes)),
+        _ => {}
+    }
+}
+
+/// Names of the functions `{"call"}` expressions in `value` call
+pub(crate) fn collect_calls(value: &serde_json::Value, calls: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(name) = map.get("call").and_then(|c| c.as_str()) {
+                if !calls.iter().any(|c| c == name) {
+                    calls.push(name.to_string());
+                }
+            }
+            map.values().for_each(|v| collect_calls(v, calls));
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|v| collect_calls(v, calls)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(json: &str) -> Vec<String> {
+        check_definitions(&Program::from_json(json).unwrap()).iter().map(DefinitionIssue::display).collect()
+    }
+
+    #[test]
+    fn test_flags_reads_calls_and_shadowing() {
+        assert_eq!(issues(r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "a", "params": {"value": {"var": "early"}}},
+            {"actor": "VM", "op": "Bind", "target": "b", "params": {"value": {"call": "square", "args": {"x": 2}}}},
+            {"actor": "VM", "op": "DefineFunction", "target": "square", "params": {"args": ["x"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"call": "helper", "args": {"y": {"var": "x"}}}}}
+            ]}},
+            {"actor": "VM", "op": "DefineFunction", "target": "helper", "params": {"args": ["y"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"var": "late"}}}
+            ]}},
+            {"actor": "VM", "op": "If", "target": "check", "condition": {"type": "comparison", "op": ">", "left": {"var": "a"}, "right": 0},
+             "then": [{"actor": "VM", "op": "Bind", "target": "both", "params": {"value": 1}}, {"actor": "VM", "op": "Bind", "target": "one", "params": {"value": 1}}],
+             "else": [{"actor": "VM", "op": "Bind", "target": "both", "params": {"value": 2}}]},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"expr": {"op": "+", "left": {"var": "both"}, "right": {"var": "one"}}}}},
+            {"actor": "VM", "op": "For", "target": "loop", "variable": "a", "from": 1, "to": {"var": "n"}, "body": [
+                {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "a"}}}
+            ]},
+            {"actor": "VM", "op": "Bind", "target": "late", "params": {"value": 0}}
+        ]}"#), vec![
+            "actions[0]: 'early' may be read before it is bound",
+            "actions[1]: 'square' is called before it is defined",
+            "actions[5]: 'one' may be read before it is bound",
+            "actions[6]: 'n' may be read before it is bound",
+            "actions[6]: loop variable 'a' overwrites a variable bound earlier",
+        ]);
+    }
+
+    #[test]
+    fn test_exists_is_not_a_read() {
+        assert_eq!(issues(r#"{"actions": [
+            {"actor": "VM", "op": "If", "target": "check", "condition": {"type": "exists", "var": "cache"}, "then": [
+                {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "cache"}}}
+            ]},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "other"}}}
+        ]}"#), vec!["actions[1]: 'other' may be read before it is bound"]);
+    }
+
+    #[test]
+    fn test_call_out_binds() {
+        assert_eq!(issues(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Call", "target": "double", "params": {"n": 21, "out": "res"}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "res"}}}
+        ]}"#), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_clean_program() {
+        let program = Program::load(std::path::Path::new("examples/fibonacci.json")).unwrap();
+        assert_eq!(check_definitions(&program), vec![]);
+    }
+}
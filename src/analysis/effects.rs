@@ -0,0 +1,130 @@
+//! Infer `effects` domain tags from operations and params
+
+use crate::{Action, Operation, Program};
+
+/// Domain tags implied by an action's operation and params
+pub fn infer_effects(action: &Action) -> Vec<String> {
+    let mut tags: Vec<&str> = Vec::new();
+
+    match action.op {
+        Operation::Gather | Operation::Pour | Operation::Mix | Operation::Stir
+        | Operation::Place | Operation::Remove | Operation::Grasp | Operation::Release
+        | Operation::Serve => tags.push("Physical"),
+        Operation::Heat | Operation::Steep => tags.extend(["Physical", "Thermal"]),
+        Operation::Measure | Operation::Scan | Operation::ReadSensor => tags.push("Sensing"),
+        Operation::Oblige | Operation::Permit | Operation::Remedy => tags.push("Legal"),
+        Operation::Transcribe | Operation::Translate | Operation::Express => tags.push("Biology"),
+        Operation::Emit | Operation::Receive | Operation::Vote => tags.push("Communication"),
+        Operation::Read | Operation::Write | Operation::StoreFact | Operation::Assert => tags.push("Memory"),
+        Operation::GenRandomInt => tags.push("Random"),
+        Operation::Generate | Operation::Parse | Operation::Execute => tags.push("AI"),
+        _ => {}
+    }
+
+    if let Some(params) = &action.params {
+        let has = |key: &str| params.contains_key(key);
+        if (has("temperature") || has("temp")) && !tags.contains(&"Thermal") {
+            tags.push("Thermal");
+        }
+        if (has("channel") || has("destination")) && !tags.contains(&"Communication") {
+            tags.push("Communication");
+        }
+        if (has("duty") || has("party")) && !tags.contains(&"Legal") {
+            tags.push("Legal");
+        }
+    }
+
+    tags.into_iter().map(|t| t.to_string()).collect()
+}
+
+/// Whether a declared tag covers an inferred one (`Bio` covers `Biology`)
+fn covers(declared: &str, inferred: &str) -> bool {
+    let (declared, inferred) = (declared.to_lowercase(), inferred.to_lowercase());
+    declared.starts_with(&inferred) || inferred.starts_with(&declared)
+}
+
+/// Inferred tags an action doesn't already declare
+pub fn missing_effects(action: &Action) -> Vec<String> {
+    let declared = action.effects.as_deref().unwrap_or_default();
+    infer_effects(action)
+        .into_iter()
+        .filter(|tag| !declared.iter().any(|d| covers(d, tag)))
+        .collect()
+}
+
+/// An action whose declared effects lack inferred tags
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectGap {
+    /// e.g. `actions[2].then[0]`
+    pub location: String,
+    pub op: Operation,
+    pub target: String,
+    pub missing: Vec<String>,
+}
+
+/// Check every action (including nested ones) for missing effect tags
+pub fn check_effects(program: &Program) -> Vec<EffectGap> {
+    let mut gaps = Vec::new();
+    program.walk(&mut |action, location| {
+        let missing = missing_effects(action);
+        if !missing.is_empty() {
+            gaps.push(EffectGap {
+                location: location.to_string(),
+                op: action.op.clone(),
+                target: action.target.clone(),
+                missing,
+            });
+        }
+    });
+    gaps
+}
+
+/// Add inferred tags to every action, keeping declared ones; returns how many
+/// actions changed
+pub fn annotate_effects(program: &mut Program) -> usize {
+    let mut changed = 0;
+    program.walk_mut(&mut |action, _| {
+        let missing = missing_effects(action);
+        if !missing.is_empty() {
+            action.effects.get_or_insert_with(Vec::new).extend(missing);
+            changed += 1;
+        }
+    });
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_effects() {
+        let heat = Action::new("robot", Operation::Heat, "water");
+        assert_eq!(infer_effects(&heat), vec!["Physical", "Thermal"]);
+
+        let oblige = Action::new("tenant", Operation::Oblige, "rent");
+        assert_eq!(infer_effects(&oblige), vec!["Legal"]);
+
+        let bind = Action::new("VM", Operation::Bind, "x");
+        assert!(infer_effects(&bind).is_empty());
+    }
+
+    #[test]
+    fn test_annotate_keeps_declared_and_descends() {
+        let json = r#"{"actions": [
+            {"actor": "cell", "op": "Transcribe", "target": "gene", "effects": ["Bio"]},
+            {"actor": "robot", "op": "If", "target": "c",
+             "condition": {"type": "comparison", "op": "==", "left": 1, "right": 1},
+             "then": [{"actor": "robot", "op": "Pour", "target": "water"}]}
+        ]}"#;
+        let mut program = Program::from_json(json).unwrap();
+
+        assert_eq!(check_effects(&program).len(), 1);
+        assert_eq!(annotate_effects(&mut program), 1);
+
+        assert_eq!(program.actions[0].effects, Some(vec!["Bio".to_string()]));
+        let pour = &program.actions[1].then_actions.as_ref().unwrap()[0];
+        assert_eq!(pour.effects, Some(vec!["Physical".to_string()]));
+        assert!(check_effects(&program).is_empty());
+    }
+}
@@ -0,0 +1,481 @@
+use crate::analysis::reads;
+use crate::{Action, BinaryOpExpr, ComparisonOp, Condition, Expression, Operation, Program};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+
+/// Kinds of rewritable patterns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdiomKind {
+    /// `i = a; While i < n { ...; i = i + 1 }` → `For i in a..n-1`
+    WhileCounter,
+    /// The same actions appearing several times → `DefineFunction`
+    RepeatedSequence,
+    /// A Write-with-operation feeding straight into another → one expression
+    WriteChain,
+}
+
+/// A detected idiom and the rewrite it suggests
+#[derive(Debug, Clone, PartialEq)]
+pub struct Idiom {
+    pub kind: IdiomKind,
+    /// Where the pattern starts, e.g. `actions[3]` or `actions[1].body[0]`
+    pub location: String,
+    pub suggestion: String,
+    /// Whether `fix` rewrites it (some rewrites could change scoping)
+    pub fixable: bool,
+}
+
+/// Find rewritable idioms anywhere in the program
+pub fn detect(program: &Program) -> Vec<Idiom> {
+    let mut idioms = Vec::new();
+    let mut functions = function_names(program);
+    detect_in(&program.actions, "actions", &mut functions, &mut idioms);
+    idioms
+}
+
+/// Apply every fixable rewrite until none are left, returning what was applied
+pub fn fix(program: &mut Program) -> Vec<Idiom> {
+    let mut functions = function_names(program);
+    let mut applied = Vec::new();
+
+    loop {
+        let before = applied.len();
+        fix_in(&mut program.actions, "actions", &mut functions, &mut applied);
+        if applied.len() == before {
+            return applied;
+        }
+    }
+}
+
+fn function_names(program: &Program) -> HashSet<String> {
+    let mut names = HashSet::new();
+    program.walk(&mut |action, _| {
+        if action.op == Operation::DefineFunction {
+            names.insert(action.target.clone());
+        }
+    });
+    names
+}
+
+fn detect_in(actions: &[Action], path: &str, functions: &mut HashSet<String>, idioms: &mut Vec<Idiom>) {
+    for index in 0..actions.len() {
+        if let Some((_, rewrite)) = while_counter(actions, index) {
+            idioms.push(rewrite.idiom(path));
+        }
+        if let Some(rewrite) = write_chain(actions, index) {
+            idioms.push(rewrite.idiom(path));
+        }
+    }
+    if let Some(rewrite) = repeated_sequence(actions, functions) {
+        idioms.push(rewrite.idiom(path));
+    }
+
+    for_each_branch(actions, path, |branch, branch_path| detect_in(branch, branch_path, functions, idioms));
+}
+
+fn fix_in(actions: &mut Vec<Action>, path: &str, functions: &mut HashSet<String>, applied: &mut Vec<Idiom>) {
+    // One rewrite per list per pass keeps indices valid; `fix` loops to a fixpoint
+    let rewrite = (0..actions.len())
+        .find_map(|i| while_counter(actions, i).map(|(_, r)| r).or_else(|| write_chain(actions, i)))
+        .or_else(|| repeated_sequence(actions, functions).filter(|r| r.fixable));
+
+    if let Some(rewrite) = rewrite {
+        applied.push(rewrite.idiom(path));
+        if let Some(name) = &rewrite.defines {
+            functions.insert(name.clone());
+        }
+        for (start, len, replacement) in rewrite.replacements.into_iter().rev() {
+            actions.splice(start..start + len, replacement);
+        }
+        return;
+    }
+
+    for (index, action) in actions.iter_mut().enumerate() {
+        for (name, branch) in [
+            ("then", &mut action.then_actions),
+            ("else", &mut action.else_actions),
+            ("body", &mut action.body_actions),
+            ("catch", &mut action.catch_actions),
+        ] {
+            if let Some(branch) = branch {
+                fix_in(branch, &format!("{}[{}].{}", path, index, name), functions, applied);
+            }
+        }
+    }
+}
+
+fn for_each_branch(actions: &[Action], path: &str, mut visit: impl FnMut(&[Action], &str)) {
+    for (index, action) in actions.iter().enumerate() {
+        for (name, branch) in [("then", &action.then_actions), ("else", &action.else_actions), ("body", &action.body_actions), ("catch", &action.catch_actions)] {
+            if let Some(branch) = branch {
+                visit(branch, &format!("{}[{}].{}", path, index, name));
+            }
+        }
+    }
+}
+
+/// A concrete rewrite of one action list
+struct Rewrite {
+    kind: IdiomKind,
+    index: usize,
+    suggestion: String,
+    fixable: bool,
+    /// `(start, len, new actions)`, non-overlapping and in ascending order
+    replacements: Vec<(usize, usize, Vec<Action>)>,
+    defines: Option<String>,
+}
+
+impl Rewrite {
+    fn idiom(&self, path: &str) -> Idiom {
+        Idiom {
+            kind: self.kind,
+            location: format!("{}[{}]", path, self.index),
+            suggestion: self.suggestion.clone(),
+            fixable: self.fixable,
+        }
+    }
+}
+
+fn value_param(action: &Action) -> Option<Expression> {
+    action.params.as_ref()
+        .and_then(|p| p.get("value"))
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+}
+
+fn integer(expr: &Expression) -> Option<i64> {
+    match expr {
+        Expression::Value(v) => v.as_i64(),
+        _ => None,
+    }
+}
+
+/// Whether `action` (or anything nested in it) mentions `var`
+fn mentions(action: &Action, var: &str) -> bool {
+    let mut found = false;
+    action.walk(&mut |a| {
+        found |= a.target == var || reads(a).iter().any(|r| r == var) || a.loop_var.as_deref() == Some(var);
+    });
+    found
+}
+
+/// `v = i + 1` (or `Write add v 1`) incrementing `var`
+fn is_increment(action: &Action, var: &str) -> bool {
+    if !action.op.writes_target() || action.target != var {
+        return false;
+    }
+
+    if let Some(Expression::BinaryOp { expr }) = value_param(action) {
+        return expr.op == "+"
+            && matches!(&*expr.left, Expression::Variable { var: v } if v == var)
+            && integer(&expr.right) == Some(1);
+    }
+
+    let params = match &action.params {
+        Some(params) => params,
+        None => return false,
+    };
+    params.get("operation").and_then(|v| v.as_str()) == Some("add")
+        && params.get("lhs_register").and_then(|v| v.as_str()) == Some(var)
+        && params.get("rhs").and_then(|v| v.as_i64()) == Some(1)
+}
+
+/// While loop at `index` counting a variable up to a constant
+fn while_counter(actions: &[Action], index: usize) -> Option<(String, Rewrite)> {
+    let action = &actions[index];
+    if action.op != Operation::While {
+        return None;
+    }
+
+    let Some(Condition::Comparison { op, left: Expression::Variable { var }, right }) = &action.condition else {
+        return None;
+    };
+    let limit = integer(right)?;
+    let to = match op {
+        ComparisonOp::LessThan => limit - 1,
+        ComparisonOp::LessThanOrEqual => limit,
+        _ => return None,
+    };
+
+    let body = action.body_actions.as_ref()?;
+    let (increment, rest) = body.split_last()?;
+    if !is_increment(increment, var) || rest.iter().any(|a| a.op.writes_target() && a.target == *var) {
+        return None;
+    }
+
+    // The counter's start value, set just before the loop with nothing in between touching it
+    let init = (0..index).rev().find(|&i| mentions(&actions[i], var))?;
+    if !actions[init].op.writes_target() || actions[init].target != *var {
+        return None;
+    }
+    let from = value_param(&actions[init]).as_ref().and_then(integer)?;
+
+    let mut for_loop = Action::new(action.actor.clone(), Operation::For, action.target.clone());
+    for_loop.loop_var = Some(var.clone());
+    for_loop.from_expr = Some(Expression::Value(json!(from)));
+    for_loop.to_expr = Some(Expression::Value(json!(to)));
+    for_loop.body_actions = Some(rest.to_vec());
+    for_loop.t = action.t;
+    for_loop.dur = action.dur;
+    for_loop.effects = action.effects.clone();
+
+    // The While leaves the counter one past the limit (or at its start if it
+    // never ran), where the For leaves it on the last value; put it back
+    let mut after = Action::new(action.actor.clone(), Operation::Bind, var.clone());
+    let end = if from > to { json!(from) } else { json!((to + 1) as f64) };
+    after.params = Some(HashMap::from([("value".to_string(), end)]));
+
+    let rewrite = Rewrite {
+        kind: IdiomKind::WhileCounter,
+        index,
+        suggestion: format!("While loop counts {} from {} to {}; use For", var, from, to),
+        fixable: true,
+        replacements: vec![
+            (init, 1, Vec::new()),
+            (index, 1, vec![for_loop, after]),
+        ],
+        defines: None,
+    };
+    Some((var.clone(), rewrite))
+}
+
+const OPERATORS: &[(&str, &str)] = &[("add", "+"), ("subtract", "-"), ("multiply", "*"), ("divide", "/")];
+
+/// Expression computed by a Write-with-operation, or a Bind of a binary expression
+fn computed_expression(action: &Action) -> Option<Expression> {
+    if action.op == Operation::Bind {
+        return match value_param(action) {
+            Some(expr @ Expression::BinaryOp { .. }) => Some(expr),
+            _ => None,
+        };
+    }
+    if action.op != Operation::Write {
+        return None;
+    }
+
+    let params = action.params.as_ref()?;
+    let operation = params.get("operation")?.as_str()?;
+    let operator = OPERATORS.iter().find(|(name, _)| *name == operation)?.1;
+
+    let operand = |side: &str| -> Option<Expression> {
+        match (params.get(&format!("{}_register", side)), params.get(side)) {
+            (Some(register), _) => Some(Expression::Variable { var: register.as_str()?.to_string() }),
+            (None, Some(value)) => Some(Expression::Value(value.clone())),
+            (None, None) => None,
+        }
+    };
+
+    Some(Expression::BinaryOp {
+        expr: BinaryOpExpr {
+            op: operator.to_string(),
+            left: Box::new(operand("lhs")?),
+            right: Box::new(operand("rhs")?),
+        },
+    })
+}
+
+fn substitute(expr: Expression, var: &str, replacement: &Expression) -> Expression {
+    match expr {
+        Expression::Variable { var: v } if v == var => replacement.clone(),
+        Expression::BinaryOp { expr } => Expression::BinaryOp {
+            expr: BinaryOpExpr {
+                op: expr.op,
+                left: Box::new(substitute(*expr.left, var, replacement)),
+                right: Box::new(substitute(*expr.right, var, replacement)),
+            },
+        },
+        other => other,
+    }
+}
+
+/// Two consecutive computations where the first result is only used by the second
+fn write_chain(actions: &[Action], index: usize) -> Option<Rewrite> {
+    let first = &actions[index];
+    let second = actions.get(index + 1)?;
+    let inner = computed_expression(first)?;
+    let outer = computed_expression(second)?;
+
+    let intermediate = &first.target;
+    if !reads(second).contains(intermediate) {
+        return None;
+    }
+    // The intermediate must not be needed afterwards (unless the second overwrites it)
+    if second.target != *intermediate && actions[index + 2..].iter().any(|a| mentions(a, intermediate)) {
+        return None;
+    }
+
+    let combined = substitute(outer, intermediate, &inner);
+    let mut bind = Action::new(second.actor.clone(), Operation::Bind, second.target.clone());
+    bind.params = Some(HashMap::from([("value".to_string(), serde_json::to_value(&combined).ok()?)]));
+    bind.t = first.t.or(second.t);
+    bind.effects = second.effects.clone();
+
+    Some(Rewrite {
+        kind: IdiomKind::WriteChain,
+        index,
+        suggestion: format!("{} is only used to compute {}; combine into one expression", intermediate, second.target),
+        fixable: true,
+        replacements: vec![(index, 2, vec![bind])],
+        defines: None,
+    })
+}
+
+/// Longest run of two or more actions that appears at least twice, without overlap
+fn repeated_sequence(actions: &[Action], functions: &HashSet<String>) -> Option<Rewrite> {
+    let keys: Vec<serde_json::Value> = actions.iter()
+        .map(|a| serde_json::to_value(a).unwrap_or_default())
+        .collect();
+
+    for len in (2..=actions.len() / 2).rev() {
+        for start in 0..=actions.len() - 2 * len {
+            let mut occurrences = vec![start];
+            let mut next = start + len;
+            while next + len <= actions.len() {
+                if keys[next..next + len] == keys[start..start + len] {
+                    occurrences.push(next);
+                    next += len;
+                } else {
+                    next += 1;
+                }
+            }
+            if occurrences.len() < 2 {
+                continue;
+            }
+
+            let sequence = &actions[start..start + len];
+            let name = (1..).map(|n| format!("repeated_{}", n)).find(|n| !functions.contains(n))?;
+
+            // Ruby functions get their own scope, so only extract sequences that
+            // don't assign variables or return
+            let fixable = sequence.iter().all(|a| {
+                let mut plain = true;
+                a.walk(&mut |inner| {
+                    plain &= !inner.op.writes_target() && inner.op != Operation::Return && inner.op != Operation::DefineFunction;
+                });
+                plain
+            });
+
+            let actor = sequence[0].actor.clone();
+            let mut define = Action::new(actor.clone(), Operation::DefineFunction, name.clone());
+            define.params = Some(HashMap::from([
+                ("args".to_string(), json!([])),
+                ("body".to_string(), serde_json::to_value(sequence).ok()?),
+            ]));
+
+            let call = |actor: &str| {
+                let mut call = Action::new(actor.to_string(), Operation::Bind, "_");
+                call.params = Some(HashMap::from([("value".to_string(), json!({"call": name, "args": {}}))]));
+                call
+            };
+
+            let replacements = occurrences.iter()
+                .enumerate()
+                .map(|(n, &at)| {
+                    let mut replacement = if n == 0 { vec![define.clone()] } else { Vec::new() };
+                    replacement.push(call(&actions[at].actor));
+                    (at, len, replacement)
+                })
+                .collect();
+
+            return Some(Rewrite {
+                kind: IdiomKind::RepeatedSequence,
+                index: start,
+                suggestion: format!(
+                    "{} actions repeated {} times; extract into DefineFunction {}",
+                    len, occurrences.len(), name
+                ),
+                fixable,
+                replacements,
+                defines: Some(name),
+            });
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::BrainSimulator;
+
+    #[test]
+    fn test_while_counter_becomes_for() {
+        let json = r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "i", "params": {"value": 0}},
+            {"actor": "VM", "op": "While", "target": "loop",
+             "condition": {"type": "comparison", "op": "<", "left": {"var": "i"}, "right": 5},
+             "body": [
+                {"actor": "VM", "op": "Emit", "target": "tick"},
+                {"actor": "VM", "op": "Bind", "target": "i",
+                 "params": {"value": {"expr": {"op": "+", "left": {"var": "i"}, "right": 1}}}}
+             ]}
+        ]}"#;
+        let mut program = Program::from_json(json).unwrap();
+
+        assert_eq!(detect(&program)[0].kind, IdiomKind::WhileCounter);
+
+        let applied = fix(&mut program);
+        assert_eq!(applied.len(), 1);
+        assert_eq!(program.actions.len(), 2);
+        let for_loop = &program.actions[0];
+        assert_eq!(for_loop.op, Operation::For);
+        assert_eq!(for_loop.to_expr, Some(Expression::Value(json!(4))));
+        assert_eq!(for_loop.body_actions.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_while_counter_fix_keeps_final_counter() {
+        let json = r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "i", "params": {"value": 0}},
+            {"actor": "VM", "op": "While", "target": "loop",
+             "condition": {"type": "comparison", "op": "<", "left": {"var": "i"}, "right": 3},
+             "body": [
+                {"actor": "VM", "op": "Bind", "target": "i",
+                 "params": {"value": {"expr": {"op": "+", "left": {"var": "i"}, "right": 1}}}}
+             ]},
+            {"actor": "VM", "op": "Bind", "target": "n", "params": {"value": {"var": "i"}}}
+        ]}"#;
+        let original = Program::from_json(json).unwrap();
+        let mut fixed = original.clone();
+        fix(&mut fixed);
+        assert_eq!(fixed.actions[0].op, Operation::For);
+
+        let run = |program: &Program| {
+            let mut brain = BrainSimulator::new();
+            brain.execute(program).unwrap();
+            (brain.state().beliefs["i"].clone(), brain.state().beliefs["n"].clone())
+        };
+        assert_eq!(run(&fixed), run(&original));
+        assert_eq!(run(&fixed).1, json!(3.0));
+    }
+
+    #[test]
+    fn test_write_chain_becomes_expression() {
+        let json = r#"{"actions": [
+            {"actor": "VM", "op": "Write", "target": "t", "params": {"operation": "multiply", "lhs_register": "x", "rhs": 2}},
+            {"actor": "VM", "op": "Write", "target": "y", "params": {"operation": "add", "lhs_register": "t", "rhs": 1}},
+            {"actor": "VM", "op": "Emit", "target": "y"}
+        ]}"#;
+        let mut program = Program::from_json(json).unwrap();
+
+        fix(&mut program);
+
+        assert_eq!(program.actions.len(), 2);
+        let value = &program.actions[0].params.as_ref().unwrap()["value"];
+        assert_eq!(value, &json!({"expr": {"op": "+", "left": {"expr": {"op": "*", "left": {"var": "x"}, "right": 2}}, "right": 1}}));
+    }
+
+    #[test]
+    fn test_repeated_sequence_extracted() {
+        let step = r#"{"actor": "cook", "op": "Stir", "target": "pot"}, {"actor": "cook", "op": "Wait", "target": "pot"}"#;
+        let json = format!(r#"{{"actions": [{step}, {step}, {step}]}}"#);
+        let mut program = Program::from_json(&json).unwrap();
+
+        let idioms = detect(&program);
+        assert!(idioms.iter().any(|i| i.kind == IdiomKind::RepeatedSequence && i.fixable));
+
+        fix(&mut program);
+        assert_eq!(program.actions[0].op, Operation::DefineFunction);
+        assert_eq!(program.actions.len(), 4);
+    }
+}
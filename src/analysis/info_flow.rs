@@ -0,0 +1,214 @@
+use crate::analysis::reads;
+use crate::{Action, Operation, Program};
+use std::collections::HashMap;
+
+/// Operations whose behaviour a value can influence
+const SINK_OPS: &[Operation] = &[
+    Operation::Emit,
+    Operation::Decide,
+    Operation::Assert,
+    Operation::StoreFact,
+    Operation::Oblige,
+    Operation::Permit,
+    Operation::Remedy,
+    Operation::Call,
+    Operation::Execute,
+    Operation::Heat,
+    Operation::Pour,
+    Operation::Mix,
+    Operation::Stir,
+    Operation::Place,
+    Operation::Remove,
+    Operation::Grasp,
+    Operation::Release,
+    Operation::Steep,
+    Operation::Serve,
+];
+
+/// Which sources are untrusted and which sinks are sensitive
+///
+/// Individual actions can also be marked with `"untrusted": true` or
+/// `"sensitive": true` params.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaintPolicy {
+    pub untrusted: Vec<Operation>,
+    pub sensitive: Vec<Operation>,
+}
+
+impl Default for TaintPolicy {
+    fn default() -> Self {
+        Self {
+            untrusted: vec![Operation::Receive, Operation::Measure, Operation::Scan, Operation::ReadSensor, Operation::Read],
+            sensitive: vec![
+                Operation::Oblige,
+                Operation::Permit,
+                Operation::Remedy,
+                Operation::Execute,
+                Operation::Call,
+                Operation::Decide,
+            ],
+        }
+    }
+}
+
+/// An action at either end of a flow; `index` counts nested actions in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowPoint {
+    pub index: usize,
+    pub actor: String,
+    pub op: Operation,
+    pub target: String,
+}
+
+impl FlowPoint {
+    pub fn display(&self) -> String {
+        format!("#{} {} {:?}({})", self.index + 1, self.actor, self.op, self.target)
+    }
+}
+
+/// A value introduced at `source` reaching `sink` through `path` variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flow {
+    pub source: FlowPoint,
+    pub sink: FlowPoint,
+    pub path: Vec<String>,
+    /// Untrusted source reaching a sensitive sink
+    pub tainted: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Origin {
+    source: FlowPoint,
+    path: Vec<String>,
+}
+
+fn flag(action: &Action, key: &str) -> bool {
+    action.params.as_ref()
+        .and_then(|p| p.get(key))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Trace values from sources to sinks, in execution order
+///
+/// Branches are merged rather than distinguished, and a variable's origins
+/// are replaced whenever it is overwritten.
+pub fn information_flows(program: &Program, policy: &TaintPolicy) -> Vec<Flow> {
+    let mut origins: HashMap<String, Vec<Origin>> = HashMap::new();
+    let mut untrusted: Vec<bool> = Vec::new();
+    let mut flows = Vec::new();
+    let mut index = 0;
+
+    program.walk(&mut |action, _| {
+        let point = FlowPoint {
+            index,
+            actor: action.actor.clone(),
+            op: action.op.clone(),
+            target: action.target.clone(),
+        };
+        index += 1;
+        untrusted.push(policy.untrusted.contains(&action.op) || flag(action, "untrusted"));
+
+        let sensitive = policy.sensitive.contains(&action.op) || flag(action, "sensitive");
+        let is_sink = SINK_OPS.contains(&action.op) || sensitive;
+
+        let mut read = reads(action);
+        if is_sink || (action.op == Operation::Receive && action.params.as_ref().is_some_and(|p| p.contains_key("source"))) {
+            // Emit x, Oblige x, ... act on their target, and a Receive from a
+            // known source passes its value along
+            read.push(action.target.clone());
+        }
+
+        let mut incoming: Vec<Origin> = Vec::new();
+        for var in &read {
+            for origin in origins.get(var).into_iter().flatten() {
+                if !incoming.iter().any(|o| o.source.index == origin.source.index) {
+                    incoming.push(origin.clone());
+                }
+            }
+        }
+
+        if is_sink {
+            for origin in &incoming {
+                flows.push(Flow {
+                    tainted: untrusted[origin.source.index] && sensitive,
+                    source: origin.source.clone(),
+                    sink: point.clone(),
+                    path: origin.path.clone(),
+                });
+            }
+            return;
+        }
+
+        let mut produced: Vec<Origin> = incoming.into_iter()
+            .map(|mut origin| {
+                if origin.path.last() != Some(&action.target) {
+                    origin.path.push(action.target.clone());
+                }
+                origin
+            })
+            .collect();
+
+        // An assignment computed from other variables passes their values on
+        // rather than introducing a new one
+        if action.op.writes_target() && (read.is_empty() || !action.op.is_assignment()) {
+            produced.insert(0, Origin { source: point, path: vec![action.target.clone()] });
+        }
+
+        origins.insert(action.target.clone(), produced);
+    });
+
+    flows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_untrusted_value_reaching_obligation_is_tainted() {
+        let json = r#"{"actions": [
+            {"actor": "Buyer", "op": "Receive", "target": "invoice"},
+            {"actor": "Clerk", "op": "Write", "target": "amount",
+             "params": {"value": {"expr": {"op": "*", "left": {"var": "invoice"}, "right": 2}}}},
+            {"actor": "Seller", "op": "Emit", "target": "receipt"},
+            {"actor": "Buyer", "op": "Oblige", "target": "payment", "params": {"sum": {"var": "amount"}}}
+        ]}"#;
+        let flows = information_flows(&Program::from_json(json).unwrap(), &TaintPolicy::default());
+
+        assert_eq!(flows.len(), 1);
+        let flow = &flows[0];
+        assert_eq!(flow.source.op, Operation::Receive);
+        assert_eq!(flow.sink.op, Operation::Oblige);
+        assert_eq!(flow.path, vec!["invoice", "amount"]);
+        assert!(flow.tainted);
+    }
+
+    #[test]
+    fn test_flows_inside_functions() {
+        let json = r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "relay", "params": {"args": [], "body": [
+                {"actor": "VM", "op": "Receive", "target": "message"},
+                {"actor": "VM", "op": "Emit", "target": "message"}
+            ]}}
+        ]}"#;
+        let flows = information_flows(&Program::from_json(json).unwrap(), &TaintPolicy::default());
+
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].source.op, Operation::Receive);
+        assert_eq!(flows[0].sink.op, Operation::Emit);
+        assert_eq!(flows[0].sink.index, 2);
+    }
+
+    #[test]
+    fn test_trusted_flow_is_not_tainted() {
+        let json = r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "x", "params": {"value": 1}},
+            {"actor": "VM", "op": "Emit", "target": "x"}
+        ]}"#;
+        let flows = information_flows(&Program::from_json(json).unwrap(), &TaintPolicy::default());
+
+        assert_eq!(flows.len(), 1);
+        assert!(!flows[0].tainted);
+    }
+}
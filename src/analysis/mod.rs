@@ -0,0 +1,90 @@
+//! Static analyses over UCL programs
+
+pub mod actor_network;
+pub mod causal_graph;
+pub mod critical_path;
+pub mod definitions;
+pub mod effects;
+pub mod idioms;
+pub mod info_flow;
+pub mod similarity;
+pub mod stats;
+
+pub use actor_network::{ActorNetwork, ActorStats, Interaction, actor_network};
+pub use causal_graph::{CausalEdge, CausalGraph, CausalNode, EdgeKind, causal_graph};
+pub use critical_path::{ActorLoad, Schedule, ScheduledAction, actor_loads, critical_path, dependencies};
+pub use definitions::{DefinitionIssue, DefinitionIssueKind, check_definitions};
+pub use effects::{EffectGap, annotate_effects, check_effects, infer_effects};
+pub use idioms::{Idiom, IdiomKind};
+pub use info_flow::{Flow, FlowPoint, TaintPolicy, information_flows};
+pub use similarity::{Cluster, Fingerprint, cluster};
+pub use stats::{ProgramStats, program_stats};
+
+use crate::{Action, Operation};
+use crate::template::{self, Piece};
+
+/// Variables an action reads through its params and condition:
+/// `{"var": ...}` expressions, the placeholders of `{"format": ...}`
+/// templates and `*_register` params
+pub fn reads(action: &Action) -> Vec<String> {
+    let mut names = Vec::new();
+
+    if let Some(params) = &action.params {
+        for (key, value) in params {
+            if key.ends_with("_register") {
+                names.extend(value.as_str().map(|s| s.to_string()));
+            }
+            collect_vars(value, &mut names);
+        }
+    }
+
+    if let Some(condition) = &action.condition {
+        if let Ok(value) = serde_json::to_value(condition) {
+            collect_vars(&value, &mut names);
+        }
+    }
+
+    names
+}
+
+/// Variables an action binds: its target, for operations that write it, and
+/// the `params.out` a `Call` stores its result in
+pub fn writes(action: &Action) -> Vec<String> {
+    let mut names = Vec::new();
+    if action.op.writes_target() {
+        names.push(action.target.clone());
+    }
+    if action.op == Operation::Call {
+        names.extend(action.params.as_ref().and_then(|p| p.get("out")?.as_str()).map(str::to_string));
+    }
+    names
+}
+
+pub(crate) fn collect_vars(value: &serde_json::Value, names: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(name) = map.get("var").and_then(|v| v.as_str()) {
+                names.push(name.to_string());
+            }
+            if let Some(format) = map.get("format").and_then(|v| v.as_str()) {
+                for piece in template::parse(format).unwrap_or_default() {
+                    if let Piece::Var(name) = piece {
+                        names.push(name);
+                    }
+                }
+            }
+            map.values().for_each(|v| collect_vars(v, names));
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|v| collect_vars(v, names)),
+        _ => {}
+    }
+}
+
+/// Visit every action with its nesting depth (0 = top level), also descending
+/// into DefineFunction bodies stored in `params.body`
+pub fn walk_with_depth(actions: &[Action], depth: usize, visit: &mut impl FnMut(&Action, usize)) {
+    for action in actions {
+        visit(action, depth);
+        action.visit_children(&mut |_, nested| walk_with_depth(nested, depth + 1, visit));
+    }
+}
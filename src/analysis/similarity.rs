@@ -0,0 +1,172 @@
+use crate::{Action, Operation, Program};
+use std::collections::BTreeMap;
+
+/// Structural fingerprint of a program: counts of op n-grams and
+/// control-flow shapes, ignoring actors, targets and values
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Fingerprint {
+    pub features: BTreeMap<String, usize>,
+}
+
+/// Programs whose fingerprints are close enough to count as variants
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    /// Indices into the corpus
+    pub members: Vec<usize>,
+    /// Member most similar to the rest, a good canonical example
+    pub canonical: usize,
+}
+
+fn is_control_flow(op: &Operation) -> bool {
+    matches!(op, Operation::If | Operation::While | Operation::For | Operation::DefineFunction)
+}
+
+impl Fingerprint {
+    pub fn of(program: &Program) -> Self {
+        let mut fingerprint = Self::default();
+        let mut ops = Vec::new();
+        fingerprint.visit(&program.actions, None, 0, &mut ops);
+
+        for n in 1..=3 {
+            for gram in ops.windows(n) {
+                fingerprint.add(format!("op:{}", gram.join(">")));
+            }
+        }
+
+        fingerprint
+    }
+
+    fn add(&mut self, feature: String) {
+        *self.features.entry(feature).or_insert(0) += 1;
+    }
+
+    /// Collect ops in execution order plus `parent>child` and depth features
+    /// for control flow
+    fn visit(&mut self, actions: &[Action], parent: Option<&str>, depth: usize, ops: &mut Vec<String>) {
+        for action in actions {
+            let op = format!("{:?}", action.op);
+            ops.push(op.clone());
+
+            if is_control_flow(&action.op) {
+                self.add(format!("shape:{}>{}", parent.unwrap_or("root"), op));
+                self.add(format!("depth:{}", depth + 1));
+            }
+
+            let mut branches: Vec<(&str, Vec<Action>)> = Vec::new();
+            for (name, branch) in [("then", &action.then_actions), ("else", &action.else_actions), ("body", &action.body_actions), ("catch", &action.catch_actions)] {
+                if let Some(branch) = branch {
+                    branches.push((name, branch.clone()));
+                }
+            }
+            // Function bodies live in params
+            if action.op == Operation::DefineFunction {
+                if let Some(body) = action.params.as_ref().and_then(|p| p.get("body")) {
+                    if let Ok(body) = serde_json::from_value::<Vec<Action>>(body.clone()) {
+                        branches.push(("body", body));
+                    }
+                }
+            }
+
+            for (name, branch) in branches {
+                self.add(format!("branch:{}.{}", op, name));
+                self.visit(&branch, Some(&op), depth + 1, ops);
+            }
+        }
+    }
+
+    /// Weighted Jaccard similarity in 0..=1
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        let mut shared = 0;
+        let mut total = 0;
+
+        for (feature, &count) in &self.features {
+            let theirs = other.features.get(feature).copied().unwrap_or(0);
+            shared += count.min(theirs);
+            total += count.max(theirs);
+        }
+        total += other.features.iter()
+            .filter(|(feature, _)| !self.features.contains_key(*feature))
+            .map(|(_, &count)| count)
+            .sum::<usize>();
+
+        if total == 0 { 1.0 } else { shared as f64 / total as f64 }
+    }
+}
+
+/// Single-linkage clustering: programs join a cluster when they are at least
+/// `threshold` similar to any member
+///
+/// Clusters are returned largest first; singletons are included.
+pub fn cluster(fingerprints: &[Fingerprint], threshold: f64) -> Vec<Cluster> {
+    let n = fingerprints.len();
+    let mut parent: Vec<usize> = (0..n).collect();
+
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut scores = vec![vec![1.0; n]; n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let score = fingerprints[i].similarity(&fingerprints[j]);
+            scores[i][j] = score;
+            scores[j][i] = score;
+            if score >= threshold {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..n {
+        groups.entry(root(&mut parent, i)).or_default().push(i);
+    }
+
+    let mut clusters: Vec<Cluster> = groups.into_values()
+        .map(|members| {
+            let mean = |i: usize| members.iter().map(|&j| scores[i][j]).sum::<f64>();
+            let canonical = members.iter().copied()
+                .max_by(|&a, &b| mean(a).total_cmp(&mean(b)).then(b.cmp(&a)))
+                .unwrap_or(members[0]);
+            Cluster { members, canonical }
+        })
+        .collect();
+
+    clusters.sort_by(|a, b| b.members.len().cmp(&a.members.len()).then(a.members[0].cmp(&b.members[0])));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(ops: &[&str]) -> Program {
+        let actions: Vec<String> = ops.iter()
+            .map(|op| format!(r#"{{"actor": "a", "op": "{}", "target": "x"}}"#, op))
+            .collect();
+        Program::from_json(&format!(r#"{{"actions": [{}]}}"#, actions.join(","))).unwrap()
+    }
+
+    #[test]
+    fn test_similar_programs_cluster_together() {
+        let corpus = [
+            program(&["Gather", "Heat", "Pour", "Steep", "Serve"]),
+            program(&["Gather", "Heat", "Pour", "Steep", "Stir", "Serve"]),
+            program(&["Bind", "Write", "Emit"]),
+        ];
+        let fingerprints: Vec<Fingerprint> = corpus.iter().map(Fingerprint::of).collect();
+
+        assert_eq!(fingerprints[0].similarity(&fingerprints[0]), 1.0);
+        assert_eq!(fingerprints[0].similarity(&fingerprints[2]), 0.0);
+
+        let clusters = cluster(&fingerprints, 0.4);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].members, vec![0, 1]);
+        assert_eq!(clusters[1].members, vec![2]);
+    }
+}
@@ -0,0 +1,105 @@
+//! Whole-program statistics, counting actions at every nesting depth
+
+use crate::analysis::walk_with_depth;
+use crate::{Operation, Program};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Counts over every action, including then/else/body and function bodies
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProgramStats {
+    pub total: usize,
+    pub top_level: usize,
+    pub operations: BTreeMap<String, usize>,
+    pub actors: BTreeMap<String, usize>,
+    pub effects: BTreeMap<String, usize>,
+    /// Actions at each depth (0 = top level)
+    pub by_depth: BTreeMap<usize, usize>,
+    /// Operation counts at each depth
+    pub operations_by_depth: BTreeMap<usize, BTreeMap<String, usize>>,
+    pub max_depth: usize,
+    /// If/While/For actions
+    pub control_flow: usize,
+    pub functions: usize,
+    pub timed: usize,
+    pub time_range: Option<(f64, f64)>,
+}
+
+impl ProgramStats {
+    /// Entries sorted by count (highest first), then by name
+    pub fn ranked<K: Clone + Ord>(counts: &BTreeMap<K, usize>) -> Vec<(K, usize)> {
+        let mut entries: Vec<_> = counts.iter().map(|(k, &v)| (k.clone(), v)).collect();
+        entries.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        entries
+    }
+}
+
+/// Gather statistics for the whole program
+pub fn program_stats(program: &Program) -> ProgramStats {
+    let mut stats = ProgramStats {
+        top_level: program.actions.len(),
+        ..Default::default()
+    };
+
+    walk_with_depth(&program.actions, 0, &mut |action, depth| {
+        let op = format!("{:?}", action.op);
+
+        stats.total += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+        *stats.by_depth.entry(depth).or_default() += 1;
+        *stats.operations_by_depth.entry(depth).or_default().entry(op.clone()).or_default() += 1;
+        *stats.operations.entry(op).or_default() += 1;
+        *stats.actors.entry(action.actor.clone()).or_default() += 1;
+        for effect in action.effects.iter().flatten() {
+            *stats.effects.entry(effect.clone()).or_default() += 1;
+        }
+
+        match action.op {
+            Operation::If | Operation::While | Operation::For => stats.control_flow += 1,
+            Operation::DefineFunction => stats.functions += 1,
+            _ => {}
+        }
+
+        if let Some(t) = action.t {
+            stats.timed += 1;
+            stats.time_range = Some(match stats.time_range {
+                Some((min, max)) => (min.min(t), max.max(t)),
+                None => (t, t),
+            });
+        }
+    });
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_include_nested_actions() {
+        let json = r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "f",
+             "params": {"args": [], "body": [{"actor": "VM", "op": "Return", "target": "x", "t": 9}]}},
+            {"actor": "VM", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": 3,
+             "body": [
+                {"actor": "VM", "op": "If", "target": "check",
+                 "condition": {"type": "comparison", "op": "==", "left": {"var": "i"}, "right": 2},
+                 "then": [{"actor": "CPU", "op": "Emit", "target": "two", "effects": ["io"], "t": 1}],
+                 "else": [{"actor": "CPU", "op": "Emit", "target": "other"}]}
+             ]}
+        ]}"#;
+        let stats = program_stats(&Program::from_json(json).unwrap());
+
+        assert_eq!(stats.top_level, 2);
+        assert_eq!(stats.total, 6);
+        assert_eq!(stats.max_depth, 2);
+        assert_eq!(stats.by_depth[&2], 2);
+        assert_eq!(stats.operations["Emit"], 2);
+        assert_eq!(stats.actors["CPU"], 2);
+        assert_eq!(stats.effects["io"], 1);
+        assert_eq!(stats.control_flow, 2);
+        assert_eq!(stats.functions, 1);
+        assert_eq!(stats.time_range, Some((1.0, 9.0)));
+    }
+}
@@ -0,0 +1,236 @@
+//! Building programs with nested control flow from Rust
+//!
+//! ```
+//! use ucl::builder::{ProgramBuilder, binary, compare, var};
+//! use ucl::ComparisonOp;
+//!
+//! let program = ProgramBuilder::new("VM")
+//!     .def_fn("double", &["n"], |f| f.ret(binary("*", var("n"), 2)))
+//!     .for_("i", 1, 3, |body| body
+//!         .if_(compare(var("i"), ComparisonOp::Equal, 2))
+//!         .then(|then| then.emit("output", "two"))
+//!         .else_(|otherwise| otherwise.emit("output", var("i"))))
+//!     .build();
+//! assert_eq!(program.actions.len(), 2);
+//! ```
+
+use crate::{Action, BinaryOpExpr, ComparisonOp, Condition, Expression, Operation, Program};
+use serde_json::Value;
+use std::collections::HashMap;
+
+impl From<i64> for Expression {
+    fn from(value: i64) -> Self {
+        Expression::Value(value.into())
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(value: f64) -> Self {
+        Expression::Value(value.into())
+    }
+}
+
+impl From<bool> for Expression {
+    fn from(value: bool) -> Self {
+        Expression::Value(value.into())
+    }
+}
+
+impl From<&str> for Expression {
+    fn from(value: &str) -> Self {
+        Expression::Value(value.into())
+    }
+}
+
+impl From<Value> for Expression {
+    fn from(value: Value) -> Self {
+        Expression::Value(value)
+    }
+}
+
+/// `{"var": name}`
+pub fn var(name: &str) -> Expression {
+    Expression::Variable { var: name.to_string() }
+}
+
+/// `{"call": function, "args": {...}}`
+pub fn call(function: &str, args: impl IntoIterator<Item = (&'static str, Expression)>) -> Expression {
+    Expression::FunctionCall {
+        call: function.to_string(),
+        args: args.into_iter().map(|(name, arg)| (name.to_string(), arg)).collect(),
+    }
+}
+
+/// `{"expr": {"op", "left", "right"}}`
+pub fn binary(op: &str, left: impl Into<Expression>, right: impl Into<Expression>) -> Expression {
+    Expression::BinaryOp {
+        expr: BinaryOpExpr { op: op.to_string(), left: Box::new(left.into()), right: Box::new(right.into()) },
+    }
+}
+
+pub fn compare(left: impl Into<Expression>, op: ComparisonOp, right: impl Into<Expression>) -> Condition {
+    Condition::Comparison { op, left: left.into(), right: right.into() }
+}
+
+/// Actions in order, each by the builder's current actor; control flow
+/// takes a closure that fills in the nested list with a builder of its own
+#[derive(Debug, Clone)]
+pub struct ProgramBuilder {
+    actor: String,
+    metadata: Option<HashMap<String, Value>>,
+    actions: Vec<Action>,
+}
+
+impl ProgramBuilder {
+    pub fn new(actor: impl Into<String>) -> Self {
+        Self { actor: actor.into(), metadata: None, actions: Vec::new() }
+    }
+
+    /// Use `actor` for the actions added from here on
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = actor.into();
+        self
+    }
+
+    pub fn metadata(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.metadata.get_or_insert_with(HashMap::new).insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Add a ready-made action as is
+    pub fn action(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Add an action by the current actor with the given params
+    pub fn op(self, op: Operation, target: &str, params: impl IntoIterator<Item = (&'static str, Value)>) -> Self {
+        let params: HashMap<String, Value> = params.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        let mut action = Action::new(self.actor.clone(), op, target);
+        if !params.is_empty() {
+            action.params = Some(params);
+        }
+        self.action(action)
+    }
+
+    pub fn bind(self, name: &str, value: impl Into<Expression>) -> Self {
+        self.op(Operation::Bind, name, [("value", expression_value(value))])
+    }
+
+    pub fn assign(self, name: &str, value: impl Into<Expression>) -> Self {
+        self.op(Operation::Assign, name, [("value", expression_value(value))])
+    }
+
+    pub fn emit(self, target: &str, content: impl Into<Expression>) -> Self {
+        self.op(Operation::Emit, target, [("content", expression_value(content))])
+    }
+
+    pub fn ret(self, value: impl Into<Expression>) -> Self {
+        self.op(Operation::Return, "result", [("value", expression_value(value))])
+    }
+
+    /// Start an `If`; finish it with [`IfBuilder::else_`] or [`IfBuilder::end`]
+    pub fn if_(self, condition: Condition) -> IfBuilder {
+        let mut action = Action::new(self.actor.clone(), Operation::If, "branch");
+        action.condition = Some(condition);
+        IfBuilder { parent: self, action }
+    }
+
+    pub fn while_(self, condition: Condition, body: impl FnOnce(ProgramBuilder) -> ProgramBuilder) -> Self {
+        let mut action = Action::new(self.actor.clone(), Operation::While, "loop");
+        action.condition = Some(condition);
+        action.body_actions = Some(self.nested(body));
+        self.action(action)
+    }
+
+    /// A `For` over `from..=to`
+    pub fn for_(self, variable: &str, from: impl Into<Expression>, to: impl Into<Expression>, body: impl FnOnce(ProgramBuilder) -> ProgramBuilder) -> Self {
+        let mut action = Action::new(self.actor.clone(), Operation::For, "loop");
+        action.loop_var = Some(variable.to_string());
+        action.from_expr = Some(from.into());
+        action.to_expr = Some(to.into());
+        action.body_actions = Some(self.nested(body));
+        self.action(action)
+    }
+
+    pub fn def_fn(self, name: &str, args: &[&str], body: impl FnOnce(ProgramBuilder) -> ProgramBuilder) -> Self {
+        let body = serde_json::to_value(self.nested(body)).unwrap_or_default();
+        self.op(Operation::DefineFunction, name, [("args", Value::from(args.to_vec())), ("body", body)])
+    }
+
+    pub fn build(self) -> Program {
+        Program { metadata: self.metadata, actions: self.actions }
+    }
+
+    fn nested(&self, fill: impl FnOnce(ProgramBuilder) -> ProgramBuilder) -> Vec<Action> {
+        fill(ProgramBuilder::new(self.actor.clone())).actions
+    }
+}
+
+/// An `If` being built by [`ProgramBuilder::if_`]
+#[derive(Debug, Clone)]
+pub struct IfBuilder {
+    parent: ProgramBuilder,
+    action: Action,
+}
+
+impl IfBuilder {
+    pub fn then(mut self, branch: impl FnOnce(ProgramBuilder) -> ProgramBuilder) -> Self {
+        self.action.then_actions = Some(self.parent.nested(branch));
+        self
+    }
+
+    pub fn else_(mut self, branch: impl FnOnce(ProgramBuilder) -> ProgramBuilder) -> ProgramBuilder {
+        self.action.else_actions = Some(self.parent.nested(branch));
+        self.end()
+    }
+
+    /// Finish an `If` with no `else`
+    pub fn end(self) -> ProgramBuilder {
+        self.parent.action(self.action)
+    }
+}
+
+fn expression_value(expression: impl Into<Expression>) -> Value {
+    serde_json::to_value(expression.into()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_same_program_as_json() {
+        let built = ProgramBuilder::new("VM")
+            .metadata("description", "Counting")
+            .def_fn("double", &["n"], |f| f.ret(binary("*", var("n"), 2)))
+            .bind("total", 0)
+            .while_(compare(var("total"), ComparisonOp::LessThan, 10), |body| body
+                .assign("total", call("double", [("n", binary("+", var("total"), 1))]))
+                .if_(compare(var("total"), ComparisonOp::GreaterThan, 5))
+                .then(|then| then.emit("output", "big"))
+                .end())
+            .actor("robot")
+            .for_("i", 1, 2, |body| body.emit("output", var("i")))
+            .build();
+
+        let expected = Program::from_json(r#"{"metadata": {"description": "Counting"}, "actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Bind", "target": "total", "params": {"value": 0}},
+            {"actor": "VM", "op": "While", "target": "loop",
+             "condition": {"type": "comparison", "op": "<", "left": {"var": "total"}, "right": 10}, "body": [
+                {"actor": "VM", "op": "Assign", "target": "total", "params": {"value": {"call": "double", "args": {"n": {"expr": {"op": "+", "left": {"var": "total"}, "right": 1}}}}}},
+                {"actor": "VM", "op": "If", "target": "branch",
+                 "condition": {"type": "comparison", "op": ">", "left": {"var": "total"}, "right": 5},
+                 "then": [{"actor": "VM", "op": "Emit", "target": "output", "params": {"content": "big"}}]}
+             ]},
+            {"actor": "robot", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": 2, "body": [
+                {"actor": "robot", "op": "Emit", "target": "output", "params": {"content": {"var": "i"}}}
+            ]}
+        ]}"#).unwrap();
+
+        assert_eq!(built.to_canonical_json().unwrap(), expected.to_canonical_json().unwrap());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{Action, Operation};
+
+/// Substrates with a known operation set
+pub const SUBSTRATES: &[&str] = &["brain", "robot", "ruby", "ai"];
+
+const BRAIN_OPERATIONS: &[Operation] = &[
+    Operation::StoreFact,
+    Operation::Assert,
+    Operation::Emit,
+    Operation::Receive,
+    Operation::Measure,
+    Operation::Scan,
+    Operation::Decide,
+    Operation::Read,
+    Operation::Write,
+    Operation::Create,
+    Operation::Bind,
+    Operation::Oblige,
+    Operation::Wait,
+    Operation::Sleep,
+    Operation::Dream,
+    Operation::Imagine,
+    Operation::GenRandomInt,
+    Operation::Return,
+    Operation::If,
+    Operation::Match,
+    Operation::ForEach,
+    Operation::Append,
+    Operation::While,
+    Operation::For,
+    Operation::DefineFunction,
+    Operation::Call,
+    Operation::OnInterrupt,
+    Operation::Break,
+    Operation::Continue,
+    Operation::Halt,
+    Operation::Try,
+    Operation::Throw,
+    Operation::Parallel,
+    Operation::When,
+    Operation::Gather,
+    Operation::Heat,
+    Operation::Pour,
+    Operation::Mix,
+    Operation::Stir,
+    Operation::Place,
+    Operation::Remove,
+    Operation::Grasp,
+    Operation::Release,
+    Operation::Steep,
+    Operation::Serve,
+];
+
+const ROBOT_OPERATIONS: &[Operation] = &[
+    Operation::If,
+    Operation::Match,
+    Operation::ForEach,
+    Operation::Append,
+    Operation::While,
+    Operation::For,
+    Operation::DefineFunction,
+    Operation::Call,
+    Operation::OnInterrupt,
+    Operation::Break,
+    Operation::Continue,
+    Operation::Halt,
+    Operation::Try,
+    Operation::Throw,
+    Operation::Parallel,
+    Operation::When,
+    Operation::Bind,
+    Operation::Return,
+    Operation::Gather,
+    Operation::Measure,
+    Operation::Scan,
+    Operation::ReadSensor,
+    Operation::Heat,
+    Operation::Pour,
+    Operation::Mix,
+    Operation::Stir,
+    Operation::Place,
+    Operation::Remove,
+    Operation::Grasp,
+    Operation::Release,
+    Operation::Steep,
+    Operation::Serve,
+    Operation::Wait,
+    Operation::Emit,
+    Operation::Receive,
+];
+
+const RUBY_OPERATIONS: &[Operation] = &[
+    Operation::Call,
+    Operation::Assign,
+    Operation::Write,
+    Operation::Read,
+    Operation::Create,
+    Operation::Emit,
+    Operation::Assert,
+    Operation::StoreFact,
+    Operation::Bind,
+    Operation::Return,
+    Operation::Decide,
+    Operation::Wait,
+    Operation::GenRandomInt,
+    Operation::If,
+    Operation::Match,
+    Operation::ForEach,
+    Operation::Append,
+    Operation::While,
+    Operation::For,
+    Operation::DefineFunction,
+    Operation::OnInterrupt,
+    Operation::Break,
+    Operation::Continue,
+    Operation::Halt,
+    Operation::Try,
+    Operation::Throw,
+];
+
+const AI_OPERATIONS: &[Operation] = &[
+    Operation::Generate,
+    Operation::Parse,
+    Operation::Execute,
+    Operation::Emit,
+];
+
+/// Operations a substrate actually executes (as opposed to ignoring or
+/// reporting as unsupported)
+pub fn substrate_operations(substrate: &str) -> Option<&'static [Operation]> {
+    match substrate.to_lowercase().as_str() {
+        "brain" | "brainvm" => Some(BRAIN_OPERATIONS),
+        "robot" | "robotvm" => Some(ROBOT_OPERATIONS),
+        "ruby" | "rubyvm" => Some(RUBY_OPERATIONS),
+        "ai" => Some(AI_OPERATIONS),
+        _ => None,
+    }
+}
+
+/// Whether `substrate` supports `op`; unknown substrates support nothing
+pub fn supports(substrate: &str, op: &Operation) -> bool {
+    substrate_operations(substrate)
+        .map(|ops| ops.contains(op))
+        .unwrap_or(false)
+}
+
+/// Comma-separated operation names, for prompts and messages
+pub fn describe(substrate: &str) -> Option<String> {
+    substrate_operations(substrate).map(|ops| {
+        ops.iter()
+            .map(|op| format!("{:?}", op))
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+/// Drop actions `substrate` can't execute, recursing into control-flow and
+/// function bodies
+///
+/// Returns the kept actions and the operations that were dropped.
+pub fn retain_supported(substrate: &str, actions: Vec<Action>) -> (Vec<Action>, Vec<Operation>) {
+    let mut dropped = Vec::new();
+    let kept = retain_in(substrate, actions, &mut dropped);
+    (kept, dropped)
+}
+
+fn retain_in(substrate: &str, actions: Vec<Action>, dropped: &mut Vec<Operation>) -> Vec<Action> {
+    let mut kept = Vec::new();
+
+    for mut action in actions {
+        if !supports(substrate, &action.op) {
+            dropped.push(action.op);
+            continue;
+        }
+
+        action.visit_children_mut(&mut |_, inner| {
+            *inner = retain_in(substrate, std::mem::take(inner), dropped);
+        });
+
+        kept.push(action);
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supports() {
+        assert!(supports("robot", &Operation::Heat));
+        assert!(!supports("ruby", &Operation::Heat));
+        assert!(supports("RubyVM", &Operation::DefineFunction));
+        assert!(!supports("toaster", &Operation::Emit));
+    }
+
+    #[test]
+    fn test_retain_supported_recurses() {
+        let json = r#"[
+            {"actor": "a", "op": "Heat", "target": "water"},
+            {"actor": "a", "op": "If", "target": "check",
+             "condition": {"type": "comparison", "op": ">", "left": 1, "right": 0},
+             "then": [
+                {"actor": "a", "op": "Stir", "target": "pot"},
+                {"actor": "a", "op": "Emit", "target": "out"}
+             ]}
+        ]"#;
+        let actions: Vec<Action> = serde_json::from_str(json).unwrap();
+
+        let (kept, dropped) = retain_supported("ruby", actions);
+
+        assert_eq!(dropped, vec![Operation::Heat, Operation::Stir]);
+        assert_eq!(kept.len(), 1);
+        let then = kept[0].then_actions.as_ref().unwrap();
+        assert_eq!(then.len(), 1);
+        assert_eq!(then[0].op, Operation::Emit);
+    }
+}
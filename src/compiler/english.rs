@@ -0,0 +1,632 @@
+use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression};
+use anyhow::Result;
+use crate::error::UclError;
+use serde_json::Value;
+
+/// Renders a program as plain-English prose, for reviewing it with people
+/// who don't read UCL
+///
+/// Top-level actions become a numbered-by-word narrative ("First, … Then, …
+/// Finally, …"); the bodies of conditions, loops and functions become
+/// indented bullet lists.
+pub struct EnglishCompiler {
+    indent_level: usize,
+}
+
+impl EnglishCompiler {
+    pub fn new() -> Self {
+        Self { indent_level: 0 }
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<String> {
+        let mut output = String::new();
+
+        if let Some(description) = program.meta().description() {
+            output.push_str(description);
+            output.push_str("\n\n");
+        }
+
+        let mut previous_t = None;
+        let count = program.actions.len();
+        for (index, action) in program.actions.iter().enumerate() {
+            let connector = if count > 1 && action.t.is_some() && action.t == previous_t {
+                "At the same time, "
+            } else if count == 1 {
+                ""
+            } else if index == 0 {
+                "First, "
+            } else if index == count - 1 {
+                "Finally, "
+            } else {
+                "Then, "
+            };
+            let timing = match action.t {
+                Some(t) if connector != "At the same time, " => format!("at {}, ", seconds(t)),
+                _ => String::new(),
+            };
+            previous_t = action.t;
+
+            output.push_str(&capitalize(&format!("{}{}{}", connector, timing, self.narrate(action)?)));
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+
+    /// One action as a sentence, followed by any nested bullet lists
+    fn narrate(&mut self, action: &Action) -> Result<String> {
+        let actor = subject(&action.actor);
+        let sentence = match &action.op {
+            Operation::If => {
+                let condition = action.condition.as_ref()
+                    .ok_or_else(|| UclError::missing("If", "condition"))?;
+                let mut text = format!("if {}, {} does the following:", self.condition(condition), actor);
+                text.push_str(&self.block(action.then_actions.as_deref().unwrap_or_default())?);
+                if let Some(else_actions) = &action.else_actions {
+                    text.push_str(&format!("\n{}Otherwise, {} does the following:", self.sibling(), actor));
+                    text.push_str(&self.block(else_actions)?);
+                }
+                return Ok(text);
+            }
+            Operation::Match => {
+                let value = action.params.as_ref()
+                    .and_then(|p| p.get("value"))
+                    .ok_or_else(|| UclError::missing("Match", "'value' parameter"))?;
+                let mut text = format!("{} checks {}.", actor, self.value_or_expression(value));
+                for case in action.match_cases().transpose()?.unwrap_or_default() {
+                    let when = match (&case.value, &case.when) {
+                        (Some(pattern), _) => format!("If it is {}", self.text(pattern)),
+                        (None, Some(condition)) => format!("If {}", self.condition(condition)),
+                        (None, None) => "Otherwise".to_string(),
+                    };
+                    text.push_str(&format!("\n{}{}, {} does the following:", self.sibling(), when, actor));
+                    text.push_str(&self.block(&case.then)?);
+                }
+                if let Some(else_actions) = &action.else_actions {
+                    text.push_str(&format!("\n{}If nothing matches, {} does the following:", self.sibling(), actor));
+                    text.push_str(&self.block(else_actions)?);
+                }
+                return Ok(text);
+            }
+            Operation::While => {
+                let condition = action.condition.as_ref()
+                    .ok_or_else(|| UclError::missing("While", "condition"))?;
+                let text = format!("as long as {}, {} repeats the following:", self.condition(condition), actor);
+                return Ok(text + &self.block(action.body_actions.as_deref().unwrap_or_default())?);
+            }
+            Operation::For => {
+                let variable = action.loop_var.as_ref()
+                    .ok_or_else(|| UclError::missing("For", "variable"))?;
+                let from = action.from_expr.as_ref()
+                    .ok_or_else(|| UclError::missing("For", "from expression"))?;
+                let to = action.to_expr.as_ref()
+                    .ok_or_else(|| UclError::missing("For", "to expression"))?;
+                let step = match &action.step_expr {
+                    Some(step) => format!(", counting by {}", self.expression(step)),
+                    None => String::new(),
+                };
+                let text = format!("for each {} from {} to {}{}, {} does the following:",
+                    variable, self.expression(from), self.expression(to), step, actor);
+                return Ok(text + &self.block(action.body_actions.as_deref().unwrap_or_default())?);
+            }
+            Operation::ForEach => {
+                let variable = action.loop_var.as_ref()
+                    .ok_or_else(|| UclError::missing("ForEach", "variable"))?;
+                let params = action.params.as_ref();
+                let items = match params.and_then(|p| p.get("over")).and_then(|o| o.as_str()) {
+                    Some(collection) => format!("{}'s {}", actor, collection.replace('_', " ")),
+                    None => self.value_or_expression(params.and_then(|p| p.get("in"))
+                        .ok_or_else(|| UclError::missing("ForEach", "'in' parameter"))?),
+                };
+                let text = format!("for each {} in {}, {} does the following:", variable, items, actor);
+                return Ok(text + &self.block(action.body_actions.as_deref().unwrap_or_default())?);
+            }
+            Operation::DefineFunction => {
+                let params = action.params.as_ref()
+                    .ok_or_else(|| UclError::missing("DefineFunction", "params"))?;
+                let args: Vec<&str> = params.get("args")
+                    .and_then(|v| v.as_array())
+                    .map(|args| args.iter().filter_map(|a| a.as_str()).collect())
+                    .unwrap_or_default();
+                let body: Vec<Action> = serde_json::from_value(params.get("body").cloned().unwrap_or(Value::Array(Vec::new())))?;
+                let given = if args.is_empty() { String::new() } else { format!(" given {}", list(&args)) };
+                let text = format!("{} learns how to {}{}, which means:", actor, action.target, given);
+                return Ok(text + &self.block(&body)?);
+            }
+            Operation::OnInterrupt => {
+                let text = format!("{} arranges that, if anything goes wrong or the run is interrupted, it will:", actor);
+                return Ok(text + &self.block(action.body_actions.as_deref().unwrap_or_default())?);
+            }
+            Operation::Try => {
+                let error = action.params.as_ref().and_then(|p| p.get("error")).and_then(|v| v.as_str()).unwrap_or("error");
+                let mut text = format!("{} tries to:", actor);
+                text += &self.block(action.body_actions.as_deref().unwrap_or_default())?;
+                text += &format!("\n{}If that fails, {} calls the problem {} and instead:", self.sibling(), actor, error);
+                return Ok(text + &self.block(action.catch_actions.as_deref().unwrap_or_default())?);
+            }
+            Operation::When => {
+                let condition = action.condition.as_ref()
+                    .ok_or_else(|| UclError::missing("When", "condition"))?;
+                let text = format!("from now on, whenever {}, {} will:", self.condition(condition), actor);
+                return Ok(text + &self.block(action.body_actions.as_deref().unwrap_or_default())?);
+            }
+            Operation::Parallel => {
+                let text = format!("{} does all of these at the same time:", actor);
+                return Ok(text + &self.block(action.body_actions.as_deref().unwrap_or_default())?);
+            }
+            Operation::Imagine => {
+                let text = format!("{} imagines how {} would take it if:", actor, action.target);
+                return Ok(text + &self.block(action.body_actions.as_deref().unwrap_or_default())?);
+            }
+            Operation::Throw => format!("{} gives up, raising {}", actor, action.target),
+            Operation::Break => format!("{} stops repeating", actor),
+            Operation::Continue => format!("{} skips straight to the next round", actor),
+            Operation::Halt => match action.params.as_ref().and_then(|p| p.get("status")) {
+                Some(status) => format!("{} stops everything, with status {}", actor, self.value_or_expression(status)),
+                None => format!("{} stops everything", actor),
+            },
+            op => self.legal(action).unwrap_or_else(|| format!("{} {}", actor, self.predicate(op, action))),
+        };
+
+        let duration = match (&action.op, action.dur) {
+            (Operation::Wait, _) | (_, None) => String::new(),
+            (_, Some(dur)) => format!(" for {}", seconds(dur)),
+        };
+        let pre = action.pre.as_ref().map(|pre| format!(", once {}", lowercase_first(pre))).unwrap_or_default();
+        let post = action.post.as_ref().map(|post| format!(", leaving {}", lowercase_first(post))).unwrap_or_default();
+        Ok(format!("{}{}{}{}.", sentence, duration, pre, post))
+    }
+
+    /// Obligations and permissions that name their `duty` or `right`, read
+    /// as "X must …" / "X may …" about the party they bind
+    fn legal(&self, action: &Action) -> Option<String> {
+        let params = action.params.as_ref()?;
+        let (modal, key) = match action.op {
+            Operation::Oblige => ("must", "duty"),
+            Operation::Permit => ("may", "right"),
+            Operation::Remedy => ("is entitled to", "remedy"),
+            _ => return None,
+        };
+        let what = params.get(key)?.as_str()?;
+
+        let party = subject(&action.target);
+        let mut clause = if action.op == Operation::Permit && action.actor != action.target {
+            format!("{} allows {} to {}", subject(&action.actor), party, lowercase_first(what))
+        } else {
+            format!("{} {} {}", party, modal, lowercase_first(what))
+        };
+
+        let mut rest: Vec<(&String, &Value)> = params.iter().filter(|(k, _)| k.as_str() != key).collect();
+        rest.sort_by_key(|(k, _)| *k);
+        let mut details = Vec::new();
+        for (name, value) in rest {
+            let text = self.text(value);
+            match name.as_str() {
+                "by" => clause.push_str(&format!(" by {}", text)),
+                "condition" => {
+                    let condition = lowercase_first(&text);
+                    clause.push_str(&format!(" if {}", condition.strip_prefix("if ").unwrap_or(&condition)));
+                }
+                "window" | "duration" => clause.push_str(&format!(" within {}", text)),
+                _ => details.push(format!("{} {}", name.replace('_', " "), text)),
+            }
+        }
+        if !details.is_empty() {
+            clause.push_str(&format!(", with {}", list(&details)));
+        }
+        Some(clause)
+    }
+
+    /// What the actor does, for operations without nested actions
+    fn predicate(&self, op: &Operation, action: &Action) -> String {
+        let target = &action.target;
+        let param = |key: &str| action.params.as_ref().and_then(|p| p.get(key));
+        let value_of = |key: &str| param(key).map(|v| self.value_or_expression(v));
+
+        match op {
+            Operation::StoreFact => {
+                let entity = param("entity").and_then(|v| v.as_str());
+                let mut facts: Vec<(&String, &Value)> = action.params.iter().flatten()
+                    .filter(|(key, _)| key.as_str() != "entity")
+                    .collect();
+                facts.sort_by_key(|(key, _)| *key);
+                match (entity, facts.is_empty()) {
+                    (Some(entity), false) => {
+                        let facts: Vec<String> = facts.iter()
+                            .map(|(key, value)| format!("the {}'s {} is {}", entity, key, self.text(value)))
+                            .collect();
+                        format!("stores the fact that {}", list(&facts))
+                    }
+                    (Some(entity), true) => format!("remembers the {}", entity),
+                    (None, _) => format!("stores a fact in {}{}", target, self.with_params(action)),
+                }
+            }
+            Operation::Emit => match value_of("content").or_else(|| value_of("message")) {
+                Some(content) => format!("says {}", content),
+                None if action.params.as_ref().is_some_and(|p| !p.is_empty()) => {
+                    format!("emits {}{}", target, self.with_params(action))
+                }
+                None => format!("says the value of {}", target),
+            },
+            Operation::Bind | Operation::Assign => match value_of("value") {
+                Some(value) => format!("sets {} to {}", target, value),
+                None => format!("names {}", target),
+            },
+            Operation::Append => match value_of("value") {
+                Some(value) => format!("adds {} to the end of {}", value, target),
+                None => format!("adds to {}", target),
+            },
+            Operation::Write => match (param("operation").and_then(|v| v.as_str()), value_of("value")) {
+                (Some(operation), _) => {
+                    let side = |register: &str, literal: &str| param(register)
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string)
+                        .or_else(|| value_of(literal))
+                        .unwrap_or_else(|| "something".to_string());
+                    let operator = match operation {
+                        "add" => "plus",
+                        "subtract" => "minus",
+                        "divide" => "divided by",
+                        _ => "times",
+                    };
+                    format!("sets {} to {} {} {}", target, side("lhs_register", "lhs"), operator, side("rhs_register", "rhs"))
+                }
+                (None, Some(value)) => format!("writes {} to {}", value, target),
+                (None, None) => format!("writes to {}", target),
+            },
+            Operation::Read => format!("reads {}", target),
+            Operation::Create => format!("creates {}{}", article(target), self.with_params(action)),
+            Operation::Delete => format!("deletes {}", target),
+            Operation::Unbind => format!("forgets {}", target),
+            Operation::Receive => format!("receives {}", target),
+            Operation::Vote => format!("votes on {}", target),
+            Operation::Measure => format!("measures {}", target),
+            Operation::Scan => format!("scans {}", target),
+            Operation::ReadSensor => {
+                let sensor = action.params.as_ref().and_then(|p| p.get("sensor")).and_then(|v| v.as_str()).unwrap_or("temperature");
+                format!("reads the {} of {}", sensor, target)
+            }
+            Operation::Decide => match value_of("condition") {
+                Some(condition) => format!("decides on {} based on {}", target, condition),
+                None => format!("decides on {}", target),
+            },
+            Operation::Wait => {
+                let duration = action.dur.or_else(|| param("duration").and_then(|v| v.as_f64()));
+                match duration {
+                    Some(duration) => format!("waits {}", seconds(duration)),
+                    None => format!("waits for {}", target),
+                }
+            }
+            Operation::Sleep => format!("sleeps on {}", target),
+            Operation::Dream => match param("count").and_then(|v| v.as_u64()) {
+                Some(count) if count != 1 => format!("dreams {} dreams about {}", count, target),
+                _ => format!("dreams about {}", target),
+            },
+            Operation::Assert => match param("statement") {
+                Some(statement) => format!("asserts that {}", self.text(statement)),
+                None => format!("asserts {}", target),
+            },
+            Operation::Oblige => format!("is obliged to {}{}", target, self.with_params(action)),
+            Operation::Permit => format!("is permitted to {}{}", target, self.with_params(action)),
+            Operation::Remedy => format!("is entitled to the remedy {}{}", target, self.with_params(action)),
+            Operation::Transcribe => format!("transcribes {}", target),
+            Operation::Translate => format!("translates {}", target),
+            Operation::Express => format!("expresses {}", target),
+            Operation::Call => format!("calls {}{}", target, self.with_params(action)),
+            Operation::Return => match value_of("value") {
+                Some(value) => format!("gives back {}", value),
+                None => format!("gives back {}", target),
+            },
+            Operation::GenRandomInt => {
+                let bound = |key: &str, default: i64| param(key).and_then(|v| v.as_i64()).unwrap_or(default);
+                format!("picks a random whole number from {} to {} and calls it {}", bound("min", 0), bound("max", 9), target)
+            }
+            Operation::Gather => match param("items").and_then(|v| v.as_array()) {
+                Some(items) => {
+                    let items: Vec<String> = items.iter().map(|item| self.text(item).replace('_', " ")).collect();
+                    format!("gathers {}", list(&items))
+                }
+                None => format!("gathers {}", target),
+            },
+            Operation::Heat => format!("heats the {}{}", target, self.with_params(action)),
+            Operation::Pour => format!("pours the {}{}", target, self.with_params(action)),
+            Operation::Mix => format!("mixes the {}{}", target, self.with_params(action)),
+            Operation::Stir => format!("stirs the {}{}", target, self.with_params(action)),
+            Operation::Place => format!("places the {}{}", target, self.with_params(action)),
+            Operation::Remove => format!("removes the {}{}", target, self.with_params(action)),
+            Operation::Grasp => format!("picks up the {}", target),
+            Operation::Release => format!("lets go of the {}", target),
+            Operation::Steep => format!("steeps the {}{}", target, self.with_params(action)),
+            Operation::Serve => format!("serves the {}{}", target, self.with_params(action)),
+            Operation::Generate => format!("generates {}{}", target, self.with_params(action)),
+            Operation::Parse => format!("parses {}", target),
+            Operation::Execute => format!("executes {}", target),
+            Operation::Custom(name) => format!("performs {} on {}{}", name, target, self.with_params(action)),
+            other => format!("does something called {:?} to {}", other, target),
+        }
+    }
+
+    /// Indented bullet list of nested actions, starting on a new line
+    fn block(&mut self, actions: &[Action]) -> Result<String> {
+        if actions.is_empty() {
+            return Ok(format!("\n{}  - nothing", self.indent()));
+        }
+
+        self.indent_level += 1;
+        let mut output = String::new();
+        for action in actions {
+            let timing = action.t.map(|t| format!("at {}, ", seconds(t))).unwrap_or_default();
+            let sentence = self.narrate(action)?;
+            output.push_str(&format!("\n{}- {}", self.indent(), capitalize(&(timing + &sentence))));
+        }
+        self.indent_level -= 1;
+        Ok(output)
+    }
+
+    /// Start of a line that follows on from the action being narrated: flush
+    /// left at the top level, a bullet when nested
+    fn sibling(&self) -> String {
+        if self.indent_level == 0 { String::new() } else { format!("{}- ", self.indent()) }
+    }
+
+    fn indent(&self) -> String {
+        "  ".repeat(self.indent_level)
+    }
+
+    /// `, with speed 2 and notes "fast"`
+    fn with_params(&self, action: &Action) -> String {
+        let mut params: Vec<(&String, &Value)> = action.params.iter().flatten().collect();
+        if params.is_empty() {
+            return String::new();
+        }
+        params.sort_by_key(|(key, _)| *key);
+        let parts: Vec<String> = params.iter()
+            .map(|(key, value)| match value {
+                Value::String(text) => format!("{} {}", key.replace('_', " "), text),
+                _ => format!("{} {}", key.replace('_', " "), self.value_or_expression(value)),
+            })
+            .collect();
+        format!(", with {}", list(&parts))
+    }
+
+    fn condition(&self, condition: &Condition) -> String {
+        match condition {
+            Condition::Comparison { op, left, right } => {
+                let relation = match op {
+                    ComparisonOp::Equal => "is",
+                    ComparisonOp::NotEqual => "is not",
+                    ComparisonOp::LessThan => "is less than",
+                    ComparisonOp::LessThanOrEqual => "is at most",
+                    ComparisonOp::GreaterThan => "is greater than",
+                    ComparisonOp::GreaterThanOrEqual => "is at least",
+                };
+                format!("{} {} {}", self.expression(left), relation, self.expression(right))
+            }
+            Condition::And { operands } => {
+                let parts: Vec<String> = operands.iter().map(|c| self.condition(c)).collect();
+                list(&parts)
+            }
+            Condition::Or { operands } => {
+                let parts: Vec<String> = operands.iter().map(|c| self.condition(c)).collect();
+                either(&parts)
+            }
+            Condition::Not { operand } => format!("it is not the case that {}", self.condition(operand)),
+            Condition::Expression { value } => format!("{} holds", self.expression(value)),
+            Condition::Matches { value, pattern } => format!("{} matches the pattern /{}/", self.expression(value), pattern),
+            Condition::StartsWith { value, prefix } => format!("{} starts with {}", self.expression(value), self.expression(prefix)),
+            Condition::Contains { value, part } => format!("{} contains {}", self.expression(value), self.expression(part)),
+            Condition::Exists { var } => format!("{} is known", var),
+        }
+    }
+
+    fn expression(&self, expr: &Expression) -> String {
+        match expr {
+            Expression::Value(value) => self.plain(value),
+            Expression::Variable { var } => var.clone(),
+            Expression::BinaryOp { expr } => {
+                let operand = |side: &Expression| match side {
+                    Expression::BinaryOp { .. } => format!("({})", self.expression(side)),
+                    Expression::FunctionCall { args, .. } if !args.is_empty() => format!("({})", self.expression(side)),
+                    _ => self.expression(side),
+                };
+                let operator = match expr.op.as_str() {
+                    "+" => "plus",
+                    "-" => "minus",
+                    "*" => "times",
+                    "/" => "divided by",
+                    "%" => "modulo",
+                    "**" => "to the power of",
+                    other => other,
+                };
+                format!("{} {} {}", operand(&expr.left), operator, operand(&expr.right))
+            }
+            Expression::FunctionCall { call, args } => {
+                if args.is_empty() {
+                    return format!("the result of {}", call);
+                }
+                let mut args: Vec<(&String, &Expression)> = args.iter().collect();
+                args.sort_by_key(|(name, _)| *name);
+                let args: Vec<String> = args.iter()
+                    .map(|(name, value)| format!("{} = {}", name, self.expression(value)))
+                    .collect();
+                format!("the result of {} with {}", call, list(&args))
+            }
+            Expression::List { list: items } => {
+                let items: Vec<String> = items.iter().map(|item| self.expression(item)).collect();
+                format!("the list of {}", if items.is_empty() { "nothing".to_string() } else { list(&items) })
+            }
+            Expression::Index { index, at } => format!("item {} of {}", self.expression(at), self.expression(index)),
+            Expression::Length { length } => format!("the length of {}", self.expression(length)),
+            Expression::StringOp { string } => {
+                let args: Vec<String> = string.args.iter().map(|arg| self.expression(arg)).collect();
+                match (string.op.as_str(), args.as_slice()) {
+                    ("upper", [text]) => format!("{} in capitals", text),
+                    ("lower", [text]) => format!("{} in lower case", text),
+                    ("contains", [haystack, needle]) => format!("whether {} contains {}", haystack, needle),
+                    ("concat", _) => format!("{} joined together", list(&args)),
+                    (op, _) => format!("the {} of {}", op, list(&args)),
+                }
+            }
+            Expression::Format { format } => format!("\"{}\"", format),
+            Expression::Unary { unary } if unary.op == "-" => format!("minus {}", self.expression(&unary.operand)),
+            Expression::Unary { unary } => format!("not {}", self.expression(&unary.operand)),
+            Expression::Test { test } => format!("whether {}", self.condition(test)),
+        }
+    }
+
+    /// A param that may hold an expression (`{"var": "x"}`) or a plain value
+    fn value_or_expression(&self, value: &Value) -> String {
+        match serde_json::from_value::<Expression>(value.clone()) {
+            Ok(Expression::Value(value)) => self.plain(&value),
+            Ok(expr) => self.expression(&expr),
+            Err(_) => self.plain(value),
+        }
+    }
+
+    /// Strings without quotes, for values that read as part of the sentence
+    fn text(&self, value: &Value) -> String {
+        match value {
+            Value::String(s) => s.clone(),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(|v| self.text(v)).collect();
+                list(&items)
+            }
+            other => self.plain(other),
+        }
+    }
+
+    fn plain(&self, value: &Value) -> String {
+        match value {
+            Value::String(s) => format!("\"{}\"", s),
+            Value::Null => "nothing".to_string(),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(|v| self.plain(v)).collect();
+                list(&items)
+            }
+            Value::Object(map) => {
+                let pairs: Vec<String> = map.iter().map(|(k, v)| format!("{} {}", k, self.plain(v))).collect();
+                list(&pairs)
+            }
+            other => other.to_string(),
+        }
+    }
+}
+
+impl Default for EnglishCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `listener` → `the listener`; names that start with a capital stay as they are
+fn subject(actor: &str) -> String {
+    if actor.starts_with(char::is_uppercase) {
+        actor.to_string()
+    } else {
+        format!("the {}", actor.replace('_', " "))
+    }
+}
+
+fn article(noun: &str) -> String {
+    if noun.starts_with(char::is_uppercase) {
+        noun.to_string()
+    } else if noun.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        format!("an {}", noun)
+    } else {
+        format!("a {}", noun)
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn seconds(value: f64) -> String {
+    if value == 1.0 {
+        "1 second".to_string()
+    } else {
+        format!("{} seconds", value)
+    }
+}
+
+fn join(items: &[impl AsRef<str>], conjunction: &str) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.as_ref().to_string(),
+        [rest @ .., last] => {
+            let rest: Vec<&str> = rest.iter().map(AsRef::as_ref).collect();
+            format!("{} {} {}", rest.join(", "), conjunction, last.as_ref())
+        }
+    }
+}
+
+/// `a, b and c`
+fn list(items: &[impl AsRef<str>]) -> String {
+    join(items, "and")
+}
+
+/// `a, b or c`
+fn either(items: &[impl AsRef<str>]) -> String {
+    join(items, "or")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_narrates_sequence_and_timing() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "listener", "op": "StoreFact", "target": "memory", "params": {"entity": "cat", "color": "black"}},
+            {"actor": "Alice", "op": "Emit", "target": "greeting", "t": 2, "params": {"content": "hello"}},
+            {"actor": "Bob", "op": "Emit", "target": "greeting", "t": 2, "params": {"content": "hi"}},
+            {"actor": "robot", "op": "Wait", "target": "kettle", "dur": 30}
+        ]}"#).unwrap();
+
+        let text = EnglishCompiler::new().compile(&program).unwrap();
+        assert_eq!(text, "\
+First, the listener stores the fact that the cat's color is black.
+Then, at 2 seconds, Alice says \"hello\".
+At the same time, Bob says \"hi\".
+Finally, the robot waits 30 seconds.
+");
+    }
+
+    #[test]
+    fn test_narrates_conditions_and_loops() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "tenant", "op": "If", "target": "rent",
+             "condition": {"type": "and", "operands": [
+                {"type": "comparison", "op": ">", "left": {"var": "days_late"}, "right": 5},
+                {"type": "not", "operand": {"type": "comparison", "op": "==", "left": {"var": "waived"}, "right": true}}
+             ]},
+             "then": [{"actor": "tenant", "op": "Oblige", "target": "pay_fee", "params": {"amount": 50}}],
+             "else": [{"actor": "tenant", "op": "Emit", "target": "ok"}]},
+            {"actor": "counter", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": {"var": "n"},
+             "body": [{"actor": "counter", "op": "Bind", "target": "total",
+                       "params": {"value": {"expr": {"op": "+", "left": {"var": "total"}, "right": {"var": "i"}}}}}]}
+        ]}"#).unwrap();
+
+        let text = EnglishCompiler::new().compile(&program).unwrap();
+        assert_eq!(text, "\
+First, if days_late is greater than 5 and it is not the case that waived is true, the tenant does the following:
+  - The tenant is obliged to pay_fee, with amount 50.
+Otherwise, the tenant does the following:
+  - The tenant says the value of ok.
+Finally, for each i from 1 to n, the counter does the following:
+  - The counter sets total to total plus i.
+");
+    }
+}
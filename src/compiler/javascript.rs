@@ -0,0 +1,742 @@
+use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression};
+use crate::template::{self, Piece};
+use anyhow::Result;
+use crate::error::UclError;
+use std::collections::{BTreeSet, HashMap};
+
+/// Helper emitted once if any action waits, since plain JS has no blocking sleep
+const SLEEP_HELPER: &str = "\
+function sleep(seconds) {
+  const end = Date.now() + seconds * 1000;
+  while (Date.now() < end) {}
+}
+";
+
+/// Emitted for seeded programs that draw random numbers; `SEED` is replaced
+/// by the low 32 bits of the seed
+const SEEDED_RANDOM_HELPER: &str = "\
+Math.random = (() => {
+  let state = SEED >>> 0;
+  return () => {
+    state = (state + 0x6D2B79F5) >>> 0;
+    let t = Math.imul(state ^ (state >>> 15), state | 1);
+    t ^= t + Math.imul(t ^ (t >>> 7), t | 61);
+    return ((t ^ (t >>> 14)) >>> 0) / 4294967296;
+  };
+})();
+";
+
+/// Helpers emitted once if any action registers an OnInterrupt handler
+const INTERRUPT_HELPER: &str = "\
+const interruptHandlers = [];
+function onInterrupt(handler) {
+  interruptHandlers.unshift(handler);
+}
+function runInterruptHandlers() {
+  for (const handler of interruptHandlers.splice(0)) handler();
+}
+if (typeof process !== \"undefined\") {
+  process.on(\"uncaughtException\", (error) => { runInterruptHandlers(); console.error(error); process.exit(1); });
+  process.on(\"SIGINT\", () => { runInterruptHandlers(); process.exit(130); });
+} else if (typeof window !== \"undefined\") {
+  window.addEventListener(\"error\", runInterruptHandlers);
+}
+";
+
+/// Compiles UCL to JavaScript that runs unchanged in Node and browsers
+///
+/// Variables are declared with `let` at the top of the scope that assigns
+/// them (the program or a function), so they behave like Ruby's
+/// function-scoped locals even when first assigned inside a loop or branch.
+pub struct JavaScriptCompiler {
+    indent_level: usize,
+    /// Argument names of every function the program defines, for ordering
+    /// the named arguments of calls
+    functions: HashMap<String, Vec<String>>,
+    seed: Option<u64>,
+}
+
+impl JavaScriptCompiler {
+    pub fn new() -> Self {
+        Self {
+            indent_level: 0,
+            functions: HashMap::new(),
+            seed: None,
+        }
+    }
+
+    /// Replace `Math.random` with a generator seeded from `seed` (mulberry32),
+    /// so random draws repeat between runs
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<String> {
+        let mut output = String::new();
+
+        output.push_str("// Generated from UCL\n");
+        output.push_str("// Universal Causal Language -> JavaScript Compiler\n\n");
+
+        let mut uses = BTreeSet::new();
+        let mut random = false;
+        crate::analysis::walk_with_depth(&program.actions, 0, &mut |action, _| {
+            match action.op {
+                Operation::Wait => { uses.insert(SLEEP_HELPER); }
+                Operation::OnInterrupt => { uses.insert(INTERRUPT_HELPER); }
+                Operation::GenRandomInt => random = true,
+                Operation::DefineFunction => {
+                    if let Some(args) = action.params.as_ref().and_then(|p| p.get("args")).and_then(|a| a.as_array()) {
+                        let args = args.iter().filter_map(|a| a.as_str()).map(str::to_string).collect();
+                        self.functions.insert(action.target.clone(), args);
+                    }
+                }
+                _ => {}
+            }
+        });
+        for helper in uses {
+            output.push_str(helper);
+            output.push('\n');
+        }
+        if let (Some(seed), true) = (self.seed, random) {
+            output.push_str(&SEEDED_RANDOM_HELPER.replace("SEED", &(seed as u32).to_string()));
+            output.push('\n');
+        }
+
+        output.push_str(&self.declarations(&program.actions, &[]));
+
+        for action in &program.actions {
+            let code = self.compile_action(action)?;
+            if !code.is_empty() {
+                output.push_str(&code);
+                output.push('\n');
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// `let a, b;` for every variable assigned in `actions` (outside nested
+    /// function definitions), except `exclude`
+    fn declarations(&self, actions: &[Action], exclude: &[String]) -> String {
+        fn assigned(actions: &[Action], names: &mut BTreeSet<String>) {
+            for action in actions {
+                if matches!(action.op, Operation::Assign | Operation::Bind | Operation::Write | Operation::GenRandomInt) {
+                    names.insert(action.target.clone());
+                }
+                if action.op == Operation::Call {
+                    names.extend(action.params.as_ref().and_then(|p| p.get("out")?.as_str()).map(str::to_string));
+                }
+                action.visit_children(&mut |name, nested| if name != "params.body" { assigned(nested, names) });
+            }
+        }
+
+        let mut names = BTreeSet::new();
+        assigned(actions, &mut names);
+        names.retain(|name| !exclude.contains(name));
+
+        if names.is_empty() {
+            return String::new();
+        }
+        let indent = "  ".repeat(self.indent_level);
+        format!("{}let {};\n", indent, names.into_iter().collect::<Vec<_>>().join(", "))
+    }
+
+    fn compile_action(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+
+        match &action.op {
+            Operation::Call => self.compile_call(action, &indent),
+            Operation::Assign => self.compile_assign(action, &indent),
+            Operation::Write => self.compile_write(action, &indent),
+            Operation::Read => Ok(format!("{}{};", indent, action.target)),
+            Operation::Create => self.compile_create(action, &indent),
+            Operation::Emit => self.compile_emit(action, &indent),
+            Operation::Assert => self.compile_assert(action, &indent),
+            Operation::StoreFact => self.compile_store_fact(action, &indent),
+            Operation::Bind => self.compile_bind(action, &indent),
+            Operation::Return => self.compile_return(action, &indent),
+            Operation::Halt => self.compile_halt(action, &indent),
+            Operation::Decide => self.compile_decide(action, &indent),
+            Operation::Wait => self.compile_wait(action, &indent),
+            Operation::GenRandomInt => self.compile_gen_random_int(action, &indent),
+            Operation::If => self.compile_if(action),
+            Operation::While => self.compile_while(action),
+            Operation::For => self.compile_for(action),
+            Operation::DefineFunction => self.compile_define_function(action),
+            Operation::OnInterrupt => self.compile_on_interrupt(action),
+            _ => {
+                // For unsupported operations, generate a comment
+                Ok(format!("{}// Unsupported operation: {:?} on {}",
+                    indent, action.op, action.target))
+            }
+        }
+    }
+
+    /// Whether `target` exists in the generated JS: a function the program
+    /// defines or a method of a JS object
+    fn is_callee(&self, target: &str) -> bool {
+        self.functions.contains_key(target) || is_js_method(target)
+    }
+
+    fn compile_call(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let target = match action.target.as_str() {
+            "puts" | "print" => "console.log",
+            other => other,
+        };
+        let is_operator = ["+", "-", "*", "/", "%", "**"].contains(&target);
+
+        if let Some(p) = &action.params {
+            if is_operator {
+                let side = |register: &str, literal: &str| {
+                    p.get(register).and_then(|v| v.as_str()).map(str::to_string)
+                        .or_else(|| p.get(literal).map(|v| self.value_to_js(v)))
+                };
+                if let (Some(lhs), Some(rhs)) = (side("lhs_register", "lhs"), side("rhs_register", "rhs")) {
+                    return Ok(format!("{}({} {} {});", indent, lhs, target, rhs));
+                }
+            }
+
+            if !self.is_callee(target) {
+                return Ok(format!("{}// Unsupported call: {}", indent, target));
+            }
+
+            // Positional arguments in the conventional order, if any are given
+            let mut args: Vec<String> = ["a", "b", "c", "arg", "args", "n", "x", "y", "z"].iter()
+                .filter_map(|key| p.get(*key))
+                .map(|val| self.value_to_js(val))
+                .collect();
+
+            // Otherwise pass the remaining params as one options object
+            if args.is_empty() {
+                let mut named: Vec<(&String, &serde_json::Value)> = p.iter()
+                    .filter(|(key, _)| !["lhs", "rhs", "receiver", "out"].contains(&key.as_str()))
+                    .collect();
+                named.sort_by_key(|(key, _)| *key);
+                if !named.is_empty() {
+                    let pairs: Vec<String> = named.iter()
+                        .map(|(key, val)| format!("{}: {}", js_key(key), self.value_to_js(val)))
+                        .collect();
+                    args.push(format!("{{ {} }}", pairs.join(", ")));
+                }
+            }
+
+            let call = format!("{}({})", target, args.join(", "));
+            return Ok(match p.get("out").and_then(|v| v.as_str()) {
+                Some(out) => format!("{}{} = {};", indent, out, call),
+                None => format!("{}{};", indent, call),
+            });
+        }
+
+        if !self.is_callee(target) {
+            return Ok(format!("{}// Unsupported call: {}", indent, target));
+        }
+        Ok(format!("{}{}();", indent, target))
+    }
+
+    fn compile_assign(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let value = action.params
+            .as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Assign", "'value' parameter"))?;
+
+        Ok(format!("{}{} = {};", indent, action.target, self.value_to_js(value)))
+    }
+
+    fn compile_write(&mut self, action: &Action, indent: &str) -> Result<String> {
+        if let Some(params) = &action.params {
+            if let Some(op) = params.get("operation") {
+                let operator = match op.as_str().unwrap_or("") {
+                    "multiply" => "*",
+                    "add" => "+",
+                    "subtract" => "-",
+                    "divide" => "/",
+                    _ => "*",
+                };
+
+                let operand = |register: &str, literal: &str| {
+                    params.get(register).map(|r| r.as_str().unwrap_or("").to_string())
+                        .or_else(|| params.get(literal).map(|v| self.value_to_js(v)))
+                };
+                let lhs = operand("lhs_register", "lhs")
+                    .ok_or_else(|| UclError::missing("Write", "lhs_register or lhs"))?;
+                let rhs = operand("rhs_register", "rhs")
+                    .ok_or_else(|| UclError::missing("Write", "rhs_register or rhs"))?;
+
+                return Ok(format!("{}{} = {} {} {};", indent, action.target, lhs, operator, rhs));
+            }
+
+            if let Some(value) = params.get("value") {
+                return Ok(format!("{}{} = {};", indent, action.target, self.value_to_js(value)));
+            }
+        }
+
+        Err(UclError::missing("Write", "'value' parameter or operation").into())
+    }
+
+    fn compile_create(&mut self, action: &Action, indent: &str) -> Result<String> {
+        match &action.params {
+            Some(params) if !params.is_empty() => {
+                let mut pairs: Vec<(&String, &serde_json::Value)> = params.iter().collect();
+                pairs.sort_by_key(|(key, _)| *key);
+                let pairs: Vec<String> = pairs.iter()
+                    .map(|(key, val)| format!("{}: {}", js_key(key), self.value_to_js(val)))
+                    .collect();
+                Ok(format!("{}new {}({{ {} }});", indent, action.target, pairs.join(", ")))
+            }
+            _ => Ok(format!("{}new {}();", indent, action.target)),
+        }
+    }
+
+    fn compile_emit(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let params = action.params.as_ref();
+        let msg = if let Some(content) = params.and_then(|p| p.get("content")) {
+            if content.as_str() == Some(&action.target) {
+                action.target.clone()
+            } else {
+                self.value_or_expression(content)?
+            }
+        } else if let Some(message) = params.and_then(|p| p.get("message")) {
+            self.value_to_js(message)
+        } else {
+            // No content param, treat target as variable name
+            action.target.clone()
+        };
+
+        Ok(format!("{}console.log({});", indent, msg))
+    }
+
+    fn compile_assert(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let statement = action.params
+            .as_ref()
+            .and_then(|p| p.get("statement"))
+            .map(|v| self.value_to_js(v))
+            .unwrap_or_else(|| self.value_to_js(&serde_json::json!(action.target)));
+
+        Ok(format!("{}// Assert: {}", indent, statement))
+    }
+
+    fn compile_store_fact(&mut self, action: &Action, indent: &str) -> Result<String> {
+        match &action.params {
+            Some(params) => {
+                let mut facts: Vec<(&String, &serde_json::Value)> = params.iter().collect();
+                facts.sort_by_key(|(key, _)| *key);
+                let facts: Vec<String> = facts.iter()
+                    .map(|(key, val)| format!("{}.{} = {}", action.target, key, self.value_to_js(val)))
+                    .collect();
+                Ok(format!("{}// Store fact: {}", indent, facts.join(", ")))
+            }
+            None => Ok(format!("{}// Store fact about {}", indent, action.target)),
+        }
+    }
+
+    fn compile_bind(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let value = action.params
+            .as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Bind", "'value' parameter"))?;
+
+        Ok(format!("{}{} = {};", indent, action.target, self.value_or_expression(value)?))
+    }
+
+    fn compile_return(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let value = match action.params.as_ref().and_then(|p| p.get("value")) {
+            Some(value) => self.value_or_expression(value)?,
+            None => action.target.clone(),
+        };
+
+        Ok(format!("{}return {};", indent, value))
+    }
+
+    fn compile_halt(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let status = match action.params.as_ref().and_then(|p| p.get("status")) {
+            Some(status) => self.value_or_expression(status)?,
+            None => "0".to_string(),
+        };
+        Ok(format!("{}process.exit({});", indent, status))
+    }
+
+    fn compile_decide(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let condition = action.params
+            .as_ref()
+            .and_then(|p| p.get("condition"))
+            .map(|v| self.value_to_js(v))
+            .unwrap_or_else(|| "true".to_string());
+
+        Ok(format!("{}// Decide {}: {}", indent, action.target, condition))
+    }
+
+    fn compile_wait(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let duration = action.dur
+            .or_else(|| {
+                action.params.as_ref()
+                    .and_then(|p| p.get("duration"))
+                    .and_then(|v| v.as_f64())
+            })
+            .unwrap_or(1.0);
+
+        Ok(format!("{}sleep({});", indent, duration))
+    }
+
+    fn compile_gen_random_int(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let bound = |key: &str, default: i64| action.params.as_ref()
+            .and_then(|p| p.get(key))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(default);
+        let (min, max) = (bound("min", 0), bound("max", 9));
+
+        Ok(format!("{}{} = Math.floor(Math.random() * {}) + {};", indent, action.target, max - min + 1, min))
+    }
+
+    /// Compile `actions` one level deeper, followed by the closing `}`
+    fn compile_block(&mut self, actions: &[Action], close: &str) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let mut output = String::new();
+
+        self.indent_level += 1;
+        for action in actions {
+            let code = self.compile_action(action)?;
+            if !code.is_empty() {
+                output.push_str(&code);
+                output.push('\n');
+            }
+        }
+        self.indent_level -= 1;
+
+        output.push_str(&format!("{}{}", indent, close));
+        Ok(output)
+    }
+
+    fn compile_if(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let condition = action.condition.as_ref()
+            .ok_or_else(|| UclError::missing("If", "condition"))?;
+
+        let mut output = format!("{}if ({}) {{\n", indent, self.compile_condition(condition)?);
+        let then_actions = action.then_actions.as_deref().unwrap_or_default();
+        match &action.else_actions {
+            Some(else_actions) => {
+                output.push_str(&self.compile_block(then_actions, "} else {\n")?);
+                output.push_str(&self.compile_block(else_actions, "}")?);
+            }
+            None => output.push_str(&self.compile_block(then_actions, "}")?),
+        }
+        Ok(output)
+    }
+
+    fn compile_while(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let condition = action.condition.as_ref()
+            .ok_or_else(|| UclError::missing("While", "condition"))?;
+
+        let output = format!("{}while ({}) {{\n", indent, self.compile_condition(condition)?);
+        Ok(output + &self.compile_block(action.body_actions.as_deref().unwrap_or_default(), "}")?)
+    }
+
+    /// Runs the body if the script dies on an uncaught error or Ctrl-C
+    fn compile_on_interrupt(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let output = format!("{}onInterrupt(() => {{\n", indent);
+        Ok(output + &self.compile_block(action.body_actions.as_deref().unwrap_or_default(), "});")?)
+    }
+
+    fn compile_for(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let loop_var = action.loop_var.as_ref()
+            .ok_or_else(|| UclError::missing("For", "variable"))?;
+        let from_expr = action.from_expr.as_ref()
+            .ok_or_else(|| UclError::missing("For", "from expression"))?;
+        let to_expr = action.to_expr.as_ref()
+            .ok_or_else(|| UclError::missing("For", "to expression"))?;
+
+        let from_val = self.compile_expression(from_expr)?;
+        let to_val = self.compile_expression(to_expr)?;
+        let step = match &action.step_expr {
+            Some(step) => format!("{} += {}", loop_var, self.compile_expression(step)?),
+            None => format!("{}++", loop_var),
+        };
+
+        let output = format!("{}for (let {} = {}; {} <= {}; {}) {{\n",
+            indent, loop_var, from_val, loop_var, to_val, step);
+        Ok(output + &self.compile_block(action.body_actions.as_deref().unwrap_or_default(), "}")?)
+    }
+
+    fn compile_define_function(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let func_name = &action.target;
+
+        let params = action.params.as_ref()
+            .ok_or_else(|| UclError::missing("DefineFunction", "params"))?;
+
+        let args = params.get("args")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| UclError::missing("DefineFunction", "args array"))?;
+
+        let arg_names: Vec<String> = args.iter()
+            .filter_map(|v| v.as_str())
+            .map(|s| s.to_string())
+            .collect();
+
+        let body_value = params.get("body")
+            .ok_or_else(|| UclError::missing("DefineFunction", "body"))?;
+        let body_actions: Vec<Action> = serde_json::from_value(body_value.clone())?;
+
+        let mut output = format!("{}function {}({}) {{\n", indent, func_name, arg_names.join(", "));
+        self.indent_level += 1;
+        output.push_str(&self.declarations(&body_actions, &arg_names));
+        self.indent_level -= 1;
+        output.push_str(&self.compile_block(&body_actions, "}")?);
+        Ok(output)
+    }
+
+    fn compile_condition(&self, condition: &Condition) -> Result<String> {
+        match condition {
+            Condition::Comparison { op, left, right } => {
+                let left_val = self.compile_expression(left)?;
+                let right_val = self.compile_expression(right)?;
+                let op_str = match op {
+                    ComparisonOp::Equal => "===",
+                    ComparisonOp::NotEqual => "!==",
+                    ComparisonOp::LessThan => "<",
+                    ComparisonOp::LessThanOrEqual => "<=",
+                    ComparisonOp::GreaterThan => ">",
+                    ComparisonOp::GreaterThanOrEqual => ">=",
+                };
+                Ok(format!("{} {} {}", left_val, op_str, right_val))
+            }
+            Condition::And { operands } => {
+                let parts: Result<Vec<String>> = operands.iter()
+                    .map(|c| self.compile_condition(c))
+                    .collect();
+                Ok(format!("({})", parts?.join(" && ")))
+            }
+            Condition::Or { operands } => {
+                let parts: Result<Vec<String>> = operands.iter()
+                    .map(|c| self.compile_condition(c))
+                    .collect();
+                Ok(format!("({})", parts?.join(" || ")))
+            }
+            Condition::Not { operand } => {
+                Ok(format!("!({})", self.compile_condition(operand)?))
+            }
+            // Only false and nil are false, as in Ruby
+            Condition::Expression { value } => Ok(format!("(({}) ?? false) !== false", self.compile_expression(value)?)),
+            Condition::Matches { value, pattern } => {
+                Ok(format!("/{}/.test(String({}))", pattern.replace('/', "\\/"), self.compile_expression(value)?))
+            }
+            Condition::StartsWith { value, prefix } => {
+                Ok(format!("String({}).startsWith(String({}))", self.compile_expression(value)?, self.compile_expression(prefix)?))
+            }
+            Condition::Contains { value, part } => {
+                Ok(format!("{}.includes({})", self.compile_expression(value)?, self.compile_expression(part)?))
+            }
+            Condition::Exists { var } => Ok(format!("typeof {} !== \"undefined\"", var)),
+        }
+    }
+
+    fn compile_expression(&self, expr: &Expression) -> Result<String> {
+        match expr {
+            Expression::Value(v) => Ok(self.value_to_js(v)),
+            Expression::Variable { var } => Ok(var.clone()),
+            Expression::BinaryOp { expr: bin_op } => {
+                let left_val = self.compile_expression(&bin_op.left)?;
+                let right_val = self.compile_expression(&bin_op.right)?;
+                Ok(format!("({} {} {})", left_val, bin_op.op, right_val))
+            }
+            Expression::FunctionCall { call, args } => {
+                // Pass arguments in the order the function declares them;
+                // for functions defined elsewhere, in name order
+                let order: Vec<String> = match self.functions.get(call) {
+                    Some(params) => params.clone(),
+                    None => {
+                        let mut names: Vec<String> = args.keys().cloned().collect();
+                        names.sort();
+                        names
+                    }
+                };
+                let arg_strs: Result<Vec<String>> = order.iter()
+                    .map(|name| match args.get(name) {
+                        Some(arg) => self.compile_expression(arg),
+                        None => Ok("undefined".to_string()),
+                    })
+                    .collect();
+                Ok(format!("{}({})", call, arg_strs?.join(", ")))
+            }
+            Expression::List { list } => {
+                let items: Result<Vec<String>> = list.iter().map(|item| self.compile_expression(item)).collect();
+                Ok(format!("[{}]", items?.join(", ")))
+            }
+            Expression::Index { index, at } => {
+                Ok(format!("{}.at({})", self.compile_expression(index)?, self.compile_expression(at)?))
+            }
+            Expression::Length { length } => Ok(format!("{}.length", self.compile_expression(length)?)),
+            Expression::StringOp { string } => {
+                let args = string.args.iter().map(|arg| self.compile_expression(arg)).collect::<Result<Vec<_>>>()?;
+                match (string.op.as_str(), args.as_slice()) {
+                    ("concat", _) => Ok(format!("[{}].join(\"\")", args.join(", "))),
+                    ("upper", [text]) => Ok(format!("String({}).toUpperCase()", text)),
+                    ("lower", [text]) => Ok(format!("String({}).toLowerCase()", text)),
+                    ("contains", [haystack, needle]) => Ok(format!("{}.includes({})", haystack, needle)),
+                    (op, _) => Err(UclError::unsupported(format!("string function '{}' with {} arguments", op, args.len()), "javascript").into()),
+                }
+            }
+            Expression::Unary { unary } if unary.op == "!" => {
+                Ok(format!("((({}) ?? false) === false)", self.compile_expression(&unary.operand)?))
+            }
+            Expression::Unary { unary } => Ok(format!("({}{})", unary.op, self.compile_expression(&unary.operand)?)),
+            Expression::Test { test } => Ok(format!("({})", self.compile_condition(test)?)),
+            Expression::Format { format } => {
+                let mut output = String::from("`");
+                for piece in template::parse(format)? {
+                    match piece {
+                        Piece::Text(text) => output.push_str(&text.replace('\\', "\\\\").replace('`', "\\`").replace('$', "\\$")),
+                        Piece::Var(name) => output.push_str(&format!("${{{}}}", name)),
+                    }
+                }
+                output.push('`');
+                Ok(output)
+            }
+        }
+    }
+
+    /// A param that may hold an expression (`{"var": "x"}`) or a plain value
+    fn value_or_expression(&self, value: &serde_json::Value) -> Result<String> {
+        match serde_json::from_value::<Expression>(value.clone()) {
+            Ok(expr) => self.compile_expression(&expr),
+            Err(_) => Ok(self.value_to_js(value)),
+        }
+    }
+
+    fn value_to_js(&self, value: &serde_json::Value) -> String {
+        match value {
+            serde_json::Value::Array(arr) => {
+                let elements: Vec<String> = arr.iter()
+                    .map(|v| self.value_to_js(v))
+                    .collect();
+                format!("[{}]", elements.join(", "))
+            }
+            serde_json::Value::Object(obj) => {
+                let pairs: Vec<String> = obj.iter()
+                    .map(|(k, v)| format!("{}: {}", js_key(k), self.value_to_js(v)))
+                    .collect();
+                format!("{{ {} }}", pairs.join(", "))
+            }
+            // JSON strings, numbers, booleans and null are valid JS literals
+            other => other.to_string(),
+        }
+    }
+}
+
+impl Default for JavaScriptCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// A function name or dotted method path such as `console.log`
+/// A method on a JS object, like `Math.max` or `console.log`
+fn is_js_method(target: &str) -> bool {
+    target.contains('.') && target.split('.').all(is_identifier)
+}
+
+/// An object key, quoted unless it's a plain identifier
+fn js_key(key: &str) -> String {
+    if is_identifier(key) {
+        key.to_string()
+    } else {
+        serde_json::Value::String(key.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_compile_call() {
+        let mut compiler = JavaScriptCompiler::new();
+        let mut params = HashMap::new();
+        params.insert("lhs".to_string(), serde_json::json!(2));
+        params.insert("rhs".to_string(), serde_json::json!(3));
+
+        let action = Action::new("VM", Operation::Call, "+")
+            .with_params(params);
+
+        assert_eq!(compiler.compile_action(&action).unwrap(), "(2 + 3);");
+
+        let puts = Action::new("VM", Operation::Call, "puts").with_params(HashMap::from([("arg".to_string(), serde_json::json!(15))]));
+        assert_eq!(compiler.compile_action(&puts).unwrap(), "console.log(15);");
+
+        let unknown = Action::new("VM", Operation::Call, "launch").with_params(HashMap::from([("n".to_string(), serde_json::json!(3))]));
+        assert_eq!(compiler.compile_action(&unknown).unwrap(), "// Unsupported call: launch");
+    }
+
+    #[test]
+    fn test_call_stores_result_in_out() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Call", "target": "double", "params": {"n": 21, "out": "res"}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "res"}}}
+        ]}"#).unwrap();
+        let code = JavaScriptCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("let res;\n"), "{}", code);
+        assert!(code.contains("res = double(21);\n"), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_fibonacci() {
+        let program = Program::from_json(&std::fs::read_to_string("examples/fibonacci.json").unwrap()).unwrap();
+        let code = JavaScriptCompiler::new().compile(&program).unwrap();
+
+        assert!(code.contains("function fibonacci(n) {\n  if (n <= 1) {\n    return n;\n  } else {"), "{}", code);
+        assert!(code.contains("let result;\n"), "{}", code);
+        assert!(code.contains("for (let i = 0; i <= 10; i++) {\n  result = fibonacci(i);\n  console.log(result);\n}"), "{}", code);
+    }
+
+    #[test]
+    fn test_helpers_only_when_used() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "OnInterrupt", "target": "cleanup",
+             "body": [{"actor": "robot", "op": "Emit", "target": "log", "params": {"content": "stopped"}}]},
+            {"actor": "robot", "op": "Wait", "target": "kettle", "dur": 0.01}
+        ]}"#).unwrap();
+
+        let code = JavaScriptCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("function sleep(seconds)"));
+        assert!(code.contains("onInterrupt(() => {\n  console.log(\"stopped\");\n});"), "{}", code);
+
+        let plain = Program::from_json(r#"{"actions": [{"actor": "a", "op": "Emit", "target": "x", "params": {"content": "hi"}}]}"#).unwrap();
+        let code = JavaScriptCompiler::new().compile(&plain).unwrap();
+        assert!(!code.contains("function sleep") && !code.contains("onInterrupt"));
+    }
+
+    #[test]
+    fn test_compile_string_functions() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "hit", "params": {"value": {"string": {"op": "contains", "args": [{"string": {"op": "lower", "args": [{"var": "s"}]}}, "tea"]}}}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"format": "Cost: ${x}"}}}
+        ]}"#).unwrap();
+
+        let code = JavaScriptCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("String(s).toLowerCase().includes(\"tea\")"), "{}", code);
+        assert!(code.contains("`Cost: \\$${x}`"), "{}", code);
+    }
+
+    #[test]
+    fn test_seed_replaces_math_random() {
+        let program = Program::from_json(r#"{"actions": [{"actor": "VM", "op": "GenRandomInt", "target": "n", "params": {"min": 1, "max": 6}}]}"#).unwrap();
+        let code = JavaScriptCompiler::new().with_seed(42).compile(&program).unwrap();
+        assert!(code.contains("Math.random = ") && code.contains("42"), "{}", code);
+
+        let plain = Program::from_json(r#"{"actions": [{"actor": "a", "op": "Emit", "target": "x", "params": {"content": "hi"}}]}"#).unwrap();
+        assert!(!JavaScriptCompiler::new().with_seed(42).compile(&plain).unwrap().contains("Math.random"));
+    }
+}
@@ -1,4 +1,19 @@
+pub mod english;
+pub mod javascript;
+pub mod prolog;
 pub mod ruby;
+pub mod rust;
+pub mod shell;
+pub mod solidity;
+pub mod sql;
+pub mod wasm;
 
+pub use english::EnglishCompiler;
+pub use javascript::JavaScriptCompiler;
+pub use prolog::PrologCompiler;
 pub use ruby::RubyCompiler;
-
+pub use rust::RustCompiler;
+pub use shell::ShellCompiler;
+pub use solidity::SolidityCompiler;
+pub use sql::{SqlCompiler, SqlDialect};
+pub use wasm::WasmCompiler;
@@ -0,0 +1,249 @@
+use crate::{Action, Operation, Program};
+use anyhow::Result;
+
+/// Predicates the generated program defines, declared dynamic (so queries
+/// on ones no action produced fail instead of raising) and discontiguous (so
+/// clauses can stay in program order)
+const PREDICATES: &[&str] = &[
+    "fact/3",
+    "assertion/3",
+    "assertion_detail/3",
+    "obliged/3",
+    "obligation_term/4",
+    "obligation_result/3",
+    "permitted/3",
+    "permission_term/4",
+];
+
+/// Inference rules over the facts every program gets
+const RULES: &str = "\
+% A condition is satisfied if there is none, it has been asserted, or some
+% entity has it as a true attribute
+satisfied(none).
+satisfied(Condition) :- assertion(_, Condition, _).
+satisfied(Condition) :- fact(_, Condition, true).
+
+% A party must perform a duty, or may exercise a right, once its
+% precondition is satisfied
+must(Party, Duty) :- obliged(Party, Duty, Condition), satisfied(Condition).
+may(Party, Right) :- permitted(Party, Right, Condition), satisfied(Condition).
+";
+
+/// Compiles the logical content of a UCL program to ISO Prolog
+///
+/// `StoreFact` becomes `fact(Entity, Attribute, Value)` clauses, `Assert`
+/// becomes `assertion(Name, Statement, Actor)`, and `Oblige`/`Permit` become
+/// `obliged`/`permitted(Party, Duty, Precondition)` with their terms. `Read`
+/// and `Decide` become directives that query the facts so far and print the
+/// answer, so consulting the file replays the program. `must/2` and `may/2`
+/// infer which duties and rights are in force.
+pub struct PrologCompiler;
+
+impl PrologCompiler {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<String> {
+        let mut output = String::new();
+
+        output.push_str("% Generated from UCL\n");
+        output.push_str("% Universal Causal Language -> Prolog Compiler\n\n");
+        for predicate in PREDICATES {
+            output.push_str(&format!(":- dynamic({}).\n:- discontiguous({}).\n", predicate, predicate));
+        }
+        output.push('\n');
+        output.push_str(RULES);
+        output.push('\n');
+
+        for action in &program.actions {
+            output.push_str(&self.compile_action(action));
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+
+    fn compile_action(&self, action: &Action) -> String {
+        match action.op {
+            Operation::StoreFact => self.compile_store_fact(action),
+            Operation::Assert => self.compile_assert(action),
+            Operation::Oblige => self.compile_legal(action, "duty", "obliged", "obligation_term", Some("obligation_result")),
+            Operation::Permit => self.compile_legal(action, "right", "permitted", "permission_term", None),
+            Operation::Read => self.compile_read(action),
+            Operation::Decide => self.compile_decide(action),
+            _ => format!("% Skipped: {:?} on {}", action.op, action.target),
+        }
+    }
+
+    fn compile_store_fact(&self, action: &Action) -> String {
+        let params = action.params.as_ref();
+        let entity = params
+            .and_then(|p| p.get("entity"))
+            .and_then(|v| v.as_str())
+            .unwrap_or(&action.target);
+
+        let mut attributes: Vec<(&String, &serde_json::Value)> = params.into_iter().flatten()
+            .filter(|(key, _)| key.as_str() != "entity")
+            .collect();
+        attributes.sort_by_key(|(key, _)| *key);
+        if attributes.is_empty() {
+            return format!("% Skipped: StoreFact about {} has no attributes", entity);
+        }
+
+        attributes.iter()
+            .map(|(key, value)| format!("fact({}, {}, {}).", atom(entity), atom(key), term(value)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn compile_assert(&self, action: &Action) -> String {
+        let params = action.params.as_ref();
+        let statement = params
+            .and_then(|p| p.get("statement"))
+            .and_then(|v| v.as_str())
+            .unwrap_or(&action.target);
+
+        let mut lines = vec![format!("assertion({}, {}, {}).", atom(&action.target), atom(statement), atom(&action.actor))];
+        let mut details: Vec<(&String, &serde_json::Value)> = params.into_iter().flatten()
+            .filter(|(key, _)| key.as_str() != "statement")
+            .collect();
+        details.sort_by_key(|(key, _)| *key);
+        lines.extend(details.iter().map(|(key, value)| {
+            format!("assertion_detail({}, {}, {}).", atom(&action.target), atom(key), term(value))
+        }));
+        lines.join("\n")
+    }
+
+    /// `Oblige` and `Permit`: the party (target), what they must or may do
+    /// (`duty`/`right`, or the target), the precondition (`pre` or the
+    /// `condition` param, else `none`), then each other param as a term
+    fn compile_legal(&self, action: &Action, kind: &str, head: &str, terms: &str, result: Option<&str>) -> String {
+        let params = action.params.as_ref();
+        let party = atom(&action.target);
+        let what = atom(params.and_then(|p| p.get(kind)).and_then(|v| v.as_str()).unwrap_or(&action.target));
+        let condition = action.pre.as_deref()
+            .or_else(|| params.and_then(|p| p.get("condition")).and_then(|v| v.as_str()))
+            .map(atom)
+            .unwrap_or_else(|| "none".to_string());
+
+        let mut lines = vec![format!("{}({}, {}, {}).", head, party, what, condition)];
+        let mut rest: Vec<(&String, &serde_json::Value)> = params.into_iter().flatten()
+            .filter(|(key, _)| key.as_str() != kind && key.as_str() != "condition")
+            .collect();
+        rest.sort_by_key(|(key, _)| *key);
+        lines.extend(rest.iter().map(|(key, value)| format!("{}({}, {}, {}, {}).", terms, party, what, atom(key), term(value))));
+        if let (Some(result), Some(post)) = (result, &action.post) {
+            lines.push(format!("{}({}, {}, {}).", result, party, what, atom(post)));
+        }
+        lines.join("\n")
+    }
+
+    /// Print every stored value of `entity.attribute`, every attribute of
+    /// `entity`, or the statement of `assertion.name`
+    fn compile_read(&self, action: &Action) -> String {
+        let target = &action.target;
+        match target.split_once('.') {
+            Some(("assertion", name)) => format!(
+                ":- forall(assertion({}, Statement, _), format('~w: ~w~n', [{}, Statement])).",
+                atom(name), atom(target)),
+            Some((entity, attribute)) => format!(
+                ":- ( fact({e}, {a}, _) -> forall(fact({e}, {a}, Value), format('~w = ~w~n', [{t}, Value])) ; format('~w is unknown~n', [{t}]) ).",
+                e = atom(entity), a = atom(attribute), t = atom(target)),
+            None => format!(
+                ":- ( fact({e}, _, _) -> forall(fact({e}, Attribute, Value), format('~w.~w = ~w~n', [{e}, Attribute, Value])) ; format('~w is unknown~n', [{e}]) ).",
+                e = atom(target)),
+        }
+    }
+
+    /// Whether the decision's `condition` (or `choice`, or target) is
+    /// satisfied by the facts so far
+    fn compile_decide(&self, action: &Action) -> String {
+        let params = action.params.as_ref();
+        let condition = ["condition", "choice"].iter()
+            .find_map(|key| params.and_then(|p| p.get(*key)).and_then(|v| v.as_str()))
+            .unwrap_or(&action.target);
+
+        format!(":- ( satisfied({c}) -> format('~w decides ~w: yes~n', [{a}, {t}]) ; format('~w decides ~w: no~n', [{a}, {t}]) ).",
+            c = atom(condition), a = atom(&action.actor), t = atom(&action.target))
+    }
+}
+
+impl Default for PrologCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A bare atom if `text` is a lowercase identifier, otherwise quoted
+fn atom(text: &str) -> String {
+    let bare = text.starts_with(|c: char| c.is_ascii_lowercase())
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if bare {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'").replace('\n', "\\n"))
+}
+
+/// A JSON value as a Prolog term: strings become atoms, arrays lists, and
+/// objects lists of `Key-Value` pairs
+fn term(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "null".to_string(),
+        serde_json::Value::Bool(b) => b.to_string(),
+        serde_json::Value::Number(n) if n.is_f64() => {
+            // Prolog floats need a fractional part: 1e300 is written 1.0e300
+            let text = format!("{:?}", n.as_f64().unwrap_or_default());
+            if text.contains('.') { text } else { text.replacen('e', ".0e", 1) }
+        }
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::String(s) => atom(s),
+        serde_json::Value::Array(items) => {
+            format!("[{}]", items.iter().map(term).collect::<Vec<_>>().join(", "))
+        }
+        serde_json::Value::Object(map) => {
+            let mut pairs: Vec<(&String, &serde_json::Value)> = map.iter().collect();
+            pairs.sort_by_key(|(key, _)| *key);
+            format!("[{}]", pairs.iter().map(|(k, v)| format!("{}-{}", atom(k), term(v))).collect::<Vec<_>>().join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terms() {
+        assert_eq!(atom("sky"), "sky");
+        assert_eq!(atom("Buyer"), "'Buyer'");
+        assert_eq!(atom("it's"), "'it\\'s'");
+        assert_eq!(term(&serde_json::json!(1e300)), "1.0e300");
+        assert_eq!(term(&serde_json::json!({"b": [1, "x"], "a": true})), "[a-true, b-[1, x]]");
+    }
+
+    #[test]
+    fn test_compile_beliefs_and_obligations() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "listener", "op": "StoreFact", "target": "memory", "params": {"entity": "sky", "color": "blue"}},
+            {"actor": "teacher", "op": "Assert", "target": "fact", "params": {"statement": "Order confirmed", "context": "shop"}},
+            {"actor": "Seller", "op": "Oblige", "target": "Seller", "params": {"duty": "Deliver", "quantity": 1},
+             "pre": "Order confirmed", "post": "Goods in buyer possession"},
+            {"actor": "listener", "op": "Read", "target": "sky.color"},
+            {"actor": "Seller", "op": "Decide", "target": "ship", "params": {"condition": "Order confirmed"}},
+            {"actor": "listener", "op": "Wait", "target": "later"}
+        ]}"#).unwrap();
+        let code = PrologCompiler::new().compile(&program).unwrap();
+
+        for expected in [
+            "fact(sky, color, blue).",
+            "assertion(fact, 'Order confirmed', teacher).\nassertion_detail(fact, context, shop).",
+            "obliged('Seller', 'Deliver', 'Order confirmed').\nobligation_term('Seller', 'Deliver', quantity, 1).\nobligation_result('Seller', 'Deliver', 'Goods in buyer possession').",
+            ":- ( fact(sky, color, _) -> forall(fact(sky, color, Value), format('~w = ~w~n', ['sky.color', Value])) ; format('~w is unknown~n', ['sky.color']) ).",
+            ":- ( satisfied('Order confirmed') -> format('~w decides ~w: yes~n', ['Seller', ship]) ;",
+            "% Skipped: Wait on later",
+        ] {
+            assert!(code.contains(expected), "missing {}\n{}", expected, code);
+        }
+    }
+}
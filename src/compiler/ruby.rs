@@ -1,10 +1,25 @@
 use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression};
-use anyhow::{anyhow, Result};
+use crate::template::{self, Piece};
+use anyhow::Result;
+use crate::error::UclError;
 use std::collections::HashMap;
 
+/// Carries a `Throw`'s value, whatever its type, to the `rescue` of a `Try`
+const THROW_CLASS: &str = "class UclThrow < StandardError
+  attr_reader :value
+
+  def initialize(value)
+    @value = value
+    super(value.to_s)
+  end
+end
+
+";
+
 pub struct RubyCompiler {
     indent_level: usize,
     variables: HashMap<String, String>,
+    seed: Option<u64>,
 }
 
 impl RubyCompiler {
@@ -12,15 +27,30 @@ impl RubyCompiler {
         Self {
             indent_level: 0,
             variables: HashMap::new(),
+            seed: None,
         }
     }
 
+    /// Start the script with `srand(seed)`, so `rand` repeats between runs
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
     pub fn compile(&mut self, program: &Program) -> Result<String> {
         let mut output = String::new();
 
         // Add a header comment
         output.push_str("# Generated from UCL\n");
         output.push_str("# Universal Causal Language -> Ruby Compiler\n\n");
+        if let Some(seed) = self.seed {
+            output.push_str(&format!("srand({})\n\n", seed));
+        }
+        let mut throws = false;
+        program.walk(&mut |action, _| throws |= matches!(action.op, Operation::Try | Operation::Throw));
+        if throws {
+            output.push_str(THROW_CLASS);
+        }
 
         // Compile each action
         for action in &program.actions {
@@ -52,9 +82,18 @@ impl RubyCompiler {
             Operation::Wait => self.compile_wait(action, &indent),
             Operation::GenRandomInt => self.compile_gen_random_int(action, &indent),
             Operation::If => self.compile_if(action),
+            Operation::Match => self.compile_match(action),
             Operation::While => self.compile_while(action),
             Operation::For => self.compile_for(action),
+            Operation::ForEach => self.compile_for_each(action),
+            Operation::Append => self.compile_append(action, &indent),
             Operation::DefineFunction => self.compile_define_function(action),
+            Operation::OnInterrupt => self.compile_on_interrupt(action),
+            Operation::Break => Ok(format!("{}break", indent)),
+            Operation::Continue => Ok(format!("{}next", indent)),
+            Operation::Try => self.compile_try(action),
+            Operation::Throw => self.compile_throw(action, &indent),
+            Operation::Halt => self.compile_halt(action, &indent),
             _ => {
                 // For unsupported operations, generate a comment
                 Ok(format!("{}# Unsupported operation: {:?} on {}",
@@ -119,14 +158,20 @@ impl RubyCompiler {
         }
 
         let args_str = args.join(", ");
-        Ok(format!("{}{}({})", indent, action.target, args_str))
+        match params.and_then(|p| p.get("out")).and_then(|v| v.as_str()) {
+            Some(out) => {
+                self.variables.insert(out.to_string(), "assigned".to_string());
+                Ok(format!("{}{} = {}({})", indent, out, action.target, args_str))
+            }
+            None => Ok(format!("{}{}({})", indent, action.target, args_str)),
+        }
     }
 
     fn compile_assign(&mut self, action: &Action, indent: &str) -> Result<String> {
         let value = action.params
             .as_ref()
             .and_then(|p| p.get("value"))
-            .ok_or_else(|| anyhow!("Assign requires 'value' parameter"))?;
+            .ok_or_else(|| UclError::missing("Assign", "'value' parameter"))?;
 
         let var_name = &action.target;
         self.variables.insert(var_name.clone(), "assigned".to_string());
@@ -152,7 +197,7 @@ impl RubyCompiler {
                 } else if let Some(lhs_val) = params.get("lhs") {
                     self.value_to_ruby(lhs_val)
                 } else {
-                    return Err(anyhow!("Write operation requires lhs_register or lhs"));
+                    return Err(UclError::missing("Write", "lhs_register or lhs").into());
                 };
 
                 // Get right operand (register or value)
@@ -161,7 +206,7 @@ impl RubyCompiler {
                 } else if let Some(rhs_val) = params.get("rhs") {
                     self.value_to_ruby(rhs_val)
                 } else {
-                    return Err(anyhow!("Write operation requires rhs_register or rhs"));
+                    return Err(UclError::missing("Write", "rhs_register or rhs").into());
                 };
 
                 return Ok(format!("{}{} = {} {} {}", indent, action.target, lhs, operator, rhs));
@@ -173,7 +218,7 @@ impl RubyCompiler {
             }
         }
 
-        Err(anyhow!("Write requires 'value' parameter or operation"))
+        Err(UclError::missing("Write", "'value' parameter or operation").into())
     }
 
     fn compile_read(&mut self, action: &Action, indent: &str) -> Result<String> {
@@ -248,7 +293,7 @@ impl RubyCompiler {
         let value_json = action.params
             .as_ref()
             .and_then(|p| p.get("value"))
-            .ok_or_else(|| anyhow!("Bind requires 'value' parameter"))?;
+            .ok_or_else(|| UclError::missing("Bind", "'value' parameter"))?;
 
         let var_name = &action.target;
         self.variables.insert(var_name.clone(), "bound".to_string());
@@ -327,7 +372,7 @@ impl RubyCompiler {
     fn compile_if(&mut self, action: &Action) -> Result<String> {
         let indent = "  ".repeat(self.indent_level);
         let condition = action.condition.as_ref()
-            .ok_or_else(|| anyhow!("If operation requires condition"))?;
+            .ok_or_else(|| UclError::missing("If", "condition"))?;
 
         let mut output = String::new();
         output.push_str(&format!("{}if {}\n", indent, self.compile_condition(condition)?));
@@ -366,7 +411,7 @@ impl RubyCompiler {
     fn compile_while(&mut self, action: &Action) -> Result<String> {
         let indent = "  ".repeat(self.indent_level);
         let condition = action.condition.as_ref()
-            .ok_or_else(|| anyhow!("While operation requires condition"))?;
+            .ok_or_else(|| UclError::missing("While", "condition"))?;
 
         let mut output = String::new();
         output.push_str(&format!("{}while {}\n", indent, self.compile_condition(condition)?));
@@ -388,14 +433,120 @@ impl RubyCompiler {
         Ok(output)
     }
 
+    /// Runs the body when the script exits on an exception, including Ctrl-C
+    fn compile_on_interrupt(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+
+        let mut output = String::new();
+        output.push_str(&format!("{}at_exit do\n", indent));
+        output.push_str(&format!("{}  if $!\n", indent));
+
+        if let Some(body_actions) = &action.body_actions {
+            self.indent_level += 2;
+            for body_action in body_actions {
+                let code = self.compile_action(body_action)?;
+                if !code.is_empty() {
+                    output.push_str(&code);
+                    output.push('\n');
+                }
+            }
+            self.indent_level -= 2;
+        }
+
+        output.push_str(&format!("{}  end\n", indent));
+        output.push_str(&format!("{}end", indent));
+        Ok(output)
+    }
+
+    /// `case value` with a `when` per arm; with any condition arms, a bare
+    /// `case` whose value arms compare with `==`
+    fn compile_match(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let value_json = action.params.as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Match", "'value' parameter"))?;
+        let value = self.value_or_expression(value_json)?;
+        let cases = action.match_cases().transpose()?.unwrap_or_default();
+        let bare = cases.iter().any(|case| case.value.is_none());
+
+        let mut output = if bare { format!("{}case
+", indent) } else { format!("{}case {}
+", indent, value) };
+        for case in &cases {
+            let pattern = match (&case.value, &case.when) {
+                (Some(pattern), _) if bare => format!("{} == {}", value, self.value_to_ruby(pattern)),
+                (Some(pattern), _) => self.value_to_ruby(pattern),
+                (None, Some(condition)) => self.compile_condition(condition)?,
+                (None, None) => "true".to_string(),
+            };
+            output.push_str(&format!("{}when {}
+", indent, pattern));
+            output.push_str(&self.compile_body(&case.then)?);
+        }
+        if let Some(else_actions) = &action.else_actions {
+            output.push_str(&format!("{}else
+", indent));
+            output.push_str(&self.compile_body(else_actions)?);
+        }
+        output.push_str(&format!("{}end", indent));
+        Ok(output)
+    }
+
+    fn compile_try(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let error = action.params.as_ref()
+            .and_then(|p| p.get("error"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("error");
+
+        let mut output = format!("{}begin\n", indent);
+        output.push_str(&self.compile_body(action.body_actions.as_deref().unwrap_or_default())?);
+        output.push_str(&format!("{}rescue => e\n", indent));
+        output.push_str(&format!("{}  {} = e.is_a?(UclThrow) ? e.value : e.message\n", indent, error));
+        output.push_str(&self.compile_body(action.catch_actions.as_deref().unwrap_or_default())?);
+        output.push_str(&format!("{}end", indent));
+        Ok(output)
+    }
+
+    fn compile_throw(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let value = match action.params.as_ref().and_then(|p| p.get("value")) {
+            Some(value_json) => self.value_or_expression(value_json)?,
+            None => self.value_to_ruby(&serde_json::json!(action.target)),
+        };
+        Ok(format!("{}raise UclThrow.new({})", indent, value))
+    }
+
+    fn compile_halt(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let status = match action.params.as_ref().and_then(|p| p.get("status")) {
+            Some(status) => self.value_or_expression(status)?,
+            None => "0".to_string(),
+        };
+        Ok(format!("{}exit({})", indent, status))
+    }
+
+    /// Actions one level deeper than the current indent, a line each
+    fn compile_body(&mut self, actions: &[Action]) -> Result<String> {
+        self.indent_level += 1;
+        let mut output = String::new();
+        for action in actions {
+            let code = self.compile_action(action)?;
+            if !code.is_empty() {
+                output.push_str(&code);
+                output.push('\n');
+            }
+        }
+        self.indent_level -= 1;
+        Ok(output)
+    }
+
     fn compile_for(&mut self, action: &Action) -> Result<String> {
         let indent = "  ".repeat(self.indent_level);
         let loop_var = action.loop_var.as_ref()
-            .ok_or_else(|| anyhow!("For operation requires variable"))?;
+            .ok_or_else(|| UclError::missing("For", "variable"))?;
         let from_expr = action.from_expr.as_ref()
-            .ok_or_else(|| anyhow!("For operation requires from expression"))?;
+            .ok_or_else(|| UclError::missing("For", "from expression"))?;
         let to_expr = action.to_expr.as_ref()
-            .ok_or_else(|| anyhow!("For operation requires to expression"))?;
+            .ok_or_else(|| UclError::missing("For", "to expression"))?;
 
         let from_val = self.compile_expression(from_expr)?;
         let to_val = self.compile_expression(to_expr)?;
@@ -421,17 +572,50 @@ impl RubyCompiler {
         Ok(output)
     }
 
+    fn compile_for_each(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let loop_var = action.loop_var.as_ref()
+            .ok_or_else(|| UclError::missing("ForEach", "variable"))?;
+        let params = action.params.as_ref();
+        if params.is_some_and(|p| p.contains_key("over")) {
+            return Err(UclError::unsupported("ForEach over simulator state", "ruby").into());
+        }
+        let list = params.and_then(|p| p.get("in"))
+            .ok_or_else(|| UclError::missing("ForEach", "'in' parameter"))?;
+
+        // A hash is walked by its keys, as in the simulators
+        let items = self.value_or_expression(list)?;
+        let items = match serde_json::from_value::<Expression>(list.clone()) {
+            Ok(Expression::Value(serde_json::Value::Object(_))) => format!("{}.keys", items),
+            Ok(Expression::Value(_) | Expression::List { .. }) => items,
+            Ok(Expression::Variable { var }) if self.variables.get(&var).is_some_and(|kind| kind == "list") => items,
+            _ => format!("({0}.is_a?(Hash) ? {0}.keys : {0})", items),
+        };
+        let mut output = format!("{}{}.each do |{}|\n", indent, items, loop_var);
+        output.push_str(&self.compile_body(action.body_actions.as_deref().unwrap_or_default())?);
+        output.push_str(&format!("{}end", indent));
+        Ok(output)
+    }
+
+    fn compile_append(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let value = action.params.as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Append", "'value' parameter"))?;
+        self.variables.insert(action.target.clone(), "list".to_string());
+        Ok(format!("{}({} ||= []) << {}", indent, action.target, self.value_or_expression(value)?))
+    }
+
     fn compile_define_function(&mut self, action: &Action) -> Result<String> {
         let indent = "  ".repeat(self.indent_level);
         let func_name = &action.target;
 
         // Extract function args and body from params
         let params = action.params.as_ref()
-            .ok_or_else(|| anyhow!("DefineFunction requires params"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "params"))?;
 
         let args = params.get("args")
             .and_then(|v| v.as_array())
-            .ok_or_else(|| anyhow!("DefineFunction requires args array"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "args array"))?;
 
         let arg_names: Vec<String> = args.iter()
             .filter_map(|v| v.as_str())
@@ -439,7 +623,7 @@ impl RubyCompiler {
             .collect();
 
         let body_value = params.get("body")
-            .ok_or_else(|| anyhow!("DefineFunction requires body"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "body"))?;
 
         // Parse body as array of actions
         let body_actions: Vec<Action> = serde_json::from_value(body_value.clone())?;
@@ -492,6 +676,17 @@ impl RubyCompiler {
             Condition::Not { operand } => {
                 Ok(format!("!({})", self.compile_condition(operand)?))
             }
+            Condition::Expression { value } => Ok(format!("({})", self.compile_expression(value)?)),
+            Condition::Matches { value, pattern } => {
+                Ok(format!("{}.to_s =~ /{}/", self.compile_expression(value)?, pattern.replace('/', "\\/")))
+            }
+            Condition::StartsWith { value, prefix } => {
+                Ok(format!("{}.to_s.start_with?({}.to_s)", self.compile_expression(value)?, self.compile_expression(prefix)?))
+            }
+            Condition::Contains { value, part } => {
+                Ok(format!("{}.include?({})", self.compile_expression(value)?, self.compile_expression(part)?))
+            }
+            Condition::Exists { var } => Ok(format!("!!defined?({})", var)),
         }
     }
 
@@ -512,6 +707,45 @@ impl RubyCompiler {
                     .collect();
                 Ok(format!("{}({})", call, arg_strs?.join(", ")))
             }
+            Expression::List { list } => {
+                let items: Result<Vec<String>> = list.iter().map(|item| self.compile_expression(item)).collect();
+                Ok(format!("[{}]", items?.join(", ")))
+            }
+            Expression::Index { index, at } => {
+                Ok(format!("{}[{}]", self.compile_expression(index)?, self.compile_expression(at)?))
+            }
+            Expression::Length { length } => Ok(format!("{}.length", self.compile_expression(length)?)),
+            Expression::StringOp { string } => {
+                let args = string.args.iter().map(|arg| self.compile_expression(arg)).collect::<Result<Vec<_>>>()?;
+                match (string.op.as_str(), args.as_slice()) {
+                    ("concat", _) => Ok(format!("[{}].join", args.join(", "))),
+                    ("upper", [text]) => Ok(format!("{}.to_s.upcase", text)),
+                    ("lower", [text]) => Ok(format!("{}.to_s.downcase", text)),
+                    ("contains", [haystack, needle]) => Ok(format!("{}.include?({})", haystack, needle)),
+                    (op, _) => Err(UclError::unsupported(format!("string function '{}' with {} arguments", op, args.len()), "ruby").into()),
+                }
+            }
+            Expression::Unary { unary } => Ok(format!("({}{})", unary.op, self.compile_expression(&unary.operand)?)),
+            Expression::Test { test } => Ok(format!("({})", self.compile_condition(test)?)),
+            Expression::Format { format } => {
+                let mut output = String::from("\"");
+                for piece in template::parse(format)? {
+                    match piece {
+                        Piece::Text(text) => output.push_str(&text.replace('\\', "\\\\").replace('"', "\\\"").replace('#', "\\#")),
+                        Piece::Var(name) => output.push_str(&format!("#{{{}}}", name)),
+                    }
+                }
+                output.push('"');
+                Ok(output)
+            }
+        }
+    }
+
+    /// A param that may hold an expression (`{"var": "x"}`) or a plain value
+    fn value_or_expression(&self, value: &serde_json::Value) -> Result<String> {
+        match serde_json::from_value::<Expression>(value.clone()) {
+            Ok(expr) => self.compile_expression(&expr),
+            Err(_) => Ok(self.value_to_ruby(value)),
         }
     }
 
@@ -575,6 +809,27 @@ mod tests {
         assert!(code.contains("(2 + 3)"));
     }
 
+    #[test]
+    fn test_call_stores_result_in_out() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Call", "target": "double", "params": {"n": 21, "out": "res"}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "res"}}}
+        ]}"#).unwrap();
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("res = double(21)\nputs res"), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_halt() {
+        let mut compiler = RubyCompiler::new();
+        let halt = |json: &str| Program::parse_action(json).unwrap();
+        assert_eq!(compiler.compile_action(&halt(r#"{"actor": "VM", "op": "Halt", "target": "stop"}"#)).unwrap(), "exit(0)");
+        assert_eq!(compiler.compile_action(&halt(r#"{"actor": "VM", "op": "Halt", "target": "stop", "params": {"status": {"var": "code"}}}"#)).unwrap(), "exit(code)");
+    }
+
     #[test]
     fn test_compile_emit() {
         let mut compiler = RubyCompiler::new();
@@ -588,5 +843,147 @@ mod tests {
         assert!(code.contains("puts"));
         assert!(code.contains("Hello, World!"));
     }
-}
 
+    #[test]
+    fn test_compile_on_interrupt() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "OnInterrupt", "target": "cleanup",
+             "body": [{"actor": "robot", "op": "Emit", "target": "log", "params": {"content": "stopped"}}]}
+        ]}"#).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("at_exit do\n  if $!\n    puts \"stopped\"\n  end\nend"), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_match() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Match", "target": "day", "params": {"value": {"var": "n"}, "cases": [
+                {"value": 0, "then": [{"actor": "VM", "op": "Emit", "target": "log", "params": {"content": "Sunday"}}]},
+                {"value": 6, "then": [{"actor": "VM", "op": "Emit", "target": "log", "params": {"content": "Saturday"}}]}
+             ]}, "else": [{"actor": "VM", "op": "Emit", "target": "log", "params": {"content": "weekday"}}]},
+            {"actor": "VM", "op": "Match", "target": "size", "params": {"value": {"var": "n"}, "cases": [
+                {"value": 0, "then": []},
+                {"when": {"type": "comparison", "op": ">", "left": {"var": "n"}, "right": 5}, "then": [
+                    {"actor": "VM", "op": "Emit", "target": "log", "params": {"content": "big"}}]}
+             ]}}
+        ]}"#).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("case n\nwhen 0\n  puts \"Sunday\"\nwhen 6\n  puts \"Saturday\"\nelse\n  puts \"weekday\"\nend"), "{}", code);
+        assert!(code.contains("case\nwhen n == 0\nwhen n > 5\n  puts \"big\"\nend"), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_lists() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "xs", "params": {"value": {"list": [1, {"var": "n"}]}}},
+            {"actor": "VM", "op": "Append", "target": "xs", "params": {"value": {"index": {"var": "xs"}, "at": -1}}},
+            {"actor": "VM", "op": "ForEach", "target": "loop", "variable": "x", "params": {"in": {"var": "xs"}}, "body": [
+                {"actor": "VM", "op": "Emit", "target": "log", "params": {"content": {"length": {"var": "xs"}}}}
+            ]}
+        ]}"#).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("xs = [1, n]\n"), "{}", code);
+        assert!(code.contains("(xs ||= []) << xs[-1]\n"), "{}", code);
+        assert!(code.contains("xs.each do |x|\n  puts xs.length\nend"), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_unary_and_truth_values() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "ok", "params": {"value": {"test": {"type": "comparison", "op": ">", "left": {"unary": {"op": "-", "operand": {"var": "x"}}}, "right": 0}}}},
+            {"actor": "VM", "op": "If", "target": "check", "condition": {"type": "expression", "value": {"unary": {"op": "!", "operand": {"var": "ok"}}}},
+             "then": [{"actor": "VM", "op": "Emit", "target": "log", "params": {"content": "no"}}]}
+        ]}"#).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("ok = ((-x) > 0)\n"), "{}", code);
+        assert!(code.contains("if ((!ok))\n"), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_for_each_over_hashes() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "ForEach", "target": "a", "variable": "k", "params": {"in": {"bob": 1}}, "body": []},
+            {"actor": "VM", "op": "ForEach", "target": "b", "variable": "k", "params": {"in": {"var": "ages"}}, "body": []}
+        ]}"#).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains(".keys.each do |k|"), "{}", code);
+        assert!(code.contains("(ages.is_a?(Hash) ? ages.keys : ages).each do |k|"), "{}", code);
+
+        let over = Program::from_json(r#"{"actions": [{"actor": "VM", "op": "ForEach", "target": "a", "variable": "k", "params": {"over": "goals"}, "body": []}]}"#).unwrap();
+        assert!(RubyCompiler::new().compile(&over).is_err());
+    }
+
+    #[test]
+    fn test_compile_exists() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "If", "target": "check", "condition": {"type": "not", "operand": {"type": "exists", "var": "cache"}},
+             "then": [{"actor": "VM", "op": "Bind", "target": "cache", "params": {"value": []}}]}
+        ]}"#).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("if !(!!defined?(cache))"), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_text_conditions() {
+        let program = Program::from_json(r##"{"actions": [
+            {"actor": "VM", "op": "If", "target": "check", "condition": {"type": "or", "operands": [
+                {"type": "matches", "value": {"var": "line"}, "pattern": "^\\d+/\\d+$"},
+                {"type": "starts_with", "value": {"var": "line"}, "prefix": "#"},
+                {"type": "contains", "value": {"var": "words"}, "part": "tea"}
+             ]}, "then": []}
+        ]}"##).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains(r##"if (line.to_s =~ /^\d+\/\d+$/ || line.to_s.start_with?("#".to_s) || words.include?("tea"))"##), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_string_functions() {
+        let program = Program::from_json(r##"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "s", "params": {"value": {"string": {"op": "concat", "args": [{"string": {"op": "upper", "args": [{"var": "a"}]}}, "-", {"var": "b"}]}}}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"format": "Answer \"#{{x}}\": {x}"}}}
+        ]}"##).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("s = [a.to_s.upcase, \"-\", b].join\n"), "{}", code);
+        assert!(code.contains(r#"puts "Answer \"\#{x}\": #{x}""#), "{}", code);
+    }
+
+    #[test]
+    fn test_compile_try_and_throw() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Try", "target": "risky", "params": {"error": "problem"},
+             "body": [{"actor": "VM", "op": "Throw", "target": "oops", "params": {"value": {"var": "x"}}}],
+             "catch": [{"actor": "VM", "op": "Emit", "target": "log", "params": {"content": "failed"}}]}
+        ]}"#).unwrap();
+
+        let code = RubyCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("class UclThrow < StandardError\n"), "{}", code);
+        assert!(code.contains("begin\n  raise UclThrow.new(x)\nrescue => e\n  problem = e.is_a?(UclThrow) ? e.value : e.message\n  puts \"failed\"\nend"), "{}", code);
+
+        let plain = Program::from_json(r#"{"actions": [{"actor": "VM", "op": "Emit", "target": "log", "params": {"content": "hi"}}]}"#).unwrap();
+        assert!(!RubyCompiler::new().compile(&plain).unwrap().contains("UclThrow"));
+    }
+
+    #[test]
+    fn test_seed_sets_srand() {
+        let program = Program::from_json(r#"{"actions": [{"actor": "VM", "op": "GenRandomInt", "target": "n", "params": {"min": 1, "max": 6}}]}"#).unwrap();
+        assert!(RubyCompiler::new().with_seed(42).compile(&program).unwrap().contains("srand(42)\n"));
+        assert!(!RubyCompiler::new().compile(&program).unwrap().contains("srand"));
+    }
+
+    #[test]
+    fn test_missing_condition_is_a_typed_error() {
+        let action = Action::new("VM", Operation::If, "check");
+        let error = RubyCompiler::new().compile_action(&action).unwrap_err();
+
+        assert_eq!(error.to_string(), "If requires condition");
+        assert!(matches!(error.downcast_ref(), Some(UclError::MissingParam { op, .. }) if op == "If"));
+    }
+}
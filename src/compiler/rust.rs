@@ -0,0 +1,725 @@
+use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression};
+use anyhow::{anyhow, Result};
+use crate::error::UclError;
+use std::collections::{BTreeSet, HashMap};
+
+/// Dynamically typed values for the generated program, with the arithmetic
+/// and comparisons UCL expressions need
+const VALUE_RUNTIME: &str = r#"#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn as_f64(&self) -> f64 {
+        match self {
+            Value::Int(i) => *i as f64,
+            Value::Float(f) => *f,
+            Value::Bool(b) => *b as i64 as f64,
+            Value::Str(s) => s.parse().unwrap_or(0.0),
+            _ => 0.0,
+        }
+    }
+
+    fn arithmetic(self, other: Value, ints: fn(i64, i64) -> Option<i64>, floats: fn(f64, f64) -> f64) -> Value {
+        match (&self, &other) {
+            (Value::Int(a), Value::Int(b)) => ints(*a, *b).map(Value::Int).unwrap_or(Value::Null),
+            _ => Value::Float(floats(self.as_f64(), other.as_f64())),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::List(items) => {
+                let items: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::Str(a), Value::Str(b)) => a.partial_cmp(b),
+            _ => self.as_f64().partial_cmp(&other.as_f64()),
+        }
+    }
+}
+
+impl std::ops::Add for Value {
+    type Output = Value;
+    fn add(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Str(a), b) => Value::Str(format!("{}{}", a, b)),
+            (a, Value::Str(b)) => Value::Str(format!("{}{}", a, b)),
+            (Value::List(mut a), Value::List(b)) => { a.extend(b); Value::List(a) }
+            (a, b) => a.arithmetic(b, i64::checked_add, |x, y| x + y),
+        }
+    }
+}
+
+impl std::ops::Sub for Value {
+    type Output = Value;
+    fn sub(self, other: Value) -> Value {
+        self.arithmetic(other, i64::checked_sub, |x, y| x - y)
+    }
+}
+
+impl std::ops::Mul for Value {
+    type Output = Value;
+    fn mul(self, other: Value) -> Value {
+        self.arithmetic(other, i64::checked_mul, |x, y| x * y)
+    }
+}
+
+impl std::ops::Div for Value {
+    type Output = Value;
+    fn div(self, other: Value) -> Value {
+        self.arithmetic(other, i64::checked_div_euclid, |x, y| x / y)
+    }
+}
+
+impl std::ops::Rem for Value {
+    type Output = Value;
+    fn rem(self, other: Value) -> Value {
+        self.arithmetic(other, i64::checked_rem_euclid, |x, y| x.rem_euclid(y))
+    }
+}
+
+fn pow(base: Value, exponent: Value) -> Value {
+    match (&base, &exponent) {
+        (Value::Int(b), Value::Int(e)) if *e >= 0 => b.checked_pow(*e as u32).map(Value::Int).unwrap_or(Value::Null),
+        _ => Value::Float(base.as_f64().powf(exponent.as_f64())),
+    }
+}
+"#;
+
+/// Emitted if any action generates a random number, so the program needs no
+/// crates
+const RANDOM_RUNTIME: &str = r#"
+fn random_int(min: i64, max: i64) -> Value {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let mut x = STATE.load(Ordering::Relaxed);
+    if x == 0 {
+        x = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(1) | 1;
+    }
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    STATE.store(x, Ordering::Relaxed);
+    Value::Int(min + (x % (max - min + 1) as u64) as i64)
+}
+"#;
+
+/// Compiles UCL to a standalone Rust `main.rs` with no dependencies
+///
+/// Every UCL value is a `Value` (defined in the generated file), so programs
+/// keep their dynamic typing: integers stay integers until mixed with floats,
+/// and `+` concatenates strings. Variables are declared once at the top of the
+/// scope that assigns them, and user functions take and return `Value`s.
+pub struct RustCompiler {
+    indent_level: usize,
+    /// Argument names of every function the program defines, for ordering
+    /// the named arguments of calls
+    functions: HashMap<String, Vec<String>>,
+    /// Whether a function body is being compiled, rather than `main`
+    in_function: bool,
+    seed: Option<u64>,
+}
+
+impl RustCompiler {
+    pub fn new() -> Self {
+        Self {
+            indent_level: 0,
+            functions: HashMap::new(),
+            in_function: false,
+            seed: None,
+        }
+    }
+
+    /// Start `random_int` from a state derived from `seed` instead of the
+    /// clock, so random draws repeat between runs
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<String> {
+        let mut output = String::new();
+
+        output.push_str("// Generated from UCL\n");
+        output.push_str("// Universal Causal Language -> Rust Compiler\n\n");
+        output.push_str("#![allow(unused, unreachable_code)]\n\n");
+        output.push_str(VALUE_RUNTIME);
+
+        let mut random = false;
+        crate::analysis::walk_with_depth(&program.actions, 0, &mut |action, _| {
+            match action.op {
+                Operation::GenRandomInt => random = true,
+                Operation::DefineFunction => {
+                    if let Some(args) = action.params.as_ref().and_then(|p| p.get("args")).and_then(|a| a.as_array()) {
+                        let args = args.iter().filter_map(|a| a.as_str()).map(str::to_string).collect();
+                        self.functions.insert(action.target.clone(), args);
+                    }
+                }
+                _ => {}
+            }
+        });
+        if random {
+            match self.seed {
+                // xorshift needs a non-zero state, and zero means "seed from the clock"
+                Some(seed) => output.push_str(&RANDOM_RUNTIME.replace(
+                    "AtomicU64::new(0)",
+                    &format!("AtomicU64::new({})", crate::rng::SeededRng::new(seed).next_u64() | 1),
+                )),
+                None => output.push_str(RANDOM_RUNTIME),
+            }
+        }
+
+        output.push_str("\nfn main() {\n");
+        self.indent_level = 1;
+        output.push_str(&self.declarations(&program.actions, &[]));
+        for action in &program.actions {
+            let code = self.compile_action(action)?;
+            if !code.is_empty() {
+                output.push_str(&code);
+                output.push('\n');
+            }
+        }
+        self.indent_level = 0;
+        output.push_str("}\n");
+
+        Ok(output)
+    }
+
+    /// `let mut x = Value::Null;` for every variable assigned or read in
+    /// `actions` (outside nested function definitions), except `exclude`
+    ///
+    /// Reading a variable nothing assigns gives nil, as it does in the
+    /// simulators, rather than failing to compile.
+    fn declarations(&self, actions: &[Action], exclude: &[String]) -> String {
+        fn used(actions: &[Action], names: &mut BTreeSet<String>) {
+            for action in actions {
+                let has_param = |key: &str| action.params.as_ref().is_some_and(|p| p.contains_key(key));
+                match action.op {
+                    Operation::Assign | Operation::Bind | Operation::Write | Operation::GenRandomInt => {
+                        names.insert(action.target.clone());
+                    }
+                    Operation::Call => {
+                        names.extend(action.params.as_ref().and_then(|p| p.get("out")?.as_str()).map(str::to_string));
+                    }
+                    // Emit prints the target variable without content, or with
+                    // content naming the target
+                    Operation::Emit if !has_param("message") && action.params.as_ref()
+                        .and_then(|p| p.get("content"))
+                        .is_none_or(|c| c.as_str() == Some(&action.target)) => {
+                        names.insert(action.target.clone());
+                    }
+                    Operation::Return if !has_param("value") => {
+                        names.insert(action.target.clone());
+                    }
+                    // Its body declares its own variables
+                    Operation::DefineFunction => continue,
+                    _ => {}
+                }
+                let mut reads = crate::analysis::reads(action);
+                for expr in [&action.from_expr, &action.to_expr, &action.step_expr].into_iter().flatten() {
+                    crate::analysis::collect_vars(&serde_json::to_value(expr).unwrap_or_default(), &mut reads);
+                }
+                names.extend(reads);
+                action.visit_children(&mut |_, nested| used(nested, names));
+            }
+        }
+
+        let mut names = BTreeSet::new();
+        used(actions, &mut names);
+
+        let indent = "  ".repeat(self.indent_level);
+        names.into_iter()
+            .filter(|name| !exclude.contains(name) && is_identifier(name))
+            .map(|name| format!("{}let mut {} = Value::Null;\n", indent, name))
+            .collect()
+    }
+
+    fn compile_action(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+
+        match &action.op {
+            Operation::Call => self.compile_call(action, &indent),
+            Operation::Assign => self.compile_assign(action, &indent),
+            Operation::Write => self.compile_write(action, &indent),
+            Operation::Read => Ok(format!("{}// Read {}", indent, action.target)),
+            Operation::Create => Ok(format!("{}// Create {}", indent, action.target)),
+            Operation::Emit => self.compile_emit(action, &indent),
+            Operation::Assert => self.compile_assert(action, &indent),
+            Operation::StoreFact => Ok(format!("{}// Store fact about {}", indent, action.target)),
+            Operation::Bind => self.compile_bind(action, &indent),
+            Operation::Return => self.compile_return(action, &indent),
+            Operation::Halt => self.compile_halt(action, &indent),
+            Operation::Decide => Ok(format!("{}// Decide {}", indent, action.target)),
+            Operation::Wait => self.compile_wait(action, &indent),
+            Operation::GenRandomInt => self.compile_gen_random_int(action, &indent),
+            Operation::If => self.compile_if(action),
+            Operation::While => self.compile_while(action),
+            Operation::For => self.compile_for(action),
+            Operation::DefineFunction => self.compile_define_function(action),
+            _ => {
+                // For unsupported operations, generate a comment
+                Ok(format!("{}// Unsupported operation: {:?} on {}",
+                    indent, action.op, action.target))
+            }
+        }
+    }
+
+    fn compile_call(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let target = &action.target;
+        let params = action.params.as_ref();
+
+        if ["+", "-", "*", "/", "%", "**"].contains(&target.as_str()) {
+            let side = |register: &str, literal: &str| {
+                params.and_then(|p| p.get(register)).and_then(|v| v.as_str()).map(|r| format!("{}.clone()", r))
+                    .or_else(|| params.and_then(|p| p.get(literal)).map(|v| self.value_to_rust(v)))
+            };
+            if let (Some(lhs), Some(rhs)) = (side("lhs_register", "lhs"), side("rhs_register", "rhs")) {
+                return Ok(format!("{}let _ = {};", indent, binary(target, &lhs, &rhs)?));
+            }
+        }
+
+        // Only functions the program defines exist in the generated binary
+        let Some(arg_names) = self.functions.get(target) else {
+            return Ok(format!("{}// Unsupported call: {}", indent, target));
+        };
+        let args: Vec<String> = arg_names.iter()
+            .map(|name| params.and_then(|p| p.get(name))
+                .map(|v| self.value_or_expression(v))
+                .unwrap_or_else(|| Ok("Value::Null".to_string())))
+            .collect::<Result<_>>()?;
+
+        Ok(match params.and_then(|p| p.get("out")).and_then(|v| v.as_str()) {
+            Some(out) => format!("{}{} = {}({});", indent, out, target, args.join(", ")),
+            None => format!("{}{}({});", indent, target, args.join(", ")),
+        })
+    }
+
+    fn compile_assign(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let value = action.params
+            .as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Assign", "'value' parameter"))?;
+
+        Ok(format!("{}{} = {};", indent, action.target, self.value_to_rust(value)))
+    }
+
+    fn compile_write(&mut self, action: &Action, indent: &str) -> Result<String> {
+        if let Some(params) = &action.params {
+            if let Some(op) = params.get("operation") {
+                let operator = match op.as_str().unwrap_or("") {
+                    "multiply" => "*",
+                    "add" => "+",
+                    "subtract" => "-",
+                    "divide" => "/",
+                    _ => "*",
+                };
+
+                let operand = |register: &str, literal: &str| {
+                    params.get(register).map(|r| format!("{}.clone()", r.as_str().unwrap_or("")))
+                        .or_else(|| params.get(literal).map(|v| self.value_to_rust(v)))
+                };
+                let lhs = operand("lhs_register", "lhs")
+                    .ok_or_else(|| UclError::missing("Write", "lhs_register or lhs"))?;
+                let rhs = operand("rhs_register", "rhs")
+                    .ok_or_else(|| UclError::missing("Write", "rhs_register or rhs"))?;
+
+                return Ok(format!("{}{} = {};", indent, action.target, binary(operator, &lhs, &rhs)?));
+            }
+
+            if let Some(value) = params.get("value") {
+                return Ok(format!("{}{} = {};", indent, action.target, self.value_to_rust(value)));
+            }
+        }
+
+        Err(UclError::missing("Write", "'value' parameter or operation").into())
+    }
+
+    fn compile_emit(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let params = action.params.as_ref();
+        let msg = if let Some(content) = params.and_then(|p| p.get("content")) {
+            if content.as_str() == Some(&action.target) {
+                action.target.clone()
+            } else {
+                self.value_or_expression(content)?
+            }
+        } else if let Some(message) = params.and_then(|p| p.get("message")) {
+            self.value_to_rust(message)
+        } else {
+            // No content param, treat target as variable name
+            action.target.clone()
+        };
+
+        Ok(format!("{}println!(\"{{}}\", {});", indent, msg))
+    }
+
+    fn compile_assert(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let statement = action.params
+            .as_ref()
+            .and_then(|p| p.get("statement"))
+            .and_then(|v| v.as_str())
+            .unwrap_or(&action.target);
+
+        Ok(format!("{}// Assert: {}", indent, statement.replace('\n', " ")))
+    }
+
+    fn compile_bind(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let value = action.params
+            .as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Bind", "'value' parameter"))?;
+
+        Ok(format!("{}{} = {};", indent, action.target, self.value_or_expression(value)?))
+    }
+
+    fn compile_return(&mut self, action: &Action, indent: &str) -> Result<String> {
+        // Returning from the top level ends the program, as in Ruby
+        if !self.in_function {
+            return Ok(format!("{}return;", indent));
+        }
+
+        let value = match action.params.as_ref().and_then(|p| p.get("value")) {
+            Some(value) => self.value_or_expression(value)?,
+            None => format!("{}.clone()", action.target),
+        };
+
+        Ok(format!("{}return {};", indent, value))
+    }
+
+    fn compile_halt(&mut self, action: &Action, indent: &str) -> Result<String> {
+        match action.params.as_ref().and_then(|p| p.get("status")) {
+            Some(status) => {
+                let status = self.value_or_expression(status)?;
+                Ok(format!("{}std::process::exit(({}).as_f64() as i32);", indent, status))
+            }
+            None => Ok(format!("{}std::process::exit(0);", indent)),
+        }
+    }
+
+    fn compile_wait(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let duration = action.dur
+            .or_else(|| {
+                action.params.as_ref()
+                    .and_then(|p| p.get("duration"))
+                    .and_then(|v| v.as_f64())
+            })
+            .unwrap_or(1.0);
+
+        Ok(format!("{}std::thread::sleep(std::time::Duration::from_secs_f64({:?}));", indent, duration))
+    }
+
+    fn compile_gen_random_int(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let bound = |key: &str, default: i64| action.params.as_ref()
+            .and_then(|p| p.get(key))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(default);
+
+        Ok(format!("{}{} = random_int({}, {});", indent, action.target, bound("min", 0), bound("max", 9)))
+    }
+
+    /// Compile `actions` one level deeper, followed by the closing `}`
+    fn compile_block(&mut self, actions: &[Action], close: &str) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let mut output = String::new();
+
+        self.indent_level += 1;
+        for action in actions {
+            let code = self.compile_action(action)?;
+            if !code.is_empty() {
+                output.push_str(&code);
+                output.push('\n');
+            }
+        }
+        self.indent_level -= 1;
+
+        output.push_str(&format!("{}{}", indent, close));
+        Ok(output)
+    }
+
+    fn compile_if(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let condition = action.condition.as_ref()
+            .ok_or_else(|| UclError::missing("If", "condition"))?;
+
+        let mut output = format!("{}if {} {{\n", indent, self.compile_condition(condition)?);
+        let then_actions = action.then_actions.as_deref().unwrap_or_default();
+        match &action.else_actions {
+            Some(else_actions) => {
+                output.push_str(&self.compile_block(then_actions, "} else {\n")?);
+                output.push_str(&self.compile_block(else_actions, "}")?);
+            }
+            None => output.push_str(&self.compile_block(then_actions, "}")?),
+        }
+        Ok(output)
+    }
+
+    fn compile_while(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let condition = action.condition.as_ref()
+            .ok_or_else(|| UclError::missing("While", "condition"))?;
+
+        let output = format!("{}while {} {{\n", indent, self.compile_condition(condition)?);
+        Ok(output + &self.compile_block(action.body_actions.as_deref().unwrap_or_default(), "}")?)
+    }
+
+    /// An inclusive range like Ruby's `(a..b).step(s)`, as a while loop so
+    /// bounds and step can be any numeric `Value`
+    fn compile_for(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let loop_var = action.loop_var.as_ref()
+            .ok_or_else(|| UclError::missing("For", "variable"))?;
+        let from_expr = action.from_expr.as_ref()
+            .ok_or_else(|| UclError::missing("For", "from expression"))?;
+        let to_expr = action.to_expr.as_ref()
+            .ok_or_else(|| UclError::missing("For", "to expression"))?;
+
+        let step = match &action.step_expr {
+            Some(step) => self.compile_expression(step)?,
+            None => "Value::Int(1)".to_string(),
+        };
+
+        let mut output = format!("{}let mut {} = {};\n", indent, loop_var, self.compile_expression(from_expr)?);
+        output.push_str(&format!("{}let {}_end = {};\n", indent, loop_var, self.compile_expression(to_expr)?));
+        output.push_str(&format!("{}while {} <= {}_end {{\n", indent, loop_var, loop_var));
+        let body = self.compile_block(action.body_actions.as_deref().unwrap_or_default(), "")?;
+        let inner = "  ".repeat(self.indent_level + 1);
+        output.push_str(body.trim_end());
+        output.push_str(&format!("\n{}{} = {}.clone() + {};\n", inner, loop_var, loop_var, step));
+        output.push_str(&format!("{}}}", indent));
+
+        Ok(output)
+    }
+
+    fn compile_define_function(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let func_name = &action.target;
+
+        let params = action.params.as_ref()
+            .ok_or_else(|| UclError::missing("DefineFunction", "params"))?;
+
+        let args = params.get("args")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| UclError::missing("DefineFunction", "args array"))?;
+
+        let arg_names: Vec<String> = args.iter()
+            .filter_map(|v| v.as_str())
+            .map(|s| s.to_string())
+            .collect();
+
+        let body_value = params.get("body")
+            .ok_or_else(|| UclError::missing("DefineFunction", "body"))?;
+        let body_actions: Vec<Action> = serde_json::from_value(body_value.clone())?;
+
+        let outer = std::mem::replace(&mut self.in_function, true);
+        let signature: Vec<String> = arg_names.iter().map(|a| format!("mut {}: Value", a)).collect();
+        let mut output = format!("{}fn {}({}) -> Value {{\n", indent, func_name, signature.join(", "));
+        self.indent_level += 1;
+        output.push_str(&self.declarations(&body_actions, &arg_names));
+        let inner = "  ".repeat(self.indent_level);
+        self.indent_level -= 1;
+        let body = self.compile_block(&body_actions, "");
+        self.in_function = outer;
+        output.push_str(body?.trim_end());
+        // Falling off the end returns nil, as in Ruby
+        output.push_str(&format!("\n{}Value::Null\n{}}}", inner, indent));
+
+        Ok(output)
+    }
+
+    fn compile_condition(&self, condition: &Condition) -> Result<String> {
+        match condition {
+            Condition::Comparison { op, left, right } => {
+                let left_val = self.compile_expression(left)?;
+                let right_val = self.compile_expression(right)?;
+                let op_str = match op {
+                    ComparisonOp::Equal => "==",
+                    ComparisonOp::NotEqual => "!=",
+                    ComparisonOp::LessThan => "<",
+                    ComparisonOp::LessThanOrEqual => "<=",
+                    ComparisonOp::GreaterThan => ">",
+                    ComparisonOp::GreaterThanOrEqual => ">=",
+                };
+                Ok(format!("{} {} {}", left_val, op_str, right_val))
+            }
+            Condition::And { operands } => {
+                let parts: Result<Vec<String>> = operands.iter()
+                    .map(|c| self.compile_condition(c))
+                    .collect();
+                Ok(format!("({})", parts?.join(" && ")))
+            }
+            Condition::Or { operands } => {
+                let parts: Result<Vec<String>> = operands.iter()
+                    .map(|c| self.compile_condition(c))
+                    .collect();
+                Ok(format!("({})", parts?.join(" || ")))
+            }
+            Condition::Not { operand } => {
+                Ok(format!("!({})", self.compile_condition(operand)?))
+            }
+            // Only false and nil are false, as in Ruby
+            Condition::Expression { value } => Ok(format!("!matches!({}, Value::Null | Value::Bool(false))", self.compile_expression(value)?)),
+            Condition::StartsWith { value, prefix } => {
+                Ok(format!("{}.to_string().starts_with(&{}.to_string())", self.compile_expression(value)?, self.compile_expression(prefix)?))
+            }
+            Condition::Contains { value, part } => Ok(format!(
+                "match ({}, {}) {{ (Value::List(items), part) => items.contains(&part), (value, part) => value.to_string().contains(&part.to_string()) }}",
+                self.compile_expression(value)?, self.compile_expression(part)?,
+            )),
+            Condition::Matches { .. } => Err(UclError::unsupported("pattern matching", "rust").into()),
+            Condition::Exists { .. } => Err(UclError::unsupported("exists conditions", "rust").into()),
+        }
+    }
+
+    fn compile_expression(&self, expr: &Expression) -> Result<String> {
+        match expr {
+            Expression::Value(v) => Ok(self.value_to_rust(v)),
+            Expression::Variable { var } => Ok(format!("{}.clone()", var)),
+            Expression::BinaryOp { expr: bin_op } => {
+                let left_val = self.compile_expression(&bin_op.left)?;
+                let right_val = self.compile_expression(&bin_op.right)?;
+                binary(&bin_op.op, &left_val, &right_val)
+            }
+            Expression::FunctionCall { call, args } => {
+                let arg_names = self.functions.get(call)
+                    .ok_or_else(|| anyhow!("Call to undefined function '{}'", call))?;
+                let arg_strs: Result<Vec<String>> = arg_names.iter()
+                    .map(|name| match args.get(name) {
+                        Some(arg) => self.compile_expression(arg),
+                        None => Ok("Value::Null".to_string()),
+                    })
+                    .collect();
+                Ok(format!("{}({})", call, arg_strs?.join(", ")))
+            }
+            Expression::List { list } => {
+                let items: Result<Vec<String>> = list.iter().map(|item| self.compile_expression(item)).collect();
+                Ok(format!("Value::List(vec![{}])", items?.join(", ")))
+            }
+            Expression::Index { .. } => Err(UclError::unsupported("list indexing", "rust").into()),
+            Expression::Length { .. } => Err(UclError::unsupported("list length", "rust").into()),
+            Expression::StringOp { .. } | Expression::Format { .. } => Err(UclError::unsupported("string functions", "rust").into()),
+            Expression::Unary { unary } => match unary.op.as_str() {
+                "-" => Ok(format!("(Value::Int(0) - {})", self.compile_expression(&unary.operand)?)),
+                "!" => Ok(format!("Value::Bool(matches!({}, Value::Null | Value::Bool(false)))", self.compile_expression(&unary.operand)?)),
+                op => Err(UclError::unsupported(format!("unary operator '{}'", op), "rust").into()),
+            },
+            Expression::Test { test } => Ok(format!("Value::Bool({})", self.compile_condition(test)?)),
+        }
+    }
+
+    /// A param that may hold an expression (`{"var": "x"}`) or a plain value
+    fn value_or_expression(&self, value: &serde_json::Value) -> Result<String> {
+        match serde_json::from_value::<Expression>(value.clone()) {
+            Ok(expr) => self.compile_expression(&expr),
+            Err(_) => Ok(self.value_to_rust(value)),
+        }
+    }
+
+    fn value_to_rust(&self, value: &serde_json::Value) -> String {
+        match value {
+            serde_json::Value::Null => "Value::Null".to_string(),
+            serde_json::Value::Bool(b) => format!("Value::Bool({})", b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => format!("Value::Int({})", i),
+                None => format!("Value::Float({:?})", n.as_f64().unwrap_or(0.0)),
+            },
+            serde_json::Value::String(s) => format!("Value::Str(String::from({:?}))", s),
+            serde_json::Value::Array(arr) => {
+                let elements: Vec<String> = arr.iter()
+                    .map(|v| self.value_to_rust(v))
+                    .collect();
+                format!("Value::List(vec![{}])", elements.join(", "))
+            }
+            // Objects have no Value variant; keep them as their JSON text
+            serde_json::Value::Object(_) => format!("Value::Str(String::from({:?}))", value.to_string()),
+        }
+    }
+}
+
+impl Default for RustCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn binary(op: &str, left: &str, right: &str) -> Result<String> {
+    match op {
+        "+" | "-" | "*" | "/" | "%" => Ok(format!("({} {} {})", left, op, right)),
+        "**" => Ok(format!("pow({}, {})", left, right)),
+        _ => Err(UclError::unsupported(format!("binary operator '{}'", op), "rust").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_expression() {
+        let compiler = RustCompiler::new();
+        let expr: Expression = serde_json::from_str(r#"{"expr": {"op": "**", "left": {"var": "x"}, "right": 2}}"#).unwrap();
+        assert_eq!(compiler.compile_expression(&expr).unwrap(), "pow(x.clone(), Value::Int(2))");
+
+        let expr: Expression = serde_json::from_str(r#"{"expr": {"op": "&", "left": 1, "right": 2}}"#).unwrap();
+        assert!(compiler.compile_expression(&expr).is_err());
+    }
+
+    #[test]
+    fn test_compile_fibonacci() {
+        let program = Program::from_json(&std::fs::read_to_string("examples/fibonacci.json").unwrap()).unwrap();
+        let code = RustCompiler::new().compile(&program).unwrap();
+
+        assert!(code.contains("  fn fibonacci(mut n: Value) -> Value {\n    if n.clone() <= Value::Int(1) {\n      return n.clone();"), "{}", code);
+        assert!(code.contains("    }\n    Value::Null\n  }\n"), "{}", code);
+        assert!(code.contains("  let mut result = Value::Null;\n"), "{}", code);
+        assert!(code.contains("  while i <= i_end {\n    result = fibonacci(i.clone());\n    println!(\"{}\", result.clone());\n    i = i.clone() + Value::Int(1);\n  }"), "{}", code);
+        assert!(!code.contains("fn random_int"));
+    }
+
+    #[test]
+    fn test_call_stores_result_in_out() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Call", "target": "double", "params": {"n": 21, "out": "res"}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "res"}}}
+        ]}"#).unwrap();
+        let code = RustCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("  let mut res = Value::Null;\n"), "{}", code);
+        assert!(code.contains("  res = double(Value::Int(21));\n"), "{}", code);
+    }
+
+    #[test]
+    fn test_seed_fixes_random_state() {
+        let program = Program::from_json(r#"{"actions": [{"actor": "VM", "op": "GenRandomInt", "target": "n", "params": {"min": 1, "max": 6}}]}"#).unwrap();
+        let seeded = RustCompiler::new().with_seed(42).compile(&program).unwrap();
+        assert!(!seeded.contains("AtomicU64::new(0)"), "{}", seeded);
+        assert_eq!(seeded, RustCompiler::new().with_seed(42).compile(&program).unwrap());
+        assert!(RustCompiler::new().compile(&program).unwrap().contains("AtomicU64::new(0)"));
+    }
+}
@@ -0,0 +1,527 @@
+use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression};
+use anyhow::{anyhow, bail, Result};
+use crate::error::UclError;
+use std::collections::HashMap;
+
+/// Compiles UCL to a portable POSIX shell script (runs under `sh`, `dash`
+/// and `bash`)
+///
+/// Arithmetic is integer-only `$(( ))`. Functions return their value on
+/// stdout and are always called inside `$( )`, so their variables can't leak
+/// into the caller; `Emit` writes to file descriptor 3, a copy of the
+/// script's stdout, so output inside a function isn't mistaken for its
+/// return value.
+pub struct ShellCompiler {
+    indent_level: usize,
+    /// Argument names of every function the program defines, for ordering
+    /// the named arguments of calls
+    functions: HashMap<String, Vec<String>>,
+    /// Whether a function body is being compiled, rather than the script
+    in_function: bool,
+    interrupt_handlers: usize,
+    seed: Option<u64>,
+}
+
+impl ShellCompiler {
+    pub fn new() -> Self {
+        Self {
+            indent_level: 0,
+            functions: HashMap::new(),
+            in_function: false,
+            interrupt_handlers: 0,
+            seed: None,
+        }
+    }
+
+    /// Seed each draw's awk from `UCL_SEED`, which starts at `seed` and goes
+    /// up by one per draw, so runs repeat; draws inside functions run in a
+    /// subshell and don't advance it for the caller
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<String> {
+        let mut output = String::new();
+
+        output.push_str("#!/bin/sh\n");
+        output.push_str("# Generated from UCL\n");
+        output.push_str("# Universal Causal Language -> POSIX shell Compiler\n\n");
+        output.push_str("exec 3>&1\n\n");
+        if let Some(seed) = self.seed {
+            // awk's srand takes a 32-bit seed on some systems
+            output.push_str(&format!("UCL_SEED={}\n\n", seed % 2147483647));
+        }
+
+        crate::analysis::walk_with_depth(&program.actions, 0, &mut |action, _| {
+            if action.op == Operation::DefineFunction {
+                if let Some(args) = action.params.as_ref().and_then(|p| p.get("args")).and_then(|a| a.as_array()) {
+                    let args = args.iter().filter_map(|a| a.as_str()).map(str::to_string).collect();
+                    self.functions.insert(action.target.clone(), args);
+                }
+            }
+        });
+
+        for action in &program.actions {
+            let code = self.compile_action(action)?;
+            if !code.is_empty() {
+                output.push_str(&code);
+                output.push('\n');
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn compile_action(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+
+        match &action.op {
+            Operation::Call => self.compile_call(action, &indent),
+            Operation::Assign | Operation::Bind => self.compile_bind(action, &indent),
+            Operation::Write => self.compile_write(action, &indent),
+            Operation::Emit => self.compile_emit(action, &indent),
+            Operation::Return => self.compile_return(action, &indent),
+            Operation::Halt => self.compile_halt(action, &indent),
+            Operation::Wait => self.compile_wait(action, &indent),
+            Operation::GenRandomInt => self.compile_gen_random_int(action, &indent),
+            Operation::If => self.compile_if(action),
+            Operation::While => self.compile_while(action),
+            Operation::For => self.compile_for(action),
+            Operation::DefineFunction => self.compile_define_function(action),
+            Operation::OnInterrupt => self.compile_on_interrupt(action),
+            _ => {
+                // For unsupported operations, generate a comment
+                Ok(format!("{}# Unsupported operation: {:?} on {}",
+                    indent, action.op, action.target))
+            }
+        }
+    }
+
+    fn compile_call(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let Some(arg_names) = self.functions.get(&action.target).cloned() else {
+            return Ok(format!("{}# Unsupported call: {}", indent, action.target));
+        };
+
+        let mut words = vec![action.target.clone()];
+        for name in &arg_names {
+            match action.params.as_ref().and_then(|p| p.get(name)) {
+                Some(value) => words.push(self.value_or_expression(value)?),
+                None => words.push("''".to_string()),
+            }
+        }
+
+        // The subshell keeps the function's variables local
+        match action.params.as_ref().and_then(|p| p.get("out")).and_then(|v| v.as_str()) {
+            Some(out) => Ok(format!("{}{}=\"$({})\"", indent, variable(out)?, words.join(" "))),
+            None => Ok(format!("{}( {} ) >/dev/null", indent, words.join(" "))),
+        }
+    }
+
+    fn compile_bind(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let value = action.params
+            .as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing(format!("{:?}", action.op), "'value' parameter"))?;
+
+        Ok(format!("{}{}={}", indent, variable(&action.target)?, self.value_or_expression(value)?))
+    }
+
+    fn compile_write(&mut self, action: &Action, indent: &str) -> Result<String> {
+        if let Some(params) = &action.params {
+            if let Some(op) = params.get("operation") {
+                let operator = match op.as_str().unwrap_or("") {
+                    "multiply" => "*",
+                    "add" => "+",
+                    "subtract" => "-",
+                    "divide" => "/",
+                    _ => "*",
+                };
+
+                let operand = |register: &str, literal: &str| -> Result<String> {
+                    match (params.get(register).and_then(|r| r.as_str()), params.get(literal)) {
+                        (Some(register), _) => Ok(variable(register)?.to_string()),
+                        (None, Some(value)) => arithmetic_literal(value),
+                        (None, None) => Err(UclError::missing("Write", format!("{} or {}", register, literal)).into()),
+                    }
+                };
+                let lhs = operand("lhs_register", "lhs")?;
+                let rhs = operand("rhs_register", "rhs")?;
+
+                return Ok(format!("{}{}=$(({} {} {}))", indent, variable(&action.target)?, lhs, operator, rhs));
+            }
+
+            if let Some(value) = params.get("value") {
+                return Ok(format!("{}{}={}", indent, variable(&action.target)?, self.value_or_expression(value)?));
+            }
+        }
+
+        Err(UclError::missing("Write", "'value' parameter or operation").into())
+    }
+
+    fn compile_emit(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let params = action.params.as_ref();
+        let word = match params.and_then(|p| p.get("content")).or_else(|| params.and_then(|p| p.get("message"))) {
+            Some(content) if content.as_str() != Some(&action.target) => self.value_or_expression(content)?,
+            // No content (or content naming the target): print the target variable
+            _ => format!("\"${}\"", variable(&action.target)?),
+        };
+
+        Ok(format!("{}printf '%s\\n' {} >&3", indent, word))
+    }
+
+    fn compile_return(&mut self, action: &Action, indent: &str) -> Result<String> {
+        // Returning from the top level ends the script, as in Ruby
+        if !self.in_function {
+            return Ok(format!("{}exit 0", indent));
+        }
+
+        let word = match action.params.as_ref().and_then(|p| p.get("value")) {
+            Some(value) => self.value_or_expression(value)?,
+            None => format!("\"${}\"", variable(&action.target)?),
+        };
+        Ok(format!("{}printf '%s\\n' {}\n{}return", indent, word, indent))
+    }
+
+    fn compile_halt(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let status = match action.params.as_ref().and_then(|p| p.get("status")) {
+            Some(status) => self.value_or_expression(status)?,
+            None => "0".to_string(),
+        };
+        Ok(format!("{}exit {}", indent, status))
+    }
+
+    fn compile_wait(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let duration = action.dur
+            .or_else(|| {
+                action.params.as_ref()
+                    .and_then(|p| p.get("duration"))
+                    .and_then(|v| v.as_f64())
+            })
+            .unwrap_or(1.0);
+
+        Ok(format!("{}sleep {}", indent, duration))
+    }
+
+    fn compile_gen_random_int(&mut self, action: &Action, indent: &str) -> Result<String> {
+        let bound = |key: &str, default: i64| action.params.as_ref()
+            .and_then(|p| p.get(key))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(default);
+        let (min, max) = (bound("min", 0), bound("max", 9));
+
+        if self.seed.is_some() {
+            return Ok(format!("{}{}=$(awk -v seed=\"$UCL_SEED\" 'BEGIN {{ srand(seed); print int({} + rand() * {}) }}')\n{}UCL_SEED=$((UCL_SEED + 1))",
+                indent, variable(&action.target)?, min, max - min + 1, indent));
+        }
+        // POSIX sh has no $RANDOM; seed awk with the PID too, so two draws in
+        // the same second differ
+        Ok(format!("{}{}=$(awk -v seed=\"$$$(date +%N 2>/dev/null)\" 'BEGIN {{ srand(seed % 2147483647); print int({} + rand() * {}) }}')",
+            indent, variable(&action.target)?, min, max - min + 1))
+    }
+
+    /// Compile `actions` one level deeper; a block without commands gets
+    /// `:` since the shell doesn't allow empty bodies
+    fn compile_block(&mut self, actions: &[Action]) -> Result<String> {
+        let mut output = String::new();
+
+        self.indent_level += 1;
+        for action in actions {
+            let code = self.compile_action(action)?;
+            if !code.is_empty() {
+                output.push_str(&code);
+                output.push('\n');
+            }
+        }
+        if output.lines().all(|line| line.trim_start().starts_with('#')) {
+            output.push_str(&format!("{}:\n", "  ".repeat(self.indent_level)));
+        }
+        self.indent_level -= 1;
+
+        Ok(output)
+    }
+
+    fn compile_if(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let condition = action.condition.as_ref()
+            .ok_or_else(|| UclError::missing("If", "condition"))?;
+
+        let mut output = format!("{}if {}; then\n", indent, self.compile_condition(condition)?);
+        output.push_str(&self.compile_block(action.then_actions.as_deref().unwrap_or_default())?);
+        if let Some(else_actions) = &action.else_actions {
+            output.push_str(&format!("{}else\n", indent));
+            output.push_str(&self.compile_block(else_actions)?);
+        }
+        output.push_str(&format!("{}fi", indent));
+        Ok(output)
+    }
+
+    fn compile_while(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let condition = action.condition.as_ref()
+            .ok_or_else(|| UclError::missing("While", "condition"))?;
+
+        let mut output = format!("{}while {}; do\n", indent, self.compile_condition(condition)?);
+        output.push_str(&self.compile_block(action.body_actions.as_deref().unwrap_or_default())?);
+        output.push_str(&format!("{}done", indent));
+        Ok(output)
+    }
+
+    /// An inclusive range, as a while loop with the bound evaluated once
+    fn compile_for(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let loop_var = variable(action.loop_var.as_ref()
+            .ok_or_else(|| UclError::missing("For", "variable"))?)?;
+        let from_expr = action.from_expr.as_ref()
+            .ok_or_else(|| UclError::missing("For", "from expression"))?;
+        let to_expr = action.to_expr.as_ref()
+            .ok_or_else(|| UclError::missing("For", "to expression"))?;
+        let step = match &action.step_expr {
+            Some(step) => self.arithmetic(step)?,
+            None => "1".to_string(),
+        };
+
+        let end = format!("{}_end", loop_var);
+        let mut output = format!("{}{}={}\n", indent, loop_var, self.compile_expression(from_expr)?);
+        output.push_str(&format!("{}{}={}\n", indent, end, self.compile_expression(to_expr)?));
+        output.push_str(&format!("{}while [ \"${}\" -le \"${}\" ]; do\n", indent, loop_var, end));
+        output.push_str(&self.compile_block(action.body_actions.as_deref().unwrap_or_default())?);
+        output.push_str(&format!("{}  {}=$(({} + {}))\n", indent, loop_var, loop_var, step));
+        output.push_str(&format!("{}done", indent));
+        Ok(output)
+    }
+
+    fn compile_define_function(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        let func_name = variable(&action.target)?;
+
+        let params = action.params.as_ref()
+            .ok_or_else(|| UclError::missing("DefineFunction", "params"))?;
+        let arg_names = self.functions.get(func_name).cloned()
+            .ok_or_else(|| UclError::missing("DefineFunction", "args array"))?;
+        let body_value = params.get("body")
+            .ok_or_else(|| UclError::missing("DefineFunction", "body"))?;
+        let body_actions: Vec<Action> = serde_json::from_value(body_value.clone())?;
+
+        let mut output = format!("{}{}() {{\n", indent, func_name);
+        for (i, arg) in arg_names.iter().enumerate() {
+            output.push_str(&format!("{}  {}=${}\n", indent, variable(arg)?, i + 1));
+        }
+
+        let outer = std::mem::replace(&mut self.in_function, true);
+        let body = self.compile_block(&body_actions);
+        self.in_function = outer;
+        output.push_str(&body?);
+        output.push_str(&format!("{}}}", indent));
+        Ok(output)
+    }
+
+    /// Runs the body if the script is interrupted or terminated
+    fn compile_on_interrupt(&mut self, action: &Action) -> Result<String> {
+        let indent = "  ".repeat(self.indent_level);
+        self.interrupt_handlers += 1;
+        let handler = format!("ucl_on_interrupt_{}", self.interrupt_handlers);
+
+        let mut output = format!("{}{}() {{\n", indent, handler);
+        output.push_str(&self.compile_block(action.body_actions.as_deref().unwrap_or_default())?);
+        output.push_str(&format!("{}}}\n", indent));
+        output.push_str(&format!("{}trap '{}; exit 130' INT TERM", indent, handler));
+        Ok(output)
+    }
+
+    /// A command that succeeds when `condition` holds
+    fn compile_condition(&self, condition: &Condition) -> Result<String> {
+        match condition {
+            Condition::Comparison { op, left, right } => {
+                let textual = [left, right].iter().any(|e| matches!(e, Expression::Value(v) if !v.is_number()));
+                let op_str = match (op, textual) {
+                    (ComparisonOp::Equal, true) => "=",
+                    (ComparisonOp::NotEqual, true) => "!=",
+                    (_, true) => bail!("The bash target can only compare strings for equality"),
+                    (ComparisonOp::Equal, false) => "-eq",
+                    (ComparisonOp::NotEqual, false) => "-ne",
+                    (ComparisonOp::LessThan, false) => "-lt",
+                    (ComparisonOp::LessThanOrEqual, false) => "-le",
+                    (ComparisonOp::GreaterThan, false) => "-gt",
+                    (ComparisonOp::GreaterThanOrEqual, false) => "-ge",
+                };
+                Ok(format!("[ {} {} {} ]", self.compile_expression(left)?, op_str, self.compile_expression(right)?))
+            }
+            Condition::And { operands } | Condition::Or { operands } => {
+                let (joiner, empty) = match condition {
+                    Condition::And { .. } => (" && ", "true"),
+                    _ => (" || ", "false"),
+                };
+                if operands.is_empty() {
+                    return Ok(empty.to_string());
+                }
+                let parts: Result<Vec<String>> = operands.iter()
+                    .map(|c| self.compile_condition(c))
+                    .collect();
+                Ok(format!("{{ {}; }}", parts?.join(joiner)))
+            }
+            Condition::Not { operand } => {
+                Ok(format!("! {}", self.compile_condition(operand)?))
+            }
+            Condition::Expression { .. } => Err(UclError::unsupported("truth values", "bash").into()),
+            Condition::Matches { .. } | Condition::StartsWith { .. } | Condition::Contains { .. } => {
+                Err(UclError::unsupported("text conditions", "bash").into())
+            }
+            Condition::Exists { var } => Ok(format!("[ -n \"${{{}+set}}\" ]", variable(var)?)),
+        }
+    }
+
+    /// A single shell word with the expression's value
+    fn compile_expression(&self, expr: &Expression) -> Result<String> {
+        match expr {
+            Expression::Value(v) => Ok(word(v)),
+            Expression::Variable { var } => Ok(format!("\"${}\"", variable(var)?)),
+            Expression::BinaryOp { .. } => {
+                let arithmetic = self.arithmetic(expr)?;
+                let inner = arithmetic.strip_prefix('(').and_then(|a| a.strip_suffix(')')).unwrap_or(&arithmetic);
+                Ok(format!("$(({}))", inner))
+            }
+            Expression::FunctionCall { .. } => Ok(format!("\"{}\"", self.call(expr)?)),
+            Expression::List { .. } | Expression::Index { .. } | Expression::Length { .. } => {
+                Err(UclError::unsupported("lists", "bash").into())
+            }
+            Expression::StringOp { .. } | Expression::Format { .. } => {
+                Err(UclError::unsupported("string functions", "bash").into())
+            }
+            Expression::Unary { unary } if unary.op == "-" => Ok(format!("$(({}))", self.arithmetic(expr)?)),
+            Expression::Unary { .. } | Expression::Test { .. } => Err(UclError::unsupported("truth values", "bash").into()),
+        }
+    }
+
+    /// The expression inside `$(( ))`
+    fn arithmetic(&self, expr: &Expression) -> Result<String> {
+        match expr {
+            Expression::Value(v) => arithmetic_literal(v),
+            // Bare names, so unset variables count as 0
+            Expression::Variable { var } => Ok(variable(var)?.to_string()),
+            Expression::BinaryOp { expr: bin_op } => {
+                if !["+", "-", "*", "/", "%"].contains(&bin_op.op.as_str()) {
+                    return Err(UclError::unsupported(format!("binary operator '{}'", bin_op.op), "bash").into());
+                }
+                Ok(format!("({} {} {})", self.arithmetic(&bin_op.left)?, bin_op.op, self.arithmetic(&bin_op.right)?))
+            }
+            Expression::FunctionCall { .. } => self.call(expr),
+            Expression::List { .. } | Expression::Index { .. } | Expression::Length { .. } => {
+                Err(UclError::unsupported("lists", "bash").into())
+            }
+            Expression::StringOp { .. } | Expression::Format { .. } => {
+                Err(UclError::unsupported("string functions", "bash").into())
+            }
+            Expression::Unary { unary } if unary.op == "-" => Ok(format!("(-{})", self.arithmetic(&unary.operand)?)),
+            Expression::Unary { .. } | Expression::Test { .. } => Err(UclError::unsupported("truth values", "bash").into()),
+        }
+    }
+
+    /// `$(name "arg" ...)`, arguments in the order the function declares them
+    fn call(&self, expr: &Expression) -> Result<String> {
+        let Expression::FunctionCall { call, args } = expr else {
+            unreachable!("only called with function calls");
+        };
+        let params = self.functions.get(call)
+            .ok_or_else(|| anyhow!("Call to undefined function '{}'", call))?;
+
+        let mut words = vec![call.clone()];
+        for param in params {
+            match args.get(param) {
+                Some(arg) => words.push(self.compile_expression(arg)?),
+                None => words.push("''".to_string()),
+            }
+        }
+        Ok(format!("$({})", words.join(" ")))
+    }
+
+    /// A param that may hold an expression (`{"var": "x"}`) or a plain value
+    fn value_or_expression(&self, value: &serde_json::Value) -> Result<String> {
+        match serde_json::from_value::<Expression>(value.clone()) {
+            Ok(expr) => self.compile_expression(&expr),
+            Err(_) => Ok(word(value)),
+        }
+    }
+}
+
+impl Default for ShellCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `name` if it's a valid shell variable name
+fn variable(name: &str) -> Result<&str> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!("'{}' is not a valid shell variable name", name);
+    }
+    Ok(name)
+}
+
+fn arithmetic_literal(value: &serde_json::Value) -> Result<String> {
+    match value {
+        serde_json::Value::Number(n) if n.is_i64() => Ok(n.to_string()),
+        serde_json::Value::Bool(b) => Ok(if *b { "1" } else { "0" }.to_string()),
+        other => bail!("The bash target only supports integer arithmetic, got {}", other),
+    }
+}
+
+/// A value as a single-quoted shell word
+fn word(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || "-_.,:/+=@%".contains(c)) {
+        return text;
+    }
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_quoting() {
+        assert_eq!(word(&serde_json::json!(42)), "42");
+        assert_eq!(word(&serde_json::json!("it's done")), "'it'\\''s done'");
+        assert_eq!(word(&serde_json::json!(null)), "''");
+    }
+
+    #[test]
+    fn test_compile_fibonacci() {
+        let program = Program::from_json(&std::fs::read_to_string("examples/fibonacci.json").unwrap()).unwrap();
+        let code = ShellCompiler::new().compile(&program).unwrap();
+
+        assert!(code.contains("fibonacci() {\n  n=$1\n  if [ \"$n\" -le 1 ]; then\n    printf '%s\\n' \"$n\"\n    return\n"), "{}", code);
+        assert!(code.contains("printf '%s\\n' $(($(fibonacci $((n - 1))) + $(fibonacci $((n - 2)))))"), "{}", code);
+        assert!(code.contains("while [ \"$i\" -le \"$i_end\" ]; do\n  result=\"$(fibonacci \"$i\")\"\n"), "{}", code);
+    }
+
+    #[test]
+    fn test_call_stores_result_in_out() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Call", "target": "double", "params": {"n": 21, "out": "res"}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "res"}}}
+        ]}"#).unwrap();
+        let code = ShellCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("res=\"$(double 21)\"\n"), "{}", code);
+    }
+
+    #[test]
+    fn test_rejects_float_arithmetic() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "a", "op": "Bind", "target": "x", "params": {"value": {"expr": {"op": "*", "left": 1.5, "right": 2}}}}
+        ]}"#).unwrap();
+
+        let err = ShellCompiler::new().compile(&program).unwrap_err();
+        assert!(err.to_string().contains("only supports integer arithmetic"));
+    }
+}
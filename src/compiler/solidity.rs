@@ -0,0 +1,336 @@
+use crate::{Action, Operation, Program};
+use crate::error::UclError;
+use anyhow::Result;
+use chrono::NaiveDate;
+
+/// Everything but the constructor: the contract's types, state-changing
+/// functions and breach handling, shared by every generated contract
+const CONTRACT_BODY: &str = r#"
+    /// Record that an obligation's precondition holds, starting its
+    /// deadline if that is relative (e.g. `Delivery+5d`)
+    function confirmPrecondition(uint256 id) external onlyParty {
+        Obligation storage o = obligations[id];
+        require(!o.preconditionMet, "precondition already confirmed");
+        o.preconditionMet = true;
+        if (o.grace > 0 && o.deadline == 0) {
+            o.deadline = block.timestamp + o.grace;
+        }
+        emit PreconditionMet(id);
+    }
+
+    /// Confirm an obligation was performed
+    function fulfill(uint256 id) external onlyParty {
+        Obligation storage o = obligations[id];
+        require(o.status == Status.Pending, "obligation is not pending");
+        require(bytes(o.precondition).length == 0 || o.preconditionMet, "precondition not met");
+        require(msg.sender != o.party || parties.length == 1, "obligor cannot confirm their own performance");
+        o.status = Status.Fulfilled;
+        emit Fulfilled(id);
+    }
+
+    /// Declare a missed deadline; every remedy the breaching party owes
+    /// (or that anyone owes) becomes due
+    function declareBreach(uint256 id) external onlyParty {
+        Obligation storage o = obligations[id];
+        require(o.status == Status.Pending, "obligation is not pending");
+        require(o.deadline != 0 && block.timestamp > o.deadline, "deadline has not passed");
+        o.status = Status.Breached;
+        emit Breached(id, o.party);
+        for (uint256 r = 0; r < remedies.length; r++) {
+            if (remedies[r].owedBy == o.party || remedies[r].owedBy == address(0)) {
+                emit RemedyOwed(id, r);
+            }
+        }
+    }
+
+    /// Exercise a right before it expires
+    function exercise(uint256 id) external {
+        Permission storage p = permissions[id];
+        require(msg.sender == p.party, "not permitted");
+        require(p.expires == 0 || block.timestamp <= p.expires, "permission has expired");
+        emit PermissionExercised(id, msg.sender);
+    }
+
+    function isParty(address account) public view returns (bool) {
+        for (uint256 i = 0; i < parties.length; i++) {
+            if (parties[i] == account) {
+                return true;
+            }
+        }
+        return false;
+    }
+}
+"#;
+
+/// Compiles the legal clauses of a program into a Solidity contract skeleton
+///
+/// The program must tag at least one action with the `Legal` effect. Each
+/// party (actor or target of a clause) becomes a constructor argument and
+/// `address`. `Oblige` becomes an obligation whose `by` param (a date, or a
+/// period after its precondition such as `Delivery+5d`) or start time `t`
+/// sets its deadline; `Permit` becomes a right that expires after its
+/// `window`/`duration`; `Remedy`, and `Oblige` with `duty: "Remedy"`, become
+/// remedies that fall due when an obligation is breached. Anything else is
+/// left as a comment, since it can't be enforced on-chain.
+pub struct SolidityCompiler {
+    parties: Vec<String>,
+}
+
+impl SolidityCompiler {
+    pub fn new() -> Self {
+        Self { parties: Vec::new() }
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<String> {
+        let legal = |action: &Action| action.effects.iter().flatten().any(|e| e.eq_ignore_ascii_case("legal"));
+        if !program.actions.iter().any(legal) {
+            return Err(UclError::unsupported("programs without clauses tagged with the Legal effect", "solidity").into());
+        }
+
+        self.parties.clear();
+        for action in &program.actions {
+            if matches!(action.op, Operation::Oblige | Operation::Permit | Operation::Remedy) {
+                for name in [&action.actor, &action.target] {
+                    if !self.parties.contains(name) {
+                        self.parties.push(name.clone());
+                    }
+                }
+            }
+        }
+
+        let meta = program.meta();
+        let title = ["contract_type", "name"].iter()
+            .find_map(|key| meta.get(key).and_then(|v| v.as_str()))
+            .unwrap_or("UCL Contract");
+
+        let mut output = String::new();
+        output.push_str("// SPDX-License-Identifier: UNLICENSED\n");
+        output.push_str("// Generated from UCL\n");
+        output.push_str("// Universal Causal Language -> Solidity Compiler\n");
+        output.push_str("pragma solidity ^0.8.20;\n\n");
+        output.push_str(&format!("/// @title {}\n", title));
+        output.push_str(&format!("contract {} {{\n", contract_name(title)));
+        output.push_str(r#"    enum Status { Pending, Fulfilled, Breached }
+
+    struct Obligation {
+        address party;
+        string duty;
+        string terms;
+        string precondition;
+        bool preconditionMet;
+        // Unix time, 0 until known
+        uint256 deadline;
+        // Seconds after the precondition is met, for relative deadlines
+        uint256 grace;
+        Status status;
+    }
+
+    struct Permission {
+        address party;
+        string right;
+        string terms;
+        // Unix time, 0 for never
+        uint256 expires;
+    }
+
+    struct Remedy {
+        // address(0) when owed by whichever party is in breach
+        address owedBy;
+        string remedy;
+        string condition;
+    }
+
+"#);
+
+        for party in &self.parties {
+            output.push_str(&format!("    address public immutable {};\n", party_ident(party)));
+        }
+        output.push_str("    address[] public parties;\n\n");
+        output.push_str("    Obligation[] public obligations;\n    Permission[] public permissions;\n    Remedy[] public remedies;\n\n");
+        output.push_str("    event PreconditionMet(uint256 indexed obligation);\n");
+        output.push_str("    event Fulfilled(uint256 indexed obligation);\n");
+        output.push_str("    event Breached(uint256 indexed obligation, address indexed party);\n");
+        output.push_str("    event RemedyOwed(uint256 indexed obligation, uint256 indexed remedy);\n");
+        output.push_str("    event PermissionExercised(uint256 indexed permission, address indexed party);\n\n");
+        output.push_str("    modifier onlyParty() {\n        require(isParty(msg.sender), \"not a party\");\n        _;\n    }\n\n");
+
+        let args: Vec<String> = self.parties.iter().map(|p| format!("address _{}", party_ident(p))).collect();
+        output.push_str(&format!("    constructor({}) {{\n", args.join(", ")));
+        for party in &self.parties {
+            let ident = party_ident(party);
+            output.push_str(&format!("        {} = _{};\n        parties.push(_{});\n", ident, ident, ident));
+        }
+        for action in &program.actions {
+            output.push('\n');
+            output.push_str(&self.compile_clause(action));
+        }
+        output.push_str("    }\n");
+        output.push_str(CONTRACT_BODY);
+
+        Ok(output)
+    }
+
+    fn compile_clause(&self, action: &Action) -> String {
+        let params = action.params.as_ref();
+        let param = |key: &str| params.and_then(|p| p.get(key));
+        let text = |key: &str| param(key).map(|v| v.as_str().map(str::to_string).unwrap_or_else(|| v.to_string()));
+        let party = |name: &str| format!("_{}", party_ident(name));
+        let condition = action.pre.clone().or_else(|| text("condition")).unwrap_or_default();
+
+        match action.op {
+            Operation::Oblige if text("duty").as_deref() == Some("Remedy") => {
+                let remedy = match param("actions").and_then(|v| v.as_array()) {
+                    Some(actions) => actions.iter().map(|a| a.as_str().map(str::to_string).unwrap_or_else(|| a.to_string())).collect::<Vec<_>>().join(" or "),
+                    None => "Remedy".to_string(),
+                };
+                format!("        // {} owes {}{}\n        remedies.push(Remedy({}, {}, {}));\n",
+                    action.target, remedy, describe_condition(&condition),
+                    party(&action.target), string(&remedy), string(&condition))
+            }
+            Operation::Oblige => {
+                let duty = text("duty").unwrap_or_else(|| action.target.clone());
+                let by = text("by");
+                let (deadline, grace) = match (by.as_deref(), action.t) {
+                    (Some(by), _) => match date_deadline(by) {
+                        Some(timestamp) => (timestamp.to_string(), "0".to_string()),
+                        None => ("0".to_string(), by.split_once('+').and_then(|(_, period)| duration(period)).unwrap_or_else(|| "0".to_string())),
+                    },
+                    (None, Some(t)) => (format!("block.timestamp + {}", t.max(0.0).round() as u64), "0".to_string()),
+                    (None, None) => ("0".to_string(), "0".to_string()),
+                };
+                let by_comment = by.map(|by| format!(" by {}", by)).unwrap_or_default();
+                format!("        // {} must {}{}{}\n        obligations.push(Obligation({}, {}, {}, {}, false, {}, {}, Status.Pending));\n",
+                    action.target, duty, by_comment, describe_condition(&condition),
+                    party(&action.target), string(&duty), string(&terms(action, &["duty", "by", "condition"])), string(&condition), deadline, grace)
+            }
+            Operation::Permit => {
+                let right = text("right").unwrap_or_else(|| action.target.clone());
+                let expires = text("by").as_deref().and_then(date_deadline).map(|t| t.to_string())
+                    .or_else(|| ["window", "duration"].iter()
+                        .find_map(|key| text(key).as_deref().and_then(duration))
+                        .map(|seconds| format!("block.timestamp + {}", seconds)))
+                    .or_else(|| action.dur.map(|d| format!("block.timestamp + {}", d.max(0.0).round() as u64)))
+                    .unwrap_or_else(|| "0".to_string());
+                format!("        // {} may {}{}\n        permissions.push(Permission({}, {}, {}, {}));\n",
+                    action.target, right, describe_condition(&condition),
+                    party(&action.target), string(&right), string(&terms(action, &["right", "by", "window", "duration"])), expires)
+            }
+            Operation::Remedy => {
+                let remedy = text("remedy").unwrap_or_else(|| action.target.clone());
+                let owed_by = if action.actor != action.target { party(&action.actor) } else { "address(0)".to_string() };
+                format!("        // {} is entitled to {}{}\n        remedies.push(Remedy({}, {}, {}));\n",
+                    action.target, remedy, describe_condition(&condition),
+                    owed_by, string(&remedy), string(&condition))
+            }
+            _ => format!("        // Not enforceable on-chain: {:?} on {}\n", action.op, action.target),
+        }
+    }
+}
+
+impl Default for SolidityCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn describe_condition(condition: &str) -> String {
+    if condition.is_empty() { String::new() } else { format!(" ({})", condition) }
+}
+
+/// The params not stored in their own field, as `key: value; ...`
+fn terms(action: &Action, skip: &[&str]) -> String {
+    let mut terms: Vec<(&String, &serde_json::Value)> = action.params.iter().flatten()
+        .filter(|(key, _)| !skip.contains(&key.as_str()))
+        .collect();
+    terms.sort_by_key(|(key, _)| *key);
+    terms.iter()
+        .map(|(key, value)| format!("{}: {}", key, value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string())))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// The last second of a `YYYY-MM-DD` date, UTC
+fn date_deadline(text: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(23, 59, 59)?.and_utc().timestamp())
+}
+
+/// A period such as `5d`, `36h`, `2 weeks` or `30 days`, as a Solidity
+/// time expression
+fn duration(text: &str) -> Option<String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let amount: u64 = text[..split].parse().ok()?;
+    let unit = match text[split..].trim().trim_end_matches('s') {
+        "s" | "second" => "seconds",
+        "m" | "min" | "minute" => "minutes",
+        "h" | "hour" => "hours",
+        "d" | "day" => "days",
+        "w" | "week" => "weeks",
+        _ => return None,
+    };
+    Some(format!("{} {}", amount, unit))
+}
+
+/// `Purchase Agreement` -> `PurchaseAgreement`
+fn contract_name(title: &str) -> String {
+    let name: String = title.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word[..1].to_ascii_uppercase() + &word[1..])
+        .collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => name,
+        _ => format!("Contract{}", name),
+    }
+}
+
+/// `Buyer` -> `buyer`, `Legal Team` -> `legalTeam`
+fn party_ident(name: &str) -> String {
+    let name = contract_name(name);
+    name[..1].to_ascii_lowercase() + &name[1..]
+}
+
+/// A string literal, `unicode"..."` if it has non-ASCII characters
+fn string(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+    if text.is_ascii() { format!("\"{}\"", escaped) } else { format!("unicode\"{}\"", escaped) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_helpers() {
+        assert_eq!(duration("5d"), Some("5 days".to_string()));
+        assert_eq!(duration("30 days"), Some("30 days".to_string()));
+        assert_eq!(duration("soon"), None);
+        assert_eq!(date_deadline("2025-11-15"), Some(1763251199));
+        assert_eq!(contract_name("Purchase Agreement"), "PurchaseAgreement");
+        assert_eq!(party_ident("Legal Team"), "legalTeam");
+        assert_eq!(string("100°C"), "unicode\"100°C\"");
+    }
+
+    #[test]
+    fn test_compile_legal_contract() {
+        let program = Program::from_json(&std::fs::read_to_string("examples/legal_contract.json").unwrap()).unwrap();
+        let code = SolidityCompiler::new().compile(&program).unwrap();
+
+        for expected in [
+            "contract PurchaseAgreement {",
+            "constructor(address _buyer, address _seller) {",
+            "obligations.push(Obligation(_buyer, \"Pay\", \"amount: 1000 USD\", \"Goods delivered and inspected\", false, 0, 5 days, Status.Pending));",
+            "obligations.push(Obligation(_seller, \"Deliver\", \"item: Widget Pro 3000; quantity: 1\", \"Order confirmed\", false, 1763251199, 0, Status.Pending));",
+            "permissions.push(Permission(_buyer, \"Return\", \"condition: Defective or not as described\", block.timestamp + 30 days));",
+            "remedies.push(Remedy(_seller, \"Replace or Refund\", \"If defective\"));",
+        ] {
+            assert!(code.contains(expected), "missing {}\n{}", expected, code);
+        }
+    }
+
+    #[test]
+    fn test_requires_legal_program() {
+        let program = Program::from_json(r#"{"actions": [{"actor": "a", "op": "Emit", "target": "x"}]}"#).unwrap();
+        assert!(SolidityCompiler::new().compile(&program).is_err());
+    }
+}
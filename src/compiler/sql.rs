@@ -0,0 +1,297 @@
+use crate::{Action, Operation, Program};
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// Which database the generated SQL is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SqlDialect {
+    #[default]
+    Sqlite,
+    Postgres,
+}
+
+impl SqlDialect {
+    fn name(self) -> &'static str {
+        match self {
+            SqlDialect::Sqlite => "SQLite",
+            SqlDialect::Postgres => "PostgreSQL",
+        }
+    }
+
+    fn id_column(self) -> &'static str {
+        match self {
+            SqlDialect::Sqlite => "INTEGER PRIMARY KEY",
+            SqlDialect::Postgres => "SERIAL PRIMARY KEY",
+        }
+    }
+
+    fn type_name(self, kind: ColumnType) -> &'static str {
+        match (self, kind) {
+            (_, ColumnType::Integer) => "INTEGER",
+            (SqlDialect::Sqlite, ColumnType::Real) => "REAL",
+            (SqlDialect::Postgres, ColumnType::Real) => "DOUBLE PRECISION",
+            (_, ColumnType::Boolean) => "BOOLEAN",
+            (_, ColumnType::Text) => "TEXT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnType {
+    Integer,
+    Real,
+    Boolean,
+    Text,
+}
+
+impl ColumnType {
+    fn of(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => ColumnType::Integer,
+            serde_json::Value::Number(_) => ColumnType::Real,
+            serde_json::Value::Bool(_) => ColumnType::Boolean,
+            _ => ColumnType::Text,
+        }
+    }
+
+    /// A type that holds values of both; integers widen to reals, anything
+    /// else mixed falls back to text
+    fn widen(self, other: ColumnType) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Integer, ColumnType::Real) | (ColumnType::Real, ColumnType::Integer) => ColumnType::Real,
+            _ => ColumnType::Text,
+        }
+    }
+}
+
+/// Table for `Assert` statements
+const ASSERTIONS_TABLE: &str = "assertions";
+/// Columns every table gets, named so they don't clash with fact attributes
+const ID_COLUMN: &str = "ucl_id";
+const ACTOR_COLUMN: &str = "ucl_actor";
+
+/// Materializes the facts of a knowledge-style program as SQL
+///
+/// Every entity a `StoreFact` describes (its `entity` param, or the target)
+/// becomes a table with a column per attribute, and each `StoreFact` an
+/// `INSERT`. `Assert`s go into an `assertions` table. A `Read` of `entity`
+/// or `entity.attribute` becomes a `SELECT` of the latest row, matching the
+/// brain simulator where later facts overwrite earlier ones. Other
+/// operations, including control flow, have no SQL equivalent and become
+/// comments.
+pub struct SqlCompiler {
+    dialect: SqlDialect,
+    /// Column types of each entity's attributes
+    tables: BTreeMap<String, BTreeMap<String, ColumnType>>,
+}
+
+impl SqlCompiler {
+    pub fn new() -> Self {
+        Self {
+            dialect: SqlDialect::default(),
+            tables: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_dialect(mut self, dialect: SqlDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<String> {
+        self.tables.clear();
+        let mut has_assertions = false;
+        for action in &program.actions {
+            match action.op {
+                Operation::StoreFact => {
+                    let (entity, attributes) = fact(action);
+                    let columns = self.tables.entry(entity).or_default();
+                    for (name, value) in attributes {
+                        let kind = ColumnType::of(value);
+                        columns.entry(name.clone()).and_modify(|k| *k = k.widen(kind)).or_insert(kind);
+                    }
+                }
+                Operation::Assert => has_assertions = true,
+                _ => {}
+            }
+        }
+
+        let mut output = String::new();
+        output.push_str("-- Generated from UCL\n");
+        output.push_str(&format!("-- Universal Causal Language -> SQL Compiler ({})\n\n", self.dialect.name()));
+        output.push_str("BEGIN;\n\n");
+
+        for (entity, columns) in &self.tables {
+            let mut definitions = vec![
+                format!("{} {}", ident(ID_COLUMN), self.dialect.id_column()),
+                format!("{} TEXT NOT NULL", ident(ACTOR_COLUMN)),
+            ];
+            definitions.extend(columns.iter().map(|(name, kind)| format!("{} {}", ident(name), self.dialect.type_name(*kind))));
+            output.push_str(&format!("CREATE TABLE IF NOT EXISTS {} (\n  {}\n);\n", ident(entity), definitions.join(",\n  ")));
+        }
+        if has_assertions {
+            output.push_str(&format!("CREATE TABLE IF NOT EXISTS {} (\n  {} {},\n  {} TEXT NOT NULL,\n  \"name\" TEXT NOT NULL,\n  \"statement\" TEXT NOT NULL,\n  \"details\" TEXT\n);\n",
+                ident(ASSERTIONS_TABLE), ident(ID_COLUMN), self.dialect.id_column(), ident(ACTOR_COLUMN)));
+        }
+        if !self.tables.is_empty() || has_assertions {
+            output.push('\n');
+        }
+
+        for action in &program.actions {
+            output.push_str(&self.compile_action(action));
+            output.push('\n');
+        }
+
+        output.push_str("\nCOMMIT;\n");
+        Ok(output)
+    }
+
+    fn compile_action(&self, action: &Action) -> String {
+        match action.op {
+            Operation::StoreFact => self.compile_store_fact(action),
+            Operation::Assert => self.compile_assert(action),
+            Operation::Read => self.compile_read(action),
+            _ => format!("-- Skipped: {:?} on {} has no SQL equivalent", action.op, action.target),
+        }
+    }
+
+    fn compile_store_fact(&self, action: &Action) -> String {
+        let (entity, attributes) = fact(action);
+        if attributes.is_empty() {
+            return format!("-- Skipped: StoreFact about {} has no attributes", entity);
+        }
+
+        let columns = &self.tables[&entity];
+        let mut names = vec![ident(ACTOR_COLUMN)];
+        let mut values = vec![string(&action.actor)];
+        for (name, value) in attributes {
+            names.push(ident(name));
+            values.push(literal(value, columns[name]));
+        }
+
+        format!("INSERT INTO {} ({}) VALUES ({});", ident(&entity), names.join(", "), values.join(", "))
+    }
+
+    fn compile_assert(&self, action: &Action) -> String {
+        let params = action.params.as_ref();
+        let statement = params
+            .and_then(|p| p.get("statement"))
+            .and_then(|v| v.as_str())
+            .unwrap_or(&action.target);
+
+        // Everything but the statement, as sorted JSON
+        let details: BTreeMap<&String, &serde_json::Value> = params.into_iter().flatten()
+            .filter(|(key, _)| key.as_str() != "statement")
+            .collect();
+        let details = if details.is_empty() {
+            "NULL".to_string()
+        } else {
+            string(&serde_json::to_string(&details).unwrap_or_default())
+        };
+
+        format!("INSERT INTO {} ({}, \"name\", \"statement\", \"details\") VALUES ({}, {}, {}, {});",
+            ident(ASSERTIONS_TABLE), ident(ACTOR_COLUMN), string(&action.actor), string(&action.target), string(statement), details)
+    }
+
+    /// `entity` selects the whole latest row, `entity.attribute` one column,
+    /// `assertion.name` an asserted statement (as the brain stores them)
+    fn compile_read(&self, action: &Action) -> String {
+        let target = &action.target;
+        let latest = format!("ORDER BY {} DESC LIMIT 1", ident(ID_COLUMN));
+
+        if self.tables.contains_key(target) {
+            return format!("SELECT * FROM {} {};", ident(target), latest);
+        }
+        if let Some((entity, attribute)) = target.rsplit_once('.') {
+            if self.tables.get(entity).is_some_and(|columns| columns.contains_key(attribute)) {
+                return format!("SELECT {} FROM {} {};", ident(attribute), ident(entity), latest);
+            }
+            if entity == "assertion" {
+                return format!("SELECT \"statement\" FROM {} WHERE \"name\" = {} {};",
+                    ident(ASSERTIONS_TABLE), string(attribute), latest);
+            }
+        }
+
+        format!("-- Skipped: Read of {} (no facts stored about it)", target)
+    }
+}
+
+impl Default for SqlCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The entity a StoreFact describes and its attributes, sorted by name
+fn fact(action: &Action) -> (String, Vec<(&String, &serde_json::Value)>) {
+    let params = action.params.as_ref();
+    let entity = params
+        .and_then(|p| p.get("entity"))
+        .and_then(|v| v.as_str())
+        .unwrap_or(&action.target)
+        .to_string();
+
+    let mut attributes: Vec<(&String, &serde_json::Value)> = params.into_iter().flatten()
+        .filter(|(key, _)| key.as_str() != "entity")
+        .collect();
+    attributes.sort_by_key(|(key, _)| *key);
+    (entity, attributes)
+}
+
+/// A double-quoted identifier, valid in both SQLite and PostgreSQL
+fn ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// A single-quoted string literal
+fn string(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// `value` as a literal for a column of type `kind`
+fn literal(value: &serde_json::Value, kind: ColumnType) -> String {
+    match (value, kind) {
+        (serde_json::Value::Null, _) => "NULL".to_string(),
+        (serde_json::Value::String(s), _) => string(s),
+        (serde_json::Value::Bool(b), ColumnType::Boolean) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        (serde_json::Value::Number(n), ColumnType::Integer | ColumnType::Real) => n.to_string(),
+        // Mixed-type columns are text; arrays and objects are stored as JSON
+        (other, _) => string(&other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "StoreFact", "target": "memory", "params": {"entity": "elephant", "color": "gray", "weight": 5400}},
+            {"actor": "zoo", "op": "StoreFact", "target": "memory", "params": {"entity": "elephant", "weight": 5512.5, "name": "O'Malley"}},
+            {"actor": "teacher", "op": "Assert", "target": "fact", "params": {"statement": "Water boils at 100°C", "context": "standard_pressure"}},
+            {"actor": "you", "op": "Read", "target": "elephant.weight"},
+            {"actor": "you", "op": "Read", "target": "assertion.fact"},
+            {"actor": "you", "op": "Emit", "target": "done"}
+        ]}"#).unwrap()
+    }
+
+    #[test]
+    fn test_compile_facts() {
+        let sql = SqlCompiler::new().compile(&program()).unwrap();
+
+        assert!(sql.contains("CREATE TABLE IF NOT EXISTS \"elephant\" (\n  \"ucl_id\" INTEGER PRIMARY KEY,\n  \"ucl_actor\" TEXT NOT NULL,\n  \"color\" TEXT,\n  \"name\" TEXT,\n  \"weight\" REAL\n);"), "{}", sql);
+        assert!(sql.contains("INSERT INTO \"elephant\" (\"ucl_actor\", \"name\", \"weight\") VALUES ('zoo', 'O''Malley', 5512.5);"), "{}", sql);
+        assert!(sql.contains("INSERT INTO \"assertions\" (\"ucl_actor\", \"name\", \"statement\", \"details\") VALUES ('teacher', 'fact', 'Water boils at 100°C', '{\"context\":\"standard_pressure\"}');"), "{}", sql);
+        assert!(sql.contains("SELECT \"weight\" FROM \"elephant\" ORDER BY \"ucl_id\" DESC LIMIT 1;"), "{}", sql);
+        assert!(sql.contains("SELECT \"statement\" FROM \"assertions\" WHERE \"name\" = 'fact' ORDER BY \"ucl_id\" DESC LIMIT 1;"), "{}", sql);
+        assert!(sql.contains("-- Skipped: Emit on done has no SQL equivalent"), "{}", sql);
+    }
+
+    #[test]
+    fn test_postgres_dialect() {
+        let sql = SqlCompiler::new().with_dialect(SqlDialect::Postgres).compile(&program()).unwrap();
+        assert!(sql.contains("\"ucl_id\" SERIAL PRIMARY KEY"));
+        assert!(sql.contains("\"weight\" DOUBLE PRECISION"));
+    }
+}
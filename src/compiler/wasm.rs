@@ -0,0 +1,843 @@
+//! Lowers numeric UCL programs to a WebAssembly module
+//!
+//! Every variable is an `f64` local and every function takes and returns
+//! `f64`s, so only arithmetic and control flow are lowered; strings may only
+//! be emitted. The module imports its output from the host:
+//!
+//! - `env.emit(f64)` prints a number
+//! - `env.emit_str(ptr: i32, len: i32)` prints a UTF-8 string from `memory`
+//! - `env.pow(f64, f64) -> f64` for `**`
+//!
+//! and exports `memory` and `main`, which runs the program's top-level
+//! actions. [`node_runner`] wraps a module in a script that provides the
+//! imports and runs it with Node.
+
+use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression};
+use anyhow::{anyhow, bail, Result};
+use crate::error::UclError;
+use std::collections::HashMap;
+
+const F64: u8 = 0x7c;
+const I32: u8 = 0x7f;
+
+/// Host functions every module imports from `env`, as (name, params, results)
+const IMPORTS: &[(&str, &[u8], &[u8])] = &[
+    ("emit", &[F64], &[]),
+    ("emit_str", &[I32, I32], &[]),
+    ("pow", &[F64, F64], &[F64]),
+];
+const EMIT: u32 = 0;
+const EMIT_STR: u32 = 1;
+const POW: u32 = 2;
+
+/// Scratch locals for computing `%` without evaluating operands twice
+const REM_LHS: &str = "rem.lhs";
+const REM_RHS: &str = "rem.rhs";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Instr {
+    F64Const(f64),
+    I32Const(i32),
+    LocalGet(u32),
+    LocalSet(u32),
+    Call(u32),
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Trunc,
+    F64Neg,
+    F64ConvertI32U,
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+    I32And,
+    I32Or,
+    I32Eqz,
+    Block,
+    Loop,
+    If,
+    Else,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Return,
+    Drop,
+    /// Kept in the text format only
+    Comment(String),
+}
+
+impl Instr {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Instr::F64Const(x) => { out.push(0x44); out.extend(x.to_le_bytes()); }
+            Instr::I32Const(x) => { out.push(0x41); sleb(*x, out); }
+            Instr::LocalGet(i) => { out.push(0x20); uleb(*i, out); }
+            Instr::LocalSet(i) => { out.push(0x21); uleb(*i, out); }
+            Instr::Call(i) => { out.push(0x10); uleb(*i, out); }
+            Instr::Br(depth) => { out.push(0x0c); uleb(*depth, out); }
+            Instr::BrIf(depth) => { out.push(0x0d); uleb(*depth, out); }
+            // Blocks take the empty block type
+            Instr::Block => out.extend([0x02, 0x40]),
+            Instr::Loop => out.extend([0x03, 0x40]),
+            Instr::If => out.extend([0x04, 0x40]),
+            Instr::Comment(_) => {}
+            simple => out.push(match simple {
+                Instr::F64Add => 0xa0,
+                Instr::F64Sub => 0xa1,
+                Instr::F64Mul => 0xa2,
+                Instr::F64Div => 0xa3,
+                Instr::F64Trunc => 0x9d,
+                Instr::F64Neg => 0x9a,
+                Instr::F64ConvertI32U => 0xb8,
+                Instr::F64Eq => 0x61,
+                Instr::F64Ne => 0x62,
+                Instr::F64Lt => 0x63,
+                Instr::F64Gt => 0x64,
+                Instr::F64Le => 0x65,
+                Instr::F64Ge => 0x66,
+                Instr::I32And => 0x71,
+                Instr::I32Or => 0x72,
+                Instr::I32Eqz => 0x45,
+                Instr::Else => 0x05,
+                Instr::End => 0x0b,
+                Instr::Return => 0x0f,
+                Instr::Drop => 0x1a,
+                _ => unreachable!("encoded above"),
+            }),
+        }
+    }
+
+    /// The text format, naming locals and functions through the lookups
+    fn wat(&self, local: &dyn Fn(u32) -> String, func: &dyn Fn(u32) -> String) -> String {
+        match self {
+            Instr::F64Const(x) => format!("f64.const {:?}", x),
+            Instr::I32Const(x) => format!("i32.const {}", x),
+            Instr::LocalGet(i) => format!("local.get {}", local(*i)),
+            Instr::LocalSet(i) => format!("local.set {}", local(*i)),
+            Instr::Call(i) => format!("call {}", func(*i)),
+            Instr::Br(depth) => format!("br {}", depth),
+            Instr::BrIf(depth) => format!("br_if {}", depth),
+            Instr::Comment(text) => format!(";; {}", text),
+            Instr::F64Add => "f64.add".into(),
+            Instr::F64Sub => "f64.sub".into(),
+            Instr::F64Mul => "f64.mul".into(),
+            Instr::F64Div => "f64.div".into(),
+            Instr::F64Trunc => "f64.trunc".into(),
+            Instr::F64Neg => "f64.neg".into(),
+            Instr::F64ConvertI32U => "f64.convert_i32_u".into(),
+            Instr::F64Eq => "f64.eq".into(),
+            Instr::F64Ne => "f64.ne".into(),
+            Instr::F64Lt => "f64.lt".into(),
+            Instr::F64Gt => "f64.gt".into(),
+            Instr::F64Le => "f64.le".into(),
+            Instr::F64Ge => "f64.ge".into(),
+            Instr::I32And => "i32.and".into(),
+            Instr::I32Or => "i32.or".into(),
+            Instr::I32Eqz => "i32.eqz".into(),
+            Instr::Block => "block".into(),
+            Instr::Loop => "loop".into(),
+            Instr::If => "if".into(),
+            Instr::Else => "else".into(),
+            Instr::End => "end".into(),
+            Instr::Return => "return".into(),
+            Instr::Drop => "drop".into(),
+        }
+    }
+}
+
+/// A function being lowered: `f64` params, then `f64` locals added on first use
+#[derive(Debug, Default)]
+struct Function {
+    name: String,
+    params: usize,
+    locals: Vec<String>,
+    returns: bool,
+    body: Vec<Instr>,
+}
+
+impl Function {
+    fn local(&mut self, name: &str) -> u32 {
+        let index = self.locals.iter().position(|l| l == name).unwrap_or_else(|| {
+            self.locals.push(name.to_string());
+            self.locals.len() - 1
+        });
+        index as u32
+    }
+}
+
+/// Compiles UCL to WebAssembly, as text (`compile`) or a binary module
+/// (`compile_binary`)
+pub struct WasmCompiler {
+    /// User functions with their argument names; function index is the
+    /// position plus the number of imports
+    signatures: Vec<(String, Vec<String>)>,
+    /// Bytes of every emitted string, placed at address 0
+    data: Vec<u8>,
+    strings: HashMap<String, (u32, u32)>,
+}
+
+/// A lowered module
+struct Module {
+    functions: Vec<Function>,
+    data: Vec<u8>,
+}
+
+impl WasmCompiler {
+    pub fn new() -> Self {
+        Self {
+            signatures: Vec::new(),
+            data: Vec::new(),
+            strings: HashMap::new(),
+        }
+    }
+
+    /// The module in the WebAssembly text format
+    pub fn compile(&mut self, program: &Program) -> Result<String> {
+        Ok(self.lower(program)?.wat())
+    }
+
+    /// The module as a `.wasm` binary
+    pub fn compile_binary(&mut self, program: &Program) -> Result<Vec<u8>> {
+        Ok(self.lower(program)?.encode())
+    }
+
+    fn lower(&mut self, program: &Program) -> Result<Module> {
+        self.signatures.clear();
+        self.data.clear();
+        self.strings.clear();
+
+        // Functions are hoisted to the module, wherever they're defined
+        let mut definitions = Vec::new();
+        crate::analysis::walk_with_depth(&program.actions, 0, &mut |action, _| {
+            if action.op == Operation::DefineFunction {
+                definitions.push(action.clone());
+            }
+        });
+
+        let mut bodies = Vec::new();
+        for definition in &definitions {
+            let params = definition.params.as_ref()
+                .ok_or_else(|| UclError::missing("DefineFunction", "params"))?;
+            let args: Vec<String> = params.get("args")
+                .and_then(|v| v.as_array())
+                .ok_or_else(|| UclError::missing("DefineFunction", "args array"))?
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect();
+            let body: Vec<Action> = serde_json::from_value(params.get("body")
+                .ok_or_else(|| UclError::missing("DefineFunction", "body"))?
+                .clone())?;
+
+            if self.signatures.iter().any(|(name, _)| name == &definition.target) {
+                bail!("Function '{}' is defined more than once", definition.target);
+            }
+            self.signatures.push((definition.target.clone(), args));
+            bodies.push(body);
+        }
+
+        let mut functions = Vec::new();
+        for ((name, args), body) in self.signatures.clone().into_iter().zip(&bodies) {
+            let mut function = Function { name, params: args.len(), locals: args, returns: true, ..Default::default() };
+            self.lower_block(&mut function, body)?;
+            // Falling off the end returns 0
+            function.body.push(Instr::F64Const(0.0));
+            functions.push(function);
+        }
+
+        let mut main = Function { name: "ucl.main".into(), ..Default::default() };
+        self.lower_block(&mut main, &program.actions)?;
+        functions.push(main);
+
+        Ok(Module { functions, data: self.data.clone() })
+    }
+
+    /// Index and argument names of a user function
+    fn function_index(&self, name: &str) -> Option<(u32, Vec<String>)> {
+        self.signatures.iter()
+            .position(|(n, _)| n == name)
+            .map(|i| ((IMPORTS.len() + i) as u32, self.signatures[i].1.clone()))
+    }
+
+    fn lower_block(&mut self, f: &mut Function, actions: &[Action]) -> Result<()> {
+        for action in actions {
+            self.lower_action(f, action)?;
+        }
+        Ok(())
+    }
+
+    fn lower_action(&mut self, f: &mut Function, action: &Action) -> Result<()> {
+        let param = |key: &str| action.params.as_ref().and_then(|p| p.get(key));
+
+        match &action.op {
+            Operation::Assign | Operation::Bind => {
+                let value = param("value")
+                    .ok_or_else(|| UclError::missing(format!("{:?}", action.op), "'value' parameter"))?;
+                self.lower_value_or_expression(f, value)?;
+                let local = f.local(&action.target);
+                f.body.push(Instr::LocalSet(local));
+            }
+            Operation::Write => {
+                if let Some(op) = param("operation") {
+                    let instr = match op.as_str().unwrap_or("") {
+                        "add" => Instr::F64Add,
+                        "subtract" => Instr::F64Sub,
+                        "divide" => Instr::F64Div,
+                        _ => Instr::F64Mul,
+                    };
+                    for (register, literal) in [("lhs_register", "lhs"), ("rhs_register", "rhs")] {
+                        match (param(register).and_then(|r| r.as_str()), param(literal)) {
+                            (Some(register), _) => {
+                                let local = f.local(register);
+                                f.body.push(Instr::LocalGet(local));
+                            }
+                            (None, Some(value)) => self.lower_value(f, value)?,
+                            (None, None) => return Err(UclError::missing("Write", format!("{} or {}", register, literal)).into()),
+                        }
+                    }
+                    f.body.push(instr);
+                } else {
+                    let value = param("value")
+                        .ok_or_else(|| UclError::missing("Write", "'value' parameter or operation"))?;
+                    self.lower_value_or_expression(f, value)?;
+                }
+                let local = f.local(&action.target);
+                f.body.push(Instr::LocalSet(local));
+            }
+            Operation::Emit => {
+                let content = param("content").or_else(|| param("message"));
+                match content {
+                    // Content naming the target prints the variable, as in the other compilers
+                    Some(content) if content.as_str() != Some(&action.target) => {
+                        match serde_json::from_value::<Expression>(content.clone()) {
+                            Ok(Expression::Value(serde_json::Value::String(text))) => self.emit_str(f, &text),
+                            Ok(expr) => {
+                                self.lower_expression(f, &expr)?;
+                                f.body.push(Instr::Call(EMIT));
+                            }
+                            Err(_) => self.emit_str(f, &content.to_string()),
+                        }
+                    }
+                    _ => {
+                        let local = f.local(&action.target);
+                        f.body.push(Instr::LocalGet(local));
+                        f.body.push(Instr::Call(EMIT));
+                    }
+                }
+            }
+            Operation::Return => {
+                if f.returns {
+                    match param("value") {
+                        Some(value) => self.lower_value_or_expression(f, value)?,
+                        None => {
+                            let local = f.local(&action.target);
+                            f.body.push(Instr::LocalGet(local));
+                        }
+                    }
+                }
+                // Returning from the top level ends the program
+                f.body.push(Instr::Return);
+            }
+            Operation::If => {
+                let condition = action.condition.as_ref()
+                    .ok_or_else(|| UclError::missing("If", "condition"))?;
+                self.lower_condition(f, condition)?;
+                f.body.push(Instr::If);
+                self.lower_block(f, action.then_actions.as_deref().unwrap_or_default())?;
+                if let Some(else_actions) = &action.else_actions {
+                    f.body.push(Instr::Else);
+                    self.lower_block(f, else_actions)?;
+                }
+                f.body.push(Instr::End);
+            }
+            Operation::While => {
+                let condition = action.condition.as_ref()
+                    .ok_or_else(|| UclError::missing("While", "condition"))?;
+                f.body.extend([Instr::Block, Instr::Loop]);
+                self.lower_condition(f, condition)?;
+                f.body.extend([Instr::I32Eqz, Instr::BrIf(1)]);
+                self.lower_block(f, action.body_actions.as_deref().unwrap_or_default())?;
+                f.body.extend([Instr::Br(0), Instr::End, Instr::End]);
+            }
+            Operation::For => {
+                let loop_var = action.loop_var.as_ref()
+                    .ok_or_else(|| UclError::missing("For", "variable"))?;
+                let from_expr = action.from_expr.as_ref()
+                    .ok_or_else(|| UclError::missing("For", "from expression"))?;
+                let to_expr = action.to_expr.as_ref()
+                    .ok_or_else(|| UclError::missing("For", "to expression"))?;
+
+                let var = f.local(loop_var);
+                // The bound is evaluated once, so each loop over `i` gets its own
+                let end = f.local(&format!("{}.end.{}", loop_var, f.locals.len()));
+
+                self.lower_expression(f, from_expr)?;
+                f.body.push(Instr::LocalSet(var));
+                self.lower_expression(f, to_expr)?;
+                f.body.extend([Instr::LocalSet(end), Instr::Block, Instr::Loop,
+                    Instr::LocalGet(var), Instr::LocalGet(end), Instr::F64Gt, Instr::BrIf(1)]);
+                self.lower_block(f, action.body_actions.as_deref().unwrap_or_default())?;
+                f.body.push(Instr::LocalGet(var));
+                match &action.step_expr {
+                    Some(step) => self.lower_expression(f, step)?,
+                    None => f.body.push(Instr::F64Const(1.0)),
+                }
+                f.body.extend([Instr::F64Add, Instr::LocalSet(var), Instr::Br(0), Instr::End, Instr::End]);
+            }
+            Operation::Call => {
+                if let Some((index, args)) = self.function_index(&action.target) {
+                    for arg in &args {
+                        match param(arg) {
+                            Some(value) => self.lower_value_or_expression(f, value)?,
+                            None => f.body.push(Instr::F64Const(0.0)),
+                        }
+                    }
+                    f.body.push(Instr::Call(index));
+                    match param("out").and_then(|v| v.as_str()) {
+                        Some(out) => {
+                            let local = f.local(out);
+                            f.body.push(Instr::LocalSet(local));
+                        }
+                        None => f.body.push(Instr::Drop),
+                    }
+                } else {
+                    f.body.push(Instr::Comment(format!("Unsupported call: {}", action.target)));
+                }
+            }
+            Operation::DefineFunction => {
+                f.body.push(Instr::Comment(format!("function {} is defined at module level", action.target)));
+            }
+            _ => {
+                f.body.push(Instr::Comment(format!("Unsupported operation: {:?} on {}", action.op, action.target)));
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_str(&mut self, f: &mut Function, text: &str) {
+        let (offset, len) = *self.strings.entry(text.to_string()).or_insert_with(|| {
+            let offset = self.data.len() as u32;
+            self.data.extend(text.as_bytes());
+            (offset, text.len() as u32)
+        });
+        f.body.extend([Instr::I32Const(offset as i32), Instr::I32Const(len as i32), Instr::Call(EMIT_STR)]);
+    }
+
+    /// A param that may hold an expression (`{"var": "x"}`) or a plain value
+    fn lower_value_or_expression(&mut self, f: &mut Function, value: &serde_json::Value) -> Result<()> {
+        match serde_json::from_value::<Expression>(value.clone()) {
+            Ok(expr) => self.lower_expression(f, &expr),
+            Err(_) => self.lower_value(f, value),
+        }
+    }
+
+    fn lower_value(&mut self, f: &mut Function, value: &serde_json::Value) -> Result<()> {
+        let number = match value {
+            serde_json::Value::Number(n) => n.as_f64().unwrap_or_default(),
+            serde_json::Value::Bool(b) => if *b { 1.0 } else { 0.0 },
+            serde_json::Value::Null => 0.0,
+            other => bail!("The wasm target only supports numeric values, got {}", other),
+        };
+        f.body.push(Instr::F64Const(number));
+        Ok(())
+    }
+
+    fn lower_expression(&mut self, f: &mut Function, expr: &Expression) -> Result<()> {
+        match expr {
+            Expression::Value(v) => self.lower_value(f, v)?,
+            Expression::Variable { var } => {
+                let local = f.local(var);
+                f.body.push(Instr::LocalGet(local));
+            }
+            Expression::BinaryOp { expr: bin_op } => {
+                self.lower_expression(f, &bin_op.left)?;
+                self.lower_expression(f, &bin_op.right)?;
+                match bin_op.op.as_str() {
+                    "+" => f.body.push(Instr::F64Add),
+                    "-" => f.body.push(Instr::F64Sub),
+                    "*" => f.body.push(Instr::F64Mul),
+                    "/" => f.body.push(Instr::F64Div),
+                    "**" => f.body.push(Instr::Call(POW)),
+                    // a - trunc(a / b) * b, the sign following the dividend
+                    "%" => {
+                        let (lhs, rhs) = (f.local(REM_LHS), f.local(REM_RHS));
+                        f.body.extend([
+                            Instr::LocalSet(rhs), Instr::LocalSet(lhs),
+                            Instr::LocalGet(lhs), Instr::LocalGet(lhs), Instr::LocalGet(rhs),
+                            Instr::F64Div, Instr::F64Trunc, Instr::LocalGet(rhs), Instr::F64Mul, Instr::F64Sub,
+                        ]);
+                    }
+                    op => return Err(UclError::unsupported(format!("binary operator '{}'", op), "wasm").into()),
+                }
+            }
+            Expression::FunctionCall { call, args } => {
+                let (index, params) = self.function_index(call)
+                    .ok_or_else(|| anyhow!("Call to undefined function '{}'", call))?;
+                for param in params {
+                    match args.get(&param) {
+                        Some(arg) => self.lower_expression(f, arg)?,
+                        None => f.body.push(Instr::F64Const(0.0)),
+                    }
+                }
+                f.body.push(Instr::Call(index));
+            }
+            Expression::List { .. } | Expression::Index { .. } | Expression::Length { .. } => {
+                return Err(UclError::unsupported("lists", "wasm").into());
+            }
+            Expression::StringOp { .. } | Expression::Format { .. } => {
+                return Err(UclError::unsupported("string functions", "wasm").into());
+            }
+            Expression::Unary { unary } => {
+                self.lower_expression(f, &unary.operand)?;
+                match unary.op.as_str() {
+                    "-" => f.body.push(Instr::F64Neg),
+                    // Numbers are all there is, so 0 is false
+                    "!" => f.body.extend([Instr::F64Const(0.0), Instr::F64Eq, Instr::F64ConvertI32U]),
+                    op => return Err(UclError::unsupported(format!("unary operator '{}'", op), "wasm").into()),
+                }
+            }
+            Expression::Test { test } => {
+                self.lower_condition(f, test)?;
+                f.body.push(Instr::F64ConvertI32U);
+            }
+        }
+        Ok(())
+    }
+
+    /// Leaves an `i32` truth value on the stack
+    fn lower_condition(&mut self, f: &mut Function, condition: &Condition) -> Result<()> {
+        match condition {
+            Condition::Comparison { op, left, right } => {
+                self.lower_expression(f, left)?;
+                self.lower_expression(f, right)?;
+                f.body.push(match op {
+                    ComparisonOp::Equal => Instr::F64Eq,
+                    ComparisonOp::NotEqual => Instr::F64Ne,
+                    ComparisonOp::LessThan => Instr::F64Lt,
+                    ComparisonOp::LessThanOrEqual => Instr::F64Le,
+                    ComparisonOp::GreaterThan => Instr::F64Gt,
+                    ComparisonOp::GreaterThanOrEqual => Instr::F64Ge,
+                });
+            }
+            Condition::And { operands } | Condition::Or { operands } => {
+                let (combine, empty) = match condition {
+                    Condition::And { .. } => (Instr::I32And, 1),
+                    _ => (Instr::I32Or, 0),
+                };
+                match operands.split_first() {
+                    None => f.body.push(Instr::I32Const(empty)),
+                    Some((first, rest)) => {
+                        self.lower_condition(f, first)?;
+                        for operand in rest {
+                            self.lower_condition(f, operand)?;
+                            f.body.push(combine.clone());
+                        }
+                    }
+                }
+            }
+            Condition::Not { operand } => {
+                self.lower_condition(f, operand)?;
+                f.body.push(Instr::I32Eqz);
+            }
+            Condition::Expression { value } => {
+                self.lower_expression(f, value)?;
+                f.body.extend([Instr::F64Const(0.0), Instr::F64Ne]);
+            }
+            Condition::Matches { .. } | Condition::StartsWith { .. } | Condition::Contains { .. } => {
+                return Err(UclError::unsupported("text conditions", "wasm").into());
+            }
+            Condition::Exists { .. } => return Err(UclError::unsupported("exists conditions", "wasm").into()),
+        }
+        Ok(())
+    }
+}
+
+impl Default for WasmCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module {
+    /// Linear memory pages needed for the data, at least one
+    fn pages(&self) -> u32 {
+        (self.data.len() as u32).div_ceil(65536).max(1)
+    }
+
+    fn function_name(&self, index: u32) -> String {
+        match IMPORTS.get(index as usize) {
+            Some((name, _, _)) => format!("$host.{}", name),
+            None => format!("${}", wat_id(&self.functions[index as usize - IMPORTS.len()].name)),
+        }
+    }
+
+    fn wat(&self) -> String {
+        let mut out = String::from(";; Generated from UCL\n;; Universal Causal Language -> WebAssembly Compiler\n(module\n");
+
+        for (name, params, results) in IMPORTS {
+            out.push_str(&format!("  (import \"env\" \"{}\" (func $host.{}{}{}))\n", name, name, wat_types("param", params), wat_types("result", results)));
+        }
+        out.push_str(&format!("  (memory (export \"memory\") {})\n", self.pages()));
+        if !self.data.is_empty() {
+            let escaped: String = self.data.iter().map(|&b| match b {
+                b'"' | b'\\' => format!("\\{}", b as char),
+                0x20..=0x7e => (b as char).to_string(),
+                _ => format!("\\{:02x}", b),
+            }).collect();
+            out.push_str(&format!("  (data (i32.const 0) \"{}\")\n", escaped));
+        }
+
+        for function in &self.functions {
+            let id = wat_id(&function.name);
+            let export = if function.returns { String::new() } else { " (export \"main\")".to_string() };
+            out.push_str(&format!("  (func ${}{}", id, export));
+            for param in &function.locals[..function.params] {
+                out.push_str(&format!(" (param ${} f64)", wat_id(param)));
+            }
+            if function.returns {
+                out.push_str(" (result f64)");
+            }
+            out.push('\n');
+            for local in &function.locals[function.params..] {
+                out.push_str(&format!("    (local ${} f64)\n", wat_id(local)));
+            }
+
+            let local = |i: u32| format!("${}", wat_id(&function.locals[i as usize]));
+            let func = |i: u32| self.function_name(i);
+            let mut depth = 2;
+            for instr in &function.body {
+                if matches!(instr, Instr::End | Instr::Else) {
+                    depth -= 1;
+                }
+                out.push_str(&format!("{}{}\n", "  ".repeat(depth), instr.wat(&local, &func)));
+                if matches!(instr, Instr::Block | Instr::Loop | Instr::If | Instr::Else) {
+                    depth += 1;
+                }
+            }
+            out.push_str("  )\n");
+        }
+
+        out.push_str(")\n");
+        out
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut types: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        let mut type_index = |params: Vec<u8>, results: Vec<u8>| {
+            let signature = (params, results);
+            match types.iter().position(|t| *t == signature) {
+                Some(i) => i as u32,
+                None => { types.push(signature); types.len() as u32 - 1 }
+            }
+        };
+        let import_types: Vec<u32> = IMPORTS.iter().map(|(_, p, r)| type_index(p.to_vec(), r.to_vec())).collect();
+        let function_types: Vec<u32> = self.functions.iter()
+            .map(|f| type_index(vec![F64; f.params], if f.returns { vec![F64] } else { vec![] }))
+            .collect();
+
+        let mut module = b"\0asm".to_vec();
+        module.extend(1u32.to_le_bytes());
+
+        section(&mut module, 1, types.len(), |out| {
+            for (params, results) in &types {
+                out.push(0x60);
+                bytes(params, out);
+                bytes(results, out);
+            }
+        });
+        section(&mut module, 2, IMPORTS.len(), |out| {
+            for ((name, _, _), ty) in IMPORTS.iter().zip(&import_types) {
+                bytes(b"env", out);
+                bytes(name.as_bytes(), out);
+                out.push(0x00);
+                uleb(*ty, out);
+            }
+        });
+        section(&mut module, 3, function_types.len(), |out| {
+            for ty in &function_types {
+                uleb(*ty, out);
+            }
+        });
+        section(&mut module, 5, 1, |out| {
+            out.push(0x00);
+            uleb(self.pages(), out);
+        });
+        section(&mut module, 7, 2, |out| {
+            bytes(b"memory", out);
+            out.extend([0x02, 0x00]);
+            bytes(b"main", out);
+            out.push(0x00);
+            uleb((IMPORTS.len() + self.functions.len() - 1) as u32, out);
+        });
+        section(&mut module, 10, self.functions.len(), |out| {
+            for function in &self.functions {
+                let mut code = Vec::new();
+                let locals = (function.locals.len() - function.params) as u32;
+                if locals > 0 {
+                    code.push(1);
+                    uleb(locals, &mut code);
+                    code.push(F64);
+                } else {
+                    code.push(0);
+                }
+                for instr in &function.body {
+                    instr.encode(&mut code);
+                }
+                code.push(0x0b);
+                uleb(code.len() as u32, out);
+                out.extend(code);
+            }
+        });
+        if !self.data.is_empty() {
+            section(&mut module, 11, 1, |out| {
+                out.extend([0x00, 0x41, 0x00, 0x0b]);
+                bytes(&self.data, out);
+            });
+        }
+
+        module
+    }
+}
+
+/// A script that instantiates `module` with the `env` imports and runs `main`
+pub fn node_runner(module: &[u8]) -> String {
+    let hex: String = module.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(r#"const bytes = Buffer.from("{}", "hex");
+let memory;
+const env = {{
+  emit: (x) => console.log(x),
+  emit_str: (ptr, len) => console.log(new TextDecoder().decode(new Uint8Array(memory.buffer, ptr, len))),
+  pow: Math.pow,
+}};
+WebAssembly.instantiate(bytes, {{ env }}).then(({{ instance }}) => {{
+  memory = instance.exports.memory;
+  instance.exports.main();
+}});
+"#, hex)
+}
+
+/// A section: id, byte size, item count, items
+fn section(module: &mut Vec<u8>, id: u8, count: usize, items: impl FnOnce(&mut Vec<u8>)) {
+    let mut content = Vec::new();
+    uleb(count as u32, &mut content);
+    items(&mut content);
+    module.push(id);
+    uleb(content.len() as u32, module);
+    module.extend(content);
+}
+
+/// A length-prefixed byte vector (also used for names)
+fn bytes(data: &[u8], out: &mut Vec<u8>) {
+    uleb(data.len() as u32, out);
+    out.extend(data);
+}
+
+fn uleb(mut value: u32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(mut value: i32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn wat_types(kind: &str, types: &[u8]) -> String {
+    types.iter()
+        .map(|&t| format!(" ({} {})", kind, if t == F64 { "f64" } else { "i32" }))
+        .collect()
+}
+
+/// `name` with anything the text format doesn't allow in an identifier
+/// replaced by `_`
+fn wat_id(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c) { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leb128() {
+        let mut out = Vec::new();
+        uleb(624485, &mut out);
+        assert_eq!(out, vec![0xe5, 0x8e, 0x26]);
+
+        out.clear();
+        sleb(-123456, &mut out);
+        assert_eq!(out, vec![0xc0, 0xbb, 0x78]);
+    }
+
+    #[test]
+    fn test_compile_fibonacci() {
+        let program = Program::from_json(&std::fs::read_to_string("examples/fibonacci.json").unwrap()).unwrap();
+
+        let wat = WasmCompiler::new().compile(&program).unwrap();
+        assert!(wat.contains("(func $fibonacci (param $n f64) (result f64)\n    local.get $n\n    f64.const 1.0\n    f64.le\n    if\n"), "{}", wat);
+        assert!(wat.contains("(data (i32.const 0) \"Fibonacci sequence:\")"), "{}", wat);
+        assert!(wat.contains("(func $ucl.main (export \"main\")"), "{}", wat);
+
+        let binary = WasmCompiler::new().compile_binary(&program).unwrap();
+        assert_eq!(&binary[..8], b"\0asm\x01\0\0\0");
+    }
+
+    #[test]
+    fn test_unary_and_truth_values() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "a", "op": "Bind", "target": "x", "params": {"value": 2}},
+            {"actor": "a", "op": "Bind", "target": "y", "params": {"value": {"unary": {"op": "-", "operand": {"var": "x"}}}}},
+            {"actor": "a", "op": "Bind", "target": "big", "params": {"value": {"test": {"type": "comparison", "op": ">", "left": {"var": "x"}, "right": 1}}}},
+            {"actor": "a", "op": "If", "target": "check", "condition": {"type": "expression", "value": {"var": "big"}}, "then": []}
+        ]}"#).unwrap();
+
+        let wat = WasmCompiler::new().compile(&program).unwrap();
+        assert!(wat.contains("local.get $x\n    f64.neg\n"), "{}", wat);
+        assert!(wat.contains("f64.gt\n    f64.convert_i32_u\n"), "{}", wat);
+        assert!(wat.contains("local.get $big\n    f64.const 0.0\n    f64.ne\n"), "{}", wat);
+        assert!(WasmCompiler::new().compile_binary(&program).is_ok());
+    }
+
+    #[test]
+    fn test_call_stores_result_in_out() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Call", "target": "double", "params": {"n": 21, "out": "res"}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "res"}}}
+        ]}"#).unwrap();
+        let code = WasmCompiler::new().compile(&program).unwrap();
+        assert!(code.contains("call $double\n    local.set $res\n"), "{}", code);
+        assert!(WasmCompiler::new().compile_binary(&program).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_non_numeric_values() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "a", "op": "Bind", "target": "name", "params": {"value": "Ada"}}
+        ]}"#).unwrap();
+
+        let err = WasmCompiler::new().compile(&program).unwrap_err();
+        assert!(err.to_string().contains("only supports numeric values"));
+    }
+}
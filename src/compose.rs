@@ -0,0 +1,206 @@
+//! Building larger programs out of smaller ones
+
+use crate::analysis::{reads, writes};
+use crate::{Action, Operation, Program};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+/// Fields whose strings are never variable or function names
+const NOT_NAMES: &[&str] = &["actor", "op", "type", "effects"];
+
+/// The result of [`merge`]
+#[derive(Debug, Clone)]
+pub struct Merge {
+    pub program: Program,
+    /// Names defined in the second program that were renamed, old to new
+    pub renames: BTreeMap<String, String>,
+}
+
+/// `first`'s actions followed by `second`'s, sharing variables and
+/// functions, so `second` sees whatever `first` left behind
+///
+/// Metadata comes from `first`, with `second`'s keys filling any gaps.
+pub fn concat(first: &Program, second: &Program) -> Program {
+    Program {
+        metadata: merged_metadata(first, second),
+        actions: first.actions.iter().chain(&second.actions).cloned().collect(),
+    }
+}
+
+/// Like [`concat`], but keeping the two programs apart: each variable or
+/// function `second` defines that `first` also uses gets a fresh name
+/// (`total` becomes `total_2`) everywhere in `second`, and `second`'s actors
+/// get `actor_prefix` in front
+///
+/// Renaming replaces every string in `second` equal to the old name, as a
+/// target, loop variable, `{"var"}`, `{"call"}`, function arg or param.
+/// Names `second` only reads are left alone, so it can still take inputs
+/// from `first`.
+pub fn merge(first: &Program, second: &Program, actor_prefix: &str) -> Merge {
+    let taken = used_names(first);
+    let mut all: HashSet<String> = taken.union(&used_names(second)).cloned().collect();
+
+    let mut renames = BTreeMap::new();
+    let mut defined: Vec<String> = defined_names(second).into_iter().filter(|name| taken.contains(name)).collect();
+    defined.sort();
+    for name in defined {
+        let fresh = (2..).map(|n| format!("{}_{}", name, n)).find(|candidate| !all.contains(candidate)).unwrap();
+        all.insert(fresh.clone());
+        renames.insert(name, fresh);
+    }
+
+    let mut actions = second.actions.clone();
+    if !renames.is_empty() {
+        let mut value = serde_json::to_value(&actions).unwrap_or(Value::Null);
+        rename(&mut value, &renames);
+        actions = serde_json::from_value(value).unwrap_or(actions);
+    }
+    if !actor_prefix.is_empty() {
+        for action in &mut actions {
+            prefix_actors(action, actor_prefix);
+        }
+    }
+
+    let program = Program {
+        metadata: merged_metadata(first, second),
+        actions: first.actions.iter().cloned().chain(actions).collect(),
+    };
+    Merge { program, renames }
+}
+
+fn merged_metadata(first: &Program, second: &Program) -> Option<std::collections::HashMap<String, Value>> {
+    match (&first.metadata, &second.metadata) {
+        (Some(first), Some(second)) => {
+            let mut merged = second.clone();
+            merged.extend(first.iter().map(|(k, v)| (k.clone(), v.clone())));
+            Some(merged)
+        }
+        (first, second) => first.clone().or_else(|| second.clone()),
+    }
+}
+
+/// Variables, functions and action ids a program creates
+fn defined_names(program: &Program) -> HashSet<String> {
+    let mut names = HashSet::new();
+    program.walk(&mut |action, _| {
+        names.extend(writes(action));
+        if action.op == Operation::DefineFunction {
+            names.insert(action.target.clone());
+        }
+        names.extend(action.loop_var.clone());
+        names.extend(action.id.clone());
+    });
+    names
+}
+
+/// Everything a program defines, reads or calls
+fn used_names(program: &Program) -> HashSet<String> {
+    let mut names = defined_names(program);
+    program.walk(&mut |action: &Action, _| {
+        names.extend(reads(action));
+        if action.op == Operation::Call {
+            names.insert(action.target.clone());
+        }
+        for value in action.params.iter().flat_map(|p| p.values()) {
+            collect_calls(value, &mut names);
+        }
+    });
+    names
+}
+
+fn collect_calls(value: &Value, names: &mut HashSet<String>) {
+    match value {
+        Value::Object(map) => {
+            names.extend(map.get("call").and_then(|c| c.as_str()).map(str::to_string));
+            map.values().for_each(|v| collect_calls(v, names));
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_calls(v, names)),
+        _ => {}
+    }
+}
+
+/// Replace renamed strings, including the arg names in `{"call", "args"}`
+fn rename(value: &mut Value, renames: &BTreeMap<String, String>) {
+    match value {
+        Value::String(s) => {
+            if let Some(new) = renames.get(s.as_str()) {
+                *s = new.clone();
+            }
+        }
+        Value::Object(map) => {
+            if map.contains_key("call") {
+                if let Some(Value::Object(args)) = map.get_mut("args") {
+                    *args = std::mem::take(args).into_iter()
+                        .map(|(name, arg)| (renames.get(&name).cloned().unwrap_or(name), arg))
+                        .collect();
+                }
+            }
+            for (key, nested) in map.iter_mut() {
+                if !NOT_NAMES.contains(&key.as_str()) {
+                    rename(nested, renames);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| rename(v, renames)),
+        _ => {}
+    }
+}
+
+fn prefix_actors(action: &mut Action, prefix: &str) {
+    action.actor = format!("{}{}", prefix, action.actor);
+    action.visit_children_mut(&mut |_, nested| nested.iter_mut().for_each(|a| prefix_actors(a, prefix)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn program(json: &str) -> Program {
+        Program::from_json(json).unwrap()
+    }
+
+    #[test]
+    fn test_concat_shares_state() {
+        let first = program(r#"{"metadata": {"description": "first"}, "actions": [{"actor": "VM", "op": "Bind", "target": "x", "params": {"value": 1}}]}"#);
+        let second = program(r#"{"metadata": {"description": "second", "author": "b"}, "actions": [{"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"var": "x"}}}]}"#);
+
+        let joined = first.concat(&second);
+        assert_eq!(joined.actions.len(), 2);
+        assert_eq!(joined.meta().description(), Some("first"));
+        assert_eq!(joined.meta().author(), Some("b"));
+    }
+
+    #[test]
+    fn test_merge_renames_collisions() {
+        let first = program(r#"{"actions": [
+            {"id": "start", "actor": "VM", "op": "Bind", "target": "total", "params": {"value": 1}},
+            {"actor": "VM", "op": "DefineFunction", "target": "square", "params": {"args": ["n"], "body": []}}
+        ]}"#);
+        let second = program(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "square", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": {"var": "n"}}}}}
+            ]}},
+            {"actor": "VM", "op": "Bind", "target": "total", "params": {"value": {"call": "square", "args": {"n": {"var": "input"}}}}},
+            {"id": "start", "actor": "VM", "op": "If", "target": "check", "condition": {"type": "comparison", "op": ">", "left": {"var": "total"}, "right": 3},
+             "then": [{"actor": "robot", "op": "Emit", "target": "out", "params": {"content": "total"}, "caused_by": ["start"]}]}
+        ]}"#);
+
+        let merged = merge(&first, &second, "b.");
+        assert_eq!(merged.renames, BTreeMap::from([
+            ("square".to_string(), "square_2".to_string()),
+            ("start".to_string(), "start_2".to_string()),
+            ("total".to_string(), "total_2".to_string()),
+        ]));
+
+        let actions = &merged.program.actions;
+        assert_eq!(actions[0].target, "total");
+        assert_eq!(actions[2].target, "square_2");
+        assert_eq!(actions[2].actor, "b.VM");
+        assert_eq!(actions[3].params.as_ref().unwrap()["value"], json!({"call": "square_2", "args": {"n": {"var": "input"}}}));
+        let then = &actions[4].then_actions.as_ref().unwrap()[0];
+        assert_eq!(then.actor, "b.robot");
+        assert_eq!(then.params.as_ref().unwrap()["content"], json!("total_2"));
+        assert_eq!((actions[4].id.as_deref(), then.caused_by.clone()), (Some("start_2"), Some(vec!["start_2".to_string()])));
+    }
+}
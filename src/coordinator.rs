@@ -1,67 +1,361 @@
-use crate::{Action, Operation, Program};
+use crate::{Action, Operation, Program, capabilities, dag};
+use crate::error::UclError;
 use crate::compiler::RubyCompiler;
-use crate::simulator::BrainSimulator;
-use anyhow::Result;
+use crate::rng::SeededRng;
+use crate::routing::{CostModel, RoutingDecision};
+use crate::simulator::{BrainSimulator, RobotSimulator, Simulator};
+use anyhow::{Result, anyhow};
 use std::collections::HashMap;
 use std::process::Command;
 
 /// Coordinates execution across multiple substrates in parallel
 pub struct MultiSubstrateCoordinator {
     ruby_state: HashMap<String, serde_json::Value>,
-    brain_simulator: BrainSimulator,
+    /// The simulated substrates, by name (`BrainVM`, `RobotVM`)
+    simulators: HashMap<&'static str, Box<dyn Simulator>>,
+    seed: Option<u64>,
     shared_memory: HashMap<String, serde_json::Value>,
+    cost_model: Option<CostModel>,
+    routing: Vec<RoutingDecision>,
+    speculate_on: Option<String>,
+    speculations: Vec<Speculation>,
+    activity: HashMap<String, SubstrateActivity>,
+    observer: Option<StepObserver>,
+    /// Seeds for the Ruby process each RubyVM action runs in, when seeded
+    ruby_seeds: Option<SeededRng>,
+    dag: bool,
     verbose: bool,
 }
 
+/// Called after every step with the coordinator, steps done and total steps
+pub type StepObserver = Box<dyn FnMut(&MultiSubstrateCoordinator, usize, usize)>;
+
+/// Lines of recent output kept per substrate
+const ACTIVITY_HISTORY: usize = 50;
+
+/// What a substrate has been doing, for live displays
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubstrateActivity {
+    pub current: Option<String>,
+    pub output: Vec<String>,
+    pub actions: usize,
+    pub errors: usize,
+}
+
+/// One action run on two substrates, with both results
+#[derive(Debug, Clone, PartialEq)]
+pub struct Speculation {
+    pub index: usize,
+    pub op: Operation,
+    pub target: String,
+    pub primary: String,
+    pub primary_result: Option<serde_json::Value>,
+    pub shadow: String,
+    pub shadow_result: Option<serde_json::Value>,
+}
+
+/// Everything a transaction restores when it rolls back
+struct Snapshot {
+    ruby_state: HashMap<String, serde_json::Value>,
+    simulators: HashMap<&'static str, serde_json::Value>,
+    shared_memory: HashMap<String, serde_json::Value>,
+}
+
+/// The simulator that runs `substrate`'s actions: the robot's, or the
+/// brain's for any actor that isn't another substrate; Ruby and the
+/// coordinator itself have none
+fn simulator_for(substrate: &str) -> Option<&'static str> {
+    match substrate {
+        "RubyVM" | "Coordinator" => None,
+        "RobotVM" => Some("RobotVM"),
+        _ => Some("BrainVM"),
+    }
+}
+
+/// Fresh brain and robot simulators
+fn simulators(verbose: bool, seed: Option<u64>) -> HashMap<&'static str, Box<dyn Simulator>> {
+    let mut brain = BrainSimulator::new().with_verbose(verbose);
+    let mut robot = RobotSimulator::new().with_verbose(verbose);
+    if let Some(seed) = seed {
+        brain = brain.with_seed(seed);
+        robot = robot.with_seed(seed);
+    }
+    HashMap::from([
+        ("BrainVM", Box::new(brain) as Box<dyn Simulator>),
+        ("RobotVM", Box::new(robot) as Box<dyn Simulator>),
+    ])
+}
+
+/// How a Vote resolves the values reported by its sources
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VotePolicy {
+    /// A value reported by more than half of the sources
+    Majority,
+    /// The first source, in order, that has a value
+    First,
+    /// Every source must report the same value
+    Strictest,
+}
+
+impl VotePolicy {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "majority" => Ok(VotePolicy::Majority),
+            "first" => Ok(VotePolicy::First),
+            "strictest" | "unanimous" => Ok(VotePolicy::Strictest),
+            _ => Err(anyhow!("Unknown vote policy: {} (expected majority, first or strictest)", name)),
+        }
+    }
+
+    /// Pick the winning value from `(source, value)` ballots
+    pub fn resolve(&self, ballots: &[(String, Option<serde_json::Value>)]) -> Result<serde_json::Value> {
+        let cast: Vec<&serde_json::Value> = ballots.iter().filter_map(|(_, v)| v.as_ref()).collect();
+
+        match self {
+            VotePolicy::First => cast.first()
+                .map(|v| (*v).clone())
+                .ok_or_else(|| anyhow!("No source reported a value")),
+
+            VotePolicy::Majority => cast.iter()
+                .find(|candidate| {
+                    let votes = cast.iter().filter(|v| values_agree(candidate, v)).count();
+                    votes * 2 > ballots.len()
+                })
+                .map(|v| (*v).clone())
+                .ok_or_else(|| anyhow!("No majority among {} sources", ballots.len())),
+
+            VotePolicy::Strictest => {
+                if let Some((source, _)) = ballots.iter().find(|(_, v)| v.is_none()) {
+                    return Err(anyhow!("{} reported no value", source));
+                }
+                let first = *cast.first().ok_or_else(|| anyhow!("No source reported a value"))?;
+                if cast.iter().all(|v| values_agree(first, v)) {
+                    Ok(first.clone())
+                } else {
+                    Err(anyhow!("Sources disagree"))
+                }
+            }
+        }
+    }
+}
+
+/// Equal values, with numbers compared within 1e-9
+fn values_agree(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => (x - y).abs() < 1e-9,
+        _ => a == b,
+    }
+}
+
+impl Speculation {
+    /// Whether both substrates produced the same result (numbers within 1e-9)
+    pub fn agrees(&self) -> bool {
+        match (&self.primary_result, &self.shadow_result) {
+            (Some(a), Some(b)) => values_agree(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    pub fn display(&self) -> String {
+        let show = |value: &Option<serde_json::Value>| {
+            value.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "(no result)".to_string())
+        };
+        format!(
+            "#{} {:?} → {}: {} = {}, {} = {}",
+            self.index + 1, self.op, self.target,
+            self.primary, show(&self.primary_result),
+            self.shadow, show(&self.shadow_result)
+        )
+    }
+}
+
 impl MultiSubstrateCoordinator {
     pub fn new() -> Self {
         Self {
             ruby_state: HashMap::new(),
-            brain_simulator: BrainSimulator::new(),
+            simulators: simulators(false, None),
+            seed: None,
             shared_memory: HashMap::new(),
+            cost_model: None,
+            routing: Vec::new(),
+            speculate_on: None,
+            speculations: Vec::new(),
+            activity: HashMap::new(),
+            observer: None,
+            ruby_seeds: None,
+            dag: false,
             verbose: false,
         }
     }
 
     pub fn with_verbose(mut self, verbose: bool) -> Self {
         self.verbose = verbose;
-        self.brain_simulator = self.brain_simulator.with_verbose(verbose);
+        self.simulators = simulators(verbose, self.seed);
+        self
+    }
+
+    /// Route actions whose actor isn't a substrate by the cheapest cost
+    /// instead of sending them all to the brain
+    pub fn with_routing(mut self, cost_model: CostModel) -> Self {
+        self.cost_model = Some(cost_model);
+        self
+    }
+
+    /// Also run every action on `substrate` (when it supports the operation)
+    /// and compare results
+    ///
+    /// Individual actions can opt in instead with a `speculate` param naming
+    /// the second substrate.
+    pub fn with_speculation(mut self, substrate: &str) -> Self {
+        self.speculate_on = Some(substrate.to_string());
+        self
+    }
+
+    /// Dual executions from the last program
+    pub fn speculations(&self) -> &[Speculation] {
+        &self.speculations
+    }
+
+    /// Speculations whose results differ between substrates
+    pub fn divergences(&self) -> Vec<&Speculation> {
+        self.speculations.iter().filter(|s| !s.agrees()).collect()
+    }
+
+    /// Make every substrate's random draws repeat between runs
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self.simulators = simulators(self.verbose, self.seed);
+        self.ruby_seeds = Some(SeededRng::new(seed));
+        self
+    }
+
+    /// Run actions wave by wave as [`dag::execution_waves`] groups them
+    /// rather than in program order
+    pub fn with_dag(mut self, dag: bool) -> Self {
+        self.dag = dag;
+        self
+    }
+
+    /// Be notified after every executed step, e.g. to redraw a dashboard
+    pub fn with_observer(mut self, observer: StepObserver) -> Self {
+        self.observer = Some(observer);
         self
     }
 
+    /// Recent activity of a substrate (`RubyVM`, `BrainVM`, `RobotVM` or `Coordinator`)
+    pub fn activity(&self, substrate: &str) -> Option<&SubstrateActivity> {
+        self.activity.get(substrate)
+    }
+
+    pub fn shared_memory(&self) -> &HashMap<String, serde_json::Value> {
+        &self.shared_memory
+    }
+
+    /// Routing decisions made for the last program
+    pub fn routing(&self) -> &[RoutingDecision] {
+        &self.routing
+    }
+
+    /// Substrate that will execute the action at `index`
+    fn substrate_for(&mut self, index: usize, action: &Action) -> String {
+        if action.op == Operation::Parallel {
+            return "Coordinator".to_string();
+        }
+        match action.actor.as_str() {
+            "RubyVM" | "BrainVM" | "RobotVM" | "Coordinator" => action.actor.clone(),
+            _ => match &self.cost_model {
+                Some(model) => {
+                    let decision = model.route(index, action);
+                    let substrate = decision.substrate.clone();
+                    self.routing.push(decision);
+                    substrate
+                }
+                None => "BrainVM".to_string(), // Default to brain
+            },
+        }
+    }
+
+    /// Run one action on its substrate outside a whole-program run, returning
+    /// the substrate that ran it
+    pub fn dispatch(&mut self, index: usize, action: &Action) -> Result<String> {
+        let substrate = self.substrate_for(index, action);
+        self.execute_on(index, &substrate, action)?;
+        Ok(substrate)
+    }
+
     pub fn execute(&mut self, program: &Program) -> Result<()> {
         if self.verbose {
             println!("🌐 Multi-Substrate Parallel Execution Engine");
             println!();
         }
 
-        // Separate actions by actor (substrate)
-        let mut ruby_actions = Vec::new();
-        let mut brain_actions = Vec::new();
-        let mut coordinator_actions = Vec::new();
+        self.routing.clear();
+        self.speculations.clear();
+        self.activity.clear();
+        let substrates: Vec<String> = program.actions.iter()
+            .enumerate()
+            .map(|(index, action)| self.substrate_for(index, action))
+            .collect();
 
-        for action in &program.actions {
-            match action.actor.as_str() {
-                "RubyVM" => ruby_actions.push(action),
-                "BrainVM" => brain_actions.push(action),
-                "Coordinator" => coordinator_actions.push(action),
-                _ => brain_actions.push(action), // Default to brain
-            }
-        }
+        // Separate actions by substrate
+        let count = |name: &str| substrates.iter().filter(|s| *s == name).count();
 
         if self.verbose {
             println!("📊 Execution Plan:");
-            println!("   💎 Ruby VM: {} operations", ruby_actions.len());
-            println!("   🧠 Brain VM: {} operations", brain_actions.len());
-            println!("   🌐 Coordinator: {} operations", coordinator_actions.len());
+            println!("   💎 Ruby VM: {} operations", count("RubyVM"));
+            println!("   🧠 Brain VM: {} operations", count("BrainVM"));
+            println!("   🦾 Robot VM: {} operations", count("RobotVM"));
+            println!("   🌐 Coordinator: {} operations", count("Coordinator"));
+            if !self.routing.is_empty() {
+                println!("   🧭 Auto-routed: {} operations", self.routing.len());
+            }
             println!();
         }
 
-        // Execute in original order, switching substrates as needed
+        // Execute in original order, or wave by wave, switching substrates as needed
+        let order: Vec<(usize, usize)> = if self.dag {
+            if program.actions.iter().any(|a| a.params.as_ref().is_some_and(|p| p.contains_key("transaction"))) {
+                return Err(anyhow!("Transactions need their actions in program order; run without DAG mode"));
+            }
+            dag::execution_waves(program).into_iter().enumerate()
+                .flat_map(|(wave, indices)| indices.into_iter().map(move |index| (wave, index)))
+                .collect()
+        } else {
+            (0..program.actions.len()).map(|index| (0, index)).collect()
+        };
         let mut current_substrate = "";
+        let mut current_wave = None;
+
+        // Consecutive actions sharing a `transaction` param succeed or fail together
+        let mut open: Option<(String, Snapshot)> = None;
 
-        for action in &program.actions {
-            let substrate = action.actor.as_str();
+        for (step, &(wave, index)) in order.iter().enumerate() {
+            let (action, substrate) = (&program.actions[index], substrates[index].as_str());
+
+            if self.dag && current_wave != Some(wave) {
+                if self.verbose {
+                    let size = order.iter().filter(|(w, _)| *w == wave).count();
+                    println!("🔀 Wave {}: {} independent action(s)", wave + 1, size);
+                }
+                current_wave = Some(wave);
+            }
+
+            let transaction = action.params.as_ref()
+                .and_then(|p| p.get("transaction"))
+                .and_then(|v| v.as_str());
+
+            if open.as_ref().map(|(name, _)| name.as_str()) != transaction {
+                if let Some((name, _)) = open.take() {
+                    if self.verbose {
+                        println!("   ✅ Committed transaction '{}'", name);
+                    }
+                }
+                open = match transaction {
+                    Some(name) => Some((name.to_string(), self.snapshot()?)),
+                    None => None,
+                };
+            }
 
             if substrate != current_substrate {
                 if self.verbose && !current_substrate.is_empty() {
@@ -70,84 +364,312 @@ impl MultiSubstrateCoordinator {
                 current_substrate = substrate;
             }
 
-            match substrate {
-                "RubyVM" => self.execute_ruby_action(action)?,
-                "BrainVM" => self.execute_brain_action(action)?,
-                "Coordinator" => self.execute_coordinator_action(action)?,
-                _ => self.execute_brain_action(action)?,
+            let result = self.execute_on(index, substrate, action).and_then(|_| {
+                match self.shadow_for(substrate, action) {
+                    Some(shadow) => self.speculate(index, substrate, &shadow, action),
+                    None => Ok(()),
+                }
+            });
+
+            if let Some(mut observer) = self.observer.take() {
+                observer(self, step + 1, program.actions.len());
+                self.observer = Some(observer);
+            }
+
+            if let Err(source) = result {
+                let transaction = match open.take() {
+                    Some((name, snapshot)) => {
+                        self.restore(snapshot)?;
+                        if self.verbose {
+                            println!("   ↩️  Rolled back transaction '{}'", name);
+                        }
+                        Some(name)
+                    }
+                    None => None,
+                };
+                return Err(UclError::SubstrateFailure {
+                    substrate: substrate.to_string(),
+                    action: index + 1,
+                    transaction,
+                    source,
+                }.into());
+            }
+        }
+
+        if let Some((name, _)) = open {
+            if self.verbose {
+                println!("   ✅ Committed transaction '{}'", name);
             }
         }
 
         Ok(())
     }
 
-    fn execute_ruby_action(&mut self, action: &Action) -> Result<()> {
-        if self.verbose {
-            println!("💎 Ruby VM: {:?} → {}", action.op, action.target);
+    fn snapshot(&self) -> Result<Snapshot> {
+        let simulators = self.simulators.iter()
+            .map(|(name, simulator)| Ok((*name, simulator.snapshot()?)))
+            .collect::<Result<_>>()?;
+        Ok(Snapshot {
+            ruby_state: self.ruby_state.clone(),
+            simulators,
+            shared_memory: self.shared_memory.clone(),
+        })
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) -> Result<()> {
+        self.ruby_state = snapshot.ruby_state;
+        for (name, state) in snapshot.simulators {
+            if let Some(simulator) = self.simulators.get_mut(name) {
+                simulator.restore(state)?;
+            }
         }
+        self.shared_memory = snapshot.shared_memory;
+        Ok(())
+    }
+
+    /// How many output lines and errors `substrate`'s simulator has, to
+    /// pick out the new ones with [`said_since`](Self::said_since)
+    fn marks(&self, substrate: &str) -> (usize, usize) {
+        let simulator = simulator_for(substrate).and_then(|name| self.simulators.get(name));
+        simulator.map_or((0, 0), |simulator| (simulator.output().len(), simulator.errors().len()))
+    }
+
+    /// Output lines and the number of errors `substrate`'s simulator has
+    /// added since [`marks`](Self::marks) gave `(output, errors)`
+    fn said_since(&self, substrate: &str, (output, errors): (usize, usize)) -> (Vec<String>, usize) {
+        match simulator_for(substrate).and_then(|name| self.simulators.get(name)) {
+            Some(simulator) => (
+                simulator.output().get(output..).unwrap_or_default().to_vec(),
+                simulator.errors().len().saturating_sub(errors),
+            ),
+            None => (Vec::new(), 0),
+        }
+    }
+
+    /// Run `action`, part of the top-level action at `index`, on `substrate`
+    fn execute_on(&mut self, index: usize, substrate: &str, action: &Action) -> Result<()> {
+        let marks = self.marks(substrate);
 
-        // Build a mini program for this action
-        let program = Program {
-            metadata: None,
-            actions: vec![action.clone()],
+        let result = match substrate {
+            "RubyVM" => self.execute_ruby_action(action),
+            "Coordinator" => self.execute_coordinator_action(index, action),
+            _ => self.execute_simulated(substrate, action),
         };
 
-        let mut compiler = RubyCompiler::new();
-        let code = compiler.compile(&program)?;
+        // Prefer what the substrate said; fall back to the value it now holds
+        let (mut lines, new_errors) = self.said_since(substrate, marks);
+        if lines.is_empty() {
+            let value = match substrate {
+                "Coordinator" => self.shared_memory.get(&action.target).cloned(),
+                _ => self.result_of(substrate, &action.target),
+            };
+            lines.extend(value.map(|v| format!("{} = {}", action.target, v)));
+        }
+        if let Err(e) = &result {
+            lines.push(format!("❌ {}", e));
+        }
 
-        // Execute and capture the result
-        let output = Command::new("ruby")
-            .arg("-e")
-            .arg(&code)
-            .output()?;
+        self.record(substrate, action, 1, new_errors + result.is_err() as usize, lines);
 
-        if !output.stdout.is_empty() {
-            let result_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        result
+    }
 
-            // Store in ruby state
-            if let Ok(num) = result_str.parse::<f64>() {
-                self.ruby_state.insert(action.target.clone(), serde_json::json!(num));
+    /// Note that `substrate` ran `actions` actions, the last being `action`
+    fn record(&mut self, substrate: &str, action: &Action, actions: usize, errors: usize, lines: Vec<String>) {
+        let activity = self.activity.entry(substrate.to_string()).or_default();
+        activity.current = Some(format!("{:?} → {}", action.op, action.target));
+        activity.actions += actions;
+        activity.errors += errors;
+        activity.output.extend(lines);
+        let excess = activity.output.len().saturating_sub(ACTIVITY_HISTORY);
+        activity.output.drain(..excess);
+    }
 
-                if self.verbose {
-                    println!("   ✓ Result: {} = {}", action.target, num);
-                }
-            } else if !result_str.is_empty() {
-                self.ruby_state.insert(action.target.clone(), serde_json::json!(result_str));
+    /// [`record`](Self::record) a parallel lane, falling back to the values
+    /// its targets now hold if the substrate said nothing
+    fn record_lane(&mut self, substrate: &str, lane: &Program, errors: usize, mut lines: Vec<String>) {
+        let Some(last) = lane.actions.last() else { return };
+        if lines.is_empty() {
+            lines = lane.actions.iter()
+                .filter_map(|action| self.result_of(substrate, &action.target).map(|v| format!("{} = {}", action.target, v)))
+                .collect();
+        }
+        self.record(substrate, last, lane.actions.len(), errors, lines);
+    }
 
-                if self.verbose {
-                    println!("   ✓ Output: {}", result_str);
+    /// Run a `Parallel`'s body with each substrate's share on its own thread
+    ///
+    /// Each substrate runs its actions in order, all of them at once;
+    /// coordinator actions run after the substrates have finished.
+    fn execute_parallel(&mut self, index: usize, action: &Action) -> Result<()> {
+        let body = action.body_actions.as_deref().unwrap_or_default();
+        let mut lanes: HashMap<String, Vec<Action>> = HashMap::new();
+        for nested in body {
+            let substrate = self.substrate_for(index, nested);
+            lanes.entry(substrate).or_default().push(nested.clone());
+        }
+        let mut lane = |name: &str| Program { metadata: None, actions: lanes.remove(name).unwrap_or_default() };
+        let (brain_lane, robot_lane, ruby_lane, coordinator_lane) =
+            (lane("BrainVM"), lane("RobotVM"), lane("RubyVM"), lane("Coordinator"));
+
+        if self.verbose {
+            println!("   ⏸️  Parallel: {} brain, {} robot and {} Ruby action(s) at once",
+                brain_lane.actions.len(), robot_lane.actions.len(), ruby_lane.actions.len());
+        }
+
+        let seeds: Vec<Option<u64>> = ruby_lane.actions.iter().map(|_| self.ruby_seeds.as_mut().map(|s| s.next_u64())).collect();
+        let marks: HashMap<&str, (usize, usize)> = self.simulators.keys().map(|&name| (name, self.marks(name))).collect();
+        let mut simulated_lanes = HashMap::from([("BrainVM", brain_lane), ("RobotVM", robot_lane)]);
+
+        let (mut results, ruby_results) = std::thread::scope(|scope| {
+            let simulated: Vec<_> = self.simulators.iter_mut()
+                .map(|(&name, simulator)| {
+                    let lane = &simulated_lanes[name];
+                    (name, scope.spawn(move || simulator.execute(lane)))
+                })
+                .collect();
+            let ruby = scope.spawn(|| ruby_lane.actions.iter().zip(&seeds)
+                .map(|(action, seed)| run_ruby(action, *seed))
+                .collect::<Vec<_>>());
+            let results: HashMap<&str, Result<()>> = simulated.into_iter().map(|(name, lane)| (name, join(lane))).collect();
+            (results, join(ruby))
+        });
+
+        let mut failures = Vec::new();
+        for name in ["BrainVM", "RobotVM"] {
+            let (Some(result), Some(lane)) = (results.remove(name), simulated_lanes.remove(name)) else { continue };
+            let (lines, errors) = self.said_since(name, marks[name]);
+            self.record_lane(name, &lane, errors + result.is_err() as usize, lines);
+            failures.extend(result.err().map(|e| (name, e)));
+        }
+
+        for (action, result) in ruby_lane.actions.iter().zip(ruby_results) {
+            let (lines, errors) = match result {
+                Ok(Some(value)) => {
+                    let line = format!("{} = {}", action.target, value);
+                    self.ruby_state.insert(action.target.clone(), value);
+                    (vec![line], 0)
                 }
-            }
+                Ok(None) => (Vec::new(), 0),
+                Err(e) => {
+                    let line = format!("❌ {}", e);
+                    failures.push(("RubyVM", e));
+                    (vec![line], 1)
+                }
+            };
+            self.record("RubyVM", action, 1, errors, lines);
         }
 
+        if let Some((substrate, error)) = failures.into_iter().next() {
+            return Err(error.context(format!("{} failed in Parallel {}", substrate, action.target)));
+        }
+        for nested in &coordinator_lane.actions {
+            self.execute_on(index, "Coordinator", nested)?;
+        }
         Ok(())
     }
 
-    fn execute_brain_action(&mut self, action: &Action) -> Result<()> {
+    /// Second substrate to run `action` on, if speculation applies to it
+    fn shadow_for(&self, substrate: &str, action: &Action) -> Option<String> {
+        if substrate == "Coordinator" {
+            return None;
+        }
+
+        let requested = action.params.as_ref()
+            .and_then(|p| p.get("speculate"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        // The coordinator-wide option only covers operations the shadow can run
+        let shadow = requested.or_else(|| {
+            self.speculate_on.clone()
+                .filter(|shadow| capabilities::supports(shadow, &action.op))
+        })?;
+
+        (shadow != substrate).then_some(shadow)
+    }
+
+    fn speculate(&mut self, index: usize, primary: &str, shadow: &str, action: &Action) -> Result<()> {
+        let primary_result = self.result_of(primary, &action.target);
+
         if self.verbose {
-            println!("🧠 Brain VM: {:?} → {}", action.op, action.target);
+            println!("   🔀 Speculating on {}", shadow);
         }
+        self.execute_on(index, shadow, action)?;
 
-        let program = Program {
-            metadata: None,
-            actions: vec![action.clone()],
+        let speculation = Speculation {
+            index,
+            op: action.op.clone(),
+            target: action.target.clone(),
+            primary: primary.to_string(),
+            primary_result,
+            shadow: shadow.to_string(),
+            shadow_result: self.result_of(shadow, &action.target),
         };
 
-        self.brain_simulator.execute(&program)?;
+        if self.verbose {
+            if speculation.agrees() {
+                println!("   ✓ Substrates agree");
+            } else {
+                println!("   ⚠️  Divergence: {}", speculation.display());
+            }
+        }
+
+        self.speculations.push(speculation);
+        Ok(())
+    }
+
+    /// The value a substrate holds for `target` after executing an action
+    fn result_of(&self, substrate: &str, target: &str) -> Option<serde_json::Value> {
+        match simulator_for(substrate) {
+            Some(name) => self.simulators.get(name)?.value(target),
+            None => self.ruby_state.get(target).cloned(),
+        }
+    }
+
+    fn execute_ruby_action(&mut self, action: &Action) -> Result<()> {
+        if self.verbose {
+            println!("💎 Ruby VM: {:?} → {}", action.op, action.target);
+        }
+
+        let seed = self.ruby_seeds.as_mut().map(|seeds| seeds.next_u64());
+        if let Some(value) = run_ruby(action, seed)? {
+            if self.verbose {
+                match &value {
+                    serde_json::Value::String(output) => println!("   ✓ Output: {}", output),
+                    number => println!("   ✓ Result: {} = {}", action.target, number),
+                }
+            }
+            self.ruby_state.insert(action.target.clone(), value);
+        }
+
+        Ok(())
+    }
+
+    /// Run `action` on the simulator for `substrate`
+    fn execute_simulated(&mut self, substrate: &str, action: &Action) -> Result<()> {
+        let name = simulator_for(substrate).ok_or_else(|| anyhow!("{} isn't simulated", substrate))?;
+        let (heading, stored) = match name {
+            "RobotVM" => ("🦾 Robot VM", "Robot recorded"),
+            _ => ("🧠 Brain VM", "Brain stored"),
+        };
+        if self.verbose {
+            println!("{}: {:?} → {}", heading, action.op, action.target);
+        }
 
-        // Check if brain generated output or stored a value
-        let brain_state = self.brain_simulator.state();
+        let simulator = self.simulators.get_mut(name).ok_or_else(|| anyhow!("No {} simulator", name))?;
+        simulator.execute_action(action)?;
 
-        if let Some(value) = brain_state.beliefs.get(&action.target) {
+        if let Some(value) = simulator.value(&action.target) {
             if self.verbose {
-                println!("   ✓ Brain stored: {} = {}", action.target, value);
+                println!("   ✓ {}: {} = {}", stored, action.target, value);
             }
         }
 
         Ok(())
     }
 
-    fn execute_coordinator_action(&mut self, action: &Action) -> Result<()> {
+    fn execute_coordinator_action(&mut self, index: usize, action: &Action) -> Result<()> {
         if self.verbose {
             println!("🌐 Coordinator: {:?} → {}", action.op, action.target);
         }
@@ -159,13 +681,21 @@ impl MultiSubstrateCoordinator {
                     if let Some(source) = params.get("source").and_then(|v| v.as_str()) {
                         if source == "BrainVM" {
                             // Get value from brain
-                            if let Some(value) = self.brain_simulator.state().beliefs.get(&action.target) {
+                            if let Some(value) = self.result_of("BrainVM", &action.target) {
                                 self.shared_memory.insert(action.target.clone(), value.clone());
 
                                 if self.verbose {
                                     println!("   📨 Received from Brain: {} = {}", action.target, value);
                                 }
                             }
+                        } else if source == "RobotVM" {
+                            if let Some(value) = self.result_of("RobotVM", &action.target) {
+                                self.shared_memory.insert(action.target.clone(), value.clone());
+
+                                if self.verbose {
+                                    println!("   📨 Received from Robot: {} = {}", action.target, value);
+                                }
+                            }
                         } else if source == "RubyVM" {
                             // Get value from ruby
                             if let Some(value) = self.ruby_state.get(&action.target) {
@@ -200,6 +730,8 @@ impl MultiSubstrateCoordinator {
                     }
                 }
             }
+            Operation::Vote => self.vote(action)?,
+            Operation::Parallel => self.execute_parallel(index, action)?,
             _ => {
                 if self.verbose {
                     println!("   ⚠️  Unsupported coordinator operation");
@@ -210,6 +742,50 @@ impl MultiSubstrateCoordinator {
         Ok(())
     }
 
+    /// Gather `target` from several substrates and store the agreed value
+    /// in shared memory
+    fn vote(&mut self, action: &Action) -> Result<()> {
+        let params = action.params.as_ref();
+
+        let sources: Vec<String> = match params.and_then(|p| p.get("sources")).and_then(|v| v.as_array()) {
+            Some(sources) => sources.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect(),
+            None => ["RubyVM", "BrainVM", "RobotVM"].iter().map(|s| s.to_string()).collect(),
+        };
+        if sources.is_empty() {
+            return Err(anyhow!("Vote on {} requires at least one source", action.target));
+        }
+
+        let policy = VotePolicy::parse(
+            params.and_then(|p| p.get("policy")).and_then(|v| v.as_str()).unwrap_or("majority")
+        )?;
+
+        let ballots: Vec<(String, Option<serde_json::Value>)> = sources.into_iter()
+            .map(|source| {
+                let value = self.result_of(&source, &action.target);
+                (source, value)
+            })
+            .collect();
+
+        if self.verbose {
+            for (source, value) in &ballots {
+                match value {
+                    Some(value) => println!("   🗳️  {}: {}", source, value),
+                    None => println!("   🗳️  {}: (abstains)", source),
+                }
+            }
+        }
+
+        let winner = policy.resolve(&ballots)
+            .map_err(|e| anyhow!("Vote on {} failed ({:?}): {}", action.target, policy, e))?;
+
+        if self.verbose {
+            println!("   ✓ Consensus ({:?}): {} = {}", policy, action.target, winner);
+        }
+
+        self.shared_memory.insert(action.target.clone(), winner);
+        Ok(())
+    }
+
     pub fn show_results(&self) {
         println!("\n📊 Final State Across All Substrates:");
         println!("{}", "─".repeat(60));
@@ -221,27 +797,27 @@ impl MultiSubstrateCoordinator {
             }
         }
 
-        println!("\n🧠 Brain VM State:");
-        let brain_state = self.brain_simulator.state();
-
-        if !brain_state.beliefs.is_empty() {
-            println!("   Beliefs:");
-            for (key, value) in &brain_state.beliefs {
-                println!("     {} = {}", key, value);
+        if !self.speculations.is_empty() {
+            let divergences = self.divergences();
+            println!("\n🔀 Speculative Execution: {} agreed, {} diverged",
+                self.speculations.len() - divergences.len(), divergences.len());
+            for speculation in divergences {
+                println!("   ⚠️  {}", speculation.display());
             }
         }
 
-        if !brain_state.thoughts.is_empty() {
-            println!("   Thoughts:");
-            for thought in &brain_state.thoughts {
-                println!("     💭 {}", thought);
+        if !self.routing.is_empty() {
+            println!("\n🧭 Routing Decisions:");
+            for decision in &self.routing {
+                println!("   {}", decision.display());
             }
         }
 
-        if !brain_state.output.is_empty() {
-            println!("   Output:");
-            for out in &brain_state.output {
-                println!("     🗣️  {}", out);
+        // The robot only once it has done something
+        for (name, heading) in [("BrainVM", "🧠 Brain VM State:"), ("RobotVM", "🦾 Robot VM State:")] {
+            if let Some(simulator) = self.simulators.get(name).filter(|s| name == "BrainVM" || !s.output().is_empty()) {
+                println!("\n{}", heading);
+                println!("{}", simulator.display());
             }
         }
 
@@ -254,9 +830,226 @@ impl MultiSubstrateCoordinator {
     }
 }
 
+/// A parallel lane's result, passing on its panic if it had one
+fn join<T>(lane: std::thread::ScopedJoinHandle<'_, T>) -> T {
+    lane.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Compile `action` on its own and run it with `ruby`, returning what it
+/// printed: a number if it parses as one, else the text, or nothing
+fn run_ruby(action: &Action, seed: Option<u64>) -> Result<Option<serde_json::Value>> {
+    let program = Program {
+        metadata: None,
+        actions: vec![action.clone()],
+    };
+
+    let mut compiler = match seed {
+        Some(seed) => RubyCompiler::new().with_seed(seed),
+        None => RubyCompiler::new(),
+    };
+    let code = compiler.compile(&program)?;
+
+    let output = Command::new("ruby")
+        .arg("-e")
+        .arg(&code)
+        .output()?;
+
+    let result = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(match result.parse::<f64>() {
+        Ok(num) => Some(serde_json::json!(num)),
+        Err(_) if !result.is_empty() => Some(serde_json::json!(result)),
+        Err(_) => None,
+    })
+}
+
 impl Default for MultiSubstrateCoordinator {
     fn default() -> Self {
         Self::new()
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speculation(primary: serde_json::Value, shadow: Option<serde_json::Value>) -> Speculation {
+        Speculation {
+            index: 0,
+            op: Operation::Write,
+            target: "x".to_string(),
+            primary: "BrainVM".to_string(),
+            primary_result: Some(primary),
+            shadow: "RobotVM".to_string(),
+            shadow_result: shadow,
+        }
+    }
+
+    #[test]
+    fn test_speculation_comparison() {
+        assert!(speculation(serde_json::json!(6), Some(serde_json::json!(6.0))).agrees());
+        assert!(!speculation(serde_json::json!(6), Some(serde_json::json!(7))).agrees());
+        assert!(!speculation(serde_json::json!(6), None).agrees());
+    }
+
+    #[test]
+    fn test_speculate_param_runs_on_both_substrates() {
+        let json = r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "answer",
+             "params": {"value": 42, "speculate": "RobotVM"}}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let mut coordinator = MultiSubstrateCoordinator::new();
+        coordinator.execute(&program).unwrap();
+
+        assert_eq!(coordinator.speculations().len(), 1);
+        let speculation = &coordinator.speculations()[0];
+        assert_eq!(speculation.shadow, "RobotVM");
+        assert!(speculation.agrees(), "{}", speculation.display());
+    }
+
+    fn ballots(values: &[Option<serde_json::Value>]) -> Vec<(String, Option<serde_json::Value>)> {
+        values.iter().enumerate().map(|(i, v)| (format!("S{}", i), v.clone())).collect()
+    }
+
+    #[test]
+    fn test_vote_policies() {
+        use serde_json::json;
+        let split = ballots(&[Some(json!(4)), Some(json!(4.0)), Some(json!(5))]);
+
+        assert_eq!(VotePolicy::Majority.resolve(&split).unwrap(), json!(4));
+        assert_eq!(VotePolicy::First.resolve(&ballots(&[None, Some(json!(5))])).unwrap(), json!(5));
+        assert!(VotePolicy::Strictest.resolve(&split).is_err());
+        assert!(VotePolicy::Strictest.resolve(&[]).is_err());
+        assert!(VotePolicy::Majority.resolve(&ballots(&[Some(json!(1)), Some(json!(2))])).is_err());
+    }
+
+    #[test]
+    fn test_vote_stores_consensus_in_shared_memory() {
+        let json = r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "x", "params": {"value": 7}},
+            {"actor": "RobotVM", "op": "Bind", "target": "x", "params": {"value": 7}},
+            {"actor": "Coordinator", "op": "Vote", "target": "x",
+             "params": {"sources": ["BrainVM", "RobotVM"], "policy": "strictest"}}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let mut coordinator = MultiSubstrateCoordinator::new();
+        coordinator.execute(&program).unwrap();
+
+        assert_eq!(coordinator.shared_memory.get("x"), Some(&serde_json::json!(7)));
+    }
+
+    #[test]
+    fn test_dag_mode_runs_waves() {
+        let json = r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "x", "params": {"value": 7}},
+            {"actor": "BrainVM", "op": "Bind", "target": "y", "params": {"value": 1}},
+            {"actor": "RobotVM", "op": "Bind", "target": "x", "params": {"value": 7}},
+            {"actor": "Coordinator", "op": "Vote", "target": "x", "params": {"sources": ["BrainVM", "RobotVM"]}}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+        assert_eq!(dag::execution_waves(&program), vec![vec![0], vec![1, 2], vec![3]]);
+
+        let steps = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let seen = steps.clone();
+        let mut coordinator = MultiSubstrateCoordinator::new().with_dag(true)
+            .with_observer(Box::new(move |_, done, total| seen.borrow_mut().push((done, total))));
+        coordinator.execute(&program).unwrap();
+        assert_eq!(coordinator.shared_memory().get("x"), Some(&serde_json::json!(7)));
+        assert_eq!(*steps.borrow(), vec![(1, 4), (2, 4), (3, 4), (4, 4)]);
+
+        let transaction = Program::from_json(r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "x", "params": {"value": 1, "transaction": "t"}}
+        ]}"#).unwrap();
+        assert!(MultiSubstrateCoordinator::new().with_dag(true).execute(&transaction).is_err());
+    }
+
+    #[test]
+    fn test_parallel_runs_each_substrate_at_once() {
+        let json = r#"{"actions": [
+            {"actor": "kitchen", "op": "Parallel", "target": "tea", "body": [
+                {"actor": "RobotVM", "op": "Heat", "target": "kettle", "params": {"temperature": "100C"}},
+                {"actor": "BrainVM", "op": "Bind", "target": "cup", "params": {"value": "ready"}},
+                {"actor": "RobotVM", "op": "Bind", "target": "boiled", "params": {"value": true}},
+                {"actor": "Coordinator", "op": "Receive", "target": "cup", "params": {"source": "BrainVM"}}
+            ]}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let mut coordinator = MultiSubstrateCoordinator::new();
+        coordinator.execute(&program).unwrap();
+
+        assert_eq!(coordinator.shared_memory()["cup"], serde_json::json!("ready"));
+        let brain = coordinator.activity("BrainVM").unwrap();
+        assert_eq!(brain.output, vec!["cup = \"ready\""]);
+        let robot = coordinator.activity("RobotVM").unwrap();
+        assert_eq!((robot.actions, robot.current.as_deref()), (2, Some("Bind → boiled")));
+        assert_eq!(coordinator.activity("Coordinator").unwrap().actions, 2);
+
+        let failing = Program::from_json(r#"{"actions": [{"actor": "Coordinator", "op": "Parallel", "target": "tea", "body": [
+            {"actor": "BrainVM", "op": "Throw", "target": "spilled"}
+        ]}]}"#).unwrap();
+        let error = format!("{:#}", MultiSubstrateCoordinator::new().execute(&failing).unwrap_err());
+        assert!(error.contains("BrainVM failed in Parallel tea"), "{}", error);
+    }
+
+    #[test]
+    fn test_routing_inside_parallel_names_the_parallel() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "x", "params": {"value": 1}},
+            {"actor": "Coordinator", "op": "Parallel", "target": "both", "body": [
+                {"actor": "someone", "op": "Bind", "target": "y", "params": {"value": 2}},
+                {"actor": "someone", "op": "Bind", "target": "z", "params": {"value": 3}}
+            ]}
+        ]}"#).unwrap();
+
+        let mut coordinator = MultiSubstrateCoordinator::new()
+            .with_routing(CostModel::default().with_cost("RobotVM", "Bind", 0.5));
+        coordinator.execute(&program).unwrap();
+
+        let routed: Vec<(usize, &str)> = coordinator.routing().iter().map(|d| (d.index, d.target.as_str())).collect();
+        assert_eq!(routed, vec![(1, "y"), (1, "z")]);
+    }
+
+    #[test]
+    fn test_activity_tracks_each_substrate() {
+        let json = r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "x", "params": {"value": 3}},
+            {"actor": "RobotVM", "op": "Measure", "target": "water", "params": {"amount": "100ml"}}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let mut coordinator = MultiSubstrateCoordinator::new();
+        coordinator.execute(&program).unwrap();
+
+        let brain = coordinator.activity("BrainVM").unwrap();
+        assert_eq!(brain.current.as_deref(), Some("Bind → x"));
+        assert_eq!(brain.output, vec!["x = 3"]);
+        let robot = coordinator.activity("RobotVM").unwrap();
+        assert_eq!(robot.actions, 1);
+        assert_eq!(robot.errors, 0);
+    }
+
+    #[test]
+    fn test_failed_transaction_rolls_back() {
+        let json = r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "before", "params": {"value": 1}},
+            {"actor": "BrainVM", "op": "Bind", "target": "inside", "params": {"value": 2, "transaction": "t"}},
+            {"actor": "Coordinator", "op": "Receive", "target": "inside",
+             "params": {"source": "BrainVM", "transaction": "t"}},
+            {"actor": "Coordinator", "op": "Vote", "target": "missing",
+             "params": {"sources": ["BrainVM"], "policy": "strictest", "transaction": "t"}}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let mut coordinator = MultiSubstrateCoordinator::new();
+        let error = coordinator.execute(&program).unwrap_err();
+
+        assert!(error.to_string().contains("transaction 't' rolled back"));
+        assert!(matches!(error.downcast_ref(), Some(UclError::SubstrateFailure { action: 4, .. })));
+        assert!(coordinator.result_of("BrainVM", "before").is_some());
+        assert!(coordinator.result_of("BrainVM", "inside").is_none());
+        assert!(coordinator.shared_memory.is_empty());
+    }
+}
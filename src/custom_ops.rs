@@ -0,0 +1,222 @@
+//! Namespaced custom operations (`{"Custom": "music:Arpeggiate"}`) and their
+//! declared semantics
+//!
+//! Declarations live in `metadata.custom_ops`, or in a sidecar JSON file with
+//! the same shape:
+//!
+//! ```json
+//! {"music:Arpeggiate": {
+//!     "description": "Play a chord one note at a time",
+//!     "params": {"chord": {"type": "string", "required": true}, "speed": {"type": "number"}},
+//!     "effects": ["Audio"]
+//! }}
+//! ```
+
+use crate::metadata::{PARAMETER_TYPES, has_type};
+use crate::{Operation, Program};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Metadata key holding custom op declarations
+pub const CUSTOM_OPS_KEY: &str = "custom_ops";
+
+/// Split `music:Arpeggiate` into `("music", "Arpeggiate")`
+pub fn split(name: &str) -> Option<(&str, &str)> {
+    name.split_once(':').filter(|(ns, op)| !ns.is_empty() && !op.is_empty())
+}
+
+/// Namespace of a custom operation, if it has one
+pub fn namespace(op: &Operation) -> Option<&str> {
+    match op {
+        Operation::Custom(name) => split(name).map(|(ns, _)| ns),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParamSpec {
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    pub description: Option<String>,
+}
+
+/// What a custom operation expects and does
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpDeclaration {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamSpec>,
+    #[serde(default)]
+    pub effects: Vec<String>,
+}
+
+/// Declared custom operations by full name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Declarations {
+    pub ops: BTreeMap<String, OpDeclaration>,
+}
+
+/// A custom operation usage that doesn't match its declaration
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomOpWarning {
+    /// `metadata`, or `action N` counting nested actions in walk order
+    pub location: String,
+    pub op: String,
+    pub message: String,
+}
+
+impl CustomOpWarning {
+    pub fn display(&self) -> String {
+        format!("{} {}: {}", self.location, self.op, self.message)
+    }
+}
+
+impl Declarations {
+    fn parse(value: &serde_json::Value) -> Result<Self> {
+        Ok(Self { ops: serde_json::from_value(value.clone())? })
+    }
+
+    /// Declarations in `metadata.custom_ops`
+    pub fn from_program(program: &Program) -> Result<Self> {
+        match program.meta().get(CUSTOM_OPS_KEY) {
+            Some(value) => Self::parse(value).context("Invalid metadata.custom_ops"),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Declarations in a sidecar JSON file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        let value: serde_json::Value = serde_json::from_str(&content)?;
+        Self::parse(&value).with_context(|| format!("Invalid custom op declarations in {}", path.display()))
+    }
+
+    /// Add `other`'s declarations, which win on conflict
+    pub fn merge(&mut self, other: Declarations) {
+        self.ops.extend(other.ops);
+    }
+
+    /// Check every custom operation in `program` against its declaration
+    pub fn check(&self, program: &Program) -> Vec<CustomOpWarning> {
+        let mut warnings = Vec::new();
+
+        for (name, declaration) in &self.ops {
+            if split(name).is_none() {
+                warnings.push(CustomOpWarning { location: "metadata".into(), op: name.clone(), message: "declared without a namespace (use ns:Name)".into() });
+            }
+            for (param, spec) in &declaration.params {
+                if let Some(kind) = spec.kind.as_deref().filter(|k| !PARAMETER_TYPES.contains(k)) {
+                    warnings.push(CustomOpWarning { location: "metadata".into(), op: name.clone(), message: format!("param '{}' has unknown type '{}'", param, kind) });
+                }
+            }
+        }
+
+        let mut index = 0;
+        program.walk(&mut |action, _| {
+            index += 1;
+            let Operation::Custom(name) = &action.op else { return };
+            let location = format!("action {}", index);
+            let mut warn = |message: String| warnings.push(CustomOpWarning { location: location.clone(), op: name.clone(), message });
+
+            if split(name).is_none() {
+                warn("has no namespace (use ns:Name)".into());
+            }
+            let Some(declaration) = self.ops.get(name) else {
+                warn("is not declared".into());
+                return;
+            };
+
+            let params = action.params.as_ref();
+            for (param, spec) in &declaration.params {
+                match params.and_then(|p| p.get(param)) {
+                    None if spec.required => warn(format!("missing required param '{}'", param)),
+                    Some(value) => {
+                        if let Some(kind) = &spec.kind {
+                            if PARAMETER_TYPES.contains(&kind.as_str()) && !has_type(value, kind) {
+                                warn(format!("param '{}' should be a {}, got {}", param, kind, value));
+                            }
+                        }
+                    }
+                    None => {}
+                }
+            }
+
+            let mut undeclared: Vec<&String> = params.into_iter().flatten()
+                .map(|(key, _)| key)
+                .filter(|key| !declaration.params.contains_key(*key))
+                .collect();
+            undeclared.sort();
+            for key in undeclared {
+                warn(format!("unknown param '{}'", key));
+            }
+
+            let tagged = action.effects.as_deref().unwrap_or_default();
+            for effect in &declaration.effects {
+                if !tagged.iter().any(|e| e.eq_ignore_ascii_case(effect)) {
+                    warn(format!("missing declared effect '{}'", effect));
+                }
+            }
+        });
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        Program::from_json(r#"{
+            "metadata": {"custom_ops": {
+                "music:Arpeggiate": {
+                    "params": {"chord": {"type": "string", "required": true}, "speed": {"type": "number"}},
+                    "effects": ["Audio"]
+                }
+            }},
+            "actions": [
+                {"actor": "Piano", "op": {"Custom": "music:Arpeggiate"}, "target": "C", "params": {"chord": "Cmaj", "speed": 2}, "effects": ["Audio"]},
+                {"actor": "Piano", "op": {"Custom": "music:Arpeggiate"}, "target": "G", "params": {"speed": "fast", "volume": 3}},
+                {"actor": "Piano", "op": {"Custom": "Strum"}, "target": "E"},
+                {"actor": "Piano", "op": "DefineFunction", "target": "encore", "params": {"args": [], "body": [
+                    {"actor": "Piano", "op": {"Custom": "music:Trill"}, "target": "A"}
+                ]}}
+            ]
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("music:Arpeggiate"), Some(("music", "Arpeggiate")));
+        assert_eq!(split("Arpeggiate"), None);
+        assert_eq!(split(":x"), None);
+        assert_eq!(namespace(&Operation::Custom("legal:Notarize".into())), Some("legal"));
+    }
+
+    #[test]
+    fn test_check_usages() {
+        let program = program();
+        let warnings: Vec<String> = Declarations::from_program(&program).unwrap()
+            .check(&program)
+            .iter()
+            .map(|w| w.display())
+            .collect();
+
+        assert_eq!(warnings, vec![
+            "action 2 music:Arpeggiate: missing required param 'chord'",
+            "action 2 music:Arpeggiate: param 'speed' should be a number, got \"fast\"",
+            "action 2 music:Arpeggiate: unknown param 'volume'",
+            "action 2 music:Arpeggiate: missing declared effect 'Audio'",
+            "action 3 Strum: has no namespace (use ns:Name)",
+            "action 3 Strum: is not declared",
+            "action 5 music:Trill: is not declared",
+        ]);
+    }
+}
@@ -0,0 +1,135 @@
+//! Running a program's actions by their dependencies instead of in order
+//!
+//! Top-level actions are grouped into waves: every action in a wave depends
+//! only on actions in earlier waves, so a wave's actions can run in any order
+//! or side by side and give the same result as running the program in order.
+
+use crate::analysis::{collect_vars, definitions::collect_calls, dependencies, reads, walk_with_depth};
+use crate::{Action, Operation, Program};
+use std::collections::HashSet;
+
+/// Names a top-level action, with everything nested in it, reads and
+/// changes
+#[derive(Debug, Default)]
+pub(crate) struct Footprint {
+    pub(crate) reads: HashSet<String>,
+    pub(crate) writes: HashSet<String>,
+    barrier: bool,
+}
+
+pub(crate) fn footprint(action: &Action) -> Footprint {
+    let mut footprint = Footprint::default();
+    walk_with_depth(std::slice::from_ref(action), 0, &mut |nested, _| {
+        let mut names = reads(nested);
+        for bound in [&nested.from_expr, &nested.to_expr, &nested.step_expr].into_iter().flatten() {
+            if let Ok(value) = serde_json::to_value(bound) {
+                collect_vars(&value, &mut names);
+            }
+        }
+        for value in nested.params.iter().flat_map(|p| p.values()) {
+            collect_calls(value, &mut names);
+        }
+        footprint.reads.extend(names);
+        footprint.writes.insert(nested.target.clone());
+        footprint.writes.extend(nested.loop_var.clone());
+        // Cleanup handlers and triggers cover whatever runs after them
+        footprint.barrier |= matches!(nested.op, Operation::OnInterrupt | Operation::When);
+    });
+    footprint
+}
+
+/// Earlier top-level actions each one has to run after
+///
+/// Adds to [`dependencies`] every earlier action that changes a name this
+/// one reads or changes, or reads a name this one changes, looking inside
+/// nested actions too. An `OnInterrupt` or `When` waits for everything before
+/// it and everything after waits for it.
+pub fn execution_dependencies(program: &Program) -> Vec<Vec<usize>> {
+    let footprints: Vec<Footprint> = program.actions.iter().map(footprint).collect();
+    let mut deps = dependencies(program);
+
+    for (j, later) in footprints.iter().enumerate() {
+        for (i, earlier) in footprints[..j].iter().enumerate() {
+            let conflict = earlier.barrier || later.barrier
+                || !later.writes.is_disjoint(&earlier.writes)
+                || !later.writes.is_disjoint(&earlier.reads)
+                || !later.reads.is_disjoint(&earlier.writes);
+            if conflict && !deps[j].contains(&i) {
+                deps[j].push(i);
+            }
+        }
+        deps[j].sort_unstable();
+    }
+    deps
+}
+
+/// Group actions by dependency depth: wave 0 needs nothing, wave 1 only
+/// wave 0, and so on; each wave keeps program order
+pub fn waves(deps: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut depth = vec![0usize; deps.len()];
+    for i in 0..deps.len() {
+        depth[i] = deps[i].iter().map(|&d| depth[d] + 1).max().unwrap_or(0);
+    }
+
+    let levels = depth.iter().copied().max().map_or(0, |d| d + 1);
+    let mut waves = vec![Vec::new(); levels];
+    for (i, d) in depth.iter().enumerate() {
+        waves[*d].push(i);
+    }
+    waves
+}
+
+/// Waves of `program`'s top-level actions that can run concurrently
+pub fn execution_waves(program: &Program) -> Vec<Vec<usize>> {
+    waves(&execution_dependencies(program))
+}
+
+/// The program with its top-level actions wave by wave
+pub fn reorder(program: &Program) -> Program {
+    Program {
+        metadata: program.metadata.clone(),
+        actions: execution_waves(program).into_iter().flatten().map(|i| program.actions[i].clone()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::BrainSimulator;
+
+    fn program() -> Program {
+        Program::from_json(r#"{"actions": [
+            {"actor": "A", "op": "Bind", "target": "x", "params": {"value": 1}},
+            {"actor": "B", "op": "Bind", "target": "y", "params": {"value": 2}},
+            {"actor": "C", "op": "Bind", "target": "sum", "params": {"value": {"expr": {"op": "+", "left": {"var": "x"}, "right": {"var": "y"}}}}},
+            {"actor": "D", "op": "Assign", "target": "x", "params": {"value": 10}},
+            {"actor": "E", "op": "If", "target": "check", "condition": {"type": "comparison", "op": ">", "left": 1, "right": 0},
+             "then": [{"actor": "E", "op": "Assign", "target": "y", "params": {"value": 20}}]},
+            {"actor": "F", "op": "Bind", "target": "z", "params": {"value": 3}}
+        ]}"#).unwrap()
+    }
+
+    #[test]
+    fn test_waves_respect_hazards() {
+        let program = program();
+        let deps = execution_dependencies(&program);
+        // Reassigning x has to wait for the read of it in sum, and for the first write
+        assert_eq!(deps[3], vec![0, 2]);
+        // So does the nested write to y
+        assert_eq!(deps[4], vec![1, 2]);
+        assert_eq!(execution_waves(&program), vec![vec![0, 1, 5], vec![2], vec![3, 4]]);
+    }
+
+    #[test]
+    fn test_reordered_program_gives_same_state() {
+        let run = |program: &Program| {
+            let mut simulator = BrainSimulator::new();
+            simulator.execute(program).unwrap();
+            let mut beliefs: Vec<(String, String)> = simulator.state().beliefs.iter().map(|(k, v)| (k.clone(), v.to_string())).collect();
+            beliefs.sort();
+            beliefs
+        };
+        let program = program();
+        assert_eq!(run(&reorder(&program)), run(&program));
+    }
+}
@@ -0,0 +1,5 @@
+//! Decompilers: source code in other languages back into UCL programs
+
+pub mod ruby;
+
+pub use ruby::RubyDecompiler;
@@ -0,0 +1,737 @@
+use crate::{Action, BinaryOpExpr, ComparisonOp, Condition, Expression, Operation, Program};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+/// Actor of every decompiled action, as in the programming examples
+const ACTOR: &str = "VM";
+
+/// Operators, longest first so `**` isn't read as two `*`
+const OPERATORS: &[&str] = &[
+    "...", "**", "==", "!=", "<=", ">=", "&&", "||", "..", "+=", "-=", "*=", "/=",
+    "+", "-", "*", "/", "%", "<", ">", "!", "(", ")", "[", "]", ",", "=", "|", ".",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(serde_json::Number),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+/// The keyword that ended a block and the rest of its line
+type Terminator = (String, String);
+
+/// An error and the line it was found on
+#[derive(Debug)]
+struct LineError(usize, String);
+
+impl std::fmt::Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.0, self.1)
+    }
+}
+
+impl std::error::Error for LineError {}
+
+/// A parsed Ruby expression, before it is split into UCL expressions and
+/// conditions
+#[derive(Debug, Clone)]
+enum Node {
+    Literal(serde_json::Value),
+    Var(String),
+    Call(String, Vec<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+    Not(Box<Node>),
+}
+
+/// Parses a constrained subset of Ruby back into a UCL program, the inverse
+/// of `RubyCompiler`
+///
+/// Supported statements are assignments (`x = expr`, `x += expr`, with
+/// `rand(a..b)` becoming `GenRandomInt`), `puts`, `return`, `break`, `next`, `sleep`, calls
+/// with literal arguments, `def`, `if`/`elsif`/`else`, `while`,
+/// `for i in a..b` and `(a..b).each do |i|`, and the `at_exit` cleanup block
+/// the compiler emits for `OnInterrupt`. Expressions are numbers, strings,
+/// booleans, `nil`, variables, arithmetic and calls to functions the script
+/// defines. Anything else is an error naming its line.
+pub struct RubyDecompiler {
+    /// Non-blank lines with comments stripped, with their line numbers
+    lines: Vec<(usize, String)>,
+    pos: usize,
+    /// Parameter names of each `def`, to name the arguments of calls
+    functions: HashMap<String, Vec<String>>,
+}
+
+impl RubyDecompiler {
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            pos: 0,
+            functions: HashMap::new(),
+        }
+    }
+
+    pub fn decompile(&mut self, source: &str) -> Result<Program> {
+        self.lines = source.lines().enumerate()
+            .map(|(i, line)| (i + 1, strip_comment(line).trim().to_string()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+        self.pos = 0;
+
+        // Functions can be called before their definition
+        self.functions.clear();
+        for (number, line) in &self.lines {
+            if let Some(rest) = line.strip_prefix("def ") {
+                let (name, args) = signature(rest).map_err(|e| LineError(*number, e.to_string()))?;
+                self.functions.insert(name, args);
+            }
+        }
+
+        let (actions, _) = self.block(&[])?;
+        let mut program = Program::new();
+        program.actions = actions;
+        Ok(program)
+    }
+
+    /// Statements up to a line starting with one of `terminators`, which is
+    /// consumed and returned with the rest of its line
+    fn block(&mut self, terminators: &[&str]) -> Result<(Vec<Action>, Option<Terminator>)> {
+        let mut actions = Vec::new();
+        while let Some((number, line)) = self.lines.get(self.pos).cloned() {
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((&line, ""));
+            if terminators.contains(&keyword) {
+                self.pos += 1;
+                return Ok((actions, Some((keyword.to_string(), rest.trim().to_string()))));
+            }
+            self.pos += 1;
+            // Errors from nested blocks already name their own line
+            let action = self.statement(&line)
+                .map_err(|e| if e.is::<LineError>() { e } else { LineError(number, e.to_string()).into() })?;
+            actions.push(action);
+        }
+
+        if terminators.is_empty() {
+            Ok((actions, None))
+        } else {
+            bail!("no matching '{}' before the end of the file", terminators.last().unwrap_or(&"end"))
+        }
+    }
+
+    fn statement(&mut self, line: &str) -> Result<Action> {
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match keyword {
+            "def" => self.define_function(rest),
+            "if" => self.if_chain(rest),
+            "while" => {
+                let condition = self.condition(parse(rest.strip_suffix(" do").unwrap_or(rest))?)?;
+                let mut action = Action::new(ACTOR, Operation::While, "while");
+                action.condition = Some(condition);
+                action.body_actions = Some(self.block(&["end"])?.0);
+                Ok(action)
+            }
+            "for" => {
+                let (variable, range) = rest.split_once(" in ")
+                    .ok_or_else(|| anyhow!("expected 'for <variable> in <range>'"))?;
+                self.for_loop(variable.trim(), range.strip_suffix(" do").unwrap_or(range))
+            }
+            "at_exit" if rest == "do" => {
+                match self.lines.get(self.pos) {
+                    Some((_, guard)) if guard == "if $!" => self.pos += 1,
+                    _ => bail!("only 'at_exit do' blocks of the form 'if $! ... end' are supported"),
+                }
+                let mut action = Action::new(ACTOR, Operation::OnInterrupt, "cleanup");
+                action.body_actions = Some(self.block(&["end"])?.0);
+                self.block(&["end"])?;
+                Ok(action)
+            }
+            "break" if rest.is_empty() => Ok(Action::new(ACTOR, Operation::Break, "loop")),
+            "next" if rest.is_empty() => Ok(Action::new(ACTOR, Operation::Continue, "loop")),
+            "return" => {
+                let value = if rest.is_empty() { Expression::Value(serde_json::Value::Null) } else { self.expression(rest)? };
+                Ok(Action::new(ACTOR, Operation::Return, "result").with_params(value_param(&value)?))
+            }
+            "puts" => {
+                let content = if rest.is_empty() { Expression::Value("".into()) } else { self.expression(rest)? };
+                let target = match &content {
+                    Expression::Variable { var } => var.clone(),
+                    _ => "output".to_string(),
+                };
+                let mut params = HashMap::new();
+                params.insert("content".to_string(), serde_json::to_value(&content)?);
+                Ok(Action::new(ACTOR, Operation::Emit, target).with_params(params))
+            }
+            "sleep" => match parse(rest)? {
+                Node::Literal(serde_json::Value::Number(n)) => Ok(Action::new(ACTOR, Operation::Wait, "sleep")
+                    .with_duration(n.as_f64().unwrap_or_default())),
+                _ => bail!("sleep needs a literal number of seconds"),
+            },
+            _ if line.ends_with('|') && line.contains(".each do |") => {
+                let (range, variable) = line.split_once(".each do |").unwrap_or_default();
+                self.for_loop(variable.trim_end_matches('|').trim(), range)
+            }
+            _ => self.simple_statement(line),
+        }
+    }
+
+    /// Assignments and calls
+    fn simple_statement(&mut self, line: &str) -> Result<Action> {
+        let tokens = tokenize(line)?;
+        match tokens.as_slice() {
+            [Token::Ident(name), Token::Op("="), Token::Ident(call), Token::Op("("), ..] if call == "rand" => {
+                let mut parser = Parser::new(tokens[4..].to_vec());
+                let (min, max) = parser.range()?;
+                parser.expect(")")?;
+                parser.finish()?;
+                let mut params = HashMap::new();
+                params.insert("min".to_string(), integer(min)?.into());
+                params.insert("max".to_string(), integer(max)?.into());
+                Ok(Action::new(ACTOR, Operation::GenRandomInt, name.clone()).with_params(params))
+            }
+            [Token::Ident(name), Token::Op(op @ ("=" | "+=" | "-=" | "*=" | "/=")), rest @ ..] => {
+                let value = Parser::new(rest.to_vec()).parse()?;
+                let value = match op.strip_suffix('=').filter(|op| !op.is_empty()) {
+                    Some(op) => Node::Binary(binary_op(op), Box::new(Node::Var(name.clone())), Box::new(value)),
+                    None => value,
+                };
+                let value = self.to_expression(value)?;
+                Ok(Action::new(ACTOR, Operation::Bind, name.clone()).with_params(value_param(&value)?))
+            }
+            [Token::Ident(_), Token::Op("("), ..] => match Parser::new(tokens).parse()? {
+                Node::Call(name, args) => {
+                    let keys: &[&str] = match args.len() {
+                        0 => &[],
+                        1 => &["arg"],
+                        2 => &["a", "b"],
+                        3 => &["a", "b", "c"],
+                        _ => bail!("calls take at most three arguments"),
+                    };
+                    let mut action = Action::new(ACTOR, Operation::Call, name);
+                    if !args.is_empty() {
+                        let mut params = HashMap::new();
+                        for (key, arg) in keys.iter().zip(args) {
+                            match arg {
+                                Node::Literal(value) => params.insert(key.to_string(), value),
+                                _ => bail!("call arguments must be literals"),
+                            };
+                        }
+                        action.params = Some(params);
+                    }
+                    Ok(action)
+                }
+                _ => bail!("unsupported statement: {}", line),
+            },
+            _ => bail!("unsupported statement: {}", line),
+        }
+    }
+
+    fn define_function(&mut self, signature_text: &str) -> Result<Action> {
+        let (name, args) = signature(signature_text)?;
+        let (body, _) = self.block(&["end"])?;
+
+        let mut params = HashMap::new();
+        params.insert("args".to_string(), serde_json::json!(args));
+        params.insert("body".to_string(), serde_json::to_value(&body)?);
+        Ok(Action::new(ACTOR, Operation::DefineFunction, name).with_params(params))
+    }
+
+    /// `if`, with each `elsif` becoming an `If` nested in the `else` branch
+    fn if_chain(&mut self, condition_text: &str) -> Result<Action> {
+        let condition = self.condition(parse(condition_text.strip_suffix(" then").unwrap_or(condition_text))?)?;
+        let mut action = Action::new(ACTOR, Operation::If, "if");
+        action.condition = Some(condition);
+
+        let (then_actions, terminator) = self.block(&["elsif", "else", "end"])?;
+        action.then_actions = Some(then_actions);
+        action.else_actions = match terminator {
+            Some((keyword, rest)) if keyword == "elsif" => Some(vec![self.if_chain(&rest)?]),
+            Some((keyword, _)) if keyword == "else" => Some(self.block(&["end"])?.0),
+            _ => None,
+        };
+        Ok(action)
+    }
+
+    /// `a..b` is inclusive; `a...b` stops before `b`
+    fn for_loop(&mut self, variable: &str, range: &str) -> Result<Action> {
+        if !is_identifier(variable) {
+            bail!("invalid loop variable: {}", variable);
+        }
+        let mut parser = Parser::new(tokenize(range)?);
+        let wrapped = parser.eat("(");
+        let (from, to) = parser.range()?;
+        if wrapped {
+            parser.expect(")")?;
+        }
+        parser.finish()?;
+
+        let mut action = Action::new(ACTOR, Operation::For, "loop");
+        action.loop_var = Some(variable.to_string());
+        action.from_expr = Some(self.to_expression(from)?);
+        action.to_expr = Some(self.to_expression(to)?);
+        action.body_actions = Some(self.block(&["end"])?.0);
+        Ok(action)
+    }
+
+    fn expression(&self, text: &str) -> Result<Expression> {
+        self.to_expression(parse(text)?)
+    }
+
+    fn to_expression(&self, node: Node) -> Result<Expression> {
+        match node {
+            Node::Literal(value) => Ok(Expression::Value(value)),
+            Node::Var(var) => Ok(Expression::Variable { var }),
+            Node::Binary(op @ ("+" | "-" | "*" | "/" | "%" | "**"), left, right) => Ok(Expression::BinaryOp {
+                expr: BinaryOpExpr {
+                    op: op.to_string(),
+                    left: Box::new(self.to_expression(*left)?),
+                    right: Box::new(self.to_expression(*right)?),
+                },
+            }),
+            Node::Call(call, args) => {
+                let names = self.functions.get(&call)
+                    .ok_or_else(|| anyhow!("call to {}, which the script doesn't define", call))?;
+                if names.len() != args.len() {
+                    bail!("{} takes {} argument(s), got {}", call, names.len(), args.len());
+                }
+                let args = names.iter().cloned()
+                    .zip(args)
+                    .map(|(name, arg)| Ok((name, self.to_expression(arg)?)))
+                    .collect::<Result<_>>()?;
+                Ok(Expression::FunctionCall { call, args })
+            }
+            Node::Binary(..) | Node::Not(_) => bail!("a condition can only be used in if or while"),
+        }
+    }
+
+    fn condition(&self, node: Node) -> Result<Condition> {
+        match node {
+            Node::Binary(op @ ("&&" | "||"), left, right) => {
+                let mut operands = Vec::new();
+                for side in [*left, *right] {
+                    match (op, self.condition(side)?) {
+                        ("&&", Condition::And { operands: inner }) | ("||", Condition::Or { operands: inner }) => operands.extend(inner),
+                        (_, other) => operands.push(other),
+                    }
+                }
+                Ok(if op == "&&" { Condition::And { operands } } else { Condition::Or { operands } })
+            }
+            Node::Binary(op, left, right) if comparison_op(op).is_some() => Ok(Condition::Comparison {
+                op: comparison_op(op).unwrap_or(ComparisonOp::Equal),
+                left: self.to_expression(*left)?,
+                right: self.to_expression(*right)?,
+            }),
+            Node::Not(operand) => Ok(Condition::Not { operand: Box::new(self.condition(*operand)?) }),
+            // UCL conditions are comparisons, so a bare boolean compares with true
+            Node::Literal(serde_json::Value::Bool(b)) => Ok(Condition::Comparison {
+                op: ComparisonOp::Equal,
+                left: Expression::Value(b.into()),
+                right: Expression::Value(true.into()),
+            }),
+            _ => bail!("conditions must be comparisons, optionally joined with &&, || and !"),
+        }
+    }
+}
+
+impl Default for RubyDecompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn value_param(value: &Expression) -> Result<HashMap<String, serde_json::Value>> {
+    let mut params = HashMap::new();
+    params.insert("value".to_string(), serde_json::to_value(value)?);
+    Ok(params)
+}
+
+fn comparison_op(op: &str) -> Option<ComparisonOp> {
+    match op {
+        "==" => Some(ComparisonOp::Equal),
+        "!=" => Some(ComparisonOp::NotEqual),
+        "<" => Some(ComparisonOp::LessThan),
+        "<=" => Some(ComparisonOp::LessThanOrEqual),
+        ">" => Some(ComparisonOp::GreaterThan),
+        ">=" => Some(ComparisonOp::GreaterThanOrEqual),
+        _ => None,
+    }
+}
+
+/// The `'static` spelling of an operator
+fn binary_op(op: &str) -> &'static str {
+    OPERATORS.iter().find(|o| **o == op).copied().unwrap_or("+")
+}
+
+/// Left and right binding power of an infix operator; `**` is right
+/// associative
+fn infix_power(op: &str) -> Option<(u8, u8)> {
+    match op {
+        "||" => Some((1, 2)),
+        "&&" => Some((3, 4)),
+        "==" | "!=" => Some((5, 6)),
+        "<" | "<=" | ">" | ">=" => Some((7, 8)),
+        "+" | "-" => Some((9, 10)),
+        "*" | "/" | "%" => Some((11, 12)),
+        "**" => Some((14, 13)),
+        _ => None,
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn integer(node: Node) -> Result<i64> {
+    match node {
+        Node::Literal(serde_json::Value::Number(n)) if n.is_i64() => Ok(n.as_i64().unwrap_or_default()),
+        _ => bail!("rand needs integer literal bounds"),
+    }
+}
+
+/// The line up to a `#` outside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// `name` or `name(a, b)` after `def`
+fn signature(text: &str) -> Result<(String, Vec<String>)> {
+    let mut parser = Parser::new(tokenize(text)?);
+    let name = parser.identifier()?;
+    let mut args = Vec::new();
+    if parser.eat("(") && !parser.eat(")") {
+        loop {
+            args.push(parser.identifier()?);
+            if parser.eat(")") {
+                break;
+            }
+            parser.expect(",")?;
+        }
+    }
+    parser.finish()?;
+    Ok((name, args))
+}
+
+fn parse(text: &str) -> Result<Node> {
+    Parser::new(tokenize(text)?).parse()
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let mut end = rest.find(|c: char| !c.is_ascii_digit() && c != '_').unwrap_or(rest.len());
+            let fraction = rest[end..].strip_prefix('.').is_some_and(|r| r.starts_with(|c: char| c.is_ascii_digit()));
+            if fraction {
+                end += 1 + rest[end + 1..].find(|c: char| !c.is_ascii_digit() && c != '_').unwrap_or(rest.len() - end - 1);
+            }
+            let digits = rest[..end].replace('_', "");
+            let number = if fraction {
+                serde_json::Number::from_f64(digits.parse()?).ok_or_else(|| anyhow!("invalid number: {}", digits))?
+            } else {
+                digits.parse::<i64>()?.into()
+            };
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            let mut chars = rest.char_indices().skip(1);
+            let end = loop {
+                match chars.next() {
+                    Some((i, q)) if q == c => break i,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) if c == '"' => value.push('\n'),
+                        Some((_, 't')) if c == '"' => value.push('\t'),
+                        Some((_, e)) if e == c || e == '\\' => value.push(e),
+                        Some((_, e)) => { value.push('\\'); value.push(e); }
+                        None => bail!("unterminated string"),
+                    },
+                    Some((i, '#')) if c == '"' && rest[i + 1..].starts_with('{') => bail!("string interpolation is not supported"),
+                    Some((_, other)) => value.push(other),
+                    None => bail!("unterminated string"),
+                }
+            };
+            tokens.push(Token::Str(value));
+            rest = &rest[end + 1..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let op = OPERATORS.iter().find(|op| rest.starts_with(**op))
+                .ok_or_else(|| anyhow!("unexpected character '{}'", c))?;
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Precedence-climbing parser over one line's tokens
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn parse(mut self) -> Result<Node> {
+        let node = self.expr(0)?;
+        self.finish()?;
+        Ok(node)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = matches!(self.tokens.get(self.pos), Some(Token::Op(o)) if *o == op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if !self.eat(op) {
+            bail!("expected '{}'{}", op, self.found());
+        }
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                Ok(name.clone())
+            }
+            _ => bail!("expected a name{}", self.found()),
+        }
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.pos < self.tokens.len() {
+            bail!("unexpected {}", describe(&self.tokens[self.pos]));
+        }
+        Ok(())
+    }
+
+    fn found(&self) -> String {
+        self.tokens.get(self.pos).map(|t| format!(", found {}", describe(t))).unwrap_or_default()
+    }
+
+    /// `from..to`, or `from...to` which excludes `to`
+    fn range(&mut self) -> Result<(Node, Node)> {
+        let from = self.expr(0)?;
+        let exclusive = if self.eat("..") {
+            false
+        } else if self.eat("...") {
+            true
+        } else {
+            bail!("expected a range{}", self.found());
+        };
+        let to = match self.expr(0)? {
+            Node::Literal(serde_json::Value::Number(n)) if exclusive && n.is_i64() => {
+                Node::Literal((n.as_i64().unwrap_or_default() - 1).into())
+            }
+            to if exclusive => Node::Binary("-", Box::new(to), Box::new(Node::Literal(1.into()))),
+            to => to,
+        };
+        Ok((from, to))
+    }
+
+    fn expr(&mut self, min_power: u8) -> Result<Node> {
+        let mut left = self.prefix()?;
+        loop {
+            let op = match self.tokens.get(self.pos) {
+                Some(Token::Op(op)) => *op,
+                Some(Token::Ident(word)) if word == "and" => "&&",
+                Some(Token::Ident(word)) if word == "or" => "||",
+                _ => break,
+            };
+            let Some((left_power, right_power)) = infix_power(op) else { break };
+            if left_power < min_power {
+                break;
+            }
+            self.pos += 1;
+            let right = self.expr(right_power)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn prefix(&mut self) -> Result<Node> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| anyhow!("expected an expression"))?;
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Node::Literal(serde_json::Value::Number(n))),
+            Token::Str(s) => Ok(Node::Literal(s.into())),
+            Token::Ident(word) => match word.as_str() {
+                "true" => Ok(Node::Literal(true.into())),
+                "false" => Ok(Node::Literal(false.into())),
+                "nil" => Ok(Node::Literal(serde_json::Value::Null)),
+                "not" => Ok(Node::Not(Box::new(self.expr(5)?))),
+                _ if self.eat("(") => {
+                    let mut args = Vec::new();
+                    if !self.eat(")") {
+                        loop {
+                            args.push(self.expr(0)?);
+                            if self.eat(")") {
+                                break;
+                            }
+                            self.expect(",")?;
+                        }
+                    }
+                    Ok(Node::Call(word, args))
+                }
+                _ => Ok(Node::Var(word)),
+            },
+            Token::Op("!") => Ok(Node::Not(Box::new(self.expr(15)?))),
+            Token::Op("-") => match self.expr(13)? {
+                Node::Literal(serde_json::Value::Number(n)) => Ok(Node::Literal(match n.as_i64() {
+                    Some(i) => (-i).into(),
+                    None => (-n.as_f64().unwrap_or_default()).into(),
+                })),
+                operand => Ok(Node::Binary("-", Box::new(Node::Literal(0.into())), Box::new(operand))),
+            },
+            Token::Op("(") => {
+                let node = self.expr(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Op("[") => {
+                let mut items = Vec::new();
+                if !self.eat("]") {
+                    loop {
+                        match self.expr(0)? {
+                            Node::Literal(value) => items.push(value),
+                            _ => bail!("array elements must be literals"),
+                        }
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Node::Literal(items.into()))
+            }
+            other => bail!("unexpected {}", describe(&other)),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(n) => n.to_string(),
+        Token::Str(s) => format!("{:?}", s),
+        Token::Ident(name) => format!("'{}'", name),
+        Token::Op(op) => format!("'{}'", op),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::RubyCompiler;
+
+    #[test]
+    fn test_decompile_statements() {
+        let program = RubyDecompiler::new().decompile(r#"
+            # Count down
+            x = 10 # start
+            total = 0
+            while x > 0 && !(x == 5)
+              total += x * 2
+              x -= 1
+            end
+            roll = rand(1..6)
+            if roll >= 5
+              puts "high"
+            elsif roll <= 2
+              puts "low"
+            else
+              puts roll
+            end
+            sleep 0.5
+        "#).unwrap();
+
+        let ops: Vec<Operation> = program.actions.iter().map(|a| a.op.clone()).collect();
+        assert_eq!(ops, vec![Operation::Bind, Operation::Bind, Operation::While, Operation::GenRandomInt, Operation::If, Operation::Wait]);
+
+        let json = serde_json::to_value(&program.actions[2]).unwrap();
+        assert_eq!(json["condition"], serde_json::json!({"type": "and", "operands": [
+            {"type": "comparison", "op": ">", "left": {"var": "x"}, "right": 0},
+            {"type": "not", "operand": {"type": "comparison", "op": "==", "left": {"var": "x"}, "right": 5}}
+        ]}));
+        assert_eq!(json["body"][0]["params"]["value"], serde_json::json!(
+            {"expr": {"op": "+", "left": {"var": "total"}, "right": {"expr": {"op": "*", "left": {"var": "x"}, "right": 2}}}}));
+
+        assert_eq!(program.actions[3].params.as_ref().unwrap()["max"], 6);
+        let elsif = &program.actions[4].else_actions.as_ref().unwrap()[0];
+        assert_eq!(elsif.op, Operation::If);
+        assert_eq!(elsif.else_actions.as_ref().unwrap()[0].target, "roll");
+        assert_eq!(program.actions[5].dur, Some(0.5));
+    }
+
+    #[test]
+    fn test_fibonacci_round_trip() {
+        let original = Program::from_json(&std::fs::read_to_string("examples/fibonacci.json").unwrap()).unwrap();
+        let ruby = RubyCompiler::new().compile(&original).unwrap();
+
+        let program = RubyDecompiler::new().decompile(&ruby).unwrap();
+        assert_eq!(RubyCompiler::new().compile(&program).unwrap(), ruby);
+
+        let call = serde_json::to_value(&program.actions[2].body_actions.as_ref().unwrap()[0]).unwrap();
+        assert_eq!(call["params"]["value"], serde_json::json!({"call": "fibonacci", "args": {"n": {"var": "i"}}}));
+    }
+
+    #[test]
+    fn test_break_and_next_round_trip() {
+        let original = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": 10, "body": [
+                {"actor": "VM", "op": "If", "target": "check", "condition": {"type": "comparison", "op": ">", "left": {"var": "i"}, "right": 5},
+                 "then": [{"actor": "VM", "op": "Break", "target": "loop"}], "else": [{"actor": "VM", "op": "Continue", "target": "loop"}]}
+            ]}
+        ]}"#).unwrap();
+        let ruby = RubyCompiler::new().compile(&original).unwrap();
+        assert!(ruby.contains("    break
+  else
+    next
+"), "{}", ruby);
+
+        let program = RubyDecompiler::new().decompile(&ruby).unwrap();
+        assert_eq!(RubyCompiler::new().compile(&program).unwrap(), ruby);
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let error = RubyDecompiler::new().decompile("x = 1\nputs \"#{x}\"").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: string interpolation is not supported");
+
+        let error = RubyDecompiler::new().decompile("while x < 3\n  if x > 1\n    x += 1\n  end\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: no matching 'end' before the end of the file");
+
+        let error = RubyDecompiler::new().decompile("if true\n  x = [y]\nend").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: array elements must be literals");
+
+        let error = RubyDecompiler::new().decompile("y = undefined_fn(2)").unwrap_err();
+        assert!(error.to_string().contains("doesn't define"), "{}", error);
+    }
+}
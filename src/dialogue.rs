@@ -0,0 +1,163 @@
+//! Conversations between two simulated brains
+//!
+//! A dialogue program's top-level actions belong to two actors, each with
+//! a brain of their own. Consecutive actions by one actor make a turn; after
+//! each turn, everything the speaker emitted reaches the listener, so a
+//! `Receive` in the listener's next turn hears it and binds the value,
+//! carrying facts from one mind to the other.
+
+use crate::simulator::BrainSimulator;
+use crate::{Action, Program};
+use anyhow::{Context, Result, bail};
+
+/// One speaker's run of consecutive actions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turn {
+    pub speaker: String,
+    /// What the speaker said during the turn, in order
+    pub said: Vec<String>,
+}
+
+/// Runs a two-actor program on a brain per actor, alternating turns
+#[derive(Default)]
+pub struct DialogueRunner {
+    brains: Vec<(String, BrainSimulator)>,
+    verbose: bool,
+    seed: Option<u64>,
+}
+
+impl DialogueRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Seed each brain's random source, the first speaker with `seed`, the
+    /// second with `seed + 1`
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Run `program`'s turns, each on its speaker's brain; brains carry on
+    /// from earlier runs
+    ///
+    /// Stops after a turn that halts its brain.
+    pub fn run(&mut self, program: &Program) -> Result<Vec<Turn>> {
+        for actor in speakers(&program.actions) {
+            self.join(actor)?;
+        }
+        if self.brains.len() < 2 {
+            bail!("A dialogue needs two speakers, but only {} acts", speakers(&program.actions).join(", "));
+        }
+
+        let mut transcript = Vec::new();
+        for actions in program.actions.chunk_by(|a, b| a.actor == b.actor) {
+            let speaker = &actions[0].actor;
+            let index = self.brains.iter().position(|(name, _)| name == speaker).unwrap_or_default();
+            let (_, brain) = &mut self.brains[index];
+
+            if self.verbose {
+                println!("🎙️  {}'s turn", speaker);
+            }
+            let spoken = brain.state().output.len();
+            let turn = Program { metadata: program.metadata.clone(), actions: actions.to_vec() };
+            brain.execute(&turn).with_context(|| format!("{}'s turn {}", speaker, transcript.len() + 1))?;
+            let said = brain.state().output[spoken..].to_vec();
+            let halted = brain.state().halted.is_some();
+
+            // What they emitted is waiting for the other brain's next Receive
+            let mut outbox = std::mem::take(&mut brain.state_mut().channels);
+            let (_, listener) = &mut self.brains[1 - index];
+            outbox.deliver_to(&mut listener.state_mut().channels);
+
+            transcript.push(Turn { speaker: speaker.clone(), said });
+            if halted {
+                break;
+            }
+        }
+        Ok(transcript)
+    }
+
+    /// Start a brain for `actor` unless they have one, as long as there
+    /// aren't two speakers already
+    fn join(&mut self, actor: &str) -> Result<()> {
+        if self.brain(actor).is_some() {
+            return Ok(());
+        }
+        if self.brains.len() == 2 {
+            bail!("A dialogue has two speakers, {} and {}, but {} acts too", self.brains[0].0, self.brains[1].0, actor);
+        }
+
+        let mut brain = BrainSimulator::new().with_verbose(self.verbose);
+        if let Some(seed) = self.seed {
+            brain = brain.with_seed(seed + self.brains.len() as u64);
+        }
+        self.brains.push((actor.to_string(), brain));
+        Ok(())
+    }
+
+    /// `name`'s brain, once they've acted
+    pub fn brain(&self, name: &str) -> Option<&BrainSimulator> {
+        self.brains.iter().find(|(speaker, _)| speaker == name).map(|(_, brain)| brain)
+    }
+
+    /// Each speaker's brain state, formatted for the terminal
+    pub fn display(&self) -> String {
+        self.brains.iter()
+            .map(|(name, brain)| format!("--- {} ---\n{}", name, brain.state().display()))
+            .collect()
+    }
+}
+
+/// Actors of `actions`, in the order they first act
+fn speakers(actions: &[Action]) -> Vec<&str> {
+    let mut speakers: Vec<&str> = Vec::new();
+    for action in actions {
+        if !speakers.contains(&action.actor.as_str()) {
+            speakers.push(&action.actor);
+        }
+    }
+    speakers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_facts_pass_between_minds() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "Alice", "op": "Bind", "target": "secret", "params": {"value": 42}},
+            {"actor": "Alice", "op": "Emit", "target": "chat", "params": {"content": "secret"}},
+            {"actor": "Bob", "op": "Receive", "target": "chat", "params": {"into": "heard"}},
+            {"actor": "Bob", "op": "Emit", "target": "chat", "params": {"content": "thanks"}},
+            {"actor": "Alice", "op": "Receive", "target": "chat", "params": {"into": "reply"}}
+        ]}"#).unwrap();
+
+        let mut dialogue = DialogueRunner::new();
+        let turns = dialogue.run(&program).unwrap();
+        let speakers: Vec<&str> = turns.iter().map(|turn| turn.speaker.as_str()).collect();
+        assert_eq!(speakers, vec!["Alice", "Bob", "Alice"]);
+        assert_eq!(turns[0].said, vec!["42"]);
+
+        let alice = dialogue.brain("Alice").unwrap().state();
+        let bob = dialogue.brain("Bob").unwrap().state();
+        assert_eq!(bob.beliefs["heard"], serde_json::json!(42));
+        assert!(!bob.beliefs.contains_key("secret"));
+        assert_eq!(alice.beliefs["reply"], serde_json::json!("thanks"));
+        assert!(alice.channels.is_empty() && bob.channels.is_empty());
+
+        let crowd = Program::from_json(r#"{"actions": [
+            {"actor": "Alice", "op": "Emit", "target": "hi"},
+            {"actor": "Bob", "op": "Emit", "target": "hi"},
+            {"actor": "Carol", "op": "Emit", "target": "hi"}
+        ]}"#).unwrap();
+        let err = DialogueRunner::new().run(&crowd).unwrap_err();
+        assert!(err.to_string().contains("Carol acts too"), "{}", err);
+    }
+}
@@ -0,0 +1,106 @@
+//! Error kinds raised by UCL itself
+//!
+//! Fallible functions return `anyhow::Result`, but the failures UCL raises
+//! carry a [`UclError`], so library users can match on the kind with
+//! `error.downcast_ref::<UclError>()` instead of parsing messages.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum UclError {
+    /// Source that isn't a valid program; `action` is the 1-based index in
+    /// [`Program::walk`](crate::Program::walk) order, when the problem is in
+    /// one action
+    Parse { action: Option<usize>, message: String },
+    /// An operation or operator a simulator or compiler target can't run
+    UnsupportedOperation { op: String, target: String },
+    /// An action missing a param or field its operation needs
+    MissingParam { op: String, param: String },
+    /// A value of the wrong kind for what's done with it, like indexing into
+    /// a number or past the end of a list
+    InvalidValue { message: String },
+    /// A variable read before anything bound it
+    UndefinedVariable { name: String },
+    /// A call to a function the program never defined
+    UndefinedFunction { name: String },
+    /// A local model or tokenizer that couldn't be loaded or run
+    Model { message: String },
+    /// Function calls nested deeper than the simulator allows
+    RecursionLimit { depth: usize },
+    /// A loop that ran past the iteration limit
+    LoopLimit { iterations: usize },
+    /// The run was interrupted (e.g. Ctrl-C)
+    Interrupted,
+    /// A `Throw` no `Try` caught, with its payload
+    Thrown { value: serde_json::Value },
+    /// Something further from the robot arm's base than it can reach
+    Unreachable { object: String, distance: f64, reach: f64 },
+    /// An action failed on one substrate of a coordinated run; `action` is
+    /// 1-based
+    SubstrateFailure {
+        substrate: String,
+        action: usize,
+        transaction: Option<String>,
+        source: anyhow::Error,
+    },
+}
+
+impl UclError {
+    pub fn parse(message: impl fmt::Display) -> Self {
+        UclError::Parse { action: None, message: message.to_string() }
+    }
+
+    pub fn missing(op: impl Into<String>, param: impl Into<String>) -> Self {
+        UclError::MissingParam { op: op.into(), param: param.into() }
+    }
+
+    pub fn unsupported(op: impl Into<String>, target: impl Into<String>) -> Self {
+        UclError::UnsupportedOperation { op: op.into(), target: target.into() }
+    }
+
+    pub fn invalid(message: impl fmt::Display) -> Self {
+        UclError::InvalidValue { message: message.to_string() }
+    }
+
+    pub fn model(message: impl fmt::Display) -> Self {
+        UclError::Model { message: message.to_string() }
+    }
+}
+
+impl fmt::Display for UclError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UclError::Parse { action: Some(action), message } => write!(f, "action {}: {}", action, message),
+            UclError::Parse { action: None, message } => write!(f, "{}", message),
+            UclError::UnsupportedOperation { op, target } => write!(f, "Unsupported {} for the {} target", op, target),
+            UclError::MissingParam { op, param } => write!(f, "{} requires {}", op, param),
+            UclError::InvalidValue { message } | UclError::Model { message } => write!(f, "{}", message),
+            UclError::UndefinedVariable { name } => write!(f, "Variable not found: {}", name),
+            UclError::UndefinedFunction { name } => write!(f, "Function not defined: {}", name),
+            UclError::RecursionLimit { depth } => write!(f, "Maximum recursion depth ({}) exceeded", depth),
+            UclError::LoopLimit { iterations } => write!(f, "While loop exceeded maximum iterations ({})", iterations),
+            UclError::Interrupted => write!(f, "Interrupted"),
+            UclError::Thrown { value: serde_json::Value::String(message) } => write!(f, "Uncaught throw: {}", message),
+            UclError::Thrown { value } => write!(f, "Uncaught throw: {}", value),
+            UclError::Unreachable { object, distance, reach } => {
+                write!(f, "{} is out of reach: {:.2} from the arm's base, which reaches {:.2}", object, distance, reach)
+            }
+            UclError::SubstrateFailure { substrate, action, transaction, source } => {
+                write!(f, "{} failed on action {}: {}", substrate, action, source)?;
+                if let Some(name) = transaction {
+                    write!(f, " (transaction '{}' rolled back)", name)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for UclError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UclError::SubstrateFailure { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Example programs bundled into the binary for `ucl examples`
+
+use crate::Program;
+use anyhow::{Result, anyhow};
+use include_dir::{Dir, include_dir};
+
+static EXAMPLES: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/examples");
+
+/// A bundled example, named by its file stem (`recipe_tea`)
+#[derive(Debug, Clone, Copy)]
+pub struct Example {
+    pub name: &'static str,
+    pub source: &'static str,
+}
+
+impl Example {
+    pub fn program(&self) -> Result<Program> {
+        Program::from_json(self.source)
+    }
+
+    /// `metadata.description`, if the example has one
+    pub fn description(&self) -> Option<String> {
+        self.program().ok()?.meta().description().map(|s| s.to_string())
+    }
+}
+
+/// Every bundled example, sorted by name
+pub fn all() -> Vec<Example> {
+    let mut examples: Vec<Example> = EXAMPLES.files()
+        .filter(|f| f.path().extension().is_some_and(|e| e == "json"))
+        .filter_map(|f| Some(Example {
+            name: f.path().file_stem()?.to_str()?,
+            source: f.contents_utf8()?,
+        }))
+        .collect();
+    examples.sort_by_key(|e| e.name);
+    examples
+}
+
+/// Look up an example by name, with or without the `.json` extension
+pub fn get(name: &str) -> Result<Example> {
+    let name = name.strip_suffix(".json").unwrap_or(name);
+    all().into_iter()
+        .find(|e| e.name == name)
+        .ok_or_else(|| anyhow!("No example named '{}'. Run `ucl examples list` to see them all.", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_examples_parse() {
+        let examples = all();
+        assert!(examples.len() >= 10);
+        for example in examples {
+            assert!(example.program().is_ok(), "{} does not parse", example.name);
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        assert_eq!(get("recipe_tea").unwrap().name, "recipe_tea");
+        assert_eq!(get("fibonacci.json").unwrap().name, "fibonacci");
+        assert!(get("nope").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Incremental re-execution for `ucl watch`
+//!
+//! Each top-level action is fingerprinted by a hash of its JSON, and the
+//! state change it made is cached. After an edit, only actions whose hash
+//! changed, and actions that depend on them in the old or new dependency
+//! graph, run again; every other action replays its cached state change.
+//! Dependencies count everything nested in an action, so a `Write` inside
+//! an `If` re-runs when the variable it reads changes.
+//!
+//! Actions run one at a time, so simultaneous groups are not atomic here, and
+//! provenance isn't tracked.
+
+use crate::analysis::dependencies;
+use crate::dag::{Footprint, footprint};
+use crate::simulator::cache::fnv1a;
+use crate::stream::{Engine, StatePatch};
+use crate::Program;
+use anyhow::Result;
+
+struct CachedAction {
+    hash: u64,
+    depends_on: Vec<usize>,
+    patch: StatePatch,
+}
+
+/// What one run did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunSummary {
+    /// Indices of actions that were executed
+    pub executed: Vec<usize>,
+    /// How many actions replayed a cached result instead
+    pub reused: usize,
+}
+
+/// Re-runs a program after edits, executing only what the edit affected
+pub struct IncrementalRunner {
+    target: String,
+    engine: Engine,
+    cache: Vec<CachedAction>,
+}
+
+/// Content hash of an action, independent of key order
+pub fn action_hash(action: &crate::Action) -> u64 {
+    let canonical = serde_json::to_value(action)
+        .and_then(|value| serde_json::to_vec(&value))
+        .unwrap_or_default();
+    fnv1a(&canonical)
+}
+
+/// For each new action, the old action with the same hash it lines up with
+/// (longest common subsequence, so inserts and deletes don't shift the rest)
+fn align(old: &[u64], new: &[u64]) -> Vec<Option<usize>> {
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut matches = vec![None; new.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            matches[j] = Some(i);
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+/// [`dependencies`] of each top-level action, plus every earlier action
+/// that changes a name it, or anything nested in it, reads
+fn input_dependencies(program: &Program) -> Vec<Vec<usize>> {
+    let footprints: Vec<Footprint> = program.actions.iter().map(footprint).collect();
+    let mut deps = dependencies(program);
+    for (j, later) in footprints.iter().enumerate() {
+        for (i, earlier) in footprints[..j].iter().enumerate() {
+            if !later.reads.is_disjoint(&earlier.writes) && !deps[j].contains(&i) {
+                deps[j].push(i);
+            }
+        }
+        deps[j].sort_unstable();
+    }
+    deps
+}
+
+impl IncrementalRunner {
+    /// `brain` or `robot`
+    pub fn new(target: &str) -> Result<Self> {
+        Ok(Self { target: target.to_string(), engine: Engine::new(target)?, cache: Vec::new() })
+    }
+
+    /// Which actions of `program` must run again given what is cached
+    fn affected(&self, matches: &[Option<usize>], deps: &[Vec<usize>]) -> Vec<bool> {
+        // Where each old action ended up, if it survived the edit
+        let mut moved_to = vec![None; self.cache.len()];
+        for (new, old) in matches.iter().enumerate() {
+            if let Some(old) = old {
+                moved_to[*old] = Some(new);
+            }
+        }
+
+        let mut affected = vec![false; matches.len()];
+        for j in 0..matches.len() {
+            affected[j] = match matches[j] {
+                None => true,
+                Some(old) => {
+                    deps[j].iter().any(|&d| affected[d])
+                        // An old input that was deleted or has to re-run
+                        || self.cache[old].depends_on.iter()
+                            .any(|&d| moved_to[d].is_none_or(|new| affected[new]))
+                }
+            };
+        }
+        affected
+    }
+
+    /// Bring the simulator to the end state of `program`, reusing cached
+    /// results where the edit can't have changed them
+    ///
+    /// If an action fails the cache is dropped, so the next run starts over.
+    pub fn run(&mut self, program: &Program) -> Result<RunSummary> {
+        let hashes: Vec<u64> = program.actions.iter().map(action_hash).collect();
+        let deps = input_dependencies(program);
+        let matches = align(&self.cache.iter().map(|c| c.hash).collect::<Vec<_>>(), &hashes);
+        let affected = self.affected(&matches, &deps);
+
+        let mut engine = Engine::new(&self.target)?;
+        let mut state = engine.state()?;
+        let mut cache = Vec::with_capacity(program.actions.len());
+        let mut summary = RunSummary { executed: Vec::new(), reused: 0 };
+
+        for (index, action) in program.actions.iter().enumerate() {
+            let patch = match matches[index] {
+                Some(old) if !affected[index] => {
+                    summary.reused += 1;
+                    let patch = self.cache[old].patch.clone();
+                    patch.apply(&mut state);
+                    patch
+                }
+                _ => {
+                    summary.executed.push(index);
+                    engine.restore(state.clone())?;
+                    if let Err(e) = engine.execute(&Program { metadata: None, actions: vec![action.clone()] }) {
+                        self.cache.clear();
+                        return Err(e);
+                    }
+                    let after = engine.state()?;
+                    let patch = StatePatch::between(&state, &after);
+                    state = after;
+                    patch
+                }
+            };
+
+            cache.push(CachedAction { hash: hashes[index], depends_on: deps[index].clone(), patch });
+        }
+
+        engine.restore(state)?;
+        self.engine = engine;
+        self.cache = cache;
+        Ok(summary)
+    }
+
+    /// The simulator state after the last run, formatted for display
+    pub fn display(&self) -> String {
+        self.engine.display()
+    }
+
+    /// The simulator state after the last run, as JSON
+    pub fn state(&self) -> Result<serde_json::Value> {
+        self.engine.state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::BrainSimulator;
+
+    fn program(actions: &str) -> Program {
+        Program::from_json(&format!(r#"{{"actions": [{}]}}"#, actions)).unwrap()
+    }
+
+    const A: &str = r#"{"actor": "a", "op": "Bind", "target": "x", "params": {"value": 2}}"#;
+    const B: &str = r#"{"actor": "b", "op": "Write", "target": "y", "params": {"operation": "multiply", "lhs_register": "x", "rhs": 3}}"#;
+    const C: &str = r#"{"actor": "c", "op": "StoreFact", "target": "memory", "params": {"entity": "cat", "color": "black"}}"#;
+
+    fn full_run(program: &Program) -> serde_json::Value {
+        let mut brain = BrainSimulator::new();
+        brain.execute(program).unwrap();
+        let mut state = serde_json::to_value(brain.state()).unwrap();
+        state.as_object_mut().unwrap().remove("provenance");
+        state
+    }
+
+    #[test]
+    fn test_only_edited_action_and_dependents_rerun() {
+        let mut runner = IncrementalRunner::new("brain").unwrap();
+        let first = runner.run(&program(&[A, B, C].join(","))).unwrap();
+        assert_eq!(first.executed, vec![0, 1, 2]);
+
+        // Unchanged: nothing runs
+        let again = runner.run(&program(&[A, B, C].join(","))).unwrap();
+        assert_eq!((again.executed.len(), again.reused), (0, 3));
+
+        // Editing x re-runs its reader but not the unrelated fact
+        let edited = program(&[&A.replace("2", "5"), B, C].join(","));
+        let summary = runner.run(&edited).unwrap();
+        assert_eq!(summary.executed, vec![0, 1]);
+        assert_eq!(summary.reused, 1);
+
+        let state = runner.state().unwrap();
+        assert_eq!(state["beliefs"]["y"], serde_json::json!(15.0));
+        assert_eq!(state, full_run(&edited));
+    }
+
+    #[test]
+    fn test_nested_readers_rerun() {
+        const IF: &str = r#"{"actor": "b", "op": "If", "target": "check",
+            "condition": {"type": "comparison", "op": ">", "left": 1, "right": 0},
+            "then": [{"actor": "b", "op": "Write", "target": "y", "params": {"operation": "multiply", "lhs_register": "x", "rhs": 10}}]}"#;
+        let mut runner = IncrementalRunner::new("brain").unwrap();
+        runner.run(&program(&[A, IF].join(","))).unwrap();
+
+        let edited = program(&[&A.replace("2", "5"), IF].join(","));
+        let summary = runner.run(&edited).unwrap();
+        assert_eq!((summary.executed, summary.reused), (vec![0, 1], 0));
+        assert_eq!(runner.state().unwrap()["beliefs"]["y"], serde_json::json!(50.0));
+        assert_eq!(runner.state().unwrap(), full_run(&edited));
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        let mut runner = IncrementalRunner::new("brain").unwrap();
+        runner.run(&program(&[A, B, C].join(","))).unwrap();
+
+        // Deleting the writer of x re-runs its old reader, which now sees no x
+        let deleted = runner.run(&program(&[B, C].join(","))).unwrap();
+        assert_eq!((deleted.executed, deleted.reused), (vec![0], 1));
+
+        // An insert at the front runs only the new action
+        let dog = C.replace("cat", "dog").replace("\"c\"", "\"d\"");
+        let inserted = program(&[dog.as_str(), B, C].join(","));
+        let summary = runner.run(&inserted).unwrap();
+        assert_eq!((summary.executed, summary.reused), (vec![0], 2));
+        assert_eq!(runner.state().unwrap(), full_run(&inserted));
+    }
+}
@@ -1,9 +1,42 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub mod analysis;
+pub mod builder;
+pub mod capabilities;
 pub mod compiler;
+pub mod compose;
 pub mod simulator;
 pub mod coordinator;
+pub mod custom_ops;
+pub mod dag;
+pub mod dialogue;
+pub mod decompiler;
+pub mod error;
+pub mod gallery;
+pub mod incremental;
+pub mod links;
+pub mod macros;
+pub mod metadata;
+pub mod minimize;
+pub mod project;
+pub mod routing;
+pub mod scheduler;
+pub mod schema;
+pub mod rng;
+pub mod signing;
+pub mod slice;
+pub mod stream;
+pub mod template;
+pub mod text;
+pub mod timeline;
+pub mod transform;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tutorial;
+pub mod yaml;
+#[cfg(feature = "tui")]
+pub mod tui;
 
 /// Core operation types in UCL
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -21,14 +54,22 @@ pub enum Operation {
     // Communication operations
     Emit,
     Receive,
+    Vote,
 
     // Observation and decision
     Measure,
+    Scan,
+    ReadSensor,  // Read `target`'s `params.sensor` (temperature, weight or position) into `params.into`, with sensor noise
     Decide,
 
     // Temporal operations
     Wait,
 
+    // Cognitive operations
+    Sleep,  // Consolidate working memory into long-term memory and let emotions settle
+    Dream,  // Recombine stored facts into `params.count` dream thoughts (1 by default)
+    Imagine,  // Run `body` in the simulated mind of `target`, reading its belief `params.ask` back
+
     // Logical/semantic operations
     Assert,
     StoreFact,
@@ -47,6 +88,7 @@ pub enum Operation {
     Call,
     Assign,
     Return,
+    Append,  // Add `params.value` to the end of the list in `target`, starting one if needed
 
     // Random number generation
     GenRandomInt,
@@ -59,14 +101,30 @@ pub enum Operation {
     Stir,
     Place,
     Remove,
+    Grasp,    // Pick up `target`; the gripper must be empty
+    Release,  // Let go of `target`, which the gripper must be holding
     Steep,
     Serve,
 
     // Control flow operations (Turing completeness)
     If,
+    Match,        // Run the first of `params.cases` matching `params.value`, else `else`
     While,
     For,
+    ForEach,      // Run `body` once per item (or key) of `params.in`, or of the state collection `params.over`, bound to `variable`
     DefineFunction,
+    OnInterrupt,  // Cleanup `body` run if a later action fails or the run is interrupted
+    Try,          // Run `body`; if it throws or fails, run `catch` instead of stopping
+    Throw,        // Fail with `params.value`, unwinding to the nearest Try
+    Parallel,     // Run `body` concurrently; actions on the same substrate keep their order
+    When,         // Run `body` whenever `condition` becomes true after a later action
+    Break,        // Leave the innermost While/For
+    Continue,     // Skip to the innermost While/For's next iteration
+    Halt,         // End the whole program with `params.status` (0 by default)
+
+    // Macros, replaced by their actions before a program runs (see [`macros`])
+    DefineMacro,
+    Expand,
 
     // AI/LLM operations
     Generate,  // AI generates code from instruction
@@ -82,6 +140,42 @@ pub enum Operation {
     Defenestrate,  // A real word but intentionally not supported
 }
 
+impl Operation {
+    /// The operation called `name`, or `Custom(name)` if this version of UCL
+    /// doesn't know it
+    pub fn named(name: &str) -> Self {
+        serde_json::from_value(name.into()).unwrap_or_else(|_| Operation::Custom(name.to_string()))
+    }
+
+    /// Whether `name` is a built-in operation
+    pub fn is_known(name: &str) -> bool {
+        serde_json::from_value::<Operation>(name.into()).is_ok()
+    }
+
+    /// Whether the operation stores a value in the variable named by its
+    /// target: an assignment, an input, a random draw or a new fact
+    pub fn writes_target(&self) -> bool {
+        self.is_assignment() || matches!(self,
+            Operation::GenRandomInt | Operation::Measure | Operation::Scan | Operation::ReadSensor
+                | Operation::Receive | Operation::Read | Operation::StoreFact | Operation::Create)
+    }
+
+    /// Whether the operation only stores a value computed from its params in
+    /// its target, with no other effect
+    pub fn is_assignment(&self) -> bool {
+        matches!(self, Operation::Bind | Operation::Assign | Operation::Write)
+    }
+}
+
+/// Accept any `op` string, so programs written for newer versions of UCL
+/// still load, with unknown names as `Custom`
+fn lenient_operation<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Operation, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(name) => Ok(Operation::named(&name)),
+        value => serde_json::from_value(value).map_err(serde::de::Error::custom),
+    }
+}
+
 /// Represents a condition for control flow (if/while)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type")]
@@ -104,6 +198,36 @@ pub enum Condition {
     Not {
         operand: Box<Condition>,
     },
+    /// Whether an expression's value is truthy: anything but `false` and
+    /// `null`, as in Ruby
+    #[serde(rename = "expression")]
+    Expression {
+        value: Expression,
+    },
+    /// Whether the value, as text, matches a regular expression
+    #[serde(rename = "matches")]
+    Matches {
+        value: Expression,
+        pattern: String,
+    },
+    /// Whether the value, as text, starts with `prefix`
+    #[serde(rename = "starts_with")]
+    StartsWith {
+        value: Expression,
+        prefix: Expression,
+    },
+    /// Whether a string contains `part`, or a list has it as an item
+    #[serde(rename = "contains")]
+    Contains {
+        value: Expression,
+        part: Expression,
+    },
+    /// Whether `var` has been bound, so optional state can be probed
+    /// without reading it
+    #[serde(rename = "exists")]
+    Exists {
+        var: String,
+    },
 }
 
 /// Comparison operators for conditions
@@ -132,6 +256,7 @@ pub enum Expression {
     /// A function call - must come before Value
     FunctionCall {
         call: String,
+        #[serde(serialize_with = "sorted_map")]
         args: HashMap<String, Expression>,
     },
     /// An arithmetic operation - must come before Value
@@ -139,10 +264,57 @@ pub enum Expression {
         #[serde(rename = "expr")]
         expr: BinaryOpExpr,
     },
+    /// A list whose items are expressions: `{"list": [{"var": "x"}, 2]}`
+    List { list: Vec<Expression> },
+    /// An item of a list, counting from 0 (negative from the end), or the
+    /// value under a key of an object: `{"index": {"var": "xs"}, "at": 0}`
+    Index { index: Box<Expression>, at: Box<Expression> },
+    /// How many items a list or object has, or characters a string:
+    /// `{"length": {"var": "xs"}}`
+    Length { length: Box<Expression> },
+    /// A string function: `{"string": {"op": "upper", "args": [{"var": "name"}]}}`
+    StringOp { string: StringOpExpr },
+    /// Text with each `{name}` replaced by that variable's value (see
+    /// [`template`]): `{"format": "The answer is {x}"}`
+    Format { format: String },
+    /// Negation, `-` of a number or `!` of a truth value:
+    /// `{"unary": {"op": "-", "operand": {"var": "x"}}}`
+    Unary { unary: UnaryOpExpr },
+    /// A condition's result, `true` or `false`:
+    /// `{"test": {"type": "comparison", "op": ">", "left": {"var": "x"}, "right": 0}}`
+    Test { test: Box<Condition> },
     /// A literal value - must come last as it matches anything
     Value(serde_json::Value),
 }
 
+/// One arm of a `Match`, in its `params.cases`: runs `then` when the matched
+/// value equals `value`, or when `when` holds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchCase {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+    #[serde(default)]
+    pub then: Vec<Action>,
+}
+
+/// String function expression: `concat` joins any number of values as text,
+/// `upper` and `lower` change the case of one, `contains` checks whether the
+/// first contains the second
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StringOpExpr {
+    pub op: String,
+    pub args: Vec<Expression>,
+}
+
+/// Unary operation expression
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UnaryOpExpr {
+    pub op: String,
+    pub operand: Box<Expression>,
+}
+
 /// Binary operation expression
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct BinaryOpExpr {
@@ -151,6 +323,16 @@ pub struct BinaryOpExpr {
     pub right: Box<Expression>,
 }
 
+/// Serialize a HashMap with its keys in sorted order, so the same program
+/// always produces the same JSON
+fn sorted_map<S: serde::Serializer, V: Serialize>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<std::collections::BTreeMap<_, _>>().serialize(serializer)
+}
+
+fn sorted_option_map<S: serde::Serializer, V: Serialize>(map: &Option<HashMap<String, V>>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.as_ref().map(|m| m.iter().collect::<std::collections::BTreeMap<_, _>>()).serialize(serializer)
+}
+
 /// A UCL Action represents a single causal event
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Action {
@@ -158,6 +340,7 @@ pub struct Action {
     pub actor: String,
 
     /// What kind of action occurs
+    #[serde(deserialize_with = "lenient_operation")]
     pub op: Operation,
 
     /// What is acted upon
@@ -172,7 +355,7 @@ pub struct Action {
     pub dur: Option<f64>,
 
     /// Contextual arguments
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "sorted_option_map")]
     pub params: Option<HashMap<String, serde_json::Value>>,
 
     /// Required preconditions (optional)
@@ -187,6 +370,14 @@ pub struct Action {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub effects: Option<Vec<String>>,
 
+    /// Name other actions' `caused_by` can refer to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// Ids of the earlier actions this one is a consequence of (see [`links`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caused_by: Option<Vec<String>>,
+
     // Control flow fields
     /// Condition for If/While operations
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -200,10 +391,15 @@ pub struct Action {
     #[serde(skip_serializing_if = "Option::is_none", rename = "else")]
     pub else_actions: Option<Vec<Action>>,
 
-    /// Actions to execute in loop body (While/For operations)
+    /// Actions to execute in loop body (While/For operations), on interrupt
+    /// (OnInterrupt) or to attempt (Try)
     #[serde(skip_serializing_if = "Option::is_none", rename = "body")]
     pub body_actions: Option<Vec<Action>>,
 
+    /// Actions to execute if the body throws or fails (Try operation)
+    #[serde(skip_serializing_if = "Option::is_none", rename = "catch")]
+    pub catch_actions: Option<Vec<Action>>,
+
     /// Loop variable name (For operation)
     #[serde(skip_serializing_if = "Option::is_none", rename = "variable")]
     pub loop_var: Option<String>,
@@ -225,7 +421,7 @@ pub struct Action {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     /// Optional program metadata
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "sorted_option_map")]
     pub metadata: Option<HashMap<String, serde_json::Value>>,
 
     /// The sequence of actions
@@ -245,10 +441,13 @@ impl Action {
             pre: None,
             post: None,
             effects: None,
+            id: None,
+            caused_by: None,
             condition: None,
             then_actions: None,
             else_actions: None,
             body_actions: None,
+            catch_actions: None,
             loop_var: None,
             from_expr: None,
             to_expr: None,
@@ -279,6 +478,80 @@ impl Action {
         self.effects = Some(effects);
         self
     }
+
+    /// Call `visit` with each list of actions nested directly in this one:
+    /// `then`, `else`, `body`, `catch`, a DefineFunction's `params.body` and
+    /// a Match's `params.cases[i].then`
+    pub fn visit_children(&self, visit: &mut impl FnMut(&str, &[Action])) {
+        for (name, branch) in [("then", &self.then_actions), ("else", &self.else_actions), ("body", &self.body_actions), ("catch", &self.catch_actions)] {
+            if let Some(branch) = branch {
+                visit(name, branch);
+            }
+        }
+        if let Some(body) = self.function_body() {
+            if let Ok(nested) = serde_json::from_value::<Vec<Action>>(body.clone()) {
+                visit("params.body", &nested);
+            }
+        }
+        if let Some(Ok(cases)) = self.match_cases() {
+            for (i, case) in cases.iter().enumerate() {
+                visit(&format!("params.cases[{}].then", i), &case.then);
+            }
+        }
+    }
+
+    /// Visit this action and every action nested in it, depth-first, each
+    /// before its children (see [`visit_children`](Self::visit_children))
+    pub fn walk(&self, visit: &mut impl FnMut(&Action)) {
+        visit(self);
+        self.visit_children(&mut |_, nested| nested.iter().for_each(|action| action.walk(visit)));
+    }
+
+    /// A Match's arms, parsed from `params.cases`; `None` for other
+    /// operations or a Match without cases
+    pub fn match_cases(&self) -> Option<anyhow::Result<Vec<MatchCase>>> {
+        if self.op != Operation::Match {
+            return None;
+        }
+        let cases = self.params.as_ref()?.get("cases")?;
+        Some(serde_json::from_value(cases.clone()).map_err(|e| error::UclError::parse(format!("Match cases: {}", e)).into()))
+    }
+
+    /// Like [`visit_children`](Self::visit_children), but the lists can be
+    /// changed; a function body is written back to `params.body`
+    pub fn visit_children_mut(&mut self, visit: &mut impl FnMut(&str, &mut Vec<Action>)) {
+        for (name, branch) in [("then", &mut self.then_actions), ("else", &mut self.else_actions), ("body", &mut self.body_actions), ("catch", &mut self.catch_actions)] {
+            if let Some(branch) = branch {
+                visit(name, branch);
+            }
+        }
+        let function = self.op == Operation::DefineFunction;
+        // Function bodies live in params as raw JSON
+        if let Some(body) = self.params.as_mut().and_then(|p| p.get_mut("body")).filter(|_| function) {
+            if let Ok(mut nested) = serde_json::from_value::<Vec<Action>>(body.clone()) {
+                visit("params.body", &mut nested);
+                if let Ok(value) = serde_json::to_value(&nested) {
+                    *body = value;
+                }
+            }
+        }
+        // So do Match arms
+        if let Some(Ok(mut cases)) = self.match_cases() {
+            for (i, case) in cases.iter_mut().enumerate() {
+                visit(&format!("params.cases[{}].then", i), &mut case.then);
+            }
+            if let (Ok(value), Some(params)) = (serde_json::to_value(&cases), self.params.as_mut()) {
+                params.insert("cases".to_string(), value);
+            }
+        }
+    }
+
+    fn function_body(&self) -> Option<&serde_json::Value> {
+        if self.op != Operation::DefineFunction {
+            return None;
+        }
+        self.params.as_ref()?.get("body")
+    }
 }
 
 impl Program {
@@ -295,22 +568,213 @@ impl Program {
         self.actions.push(action);
     }
 
+    /// This program followed by `other`, sharing variables and functions (see
+    /// [`compose::concat`])
+    pub fn concat(&self, other: &Program) -> Program {
+        compose::concat(self, other)
+    }
+
+    /// This program followed by `other`, with `other`'s colliding variables
+    /// and functions renamed and `prefix` put before its actors (see
+    /// [`compose::merge`])
+    pub fn merge_with_actor_prefix(&self, other: &Program, prefix: &str) -> Program {
+        compose::merge(self, other, prefix).program
+    }
+
+    /// The actions `filter` matches, inside whatever control flow surrounds
+    /// them (see [`slice::slice`])
+    pub fn slice(&self, filter: &slice::SliceFilter) -> Program {
+        slice::slice(self, filter)
+    }
+
+    /// Replace each `Expand` with its macro's actions (see [`macros`]),
+    /// returning how many were expanded
+    pub fn expand_macros(&mut self) -> anyhow::Result<usize> {
+        macros::expand(self)
+    }
+
+    /// Visit every action depth-first, each before the actions nested in it
+    /// (see [`Action::visit_children`]), with its path, e.g.
+    /// `actions[2].then[0]` or `actions[0].params.body[1]`
+    pub fn walk(&self, visit: &mut impl FnMut(&Action, &str)) {
+        walk_actions(&self.actions, "actions", visit);
+    }
+
+    /// Like [`walk`](Self::walk), but each action can be changed, including
+    /// its nested lists before they are visited
+    pub fn walk_mut(&mut self, visit: &mut impl FnMut(&mut Action, &str)) {
+        walk_actions_mut(&mut self.actions, "actions", visit);
+    }
+
+    /// Top-level actions as index ranges, where consecutive actions sharing
+    /// the same `t` form one simultaneous group and every other action is a
+    /// group of its own
+    pub fn simultaneous_groups(&self) -> Vec<std::ops::Range<usize>> {
+        let mut groups: Vec<std::ops::Range<usize>> = Vec::new();
+        for (i, action) in self.actions.iter().enumerate() {
+            match groups.last_mut() {
+                Some(group) if action.t.is_some() && self.actions[group.start].t == action.t => group.end = i + 1,
+                _ => groups.push(i..i + 1),
+            }
+        }
+        groups
+    }
+
+    /// Typed access to the standard metadata fields
+    pub fn meta(&self) -> metadata::Metadata<'_> {
+        metadata::Metadata::new(self.metadata.as_ref())
+    }
+
     /// Parse a UCL program from JSON
     pub fn from_json(json: &str) -> anyhow::Result<Self> {
-        Ok(serde_json::from_str(json)?)
+        Ok(serde_json::from_str(json).map_err(error::UclError::parse)?)
     }
 
-    /// Serialize to JSON
+    /// Serialize to JSON; map keys are sorted, so output is deterministic
     pub fn to_json(&self) -> anyhow::Result<String> {
         Ok(serde_json::to_string_pretty(self)?)
     }
 
+    /// Compact JSON with every object's keys sorted, struct fields included:
+    /// the same logical program always gives the same bytes
+    pub fn to_canonical_json(&self) -> anyhow::Result<String> {
+        // serde_json::Value keeps object keys sorted
+        Ok(serde_json::to_string(&serde_json::to_value(self)?)?)
+    }
+
+    /// Parse a program written in UCL text syntax (see [`text`])
+    pub fn from_text(source: &str) -> anyhow::Result<Self> {
+        text::parse(source)
+    }
+
+    /// Write the program in UCL text syntax
+    pub fn to_text(&self) -> anyhow::Result<String> {
+        text::to_text(self)
+    }
+
+    /// Parse a program from YAML
+    pub fn from_yaml(source: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_value(yaml::from_yaml(source)?)?)
+    }
+
+    /// Serialize to YAML, with action fields in their usual order and other
+    /// keys sorted
+    pub fn to_yaml(&self) -> anyhow::Result<String> {
+        yaml::to_yaml(self)
+    }
+
+    /// Read a program file: UCL text if it ends in `.ucl`, YAML if `.yaml` or
+    /// `.yml`, otherwise JSON
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ucl") => Self::from_text(&content),
+            Some("yaml" | "yml") => Self::from_yaml(&content),
+            _ => Self::from_json(&content),
+        }
+    }
+
+    /// Parse JSON, rejecting operation names this version doesn't know
+    /// instead of loading them as `Custom`
+    pub fn from_json_strict(json: &str) -> anyhow::Result<Self> {
+        Self::from_value_checked(serde_json::from_str(json)?, true).map(|(program, _)| program)
+    }
+
+    /// Read a program file like [`load`](Self::load), also returning a
+    /// warning for each action whose `op` loaded as `Custom` because this
+    /// version doesn't know it; with `strict` those are errors instead
+    ///
+    /// UCL text has no separate syntax for custom operations, so `.ucl` files
+    /// never warn.
+    pub fn load_checked(path: &std::path::Path, strict: bool) -> anyhow::Result<(Self, Vec<String>)> {
+        let content = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ucl") => Ok((Self::from_text(&content)?, Vec::new())),
+            Some("yaml" | "yml") => Self::from_value_checked(yaml::from_yaml(&content)?, strict),
+            _ => Self::from_value_checked(serde_json::from_str(&content)?, strict),
+        }
+    }
+
+    fn from_value_checked(value: serde_json::Value, strict: bool) -> anyhow::Result<(Self, Vec<String>)> {
+        let unknown = unknown_operations(&value);
+        if let Some((action, name)) = unknown.first().filter(|_| strict) {
+            let message = format!("unknown operation '{}'", name);
+            return Err(error::UclError::Parse { action: Some(*action), message }.into());
+        }
+        let warnings = unknown.into_iter()
+            .map(|(action, name)| format!("action {}: unknown operation '{}' (loaded as Custom)", action, name))
+            .collect();
+        Ok((serde_json::from_value(value).map_err(error::UclError::parse)?, warnings))
+    }
+
     /// Parse a single action from JSON
     pub fn parse_action(json: &str) -> anyhow::Result<Action> {
         Ok(serde_json::from_str(json)?)
     }
 }
 
+fn walk_actions(actions: &[Action], path: &str, visit: &mut impl FnMut(&Action, &str)) {
+    for (index, action) in actions.iter().enumerate() {
+        let location = format!("{}[{}]", path, index);
+        visit(action, &location);
+        action.visit_children(&mut |name, nested| walk_actions(nested, &format!("{}.{}", location, name), visit));
+    }
+}
+
+fn walk_actions_mut(actions: &mut [Action], path: &str, visit: &mut impl FnMut(&mut Action, &str)) {
+    for (index, action) in actions.iter_mut().enumerate() {
+        let location = format!("{}[{}]", path, index);
+        visit(action, &location);
+        action.visit_children_mut(&mut |name, nested| walk_actions_mut(nested, &format!("{}.{}", location, name), visit));
+    }
+}
+
+/// Each `op` string in a program's JSON that isn't built in, with its
+/// action's 1-based index in [`Program::walk`] order
+fn unknown_operations(program: &serde_json::Value) -> Vec<(usize, String)> {
+    fn visit(actions: &serde_json::Value, index: &mut usize, found: &mut Vec<(usize, String)>) {
+        for action in actions.as_array().into_iter().flatten() {
+            *index += 1;
+            let op = action.get("op").and_then(|op| op.as_str());
+            if let Some(name) = op.filter(|name| !Operation::is_known(name)) {
+                found.push((*index, name.to_string()));
+            }
+            for branch in ["then", "else", "body", "catch"] {
+                if let Some(nested) = action.get(branch) {
+                    visit(nested, index, found);
+                }
+            }
+
+            // The lists Action::visit_children finds in params, when they parse
+            let params = action.get("params");
+            match op {
+                Some("DefineFunction") => {
+                    if let Some(body) = params.and_then(|p| p.get("body"))
+                        .filter(|body| serde_json::from_value::<Vec<Action>>((*body).clone()).is_ok()) {
+                        visit(body, index, found);
+                    }
+                }
+                Some("Match") => {
+                    let cases = params.and_then(|p| p.get("cases"))
+                        .filter(|cases| serde_json::from_value::<Vec<MatchCase>>((*cases).clone()).is_ok());
+                    for case in cases.and_then(|cases| cases.as_array()).into_iter().flatten() {
+                        if let Some(then) = case.get("then") {
+                            visit(then, index, found);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    if let Some(actions) = program.get("actions") {
+        visit(actions, &mut 0, &mut found);
+    }
+    found
+}
+
 impl Default for Program {
     fn default() -> Self {
         Self::new()
@@ -360,5 +824,129 @@ mod tests {
         let parsed = Program::from_json(&json).unwrap();
         assert_eq!(parsed.actions.len(), 1);
     }
-}
 
+    #[test]
+    fn test_simultaneous_groups() {
+        let mut program = Program::new();
+        for t in [Some(0.0), Some(0.0), Some(0.0), Some(1.0), None, None, Some(2.0), Some(2.0)] {
+            let mut action = Action::new("Piano", Operation::Emit, "Note");
+            action.t = t;
+            program.add_action(action);
+        }
+
+        assert_eq!(program.simultaneous_groups(), vec![0..3, 3..4, 4..5, 5..6, 6..8]);
+    }
+
+    #[test]
+    fn test_serialization_is_deterministic() {
+        let a = Program::from_json(r#"{"metadata": {"z": 1, "a": 2, "m": 3}, "actions": [
+            {"actor": "x", "op": "Bind", "target": "y", "params": {"value": {"call": "f", "args": {"q": 1, "b": 2}}, "c": 1, "a": 2}}
+        ]}"#).unwrap();
+        let b = Program::from_json(r#"{"actions": [
+            {"params": {"a": 2, "c": 1, "value": {"args": {"b": 2, "q": 1}, "call": "f"}}, "target": "y", "op": "Bind", "actor": "x"}
+        ], "metadata": {"m": 3, "a": 2, "z": 1}}"#).unwrap();
+
+        assert_eq!(a.to_json().unwrap(), b.to_json().unwrap());
+        assert_eq!(a.to_canonical_json().unwrap(), b.to_canonical_json().unwrap());
+        assert!(a.to_json().unwrap().find("\"a\": 2").unwrap() < a.to_json().unwrap().find("\"z\": 1").unwrap());
+        assert!(a.to_canonical_json().unwrap().starts_with(r#"{"actions":[{"actor":"x","op":"Bind","params":{"a":2,"#));
+    }
+
+    #[test]
+    fn test_walk_visits_nested_actions_and_function_bodies() {
+        let mut program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "f", "params": {"args": [], "body": [
+                {"actor": "VM", "op": "Return", "target": "result"}
+            ]}},
+            {"actor": "VM", "op": "If", "target": "check", "then": [
+                {"actor": "VM", "op": "Emit", "target": "yes"}
+            ], "else": [
+                {"actor": "VM", "op": "While", "target": "loop", "body": [
+                    {"actor": "VM", "op": "Emit", "target": "no"}
+                ]}
+            ]}
+        ]}"#).unwrap();
+
+        let mut visited = Vec::new();
+        program.walk(&mut |action, path| visited.push(format!("{} {}", path, action.target)));
+        assert_eq!(visited, vec![
+            "actions[0] f",
+            "actions[0].params.body[0] result",
+            "actions[1] check",
+            "actions[1].then[0] yes",
+            "actions[1].else[0] loop",
+            "actions[1].else[0].body[0] no",
+        ]);
+
+        program.walk_mut(&mut |action, _| action.actor = "robot".into());
+        let mut actors = Vec::new();
+        program.walk(&mut |action, _| actors.push(action.actor.clone()));
+        assert_eq!(actors, vec!["robot"; 6]);
+    }
+
+    #[test]
+    fn test_walk_visits_match_arms() {
+        let mut program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Match", "target": "day", "params": {"value": 1, "cases": [
+                {"value": 0, "then": [{"actor": "VM", "op": "Emit", "target": "rest"}]},
+                {"value": 1, "then": [{"actor": "VM", "op": "Emit", "target": "work"}]}
+            ]}, "else": [{"actor": "VM", "op": "Emit", "target": "other"}]}
+        ]}"#).unwrap();
+
+        let mut visited = Vec::new();
+        program.walk(&mut |_, path| visited.push(path.to_string()));
+        assert_eq!(visited, vec!["actions[0]", "actions[0].else[0]", "actions[0].params.cases[0].then[0]", "actions[0].params.cases[1].then[0]"]);
+
+        program.walk_mut(&mut |action, _| action.actor = "robot".into());
+        let cases = program.actions[0].match_cases().unwrap().unwrap();
+        assert_eq!(cases[1].then[0].actor, "robot");
+        assert_eq!(cases[1].value, Some(serde_json::json!(1)));
+    }
+
+    #[test]
+    fn test_unknown_operations_load_as_custom() {
+        let json = r#"{"actions": [
+            {"actor": "VM", "op": "Emit", "target": "out"},
+            {"actor": "VM", "op": {"Custom": "Splice"}, "target": "rna"},
+            {"actor": "VM", "op": "If", "target": "check", "then": [
+                {"actor": "VM", "op": "Teleport", "target": "home"}
+            ]}
+        ]}"#;
+        let nested = r#"{"actions": [
+            {"actor": "VM", "op": "Try", "target": "risky", "body": [], "catch": [
+                {"actor": "VM", "op": "Warp", "target": "away"}
+            ]},
+            {"actor": "VM", "op": "DefineFunction", "target": "f", "params": {"args": [], "body": [
+                {"actor": "VM", "op": "Fold", "target": "paper"}
+            ]}},
+            {"actor": "VM", "op": "Match", "target": "x", "params": {"cases": [
+                {"value": 1, "then": [{"actor": "VM", "op": "Emit", "target": "one"}]},
+                {"value": 2, "then": [{"actor": "VM", "op": "Juggle", "target": "balls"}]}
+            ]}}
+        ]}"#;
+
+        let program = Program::from_json(json).unwrap();
+        assert_eq!(program.actions[1].op, Operation::Custom("Splice".into()));
+        assert_eq!(program.actions[2].then_actions.as_ref().unwrap()[0].op, Operation::Custom("Teleport".into()));
+
+        let (_, warnings) = Program::from_value_checked(serde_json::from_str(json).unwrap(), false).unwrap();
+        assert_eq!(warnings, vec!["action 4: unknown operation 'Teleport' (loaded as Custom)"]);
+
+        let error = Program::from_json_strict(json).unwrap_err();
+        assert_eq!(error.to_string(), "action 4: unknown operation 'Teleport'");
+        assert!(matches!(error.downcast_ref(), Some(error::UclError::Parse { action: Some(4), .. })));
+
+        // Numbered as Program::walk visits them
+        let value: serde_json::Value = serde_json::from_str(nested).unwrap();
+        let mut order = Vec::new();
+        Program::from_json(nested).unwrap().walk(&mut |action, _| order.push(action.target.clone()));
+        let found: Vec<(String, String)> = unknown_operations(&value).into_iter()
+            .map(|(index, name)| (order[index - 1].clone(), name))
+            .collect();
+        assert_eq!(found, vec![
+            ("away".to_string(), "Warp".to_string()),
+            ("paper".to_string(), "Fold".to_string()),
+            ("balls".to_string(), "Juggle".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,131 @@
+//! Explicit causal links between actions
+//!
+//! An action with an `id` can be named in a later action's `caused_by`:
+//!
+//! ```json
+//! {"id": "boil", "actor": "kettle", "op": "Heat", "target": "water"},
+//! {"actor": "cook", "op": "Pour", "target": "water", "caused_by": ["boil"]}
+//! ```
+//!
+//! Without links, causality is only implied by the order of the actions;
+//! with them, analyses, graphs and provenance show which actions really led
+//! to which.
+
+use crate::analysis::walk_with_depth;
+use crate::Program;
+use std::collections::HashMap;
+
+/// An id or `caused_by` reference that doesn't hold together
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkProblem {
+    /// Where the action is, e.g. `actions[2].then[0]`
+    pub path: String,
+    pub message: String,
+}
+
+impl LinkProblem {
+    pub fn display(&self) -> String {
+        format!("{}: {}", self.path, self.message)
+    }
+}
+
+/// Path of the action each id names, nested actions and function bodies
+/// included; the first action wins if an id is used twice
+pub fn ids(program: &Program) -> HashMap<String, String> {
+    let mut ids = HashMap::new();
+    program.walk(&mut |action, path| {
+        if let Some(id) = &action.id {
+            ids.entry(id.clone()).or_insert_with(|| path.to_string());
+        }
+    });
+    ids
+}
+
+/// Empty or duplicate ids, and `caused_by` entries naming no action, the
+/// action itself or one that comes after it
+pub fn check(program: &Program) -> Vec<LinkProblem> {
+    let all = ids(program);
+    let mut seen: HashMap<String, String> = HashMap::new();
+    let mut problems = Vec::new();
+
+    program.walk(&mut |action, path| {
+        let mut problem = |message: String| problems.push(LinkProblem { path: path.to_string(), message });
+
+        for cause in action.caused_by.iter().flatten() {
+            if action.id.as_ref() == Some(cause) && !seen.contains_key(cause) {
+                problem(format!("is caused by itself ('{}')", cause));
+            } else if !seen.contains_key(cause) {
+                match all.get(cause) {
+                    Some(later) => problem(format!("is caused by '{}', which comes after it at {}", cause, later)),
+                    None => problem(format!("is caused by unknown id '{}'", cause)),
+                }
+            }
+        }
+
+        if let Some(id) = &action.id {
+            if id.is_empty() {
+                problem("has an empty id".to_string());
+            } else if let Some(first) = seen.get(id) {
+                problem(format!("id '{}' is already used at {}", id, first));
+            } else {
+                seen.insert(id.clone(), path.to_string());
+            }
+        }
+    });
+    problems
+}
+
+/// For each top-level action, the earlier top-level actions that it or any
+/// action nested in it is caused by
+pub fn top_level_causes(program: &Program) -> Vec<Vec<usize>> {
+    let mut owner: HashMap<String, usize> = HashMap::new();
+    for (index, action) in program.actions.iter().enumerate() {
+        walk_with_depth(std::slice::from_ref(action), 0, &mut |nested, _| {
+            if let Some(id) = &nested.id {
+                owner.entry(id.clone()).or_insert(index);
+            }
+        });
+    }
+
+    program.actions.iter().enumerate().map(|(index, action)| {
+        let mut causes = Vec::new();
+        walk_with_depth(std::slice::from_ref(action), 0, &mut |nested, _| {
+            for cause in nested.caused_by.iter().flatten() {
+                if let Some(&from) = owner.get(cause) {
+                    if from < index {
+                        causes.push(from);
+                    }
+                }
+            }
+        });
+        causes.sort_unstable();
+        causes.dedup();
+        causes
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_references() {
+        let program = Program::from_json(r#"{"actions": [
+            {"id": "boil", "actor": "kettle", "op": "Heat", "target": "water"},
+            {"id": "boil", "actor": "cook", "op": "Gather", "target": "cup", "caused_by": ["pour", "boil", "nope"]},
+            {"actor": "cook", "op": "If", "target": "check", "condition": {"type": "comparison", "op": ">", "left": 1, "right": 0},
+             "then": [{"id": "pour", "actor": "cook", "op": "Pour", "target": "water", "caused_by": ["boil", "pour"]}]},
+            {"id": "", "actor": "cook", "op": "Serve", "target": "tea", "caused_by": ["pour"]}
+        ]}"#).unwrap();
+
+        let problems: Vec<String> = check(&program).iter().map(LinkProblem::display).collect();
+        assert_eq!(problems, vec![
+            "actions[1]: is caused by 'pour', which comes after it at actions[2].then[0]",
+            "actions[1]: is caused by unknown id 'nope'",
+            "actions[1]: id 'boil' is already used at actions[0]",
+            "actions[2].then[0]: is caused by itself ('pour')",
+            "actions[3]: has an empty id",
+        ]);
+        assert_eq!(top_level_causes(&program), vec![vec![], vec![0], vec![0], vec![2]]);
+    }
+}
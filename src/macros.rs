@@ -0,0 +1,217 @@
+//! Action templates instantiated before a program runs
+//!
+//! A macro is a list of actions with `$arg` placeholders, declared in
+//! `metadata.macros` or by a `DefineMacro` action:
+//!
+//! ```json
+//! {"metadata": {"macros": {"note": {"args": ["pitch", "t"], "actions": [
+//!     {"actor": "$actor", "op": "Emit", "target": "Note", "t": "$t", "params": {"pitch": "$pitch"}}
+//! ]}}},
+//!  "actions": [
+//!     {"actor": "Piano1", "op": "Expand", "target": "note", "params": {"pitch": "C4", "t": 0.0}}
+//! ]}
+//! ```
+//!
+//! A string that is exactly `$name` becomes the argument's value, whatever
+//! its type; `${name}` inside a longer string is replaced by its text.
+//! `$actor` is the expanding action's actor unless passed explicitly.
+
+use crate::error::UclError;
+use crate::{Action, Operation, Program};
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Metadata key holding macro declarations
+pub const MACROS_KEY: &str = "macros";
+
+/// How deeply expansions may produce further `Expand`s
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Macro {
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Defaults for args an `Expand` may leave out
+    #[serde(default)]
+    pub defaults: Map<String, Value>,
+    pub actions: Vec<Value>,
+}
+
+/// Replace every `Expand` with its macro's actions and drop the macro
+/// declarations; returns how many expansions were made
+pub fn expand(program: &mut Program) -> Result<usize> {
+    let mut macros = declared(program)?;
+    if let Some(metadata) = &mut program.metadata {
+        metadata.remove(MACROS_KEY);
+    }
+    take_definitions(&mut program.actions, &mut macros)?;
+
+    let mut count = 0;
+    expand_actions(&mut program.actions, &macros, 0, &mut count)?;
+    Ok(count)
+}
+
+/// Macros declared in `metadata.macros`
+pub fn declared(program: &Program) -> Result<HashMap<String, Macro>> {
+    match program.meta().get(MACROS_KEY) {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| UclError::parse(format!("metadata.{}: {}", MACROS_KEY, e)).into()),
+        None => Ok(HashMap::new()),
+    }
+}
+
+/// Remove `DefineMacro` actions, adding what they define to `macros`
+fn take_definitions(actions: &mut Vec<Action>, macros: &mut HashMap<String, Macro>) -> Result<()> {
+    let mut error = None;
+    actions.retain(|action| {
+        if action.op != Operation::DefineMacro {
+            return true;
+        }
+        let params = action.params.clone().unwrap_or_default();
+        let definition = Macro {
+            args: serde_json::from_value(params.get("args").cloned().unwrap_or_else(|| Value::Array(vec![]))).unwrap_or_default(),
+            defaults: params.get("defaults").and_then(Value::as_object).cloned().unwrap_or_default(),
+            actions: match params.get("body").and_then(Value::as_array) {
+                Some(body) => body.clone(),
+                None => {
+                    error.get_or_insert(UclError::missing("DefineMacro", "body"));
+                    Vec::new()
+                }
+            },
+        };
+        macros.insert(action.target.clone(), definition);
+        false
+    });
+    if let Some(error) = error {
+        return Err(error.into());
+    }
+
+    for action in actions.iter_mut() {
+        let mut nested = Ok(());
+        action.visit_children_mut(&mut |_, children| {
+            if nested.is_ok() {
+                nested = take_definitions(children, macros);
+            }
+        });
+        nested?;
+    }
+    Ok(())
+}
+
+fn expand_actions(actions: &mut Vec<Action>, macros: &HashMap<String, Macro>, depth: usize, count: &mut usize) -> Result<()> {
+    let mut expanded = Vec::with_capacity(actions.len());
+    for mut action in std::mem::take(actions) {
+        if action.op != Operation::Expand {
+            let mut nested = Ok(());
+            action.visit_children_mut(&mut |_, children| {
+                if nested.is_ok() {
+                    nested = expand_actions(children, macros, depth, count);
+                }
+            });
+            nested?;
+            expanded.push(action);
+            continue;
+        }
+
+        if depth >= MAX_DEPTH {
+            return Err(UclError::RecursionLimit { depth: MAX_DEPTH }.into());
+        }
+        let mut instance = instantiate(&action, macros)?;
+        *count += 1;
+        expand_actions(&mut instance, macros, depth + 1, count)?;
+        expanded.extend(instance);
+    }
+    *actions = expanded;
+    Ok(())
+}
+
+/// The actions one `Expand` stands for
+fn instantiate(action: &Action, macros: &HashMap<String, Macro>) -> Result<Vec<Action>> {
+    let Some(definition) = macros.get(&action.target) else {
+        bail!("Unknown macro: {}", action.target);
+    };
+
+    let mut args = definition.defaults.clone();
+    args.insert("actor".to_string(), Value::from(action.actor.clone()));
+    for (name, value) in action.params.iter().flatten() {
+        if !definition.args.contains(name) && !definition.defaults.contains_key(name) && name != "actor" {
+            bail!("Macro {} has no argument '{}'", action.target, name);
+        }
+        args.insert(name.clone(), value.clone());
+    }
+    if let Some(missing) = definition.args.iter().find(|name| !args.contains_key(*name)) {
+        return Err(UclError::missing(format!("Expand of {}", action.target), format!("'{}'", missing)).into());
+    }
+
+    let mut actions = Value::Array(definition.actions.clone());
+    substitute(&mut actions, &args);
+    serde_json::from_value(actions).map_err(|e| UclError::parse(format!("macro {}: {}", action.target, e)).into())
+}
+
+fn substitute(value: &mut Value, args: &Map<String, Value>) {
+    match value {
+        Value::String(s) => {
+            if let Some(arg) = s.strip_prefix('$').and_then(|name| args.get(name)) {
+                *value = arg.clone();
+            } else if s.contains("${") {
+                for (name, arg) in args {
+                    let text = arg.as_str().map_or_else(|| arg.to_string(), str::to_string);
+                    *s = s.replace(&format!("${{{}}}", name), &text);
+                }
+            }
+        }
+        Value::Object(map) => map.values_mut().for_each(|v| substitute(v, args)),
+        Value::Array(items) => items.iter_mut().for_each(|v| substitute(v, args)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_expands_metadata_and_action_macros() {
+        let mut program = Program::from_json(r#"{"metadata": {"macros": {
+            "note": {"args": ["pitch", "t"], "defaults": {"velocity": 80}, "actions": [
+                {"actor": "$actor", "op": "Emit", "target": "Note", "t": "$t", "params": {"pitch": "$pitch", "velocity": "$velocity", "label": "note ${pitch}"}}
+            ]}}},
+         "actions": [
+            {"actor": "VM", "op": "DefineMacro", "target": "pair", "params": {"args": ["low", "high"], "body": [
+                {"actor": "$actor", "op": "Expand", "target": "note", "params": {"pitch": "$low", "t": 0}},
+                {"actor": "$actor", "op": "Expand", "target": "note", "params": {"pitch": "$high", "t": 1, "velocity": 90}}
+            ]}},
+            {"actor": "Piano1", "op": "Expand", "target": "pair", "params": {"low": "C4", "high": "C5"}},
+            {"actor": "VM", "op": "While", "target": "loop", "condition": {"type": "comparison", "op": "==", "left": 1, "right": 2},
+             "body": [{"actor": "Piano2", "op": "Expand", "target": "note", "params": {"pitch": "G4", "t": 2.5}}]}
+        ]}"#).unwrap();
+
+        assert_eq!(expand(&mut program).unwrap(), 4);
+        assert!(program.meta().get(MACROS_KEY).is_none());
+        assert_eq!(program.actions.len(), 3);
+
+        let high = &program.actions[1];
+        assert_eq!((high.actor.as_str(), high.op.clone(), high.t), ("Piano1", Operation::Emit, Some(1.0)));
+        assert_eq!(high.params.as_ref().unwrap()["velocity"], json!(90));
+        assert_eq!(high.params.as_ref().unwrap()["label"], json!("note C5"));
+        let nested = &program.actions[2].body_actions.as_ref().unwrap()[0];
+        assert_eq!((nested.actor.as_str(), nested.t), ("Piano2", Some(2.5)));
+        assert_eq!(nested.params.as_ref().unwrap()["velocity"], json!(80));
+    }
+
+    #[test]
+    fn test_expansion_errors() {
+        let expand_json = |json: &str| expand(&mut Program::from_json(json).unwrap()).unwrap_err().to_string();
+
+        assert_eq!(expand_json(r#"{"actions": [{"actor": "VM", "op": "Expand", "target": "nope"}]}"#), "Unknown macro: nope");
+        assert_eq!(
+            expand_json(r#"{"metadata": {"macros": {"m": {"args": ["x"], "actions": []}}}, "actions": [{"actor": "VM", "op": "Expand", "target": "m"}]}"#),
+            "Expand of m requires 'x'"
+        );
+        assert!(expand_json(r#"{"metadata": {"macros": {"loop": {"actions": [{"actor": "VM", "op": "Expand", "target": "loop"}]}}},
+            "actions": [{"actor": "VM", "op": "Expand", "target": "loop"}]}"#).starts_with("Maximum recursion depth"));
+    }
+}
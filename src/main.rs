@@ -1,8 +1,12 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use ucl::{Program, Operation, compiler::RubyCompiler, simulator::{BrainSimulator, RobotSimulator, MockAISimulator}, coordinator::MultiSubstrateCoordinator};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "local-model")]
+use ucl::simulator::LocalModelBackend;
+use ucl::{Program, analysis, compose, compiler::{EnglishCompiler, JavaScriptCompiler, PrologCompiler, RubyCompiler, RustCompiler, ShellCompiler, SolidityCompiler, SqlCompiler, SqlDialect, WasmCompiler, wasm}, simulator::{ArmLimits, BrainSimulator, CognitiveProfile, Forgetting, InteractiveBrainRunner, RobotSimulator, MockAISimulator, NoiseModel, Workspace, CallPolicy, GenerationBackend, PromptTemplate, ResponseCache, noise}, coordinator::MultiSubstrateCoordinator, custom_ops, dialogue::DialogueRunner, decompiler::RubyDecompiler, gallery, incremental::IncrementalRunner, links, minimize::{Failure, minimize}, project::Project, routing::{CostModel, ROUTABLE_SUBSTRATES}, scheduler::Scheduler, schema, signing, slice::SliceFilter, stream::StreamExecutor, transform::{dce, Fold, Pass, PassManager}};
 
 #[derive(Parser)]
 #[command(name = "ucl")]
@@ -18,6 +22,15 @@ enum Commands {
     Validate {
         /// Path to the UCL file
         file: PathBuf,
+
+        /// Sidecar JSON declaring custom operations (merged over metadata.custom_ops)
+        #[arg(long)]
+        ops: Option<PathBuf>,
+
+        /// Reject operations this version doesn't know instead of loading them as Custom,
+        /// and params an operation is missing or has the wrong type for
+        #[arg(long)]
+        strict: bool,
     },
 
     /// Display a UCL file in human-readable format
@@ -30,48 +43,294 @@ enum Commands {
         compact: bool,
     },
 
+    /// Rewrite UCL files in canonical form (sorted keys) for clean diffs
+    Fmt {
+        /// UCL files to format
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Don't write; fail if any file isn't already formatted
+        #[arg(long)]
+        check: bool,
+
+        /// Write compact canonical JSON (byte-identical for the same program) instead of pretty JSON
+        #[arg(long)]
+        canonical: bool,
+    },
+
     /// Convert a UCL file to a different format
     Convert {
         /// Path to the UCL file
         file: PathBuf,
 
-        /// Output format (currently only json)
+        /// Output format: json, yaml or ucl-text
         #[arg(short, long, default_value = "json")]
         format: String,
     },
 
+    /// Expand macros, printing the program they stand for
+    Expand {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
     /// Analyze a UCL program
     Analyze {
         /// Path to the UCL file
         file: PathBuf,
     },
 
+    /// Show who talks to whom via Emit/Receive
+    Network {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Output format (text, dot or json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Lay actions out by `t` and `dur` and report overlapping changes to a target
+    Timeline {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Output format (text or json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Graph which actions produce the values later actions consume
+    Graph {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Output format (dot, mermaid or json)
+        #[arg(short, long, default_value = "dot")]
+        format: String,
+    },
+
+    /// Trace how values flow from sources to the actions they influence
+    Flow {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Fail if an untrusted source reaches a sensitive operation
+        #[arg(long)]
+        taint: bool,
+    },
+
+    /// Edit a UCL program in a terminal UI (requires the `tui` feature)
+    Edit {
+        /// Path to the UCL file
+        file: PathBuf,
+    },
+
+    /// Infer effects tags from operations and params
+    Effects {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Add the inferred tags to the file in place
+        #[arg(long)]
+        write: bool,
+
+        /// Fail if any action is missing an inferred tag
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Suggest simpler ways to write common patterns
+    Idioms {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Rewrite the file with the fixable suggestions applied
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Report variables read before they're bound, functions called before they're defined
+    /// and loop variables that overwrite other variables
+    Lint {
+        /// Path to the UCL file
+        file: PathBuf,
+    },
+
+    /// Rewrite a program with a sequence of transformation passes
+    Transform {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Pass to run, in order (inline, fold, dce, rename-actors:OLD=NEW,...); repeatable
+        #[arg(short, long = "pass", required = true)]
+        passes: Vec<String>,
+
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Combine programs into one, renaming variables and functions that collide
+    Merge {
+        /// Programs to combine, in order
+        #[arg(required = true, num_args = 2..)]
+        files: Vec<PathBuf>,
+
+        /// Share variables and functions between the programs instead of renaming
+        #[arg(long)]
+        shared: bool,
+
+        /// Prefix each later program's actors with its file name, e.g. `b.VM`
+        #[arg(long)]
+        prefix_actors: bool,
+
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Extract the actions for some actors, effect tags or targets, keeping the control flow around them
+    Slice {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Keep actions by this actor; repeatable
+        #[arg(long = "actor")]
+        actors: Vec<String>,
+
+        /// Keep actions with this effect tag, e.g. Legal; repeatable
+        #[arg(long = "effect")]
+        effects: Vec<String>,
+
+        /// Keep actions on this target; repeatable
+        #[arg(long = "target")]
+        targets: Vec<String>,
+
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Fold constants, then remove dead stores, functions and branches
+    Optimize {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Shrink a program to the smallest one that still fails
+    Minimize {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Failure to preserve (brain-error, robot-error, ruby-error or ruby-brain-differ)
+        #[arg(long)]
+        failure: String,
+
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Sign a program with an ed25519 key (created if the key file doesn't exist)
+    Sign {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Hex-encoded signing key; its public half is written next to it as .pub
+        #[arg(short, long)]
+        key: PathBuf,
+
+        /// Output file (optional, defaults to signing in place)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Verify a signed program's integrity and origin
+    Verify {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Only accept signatures from this public key file
+        #[arg(long)]
+        public_key: Option<PathBuf>,
+    },
+
+    /// Build the project described by ucl.toml into its output artifacts
+    Build {
+        /// Path to the manifest (defaults to the nearest ucl.toml)
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+
     /// Compile a UCL program to another language
     Compile {
         /// Path to the UCL file
         file: PathBuf,
 
-        /// Target language (currently only ruby)
+        /// Target language: ruby, js, rust, bash, wasm (binary, needs --output), wat, sql (SQLite), postgres or prolog for a program's facts, solidity for a legal contract, or english for a plain-English narrative
         #[arg(short, long, default_value = "ruby")]
         target: String,
 
         /// Output file (optional, defaults to stdout)
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// Fold constant arithmetic and prune constant If branches first
+        #[arg(long)]
+        fold: bool,
+
+        /// Seed the compiled program's random numbers (ruby, js, rust and bash)
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+
+    /// Decompile a Ruby script (a constrained subset) into a UCL program
+    Decompile {
+        /// Path to the Ruby file
+        file: PathBuf,
+
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 
-    /// Compile and run a UCL program
+    /// Compile and run a UCL program, or the project in ucl.toml when no file is given
     Run {
         /// Path to the UCL file
-        file: PathBuf,
+        file: Option<PathBuf>,
 
-        /// Target language (ruby or brain)
-        #[arg(short, long, default_value = "ruby")]
-        target: String,
+        /// Target language (ruby, js, rust, bash, wasm, brain or robot); defaults to ruby, or to the manifest's targets
+        #[arg(short, long)]
+        target: Option<String>,
 
         /// Verbose output
         #[arg(short, long)]
         verbose: bool,
+
+        /// Fold constant arithmetic and prune constant If branches first
+        #[arg(long)]
+        fold: bool,
+
+        /// Run top-level actions in the order their `t` places them
+        #[arg(long)]
+        timeline: bool,
+
+        /// Run top-level actions wave by wave in dependency order rather than as written
+        #[arg(long, conflicts_with = "timeline")]
+        dag: bool,
+
+        /// Seed random draws so runs repeat exactly
+        #[arg(long)]
+        seed: Option<u64>,
     },
 
     /// Simulate execution on a virtual human brain
@@ -86,6 +345,37 @@ enum Commands {
         /// Run on production (your actual brain) instead of simulated brain
         #[arg(short, long)]
         production: bool,
+
+        /// Explain which actions a belief was derived from
+        #[arg(long, value_name = "BELIEF")]
+        why: Option<String>,
+
+        /// Seed GenRandomInt so runs repeat exactly
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Start from the brain state saved in this file, if it exists, and
+        /// save the state back to it after the run
+        #[arg(long, value_name = "FILE", conflicts_with = "production")]
+        state: Option<PathBuf>,
+
+        #[command(flatten)]
+        limits: BrainLimits,
+    },
+
+    /// Run a two-actor program as a conversation between two brains, each
+    /// hearing what the other emits
+    Dialogue {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Verbose output showing each cognitive operation
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Seed each brain's GenRandomInt so runs repeat exactly
+        #[arg(long)]
+        seed: Option<u64>,
     },
 
     /// Simulate execution on a virtual robot
@@ -96,6 +386,33 @@ enum Commands {
         /// Verbose output showing each physical operation
         #[arg(short, long)]
         verbose: bool,
+
+        /// Run N noisy simulations and report how often the program succeeds
+        #[arg(long, value_name = "N")]
+        monte_carlo: Option<usize>,
+
+        /// Standard deviation of placement noise (enables noise)
+        #[arg(long)]
+        position_sigma: Option<f64>,
+
+        /// Relative standard deviation of amount noise (enables noise)
+        #[arg(long)]
+        amount_sigma: Option<f64>,
+
+        /// Standard deviation of ReadSensor readings (enables noise)
+        #[arg(long)]
+        sensor_sigma: Option<f64>,
+
+        /// Seed for the noise random source
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Explain which actions a variable was derived from
+        #[arg(long, value_name = "VARIABLE")]
+        why: Option<String>,
+
+        #[command(flatten)]
+        arm: ArmArgs,
     },
 
     /// Simulate AI code generation (Mock LLM)
@@ -106,6 +423,60 @@ enum Commands {
         /// Verbose output showing generation process
         #[arg(short, long)]
         verbose: bool,
+
+        #[command(flatten)]
+        backend: AiBackendArgs,
+
+        /// JSON file with custom system/user prompt templates
+        #[arg(long)]
+        prompt_config: Option<PathBuf>,
+
+        /// Always query the backend instead of reusing cached responses
+        #[arg(long)]
+        no_ai_cache: bool,
+
+        #[command(flatten)]
+        limits: AiCallLimits,
+    },
+
+    /// Generate a UCL program from a plain-English instruction
+    FromText {
+        /// What the program should do, e.g. "compute a factorial"
+        instruction: String,
+
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Output format: json, yaml or ucl-text (defaults to the output file's extension)
+        #[arg(short, long)]
+        format: Option<String>,
+
+        /// Only generate operations this substrate can run
+        #[arg(long)]
+        substrate: Option<String>,
+
+        #[command(flatten)]
+        backend: AiBackendArgs,
+
+        /// Always query the backend instead of reusing cached responses
+        #[arg(long)]
+        no_ai_cache: bool,
+
+        #[command(flatten)]
+        limits: AiCallLimits,
+    },
+
+    /// Work with a directory of UCL programs
+    Corpus {
+        #[command(subcommand)]
+        action: CorpusAction,
+    },
+
+    /// Manage the on-disk cache of AI backend responses
+    AiCache {
+        #[command(subcommand)]
+        action: AiCacheAction,
     },
 
     /// Execute across multiple substrates in parallel
@@ -116,68 +487,272 @@ enum Commands {
         /// Verbose output
         #[arg(short, long)]
         verbose: bool,
+
+        /// Route actions without a substrate actor by cost instead of to the brain
+        #[arg(long)]
+        auto_route: bool,
+
+        /// JSON cost model for --auto-route (defaults to the built-in one)
+        #[arg(long)]
+        cost_model: Option<PathBuf>,
+
+        /// Also run each action on this substrate and compare results
+        #[arg(long)]
+        speculate: Option<String>,
+
+        /// Show a live dashboard instead of printing as actions run
+        #[arg(long)]
+        tui: bool,
+
+        /// Run independent actions wave by wave instead of in program order
+        #[arg(long)]
+        dag: bool,
+
+        /// Seed every substrate's random draws so runs repeat exactly
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+
+    /// Execute actions read from stdin against a persistent simulator
+    Exec {
+        /// Simulator to run on (brain, robot or ai)
+        #[arg(short, long, default_value = "brain")]
+        target: String,
+
+        /// Read one JSON action (or program) per line and print each state delta as it runs
+        #[arg(long)]
+        stream: bool,
+
+        /// Run an NDJSON trace (one action per line) from this file, one action at a time
+        #[arg(long, conflicts_with = "stream")]
+        file: Option<PathBuf>,
+
+        /// With --file, how many of the newest output lines, trace entries and provenance records to keep
+        #[arg(long, default_value = "1000", requires = "file")]
+        keep: usize,
+    },
+
+    /// Re-run a program whenever the file changes, re-executing only the actions an edit affects
+    Watch {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Simulator to run on (brain, robot or ai)
+        #[arg(short, long, default_value = "brain")]
+        target: String,
+
+        /// How often to check the file, in milliseconds
+        #[arg(long, default_value = "500")]
+        interval: u64,
+    },
+
+    /// Dispatch each action to its substrate at the wall-clock time its `t` names
+    Schedule {
+        /// Path to the UCL file
+        file: PathBuf,
+
+        /// Play faster (2) or slower (0.5) than written
+        #[arg(long, default_value = "1.0")]
+        speed: f64,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Browse and run the example programs bundled with ucl
+    Examples {
+        #[command(subcommand)]
+        action: ExamplesAction,
+    },
+
+    /// Interactive tutorial: predict, step through and learn each operation family
+    Learn {
+        /// Lesson to start from (1-based)
+        #[arg(short, long, default_value = "1")]
+        lesson: usize,
     },
 }
 
-fn main() {
-    let cli = Cli::parse();
+/// Which generation backend the AI simulator queries
+#[derive(Args)]
+struct AiBackendArgs {
+    /// Generation backend (mock or local)
+    #[arg(short, long, default_value = "mock")]
+    backend: String,
 
-    match &cli.command {
-        Commands::Validate { file } => {
-            match validate_file(file) {
-                Ok(_) => {
-                    println!("✓ Valid UCL program");
-                    std::process::exit(0);
-                }
-                Err(e) => {
-                    eprintln!("✗ Validation error: {}", e);
-                    std::process::exit(1);
-                }
-            }
-        }
+    /// Path to a GGUF model file (local backend)
+    #[arg(long)]
+    model: Option<PathBuf>,
 
-        Commands::Display { file, compact } => {
-            match display_file(file, *compact) {
-                Ok(_) => std::process::exit(0),
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+    /// Path to tokenizer.json (local backend, defaults to next to the model)
+    #[arg(long)]
+    tokenizer: Option<PathBuf>,
+}
+
+impl AiBackendArgs {
+    /// The backend to use, or `None` for the built-in mock knowledge base
+    fn load(&self) -> anyhow::Result<Option<Box<dyn GenerationBackend>>> {
+        match self.backend.as_str() {
+            "mock" => Ok(None),
+            "local" => {
+                let model = self.model.as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("The local backend requires --model <file.gguf>"))?;
+                Ok(Some(load_local_backend(model, self.tokenizer.as_deref())?))
             }
+            _ => anyhow::bail!("Unsupported AI backend: {}. Use 'mock' or 'local'.", self.backend),
         }
+    }
+}
 
-        Commands::Convert { file, format } => {
-            match convert_file(file, format) {
-                Ok(_) => std::process::exit(0),
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            }
+/// Forgetting and cognitive limits of the simulated brain
+#[derive(Args)]
+struct BrainLimits {
+    /// Forget unused beliefs, their strength halving every SECONDS of
+    /// simulated time
+    #[arg(long, value_name = "SECONDS", conflicts_with = "production")]
+    half_life: Option<f64>,
+
+    /// Cognitive limits to simulate: focused or distracted
+    #[arg(long, value_name = "PROFILE", conflicts_with = "production")]
+    profile: Option<String>,
+}
+
+/// How and where the simulated robot arm moves
+#[derive(Args)]
+struct ArmArgs {
+    /// Top arm speed, in workspace units per second
+    #[arg(long, value_name = "SPEED")]
+    max_speed: Option<f64>,
+
+    /// Furthest the arm reaches from its base
+    #[arg(long, value_name = "DISTANCE")]
+    reach: Option<f64>,
+
+    /// JSON file with the table's extents and named zones; motions that
+    /// leave it or collide are refused
+    #[arg(long, value_name = "FILE")]
+    workspace: Option<PathBuf>,
+}
+
+impl ArmArgs {
+    fn apply(&self, simulator: RobotSimulator) -> anyhow::Result<RobotSimulator> {
+        let defaults = ArmLimits::default();
+        let mut simulator = simulator.with_arm(ArmLimits {
+            max_speed: self.max_speed.unwrap_or(defaults.max_speed),
+            reach: self.reach.unwrap_or(defaults.reach),
+        });
+        if let Some(path) = &self.workspace {
+            let json = fs::read_to_string(path)?;
+            simulator = simulator.with_workspace(Workspace::from_json(&json)?);
         }
+        Ok(simulator)
+    }
+}
 
-        Commands::Analyze { file } => {
-            match analyze_file(file) {
-                Ok(_) => std::process::exit(0),
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            }
+impl BrainLimits {
+    fn apply(&self, mut simulator: BrainSimulator) -> anyhow::Result<BrainSimulator> {
+        if let Some(half_life) = self.half_life {
+            simulator = simulator.with_forgetting(Forgetting { half_life, ..Forgetting::default() });
+        }
+        if let Some(name) = &self.profile {
+            let profile = CognitiveProfile::named(name).ok_or_else(|| {
+                anyhow::anyhow!("Unsupported cognitive profile: {}. Use one of: {}", name, CognitiveProfile::NAMES.join(", "))
+            })?;
+            simulator = simulator.with_profile(profile);
         }
+        Ok(simulator)
+    }
+}
 
-        Commands::Compile { file, target, output } => {
-            match compile_file(file, target, output.as_ref()) {
-                Ok(_) => std::process::exit(0),
-                Err(e) => {
+/// Retry, timeout and rate-limit settings for AI backend calls
+#[derive(Args)]
+struct AiCallLimits {
+    /// Retries after a failed backend call
+    #[arg(long, default_value = "2")]
+    ai_retries: usize,
+
+    /// Seconds before a single backend call is abandoned (0 disables)
+    #[arg(long, default_value = "120")]
+    ai_timeout: f64,
+
+    /// Maximum backend calls per minute
+    #[arg(long)]
+    ai_rpm: Option<u32>,
+}
+
+impl AiCallLimits {
+    fn policy(&self) -> CallPolicy {
+        CallPolicy {
+            max_retries: self.ai_retries,
+            timeout: (self.ai_timeout > 0.0).then(|| std::time::Duration::from_secs_f64(self.ai_timeout)),
+            requests_per_minute: self.ai_rpm,
+            ..CallPolicy::default()
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum CorpusAction {
+    /// Group structurally similar programs
+    Cluster {
+        /// Directory of UCL files
+        dir: PathBuf,
+
+        /// Minimum similarity (0-1) for two programs to share a cluster
+        #[arg(long, default_value = "0.6")]
+        threshold: f64,
+    },
+}
+
+#[derive(Subcommand)]
+enum AiCacheAction {
+    /// Delete all cached responses
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ExamplesAction {
+    /// List bundled examples
+    List,
+
+    /// Print an example's JSON
+    Show {
+        /// Example name, e.g. fibonacci
+        name: String,
+    },
+
+    /// Run an example
+    Run {
+        /// Example name, e.g. recipe_tea
+        name: String,
+
+        /// Target (ruby, js, rust, bash, wasm, brain or robot)
+        #[arg(short, long, default_value = "brain")]
+        target: String,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Commands::Exec { target, stream, file, keep } => {
+            match exec_stdin(target, *stream, file.as_deref(), *keep) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
                     eprintln!("Error: {}", e);
                     std::process::exit(1);
                 }
             }
         }
 
-        Commands::Run { file, target, verbose } => {
-            match run_file(file, target, *verbose) {
+        Commands::Watch { file, target, interval } => {
+            match watch_file(file, target, *interval) {
                 Ok(_) => std::process::exit(0),
                 Err(e) => {
                     eprintln!("Error: {}", e);
@@ -186,8 +761,8 @@ fn main() {
             }
         }
 
-        Commands::Brain { file, verbose, production } => {
-            match brain_simulate(file, *verbose, *production) {
+        Commands::Dialogue { file, verbose, seed } => {
+            match dialogue_file(file, *verbose, *seed) {
                 Ok(_) => std::process::exit(0),
                 Err(e) => {
                     eprintln!("Error: {}", e);
@@ -196,8 +771,8 @@ fn main() {
             }
         }
 
-        Commands::Robot { file, verbose } => {
-            match robot_simulate(file, *verbose) {
+        Commands::Schedule { file, speed, verbose } => {
+            match schedule_file(file, *speed, *verbose) {
                 Ok(_) => std::process::exit(0),
                 Err(e) => {
                     eprintln!("Error: {}", e);
@@ -206,8 +781,8 @@ fn main() {
             }
         }
 
-        Commands::Ai { file, verbose } => {
-            match ai_simulate(file, *verbose) {
+        Commands::Examples { action } => {
+            match examples(action) {
                 Ok(_) => std::process::exit(0),
                 Err(e) => {
                     eprintln!("Error: {}", e);
@@ -216,8 +791,8 @@ fn main() {
             }
         }
 
-        Commands::Parallel { file, verbose } => {
-            match parallel_execute(file, *verbose) {
+        Commands::Learn { lesson } => {
+            match learn(*lesson) {
                 Ok(_) => std::process::exit(0),
                 Err(e) => {
                     eprintln!("Error: {}", e);
@@ -225,150 +800,1133 @@ fn main() {
                 }
             }
         }
-    }
-}
 
-fn validate_file(path: &PathBuf) -> anyhow::Result<Program> {
-    let content = fs::read_to_string(path)?;
-    let program = Program::from_json(&content)?;
-    Ok(program)
-}
+        Commands::Validate { file, ops, strict } => {
+            match Program::load_checked(file, *strict).and_then(|(mut program, unknown)| {
+                program.expand_macros()?;
+                check_links(&program)?;
+                let problems = schema::check(&program);
+                if *strict && !problems.is_empty() {
+                    let lines: Vec<String> = problems.iter().map(|p| format!("  {}", p.display())).collect();
+                    anyhow::bail!("{} param problem(s):\n{}", problems.len(), lines.join("\n"));
+                }
+                let warnings = custom_op_warnings(&program, ops.as_deref())?;
+                Ok((program, unknown, warnings))
+            }) {
+                Ok((program, unknown_warnings, custom_warnings)) => {
+                    println!("✓ Valid UCL program");
+                    for warning in program.meta().validate() {
+                        println!("  ⚠️  metadata.{}", warning.display());
+                    }
+                    for warning in unknown_warnings {
+                        println!("  ⚠️  {}", warning);
+                    }
+                    for warning in custom_warnings {
+                        println!("  ⚠️  {}", warning.display());
+                    }
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    eprintln!("✗ Validation error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
 
-fn display_file(path: &PathBuf, compact: bool) -> anyhow::Result<()> {
-    let program = validate_file(path)?;
+        Commands::Display { file, compact } => {
+            match display_file(file, *compact) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
 
-    if compact {
-        println!("{}", serde_json::to_string(&program)?);
-    } else {
-        if let Some(metadata) = &program.metadata {
-            println!("=== Metadata ===");
-            for (key, value) in metadata {
-                println!("  {}: {}", key, value);
+        Commands::Fmt { files, check, canonical } => {
+            match fmt_files(files, *check, *canonical) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
             }
-            println!();
         }
 
-        println!("=== Actions ({}) ===", program.actions.len());
-        for (i, action) in program.actions.iter().enumerate() {
-            println!("\n[{}] {:?}", i, action.op);
-            println!("  Actor:  {}", action.actor);
-            println!("  Target: {}", action.target);
+        Commands::Convert { file, format } => {
+            match convert_file(file, format) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
 
-            if let Some(t) = action.t {
-                println!("  Time:   {}", t);
+        Commands::Expand { file, output } => {
+            match expand_file(file, output.as_ref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
             }
+        }
 
-            if let Some(dur) = action.dur {
-                println!("  Duration: {}", dur);
+        Commands::Analyze { file } => {
+            match analyze_file(file) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
             }
+        }
 
-            if let Some(params) = &action.params {
-                println!("  Parameters:");
-                for (key, value) in params {
-                    println!("    {}: {}", key, value);
+        Commands::Graph { file, format } => {
+            match graph_file(file, format) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
                 }
             }
+        }
 
-            if let Some(effects) = &action.effects {
-                println!("  Effects: [{}]", effects.join(", "));
+        Commands::Timeline { file, format } => {
+            match timeline_file(file, format) {
+                Ok(conflicts) => std::process::exit(if conflicts { 1 } else { 0 }),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Network { file, format } => {
+            match network_file(file, format) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Flow { file, taint } => {
+            match flow_file(file, *taint) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Edit { file } => {
+            match edit_file(file) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Effects { file, write, check } => {
+            match effects_file(file, *write, *check) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Idioms { file, fix } => {
+            match idioms_file(file, *fix) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Lint { file } => {
+            match lint_file(file) {
+                Ok(true) => std::process::exit(0),
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Transform { file, passes, output } => {
+            match transform_file(file, passes, output.as_ref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Merge { files, shared, prefix_actors, output } => {
+            match merge_files(files, *shared, *prefix_actors, output.as_ref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Slice { file, actors, effects, targets, output } => {
+            let filter = SliceFilter { actors: actors.clone(), effects: effects.clone(), targets: targets.clone() };
+            match slice_file(file, &filter, output.as_ref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Optimize { file, output } => {
+            match optimize_file(file, output.as_ref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Minimize { file, failure, output } => {
+            match minimize_file(file, failure, output.as_ref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Sign { file, key, output } => {
+            match sign_file(file, key, output.as_ref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Verify { file, public_key } => {
+            match verify_file(file, public_key.as_deref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Build { manifest } => {
+            match build_project(manifest.as_deref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Compile { file, target, output, fold, seed } => {
+            match compile_file(file, target, output.as_ref(), *fold, *seed) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Decompile { file, output } => {
+            match decompile_file(file, output.as_ref()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
             }
         }
+
+        Commands::Run { file, target, verbose, fold, timeline, dag, seed } => {
+            let result = match file {
+                Some(file) => run_file(file, target.as_deref().unwrap_or("ruby"), *verbose, *fold, *timeline, *dag, *seed),
+                None => run_project(target.as_deref(), *verbose, *seed),
+            };
+            match result {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Brain { file, verbose, production, why, seed, state, limits } => {
+            match brain_simulate(file, *verbose, *production, why.as_deref(), *seed, state.as_deref(), limits) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Robot { file, verbose, monte_carlo, position_sigma, amount_sigma, sensor_sigma, seed, why, arm } => {
+            let noise = if monte_carlo.is_some() || position_sigma.is_some() || amount_sigma.is_some() || sensor_sigma.is_some() {
+                let defaults = NoiseModel::default();
+                Some(NoiseModel::new(
+                    position_sigma.unwrap_or(defaults.position_sigma),
+                    amount_sigma.unwrap_or(defaults.amount_sigma),
+                ).with_sensor_sigma(sensor_sigma.unwrap_or(defaults.sensor_sigma)))
+            } else {
+                None
+            };
+
+            match robot_simulate(file, *verbose, noise, *monte_carlo, *seed, why.as_deref(), arm) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::FromText { instruction, output, format, substrate, backend, no_ai_cache, limits } => {
+            match from_text(instruction, output.as_ref(), format.as_deref(), substrate.as_deref(), backend, !*no_ai_cache, limits.policy()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Ai { file, verbose, backend, prompt_config, no_ai_cache, limits } => {
+            match ai_simulate(file, *verbose, backend, prompt_config.as_ref(), !*no_ai_cache, limits.policy()) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Corpus { action } => {
+            match corpus(action) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::AiCache { action } => {
+            match ai_cache(action) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Parallel { file, verbose, auto_route, cost_model, speculate, tui, dag, seed } => {
+            let mut coordinator = MultiSubstrateCoordinator::new().with_dag(*dag);
+            if let Some(seed) = seed {
+                coordinator = coordinator.with_seed(*seed);
+            }
+            match parallel_execute(coordinator, file, *verbose, *auto_route, cost_model.as_ref(), speculate.as_deref(), *tui) {
+                Ok(_) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+fn exec_stdin(target: &str, stream: bool, file: Option<&Path>, keep: usize) -> anyhow::Result<()> {
+    let mut executor = StreamExecutor::new(target)?;
+    let stdin = std::io::stdin();
+
+    if let Some(path) = file {
+        let input = fs::File::open(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+        executor = executor.with_history_limit(keep);
+        let count = executor.run_actions(std::io::BufReader::new(input))?;
+        println!("✓ Executed {} actions from {}\n", count, path.display());
+        println!("{}", executor.display());
+    } else if stream {
+        executor.run(stdin.lock(), &mut std::io::stdout())?;
+    } else {
+        let input = std::io::read_to_string(stdin)?;
+        let changes = executor.execute_line(&input)?;
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    }
+    Ok(())
+}
+
+fn watch_file(path: &PathBuf, target: &str, interval: u64) -> anyhow::Result<()> {
+    let mut runner = IncrementalRunner::new(target)?;
+    let mut last_modified = None;
+
+    println!("👀 Watching {} (Ctrl-C to stop)", path.display());
+
+    loop {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified != last_modified {
+            last_modified = modified;
+
+            let outcome = validate_file(path).and_then(|program| {
+                let summary = runner.run(&program)?;
+                Ok((program.actions.len(), summary))
+            });
+
+            println!("\n{}", "=".repeat(60));
+            match outcome {
+                Ok((total, summary)) => {
+                    println!("🔁 Re-executed {} of {} actions ({} reused)", summary.executed.len(), total, summary.reused);
+                    if !summary.executed.is_empty() {
+                        let steps: Vec<String> = summary.executed.iter().map(|i| (i + 1).to_string()).collect();
+                        println!("   Steps: {}", steps.join(", "));
+                    }
+                    println!("\n{}", runner.display());
+                }
+                Err(e) => println!("❌ {}", e),
+            }
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(interval));
+    }
+}
+
+fn schedule_file(path: &PathBuf, speed: f64, verbose: bool) -> anyhow::Result<()> {
+    let program = validate_file(path)?;
+    let mut coordinator = MultiSubstrateCoordinator::new().with_verbose(verbose);
+
+    println!("⏱️  Real-time schedule: {} actions{}", program.actions.len(),
+        if speed != 1.0 { format!(" at {}x speed", speed) } else { String::new() });
+    println!();
+
+    let report = Scheduler::new().with_speed(speed).run(&program, &mut coordinator, |action, dispatch| {
+        println!("  [{:>8.3}s] {:<10} {:?} → {}{}",
+            dispatch.sent, dispatch.substrate, action.op, action.target,
+            if dispatch.lateness() >= 0.001 { format!("  (late {:.1}ms)", dispatch.lateness() * 1000.0) } else { String::new() });
+    })?;
+
+    println!();
+    println!("✅ Dispatched {} actions; lateness mean {:.2}ms, max {:.2}ms",
+        report.dispatches.len(), report.mean_lateness() * 1000.0, report.max_lateness() * 1000.0);
+    Ok(())
+}
+
+fn dialogue_file(path: &PathBuf, verbose: bool, seed: Option<u64>) -> anyhow::Result<()> {
+    let program = validate_file(path)?;
+    let mut dialogue = DialogueRunner::new().with_verbose(verbose);
+    if let Some(seed) = seed {
+        dialogue = dialogue.with_seed(seed);
+    }
+
+    println!("💬 Simulating a conversation between two brains...\n");
+
+    for turn in dialogue.run(&program)? {
+        if turn.said.is_empty() {
+            println!("  {}: …", turn.speaker);
+        }
+        for line in &turn.said {
+            println!("  {}: {}", turn.speaker, line);
+        }
+    }
+
+    println!("\n{}", dialogue.display());
+    Ok(())
+}
+
+fn examples(action: &ExamplesAction) -> anyhow::Result<()> {
+    match action {
+        ExamplesAction::List => {
+            let examples = gallery::all();
+            let width = examples.iter().map(|e| e.name.len()).max().unwrap_or(0);
+            println!("📚 {} bundled examples:\n", examples.len());
+            for example in examples {
+                println!("  {:width$}  {}", example.name, example.description().unwrap_or_default(), width = width);
+            }
+            println!("\nShow one with `ucl examples show <name>`, run it with `ucl examples run <name>`.");
+        }
+        ExamplesAction::Show { name } => {
+            print!("{}", gallery::get(name)?.source);
+        }
+        ExamplesAction::Run { name, target, verbose } => {
+            run_program(&gallery::get(name)?.program()?, target, *verbose, None)?;
+        }
+    }
+    Ok(())
+}
+
+fn learn(lesson: usize) -> anyhow::Result<()> {
+    let count = ucl::tutorial::LESSONS.len();
+    if lesson == 0 || lesson > count {
+        anyhow::bail!("Lesson must be between 1 and {}", count);
+    }
+
+    println!("📚 Welcome to UCL! Answer each question, then press Enter to step through.");
+    println!("   Type q at any prompt to stop.");
+
+    let stdin = std::io::stdin();
+    ucl::tutorial::run(&mut stdin.lock(), &mut std::io::stdout(), lesson - 1)?;
+    Ok(())
+}
+
+/// Set when the user presses Ctrl-C, so simulators can stop and run OnInterrupt handlers
+fn ctrl_c_flag() -> anyhow::Result<Arc<AtomicBool>> {
+    static FLAG: std::sync::OnceLock<Arc<AtomicBool>> = std::sync::OnceLock::new();
+    if let Some(flag) = FLAG.get() {
+        return Ok(Arc::clone(flag));
+    }
+
+    let flag = Arc::new(AtomicBool::new(false));
+    let handler_flag = Arc::clone(&flag);
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))?;
+    Ok(Arc::clone(FLAG.get_or_init(|| flag)))
+}
+
+fn custom_op_warnings(program: &Program, sidecar: Option<&Path>) -> anyhow::Result<Vec<custom_ops::CustomOpWarning>> {
+    let mut declarations = custom_ops::Declarations::from_program(program)?;
+    if let Some(path) = sidecar {
+        declarations.merge(custom_ops::Declarations::from_file(path)?);
+    }
+    Ok(declarations.check(program))
+}
+
+/// Load a program with its macros expanded
+fn validate_file(path: impl AsRef<Path>) -> anyhow::Result<Program> {
+    let mut program = Program::load(path.as_ref())?;
+    program.expand_macros()?;
+    check_links(&program)?;
+    Ok(program)
+}
+
+/// Load a program as written, macros unexpanded, for commands that write
+/// it back or sign it
+fn load_source(path: impl AsRef<Path>) -> anyhow::Result<Program> {
+    let program = Program::load(path.as_ref())?;
+    check_links(&program)?;
+    Ok(program)
+}
+
+/// Fail on ids and `caused_by` references that don't hold together
+fn check_links(program: &Program) -> anyhow::Result<()> {
+    let problems = links::check(program);
+    if !problems.is_empty() {
+        let lines: Vec<String> = problems.iter().map(|p| format!("  {}", p.display())).collect();
+        anyhow::bail!("{} link problem(s):\n{}", problems.len(), lines.join("\n"));
+    }
+    Ok(())
+}
+
+fn fmt_files(paths: &[PathBuf], check: bool, canonical: bool) -> anyhow::Result<()> {
+    let mut unformatted = Vec::new();
+
+    for path in paths {
+        let content = fs::read_to_string(path)?;
+        let program = Program::load(path)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        let formatted = match format_for_path(path) {
+            "json" if canonical => program.to_canonical_json()? + "\n",
+            format => format_program(&program, format)?,
+        };
+
+        if formatted == content {
+            continue;
+        }
+        if check {
+            println!("✗ {}", path.display());
+        } else {
+            fs::write(path, &formatted)?;
+            println!("✓ Formatted {}", path.display());
+        }
+        unformatted.push(path);
+    }
+
+    if check && !unformatted.is_empty() {
+        anyhow::bail!("{} of {} files need formatting", unformatted.len(), paths.len());
+    }
+    Ok(())
+}
+
+fn display_file(path: &PathBuf, compact: bool) -> anyhow::Result<()> {
+    let program = validate_file(path)?;
+
+    if compact {
+        println!("{}", serde_json::to_string(&program)?);
+    } else {
+        if let Some(metadata) = &program.metadata {
+            println!("=== Metadata ===");
+            for (key, value) in metadata {
+                println!("  {}: {}", key, value);
+            }
+            println!();
+        }
+
+        println!("=== Actions ({}) ===", program.actions.len());
+        for (i, action) in program.actions.iter().enumerate() {
+            println!("\n[{}] {:?}", i, action.op);
+            println!("  Actor:  {}", action.actor);
+            println!("  Target: {}", action.target);
+
+            if let Some(t) = action.t {
+                println!("  Time:   {}", t);
+            }
+
+            if let Some(dur) = action.dur {
+                println!("  Duration: {}", dur);
+            }
+
+            if let Some(params) = &action.params {
+                println!("  Parameters:");
+                for (key, value) in params {
+                    println!("    {}: {}", key, value);
+                }
+            }
+
+            if let Some(effects) = &action.effects {
+                println!("  Effects: [{}]", effects.join(", "));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn convert_file(path: &Path, format: &str) -> anyhow::Result<()> {
+    let program = Program::load(path)?;
+    print!("{}", format_program(&program, format)?);
+    Ok(())
+}
+
+fn expand_file(path: &Path, output: Option<&PathBuf>) -> anyhow::Result<()> {
+    let mut program = Program::load(path)?;
+    let count = program.expand_macros()?;
+    eprintln!("Expanded {} macro use(s)", count);
+
+    let code = format_program(&program, format_for_path(output.map_or(path, |p| p.as_path())))?;
+    if let Some(output_path) = output {
+        fs::write(output_path, code)?;
+        eprintln!("Wrote {}", output_path.display());
+    } else {
+        print!("{}", code);
+    }
+
+    Ok(())
+}
+
+/// A program serialized as `json`, `yaml` or `ucl-text`, ending in a newline
+fn format_program(program: &Program, format: &str) -> anyhow::Result<String> {
+    match format {
+        "json" => Ok(program.to_json()? + "\n"),
+        "yaml" => program.to_yaml(),
+        "ucl-text" => program.to_text(),
+        _ => anyhow::bail!("Unsupported format: {}. Use 'json', 'yaml' or 'ucl-text'.", format),
+    }
+}
+
+/// The program format a file's extension implies, as `Program::load` reads it
+fn format_for_path(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("ucl") => "ucl-text",
+        Some("yaml" | "yml") => "yaml",
+        _ => "json",
+    }
+}
+
+fn analyze_file(path: &PathBuf) -> anyhow::Result<()> {
+    let program = validate_file(path)?;
+
+    let stats = analysis::program_stats(&program);
+
+    println!("=== UCL Program Analysis ===\n");
+    println!("Total actions: {} ({} top-level)", stats.total, stats.top_level);
+    println!("Control flow: {} If/While/For, {} function(s), max nesting depth {}",
+        stats.control_flow, stats.functions, stats.max_depth);
+
+    println!("\nOperation distribution:");
+    for (op, count) in analysis::ProgramStats::ranked(&stats.operations) {
+        println!("  {}: {}", op, count);
+    }
+
+    println!("\nTop actors:");
+    for (actor, count) in analysis::ProgramStats::ranked(&stats.actors).iter().take(10) {
+        println!("  {}: {}", actor, count);
+    }
+
+    if !stats.effects.is_empty() {
+        println!("\nDomain tags:");
+        for (domain, count) in analysis::ProgramStats::ranked(&stats.effects) {
+            println!("  {}: {}", domain, count);
+        }
+    }
+
+    if stats.max_depth > 0 {
+        println!("\nBy depth:");
+        for (depth, count) in &stats.by_depth {
+            let ops: Vec<String> = analysis::ProgramStats::ranked(&stats.operations_by_depth[depth])
+                .into_iter()
+                .map(|(op, n)| format!("{} {}", op, n))
+                .collect();
+            println!("  {}: {} action(s) — {}", depth, count, ops.join(", "));
+        }
+    }
+
+    // Temporal analysis
+    if let Some((min, max)) = stats.time_range {
+        println!("\nTemporal analysis:");
+        println!("  Actions with timestamps: {}", stats.timed);
+        println!("  Time range: {} to {}", min, max);
+    }
+
+    if program.actions.iter().any(|a| a.t.is_some() || a.dur.is_some()) {
+        print_critical_path(&program);
+    }
+
+    Ok(())
+}
+
+fn print_critical_path(program: &Program) {
+    let schedule = analysis::critical_path(program);
+    let describe = |i: usize| {
+        let action = &program.actions[i];
+        format!("#{} {} {:?} {}", i + 1, action.actor, action.op, action.target)
+    };
+
+    println!("\nCritical path:");
+    println!("  Makespan: {}", schedule.makespan);
+    for &i in &schedule.critical_path {
+        let action = &schedule.actions[i];
+        println!("  {} ({} → {})", describe(i), action.earliest_start, action.earliest_finish);
+    }
+
+    println!("\nActor utilization:");
+    for load in analysis::actor_loads(program, &schedule) {
+        let idle: Vec<String> = load.idle.iter().map(|(from, to)| format!("{} → {}", from, to)).collect();
+        println!("  {}: busy {} ({:.0}%){}", load.actor, load.busy, load.utilization * 100.0,
+            if idle.is_empty() { String::new() } else { format!(", idle {}", idle.join(", ")) });
+    }
+
+    let slack: Vec<_> = schedule.actions.iter().filter(|a| a.slack > 0.0).collect();
+    if !slack.is_empty() {
+        println!("\nSlack:");
+        for action in slack {
+            println!("  {}: {}", describe(action.index), action.slack);
+        }
+    }
+
+    if !schedule.parallel_groups.is_empty() {
+        println!("\nCould run in parallel:");
+        for group in &schedule.parallel_groups {
+            let names: Vec<String> = group.iter().map(|&i| describe(i)).collect();
+            println!("  {}", names.join(" | "));
+        }
+    }
+}
+
+/// Print the timeline; returns whether there were conflicts
+fn timeline_file(path: &Path, format: &str) -> anyhow::Result<bool> {
+    let program = validate_file(path)?;
+    let timeline = ucl::timeline::timeline(&program);
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&timeline)?),
+        "text" => {
+            println!("=== Timeline ===\n");
+            for slot in &timeline.slots {
+                let action = &program.actions[slot.index];
+                println!("  {:>8.2}–{:<8.2} {}{} {:?} {}  (action {})",
+                    slot.start, slot.end, "  ".repeat(slot.lane), action.actor, action.op, action.target, slot.index + 1);
+            }
+            println!("\n  Ends at {:.2}, up to {} action(s) at once", timeline.end, timeline.lanes);
+
+            if !timeline.conflicts.is_empty() {
+                println!("\n⚠️  {} conflict(s):", timeline.conflicts.len());
+                for conflict in &timeline.conflicts {
+                    println!("  {}", describe_conflict(&program, conflict));
+                }
+            }
+        }
+        _ => anyhow::bail!("Unsupported timeline format: {}. Use text or json.", format),
+    }
+    Ok(!timeline.conflicts.is_empty())
+}
+
+fn describe_conflict(program: &Program, conflict: &ucl::timeline::Conflict) -> String {
+    let (first, second) = (&program.actions[conflict.first], &program.actions[conflict.second]);
+    format!("{} changed by action {} ({} {:?}) and action {} ({} {:?}) at once, from {:.2}",
+        conflict.target, conflict.first + 1, first.actor, first.op, conflict.second + 1, second.actor, second.op, conflict.at)
+}
+
+fn network_file(path: &PathBuf, format: &str) -> anyhow::Result<()> {
+    let program = validate_file(path)?;
+    let network = analysis::actor_network(&program);
+
+    match format {
+        "dot" => print!("{}", network.to_dot()),
+        "json" => println!("{}", serde_json::to_string_pretty(&network)?),
+        "text" => {
+            println!("=== Actor Interaction Network ===\n");
+
+            if network.edges.is_empty() {
+                println!("No Emit/Receive pairs found.");
+                return Ok(());
+            }
+
+            println!("Interactions:");
+            for edge in &network.edges {
+                println!("  {} → {} ({} via {})", edge.from, edge.to, edge.messages, edge.channels.join(", "));
+            }
+
+            println!("\nActors by centrality:");
+            for actor in network.by_centrality() {
+                println!(
+                    "  {}: centrality {:.2}, out {} / in {}, sent {} / received {}",
+                    actor.actor, actor.centrality, actor.out_degree, actor.in_degree, actor.sent, actor.received
+                );
+            }
+        }
+        _ => anyhow::bail!("Unsupported network format: {}. Use text, dot or json.", format),
+    }
+
+    Ok(())
+}
+
+fn graph_file(path: &Path, format: &str) -> anyhow::Result<()> {
+    let program = validate_file(path)?;
+    let graph = analysis::causal_graph(&program);
+
+    match format {
+        "dot" => print!("{}", graph.to_dot()),
+        "mermaid" => print!("{}", graph.to_mermaid()),
+        "json" => println!("{}", serde_json::to_string_pretty(&graph)?),
+        _ => anyhow::bail!("Unsupported graph format: {}. Use dot, mermaid or json.", format),
+    }
+
+    Ok(())
+}
+
+fn flow_file(path: &PathBuf, taint: bool) -> anyhow::Result<()> {
+    let program = validate_file(path)?;
+    let flows = analysis::information_flows(&program, &analysis::TaintPolicy::default());
+
+    println!("=== Information Flow ===\n");
+
+    if flows.is_empty() {
+        println!("No values reach an Emit, decision, obligation or physical operation.");
+        return Ok(());
+    }
+
+    for flow in &flows {
+        println!("  {} → {} via {}", flow.source.display(), flow.sink.display(), flow.path.join(" → "));
+    }
+
+    if taint {
+        let tainted: Vec<_> = flows.iter().filter(|f| f.tainted).collect();
+
+        println!("\nTaint check:");
+        if tainted.is_empty() {
+            println!("  ✓ No untrusted values reach sensitive operations");
+            return Ok(());
+        }
+
+        for flow in &tainted {
+            println!("  ⚠️  untrusted {} reaches sensitive {} via {}",
+                flow.source.display(), flow.sink.display(), flow.path.join(" → "));
+        }
+        anyhow::bail!("{} tainted flow(s)", tainted.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn edit_file(path: &Path) -> anyhow::Result<()> {
+    ucl::tui::editor::run(path.to_path_buf())
+}
+
+#[cfg(not(feature = "tui"))]
+fn edit_file(_path: &Path) -> anyhow::Result<()> {
+    anyhow::bail!("The editor requires building ucl with `--features tui`")
+}
+
+fn effects_file(path: &PathBuf, write: bool, check: bool) -> anyhow::Result<()> {
+    let mut program = load_source(path)?;
+    let gaps = analysis::check_effects(&program);
+
+    println!("=== Effects ===\n");
+
+    if gaps.is_empty() {
+        println!("✓ Every action declares its inferred effects");
+        return Ok(());
+    }
+
+    for gap in &gaps {
+        println!("  {} {:?} {}: missing [{}]", gap.location, gap.op, gap.target, gap.missing.join(", "));
+    }
+
+    if write {
+        let changed = analysis::annotate_effects(&mut program);
+        fs::write(path, format_program(&program, format_for_path(path))?)?;
+        println!("\nTagged {} action(s) in {}", changed, path.display());
+    } else if check {
+        anyhow::bail!("{} action(s) missing effects tags", gaps.len());
+    }
+
+    Ok(())
+}
+
+fn idioms_file(path: &PathBuf, fix: bool) -> anyhow::Result<()> {
+    let mut program = load_source(path)?;
+
+    println!("=== Idioms ===\n");
+
+    let idioms = if fix {
+        let applied = analysis::idioms::fix(&mut program);
+        if !applied.is_empty() {
+            fs::write(path, format_program(&program, format_for_path(path))?)?;
+        }
+        applied
+    } else {
+        analysis::idioms::detect(&program)
+    };
+
+    if idioms.is_empty() {
+        println!("No idioms found.");
+        return Ok(());
+    }
+
+    for idiom in &idioms {
+        let marker = if fix { "✓" } else if idiom.fixable { "→" } else { " " };
+        println!("  {} {}: {}", marker, idiom.location, idiom.suggestion);
+    }
+
+    if fix {
+        println!("\nApplied {} rewrite(s) to {}", idioms.len(), path.display());
+    } else if idioms.iter().any(|i| i.fixable) {
+        println!("\nRun with --fix to apply the rewrites marked →");
+    }
+
+    Ok(())
+}
+
+/// Whether the program is clean
+fn lint_file(path: &Path) -> anyhow::Result<bool> {
+    let program = validate_file(path)?;
+    let issues = analysis::check_definitions(&program);
+
+    for issue in &issues {
+        println!("⚠️  {}", issue.display());
+    }
+    if issues.is_empty() {
+        println!("✓ No problems found in {}", path.display());
+    } else {
+        println!("\n{} problem(s) in {}", issues.len(), path.display());
+    }
+    Ok(issues.is_empty())
+}
+
+fn transform_file(path: &Path, passes: &[String], output: Option<&PathBuf>) -> anyhow::Result<()> {
+    let mut program = validate_file(path)?;
+    let manager = PassManager::from_specs(passes)?;
+
+    for report in manager.run(&mut program)? {
+        eprintln!("{} {}", if report.changed { "✓" } else { "·" }, report.name);
+    }
+
+    // Keep the format of the file being written
+    let code = format_program(&program, format_for_path(output.map_or(path, |p| p.as_path())))?;
+    if let Some(output_path) = output {
+        fs::write(output_path, code)?;
+        eprintln!("Wrote {}", output_path.display());
+    } else {
+        print!("{}", code);
+    }
+
+    Ok(())
+}
+
+fn merge_files(paths: &[PathBuf], shared: bool, prefix_actors: bool, output: Option<&PathBuf>) -> anyhow::Result<()> {
+    let mut program = validate_file(&paths[0])?;
+
+    for path in &paths[1..] {
+        let next = validate_file(path)?;
+        if shared {
+            program = program.concat(&next);
+            continue;
+        }
+
+        let prefix = match path.file_stem() {
+            Some(stem) if prefix_actors => format!("{}.", stem.to_string_lossy()),
+            _ => String::new(),
+        };
+        let merge = compose::merge(&program, &next, &prefix);
+        for (old, new) in &merge.renames {
+            eprintln!("{}: renamed {} → {}", path.display(), old, new);
+        }
+        program = merge.program;
+    }
+
+    let code = format_program(&program, format_for_path(output.map_or(paths[0].as_path(), |p| p.as_path())))?;
+    if let Some(output_path) = output {
+        fs::write(output_path, code)?;
+        eprintln!("Wrote {}", output_path.display());
+    } else {
+        print!("{}", code);
+    }
+
+    Ok(())
+}
+
+fn slice_file(path: &Path, filter: &SliceFilter, output: Option<&PathBuf>) -> anyhow::Result<()> {
+    if filter.is_empty() {
+        anyhow::bail!("Give at least one --actor, --effect or --target");
+    }
+    let program = validate_file(path)?;
+    let sliced = program.slice(filter);
+
+    let (before, after) = (analysis::program_stats(&program).total, analysis::program_stats(&sliced).total);
+    eprintln!("Kept {} of {} actions", after, before);
+
+    let code = format_program(&sliced, format_for_path(output.map_or(path, |p| p.as_path())))?;
+    if let Some(output_path) = output {
+        fs::write(output_path, code)?;
+        eprintln!("Wrote {}", output_path.display());
+    } else {
+        print!("{}", code);
+    }
+
+    Ok(())
+}
+
+fn optimize_file(path: &Path, output: Option<&PathBuf>) -> anyhow::Result<()> {
+    let mut program = validate_file(path)?;
+    Fold.run(&mut program)?;
+
+    let removed = dce::eliminate(&mut program);
+    for removal in &removed {
+        eprintln!("removed {:?} {} ({})", removal.op, removal.target, removal.reason);
+    }
+    eprintln!("{} dead action(s) removed", removed.len());
+
+    let code = format_program(&program, format_for_path(output.map_or(path, |p| p.as_path())))?;
+    if let Some(output_path) = output {
+        fs::write(output_path, code)?;
+        eprintln!("Wrote {}", output_path.display());
+    } else {
+        print!("{}", code);
     }
 
     Ok(())
 }
 
-fn convert_file(path: &PathBuf, format: &str) -> anyhow::Result<()> {
+fn minimize_file(path: &PathBuf, failure: &str, output: Option<&PathBuf>) -> anyhow::Result<()> {
     let program = validate_file(path)?;
+    let failure = Failure::parse(failure)?;
+    failure.check_available()?;
 
-    match format {
-        "json" => {
-            println!("{}", program.to_json()?);
-        }
-        _ => {
-            anyhow::bail!("Unsupported format: {}. Currently only 'json' is supported.", format);
-        }
+    let result = minimize(&program, |candidate| failure.reproduces(candidate))?;
+    let json = result.program.to_json()?;
+
+    let count = |program: &Program| {
+        let mut n = 0;
+        program.walk(&mut |_, _| n += 1);
+        n
+    };
+    let (before, after) = (count(&program), count(&result.program));
+    eprintln!("Minimized {} → {} actions ({} candidates tried)", before, after, result.tests);
+
+    if let Some(output_path) = output {
+        fs::write(output_path, json)?;
+        eprintln!("Wrote {}", output_path.display());
+    } else {
+        println!("{}", json);
     }
 
     Ok(())
 }
 
-fn analyze_file(path: &PathBuf) -> anyhow::Result<()> {
-    let program = validate_file(path)?;
-
-    println!("=== UCL Program Analysis ===\n");
-    println!("Total actions: {}", program.actions.len());
-
-    // Count operations
-    let mut op_counts = std::collections::HashMap::new();
-    for action in &program.actions {
-        *op_counts.entry(format!("{:?}", action.op)).or_insert(0) += 1;
-    }
-
-    println!("\nOperation distribution:");
-    let mut ops: Vec<_> = op_counts.iter().collect();
-    ops.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
-    for (op, count) in ops {
-        println!("  {}: {}", op, count);
-    }
+fn sign_file(path: &PathBuf, key_path: &Path, output: Option<&PathBuf>) -> anyhow::Result<()> {
+    let mut program = load_source(path)?;
 
-    // Count actors
-    let mut actor_counts = std::collections::HashMap::new();
-    for action in &program.actions {
-        *actor_counts.entry(&action.actor).or_insert(0) += 1;
-    }
+    let key = if key_path.exists() {
+        signing::read_signing_key(key_path)?
+    } else {
+        let key = signing::generate_key()?;
+        signing::write_key_pair(&key, key_path)?;
+        println!("🔑 Generated {} and {}", key_path.display(), key_path.with_extension("pub").display());
+        key
+    };
 
-    println!("\nTop actors:");
-    let mut actors: Vec<_> = actor_counts.iter().collect();
-    actors.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
-    for (actor, count) in actors.iter().take(10) {
-        println!("  {}: {}", actor, count);
-    }
+    signing::sign(&mut program, &key)?;
 
-    // Effects domains
-    let mut domain_counts = std::collections::HashMap::new();
-    for action in &program.actions {
-        if let Some(effects) = &action.effects {
-            for effect in effects {
-                *domain_counts.entry(effect).or_insert(0) += 1;
-            }
-        }
-    }
+    let output = output.unwrap_or(path);
+    fs::write(output, format_program(&program, format_for_path(output))?)?;
+    println!("✍️  Signed {} with {}", output.display(), signing::to_hex(key.verifying_key().as_bytes()));
 
-    if !domain_counts.is_empty() {
-        println!("\nDomain tags:");
-        for (domain, count) in domain_counts.iter() {
-            println!("  {}: {}", domain, count);
-        }
-    }
+    Ok(())
+}
 
-    // Temporal analysis
-    let timed_actions = program.actions.iter().filter(|a| a.t.is_some()).count();
-    if timed_actions > 0 {
-        println!("\nTemporal analysis:");
-        println!("  Actions with timestamps: {}", timed_actions);
+fn verify_file(path: &PathBuf, public_key: Option<&Path>) -> anyhow::Result<()> {
+    let program = load_source(path)?;
+    let trusted = public_key.map(signing::read_verifying_key).transpose()?;
 
-        let times: Vec<f64> = program.actions.iter().filter_map(|a| a.t).collect();
-        if !times.is_empty() {
-            let min = times.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-            let max = times.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-            println!("  Time range: {} to {}", min, max);
-        }
-    }
+    let signer = signing::verify(&program, trusted.as_ref())?;
+    println!("✓ Valid signature from {}", signing::to_hex(signer.as_bytes()));
 
     Ok(())
 }
 
-fn compile_file(path: &PathBuf, target: &str, output: Option<&PathBuf>) -> anyhow::Result<()> {
-    let program = validate_file(path)?;
+fn compile_file(path: &Path, target: &str, output: Option<&PathBuf>, fold: bool, seed: Option<u64>) -> anyhow::Result<()> {
+    let program = load_folded(path, fold)?;
+
+    if target == "wasm" {
+        let Some(output_path) = output else {
+            anyhow::bail!("The wasm target writes a binary module; pass --output, or use --target wat for text");
+        };
+        fs::write(output_path, WasmCompiler::new().compile_binary(&program)?)?;
+        println!("Compiled to {}", output_path.display());
+        return Ok(());
+    }
 
     let code = match target {
-        "ruby" => {
-            let mut compiler = RubyCompiler::new();
-            compiler.compile(&program)?
-        }
+        "ruby" => seed.map_or_else(RubyCompiler::new, |seed| RubyCompiler::new().with_seed(seed)).compile(&program)?,
+        "js" | "javascript" => seed.map_or_else(JavaScriptCompiler::new, |seed| JavaScriptCompiler::new().with_seed(seed)).compile(&program)?,
+        "rust" => seed.map_or_else(RustCompiler::new, |seed| RustCompiler::new().with_seed(seed)).compile(&program)?,
+        "bash" | "sh" => seed.map_or_else(ShellCompiler::new, |seed| ShellCompiler::new().with_seed(seed)).compile(&program)?,
+        "prolog" => PrologCompiler::new().compile(&program)?,
+        "sql" | "sqlite" => SqlCompiler::new().compile(&program)?,
+        "postgres" => SqlCompiler::new().with_dialect(SqlDialect::Postgres).compile(&program)?,
+        "wat" => WasmCompiler::new().compile(&program)?,
+        "solidity" => SolidityCompiler::new().compile(&program)?,
+        "english" => EnglishCompiler::new().compile(&program)?,
         _ => {
-            anyhow::bail!("Unsupported target language: {}. Currently only 'ruby' is supported.", target);
+            anyhow::bail!("Unsupported target language: {}. Use 'ruby', 'js', 'rust', 'bash', 'wasm', 'wat', 'sql', 'postgres', 'prolog', 'solidity' or 'english'.", target);
         }
     };
 
@@ -382,19 +1940,103 @@ fn compile_file(path: &PathBuf, target: &str, output: Option<&PathBuf>) -> anyho
     Ok(())
 }
 
-fn run_file(path: &PathBuf, target: &str, verbose: bool) -> anyhow::Result<()> {
-    let program = validate_file(path)?;
+fn decompile_file(path: &PathBuf, output: Option<&PathBuf>) -> anyhow::Result<()> {
+    let source = fs::read_to_string(path)?;
+    let json = RubyDecompiler::new().decompile(&source)?.to_json()?;
+
+    if let Some(output_path) = output {
+        fs::write(output_path, json)?;
+        println!("Decompiled to {}", output_path.display());
+    } else {
+        println!("{}", json);
+    }
+
+    Ok(())
+}
+
+fn load_project(manifest: Option<&Path>) -> anyhow::Result<Project> {
+    match manifest {
+        Some(path) => Project::load(path),
+        None => Project::discover(&std::env::current_dir()?),
+    }
+}
+
+fn build_project(manifest: Option<&Path>) -> anyhow::Result<()> {
+    let project = load_project(manifest)?;
+    println!("📦 Building {}", project.manifest.project.name);
+
+    for path in project.build()? {
+        println!("  ✓ {}", path.display());
+    }
+    Ok(())
+}
+
+fn run_project(target: Option<&str>, verbose: bool, seed: Option<u64>) -> anyhow::Result<()> {
+    let project = load_project(None)?;
+    let program = project.bundle()?;
+
+    let targets = match target {
+        Some(target) => vec![target.to_string()],
+        None if project.manifest.project.targets.is_empty() => vec!["ruby".to_string()],
+        None => project.manifest.project.targets.clone(),
+    };
 
+    for target in targets {
+        println!("▶️  {} on {}", project.manifest.project.name, target);
+        run_program(&program, &target, verbose, seed)?;
+    }
+    Ok(())
+}
+
+fn run_file(path: &Path, target: &str, verbose: bool, fold: bool, timeline: bool, dag: bool, seed: Option<u64>) -> anyhow::Result<()> {
+    let mut program = load_folded(path, fold)?;
+    if timeline {
+        let placed = ucl::timeline::timeline(&program);
+        for conflict in &placed.conflicts {
+            eprintln!("⚠️  {}", describe_conflict(&program, conflict));
+        }
+        program = placed.ordered(&program);
+    }
+    if dag {
+        program = ucl::dag::reorder(&program);
+    }
+    run_program(&program, target, verbose, seed)
+}
+
+/// Load a program, constant-folded if asked
+fn load_folded(path: &Path, fold: bool) -> anyhow::Result<Program> {
+    let mut program = validate_file(path)?;
+    if fold {
+        Fold.run(&mut program)?;
+    }
+    Ok(program)
+}
+
+fn run_program(program: &Program, target: &str, verbose: bool, seed: Option<u64>) -> anyhow::Result<()> {
     match target {
         "brain" => {
-            let mut simulator = BrainSimulator::new().with_verbose(verbose);
-            simulator.execute(&program)?;
+            let mut simulator = BrainSimulator::new().with_verbose(verbose).with_interrupt(ctrl_c_flag()?);
+            if let Some(seed) = seed {
+                simulator = simulator.with_seed(seed);
+            }
+            simulator.execute(program)?;
+
+            println!("\n{}", simulator.state().display());
+            exit_if_halted(simulator.state().halted);
+        }
+        "robot" => {
+            let mut simulator = RobotSimulator::new().with_verbose(verbose).with_interrupt(ctrl_c_flag()?);
+            if let Some(seed) = seed {
+                simulator = simulator.with_seed(seed);
+            }
+            simulator.execute(program)?;
 
             println!("\n{}", simulator.state().display());
+            exit_if_halted(simulator.state().halted);
         }
         "ruby" => {
-            let mut compiler = RubyCompiler::new();
-            let code = compiler.compile(&program)?;
+            let mut compiler = seed.map_or_else(RubyCompiler::new, |seed| RubyCompiler::new().with_seed(seed));
+            let code = compiler.compile(program)?;
 
             // Check if ruby is available
             let ruby_check = Command::new("ruby")
@@ -427,26 +2069,167 @@ fn run_file(path: &PathBuf, target: &str, verbose: bool) -> anyhow::Result<()> {
                 anyhow::bail!("Ruby execution failed with status: {}", output.status);
             }
         }
+        "js" | "javascript" => {
+            let code = seed.map_or_else(JavaScriptCompiler::new, |seed| JavaScriptCompiler::new().with_seed(seed)).compile(program)?;
+
+            if Command::new("node").arg("--version").output().is_err() {
+                anyhow::bail!("Node.js is not installed or not in PATH. Please install Node.js to run UCL programs as JavaScript.");
+            }
+
+            println!("=== Compiled JavaScript Code ===");
+            println!("{}", code);
+            println!("\n=== Execution Output ===");
+
+            let output = Command::new("node")
+                .arg("-e")
+                .arg(&code)
+                .output()?;
+
+            if !output.stdout.is_empty() {
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+            }
+
+            if !output.stderr.is_empty() {
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            }
+
+            if !output.status.success() {
+                anyhow::bail!("JavaScript execution failed with status: {}", output.status);
+            }
+        }
+        "rust" => {
+            let code = seed.map_or_else(RustCompiler::new, |seed| RustCompiler::new().with_seed(seed)).compile(program)?;
+
+            if Command::new("rustc").arg("--version").output().is_err() {
+                anyhow::bail!("rustc is not installed or not in PATH. Please install Rust to run UCL programs natively.");
+            }
+
+            println!("=== Compiled Rust Code ===");
+            println!("{}", code);
+
+            let dir = std::env::temp_dir().join(format!("ucl_rust_{}", std::process::id()));
+            fs::create_dir_all(&dir)?;
+            let source = dir.join("main.rs");
+            let binary = dir.join("main");
+            fs::write(&source, &code)?;
+
+            let build = Command::new("rustc")
+                .args(["-O", "--edition", "2021", "-o"])
+                .arg(&binary)
+                .arg(&source)
+                .output()?;
+            if !build.status.success() {
+                eprint!("{}", String::from_utf8_lossy(&build.stderr));
+                let _ = fs::remove_dir_all(&dir);
+                anyhow::bail!("rustc failed with status: {}", build.status);
+            }
+
+            println!("\n=== Execution Output ===");
+            let output = Command::new(&binary).output();
+            let _ = fs::remove_dir_all(&dir);
+            let output = output?;
+
+            if !output.stdout.is_empty() {
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+            }
+
+            if !output.stderr.is_empty() {
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            }
+
+            if !output.status.success() {
+                anyhow::bail!("Rust program failed with status: {}", output.status);
+            }
+        }
+        "wasm" => {
+            let module = WasmCompiler::new().compile_binary(program)?;
+
+            if Command::new("node").arg("--version").output().is_err() {
+                anyhow::bail!("Node.js is not installed or not in PATH. Please install Node.js to run UCL programs as WebAssembly.");
+            }
+
+            println!("=== Compiled WebAssembly ({} bytes) ===", module.len());
+            println!("{}", WasmCompiler::new().compile(program)?);
+            println!("\n=== Execution Output ===");
+
+            let output = Command::new("node")
+                .arg("-e")
+                .arg(wasm::node_runner(&module))
+                .output()?;
+
+            if !output.stdout.is_empty() {
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+            }
+
+            if !output.stderr.is_empty() {
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            }
+
+            if !output.status.success() {
+                anyhow::bail!("WebAssembly execution failed with status: {}", output.status);
+            }
+        }
+        "bash" | "sh" => {
+            let code = seed.map_or_else(ShellCompiler::new, |seed| ShellCompiler::new().with_seed(seed)).compile(program)?;
+
+            println!("=== Compiled Shell Script ===");
+            println!("{}", code);
+            println!("\n=== Execution Output ===");
+
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(&code)
+                .output()?;
+
+            if !output.stdout.is_empty() {
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+            }
+
+            if !output.stderr.is_empty() {
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            }
+
+            if !output.status.success() {
+                anyhow::bail!("Shell script failed with status: {}", output.status);
+            }
+        }
         _ => {
-            anyhow::bail!("Unsupported target language: {}. Currently 'ruby' and 'brain' are supported.", target);
+            anyhow::bail!("Unsupported target language: {}. Currently 'ruby', 'js', 'rust', 'bash', 'wasm', 'brain' and 'robot' are supported.", target);
         }
     }
 
     Ok(())
 }
 
-fn brain_simulate(path: &PathBuf, verbose: bool, production: bool) -> anyhow::Result<()> {
+fn brain_simulate(
+    path: &PathBuf,
+    verbose: bool,
+    production: bool,
+    why: Option<&str>,
+    seed: Option<u64>,
+    state: Option<&Path>,
+    limits: &BrainLimits,
+) -> anyhow::Result<()> {
     let program = validate_file(path)?;
 
     if production {
         return run_on_production_brain(&program);
     }
 
-    let mut simulator = BrainSimulator::new().with_verbose(verbose);
+    let mut simulator = limits.apply(BrainSimulator::new().with_verbose(verbose).with_interrupt(ctrl_c_flag()?))?;
+    if let Some(seed) = seed {
+        simulator = simulator.with_seed(seed);
+    }
+    if let Some(state) = state.filter(|state| state.exists()) {
+        simulator.load_state(state)?;
+    }
 
     println!("🧠 Simulating language execution on virtual human brain...\n");
 
     simulator.execute(&program)?;
+    if let Some(state) = state {
+        simulator.save_state(state)?;
+    }
 
     println!("\n{}", simulator.state().display());
 
@@ -457,13 +2240,64 @@ fn brain_simulate(path: &PathBuf, verbose: bool, production: bool) -> anyhow::Re
         }
     }
 
+    if let Some(name) = why {
+        print_why(name, &simulator.state().provenance);
+    }
+    exit_if_halted(simulator.state().halted);
+
     Ok(())
 }
 
-fn robot_simulate(path: &PathBuf, verbose: bool) -> anyhow::Result<()> {
+/// Exit with the status a `Halt` ended the program with, unless it was 0
+fn exit_if_halted(status: Option<i64>) {
+    if let Some(status) = status.filter(|status| *status != 0) {
+        std::process::exit(status as i32);
+    }
+}
+
+fn print_why(name: &str, provenance: &ucl::simulator::Provenance) {
+    let lines = provenance.explain(name);
+    if lines.is_empty() {
+        println!("\nNo action set {}", name);
+        return;
+    }
+    println!("\nWhy {}?", name);
+    for line in lines {
+        println!("  {}", line);
+    }
+}
+
+fn robot_simulate(
+    path: &PathBuf,
+    verbose: bool,
+    noise: Option<NoiseModel>,
+    monte_carlo: Option<usize>,
+    seed: Option<u64>,
+    why: Option<&str>,
+    arm: &ArmArgs,
+) -> anyhow::Result<()> {
     let program = validate_file(path)?;
 
-    let mut simulator = RobotSimulator::new().with_verbose(verbose);
+    if let Some(runs) = monte_carlo {
+        let noise = noise.unwrap_or_default();
+        let seed = seed.unwrap_or(0);
+
+        println!("🎲 Running {} noisy robot simulations (seed {})...", runs, seed);
+        println!("   position σ = {}, amount σ = {:.1}%\n", noise.position_sigma, noise.amount_sigma * 100.0);
+
+        let report = noise::monte_carlo(&program, &noise, runs, seed)?;
+        println!("{}", report.display());
+
+        return Ok(());
+    }
+
+    let mut simulator = arm.apply(RobotSimulator::new().with_verbose(verbose).with_interrupt(ctrl_c_flag()?))?;
+    if let Some(noise) = noise {
+        simulator = simulator.with_noise(noise);
+    }
+    if let Some(seed) = seed {
+        simulator = simulator.with_seed(seed);
+    }
 
     println!("🤖 Simulating physical execution on virtual robot...\n");
 
@@ -471,13 +2305,36 @@ fn robot_simulate(path: &PathBuf, verbose: bool) -> anyhow::Result<()> {
 
     println!("\n{}", simulator.state().display());
 
+    if let Some(name) = why {
+        print_why(name, &simulator.state().provenance);
+    }
+    exit_if_halted(simulator.state().halted);
+
     Ok(())
 }
 
-fn ai_simulate(path: &PathBuf, verbose: bool) -> anyhow::Result<()> {
+fn ai_simulate(
+    path: &PathBuf,
+    verbose: bool,
+    backend: &AiBackendArgs,
+    prompt_config: Option<&PathBuf>,
+    use_cache: bool,
+    policy: CallPolicy,
+) -> anyhow::Result<()> {
     let program = validate_file(path)?;
 
-    let mut simulator = MockAISimulator::new().with_verbose(verbose);
+    let mut simulator = MockAISimulator::new()
+        .with_verbose(verbose)
+        .with_cache(use_cache.then(ResponseCache::default))
+        .with_call_policy(policy);
+
+    if let Some(config) = prompt_config {
+        simulator = simulator.with_prompt_template(PromptTemplate::from_file(config)?);
+    }
+
+    if let Some(backend) = backend.load()? {
+        simulator = simulator.with_backend(backend);
+    }
 
     println!("🧠🤖 Simulating AI code generation (Mock LLM)...\n");
 
@@ -501,224 +2358,161 @@ fn ai_simulate(path: &PathBuf, verbose: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn run_on_production_brain(program: &Program) -> anyhow::Result<()> {
-    use std::io::{self, Write};
+fn from_text(
+    instruction: &str,
+    output: Option<&PathBuf>,
+    format: Option<&str>,
+    substrate: Option<&str>,
+    backend: &AiBackendArgs,
+    use_cache: bool,
+    policy: CallPolicy,
+) -> anyhow::Result<()> {
+    let mut simulator = MockAISimulator::new()
+        .with_cache(use_cache.then(ResponseCache::default))
+        .with_call_policy(policy);
+    if let Some(backend) = backend.load()? {
+        simulator = simulator.with_backend(backend);
+    }
 
-    println!("🧠💼 PRODUCTION MODE: Running on YOUR actual brain!");
-    println!("{}", "=".repeat(60));
-    println!();
-    println!("⚠️  WARNING: This will execute directly on human wetware.");
-    println!("    No virtual machine. No sandbox. Just your neurons.");
-    println!();
-    println!("Instructions:");
-    println!("  - Read each operation carefully");
-    println!("  - Execute it using your brain");
-    println!("  - Report your internal state after each step");
-    println!();
-    print!("Ready to begin? (y/n): ");
-    io::stdout().flush()?;
+    let program = simulator.program_from_text(instruction, substrate)?;
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
+    let format = format.unwrap_or_else(|| output.map_or("json", |path| format_for_path(path)));
+    let code = format_program(&program, format)?;
 
-    if !input.trim().eq_ignore_ascii_case("y") {
-        println!("\n❌ Aborted. Your brain remains in its current state.");
-        return Ok(());
+    if let Some(output_path) = output {
+        fs::write(output_path, code)?;
+        println!("Generated {}", output_path.display());
+    } else {
+        print!("{}", code);
     }
 
-    println!("\n🚀 Initiating brain program execution...\n");
-
-    let mut execution_log = Vec::new();
-    let start_time = std::time::Instant::now();
+    Ok(())
+}
 
-    for (i, action) in program.actions.iter().enumerate() {
-        println!("{}", "─".repeat(60));
-        println!("STEP {}/{}: {:?} Operation", i + 1, program.actions.len(), action.op);
-        println!("{}", "─".repeat(60));
-        println!();
-        println!("📋 Instruction:");
-        println!("   Actor:  {}", action.actor);
-        println!("   Op:     {:?}", action.op);
-        println!("   Target: {}", action.target);
+fn corpus(action: &CorpusAction) -> anyhow::Result<()> {
+    match action {
+        CorpusAction::Cluster { dir, threshold } => corpus_cluster(dir, *threshold),
+    }
+}
 
-        if let Some(params) = &action.params {
-            println!("   Params:");
-            for (key, value) in params {
-                println!("     • {} = {}", key, value);
+fn corpus_cluster(dir: &Path, threshold: f64) -> anyhow::Result<()> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ["json", "ucl", "yaml", "yml"].iter().any(|e| ext == *e)))
+        .collect();
+    paths.sort();
+
+    let mut names = Vec::new();
+    let mut fingerprints = Vec::new();
+    for path in &paths {
+        match validate_file(path) {
+            Ok(program) => {
+                names.push(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                fingerprints.push(analysis::Fingerprint::of(&program));
             }
+            Err(e) => eprintln!("⚠️  Skipping {}: {}", path.display(), e),
         }
+    }
 
-        if let Some(effects) = &action.effects {
-            println!("   Effects: [{}]", effects.join(", "));
-        }
+    let clusters = analysis::cluster(&fingerprints, threshold);
 
-        println!();
-        println!("🎯 Your Task:");
-
-        match action.op {
-            Operation::StoreFact => {
-                println!("   → Store this information in your memory");
-                println!("   → Commit '{}' to long-term storage", action.target);
-            }
-            Operation::Assert => {
-                println!("   → Assert this as a strong belief");
-                println!("   → Make this a core part of your worldview");
-            }
-            Operation::Emit => {
-                println!("   → Generate and speak this output");
-                println!("   → Say it out loud or in your mind");
-            }
-            Operation::Receive => {
-                println!("   → Process this input through your senses");
-                println!("   → Pay attention to: {}", action.target);
-            }
-            Operation::Decide => {
-                println!("   → Make this decision");
-                println!("   → Commit to: {}", action.target);
-            }
-            Operation::Measure => {
-                println!("   → Observe and measure");
-                println!("   → Focus your attention on: {}", action.target);
-            }
-            Operation::Create => {
-                println!("   → Create this new concept in your mind");
-                println!("   → Imagine: {}", action.target);
-            }
-            Operation::Bind => {
-                println!("   → Bind this concept to a mental variable");
-                println!("   → Associate '{}' with a value", action.target);
-            }
-            Operation::Write => {
-                if let Some(params) = &action.params {
-                    if params.contains_key("lhs_register") && params.contains_key("rhs_register") {
-                        let lhs = params.get("lhs_register").and_then(|v| v.as_str()).unwrap_or("?");
-                        let rhs = params.get("rhs_register").and_then(|v| v.as_str()).unwrap_or("?");
-                        let op = params.get("operation").and_then(|v| v.as_str()).unwrap_or("multiply");
-                        let symbol = match op {
-                            "multiply" => "×",
-                            "add" => "+",
-                            "subtract" => "-",
-                            "divide" => "÷",
-                            _ => "×"
-                        };
-                        println!("   → Recall {} and {}", lhs, rhs);
-                        println!("   → Calculate: {} {} {}", lhs, symbol, rhs);
-                        println!("   → Store the answer in: {}", action.target);
-                    } else {
-                        println!("   → Update memory: {}", action.target);
-                        println!("   → Store a new value");
-                    }
-                } else {
-                    println!("   → Update memory: {}", action.target);
-                }
-            }
-            Operation::Oblige => {
-                println!("   → Accept this obligation");
-                println!("   → Add to your active goals");
-            }
-            Operation::Wait => {
-                let duration = action.dur.unwrap_or(1.0);
-                println!("   → Wait and let {} seconds pass", duration);
-                println!("   → Be present in this moment");
-            }
-            Operation::GenRandomInt => {
-                if let Some(params) = &action.params {
-                    let min = params.get("min").and_then(|v| v.as_i64()).unwrap_or(0);
-                    let max = params.get("max").and_then(|v| v.as_i64()).unwrap_or(9);
-                    println!("   → Think of a random number between {} and {}", min, max);
-                    println!("   → Remember it as '{}'", action.target);
-                } else {
-                    println!("   → Generate a random number");
-                    println!("   → Remember it as '{}'", action.target);
-                }
-            }
-            _ => {
-                println!("   ⚠️  UNKNOWN OPERATION!");
-                println!("   → Experience confusion");
-                println!("   → Notice you don't understand");
+    println!("=== Corpus Clusters ({} programs, threshold {}) ===\n", names.len(), threshold);
+    for (number, cluster) in clusters.iter().filter(|c| c.members.len() > 1).enumerate() {
+        println!("Cluster {} ({} programs, canonical: {})", number + 1, cluster.members.len(), names[cluster.canonical]);
+        for &member in &cluster.members {
+            if member != cluster.canonical {
+                let score = fingerprints[cluster.canonical].similarity(&fingerprints[member]);
+                println!("  {} ({:.0}% similar)", names[member], score * 100.0);
             }
         }
-
-        println!();
-        print!("✅ Press ENTER when you've executed this operation...");
-        io::stdout().flush()?;
-
-        let mut _dummy = String::new();
-        io::stdin().read_line(&mut _dummy)?;
-
-        // Ask for state report
         println!();
-        println!("📊 Post-Execution Report:");
-        println!();
-
-        print!("What are you thinking right now? ");
-        io::stdout().flush()?;
-        let mut thought = String::new();
-        io::stdin().read_line(&mut thought)?;
+    }
 
-        print!("How do you feel? (emotion): ");
-        io::stdout().flush()?;
-        let mut emotion = String::new();
-        io::stdin().read_line(&mut emotion)?;
+    let unique: Vec<&str> = clusters.iter()
+        .filter(|c| c.members.len() == 1)
+        .map(|c| names[c.members[0]].as_str())
+        .collect();
+    if !unique.is_empty() {
+        println!("Unique: {}", unique.join(", "));
+    }
 
-        print!("What do you remember? ");
-        io::stdout().flush()?;
-        let mut memory = String::new();
-        io::stdin().read_line(&mut memory)?;
+    Ok(())
+}
 
-        execution_log.push(format!(
-            "Step {}: {:?}({})\n  Thought: {}\n  Emotion: {}\n  Memory: {}",
-            i + 1,
-            action.op,
-            action.target,
-            thought.trim(),
-            emotion.trim(),
-            memory.trim()
-        ));
+fn ai_cache(action: &AiCacheAction) -> anyhow::Result<()> {
+    let cache = ResponseCache::default();
 
-        println!("\n✓ Step {} complete. Brain state updated.\n", i + 1);
+    match action {
+        AiCacheAction::Clear => {
+            let removed = cache.clear()?;
+            println!("🗑️  Removed {} cached AI responses from {}", removed, cache.dir().display());
+        }
     }
 
-    let elapsed = start_time.elapsed();
-
-    println!("\n");
-    println!("🎉 PROGRAM EXECUTION COMPLETE 🎉");
-    println!("{}", "=".repeat(60));
-    println!();
-    println!("📈 Performance Metrics:");
-    println!("   Total Operations: {}", program.actions.len());
-    println!("   Execution Time: {:.2?}", elapsed);
-    println!("   Avg Time/Op: {:.2?}", elapsed / program.actions.len() as u32);
-    println!();
-    println!("🧠 Production Brain State Capture:");
-    println!("{}", "─".repeat(60));
+    Ok(())
+}
 
-    for log in &execution_log {
-        println!("{}", log);
-        println!();
-    }
+#[cfg(feature = "local-model")]
+fn load_local_backend(model: &Path, tokenizer: Option<&Path>) -> anyhow::Result<Box<dyn GenerationBackend>> {
+    println!("📦 Loading local model {}...", model.display());
+    let backend = LocalModelBackend::load(model, tokenizer)?;
+    Ok(Box::new(backend))
+}
 
-    println!("{}", "=".repeat(60));
-    println!();
-    println!("💡 Insights:");
-    println!("   • Your brain successfully executed {} UCL operations", program.actions.len());
-    println!("   • Language literally ran as a program on your neurons");
-    println!("   • You are now running UCL in production 🚀");
-    println!();
-    println!("Thank you for being a biological runtime environment! 🧠✨");
+#[cfg(not(feature = "local-model"))]
+fn load_local_backend(_model: &Path, _tokenizer: Option<&Path>) -> anyhow::Result<Box<dyn GenerationBackend>> {
+    anyhow::bail!("This build of ucl has no local model support. Rebuild with `--features local-model`.")
+}
 
+fn run_on_production_brain(program: &Program) -> anyhow::Result<()> {
+    let stdin = std::io::stdin();
+    InteractiveBrainRunner::new(stdin.lock(), std::io::stdout()).run(program)?;
     Ok(())
 }
 
-fn parallel_execute(path: &PathBuf, verbose: bool) -> anyhow::Result<()> {
+fn parallel_execute(
+    coordinator: MultiSubstrateCoordinator,
+    path: &PathBuf,
+    verbose: bool,
+    auto_route: bool,
+    cost_model: Option<&PathBuf>,
+    speculate: Option<&str>,
+    tui: bool,
+) -> anyhow::Result<()> {
     let program = validate_file(path)?;
 
+    // Verbose output would scribble over the dashboard
+    let mut coordinator = coordinator.with_verbose(verbose && !tui);
+
+    if auto_route || cost_model.is_some() {
+        let model = match cost_model {
+            Some(path) => CostModel::from_file(path)?,
+            None => CostModel::default(),
+        };
+        coordinator = coordinator.with_routing(model);
+    }
+
+    if let Some(substrate) = speculate {
+        if !ROUTABLE_SUBSTRATES.contains(&substrate) {
+            anyhow::bail!("Cannot speculate on {}. Use one of: {}", substrate, ROUTABLE_SUBSTRATES.join(", "));
+        }
+        coordinator = coordinator.with_speculation(substrate);
+    }
+
+    if tui {
+        return parallel_dashboard(coordinator, &program, path);
+    }
+
     println!("🌐 Multi-Substrate Parallel Execution");
     println!("{}", "=".repeat(60));
     println!();
 
-    let mut coordinator = MultiSubstrateCoordinator::new().with_verbose(verbose);
-    coordinator.execute(&program)?;
+    // Show the (possibly rolled back) state even when a step fails
+    if let Err(e) = coordinator.execute(&program) {
+        coordinator.show_results();
+        return Err(e);
+    }
 
     coordinator.show_results();
 
@@ -730,3 +2524,27 @@ fn parallel_execute(path: &PathBuf, verbose: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "tui")]
+fn parallel_dashboard(coordinator: MultiSubstrateCoordinator, program: &Program, path: &Path) -> anyhow::Result<()> {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use ucl::tui::Dashboard;
+
+    let dashboard = Rc::new(RefCell::new(Dashboard::start(path.display().to_string())?));
+
+    let observer = Rc::clone(&dashboard);
+    let mut coordinator = coordinator.with_observer(Box::new(move |coordinator, done, total| {
+        // A failed redraw shouldn't abort the program being run
+        let _ = observer.borrow_mut().update(coordinator, done, total);
+    }));
+
+    let outcome = coordinator.execute(program);
+    dashboard.borrow_mut().finish(&coordinator, &outcome)?;
+    outcome
+}
+
+#[cfg(not(feature = "tui"))]
+fn parallel_dashboard(_coordinator: MultiSubstrateCoordinator, _program: &Program, _path: &Path) -> anyhow::Result<()> {
+    anyhow::bail!("The dashboard requires building ucl with `--features tui`")
+}
+
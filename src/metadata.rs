@@ -0,0 +1,235 @@
+//! Standard program metadata fields, typed accessors and validation
+//!
+//! See METADATA.md for the schema. Any other keys are allowed and ignored.
+
+use crate::capabilities::SUBSTRATES;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Fields every program should declare
+pub const RECOMMENDED_FIELDS: &[&str] = &["description", "domain", "author", "version", "license"];
+
+/// Types a declared parameter may have
+pub const PARAMETER_TYPES: &[&str] = &["string", "number", "integer", "boolean", "array", "object"];
+
+/// A declared program input from `metadata.parameters`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub kind: Option<String>,
+    pub default: Option<Value>,
+    pub description: Option<String>,
+}
+
+/// A missing or invalid metadata field
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataWarning {
+    pub field: String,
+    pub message: String,
+}
+
+impl MetadataWarning {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        Self { field: field.to_string(), message: message.into() }
+    }
+
+    pub fn display(&self) -> String {
+        format!("{}: {}", self.field, self.message)
+    }
+}
+
+/// Read-only view over `Program::metadata`
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata<'a> {
+    fields: Option<&'a HashMap<String, Value>>,
+}
+
+impl<'a> Metadata<'a> {
+    pub fn new(fields: Option<&'a HashMap<String, Value>>) -> Self {
+        Self { fields }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a Value> {
+        self.fields?.get(key)
+    }
+
+    fn text(&self, key: &str) -> Option<&'a str> {
+        self.get(key)?.as_str()
+    }
+
+    pub fn author(&self) -> Option<&'a str> {
+        self.text("author")
+    }
+
+    pub fn version(&self) -> Option<&'a str> {
+        self.text("version")
+    }
+
+    pub fn license(&self) -> Option<&'a str> {
+        self.text("license")
+    }
+
+    pub fn description(&self) -> Option<&'a str> {
+        self.text("description")
+    }
+
+    pub fn domain(&self) -> Option<&'a str> {
+        self.text("domain")
+    }
+
+    /// Substrates the program needs, from `substrates` or the older single `target`
+    pub fn substrates(&self) -> Vec<&'a str> {
+        match self.get("substrates") {
+            Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).collect(),
+            _ => self.text("target").into_iter().collect(),
+        }
+    }
+
+    /// Declared parameters, sorted by name
+    pub fn parameters(&self) -> Vec<Parameter> {
+        let Some(Value::Object(params)) = self.get("parameters") else {
+            return Vec::new();
+        };
+
+        let mut parameters: Vec<Parameter> = params.iter()
+            .map(|(name, spec)| Parameter {
+                name: name.clone(),
+                kind: spec.get("type").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                default: spec.get("default").cloned(),
+                description: spec.get("description").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            })
+            .collect();
+        parameters.sort_by(|a, b| a.name.cmp(&b.name));
+        parameters
+    }
+
+    /// Missing recommended fields and fields with the wrong shape
+    pub fn validate(&self) -> Vec<MetadataWarning> {
+        let mut warnings = Vec::new();
+
+        for field in RECOMMENDED_FIELDS {
+            match self.get(field) {
+                None => warnings.push(MetadataWarning::new(field, "missing")),
+                Some(Value::String(s)) if s.trim().is_empty() => warnings.push(MetadataWarning::new(field, "empty")),
+                Some(Value::String(_)) => {}
+                Some(other) => warnings.push(MetadataWarning::new(field, format!("expected a string, got {}", other))),
+            }
+        }
+
+        if let Some(version) = self.version() {
+            let parts: Vec<&str> = version.split('.').collect();
+            if !(2..=3).contains(&parts.len()) || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit())) {
+                warnings.push(MetadataWarning::new("version", format!("'{}' is not MAJOR.MINOR[.PATCH]", version)));
+            }
+        }
+
+        match self.get("substrates") {
+            None => {}
+            Some(Value::Array(items)) => {
+                for item in items {
+                    match item.as_str() {
+                        Some(name) if SUBSTRATES.contains(&name) => {}
+                        _ => warnings.push(MetadataWarning::new(
+                            "substrates",
+                            format!("unknown substrate {} (expected one of: {})", item, SUBSTRATES.join(", ")),
+                        )),
+                    }
+                }
+            }
+            Some(_) => warnings.push(MetadataWarning::new("substrates", "expected an array of substrate names")),
+        }
+
+        match self.get("parameters") {
+            None => {}
+            Some(Value::Object(params)) => {
+                for (name, spec) in params {
+                    warnings.extend(validate_parameter(name, spec));
+                }
+            }
+            Some(_) => warnings.push(MetadataWarning::new("parameters", "expected an object of parameter specs")),
+        }
+
+        warnings
+    }
+}
+
+fn validate_parameter(name: &str, spec: &Value) -> Vec<MetadataWarning> {
+    let field = format!("parameters.{}", name);
+    let Some(spec) = spec.as_object() else {
+        return vec![MetadataWarning::new(&field, "expected {type, default, description}")];
+    };
+
+    let Some(kind) = spec.get("type").and_then(|v| v.as_str()) else {
+        return vec![MetadataWarning::new(&field, "missing type")];
+    };
+    if !PARAMETER_TYPES.contains(&kind) {
+        return vec![MetadataWarning::new(&field, format!("unknown type '{}' (expected one of: {})", kind, PARAMETER_TYPES.join(", ")))];
+    }
+
+    match spec.get("default") {
+        Some(default) if !has_type(default, kind) => {
+            vec![MetadataWarning::new(&field, format!("default {} is not a {}", default, kind))]
+        }
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn has_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Program;
+
+    #[test]
+    fn test_typed_accessors() {
+        let program = Program::from_json(r#"{
+            "metadata": {
+                "author": "Ada", "version": "1.2.0", "license": "MIT",
+                "description": "Boil water", "domain": "cooking",
+                "substrates": ["robot", "brain"],
+                "parameters": {"temperature": {"type": "number", "default": 95, "description": "Target °C"}}
+            },
+            "actions": []
+        }"#).unwrap();
+        let meta = program.meta();
+
+        assert_eq!(meta.author(), Some("Ada"));
+        assert_eq!(meta.domain(), Some("cooking"));
+        assert_eq!(meta.substrates(), vec!["robot", "brain"]);
+        assert_eq!(meta.parameters()[0].kind.as_deref(), Some("number"));
+        assert!(meta.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validation_warnings() {
+        let program = Program::from_json(r#"{
+            "metadata": {
+                "description": "x", "domain": "test", "author": 42, "version": "one",
+                "substrates": ["quantum"],
+                "parameters": {"n": {"type": "integer", "default": "five"}}
+            },
+            "actions": []
+        }"#).unwrap();
+
+        let fields: Vec<String> = program.meta().validate().into_iter().map(|w| w.field).collect();
+        assert_eq!(fields, vec!["author", "license", "version", "substrates", "parameters.n"]);
+    }
+
+    #[test]
+    fn test_legacy_target_and_missing_metadata() {
+        let program = Program::from_json(r#"{"metadata": {"target": "ruby"}, "actions": []}"#).unwrap();
+        assert_eq!(program.meta().substrates(), vec!["ruby"]);
+
+        assert_eq!(Program::new().meta().validate().len(), 5);
+    }
+}
@@ -0,0 +1,361 @@
+//! Delta-debugging minimizer: shrink a program while it keeps failing
+
+use crate::compiler::RubyCompiler;
+use crate::simulator::{BrainSimulator, RobotSimulator};
+use crate::{Action, Operation, Program};
+use anyhow::{Result, anyhow, bail};
+use std::process::Command;
+
+/// Failure predicates understood by `ucl minimize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The brain simulator returns an error
+    BrainError,
+    /// The robot simulator returns or records an error
+    RobotError,
+    /// The compiled Ruby fails to run
+    RubyError,
+    /// Ruby prints something different from the brain's output
+    RubyBrainDiffer,
+}
+
+impl Failure {
+    pub const NAMES: &'static [&'static str] = &["brain-error", "robot-error", "ruby-error", "ruby-brain-differ"];
+
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "brain-error" => Ok(Failure::BrainError),
+            "robot-error" => Ok(Failure::RobotError),
+            "ruby-error" => Ok(Failure::RubyError),
+            "ruby-brain-differ" => Ok(Failure::RubyBrainDiffer),
+            _ => bail!("Unknown failure predicate: {}. Use one of: {}", name, Self::NAMES.join(", ")),
+        }
+    }
+
+    /// Fail early if the predicate needs a tool that isn't installed
+    pub fn check_available(&self) -> Result<()> {
+        if matches!(self, Failure::RubyError | Failure::RubyBrainDiffer)
+            && Command::new("ruby").arg("--version").output().is_err()
+        {
+            bail!("Ruby is not installed or not in PATH");
+        }
+        Ok(())
+    }
+
+    /// Whether `program` exhibits this failure
+    pub fn reproduces(&self, program: &Program) -> bool {
+        match self {
+            Failure::BrainError => BrainSimulator::new().execute(program).is_err(),
+            Failure::RobotError => {
+                let mut robot = RobotSimulator::new();
+                robot.execute(program).is_err() || !robot.state().errors.is_empty()
+            }
+            Failure::RubyError => matches!(run_ruby(program), Ok(None)),
+            Failure::RubyBrainDiffer => {
+                let mut brain = BrainSimulator::new();
+                match (run_ruby(program), brain.execute(program)) {
+                    (Ok(Some(ruby)), Ok(())) => !same_output(&ruby, &brain.state().output),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// Compile and run with Ruby: `Ok(Some(lines))` on success, `Ok(None)` if Ruby
+/// failed, `Err` if the program doesn't compile or Ruby can't be started
+fn run_ruby(program: &Program) -> Result<Option<Vec<String>>> {
+    let code = RubyCompiler::new().compile(program)?;
+    let output = Command::new("ruby").arg("-e").arg(&code).output()?;
+
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&output.stdout).lines().map(|l| l.to_string()).collect()))
+}
+
+/// Compare outputs line by line, treating `7` and `7.0` as equal
+fn same_output(ruby: &[String], brain: &[String]) -> bool {
+    let normalize = |line: &String| {
+        let line = line.trim().trim_matches('"');
+        line.parse::<f64>().map(|n| n.to_string()).unwrap_or_else(|_| line.to_string())
+    };
+    ruby.len() == brain.len() && ruby.iter().zip(brain).all(|(a, b)| normalize(a) == normalize(b))
+}
+
+/// The smallest program found and how many candidates were tried
+#[derive(Debug, Clone)]
+pub struct Minimized {
+    pub program: Program,
+    pub tests: usize,
+}
+
+/// Nested action list selector
+#[derive(Debug, Clone, Copy)]
+enum Branch {
+    Then,
+    Else,
+    Body,
+    Catch,
+}
+
+impl Branch {
+    const ALL: [Branch; 4] = [Branch::Then, Branch::Else, Branch::Body, Branch::Catch];
+
+    fn of(self, action: &Action) -> &Option<Vec<Action>> {
+        match self {
+            Branch::Then => &action.then_actions,
+            Branch::Else => &action.else_actions,
+            Branch::Body => &action.body_actions,
+            Branch::Catch => &action.catch_actions,
+        }
+    }
+
+    fn of_mut(self, action: &mut Action) -> &mut Option<Vec<Action>> {
+        match self {
+            Branch::Then => &mut action.then_actions,
+            Branch::Else => &mut action.else_actions,
+            Branch::Body => &mut action.body_actions,
+            Branch::Catch => &mut action.catch_actions,
+        }
+    }
+}
+
+/// Route from the top-level list to a nested one
+type ListPath = Vec<(usize, Branch)>;
+
+fn list_paths(actions: &[Action], prefix: &ListPath, paths: &mut Vec<ListPath>) {
+    paths.push(prefix.clone());
+    for (index, action) in actions.iter().enumerate() {
+        for branch in Branch::ALL {
+            if let Some(nested) = branch.of(action) {
+                let mut path = prefix.clone();
+                path.push((index, branch));
+                list_paths(nested, &path, paths);
+            }
+        }
+    }
+}
+
+fn list<'a>(actions: &'a [Action], path: &[(usize, Branch)]) -> Option<&'a [Action]> {
+    match path.split_first() {
+        None => Some(actions),
+        Some((&(index, branch), rest)) => list(branch.of(actions.get(index)?).as_ref()?, rest),
+    }
+}
+
+fn list_mut<'a>(actions: &'a mut Vec<Action>, path: &[(usize, Branch)]) -> Option<&'a mut Vec<Action>> {
+    match path.split_first() {
+        None => Some(actions),
+        Some((&(index, branch), rest)) => {
+            let nested = branch.of_mut(actions.get_mut(index)?).as_mut()?;
+            list_mut(nested, rest)
+        }
+    }
+}
+
+struct Minimizer<F> {
+    program: Program,
+    fails: F,
+    tests: usize,
+}
+
+impl<F: FnMut(&Program) -> bool> Minimizer<F> {
+    /// Keep `candidate` if it still fails
+    fn attempt(&mut self, candidate: Program) -> bool {
+        self.tests += 1;
+        if (self.fails)(&candidate) {
+            self.program = candidate;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Candidate with the list at `path` replaced by `f(list)`
+    fn edit_list(&self, path: &ListPath, f: impl FnOnce(&mut Vec<Action>)) -> Option<Program> {
+        let mut candidate = self.program.clone();
+        f(list_mut(&mut candidate.actions, path)?);
+        Some(candidate)
+    }
+
+    /// Classic ddmin over one action list: drop ever-smaller chunks
+    fn reduce_list(&mut self, path: &ListPath) -> bool {
+        let mut changed = false;
+        let mut chunks = 2;
+
+        loop {
+            let len = match list(&self.program.actions, path).map(|l| l.len()) {
+                Some(len) if len > 0 => len,
+                _ => return changed,
+            };
+            let chunks_now = chunks.min(len);
+            let size = len.div_ceil(chunks_now);
+
+            let removed = (0..len).step_by(size).any(|start| {
+                let end = (start + size).min(len);
+                self.edit_list(path, |list| { list.drain(start..end); })
+                    .is_some_and(|candidate| self.attempt(candidate))
+            });
+
+            if removed {
+                changed = true;
+                chunks = (chunks_now - 1).max(2);
+            } else if chunks_now >= len {
+                return changed;
+            } else {
+                chunks = (chunks_now * 2).min(len);
+            }
+        }
+    }
+
+    /// Per-action simplifications: inline control flow, drop branches and fields
+    fn simplify_action(&mut self, path: &ListPath, index: usize) -> bool {
+        let Some(action) = list(&self.program.actions, path).and_then(|l| l.get(index).cloned()) else {
+            return false;
+        };
+
+        // Replace a control-flow action by one of its branches
+        if matches!(action.op, Operation::If | Operation::While | Operation::For) {
+            for branch in Branch::ALL {
+                if let Some(inner) = branch.of(&action).clone() {
+                    if self.edit_list(path, |list| { list.splice(index..=index, inner); })
+                        .is_some_and(|c| self.attempt(c))
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        let mut simpler: Vec<Action> = Vec::new();
+        if action.else_actions.is_some() {
+            simpler.push(Action { else_actions: None, ..action.clone() });
+        }
+        if action.catch_actions.is_some() {
+            simpler.push(Action { catch_actions: None, ..action.clone() });
+        }
+        if action.t.is_some() || action.dur.is_some() {
+            simpler.push(Action { t: None, dur: None, ..action.clone() });
+        }
+        if action.effects.is_some() || action.pre.is_some() || action.post.is_some() {
+            simpler.push(Action { effects: None, pre: None, post: None, ..action.clone() });
+        }
+        for key in action.params.iter().flat_map(|p| p.keys()) {
+            let mut without = action.clone();
+            if let Some(params) = without.params.as_mut() {
+                params.remove(key);
+                if params.is_empty() {
+                    without.params = None;
+                }
+            }
+            simpler.push(without);
+        }
+
+        simpler.into_iter().any(|replacement| {
+            self.edit_list(path, |list| list[index] = replacement)
+                .is_some_and(|c| self.attempt(c))
+        })
+    }
+
+    fn run(mut self) -> Minimized {
+        loop {
+            let mut changed = false;
+
+            let mut paths = Vec::new();
+            list_paths(&self.program.actions, &Vec::new(), &mut paths);
+            for path in &paths {
+                changed |= self.reduce_list(path);
+            }
+
+            let mut paths = Vec::new();
+            list_paths(&self.program.actions, &Vec::new(), &mut paths);
+            for path in &paths {
+                let mut index = 0;
+                while let Some(len) = list(&self.program.actions, path).map(|l| l.len()) {
+                    if index >= len {
+                        break;
+                    }
+                    if self.simplify_action(path, index) {
+                        changed = true;
+                    } else {
+                        index += 1;
+                    }
+                }
+            }
+
+            if !changed {
+                return Minimized { program: self.program, tests: self.tests };
+            }
+        }
+    }
+}
+
+/// Shrink `program` to a smaller one for which `fails` still holds
+pub fn minimize(program: &Program, mut fails: impl FnMut(&Program) -> bool) -> Result<Minimized> {
+    if !fails(program) {
+        return Err(anyhow!("The original program does not exhibit the failure"));
+    }
+
+    Ok(Minimizer { program: program.clone(), fails, tests: 1 }.run())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emits(program: &Program) -> Vec<String> {
+        let mut targets = Vec::new();
+        program.walk(&mut |a, _| {
+            if a.op == Operation::Emit {
+                targets.push(a.target.clone());
+            }
+        });
+        targets
+    }
+
+    #[test]
+    fn test_minimize_keeps_only_culprit() {
+        let json = r#"{"actions": [
+            {"actor": "A", "op": "Emit", "target": "one", "t": 0},
+            {"actor": "A", "op": "Emit", "target": "two"},
+            {"actor": "A", "op": "If",
+             "condition": {"type": "comparison", "op": "==", "left": 1, "right": 1},
+             "target": "check",
+             "then": [
+                {"actor": "A", "op": "Emit", "target": "three"},
+                {"actor": "A", "op": "Emit", "target": "bad", "params": {"why": "bug"}}
+             ]},
+            {"actor": "A", "op": "Emit", "target": "four"}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let result = minimize(&program, |p| emits(p).contains(&"bad".to_string())).unwrap();
+
+        assert_eq!(result.program.actions.len(), 1);
+        let action = &result.program.actions[0];
+        assert_eq!(action.target, "bad");
+        assert!(action.params.is_none());
+    }
+
+    #[test]
+    fn test_minimize_requires_failing_input() {
+        let program = Program::from_json(r#"{"actions": []}"#).unwrap();
+        assert!(minimize(&program, |_| false).is_err());
+    }
+
+    #[test]
+    fn test_robot_error_predicate() {
+        let json = r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "cup"},
+            {"actor": "robot", "op": "StoreFact", "target": "memory"},
+            {"actor": "robot", "op": "Stir", "target": "cup"}
+        ]}"#;
+        let program = Program::from_json(json).unwrap();
+
+        let result = minimize(&program, |p| Failure::RobotError.reproduces(p)).unwrap();
+
+        assert_eq!(result.program.actions.len(), 1);
+        assert_eq!(result.program.actions[0].op, Operation::StoreFact);
+    }
+}
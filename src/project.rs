@@ -0,0 +1,234 @@
+//! Multi-file projects described by a `ucl.toml` manifest
+//!
+//! ```toml
+//! [project]
+//! name = "tea"
+//! entry = "src/main.json"
+//! modules = ["src/kettle.json"]
+//! targets = ["robot", "ruby"]
+//!
+//! [parameters]
+//! temperature = 95
+//!
+//! [build]
+//! out_dir = "build"
+//! artifacts = ["json", "ruby"]
+//! ```
+//!
+//! Modules are prepended to the entry program (so the functions they define
+//! are available), and each parameter becomes a `Bind` at the very start.
+
+use crate::compiler::RubyCompiler;
+use crate::{Action, Operation, Program};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "ucl.toml";
+
+/// Artifact kinds `ucl build` can write
+pub const ARTIFACT_KINDS: &[&str] = &["json", "ruby"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub project: ProjectSection,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub build: BuildSection,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectSection {
+    pub name: String,
+    pub version: Option<String>,
+    /// Main program, relative to the manifest
+    pub entry: PathBuf,
+    /// Programs whose actions come before the entry's
+    #[serde(default)]
+    pub modules: Vec<PathBuf>,
+    /// Where `ucl run` executes the project: `brain`, `robot`, `ruby`, `js`, `rust`, `bash` or `wasm`
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildSection {
+    #[serde(default = "default_out_dir")]
+    pub out_dir: PathBuf,
+    /// Defaults to the bundled JSON, plus Ruby if `ruby` is a target
+    pub artifacts: Option<Vec<String>>,
+}
+
+fn default_out_dir() -> PathBuf {
+    PathBuf::from("build")
+}
+
+impl Default for BuildSection {
+    fn default() -> Self {
+        Self { out_dir: default_out_dir(), artifacts: None }
+    }
+}
+
+/// A loaded manifest and the directory it lives in
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    pub fn load(manifest_path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(manifest_path)
+            .with_context(|| format!("Reading {}", manifest_path.display()))?;
+        let manifest: Manifest = toml::from_str(&content)
+            .with_context(|| format!("Parsing {}", manifest_path.display()))?;
+
+        if let Some(kind) = manifest.build.artifacts.iter().flatten().find(|k| !ARTIFACT_KINDS.contains(&k.as_str())) {
+            bail!("Unknown artifact '{}' (expected one of: {})", kind, ARTIFACT_KINDS.join(", "));
+        }
+
+        let root = manifest_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(Self { root, manifest })
+    }
+
+    /// Find `ucl.toml` in `dir` or the nearest directory above it
+    pub fn discover(dir: &Path) -> Result<Self> {
+        let found = dir.ancestors()
+            .map(|d| d.join(MANIFEST_FILE))
+            .find(|path| path.is_file());
+        match found {
+            Some(path) => Self::load(&path),
+            None => bail!("No {} found in {} or any parent directory", MANIFEST_FILE, dir.display()),
+        }
+    }
+
+    fn read_program(&self, relative: &Path) -> Result<Program> {
+        let path = self.root.join(relative);
+        Program::load(&path).with_context(|| format!("Loading {}", path.display()))
+    }
+
+    /// One program: parameter bindings, then every module, then the entry
+    pub fn bundle(&self) -> Result<Program> {
+        let mut program = self.read_program(&self.manifest.project.entry)?;
+
+        let mut actions: Vec<Action> = self.manifest.parameters.iter()
+            .map(|(name, value)| {
+                Action::new("ucl", Operation::Bind, name.clone())
+                    .with_params([("value".to_string(), value.clone())].into())
+            })
+            .collect();
+        for module in &self.manifest.project.modules {
+            actions.extend(self.read_program(module)?.actions);
+        }
+        actions.append(&mut program.actions);
+        program.actions = actions;
+
+        let metadata = program.metadata.get_or_insert_with(Default::default);
+        metadata.entry("name".to_string()).or_insert_with(|| self.manifest.project.name.clone().into());
+        if let Some(version) = &self.manifest.project.version {
+            metadata.entry("version".to_string()).or_insert_with(|| version.clone().into());
+        }
+        if !self.manifest.project.targets.is_empty() {
+            metadata.entry("substrates".to_string())
+                .or_insert_with(|| self.manifest.project.targets.clone().into());
+        }
+
+        Ok(program)
+    }
+
+    fn artifacts(&self) -> Vec<String> {
+        self.manifest.build.artifacts.clone().unwrap_or_else(|| {
+            let mut kinds = vec!["json".to_string()];
+            if self.manifest.project.targets.iter().any(|t| t == "ruby") {
+                kinds.push("ruby".to_string());
+            }
+            kinds
+        })
+    }
+
+    /// Write every artifact into the output directory, returning their paths
+    pub fn build(&self) -> Result<Vec<PathBuf>> {
+        let program = self.bundle()?;
+        let out_dir = self.root.join(&self.manifest.build.out_dir);
+        fs::create_dir_all(&out_dir).with_context(|| format!("Creating {}", out_dir.display()))?;
+
+        let name = &self.manifest.project.name;
+        let mut written = Vec::new();
+        for kind in self.artifacts() {
+            let (path, content) = match kind.as_str() {
+                "json" => (out_dir.join(format!("{}.json", name)), program.to_json()?),
+                "ruby" => (out_dir.join(format!("{}.rb", name)), RubyCompiler::new().compile(&program)?),
+                _ => unreachable!("artifact kinds are checked on load"),
+            };
+            fs::write(&path, content).with_context(|| format!("Writing {}", path.display()))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ucl_project_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+
+        fs::write(dir.join(MANIFEST_FILE), r#"
+            [project]
+            name = "greeter"
+            version = "0.2.0"
+            entry = "src/main.json"
+            modules = ["src/lib.json"]
+            targets = ["brain", "ruby"]
+
+            [parameters]
+            times = 2
+        "#).unwrap();
+        fs::write(dir.join("src/lib.json"), r#"{"actions": [
+            {"actor": "lib", "op": "DefineFunction", "target": "twice",
+             "params": {"args": ["n"], "body": [{"actor": "lib", "op": "Return", "target": "r",
+                "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}]}}
+        ]}"#).unwrap();
+        fs::write(dir.join("src/main.json"), r#"{"actions": [
+            {"actor": "main", "op": "Bind", "target": "result", "params": {"value": {"call": "twice", "args": {"n": {"var": "times"}}}}}
+        ]}"#).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_bundle_resolves_modules_and_parameters() {
+        let dir = project_dir("bundle");
+        let project = Project::discover(&dir.join("src")).unwrap();
+        let program = project.bundle().unwrap();
+
+        let ops: Vec<String> = program.actions.iter().map(|a| format!("{:?} {}", a.op, a.target)).collect();
+        assert_eq!(ops, vec!["Bind times", "DefineFunction twice", "Bind result"]);
+        assert_eq!(program.meta().version(), Some("0.2.0"));
+
+        let mut brain = crate::simulator::BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state().beliefs["result"], serde_json::json!(4.0));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_writes_artifacts() {
+        let dir = project_dir("build");
+        let written = Project::load(&dir.join(MANIFEST_FILE)).unwrap().build().unwrap();
+
+        assert_eq!(written, vec![dir.join("build/greeter.json"), dir.join("build/greeter.rb")]);
+        assert!(fs::read_to_string(&written[1]).unwrap().contains("def twice"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
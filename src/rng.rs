@@ -0,0 +1,85 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+/// Small deterministic pseudo-random generator (SplitMix64)
+///
+/// Simulators use this instead of an external crate so that runs can be
+/// replayed exactly from a seed.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    /// Create a generator from an explicit seed
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Create a generator seeded from the system clock
+    pub fn from_entropy() -> Self {
+        Self::new(RandomState::new().hash_one(std::time::SystemTime::now()))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in the inclusive range [min, max]
+    pub fn gen_range_i64(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min {
+            return min;
+        }
+        let range = (max - min) as u64 + 1;
+        min + (self.next_u64() % range) as i64
+    }
+
+    /// Normally distributed sample (Box-Muller transform)
+    pub fn gaussian(&mut self, mean: f64, sigma: f64) -> f64 {
+        if sigma == 0.0 {
+            return mean;
+        }
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        mean + sigma * z
+    }
+}
+
+impl Default for SeededRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = SeededRng::new(42);
+        let mut b = SeededRng::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_range_is_inclusive() {
+        let mut rng = SeededRng::new(7);
+        for _ in 0..1000 {
+            let n = rng.gen_range_i64(0, 9);
+            assert!((0..=9).contains(&n));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{Action, Operation, capabilities};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Substrate actors the coordinator can route to, in tie-break order
+pub const ROUTABLE_SUBSTRATES: &[&str] = &["RubyVM", "RobotVM", "BrainVM"];
+
+/// Where actions go when no substrate has a cost for them
+pub const FALLBACK_SUBSTRATE: &str = "BrainVM";
+
+/// Broad kinds of work, each cheapest on a different substrate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCategory {
+    Arithmetic,
+    Physical,
+    Semantic,
+}
+
+impl OpCategory {
+    pub fn of(op: &Operation) -> Option<Self> {
+        match op {
+            Operation::Assign | Operation::Call | Operation::Write | Operation::GenRandomInt
+            | Operation::Return | Operation::Halt | Operation::If | Operation::While | Operation::For
+            | Operation::DefineFunction => Some(OpCategory::Arithmetic),
+
+            Operation::Gather | Operation::Measure | Operation::Scan | Operation::ReadSensor | Operation::Heat
+            | Operation::Pour | Operation::Mix | Operation::Stir | Operation::Place
+            | Operation::Remove | Operation::Grasp | Operation::Release | Operation::Steep
+            | Operation::Serve => Some(OpCategory::Physical),
+
+            Operation::StoreFact | Operation::Assert | Operation::Decide | Operation::Oblige
+            | Operation::Read | Operation::Receive | Operation::Bind | Operation::Emit
+            | Operation::Sleep | Operation::Dream | Operation::Imagine => Some(OpCategory::Semantic),
+
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpCategory::Arithmetic => "arithmetic",
+            OpCategory::Physical => "physical",
+            OpCategory::Semantic => "semantic",
+        }
+    }
+}
+
+/// Per-substrate execution costs used to pick a substrate automatically
+///
+/// Costs are keyed by substrate actor, then by category name (`arithmetic`,
+/// `physical`, `semantic`) or by operation name, which takes precedence.
+/// A substrate with no cost for an operation is never chosen for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CostModel {
+    costs: HashMap<String, HashMap<String, f64>>,
+}
+
+/// Where an action was sent and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutingDecision {
+    /// The top-level action routed; actions in a `Parallel`'s body share
+    /// the `Parallel`'s index
+    pub index: usize,
+    pub op: Operation,
+    pub target: String,
+    pub substrate: String,
+    pub cost: Option<f64>,
+}
+
+impl RoutingDecision {
+    pub fn display(&self) -> String {
+        match self.cost {
+            Some(cost) => format!("#{} {:?} → {}: {} (cost {})", self.index + 1, self.op, self.target, self.substrate, cost),
+            None => format!("#{} {:?} → {}: {} (no cost, fallback)", self.index + 1, self.op, self.target, self.substrate),
+        }
+    }
+}
+
+impl CostModel {
+    /// Load a JSON object of `{"Substrate": {"category or Op": cost}}`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read cost model {}: {}", path.display(), e))?;
+        let model: Self = serde_json::from_str(&content)?;
+
+        if let Some(unknown) = model.costs.keys().find(|s| !ROUTABLE_SUBSTRATES.contains(&s.as_str())) {
+            return Err(anyhow!(
+                "Unknown substrate in cost model: {} (expected one of {})",
+                unknown,
+                ROUTABLE_SUBSTRATES.join(", ")
+            ));
+        }
+
+        Ok(model)
+    }
+
+    pub fn with_cost(mut self, substrate: &str, key: &str, cost: f64) -> Self {
+        self.costs.entry(substrate.to_string()).or_default().insert(key.to_string(), cost);
+        self
+    }
+
+    /// Cost of running `op` on `substrate`, if it can run there at all
+    pub fn cost(&self, substrate: &str, op: &Operation) -> Option<f64> {
+        if !capabilities::supports(substrate, op) {
+            return None;
+        }
+
+        let costs = self.costs.get(substrate)?;
+        costs.get(&format!("{:?}", op)).copied()
+            .or_else(|| OpCategory::of(op).and_then(|c| costs.get(c.name()).copied()))
+    }
+
+    /// Pick the cheapest substrate for an action
+    pub fn route(&self, index: usize, action: &Action) -> RoutingDecision {
+        let mut best: Option<(&str, f64)> = None;
+
+        for substrate in ROUTABLE_SUBSTRATES {
+            if let Some(cost) = self.cost(substrate, &action.op) {
+                if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                    best = Some((substrate, cost));
+                }
+            }
+        }
+
+        let (substrate, cost) = match best {
+            Some((substrate, cost)) => (substrate, Some(cost)),
+            None => (FALLBACK_SUBSTRATE, None),
+        };
+
+        RoutingDecision {
+            index,
+            op: action.op.clone(),
+            target: action.target.clone(),
+            substrate: substrate.to_string(),
+            cost,
+        }
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self { costs: HashMap::new() }
+            .with_cost("RubyVM", "arithmetic", 1.0)
+            .with_cost("RubyVM", "semantic", 5.0)
+            .with_cost("BrainVM", "arithmetic", 10.0)
+            .with_cost("BrainVM", "semantic", 1.0)
+            .with_cost("BrainVM", "physical", 20.0)
+            .with_cost("RobotVM", "physical", 1.0)
+            .with_cost("RobotVM", "arithmetic", 15.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(op: Operation) -> Action {
+        Action::new("auto", op, "x")
+    }
+
+    #[test]
+    fn test_default_routing_by_category() {
+        let model = CostModel::default();
+
+        assert_eq!(model.route(0, &action(Operation::GenRandomInt)).substrate, "RubyVM");
+        assert_eq!(model.route(0, &action(Operation::Heat)).substrate, "RobotVM");
+        assert_eq!(model.route(0, &action(Operation::StoreFact)).substrate, "BrainVM");
+    }
+
+    #[test]
+    fn test_op_override_and_fallback() {
+        let model = CostModel::default().with_cost("BrainVM", "GenRandomInt", 0.5);
+        assert_eq!(model.route(0, &action(Operation::GenRandomInt)).substrate, "BrainVM");
+
+        let decision = model.route(0, &action(Operation::Flurble));
+        assert_eq!(decision.substrate, FALLBACK_SUBSTRATE);
+        assert_eq!(decision.cost, None);
+    }
+}
@@ -0,0 +1,247 @@
+//! Real-time execution: dispatch each action at the wall-clock moment its
+//! `t` names
+//!
+//! `t` is seconds from the start of the run, or seconds after
+//! `metadata.epoch` (an RFC 3339 timestamp) when the program has one.
+
+use crate::coordinator::MultiSubstrateCoordinator;
+use crate::{Action, Program};
+use anyhow::{Context, Result, bail};
+use std::time::{Duration, Instant};
+
+/// Metadata key holding the absolute time that `t = 0` refers to
+pub const EPOCH_KEY: &str = "epoch";
+
+/// How close to a deadline the scheduler stops sleeping and re-checks the clock
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+/// Source of time, so schedules can be tested without waiting
+pub trait Clock {
+    /// Time elapsed since the clock was created
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// The real monotonic clock
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// One action sent to its substrate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dispatch {
+    pub index: usize,
+    pub substrate: String,
+    /// Seconds from the start of the run the action was due
+    pub due: f64,
+    /// Seconds from the start of the run it was actually sent
+    pub sent: f64,
+}
+
+impl Dispatch {
+    /// How far behind schedule the action went out, in seconds
+    pub fn lateness(&self) -> f64 {
+        (self.sent - self.due).max(0.0)
+    }
+}
+
+/// Everything dispatched during a run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScheduleReport {
+    pub dispatches: Vec<Dispatch>,
+}
+
+impl ScheduleReport {
+    pub fn max_lateness(&self) -> f64 {
+        self.dispatches.iter().map(Dispatch::lateness).fold(0.0, f64::max)
+    }
+
+    pub fn mean_lateness(&self) -> f64 {
+        if self.dispatches.is_empty() {
+            return 0.0;
+        }
+        self.dispatches.iter().map(Dispatch::lateness).sum::<f64>() / self.dispatches.len() as f64
+    }
+}
+
+/// Dispatches a program's actions at their timestamps
+///
+/// Every deadline is measured from the same start instant rather than from
+/// the previous action, so oversleeping or a slow substrate never pushes later
+/// actions back: the next wait is simply shorter.
+pub struct Scheduler<C: Clock> {
+    clock: C,
+    speed: f64,
+}
+
+impl Scheduler<SystemClock> {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock::new())
+    }
+}
+
+impl Default for Scheduler<SystemClock> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Self { clock, speed: 1.0 }
+    }
+
+    /// Play the schedule faster (`2.0`) or slower (`0.5`) than written
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Seconds from now that `t = 0` refers to: 0, or the distance to
+    /// `metadata.epoch` (negative if it has passed)
+    fn origin(program: &Program) -> Result<f64> {
+        let Some(epoch) = program.meta().get(EPOCH_KEY) else {
+            return Ok(0.0);
+        };
+        let epoch = epoch.as_str().context("metadata.epoch must be an RFC 3339 timestamp string")?;
+        let epoch = chrono::DateTime::parse_from_rfc3339(epoch)
+            .with_context(|| format!("Invalid metadata.epoch: {}", epoch))?;
+        Ok((epoch.with_timezone(&chrono::Utc) - chrono::Utc::now()).as_seconds_f64())
+    }
+
+    fn wait_until(&mut self, deadline: f64) {
+        loop {
+            let now = self.clock.now().as_secs_f64();
+            if now >= deadline {
+                return;
+            }
+            let remaining = Duration::from_secs_f64(deadline - now);
+            self.clock.sleep(if remaining > SPIN_MARGIN { remaining - SPIN_MARGIN } else { remaining });
+        }
+    }
+
+    /// Run `program` on `coordinator`, calling `on_dispatch` as each action goes out
+    ///
+    /// Actions without `t` go out as soon as the one before them has.
+    /// Actions whose time has already passed go out immediately and show up
+    /// as late in the report.
+    pub fn run(
+        &mut self,
+        program: &Program,
+        coordinator: &mut MultiSubstrateCoordinator,
+        mut on_dispatch: impl FnMut(&Action, &Dispatch),
+    ) -> Result<ScheduleReport> {
+        if !(self.speed > 0.0 && self.speed.is_finite()) {
+            bail!("Speed must be a positive number, got {}", self.speed);
+        }
+
+        let origin = Self::origin(program)? + self.clock.now().as_secs_f64();
+        let mut report = ScheduleReport::default();
+        let mut due = origin;
+
+        for (index, action) in program.actions.iter().enumerate() {
+            if let Some(t) = action.t {
+                due = origin + t / self.speed;
+            }
+            self.wait_until(due);
+
+            let sent = self.clock.now().as_secs_f64();
+            let substrate = coordinator.dispatch(index, action)
+                .with_context(|| format!("Action {} ({:?} {})", index + 1, action.op, action.target))?;
+
+            let dispatch = Dispatch { index, substrate, due, sent };
+            on_dispatch(action, &dispatch);
+            report.dispatches.push(dispatch);
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Virtual time that oversleeps by a fixed amount, like a busy OS
+    struct LaggyClock {
+        now: Duration,
+        lag: Duration,
+        sleeps: usize,
+    }
+
+    impl Clock for LaggyClock {
+        fn now(&self) -> Duration {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.now += duration + self.lag;
+            self.sleeps += 1;
+        }
+    }
+
+    fn program() -> Program {
+        Program::from_json(r#"{"actions": [
+            {"actor": "BrainVM", "op": "Emit", "target": "C4", "t": 0, "params": {"content": "C"}},
+            {"actor": "BrainVM", "op": "Emit", "target": "E4", "t": 1, "params": {"content": "E"}},
+            {"actor": "BrainVM", "op": "Emit", "target": "rest"},
+            {"actor": "BrainVM", "op": "Emit", "target": "G4", "t": 3, "params": {"content": "G"}}
+        ]}"#).unwrap()
+    }
+
+    #[test]
+    fn test_lag_does_not_accumulate() {
+        let clock = LaggyClock { now: Duration::ZERO, lag: Duration::from_millis(10), sleeps: 0 };
+        let mut scheduler = Scheduler::with_clock(clock);
+        let mut coordinator = MultiSubstrateCoordinator::new();
+
+        let report = scheduler.run(&program(), &mut coordinator, |_, _| {}).unwrap();
+
+        let due: Vec<f64> = report.dispatches.iter().map(|d| d.due).collect();
+        assert_eq!(due, vec![0.0, 1.0, 1.0, 3.0]);
+        // Each wait overshoots by at most one lag, and the error never grows
+        for dispatch in &report.dispatches {
+            assert!(dispatch.lateness() <= 0.010 + 1e-9, "{:?}", dispatch);
+        }
+        assert_eq!(coordinator.activity("BrainVM").unwrap().actions, 4);
+    }
+
+    #[test]
+    fn test_speed_and_passed_epoch() {
+        let clock = LaggyClock { now: Duration::ZERO, lag: Duration::ZERO, sleeps: 0 };
+        let mut scheduler = Scheduler::with_clock(clock).with_speed(2.0);
+        let report = scheduler.run(&program(), &mut MultiSubstrateCoordinator::new(), |_, _| {}).unwrap();
+        assert_eq!(report.dispatches.last().unwrap().due, 1.5);
+
+        // An epoch in the past makes everything late but still runs it, without sleeping
+        let mut late = program();
+        late.metadata = Some([(EPOCH_KEY.to_string(), serde_json::json!("2000-01-01T00:00:00Z"))].into());
+        let mut scheduler = Scheduler::with_clock(LaggyClock { now: Duration::ZERO, lag: Duration::ZERO, sleeps: 0 });
+        let report = scheduler.run(&late, &mut MultiSubstrateCoordinator::new(), |_, _| {}).unwrap();
+        assert_eq!(report.dispatches.len(), 4);
+        assert!(report.max_lateness() > 1e8);
+        assert_eq!(scheduler.clock.sleeps, 0);
+    }
+}
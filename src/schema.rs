@@ -0,0 +1,184 @@
+//! The params and fields each built-in operation needs
+//!
+//! Simulators and compilers only notice a missing `value` or a `body` that
+//! isn't a list when they reach the action; [`check`] finds them up front.
+
+use crate::metadata::has_type;
+use crate::{Action, Operation, Program};
+
+/// One param an operation reads; `kind` is a `metadata::PARAMETER_TYPES`
+/// name, or `None` for any JSON value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamRule {
+    pub name: &'static str,
+    pub kind: Option<&'static str>,
+    pub required: bool,
+}
+
+const fn required(name: &'static str, kind: Option<&'static str>) -> ParamRule {
+    ParamRule { name, kind, required: true }
+}
+
+const fn optional(name: &'static str, kind: Option<&'static str>) -> ParamRule {
+    ParamRule { name, kind, required: false }
+}
+
+const VALUE: &[ParamRule] = &[required("value", None)];
+const FUNCTION: &[ParamRule] = &[required("args", Some("array")), required("body", Some("array"))];
+const MACRO: &[ParamRule] = &[optional("args", Some("array")), optional("defaults", Some("object")), required("body", Some("array"))];
+const POUR: &[ParamRule] = &[required("from", Some("string")), required("into", Some("string"))];
+const RANDOM: &[ParamRule] = &[optional("min", Some("integer")), optional("max", Some("integer"))];
+const GATHER: &[ParamRule] = &[optional("items", Some("array")), optional("positions", Some("object")), optional("sizes", Some("object")), optional("capacities", Some("object"))];
+const OBLIGE: &[ParamRule] = &[optional("duty", Some("string"))];
+const TRY: &[ParamRule] = &[optional("error", Some("string"))];
+const THROW: &[ParamRule] = &[optional("value", None)];
+const HALT: &[ParamRule] = &[optional("status", None)];
+const READ_SENSOR: &[ParamRule] = &[optional("sensor", Some("string")), optional("into", Some("string"))];
+const READ: &[ParamRule] = &[optional("into", Some("string")), optional("follow", Some("string"))];
+const DREAM: &[ParamRule] = &[optional("count", Some("integer"))];
+const IMAGINE: &[ParamRule] = &[optional("ask", Some("string")), optional("into", Some("string"))];
+const FOR_EACH: &[ParamRule] = &[optional("in", None), optional("over", Some("string"))];
+const MATCH: &[ParamRule] = &[required("value", None), optional("cases", Some("array"))];
+
+/// Effects a variable binding can have; a Bind declaring anything else is
+/// something binding in the world, like a protein to DNA, and takes no `value`
+const MEMORY_EFFECTS: &[&str] = &["Memory", "Stack", "Heap"];
+
+/// The params `op` reads; operations not listed take whatever they're given
+pub fn param_rules(op: &Operation) -> &'static [ParamRule] {
+    match op {
+        Operation::Bind | Operation::Assign | Operation::Append => VALUE,
+        Operation::DefineFunction => FUNCTION,
+        Operation::DefineMacro => MACRO,
+        Operation::Pour => POUR,
+        Operation::GenRandomInt => RANDOM,
+        Operation::Gather => GATHER,
+        Operation::Oblige => OBLIGE,
+        Operation::Try => TRY,
+        Operation::Throw => THROW,
+        Operation::Halt => HALT,
+        Operation::Read => READ,
+        Operation::ReadSensor => READ_SENSOR,
+        Operation::Dream => DREAM,
+        Operation::Imagine => IMAGINE,
+        Operation::Match => MATCH,
+        Operation::ForEach => FOR_EACH,
+        _ => &[],
+    }
+}
+
+/// Action fields outside `params` that `op` can't run without
+pub fn required_fields(op: &Operation) -> &'static [&'static str] {
+    match op {
+        Operation::If => &["condition", "then"],
+        Operation::While => &["condition"],
+        Operation::For => &["variable", "from", "to"],
+        Operation::ForEach => &["variable"],
+        Operation::Try => &["body", "catch"],
+        Operation::Parallel => &["body"],
+        Operation::Imagine => &["body"],
+        Operation::When => &["condition", "body"],
+        _ => &[],
+    }
+}
+
+/// A param or field an action is missing or has the wrong type for
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamProblem {
+    /// Where the action is, e.g. `actions[2].then[0]`
+    pub path: String,
+    pub op: Operation,
+    pub message: String,
+}
+
+impl ParamProblem {
+    pub fn display(&self) -> String {
+        format!("{} {:?}: {}", self.path, self.op, self.message)
+    }
+}
+
+/// Every action's params and fields against its operation's rules,
+/// function bodies included
+pub fn check(program: &Program) -> Vec<ParamProblem> {
+    let mut problems = Vec::new();
+    program.walk(&mut |action, path| {
+        for message in check_action(action) {
+            problems.push(ParamProblem { path: path.to_string(), op: action.op.clone(), message });
+        }
+    });
+    problems
+}
+
+fn check_action(action: &Action) -> Vec<String> {
+    let mut messages = Vec::new();
+
+    for field in required_fields(&action.op) {
+        let present = match *field {
+            "condition" => action.condition.is_some(),
+            "then" => action.then_actions.is_some(),
+            "variable" => action.loop_var.is_some(),
+            "from" => action.from_expr.is_some(),
+            "to" => action.to_expr.is_some(),
+            "body" => action.body_actions.is_some(),
+            "catch" => action.catch_actions.is_some(),
+            _ => true,
+        };
+        if !present {
+            messages.push(format!("missing field '{}'", field));
+        }
+    }
+
+    let physical = action.op == Operation::Bind && action.effects.as_ref()
+        .is_some_and(|effects| effects.iter().any(|e| !MEMORY_EFFECTS.contains(&e.as_str())));
+    let rules = if physical { &[] } else { param_rules(&action.op) };
+
+    for rule in rules {
+        match action.params.as_ref().and_then(|p| p.get(rule.name)) {
+            None if rule.required => messages.push(format!("missing required param '{}'", rule.name)),
+            Some(value) => {
+                if let Some(kind) = rule.kind.filter(|kind| !has_type(value, kind)) {
+                    messages.push(format!("param '{}' should be a {}, got {}", rule.name, kind, value));
+                }
+            }
+            None => {}
+        }
+    }
+
+    if action.op == Operation::ForEach && !action.params.as_ref().is_some_and(|p| p.contains_key("in") || p.contains_key("over")) {
+        messages.push("missing required param 'in' or 'over'".to_string());
+    }
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_missing_and_mistyped_params() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "x", "params": {"value": 1}},
+            {"actor": "VM", "op": "Bind", "target": "y"},
+            {"actor": "Protein", "op": "Bind", "target": "DNA", "params": {"site": "E-box"}, "effects": ["Bio"]},
+            {"actor": "VM", "op": "DefineFunction", "target": "f", "params": {"args": "n", "body": [
+                {"actor": "VM", "op": "Assign", "target": "z", "params": {}}
+            ]}},
+            {"actor": "robot", "op": "If", "target": "hot", "condition": {"type": "comparison", "op": ">", "left": 1, "right": 0},
+             "then": [{"actor": "robot", "op": "Pour", "target": "water", "params": {"from": "kettle", "into": 3}}]},
+            {"actor": "VM", "op": "For", "target": "loop", "variable": "i", "from": 1},
+            {"actor": "VM", "op": "Emit", "target": "out"},
+            {"actor": "VM", "op": "ForEach", "target": "loop", "variable": "x", "params": {}}
+        ]}"#).unwrap();
+
+        let problems: Vec<String> = check(&program).iter().map(ParamProblem::display).collect();
+        assert_eq!(problems, vec![
+            "actions[1] Bind: missing required param 'value'",
+            "actions[3] DefineFunction: param 'args' should be a array, got \"n\"",
+            "actions[3].params.body[0] Assign: missing required param 'value'",
+            "actions[4].then[0] Pour: param 'into' should be a string, got 3",
+            "actions[5] For: missing field 'to'",
+            "actions[7] ForEach: missing required param 'in' or 'over'",
+        ]);
+    }
+}
@@ -0,0 +1,168 @@
+//! Ed25519 signatures over a canonical serialization of a program
+//!
+//! The signature block lives in `metadata.signature` and covers the whole
+//! program except the block itself.
+
+use crate::Program;
+use anyhow::{Context, Result, anyhow, bail};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Metadata key holding the signature block
+pub const SIGNATURE_KEY: &str = "signature";
+
+const ALGORITHM: &str = "ed25519";
+
+/// Embedded in `metadata.signature`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignatureBlock {
+    pub algorithm: String,
+    /// Hex-encoded verifying key
+    pub public_key: String,
+    /// Hex-encoded signature
+    pub signature: String,
+}
+
+/// Bytes that get signed: compact JSON with sorted keys and no signature block
+pub fn canonical_bytes(program: &Program) -> Result<Vec<u8>> {
+    let mut unsigned = program.clone();
+    if let Some(metadata) = unsigned.metadata.as_mut() {
+        metadata.remove(SIGNATURE_KEY);
+        if metadata.is_empty() {
+            unsigned.metadata = None;
+        }
+    }
+
+    Ok(unsigned.to_canonical_json()?.into_bytes())
+}
+
+/// Sign the program in place, replacing any existing signature
+pub fn sign(program: &mut Program, key: &SigningKey) -> Result<()> {
+    let signature = key.sign(&canonical_bytes(program)?);
+    let block = SignatureBlock {
+        algorithm: ALGORITHM.to_string(),
+        public_key: to_hex(key.verifying_key().as_bytes()),
+        signature: to_hex(&signature.to_bytes()),
+    };
+
+    program.metadata
+        .get_or_insert_with(Default::default)
+        .insert(SIGNATURE_KEY.to_string(), serde_json::to_value(block)?);
+    Ok(())
+}
+
+/// Check the embedded signature, optionally requiring a specific signer;
+/// returns the verifying key that signed it
+pub fn verify(program: &Program, trusted: Option<&VerifyingKey>) -> Result<VerifyingKey> {
+    let block = program.metadata.as_ref()
+        .and_then(|m| m.get(SIGNATURE_KEY))
+        .ok_or_else(|| anyhow!("Program is not signed"))?;
+    let block: SignatureBlock = serde_json::from_value(block.clone())
+        .context("Malformed signature block")?;
+
+    if block.algorithm != ALGORITHM {
+        bail!("Unsupported signature algorithm: {}", block.algorithm);
+    }
+
+    let public_key = VerifyingKey::from_bytes(&from_hex::<32>(&block.public_key)?)?;
+    if let Some(trusted) = trusted {
+        if trusted != &public_key {
+            bail!("Signed by an untrusted key: {}", block.public_key);
+        }
+    }
+
+    let signature = Signature::from_bytes(&from_hex::<64>(&block.signature)?);
+    public_key.verify(&canonical_bytes(program)?, &signature)
+        .map_err(|_| anyhow!("Signature does not match the program; it was modified after signing"))?;
+
+    Ok(public_key)
+}
+
+/// A fresh random signing key
+pub fn generate_key() -> Result<SigningKey> {
+    let mut seed = [0u8; 32];
+    getrandom::fill(&mut seed).map_err(|e| anyhow!("No randomness available: {}", e))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// Read a hex-encoded signing key
+pub fn read_signing_key(path: &Path) -> Result<SigningKey> {
+    let hex = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    Ok(SigningKey::from_bytes(&from_hex::<32>(hex.trim())?))
+}
+
+/// Read a hex-encoded verifying key
+pub fn read_verifying_key(path: &Path) -> Result<VerifyingKey> {
+    let hex = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    Ok(VerifyingKey::from_bytes(&from_hex::<32>(hex.trim())?)?)
+}
+
+/// Write `key` to `path` and its verifying key to `path.pub`
+pub fn write_key_pair(key: &SigningKey, path: &Path) -> Result<()> {
+    fs::write(path, to_hex(&key.to_bytes()))?;
+    fs::write(path.with_extension("pub"), to_hex(key.verifying_key().as_bytes()))?;
+    Ok(())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Result<[u8; N]> {
+    if hex.len() != N * 2 || !hex.is_ascii() {
+        bail!("Expected {} hex characters, got {}", N * 2, hex.len());
+    }
+
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| anyhow!("Invalid hex: {}", &hex[i * 2..i * 2 + 2]))?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        Program::from_json(r#"{
+            "metadata": {"author": "test"},
+            "actions": [{"actor": "robot", "op": "Heat", "target": "water", "params": {"temperature": "100C"}}]
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify_roundtrip() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut program = program();
+        sign(&mut program, &key).unwrap();
+
+        // Survives a trip through JSON, including key reordering
+        let reparsed = Program::from_json(&program.to_json().unwrap()).unwrap();
+        assert_eq!(verify(&reparsed, None).unwrap(), key.verifying_key());
+        assert!(verify(&reparsed, Some(&key.verifying_key())).is_ok());
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut program = program();
+        sign(&mut program, &key).unwrap();
+
+        program.actions[0].target = "hand".to_string();
+        assert!(verify(&program, None).unwrap_err().to_string().contains("modified"));
+    }
+
+    #[test]
+    fn test_untrusted_key_rejected() {
+        let mut program = program();
+        sign(&mut program, &SigningKey::from_bytes(&[7u8; 32])).unwrap();
+
+        let other = SigningKey::from_bytes(&[8u8; 32]).verifying_key();
+        assert!(verify(&program, Some(&other)).is_err());
+        assert!(verify(&Program::new(), None).is_err());
+    }
+}
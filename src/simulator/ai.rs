@@ -1,9 +1,26 @@
 use crate::{Action, Operation, Program};
+use crate::capabilities;
+use crate::simulator::backend::{CallPolicy, GenerationBackend, GenerationError, Prompt, PromptTemplate, extract_actions};
+use crate::simulator::cache::ResponseCache;
+use crate::simulator::Simulator;
+use crate::simulator::substrate::keep_last;
 use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A Generate action whose backend call ultimately failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationFailure {
+    pub target: String,
+    pub instruction: String,
+    pub error: GenerationError,
+}
 
 /// Represents the state of a Mock LLM
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MockAIState {
     /// Deterministic mapping: instruction keyword → UCL code (as JSON)
     pub knowledge_base: HashMap<String, String>,
@@ -20,6 +37,12 @@ pub struct MockAIState {
     /// Model configuration
     pub model_name: String,
     pub temperature: f64,
+
+    /// Generations answered from the response cache
+    pub cache_hits: usize,
+
+    /// Generations that failed after retries
+    pub failures: Vec<GenerationFailure>,
 }
 
 impl MockAIState {
@@ -181,6 +204,8 @@ impl MockAIState {
             generated_code: HashMap::new(),
             model_name: "MockLLM-UCL-v1".to_string(),
             temperature: 0.0,
+            cache_hits: 0,
+            failures: Vec::new(),
         }
     }
 
@@ -191,6 +216,10 @@ impl MockAIState {
 
         output.push_str(&format!("Model: {} (temperature: {})\n\n", self.model_name, self.temperature));
 
+        if self.cache_hits > 0 {
+            output.push_str(&format!("Cache hits: {}\n\n", self.cache_hits));
+        }
+
         if !self.prompts.is_empty() {
             output.push_str("Prompt History:\n");
             for (i, prompt) in self.prompts.iter().enumerate() {
@@ -199,6 +228,14 @@ impl MockAIState {
             output.push('\n');
         }
 
+        if !self.failures.is_empty() {
+            output.push_str("⚠️  Generation Failures:\n");
+            for failure in &self.failures {
+                output.push_str(&format!("  • {} (\"{}\"): {}\n", failure.target, failure.instruction, failure.error));
+            }
+            output.push('\n');
+        }
+
         if !self.generated_code.is_empty() {
             output.push_str("Generated Code:\n");
             for (name, actions) in &self.generated_code {
@@ -226,6 +263,11 @@ impl Default for MockAIState {
 pub struct MockAISimulator {
     state: MockAIState,
     verbose: bool,
+    backend: Option<Arc<Mutex<Box<dyn GenerationBackend>>>>,
+    prompt_template: PromptTemplate,
+    cache: Option<ResponseCache>,
+    policy: CallPolicy,
+    last_call: Option<Instant>,
 }
 
 impl MockAISimulator {
@@ -233,6 +275,11 @@ impl MockAISimulator {
         Self {
             state: MockAIState::new(),
             verbose: false,
+            backend: None,
+            prompt_template: PromptTemplate::default(),
+            cache: None,
+            policy: CallPolicy::default(),
+            last_call: None,
         }
     }
 
@@ -241,10 +288,60 @@ impl MockAISimulator {
         self
     }
 
+    /// Generate with a real model instead of the built-in knowledge base
+    pub fn with_backend(mut self, backend: Box<dyn GenerationBackend>) -> Self {
+        self.state.model_name = backend.name().to_string();
+        self.backend = Some(Arc::new(Mutex::new(backend)));
+        self
+    }
+
+    /// Configure retries, timeouts and rate limiting for backend calls
+    pub fn with_call_policy(mut self, policy: CallPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Reuse backend completions stored in `cache` (None disables caching)
+    pub fn with_cache(mut self, cache: Option<ResponseCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Customize the prompts sent to a real backend
+    pub fn with_prompt_template(mut self, template: PromptTemplate) -> Self {
+        self.prompt_template = template;
+        self
+    }
+
     pub fn state(&self) -> &MockAIState {
         &self.state
     }
 
+    /// Turn a plain-English instruction into a standalone program, answered
+    /// by the knowledge base or backend exactly as a `Generate` action is
+    pub fn program_from_text(&mut self, instruction: &str, substrate: Option<&str>) -> Result<Program> {
+        let mut params = HashMap::new();
+        params.insert("instruction".to_string(), serde_json::json!(instruction));
+        if let Some(substrate) = substrate {
+            params.insert("substrate".to_string(), serde_json::json!(substrate));
+        }
+        let action = Action::new("user", Operation::Generate, "program").with_params(params);
+
+        let failures = self.state.failures.len();
+        self.generate(&action)?;
+        if let Some(failure) = self.state.failures.get(failures) {
+            return Err(anyhow!("Generation failed: {}", failure.error));
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("description".to_string(), serde_json::json!(instruction));
+        metadata.insert("generated_by".to_string(), serde_json::json!(self.state.model_name));
+        Ok(Program {
+            metadata: Some(metadata),
+            actions: self.state.generated_code.get("program").cloned().unwrap_or_default(),
+        })
+    }
+
     pub fn execute(&mut self, program: &Program) -> Result<()> {
         if self.verbose {
             println!("🤖 Starting Mock AI execution...\n");
@@ -295,6 +392,29 @@ impl MockAISimulator {
             println!("  💭 Received instruction: \"{}\"", instruction);
         }
 
+        // Optional target substrate: generated code is restricted to what it can run
+        let substrate = action.params
+            .as_ref()
+            .and_then(|p| p.get("substrate"))
+            .and_then(|v| v.as_str());
+
+        if let Some(substrate) = substrate {
+            if capabilities::substrate_operations(substrate).is_none() {
+                return Err(anyhow!(
+                    "Unknown substrate: {} (expected one of {})",
+                    substrate,
+                    capabilities::SUBSTRATES.join(", ")
+                ));
+            }
+            if self.verbose {
+                println!("  🎯 Targeting substrate: {}", substrate);
+            }
+        }
+
+        if self.backend.is_some() {
+            return self.generate_with_backend(action, instruction, substrate);
+        }
+
         // Look up in knowledge base (fuzzy match on keywords)
         let mut matched_key = None;
         for key in self.state.knowledge_base.keys() {
@@ -314,6 +434,7 @@ impl MockAISimulator {
 
             // Parse the JSON into actions
             let actions: Vec<Action> = serde_json::from_str(code_json)?;
+            let actions = self.adapt_to_substrate(actions, substrate).map_err(|e| anyhow!(e))?;
 
             self.state.generated_code.insert(action.target.clone(), actions.clone());
             self.state.responses.push(format!("Generated {} for: {}", key, instruction));
@@ -337,6 +458,182 @@ impl MockAISimulator {
         generated_code
     }
 
+    /// Remove operations the target substrate can't execute
+    ///
+    /// Fails when nothing usable is left, so an unrunnable program is never stored.
+    fn adapt_to_substrate(&mut self, actions: Vec<Action>, substrate: Option<&str>) -> std::result::Result<Vec<Action>, String> {
+        let Some(substrate) = substrate else {
+            return Ok(actions);
+        };
+
+        let (kept, dropped) = capabilities::retain_supported(substrate, actions);
+
+        if !dropped.is_empty() {
+            let names: Vec<String> = dropped.iter().map(|op| format!("{:?}", op)).collect();
+            let note = format!("Dropped operations unsupported by {}: {}", substrate, names.join(", "));
+            if self.verbose {
+                println!("  ✂️  {}", note);
+            }
+            self.state.responses.push(note);
+        }
+
+        if kept.is_empty() {
+            return Err(format!("no generated operations are supported by {}", substrate));
+        }
+
+        Ok(kept)
+    }
+
+    fn generate_with_backend(&mut self, action: &Action, instruction: &str, substrate: Option<&str>) -> Result<()> {
+        let prompt = self.prompt_template.render(instruction, substrate);
+
+        let result = self.query_backend(instruction, &prompt)
+            .and_then(|completion| {
+                extract_actions(&completion)
+                    .map_err(|e| GenerationError::InvalidResponse { message: e.to_string() })
+            })
+            .and_then(|actions| {
+                self.adapt_to_substrate(actions, substrate)
+                    .map_err(|message| GenerationError::InvalidResponse { message })
+            });
+
+        let actions = match result {
+            Ok(actions) => actions,
+            Err(error) => {
+                // Record the failure and keep running; a later Execute of this
+                // target will report the missing code.
+                self.state.responses.push(format!("Failed to generate for {}: {}", instruction, error));
+                if self.verbose {
+                    println!("  ❌ Generation failed: {}", error);
+                }
+                self.state.failures.push(GenerationFailure {
+                    target: action.target.clone(),
+                    instruction: instruction.to_string(),
+                    error,
+                });
+                return Ok(());
+            }
+        };
+
+        self.state.generated_code.insert(action.target.clone(), actions.clone());
+        self.state.responses.push(format!("Generated {} actions for: {}", actions.len(), instruction));
+
+        if self.verbose {
+            println!("  ✅ Generated {} UCL actions", actions.len());
+        }
+
+        Ok(())
+    }
+
+    /// Ask the backend for a completion, consulting the response cache first
+    fn query_backend(&mut self, instruction: &str, prompt: &Prompt) -> std::result::Result<String, GenerationError> {
+        let model = self.state.model_name.clone();
+        let temperature = self.state.temperature;
+        let text = prompt.to_text();
+
+        if let Some(cache) = &self.cache {
+            if let Some(completion) = cache.get(instruction, &model, temperature, &text) {
+                self.state.cache_hits += 1;
+                if self.verbose {
+                    println!("  📦 Using cached response from {}", model);
+                }
+                return Ok(completion);
+            }
+        }
+
+        if self.verbose {
+            println!("  ✨ Querying {}...", model);
+        }
+
+        let completion = self.call_with_retries(prompt)?;
+
+        if let Some(cache) = &self.cache {
+            // A cache write failure shouldn't lose a successful generation
+            if let Err(e) = cache.put(instruction, &model, temperature, &text, &completion) {
+                if self.verbose {
+                    println!("  ⚠️  Could not cache response: {}", e);
+                }
+            }
+        }
+
+        Ok(completion)
+    }
+
+    fn call_with_retries(&mut self, prompt: &Prompt) -> std::result::Result<String, GenerationError> {
+        let attempts = self.policy.max_retries + 1;
+        let mut backoff = self.policy.initial_backoff;
+        let mut last_error = None;
+
+        for attempt in 1..=attempts {
+            self.wait_for_rate_limit();
+
+            match self.call_once(prompt) {
+                Ok(completion) => return Ok(completion),
+                Err(error) => {
+                    if self.verbose {
+                        println!("  ⚠️  Attempt {}/{} failed: {}", attempt, attempts, error);
+                    }
+                    last_error = Some(error);
+
+                    if attempt < attempts {
+                        std::thread::sleep(backoff);
+                        backoff = backoff.mul_f64(self.policy.backoff_multiplier);
+                    }
+                }
+            }
+        }
+
+        let last = last_error.expect("at least one attempt is made");
+        if attempts == 1 {
+            Err(last)
+        } else {
+            Err(GenerationError::RetriesExhausted { attempts, last: Box::new(last) })
+        }
+    }
+
+    /// One backend call, run on a worker thread when a timeout is configured
+    ///
+    /// A timed-out call keeps running in the background and holds the backend
+    /// lock until it finishes; the next call simply waits for it, still bounded
+    /// by its own timeout.
+    fn call_once(&mut self, prompt: &Prompt) -> std::result::Result<String, GenerationError> {
+        let backend = Arc::clone(self.backend.as_ref().expect("backend checked by caller"));
+        let temperature = self.state.temperature;
+        self.last_call = Some(Instant::now());
+
+        let Some(timeout) = self.policy.timeout else {
+            let mut backend = backend.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            return backend.complete(prompt, temperature)
+                .map_err(|e| GenerationError::Backend { message: e.to_string() });
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let prompt = prompt.clone();
+        std::thread::spawn(move || {
+            let mut backend = backend.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let result = backend.complete(&prompt, temperature).map_err(|e| e.to_string());
+            let _ = sender.send(result);
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(Ok(completion)) => Ok(completion),
+            Ok(Err(message)) => Err(GenerationError::Backend { message }),
+            Err(_) => Err(GenerationError::Timeout { after: timeout }),
+        }
+    }
+
+    fn wait_for_rate_limit(&self) {
+        if let (Some(interval), Some(last)) = (self.policy.min_interval(), self.last_call) {
+            let elapsed = last.elapsed();
+            if elapsed < interval {
+                if self.verbose {
+                    println!("  ⏳ Rate limited, waiting {:.1}s", (interval - elapsed).as_secs_f64());
+                }
+                std::thread::sleep(interval - elapsed);
+            }
+        }
+    }
+
     fn parse(&mut self, action: &Action) -> Result<()> {
         if self.verbose {
             println!("  📝 Parsing code from {}", action.target);
@@ -390,3 +687,225 @@ impl Default for MockAISimulator {
     }
 }
 
+impl Simulator for MockAISimulator {
+    fn name(&self) -> &'static str {
+        "ai"
+    }
+
+    fn execute(&mut self, program: &Program) -> Result<()> {
+        MockAISimulator::execute(self, program)
+    }
+
+    fn snapshot(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(&self.state)?)
+    }
+
+    fn restore(&mut self, snapshot: serde_json::Value) -> Result<()> {
+        self.state = serde_json::from_value(snapshot)?;
+        Ok(())
+    }
+
+    fn value(&self, name: &str) -> Option<serde_json::Value> {
+        serde_json::to_value(self.state.generated_code.get(name)?).ok()
+    }
+
+    fn output(&self) -> &[String] {
+        &self.state.responses
+    }
+
+    fn reset(&mut self) {
+        // The model and temperature are configuration, not history
+        let fresh = MockAIState { model_name: self.state.model_name.clone(), temperature: self.state.temperature, ..MockAIState::new() };
+        self.state = fresh;
+        self.last_call = None;
+    }
+
+    fn trim_history(&mut self, limit: usize) {
+        keep_last(&mut self.state.prompts, limit);
+        keep_last(&mut self.state.responses, limit);
+        keep_last(&mut self.state.failures, limit);
+    }
+
+    fn display(&self) -> String {
+        self.state.display()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Backend that fails `failures` times, then returns a fixed program,
+    /// counting every call
+    struct CountingBackend {
+        calls: Arc<AtomicUsize>,
+        failures: usize,
+    }
+
+    impl GenerationBackend for CountingBackend {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn complete(&mut self, _prompt: &Prompt, _temperature: f64) -> Result<String> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call < self.failures {
+                return Err(anyhow!("service unavailable"));
+            }
+            Ok(r#"[{"actor": "VM", "op": "Emit", "target": "out"}]"#.to_string())
+        }
+    }
+
+    fn fast_policy(max_retries: usize) -> CallPolicy {
+        CallPolicy {
+            max_retries,
+            initial_backoff: Duration::ZERO,
+            ..CallPolicy::default()
+        }
+    }
+
+    fn generate_program() -> Program {
+        Program::from_json(r#"{"actions": [
+            {"actor": "ai", "op": "Generate", "target": "code", "params": {"instruction": "say something"}}
+        ]}"#).unwrap()
+    }
+
+    #[test]
+    fn test_program_from_text() {
+        let mut simulator = MockAISimulator::new();
+        let program = simulator.program_from_text("Please write a Fibonacci function", None).unwrap();
+        assert_eq!(program.actions[0].op, Operation::DefineFunction);
+        assert_eq!(program.meta().get("description").unwrap(), "Please write a Fibonacci function");
+
+        assert!(simulator.program_from_text("bake a cake", None).is_err());
+
+        let backend = CountingBackend { calls: Arc::new(AtomicUsize::new(0)), failures: 1 };
+        let mut simulator = MockAISimulator::new().with_backend(Box::new(backend)).with_call_policy(fast_policy(0));
+        let error = simulator.program_from_text("anything", None).unwrap_err();
+        assert!(error.to_string().starts_with("Generation failed"), "{}", error);
+    }
+
+    #[test]
+    fn test_cache_avoids_second_query() {
+        let dir = std::env::temp_dir().join(format!("ucl-ai-cache-test-{}", std::process::id()));
+        let cache = ResponseCache::new(&dir);
+        cache.clear().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..2 {
+            let mut ai = MockAISimulator::new()
+                .with_backend(Box::new(CountingBackend { calls: calls.clone(), failures: 0 }))
+                .with_cache(Some(cache.clone()));
+            ai.execute(&generate_program()).unwrap();
+            assert_eq!(ai.state().generated_code["code"].len(), 1);
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.clear().unwrap(), 1);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_cache_misses_on_a_different_request_under_the_same_key() {
+        let dir = std::env::temp_dir().join(format!("ucl-ai-cache-collision-test-{}", std::process::id()));
+        let cache = ResponseCache::new(&dir);
+        cache.put("sort a list", "model", 0.2, "prompt one", "sorted").unwrap();
+        assert_eq!(cache.get("sort a list", "model", 0.2, "prompt one"), Some("sorted".to_string()));
+
+        // Stand in for a hash collision: the entry for one request holding another's
+        let key = ResponseCache::key("sort a list", "model", 0.2, "prompt one");
+        let path = dir.join(format!("{}.json", key));
+        let entry = std::fs::read_to_string(&path).unwrap().replace("prompt one", "prompt two");
+        std::fs::write(&path, entry).unwrap();
+        assert_eq!(cache.get("sort a list", "model", 0.2, "prompt one"), None);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_retry_recovers_from_transient_failure() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut ai = MockAISimulator::new()
+            .with_backend(Box::new(CountingBackend { calls: calls.clone(), failures: 2 }))
+            .with_call_policy(fast_policy(2));
+
+        ai.execute(&generate_program()).unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(ai.state().generated_code.contains_key("code"));
+        assert!(ai.state().failures.is_empty());
+    }
+
+    #[test]
+    fn test_exhausted_retries_recorded_not_fatal() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut ai = MockAISimulator::new()
+            .with_backend(Box::new(CountingBackend { calls, failures: 10 }))
+            .with_call_policy(fast_policy(1));
+
+        ai.execute(&generate_program()).unwrap();
+
+        assert_eq!(ai.state().failures.len(), 1);
+        assert!(matches!(
+            ai.state().failures[0].error,
+            GenerationError::RetriesExhausted { attempts: 2, .. }
+        ));
+    }
+
+    /// Backend that always answers with the same completion
+    struct FixedBackend(&'static str);
+
+    impl GenerationBackend for FixedBackend {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn complete(&mut self, _prompt: &Prompt, _temperature: f64) -> Result<String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    fn generate_for(substrate: &str) -> Program {
+        let json = format!(
+            r#"{{"actions": [{{"actor": "ai", "op": "Generate", "target": "code",
+                "params": {{"instruction": "make tea", "substrate": "{}"}}}}]}}"#,
+            substrate
+        );
+        Program::from_json(&json).unwrap()
+    }
+
+    #[test]
+    fn test_substrate_filters_generated_code() {
+        let completion = r#"[
+            {"actor": "VM", "op": "Heat", "target": "water"},
+            {"actor": "VM", "op": "Emit", "target": "out"}
+        ]"#;
+        let mut ai = MockAISimulator::new().with_backend(Box::new(FixedBackend(completion)));
+
+        ai.execute(&generate_for("ruby")).unwrap();
+
+        let code = &ai.state().generated_code["code"];
+        assert_eq!(code.len(), 1);
+        assert_eq!(code[0].op, Operation::Emit);
+    }
+
+    #[test]
+    fn test_substrate_rejects_unrunnable_code() {
+        let completion = r#"[{"actor": "VM", "op": "Heat", "target": "water"}]"#;
+        let mut ai = MockAISimulator::new().with_backend(Box::new(FixedBackend(completion)));
+
+        ai.execute(&generate_for("ruby")).unwrap();
+
+        assert!(!ai.state().generated_code.contains_key("code"));
+        assert!(matches!(ai.state().failures[0].error, GenerationError::InvalidResponse { .. }));
+    }
+
+    #[test]
+    fn test_unknown_substrate_is_an_error() {
+        let mut ai = MockAISimulator::new();
+        assert!(ai.execute(&generate_for("toaster")).is_err());
+    }
+}
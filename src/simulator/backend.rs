@@ -0,0 +1,225 @@
+use crate::{Action, Program, capabilities};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// A text-generation engine that can stand in for the built-in knowledge base
+///
+/// Backends receive a fully rendered prompt and return the raw completion;
+/// the AI simulator is responsible for turning the completion into actions.
+pub trait GenerationBackend: Send {
+    /// Model identifier reported in the AI state
+    fn name(&self) -> &str;
+
+    /// Produce a completion for `prompt`
+    fn complete(&mut self, prompt: &Prompt, temperature: f64) -> Result<String>;
+}
+
+/// A rendered prompt, split the way chat models expect it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt {
+    pub system: String,
+    pub user: String,
+}
+
+impl Prompt {
+    /// Single-string form for completion-style models
+    pub fn to_text(&self) -> String {
+        format!("{}\n\n{}", self.system, self.user)
+    }
+}
+
+/// System/user prompt templates used when querying a real backend
+///
+/// Templates may contain `{instruction}`, `{substrate}` and `{capabilities}`
+/// placeholders. `{capabilities}` expands to the operations the target
+/// substrate supports, so a template can steer generation towards what will
+/// actually run there.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    #[serde(default = "default_system_template")]
+    pub system: String,
+
+    #[serde(default = "default_user_template")]
+    pub user: String,
+
+    /// Substrate whose capabilities are injected when none is requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub substrate: Option<String>,
+}
+
+fn default_system_template() -> String {
+    "You translate instructions into Universal Causal Language (UCL).\n\
+     A UCL program is a JSON array of actions. Each action has an \"actor\", \
+     an \"op\", a \"target\" and optional \"params\".\n\
+     Use only these operations: {capabilities}.\n\
+     Respond with the JSON array only.".to_string()
+}
+
+fn default_user_template() -> String {
+    "Instruction: {instruction}\nUCL:".to_string()
+}
+
+/// Operations offered to the model when no substrate is targeted
+const GENERAL_CAPABILITIES: &str = "Bind, Emit, Write, Read, If, While, For, DefineFunction, Return, Call";
+
+impl PromptTemplate {
+    /// Load templates from a JSON file with `system`, `user` and optional `substrate` keys
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read prompt config {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn render(&self, instruction: &str, substrate: Option<&str>) -> Prompt {
+        let substrate = substrate.or(self.substrate.as_deref());
+        let capabilities = substrate
+            .and_then(capabilities::describe)
+            .unwrap_or_else(|| GENERAL_CAPABILITIES.to_string());
+
+        let fill = |template: &str| {
+            template
+                .replace("{instruction}", instruction)
+                .replace("{substrate}", substrate.unwrap_or("any"))
+                .replace("{capabilities}", &capabilities)
+        };
+
+        Prompt {
+            system: fill(&self.system),
+            user: fill(&self.user),
+        }
+    }
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self {
+            system: default_system_template(),
+            user: default_user_template(),
+            substrate: None,
+        }
+    }
+}
+
+/// How backend calls are retried, timed out and rate limited
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallPolicy {
+    /// Additional attempts after the first failure
+    pub max_retries: usize,
+
+    /// Delay before the first retry; multiplied by `backoff_multiplier` after each one
+    pub initial_backoff: Duration,
+    pub backoff_multiplier: f64,
+
+    /// Abandon a single call after this long
+    pub timeout: Option<Duration>,
+
+    /// Upper bound on calls per minute across the whole run
+    pub requests_per_minute: Option<u32>,
+}
+
+impl CallPolicy {
+    /// Minimum spacing between calls implied by the rate limit
+    pub fn min_interval(&self) -> Option<Duration> {
+        self.requests_per_minute
+            .filter(|rpm| *rpm > 0)
+            .map(|rpm| Duration::from_secs_f64(60.0 / rpm as f64))
+    }
+}
+
+impl Default for CallPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(500),
+            backoff_multiplier: 2.0,
+            timeout: Some(Duration::from_secs(120)),
+            requests_per_minute: None,
+        }
+    }
+}
+
+/// Why a generation failed, recorded in the AI state instead of aborting the run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GenerationError {
+    /// A single call exceeded the policy timeout
+    Timeout { after: Duration },
+
+    /// The backend itself returned an error
+    Backend { message: String },
+
+    /// The backend answered, but not with usable UCL
+    InvalidResponse { message: String },
+
+    /// Every attempt failed; `last` is the final failure
+    RetriesExhausted { attempts: usize, last: Box<GenerationError> },
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerationError::Timeout { after } => write!(f, "timed out after {:.1}s", after.as_secs_f64()),
+            GenerationError::Backend { message } => write!(f, "backend error: {}", message),
+            GenerationError::InvalidResponse { message } => write!(f, "invalid response: {}", message),
+            GenerationError::RetriesExhausted { attempts, last } => {
+                write!(f, "gave up after {} attempts ({})", attempts, last)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+/// Pull UCL actions out of a model completion
+///
+/// Accepts either a bare JSON array of actions or a full program object, and
+/// tolerates surrounding prose or code fences.
+pub fn extract_actions(text: &str) -> Result<Vec<Action>> {
+    if let (Some(start), Some(end)) = (text.find('{'), text.rfind('}')) {
+        if let Ok(program) = serde_json::from_str::<Program>(&text[start..=end]) {
+            return Ok(program.actions);
+        }
+    }
+
+    let start = text.find('[')
+        .ok_or_else(|| anyhow!("Model response contains no UCL actions"))?;
+    let end = text.rfind(']')
+        .ok_or_else(|| anyhow!("Model response contains no UCL actions"))?;
+
+    serde_json::from_str(&text[start..=end])
+        .map_err(|e| anyhow!("Model response is not valid UCL: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_actions_from_prose() {
+        let text = "Sure! Here is the program:\n```json\n[{\"actor\": \"VM\", \"op\": \"Emit\", \"target\": \"out\"}]\n```";
+        let actions = extract_actions(text).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].target, "out");
+    }
+
+    #[test]
+    fn test_template_injects_capabilities() {
+        let template = PromptTemplate {
+            system: "Target {substrate}: {capabilities}".to_string(),
+            user: "Do: {instruction}".to_string(),
+            substrate: None,
+        };
+
+        let prompt = template.render("boil water", Some("robot"));
+        assert!(prompt.system.starts_with("Target robot: "));
+        assert!(prompt.system.contains("Heat"));
+        assert_eq!(prompt.user, "Do: boil water");
+    }
+
+    #[test]
+    fn test_extract_actions_rejects_garbage() {
+        assert!(extract_actions("I cannot help with that").is_err());
+    }
+}
@@ -1,20 +1,36 @@
-use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression};
-use anyhow::{Result, anyhow};
+use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression, template};
+use crate::rng::SeededRng;
+use crate::simulator::{Channels, CognitiveProfile, Forgetting, HostFn, KnowledgeGraph, Memory, Simulator};
+use crate::simulator::memory::Trace;
+use crate::simulator::control::{DEFAULT_MAX_RECURSION_DEPTH, Signal, Trigger, caught, ends_loop, nested, same_value};
+use crate::simulator::{sleep, values};
+use crate::simulator::provenance::{ActionPath, Provenance, WriteLog, origin_suffix};
+use crate::simulator::substrate::keep_last;
+use anyhow::{Context, Result, anyhow};
+use crate::error::UclError;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Represents a learned function (skill) in the brain
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionDef {
     pub args: Vec<String>,
     pub body: Vec<crate::Action>,
 }
 
 /// Represents the state of a simulated human brain
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BrainState {
     /// Facts and beliefs stored in memory
     pub beliefs: HashMap<String, serde_json::Value>,
 
+    /// Facts `StoreFact` recorded, as subject–predicate–object triples
+    #[serde(default)]
+    pub knowledge: KnowledgeGraph,
+
     /// Emotional state
     pub emotions: HashMap<String, f64>,
 
@@ -38,12 +54,34 @@ pub struct BrainState {
 
     /// Learned functions (skills/procedures)
     pub functions: HashMap<String, FunctionDef>,
+
+    /// Emitted values no other actor has received yet
+    #[serde(default)]
+    pub channels: Channels,
+
+    /// Status the program ended with, if a `Halt` stopped it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub halted: Option<i64>,
+
+    /// How well beliefs and working memory items are remembered, when the
+    /// simulator forgets
+    #[serde(default)]
+    pub memory: Memory,
+
+    /// Which action produced each belief and output line
+    #[serde(default)]
+    pub provenance: Provenance,
+
+    /// Other agents' minds as this brain imagines them, by name
+    #[serde(default)]
+    pub minds: HashMap<String, BrainState>,
 }
 
 impl BrainState {
     pub fn new() -> Self {
         Self {
             beliefs: HashMap::new(),
+            knowledge: KnowledgeGraph::default(),
             emotions: HashMap::new(),
             working_memory: Vec::new(),
             attention: None,
@@ -52,18 +90,51 @@ impl BrainState {
             goals: Vec::new(),
             trace: Vec::new(),
             functions: HashMap::new(),
+            channels: Channels::default(),
+            halted: None,
+            memory: Memory::default(),
+            provenance: Provenance::default(),
+            minds: HashMap::new(),
         }
     }
 
+    /// Forget what only means something within one run: output, thoughts,
+    /// the trace, working memory, attention, undelivered messages, the halt
+    /// status and provenance. Beliefs, knowledge, emotions, goals, learned
+    /// functions, memory strengths and imagined minds stay.
+    pub fn end_run(&mut self) {
+        self.working_memory.clear();
+        self.memory.working.clear();
+        self.attention = None;
+        self.output.clear();
+        self.thoughts.clear();
+        self.trace.clear();
+        self.channels = Channels::default();
+        self.halted = None;
+        self.provenance = Provenance::default();
+    }
+
     pub fn display(&self) -> String {
         let mut output = String::new();
 
         output.push_str("=== Brain State ===\n\n");
 
+        if let Some(status) = self.halted {
+            output.push_str(&format!("Halted with status {}\n\n", status));
+        }
+
         if !self.beliefs.is_empty() {
             output.push_str("Beliefs:\n");
             for (key, value) in &self.beliefs {
-                output.push_str(&format!("  {} = {}\n", key, value));
+                output.push_str(&format!("  {} = {}{}\n", key, value, origin_suffix(self.provenance.value(key))));
+            }
+            output.push('\n');
+        }
+
+        if !self.knowledge.is_empty() {
+            output.push_str("Knowledge:\n");
+            for triple in self.knowledge.iter() {
+                output.push_str(&format!("  {}\n", triple.sentence()));
             }
             output.push('\n');
         }
@@ -84,6 +155,17 @@ impl BrainState {
             output.push('\n');
         }
 
+        if !self.memory.beliefs.is_empty() {
+            output.push_str(&format!("Memory Strength (t={:.1}s):\n", self.memory.clock));
+            let mut names: Vec<&String> = self.memory.beliefs.keys().collect();
+            names.sort();
+            for name in names {
+                output.push_str(&format!("  {}: {:.2} (last used t={:.1}s)\n",
+                    name, self.memory.beliefs[name].strength, self.memory.beliefs[name].last_used));
+            }
+            output.push('\n');
+        }
+
         if let Some(focus) = &self.attention {
             output.push_str(&format!("Current Focus: {}\n\n", focus));
         }
@@ -104,10 +186,31 @@ impl BrainState {
             output.push('\n');
         }
 
+        if !self.channels.is_empty() {
+            output.push_str("Unreceived Messages:\n");
+            for (channel, count) in self.channels.pending() {
+                output.push_str(&format!("  {}: {}\n", channel, count));
+            }
+            output.push('\n');
+        }
+
+        if !self.minds.is_empty() {
+            output.push_str("Other Minds:\n");
+            let mut names: Vec<&String> = self.minds.keys().collect();
+            names.sort();
+            for name in names {
+                let mut beliefs: Vec<(&String, &serde_json::Value)> = self.minds[name].beliefs.iter().collect();
+                beliefs.sort_by(|a, b| a.0.cmp(b.0));
+                let beliefs: Vec<String> = beliefs.iter().map(|(key, value)| format!("{} = {}", key, value)).collect();
+                output.push_str(&format!("  {} believes: {}\n", name, beliefs.join(", ")));
+            }
+            output.push('\n');
+        }
+
         if !self.output.is_empty() {
             output.push_str("Output/Speech:\n");
-            for text in &self.output {
-                output.push_str(&format!("  🗣️  {}\n", text));
+            for (i, text) in self.output.iter().enumerate() {
+                output.push_str(&format!("  🗣️  {}{}\n", text, origin_suffix(self.provenance.output(i))));
             }
             output.push('\n');
         }
@@ -122,12 +225,37 @@ impl Default for BrainState {
     }
 }
 
+/// Runs a custom operation (`Meditate`, or a namespaced `music:Arpeggiate`)
+/// against the state
+pub type NamespaceHandler = Box<dyn FnMut(&Action, &mut BrainState) -> Result<()> + Send>;
+
 /// Simulates a human brain as a VM that executes language programs
 pub struct BrainSimulator {
     state: BrainState,
     verbose: bool,
     recursion_depth: usize,
     max_recursion_depth: usize,
+    interrupt: Option<Arc<AtomicBool>>,
+    interrupt_handlers: Vec<Vec<Action>>,
+    triggers: Vec<Trigger>,
+    /// A trigger's handler is running, so actions in it don't check triggers
+    firing: bool,
+    namespaces: HashMap<String, NamespaceHandler>,
+    handlers: HashMap<String, NamespaceHandler>,
+    host_fns: HashMap<String, HostFn>,
+    path: ActionPath,
+    writes: WriteLog,
+    /// Locals of the function calls under way, innermost last
+    frames: Vec<HashMap<String, serde_json::Value>>,
+    /// How beliefs fade, if they do
+    forgetting: Option<Forgetting>,
+    profile: CognitiveProfile,
+    /// Actions since attention last moved
+    attended: usize,
+    /// Loops the running action is inside, within the current function
+    loop_depth: usize,
+    signal: Option<Signal>,
+    rng: SeededRng,
 }
 
 impl BrainSimulator {
@@ -136,7 +264,23 @@ impl BrainSimulator {
             state: BrainState::new(),
             verbose: false,
             recursion_depth: 0,
-            max_recursion_depth: 1000,
+            max_recursion_depth: DEFAULT_MAX_RECURSION_DEPTH,
+            interrupt: None,
+            interrupt_handlers: Vec::new(),
+            triggers: Vec::new(),
+            firing: false,
+            namespaces: HashMap::new(),
+            handlers: HashMap::new(),
+            host_fns: HashMap::new(),
+            path: ActionPath::default(),
+            writes: WriteLog::default(),
+            frames: Vec::new(),
+            forgetting: None,
+            profile: CognitiveProfile::default(),
+            attended: 0,
+            loop_depth: 0,
+            signal: None,
+            rng: SeededRng::from_entropy(),
         }
     }
 
@@ -145,35 +289,398 @@ impl BrainSimulator {
         self
     }
 
+    /// Seed the random source `GenRandomInt` draws from, so runs repeat exactly
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SeededRng::new(seed);
+        self
+    }
+
+    /// How deeply blocks and function calls may nest before the run fails
+    /// with a recursion error; 10,000 by default
+    pub fn with_max_recursion_depth(mut self, depth: usize) -> Self {
+        self.max_recursion_depth = depth;
+        self
+    }
+
+    /// Forget beliefs that go unused, and keep working memory to its most
+    /// salient items, as `model` describes (see [`memory`](crate::simulator::memory))
+    pub fn with_forgetting(mut self, model: Forgetting) -> Self {
+        self.forgetting = Some(model);
+        self
+    }
+
+    /// Limit working memory, attention and arithmetic as `profile` says;
+    /// [`CognitiveProfile::focused`] by default
+    pub fn with_profile(mut self, profile: CognitiveProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Stop at the next action once `flag` is set (e.g. by a Ctrl-C handler),
+    /// running any OnInterrupt handlers first
+    pub fn with_interrupt(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.interrupt = Some(flag);
+        self
+    }
+
+    /// Route every `Custom("<namespace>:...")` operation to `handler`
+    pub fn register_namespace(
+        &mut self,
+        namespace: &str,
+        handler: impl FnMut(&Action, &mut BrainState) -> Result<()> + Send + 'static,
+    ) {
+        self.namespaces.insert(namespace.to_string(), Box::new(handler));
+    }
+
+    /// Route `Custom("<name>")` operations to `handler` instead of treating
+    /// them as unknown; takes precedence over a namespace handler
+    pub fn register(
+        &mut self,
+        name: &str,
+        handler: impl FnMut(&Action, &mut BrainState) -> Result<()> + Send + 'static,
+    ) {
+        self.handlers.insert(name.to_string(), Box::new(handler));
+    }
+
+    /// Run `function` for `Call` actions targeting `name` and for `{"call":
+    /// name}` expressions, passing the evaluated arguments by name; a
+    /// function the program defines itself takes precedence
+    ///
+    /// A `Call`'s params are its arguments, except `out`, which names the
+    /// belief to store the result in.
+    pub fn bind_fn(
+        &mut self,
+        name: &str,
+        function: impl FnMut(&serde_json::Map<String, serde_json::Value>) -> Result<serde_json::Value> + Send + 'static,
+    ) {
+        self.host_fns.insert(name.to_string(), Box::new(function));
+    }
+
     pub fn state(&self) -> &BrainState {
         &self.state
     }
 
+    /// Answer a question about the state such as `belief cat.color` or
+    /// `why total` (see [`BrainState::query`])
+    pub fn query(&self, question: &str) -> Result<serde_json::Value> {
+        self.state.query(question)
+    }
+
+    /// The state, for hosts that feed a brain from outside, such as
+    /// delivering another brain's messages
+    pub fn state_mut(&mut self) -> &mut BrainState {
+        &mut self.state
+    }
+
+    /// Replace the whole state, e.g. to roll back to an earlier snapshot
+    pub fn restore_state(&mut self, state: BrainState) {
+        self.state = state;
+    }
+
+    /// Write what the brain learned to `path` as JSON, for
+    /// [`load_state`](Self::load_state) to pick up in a later run; what
+    /// belongs to this run alone (see [`BrainState::end_run`]) is left out
+    pub fn save_state(&self, path: &Path) -> Result<()> {
+        let mut state = self.state.clone();
+        state.end_run();
+        let json = serde_json::to_string_pretty(&state)?;
+        std::fs::write(path, json).with_context(|| format!("Writing {}", path.display()))
+    }
+
+    /// Replace the state with one [`save_state`](Self::save_state) wrote, so
+    /// beliefs, learned functions and the rest carry over between runs
+    pub fn load_state(&mut self, path: &Path) -> Result<()> {
+        let json = std::fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        let mut state: BrainState = serde_json::from_str(&json).with_context(|| format!("Invalid brain state in {}", path.display()))?;
+        // Files written before save_state left these out still have them
+        state.end_run();
+        self.state = state;
+        Ok(())
+    }
+
     pub fn execute(&mut self, program: &Program) -> Result<()> {
+        self.state.halted = None;
+        self.path.clear();
+        self.writes.clear();
+        self.frames.clear();
+        self.loop_depth = 0;
+        self.signal = None;
+        let result = self.execute_groups(program);
+        match &result {
+            Err(e) => self.run_interrupt_handlers(e),
+            // Handlers only cover the run that registered them
+            Ok(()) => self.interrupt_handlers.clear(),
+        }
+        result
+    }
+
+    fn execute_groups(&mut self, program: &Program) -> Result<()> {
         if self.verbose {
             println!("🧠 Starting brain simulation...\n");
         }
 
-        for (i, action) in program.actions.iter().enumerate() {
+        for group in program.simultaneous_groups() {
+            if group.len() > 1 {
+                self.execute_simultaneous(&program.actions, group)?;
+                if self.signal.take().is_some() {
+                    break;
+                }
+                continue;
+            }
+
+            let (i, action) = (group.start, &program.actions[group.start]);
             if self.verbose {
                 println!("Step {}: {:?} - {} → {}",
                     i + 1, action.op, action.actor, action.target);
             }
 
-            self.execute_action(action)?;
+            self.path.push(i.to_string());
+            let result = self.execute_action(action);
+            self.path.pop();
+            result?;
 
             if self.verbose {
                 println!();
             }
+            // A Return outside any function, or a Halt, ends the program
+            if self.signal.take().is_some() {
+                break;
+            }
         }
 
         Ok(())
     }
 
+    /// Run registered OnInterrupt handlers, most recent first, after `error`
+    /// stopped the program
+    fn run_interrupt_handlers(&mut self, error: &anyhow::Error) {
+        let handlers = std::mem::take(&mut self.interrupt_handlers);
+        if handlers.is_empty() {
+            return;
+        }
+
+        if self.verbose {
+            println!("  🚨 {}: running {} interrupt handler(s)", error, handlers.len());
+        }
+        self.state.trace.push(format!("OnInterrupt({})", error));
+        self.recursion_depth = 0;
+        self.path.clear();
+        self.frames.clear();
+        self.loop_depth = 0;
+        self.signal = None;
+
+        for (k, handler) in handlers.iter().enumerate().rev() {
+            for (j, action) in handler.iter().enumerate() {
+                self.path.push(format!("interrupt[{}].body[{}]", k, j));
+                let result = self.execute_action(action);
+                self.path.pop();
+                if let Err(e) = result {
+                    self.state.trace.push(format!("OnInterrupt handler failed: {}", e));
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Run actions that share a timestamp as one atomic step: if any fails the
+    /// state is rolled back to before the group, and their trace entries are
+    /// merged into one
+    fn execute_simultaneous(&mut self, actions: &[Action], group: std::ops::Range<usize>) -> Result<()> {
+        let t = actions[group.start].t.unwrap_or_default();
+        if self.verbose {
+            println!("Steps {}-{} (simultaneous at t={}):", group.start + 1, group.end, t);
+            for action in &actions[group.clone()] {
+                println!("  {:?} - {} → {}", action.op, action.actor, action.target);
+            }
+        }
+
+        let before = self.state.clone();
+        let handlers = self.interrupt_handlers.len();
+        for (i, action) in actions.iter().enumerate().take(group.end).skip(group.start) {
+            self.path.push(i.to_string());
+            let result = self.execute_action(action);
+            self.path.pop();
+            if let Err(e) = result {
+                self.state = before;
+                self.interrupt_handlers.truncate(handlers);
+                return Err(e.context(format!("in simultaneous group at t={}", t)));
+            }
+        }
+
+        let entries: Vec<String> = self.state.trace.drain(before.trace.len()..).collect();
+        self.state.trace.push(format!("t={} [{}]", t, entries.join(" ‖ ")));
+
+        if self.verbose {
+            println!();
+        }
+        Ok(())
+    }
+
     fn execute_action(&mut self, action: &Action) -> Result<()> {
+        nested(|| self.execute_nested(action))
+    }
+
+    fn execute_nested(&mut self, action: &Action) -> Result<()> {
+        let mark = self.writes.begin();
+        let focus = self.state.attention.clone();
+        let result = self.dispatch_action(action);
+        self.drift_attention(focus);
+        if let Some(model) = self.forgetting {
+            let before = self.writes.since(mark);
+            self.age_memories(action, &before, &model);
+        }
+        let before = self.writes.finish(mark);
+        self.record_provenance(action, &before);
+        result?;
+        self.check_triggers()
+    }
+
+    /// Strengthen the beliefs `action` used, then let the time it took pass
+    /// and forget what faded
+    fn age_memories(&mut self, action: &Action, before: &HashMap<String, Option<serde_json::Value>>, model: &Forgetting) {
+        let mut used = crate::analysis::reads(action);
+        used.push(action.target.clone());
+        used.extend(before.iter().filter(|(name, old)| self.state.beliefs.get(*name) != old.as_ref()).map(|(name, _)| name.clone()));
+        used.sort();
+        used.dedup();
+        for name in used.iter().filter(|name| self.state.beliefs.contains_key(*name)) {
+            self.state.memory.rehearse(name, model);
+        }
+
+        let seconds = action.dur.unwrap_or(if action.op == Operation::Wait { 1.0 } else { 0.0 });
+        if seconds > 0.0 {
+            for name in self.state.memory.pass(seconds, model) {
+                self.writes.remove(&mut self.state.beliefs, &name);
+                self.state.thoughts.push(format!("Forgot: {}", name));
+                if self.verbose {
+                    println!("  🌫️  Forgot: {}", name);
+                }
+            }
+        }
+    }
+
+    /// Count another action on the current focus, letting it go once the
+    /// attention span runs out; `focus` is what attention was on before
+    fn drift_attention(&mut self, focus: Option<String>) {
+        if self.state.attention != focus {
+            self.attended = 0;
+            return;
+        }
+        let (Some(span), Some(focus)) = (self.profile.attention_span, focus) else {
+            return;
+        };
+        self.attended += 1;
+        if self.attended >= span {
+            self.attended = 0;
+            self.state.attention = None;
+            self.state.thoughts.push(format!("Mind wandered from: {}", focus));
+            if self.verbose {
+                println!("  🦋 Mind wandered from: {}", focus);
+            }
+        }
+    }
+
+    /// A computed number as the brain works it out, off by the profile's
+    /// noise
+    fn slip(&mut self, value: serde_json::Value) -> serde_json::Value {
+        match value.as_f64() {
+            Some(number) if self.profile.noise > 0.0 => serde_json::json!(number * self.rng.gaussian(1.0, self.profile.noise)),
+            _ => value,
+        }
+    }
+
+    /// Add `item` to working memory; when it's full, drop the oldest item,
+    /// or with forgetting the least salient
+    fn remember(&mut self, item: String) {
+        self.state.working_memory.push(item);
+        let capacity = self.profile.working_memory;
+        let Some(model) = self.forgetting else {
+            if self.state.working_memory.len() > capacity {
+                self.state.working_memory.remove(0);
+            }
+            return;
+        };
+
+        // Strong feelings make an item stand out
+        let feeling = self.state.emotions.values().copied().fold(0.0, f64::max);
+        let memory = &mut self.state.memory;
+        memory.working.resize(self.state.working_memory.len() - 1, Trace { strength: 0.0, last_used: memory.clock });
+        memory.working.push(Trace { strength: 1.0 + feeling, last_used: memory.clock });
+        while self.state.working_memory.len() > capacity {
+            let least = memory.least_salient(self.state.working_memory.len(), &model);
+            self.state.working_memory.remove(least);
+            memory.working.remove(least);
+        }
+    }
+
+    /// Note which values and output lines `action` changed, and what it read
+    fn record_provenance(&mut self, action: &Action, before: &HashMap<String, Option<serde_json::Value>>) {
+        for name in before.keys().filter(|name| !self.state.beliefs.contains_key(*name)) {
+            self.state.provenance.forget(name);
+        }
+        let changed: Vec<&String> = before.iter()
+            .filter(|(name, old)| self.state.beliefs.get(*name).is_some_and(|value| Some(value) != old.as_ref()))
+            .map(|(name, _)| name)
+            .collect();
+        // An existing value the action named but didn't change was read (e.g. Emit)
+        let existed = before.get(&action.target)
+            .map_or(self.state.beliefs.contains_key(&action.target), Option::is_some);
+        let mut reads = crate::analysis::reads(action);
+        if existed && !changed.contains(&&action.target) {
+            reads.push(action.target.clone());
+        }
+        self.state.provenance.record(
+            self.path.current(),
+            action,
+            reads,
+            changed,
+            self.state.output.len(),
+        );
+    }
+
+    /// A variable's value: a local of the running function call, else a
+    /// belief
+    fn lookup(&self, name: &str) -> Option<&serde_json::Value> {
+        match self.frames.last() {
+            Some(locals) if locals.contains_key(name) => locals.get(name),
+            _ => self.state.beliefs.get(name),
+        }
+    }
+
+    /// Bind a variable: a local inside a function call, else a belief
+    fn bind(&mut self, name: &str, value: serde_json::Value) {
+        match self.frames.last_mut() {
+            Some(locals) => {
+                locals.insert(name.to_string(), value);
+            }
+            None => self.writes.set(&mut self.state.beliefs, name.to_string(), value),
+        }
+    }
+
+    /// Run `actions` one level deeper, as the `label` branch of the current action
+    fn execute_block(&mut self, actions: &[Action], label: &str) -> Result<()> {
+        for (j, action) in actions.iter().enumerate() {
+            if self.signal.is_some() {
+                break;
+            }
+            self.path.push(format!("{}[{}]", label, j));
+            self.recursion_depth += 1;
+            let result = self.execute_action(action);
+            self.recursion_depth -= 1;
+            self.path.pop();
+            result?;
+        }
+        Ok(())
+    }
+
+    fn dispatch_action(&mut self, action: &Action) -> Result<()> {
         // Check recursion depth
         if self.recursion_depth >= self.max_recursion_depth {
-            return Err(anyhow!("Maximum recursion depth exceeded"));
+            return Err(UclError::RecursionLimit { depth: self.max_recursion_depth }.into());
+        }
+
+        if self.interrupt.as_ref().is_some_and(|flag| flag.swap(false, Ordering::SeqCst)) {
+            return Err(UclError::Interrupted.into());
         }
 
         let trace_msg = format!("{:?}({})", action.op, action.target);
@@ -184,7 +691,7 @@ impl BrainSimulator {
             Operation::Assert => self.assert_fact(action),
             Operation::Emit => self.emit(action),
             Operation::Receive => self.receive(action),
-            Operation::Measure => self.measure(action),
+            Operation::Measure | Operation::Scan => self.measure(action),
             Operation::Decide => self.decide(action),
             Operation::Read => self.read(action),
             Operation::Write => self.write_memory(action),
@@ -192,13 +699,31 @@ impl BrainSimulator {
             Operation::Bind => self.bind_concept(action),
             Operation::Oblige => self.create_obligation(action),
             Operation::Wait => self.wait(action),
+            Operation::Sleep => self.sleep(action),
+            Operation::Dream => self.dream(action),
+            Operation::Imagine => self.imagine(action),
             Operation::GenRandomInt => self.gen_random_int(action),
 
             // Control flow operations
             Operation::If => self.execute_if(action),
             Operation::While => self.execute_while(action),
             Operation::For => self.execute_for(action),
+            Operation::ForEach => self.execute_for_each(action),
+            Operation::Append => self.append(action),
             Operation::DefineFunction => self.execute_define_function(action),
+            Operation::Call if self.state.functions.contains_key(&action.target) || self.host_fns.contains_key(&action.target) => {
+                self.execute_call(action)
+            }
+            Operation::Break | Operation::Continue => self.raise_loop_signal(action),
+            Operation::Return => self.raise_return(action),
+            Operation::Halt => self.halt(action),
+            Operation::Match => self.execute_match(action),
+            Operation::Try => self.execute_try(action),
+            // Any interleaving will do, and program order is one
+            Operation::Parallel => self.execute_block(action.body_actions.as_deref().unwrap_or_default(), "body"),
+            Operation::Throw => self.throw(action),
+            Operation::OnInterrupt => self.register_interrupt_handler(action),
+            Operation::When => self.register_trigger(action),
 
             // Cooking operations - simulated as physical actions
             Operation::Gather => self.physical_action(action, "👐", "Gathering"),
@@ -208,9 +733,16 @@ impl BrainSimulator {
             Operation::Stir => self.physical_action(action, "🥄", "Stirring"),
             Operation::Place => self.physical_action(action, "📍", "Placing"),
             Operation::Remove => self.physical_action(action, "✋", "Removing"),
+            Operation::Grasp => self.physical_action(action, "🤏", "Grasping"),
+            Operation::Release => self.physical_action(action, "🖐️", "Releasing"),
             Operation::Steep => self.physical_action(action, "⏱️", "Steeping"),
             Operation::Serve => self.physical_action(action, "🍽️", "Serving"),
 
+            Operation::Custom(name) if self.handlers.contains_key(name) => self.execute_registered(action, name),
+            Operation::Custom(name) if crate::custom_ops::split(name).is_some_and(|(ns, _)| self.namespaces.contains_key(ns)) => {
+                self.execute_namespaced(action, name)
+            }
+
             _ => {
                 // Brain encounters something it doesn't understand
                 let confusion = format!("Sorry, I don't know what that means: {:?}", action.op);
@@ -248,7 +780,8 @@ impl BrainSimulator {
 
             for (key, value) in &properties {
                 let fact_key = format!("{}.{}", entity, key);
-                self.state.beliefs.insert(fact_key.clone(), value.clone());
+                self.writes.set(&mut self.state.beliefs, fact_key.clone(), value.clone());
+                self.state.knowledge.insert(entity, key, value.clone());
 
                 if self.verbose {
                     println!("  📝 Stored: {} = {}", fact_key, value);
@@ -260,12 +793,7 @@ impl BrainSimulator {
                 let memory_item = format!("The {} has properties: {}",
                     entity,
                     properties.keys().map(|k| k.as_str()).collect::<Vec<_>>().join(", "));
-                self.state.working_memory.push(memory_item);
-
-                // Keep working memory limited
-                if self.state.working_memory.len() > 7 {
-                    self.state.working_memory.remove(0);
-                }
+                self.remember(memory_item);
             }
         }
         Ok(())
@@ -279,7 +807,8 @@ impl BrainSimulator {
             .and_then(|v| v.as_str())
             .unwrap_or(&action.target);
 
-        self.state.beliefs.insert(
+        self.writes.set(
+            &mut self.state.beliefs,
             format!("assertion.{}", action.target),
             serde_json::json!(statement)
         );
@@ -294,34 +823,39 @@ impl BrainSimulator {
     }
 
     fn emit(&mut self, action: &Action) -> Result<()> {
-        // Generate output (speech/expression)
-        let message = if let Some(params) = action.params.as_ref() {
+        // Generate output (speech/expression), and the value another actor hears
+        let (message, value) = if let Some(params) = action.params.as_ref() {
             if let Some(content) = params.get("content") {
+                // Expressions (`{"format": ...}`, `{"var": ...}`) say what they evaluate to
+                if let Some(expr) = serde_json::from_value::<Expression>(content.clone()).ok().filter(|e| !matches!(e, Expression::Value(_))) {
+                    let value = self.evaluate_expression(&expr)?;
+                    (values::text(&value), value)
                 // If content is a string matching a variable, output the variable's value
-                if let Some(content_str) = content.as_str() {
-                    if let Some(value) = self.state.beliefs.get(content_str) {
-                        value.to_string()
+                } else if let Some(content_str) = content.as_str() {
+                    if let Some(value) = self.lookup(content_str) {
+                        (value.to_string(), value.clone())
                     } else {
-                        content_str.to_string()
+                        (content_str.to_string(), content.clone())
                     }
                 } else {
-                    content.to_string()
+                    (content.to_string(), content.clone())
                 }
             } else if let Some(message) = params.get("message") {
-                message.as_str().map(|s| s.to_string()).unwrap_or_else(|| message.to_string())
+                (message.as_str().map(|s| s.to_string()).unwrap_or_else(|| message.to_string()), message.clone())
             } else {
-                action.target.clone()
+                (action.target.clone(), serde_json::json!(action.target))
             }
         } else {
             // No params - check if target is a variable
-            if let Some(value) = self.state.beliefs.get(&action.target) {
-                value.to_string()
+            if let Some(value) = self.lookup(&action.target) {
+                (value.to_string(), value.clone())
             } else {
-                action.target.clone()
+                (action.target.clone(), serde_json::json!(action.target))
             }
         };
 
         self.state.output.push(message.clone());
+        self.state.channels.send(action, value);
 
         // Check for emotional content
         if let Some(params) = &action.params {
@@ -340,14 +874,32 @@ impl BrainSimulator {
     }
 
     fn receive(&mut self, action: &Action) -> Result<()> {
-        // Receive input (perception)
+        // A message another actor emitted on this channel is remembered as
+        // `params.into`, else the target
+        if let Some(message) = self.state.channels.take(action) {
+            let name = action.params.as_ref()
+                .and_then(|p| p.get("into"))
+                .and_then(|v| v.as_str())
+                .unwrap_or(&action.target);
+            let heard = values::text(&message.content);
+            self.remember(format!("Heard from {}: {}", message.from, heard));
+            self.state.attention = Some(heard.clone());
+            self.bind(name, message.content);
+
+            if self.verbose {
+                println!("  👂 {} said: \"{}\"", message.from, heard);
+            }
+            return Ok(());
+        }
+
+        // Otherwise it's perception of whatever the action describes
         let input = action.params
             .as_ref()
             .and_then(|p| p.get("content"))
             .and_then(|v| v.as_str())
             .unwrap_or(&action.target);
 
-        self.state.working_memory.push(format!("Heard: {}", input));
+        self.remember(format!("Heard: {}", input));
         self.state.attention = Some(input.to_string());
 
         if self.verbose {
@@ -364,7 +916,7 @@ impl BrainSimulator {
         if let Some(params) = &action.params {
             for (key, value) in params {
                 let obs_key = format!("observed.{}.{}", action.target, key);
-                self.state.beliefs.insert(obs_key, value.clone());
+                self.writes.set(&mut self.state.beliefs, obs_key, value.clone());
             }
         }
 
@@ -400,11 +952,42 @@ impl BrainSimulator {
     }
 
     fn read(&mut self, action: &Action) -> Result<()> {
+        let params = action.params.as_ref();
+        let into = params.and_then(|p| p.get("into")).and_then(|v| v.as_str());
+
+        // Following facts from the target, along `follow` unless it's `*`
+        if let Some(predicate) = params.and_then(|p| p.get("follow")).and_then(|v| v.as_str()) {
+            let found = self.state.knowledge.reachable(&action.target, Some(predicate).filter(|p| *p != "*"));
+            self.remember(format!("Recalled: {} {} {}", action.target, predicate, found.join(", ")));
+            if let Some(into) = into {
+                self.bind(into, serde_json::json!(found));
+            }
+            if self.verbose {
+                println!("  🕸️  From {} by {}: {}", action.target, predicate, found.join(", "));
+            }
+            return Ok(());
+        }
+
+        // A pattern like `cat.*` queries the knowledge graph
+        if action.target.contains('*') {
+            let found = self.state.knowledge.matching(&action.target);
+            let sentences: Vec<String> = found.iter().map(|t| t.sentence()).collect();
+            let found: Vec<serde_json::Value> = found.iter().map(|t| t.to_value()).collect();
+            self.remember(format!("Recalled: {}", sentences.join("; ")));
+            if let Some(into) = into {
+                self.bind(into, serde_json::Value::Array(found));
+            }
+            if self.verbose {
+                println!("  🕸️  {}: {}", action.target, sentences.join("; "));
+            }
+            return Ok(());
+        }
+
         // Read from memory
-        let value = self.state.beliefs.get(&action.target);
+        let value = self.lookup(&action.target).cloned();
 
         if let Some(v) = value {
-            self.state.working_memory.push(format!("Recalled: {} = {}", action.target, v));
+            self.remember(format!("Recalled: {} = {}", action.target, v));
 
             if self.verbose {
                 println!("  📖 Recalled: {} = {}", action.target, v);
@@ -425,7 +1008,7 @@ impl BrainSimulator {
 
                 // Get left operand (register or value)
                 let lhs_val = if let Some(lhs_reg) = params.get("lhs_register") {
-                    self.state.beliefs.get(lhs_reg.as_str().unwrap_or(""))
+                    self.lookup(lhs_reg.as_str().unwrap_or(""))
                         .and_then(|v| v.as_f64().or_else(|| v.as_i64().map(|i| i as f64)))
                         .unwrap_or(0.0)
                 } else if let Some(lhs) = params.get("lhs") {
@@ -436,7 +1019,7 @@ impl BrainSimulator {
 
                 // Get right operand (register or value)
                 let rhs_val = if let Some(rhs_reg) = params.get("rhs_register") {
-                    self.state.beliefs.get(rhs_reg.as_str().unwrap_or(""))
+                    self.lookup(rhs_reg.as_str().unwrap_or(""))
                         .and_then(|v| v.as_f64().or_else(|| v.as_i64().map(|i| i as f64)))
                         .unwrap_or(0.0)
                 } else if let Some(rhs) = params.get("rhs") {
@@ -453,7 +1036,7 @@ impl BrainSimulator {
                     _ => lhs_val * rhs_val,
                 };
 
-                self.state.beliefs.insert(action.target.clone(), serde_json::json!(result));
+                self.bind(&action.target, serde_json::json!(result));
                 self.state.thoughts.push(format!("Calculated: {} = {} {} {} = {}",
                     action.target, lhs_val,
                     match operation { "multiply" => "×", "add" => "+", "subtract" => "-", "divide" => "÷", _ => "×" },
@@ -468,7 +1051,7 @@ impl BrainSimulator {
 
             // Otherwise use direct value
             if let Some(value) = params.get("value") {
-                self.state.beliefs.insert(action.target.clone(), value.clone());
+                self.bind(&action.target, value.clone());
 
                 if self.verbose {
                     println!("  💾 Stored: {} = {}", action.target, value);
@@ -482,7 +1065,8 @@ impl BrainSimulator {
     fn create_concept(&mut self, action: &Action) -> Result<()> {
         // Create a new concept/idea
         self.state.thoughts.push(format!("Conceived of: {}", action.target));
-        self.state.beliefs.insert(
+        self.writes.set(
+            &mut self.state.beliefs,
             format!("concept.{}", action.target),
             serde_json::json!({"exists": true})
         );
@@ -498,7 +1082,15 @@ impl BrainSimulator {
         // Bind a concept to a value (mental variable)
         if let Some(params) = &action.params {
             if let Some(value) = params.get("value") {
-                self.state.beliefs.insert(action.target.clone(), value.clone());
+                // Variables, calls and arithmetic are evaluated; anything else is stored as-is
+                let value = match serde_json::from_value::<Expression>(value.clone()) {
+                    Ok(Expression::Value(_)) | Err(_) => value.clone(),
+                    Ok(expr) => {
+                        let value = self.evaluate_expression(&expr)?;
+                        self.slip(value)
+                    }
+                };
+                self.bind(&action.target, value.clone());
 
                 if self.verbose {
                     println!("  🔗 Bound: {} = {}", action.target, value);
@@ -540,11 +1132,74 @@ impl BrainSimulator {
         Ok(())
     }
 
-    fn gen_random_int(&mut self, action: &Action) -> Result<()> {
-        // Generate a random integer
-        use std::collections::hash_map::RandomState;
-        use std::hash::{BuildHasher, Hash, Hasher};
+    fn sleep(&mut self, action: &Action) -> Result<()> {
+        self.writes.touch(&self.state.beliefs, sleep::LONG_TERM);
+        let stored = sleep::consolidate(&mut self.state);
+        sleep::settle(&mut self.state.emotions);
+        self.state.attention = None;
+        self.state.thoughts.push(format!("Slept on {}, consolidating {} memories", action.target, stored.len()));
+
+        if self.verbose {
+            println!("  😴 Slept, consolidating {} memories", stored.len());
+        }
+
+        Ok(())
+    }
+
+    fn dream(&mut self, action: &Action) -> Result<()> {
+        let count = action.params.as_ref().and_then(|p| p.get("count")).and_then(|v| v.as_u64()).unwrap_or(1);
+        for _ in 0..count {
+            let Some(dream) = sleep::dream(&self.state.beliefs, &mut self.rng) else {
+                self.state.thoughts.push("Dreamt of nothing".to_string());
+                break;
+            };
+            if self.verbose {
+                println!("  🌙 {}", dream);
+            }
+            self.state.thoughts.push(dream);
+        }
+
+        Ok(())
+    }
+
+    /// Run `body` in the mind of `target`, a brain of its own that keeps
+    /// its state in `minds` between Imagines, then bind its belief
+    /// `params.ask` as `params.into`, else under the mind's name, like
+    /// `Alice.cat.color`, so it can't overwrite the brain's own belief
+    fn imagine(&mut self, action: &Action) -> Result<()> {
+        let name = &action.target;
+        let mut mind = BrainSimulator::new()
+            .with_seed(self.rng.next_u64())
+            .with_max_recursion_depth(self.max_recursion_depth.saturating_sub(self.recursion_depth));
+        if let Some(flag) = &self.interrupt {
+            mind = mind.with_interrupt(flag.clone());
+        }
+        mind.state = self.state.minds.remove(name).unwrap_or_default();
+
+        let body = Program { metadata: None, actions: action.body_actions.clone().unwrap_or_default() };
+        let result = mind.execute(&body);
+        self.state.minds.insert(name.clone(), mind.state);
+        result.with_context(|| format!("Imagining {}'s mind", name))?;
+
+        let params = action.params.as_ref();
+        if let Some(ask) = params.and_then(|p| p.get("ask")).and_then(|v| v.as_str()) {
+            let value = self.state.minds[name].beliefs.get(ask).cloned().unwrap_or(serde_json::Value::Null);
+            let into = match params.and_then(|p| p.get("into")).and_then(|v| v.as_str()) {
+                Some(into) => into.to_string(),
+                None => format!("{}.{}", name, ask),
+            };
+            self.state.thoughts.push(format!("I think {} believes {} = {}", name, ask, value));
+            self.bind(&into, value);
+        }
+
+        if self.verbose {
+            println!("  🪞 Imagined {}'s mind", name);
+        }
+
+        Ok(())
+    }
 
+    fn gen_random_int(&mut self, action: &Action) -> Result<()> {
         let (min, max) = if let Some(params) = &action.params {
             let min_val = params.get("min")
                 .and_then(|v| v.as_i64())
@@ -556,21 +1211,9 @@ impl BrainSimulator {
         } else {
             (0, 9)
         };
+        let random_num = self.rng.gen_range_i64(min, max);
 
-        // Simple random number generation using system time
-        let state = RandomState::new();
-        let mut hasher = state.build_hasher();
-        std::time::SystemTime::now().hash(&mut hasher);
-        action.target.hash(&mut hasher);
-        let hash = hasher.finish();
-        let range = (max - min + 1) as u64;
-        let random_num = min + (hash % range) as i64;
-
-        // Store in beliefs
-        self.state.beliefs.insert(
-            action.target.clone(),
-            serde_json::json!(random_num)
-        );
+        self.bind(&action.target, serde_json::json!(random_num));
 
         self.state.thoughts.push(format!("Generated random number: {} = {}", action.target, random_num));
 
@@ -583,7 +1226,7 @@ impl BrainSimulator {
 
     fn execute_if(&mut self, action: &Action) -> Result<()> {
         let condition = action.condition.as_ref()
-            .ok_or_else(|| anyhow!("If requires condition"))?;
+            .ok_or_else(|| UclError::missing("If", "condition"))?;
 
         let result = self.evaluate_condition(condition)?;
 
@@ -593,44 +1236,143 @@ impl BrainSimulator {
 
         if result {
             if let Some(then_actions) = &action.then_actions {
-                for then_action in then_actions {
-                    self.recursion_depth += 1;
-                    self.execute_action(then_action)?;
-                    self.recursion_depth -= 1;
-                }
+                self.execute_block(then_actions, "then")?;
             }
         } else if let Some(else_actions) = &action.else_actions {
-            for else_action in else_actions {
-                self.recursion_depth += 1;
-                self.execute_action(else_action)?;
-                self.recursion_depth -= 1;
+            self.execute_block(else_actions, "else")?;
+        }
+
+        Ok(())
+    }
+
+    /// A `Call` statement: run the function the program defined, else the
+    /// host function, with the params as arguments
+    fn execute_call(&mut self, action: &Action) -> Result<()> {
+        let mut args = serde_json::Map::new();
+        let mut out = None;
+        for (key, value) in action.params.iter().flatten() {
+            if key == "out" {
+                out = value.as_str();
+                continue;
             }
+            // Variables, calls and arithmetic are evaluated; anything else is passed as-is
+            let value = match serde_json::from_value::<Expression>(value.clone()) {
+                Ok(Expression::Value(_)) | Err(_) => value.clone(),
+                Ok(expr) => self.evaluate_expression(&expr)?,
+            };
+            args.insert(key.clone(), value);
         }
 
+        let result = match self.state.functions.contains_key(&action.target) {
+            true => self.call_function(&action.target, args.into_iter().collect())?,
+            false => self.call_host(&action.target, &args)?,
+        };
+        if let Some(out) = out {
+            self.bind(out, result);
+        }
         Ok(())
     }
 
+    fn call_host(&mut self, name: &str, args: &serde_json::Map<String, serde_json::Value>) -> Result<serde_json::Value> {
+        if self.verbose {
+            println!("  🔌 {}({})", name, serde_json::Value::Object(args.clone()));
+        }
+        let function = self.host_fns.get_mut(name).expect("checked by caller");
+        function(args).map_err(|e| anyhow!("{}: {}", name, e))
+    }
+
+    fn execute_registered(&mut self, action: &Action, name: &str) -> Result<()> {
+        if self.verbose {
+            println!("  🧩 {} (registered handler)", name);
+        }
+        let handler = self.handlers.get_mut(name).expect("checked by caller");
+        handler(action, &mut self.state).map_err(|e| anyhow!("{}: {}", name, e))
+    }
+
+    fn execute_namespaced(&mut self, action: &Action, name: &str) -> Result<()> {
+        let (namespace, _) = crate::custom_ops::split(name).expect("checked by caller");
+        if self.verbose {
+            println!("  🧩 {} ({} handler)", name, namespace);
+        }
+        let handler = self.namespaces.get_mut(namespace).expect("checked by caller");
+        handler(action, &mut self.state).map_err(|e| anyhow!("{}: {}", name, e))
+    }
+
+    fn register_interrupt_handler(&mut self, action: &Action) -> Result<()> {
+        let handler = action.body_actions.clone().unwrap_or_default();
+        if self.verbose {
+            println!("  🛟 Registered interrupt handler ({} actions)", handler.len());
+        }
+        self.interrupt_handlers.push(handler);
+        Ok(())
+    }
+
+    fn register_trigger(&mut self, action: &Action) -> Result<()> {
+        let trigger = Trigger::from_action(action)?;
+        if self.verbose {
+            println!("  ⚡ Registered trigger for {} ({} actions)", action.target, trigger.body.len());
+        }
+        self.triggers.push(trigger);
+        Ok(())
+    }
+
+    /// Run the handler of every `When` whose condition has become true since
+    /// it was last checked; a condition that can't be evaluated yet (say, of
+    /// a variable not bound yet) doesn't hold
+    fn check_triggers(&mut self) -> Result<()> {
+        if self.firing || self.triggers.is_empty() || self.signal == Some(Signal::Halt) {
+            return Ok(());
+        }
+        self.firing = true;
+        // Triggers watch beliefs, whichever function call is running
+        let frames = std::mem::take(&mut self.frames);
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let mut signal = self.signal.take();
+        let mut result = Ok(());
+        for k in 0..self.triggers.len() {
+            let condition = self.triggers[k].condition.clone();
+            let holds = self.evaluate_condition(&condition).unwrap_or(false);
+            let rising = holds && !self.triggers[k].held;
+            self.triggers[k].held = holds;
+            if rising {
+                if self.verbose {
+                    println!("  ⚡ Trigger {} fired", k);
+                }
+                let body = self.triggers[k].body.clone();
+                result = self.execute_block(&body, &format!("trigger[{}].body", k));
+                // A Return only ends the handler, but a Halt ends the program
+                if self.signal.take() == Some(Signal::Halt) {
+                    signal = Some(Signal::Halt);
+                }
+                if result.is_err() || signal == Some(Signal::Halt) {
+                    break;
+                }
+            }
+        }
+        self.loop_depth = loop_depth;
+        self.frames = frames;
+        self.signal = signal;
+        self.firing = false;
+        result
+    }
+
     fn execute_while(&mut self, action: &Action) -> Result<()> {
         let condition = action.condition.as_ref()
-            .ok_or_else(|| anyhow!("While requires condition"))?;
+            .ok_or_else(|| UclError::missing("While", "condition"))?;
 
         let mut iterations = 0;
         const MAX_ITERATIONS: usize = 10000;
 
         while self.evaluate_condition(condition)? {
             if iterations >= MAX_ITERATIONS {
-                return Err(anyhow!("While loop exceeded maximum iterations"));
-            }
-
-            if let Some(body_actions) = &action.body_actions {
-                for body_action in body_actions {
-                    self.recursion_depth += 1;
-                    self.execute_action(body_action)?;
-                    self.recursion_depth -= 1;
-                }
+                return Err(UclError::LoopLimit { iterations: MAX_ITERATIONS }.into());
             }
 
+            self.execute_body(action)?;
             iterations += 1;
+            if ends_loop(&mut self.signal) {
+                break;
+            }
         }
 
         if self.verbose {
@@ -640,44 +1382,222 @@ impl BrainSimulator {
         Ok(())
     }
 
+    /// One iteration of a loop's body; a `Break` or `Continue` in it is left
+    /// in `signal` for the loop
+    fn execute_body(&mut self, action: &Action) -> Result<()> {
+        if let Some(body_actions) = &action.body_actions {
+            self.loop_depth += 1;
+            self.execute_block(body_actions, "body")?;
+            self.loop_depth -= 1;
+        }
+        Ok(())
+    }
+
+    fn raise_loop_signal(&mut self, action: &Action) -> Result<()> {
+        if self.loop_depth == 0 {
+            return Err(UclError::parse(format!("{:?} outside a loop", action.op)).into());
+        }
+        if self.verbose {
+            println!("  ⏭️  {:?}", action.op);
+        }
+        self.signal = Some(if action.op == Operation::Break { Signal::Break } else { Signal::Continue });
+        Ok(())
+    }
+
+    /// Leave the running function with `params.value`, evaluated (null
+    /// without one); outside a function this ends the program
+    fn raise_return(&mut self, action: &Action) -> Result<()> {
+        let value = match action.params.as_ref().and_then(|p| p.get("value")) {
+            Some(value) => self.evaluate_param(value)?,
+            None => serde_json::Value::Null,
+        };
+        if self.verbose {
+            println!("  ↩️  Return {}", value);
+        }
+        self.signal = Some(Signal::Return(value));
+        Ok(())
+    }
+
+    /// End the whole program with `params.status`, 0 by default
+    fn halt(&mut self, action: &Action) -> Result<()> {
+        let status = match action.params.as_ref().and_then(|p| p.get("status")) {
+            Some(status) => self.evaluate_param(status)?.as_i64()
+                .ok_or_else(|| UclError::invalid("Halt status must be an integer"))?,
+            None => 0,
+        };
+        if self.verbose {
+            println!("  🛑 Halt with status {}", status);
+        }
+        self.state.halted = Some(status);
+        self.signal = Some(Signal::Halt);
+        Ok(())
+    }
+
+    /// Run the body; if it fails, unwind to here, bind the error (`error`, or
+    /// the name in `params.error`) and run the catch block
+    fn execute_try(&mut self, action: &Action) -> Result<()> {
+        let (depth, path, loop_depth) = (self.recursion_depth, self.path.depth(), self.loop_depth);
+        let error = match self.execute_block(action.body_actions.as_deref().unwrap_or_default(), "body") {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        let Some(value) = caught(&error) else { return Err(error) };
+        self.recursion_depth = depth;
+        self.path.truncate(path);
+        self.loop_depth = loop_depth;
+
+        let name = action.params.as_ref()
+            .and_then(|p| p.get("error"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("error");
+        if self.verbose {
+            println!("  🪂 Caught: {}", value);
+        }
+        self.bind(name, value);
+        self.execute_block(action.catch_actions.as_deref().unwrap_or_default(), "catch")
+    }
+
+    /// Fail with `params.value` (evaluated), or the target's name without one
+    fn throw(&mut self, action: &Action) -> Result<()> {
+        let value = match action.params.as_ref().and_then(|p| p.get("value")) {
+            Some(value) => self.evaluate_param(value)?,
+            None => serde_json::json!(action.target),
+        };
+        Err(UclError::Thrown { value }.into())
+    }
+
+    /// Run the first arm whose `value` equals `params.value` (evaluated) or
+    /// whose `when` holds, or `else` if none does
+    fn execute_match(&mut self, action: &Action) -> Result<()> {
+        let value = action.params.as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Match", "'value' parameter"))?;
+        let value = self.evaluate_param(value)?;
+
+        let cases = action.match_cases().transpose()?.unwrap_or_default();
+        for (i, case) in cases.iter().enumerate() {
+            let hit = match (&case.value, &case.when) {
+                (Some(pattern), _) => same_value(&value, pattern),
+                (None, Some(condition)) => self.evaluate_condition(condition)?,
+                (None, None) => true,
+            };
+            if hit {
+                if self.verbose {
+                    println!("  🔀 {} matched case {}", value, i);
+                }
+                return self.execute_block(&case.then, &format!("params.cases[{}].then", i));
+            }
+        }
+        if let Some(else_actions) = &action.else_actions {
+            self.execute_block(else_actions, "else")?;
+        }
+        Ok(())
+    }
+
+    /// A param that may be an expression, evaluated; plain values as given
+    fn evaluate_param(&mut self, value: &serde_json::Value) -> Result<serde_json::Value> {
+        match serde_json::from_value::<Expression>(value.clone()) {
+            Ok(Expression::Value(_)) | Err(_) => Ok(value.clone()),
+            Ok(expr) => self.evaluate_expression(&expr),
+        }
+    }
+
     fn execute_for(&mut self, action: &Action) -> Result<()> {
         let loop_var = action.loop_var.as_ref()
-            .ok_or_else(|| anyhow!("For requires variable"))?;
+            .ok_or_else(|| UclError::missing("For", "variable"))?;
         let from_expr = action.from_expr.as_ref()
-            .ok_or_else(|| anyhow!("For requires from expression"))?;
+            .ok_or_else(|| UclError::missing("For", "from expression"))?;
         let to_expr = action.to_expr.as_ref()
-            .ok_or_else(|| anyhow!("For requires to expression"))?;
+            .ok_or_else(|| UclError::missing("For", "to expression"))?;
 
         let from_val = self.evaluate_expression(from_expr)?;
         let to_val = self.evaluate_expression(to_expr)?;
 
-        let from_i = from_val.as_i64().ok_or_else(|| anyhow!("For from must be integer"))?;
-        let to_i = to_val.as_i64().ok_or_else(|| anyhow!("For to must be integer"))?;
+        let from_i = from_val.as_i64().ok_or_else(|| UclError::invalid("For from must be integer"))?;
+        let to_i = to_val.as_i64().ok_or_else(|| UclError::invalid("For to must be integer"))?;
 
         for i in from_i..=to_i {
             // Set loop variable
-            self.state.beliefs.insert(loop_var.clone(), serde_json::json!(i));
+            self.bind(loop_var, serde_json::json!(i));
 
-            if let Some(body_actions) = &action.body_actions {
-                for body_action in body_actions {
-                    self.recursion_depth += 1;
-                    self.execute_action(body_action)?;
-                    self.recursion_depth -= 1;
-                }
+            self.execute_body(action)?;
+            if ends_loop(&mut self.signal) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Names of the state a `ForEach` can go `over`
+    pub const COLLECTIONS: &'static [&'static str] = &["beliefs", "emotions", "goals", "working_memory", "thoughts"];
+
+    fn collection(&self, name: &str) -> Result<Vec<serde_json::Value>> {
+        let strings = |items: &[String]| items.iter().map(|s| serde_json::Value::String(s.clone())).collect();
+        Ok(match name {
+            "beliefs" => values::keys(&self.state.beliefs),
+            "emotions" => values::keys(&self.state.emotions),
+            "goals" => strings(&self.state.goals),
+            "working_memory" => strings(&self.state.working_memory),
+            "thoughts" => strings(&self.state.thoughts),
+            _ => return Err(UclError::invalid(format!("ForEach can't go over '{}'; the brain has {}", name, Self::COLLECTIONS.join(", "))).into()),
+        })
+    }
+
+    fn execute_for_each(&mut self, action: &Action) -> Result<()> {
+        let loop_var = action.loop_var.as_ref()
+            .ok_or_else(|| UclError::missing("ForEach", "variable"))?;
+        let params = action.params.as_ref();
+        let items = match params.and_then(|p| p.get("over")) {
+            Some(name) => self.collection(name.as_str().ok_or_else(|| UclError::invalid("ForEach 'over' must name a collection"))?)?,
+            None => {
+                let list = params.and_then(|p| p.get("in"))
+                    .ok_or_else(|| UclError::missing("ForEach", "'in' or 'over' parameter"))?;
+                values::items(self.evaluate_param(list)?)?
+            }
+        };
+
+        for item in items {
+            self.bind(loop_var, item);
+
+            self.execute_body(action)?;
+            if ends_loop(&mut self.signal) {
+                break;
             }
         }
 
         Ok(())
     }
 
+    fn append(&mut self, action: &Action) -> Result<()> {
+        let item = action.params.as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Append", "'value' parameter"))?;
+        let item = self.evaluate_param(item)?;
+
+        // Inside a function this adds to a belief too, unless a local hides it
+        let global = self.frames.last().is_some_and(|locals| !locals.contains_key(&action.target))
+            && self.state.beliefs.contains_key(&action.target);
+        let list = values::append(self.lookup(&action.target).cloned(), item)?;
+        if self.verbose {
+            println!("  ➕ {} = {}", action.target, list);
+        }
+        if global {
+            self.writes.set(&mut self.state.beliefs, action.target.clone(), list);
+        } else {
+            self.bind(&action.target, list);
+        }
+        Ok(())
+    }
+
     fn execute_define_function(&mut self, action: &Action) -> Result<()> {
         let func_name = &action.target;
         let params = action.params.as_ref()
-            .ok_or_else(|| anyhow!("DefineFunction requires params"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "params"))?;
 
         let args = params.get("args")
             .and_then(|v| v.as_array())
-            .ok_or_else(|| anyhow!("DefineFunction requires args array"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "args array"))?;
 
         let arg_names: Vec<String> = args.iter()
             .filter_map(|v| v.as_str())
@@ -685,7 +1605,7 @@ impl BrainSimulator {
             .collect();
 
         let body_value = params.get("body")
-            .ok_or_else(|| anyhow!("DefineFunction requires body"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "body"))?;
 
         let body_actions: Vec<Action> = serde_json::from_value(body_value.clone())?;
 
@@ -763,6 +1683,17 @@ impl BrainSimulator {
             Condition::Not { operand } => {
                 Ok(!self.evaluate_condition(operand)?)
             }
+            Condition::Expression { value } => Ok(values::truthy(&self.evaluate_expression(value)?)),
+            Condition::Matches { value, pattern } => values::matches(&self.evaluate_expression(value)?, pattern),
+            Condition::StartsWith { value, prefix } => {
+                let (value, prefix) = (self.evaluate_expression(value)?, self.evaluate_expression(prefix)?);
+                Ok(values::text(&value).starts_with(&values::text(&prefix)))
+            }
+            Condition::Contains { value, part } => {
+                let (value, part) = (self.evaluate_expression(value)?, self.evaluate_expression(part)?);
+                Ok(values::contains(&value, &part))
+            }
+            Condition::Exists { var } => Ok(self.lookup(var).is_some()),
         }
     }
 
@@ -770,16 +1701,16 @@ impl BrainSimulator {
         match expr {
             Expression::Value(v) => Ok(v.clone()),
             Expression::Variable { var } => {
-                self.state.beliefs.get(var)
+                self.lookup(var)
                     .cloned()
-                    .ok_or_else(|| anyhow!("Variable not found: {}", var))
+                    .ok_or_else(|| UclError::UndefinedVariable { name: var.clone() }.into())
             }
             Expression::BinaryOp { expr: bin_op } => {
                 let left_val = self.evaluate_expression(&bin_op.left)?;
                 let right_val = self.evaluate_expression(&bin_op.right)?;
 
-                let left_num = left_val.as_f64().ok_or_else(|| anyhow!("Left operand must be number"))?;
-                let right_num = right_val.as_f64().ok_or_else(|| anyhow!("Right operand must be number"))?;
+                let left_num = left_val.as_f64().ok_or_else(|| UclError::invalid("Left operand must be number"))?;
+                let right_num = right_val.as_f64().ok_or_else(|| UclError::invalid("Right operand must be number"))?;
 
                 let result = match bin_op.op.as_str() {
                     "+" => left_num + right_num,
@@ -787,63 +1718,88 @@ impl BrainSimulator {
                     "*" => left_num * right_num,
                     "/" => {
                         if right_num == 0.0 {
-                            return Err(anyhow!("Division by zero"));
+                            return Err(UclError::invalid("Division by zero").into());
                         }
                         left_num / right_num
                     }
                     "%" => left_num % right_num,
-                    _ => return Err(anyhow!("Unknown operator: {}", bin_op.op)),
+                    _ => return Err(UclError::unsupported(format!("binary operator '{}'", bin_op.op), "brain").into()),
                 };
 
                 Ok(serde_json::json!(result))
             }
+            Expression::List { list } => {
+                let items = list.iter().map(|item| self.evaluate_expression(item)).collect::<Result<_>>()?;
+                Ok(serde_json::Value::Array(items))
+            }
+            Expression::Index { index, at } => {
+                let (list, at) = (self.evaluate_expression(index)?, self.evaluate_expression(at)?);
+                values::index(&list, &at)
+            }
+            Expression::Length { length } => values::length(&self.evaluate_expression(length)?),
+            Expression::StringOp { string } => {
+                let args = string.args.iter().map(|arg| self.evaluate_expression(arg)).collect::<Result<Vec<_>>>()?;
+                values::string_op(&string.op, &args)
+            }
+            Expression::Unary { unary } => {
+                let operand = self.evaluate_expression(&unary.operand)?;
+                values::unary(&unary.op, &operand)
+            }
+            Expression::Test { test } => Ok(serde_json::Value::Bool(self.evaluate_condition(test)?)),
+            Expression::Format { format } => {
+                let text = template::render(format, |name| {
+                    self.lookup(name)
+                        .map(values::text)
+                        .ok_or_else(|| UclError::UndefinedVariable { name: name.to_string() }.into())
+                })?;
+                Ok(serde_json::Value::String(text))
+            }
+            Expression::FunctionCall { call, args } if !self.state.functions.contains_key(call) && self.host_fns.contains_key(call) => {
+                let mut values = serde_json::Map::new();
+                for (name, expr) in args {
+                    values.insert(name.clone(), self.evaluate_expression(expr)?);
+                }
+                self.call_host(call, &values)
+            }
             Expression::FunctionCall { call, args } => {
-                // Get function definition
-                let func_def = self.state.functions.get(call)
-                    .ok_or_else(|| anyhow!("Function not defined: {}", call))?
-                    .clone();
-
-                // Save current variable state
-                let saved_vars: HashMap<String, serde_json::Value> = func_def.args.iter()
-                    .filter_map(|arg| self.state.beliefs.get(arg).map(|v| (arg.clone(), v.clone())))
-                    .collect();
-
-                // Bind arguments
+                // Arguments are evaluated in the caller and become the call's first locals
+                let mut locals = HashMap::new();
                 for (arg_name, arg_expr) in args {
-                    let arg_value = self.evaluate_expression(arg_expr)?;
-                    self.state.beliefs.insert(arg_name.clone(), arg_value);
-                }
-
-                // Execute function body
-                let mut return_value = serde_json::Value::Null;
-                for action in &func_def.body {
-                    // Check for Return operation
-                    if matches!(action.op, Operation::Return) {
-                        if let Some(params) = &action.params {
-                            if let Some(value_expr) = params.get("value") {
-                                // value_expr might be an Expression wrapped in JSON
-                                // Try to deserialize it as Expression
-                                if let Ok(expr) = serde_json::from_value::<Expression>(value_expr.clone()) {
-                                    return_value = self.evaluate_expression(&expr)?;
-                                } else {
-                                    return_value = value_expr.clone();
-                                }
-                            }
-                        }
-                        break;
-                    }
-
-                    self.recursion_depth += 1;
-                    self.execute_action(action)?;
-                    self.recursion_depth -= 1;
+                    locals.insert(arg_name.clone(), self.evaluate_expression(arg_expr)?);
                 }
+                self.call_function(call, locals)
+            }
+        }
+    }
 
-                // Restore saved variables
-                for (arg_name, saved_value) in saved_vars {
-                    self.state.beliefs.insert(arg_name, saved_value);
-                }
+    /// Run the program's function `name` in a new frame holding `locals`
+    fn call_function(&mut self, name: &str, locals: HashMap<String, serde_json::Value>) -> Result<serde_json::Value> {
+        let func_def = self.state.functions.get(name)
+            .ok_or_else(|| UclError::UndefinedFunction { name: name.to_string() })?
+            .clone();
+
+        // The body sees its own locals and the beliefs, not the caller's
+        // locals; loops around the call aren't the body's to break
+        self.frames.push(locals);
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.path.push(format!("{}()", name));
+        let result = self.execute_function_body(&func_def.body);
+        self.path.pop();
+        self.loop_depth = loop_depth;
+        self.frames.pop();
+        result
+    }
 
-                Ok(return_value)
+    /// Run a called function's body in its frame until it ends or a
+    /// `Return` anywhere in it, giving the returned value (null without one)
+    fn execute_function_body(&mut self, body: &[Action]) -> Result<serde_json::Value> {
+        self.execute_block(body, "body")?;
+        match self.signal.take() {
+            Some(Signal::Return(value)) => Ok(value),
+            // A Halt keeps going up to the program
+            signal => {
+                self.signal = signal;
+                Ok(serde_json::Value::Null)
             }
         }
     }
@@ -872,10 +1828,7 @@ impl BrainSimulator {
         self.state.thoughts.push(format!("Performing action: {}", description));
 
         // Track the action in working memory
-        self.state.working_memory.push(description.clone());
-        if self.state.working_memory.len() > 7 {
-            self.state.working_memory.remove(0);
-        }
+        self.remember(description.clone());
 
         // Physical actions create mild satisfaction
         *self.state.emotions.entry("focus".to_string()).or_insert(0.0) += 0.2;
@@ -894,6 +1847,59 @@ impl Default for BrainSimulator {
     }
 }
 
+impl Simulator for BrainSimulator {
+    fn name(&self) -> &'static str {
+        "brain"
+    }
+
+    fn execute(&mut self, program: &Program) -> Result<()> {
+        BrainSimulator::execute(self, program)
+    }
+
+    fn snapshot(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(&self.state)?)
+    }
+
+    fn restore(&mut self, snapshot: serde_json::Value) -> Result<()> {
+        self.state = serde_json::from_value(snapshot)?;
+        Ok(())
+    }
+
+    fn value(&self, name: &str) -> Option<serde_json::Value> {
+        self.state.beliefs.get(name).cloned()
+    }
+
+    fn output(&self) -> &[String] {
+        &self.state.output
+    }
+
+    fn reset(&mut self) {
+        self.state = BrainState::new();
+        self.interrupt_handlers.clear();
+        self.triggers.clear();
+        self.recursion_depth = 0;
+        self.path.clear();
+        self.writes.clear();
+        self.frames.clear();
+        self.attended = 0;
+        self.loop_depth = 0;
+        self.signal = None;
+    }
+
+    fn trim_history(&mut self, limit: usize) {
+        let state = &mut self.state;
+        let dropped = keep_last(&mut state.output, limit);
+        state.provenance.forget_output(dropped);
+        state.provenance.keep_last(limit);
+        keep_last(&mut state.thoughts, limit);
+        keep_last(&mut state.trace, limit);
+    }
+
+    fn display(&self) -> String {
+        self.state.display()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -942,5 +1948,776 @@ mod tests {
 
         assert!(!brain.state.thoughts.is_empty());
     }
-}
 
+    #[test]
+    fn test_simultaneous_group_is_one_atomic_step() {
+        let chord = Program::from_json(r#"{"actions": [
+            {"actor": "Piano", "op": "Emit", "target": "C4", "t": 0},
+            {"actor": "Piano", "op": "Emit", "target": "E4", "t": 0},
+            {"actor": "Piano", "op": "Emit", "target": "G4", "t": 1}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&chord).unwrap();
+        assert_eq!(brain.state.trace, vec!["t=0 [Emit(C4) ‖ Emit(E4)]", "Emit(G4)"]);
+
+        // The second action fails, so the first one's belief is rolled back too
+        let failing = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "x", "t": 2, "params": {"value": 1}},
+            {"actor": "you", "op": "Bind", "target": "y", "t": 2, "params": {"value": {"var": "missing"}}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        assert!(brain.execute(&failing).is_err());
+        assert!(brain.state.beliefs.is_empty());
+        assert!(brain.state.trace.is_empty());
+    }
+
+    #[test]
+    fn test_on_interrupt_runs_when_flag_is_set() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "OnInterrupt", "target": "cleanup",
+             "body": [{"actor": "you", "op": "Emit", "target": "bye", "params": {"content": "Cleaning up"}}]},
+            {"actor": "you", "op": "Emit", "target": "hi", "params": {"content": "Working"}}
+        ]}"#).unwrap();
+
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut brain = BrainSimulator::new().with_interrupt(Arc::clone(&flag));
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.output, vec!["Working"]);
+
+        // The first run finished, so its handler is gone
+        assert!(brain.interrupt_handlers.is_empty());
+        flag.store(true, Ordering::SeqCst);
+        let err = brain.execute(&program).unwrap_err();
+        assert_eq!(err.to_string(), "Interrupted");
+        assert!(matches!(err.downcast_ref(), Some(UclError::Interrupted)));
+        assert_eq!(brain.state.output, vec!["Working"]);
+
+        // A handler registered in the run it interrupts runs once
+        let failing = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "OnInterrupt", "target": "cleanup",
+             "body": [{"actor": "you", "op": "Emit", "target": "bye", "params": {"content": "Cleaning up"}}]},
+            {"actor": "you", "op": "Bind", "target": "x", "params": {"value": {"var": "missing"}}}
+        ]}"#).unwrap();
+        let mut brain = BrainSimulator::new();
+        assert!(brain.execute(&failing).is_err());
+        assert_eq!(brain.state.output, vec!["Cleaning up"]);
+        assert!(brain.interrupt_handlers.is_empty());
+
+        // One registered in a simultaneous group that fails is rolled back with it
+        let group = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "OnInterrupt", "target": "cleanup", "t": 0,
+             "body": [{"actor": "you", "op": "Emit", "target": "bye", "params": {"content": "Cleaning up"}}]},
+            {"actor": "you", "op": "Bind", "target": "x", "t": 0, "params": {"value": {"var": "missing"}}}
+        ]}"#).unwrap();
+        let mut brain = BrainSimulator::new();
+        assert!(brain.execute(&group).is_err());
+        assert!(brain.state.output.is_empty());
+    }
+
+    #[test]
+    fn test_errors_have_kinds() {
+        let kind = |json: &str| {
+            // A low limit keeps runaway recursion quick to hit
+            let mut brain = BrainSimulator::new().with_max_recursion_depth(20);
+            let error = brain.execute(&Program::from_json(json).unwrap()).unwrap_err();
+            format!("{:?}", error.downcast_ref::<UclError>().unwrap())
+        };
+
+        assert_eq!(kind(r#"{"actions": [{"actor": "you", "op": "While", "target": "forever",
+            "condition": {"type": "comparison", "op": "==", "left": 1, "right": 1}}]}"#),
+            "LoopLimit { iterations: 10000 }");
+        assert_eq!(kind(r#"{"actions": [{"actor": "you", "op": "If", "target": "check"}]}"#),
+            r#"MissingParam { op: "If", param: "condition" }"#);
+        assert_eq!(kind(r#"{"actions": [
+            {"actor": "you", "op": "DefineFunction", "target": "f", "params": {"args": [],
+             "body": [{"actor": "you", "op": "Bind", "target": "x", "params": {"value": {"call": "f", "args": {}}}}]}},
+            {"actor": "you", "op": "Bind", "target": "y", "params": {"value": {"call": "f", "args": {}}}}
+        ]}"#), "RecursionLimit { depth: 20 }");
+        assert_eq!(kind(r#"{"actions": [{"actor": "you", "op": "Bind", "target": "x",
+            "params": {"value": {"unary": {"op": "-", "operand": "tea"}}}}]}"#),
+            r#"InvalidValue { message: "Can't negate \"tea\"" }"#);
+        assert_eq!(kind(r#"{"actions": [{"actor": "you", "op": "Bind", "target": "x",
+            "params": {"value": {"unary": {"op": "~", "operand": 1}}}}]}"#),
+            r#"UnsupportedOperation { op: "unary operator '~'", target: "simulator" }"#);
+        assert_eq!(kind(r#"{"actions": [{"actor": "you", "op": "Bind", "target": "x", "params": {"value": {"var": "nope"}}}]}"#),
+            r#"UndefinedVariable { name: "nope" }"#);
+    }
+
+    #[test]
+    fn test_deep_recursion_only_hits_the_configured_limit() {
+        // sum(n) = n + sum(n - 1), far deeper than a test thread's stack allows natively
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "DefineFunction", "target": "sum", "params": {"args": ["n"], "body": [
+                {"actor": "you", "op": "If", "target": "base", "condition": {"type": "comparison", "op": "<=", "left": {"var": "n"}, "right": 0},
+                 "then": [{"actor": "you", "op": "Return", "target": "r", "params": {"value": 0}}]},
+                {"actor": "you", "op": "Return", "target": "r", "params": {"value": {"expr": {"op": "+", "left": {"var": "n"},
+                 "right": {"call": "sum", "args": {"n": {"expr": {"op": "-", "left": {"var": "n"}, "right": 1}}}}}}}}]}},
+            {"actor": "you", "op": "Bind", "target": "total", "params": {"value": {"call": "sum", "args": {"n": 1500}}}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["total"].as_f64(), Some(1125750.0));
+
+        let mut brain = BrainSimulator::new().with_max_recursion_depth(1000);
+        let err = brain.execute(&program).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(UclError::RecursionLimit { depth: 1000 })));
+    }
+
+    #[test]
+    fn test_state_carries_over_between_runs() {
+        let path = std::env::temp_dir().join(format!("ucl-brain-state-{}.json", std::process::id()));
+        let learn = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "name", "params": {"value": "Ada"}},
+            {"actor": "you", "op": "DefineFunction", "target": "greet", "params": {"args": [], "body": [
+                {"actor": "you", "op": "Return", "target": "r", "params": {"value": {"format": "hi {name}"}}}]}}
+        ]}"#).unwrap();
+        let mut brain = BrainSimulator::new();
+        brain.execute(&learn).unwrap();
+        brain.save_state(&path).unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["trace"], serde_json::json!([]));
+        assert_eq!(saved["provenance"]["records"], serde_json::json!([]));
+
+        let recall = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "said", "params": {"value": {"call": "greet", "args": {}}}}
+        ]}"#).unwrap();
+        let mut brain = BrainSimulator::new();
+        brain.load_state(&path).unwrap();
+        brain.execute(&recall).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(brain.state.beliefs["said"], serde_json::json!("hi Ada"));
+        // The trace holds only this run's Bind and the Return it called
+        assert_eq!(brain.state.trace.len(), 2);
+
+        let err = brain.load_state(&path).unwrap_err();
+        assert!(err.to_string().starts_with("Reading"), "{}", err);
+    }
+
+    #[test]
+    fn test_forgetting_unused_beliefs() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "a", "params": {"value": 1}},
+            {"actor": "you", "op": "Bind", "target": "b", "params": {"value": 2}},
+            {"actor": "you", "op": "Wait", "target": "a while", "dur": 100},
+            {"actor": "you", "op": "Read", "target": "a"},
+            {"actor": "you", "op": "Wait", "target": "a bit longer", "dur": 30}
+        ]}"#).unwrap();
+
+        // Without forgetting the brain is a plain store
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert!(brain.state.beliefs.contains_key("b"));
+        assert!(brain.state.memory.beliefs.is_empty());
+
+        // b fades unused for 130s, past two half-lives; recalling a at 100s kept it
+        let mut brain = BrainSimulator::new().with_forgetting(Forgetting::default());
+        brain.execute(&program).unwrap();
+        assert!(brain.state.beliefs.contains_key("a"));
+        assert!(!brain.state.beliefs.contains_key("b"));
+        assert!(brain.state.thoughts.contains(&"Forgot: b".to_string()));
+        assert!(brain.state.provenance.value("b").is_none());
+        assert_eq!(brain.state.memory.clock, 130.0);
+    }
+
+    #[test]
+    fn test_working_memory_keeps_salient_items() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": {"Custom": "Panic"}, "target": "smoke"},
+            {"actor": "you", "op": "Receive", "target": "fire!"},
+            {"actor": "you", "op": {"Custom": "Calm"}, "target": "breath"},
+            {"actor": "you", "op": "Receive", "target": "a"},
+            {"actor": "you", "op": "Receive", "target": "b"}
+        ]}"#).unwrap();
+
+        let profile = CognitiveProfile { working_memory: 2, ..CognitiveProfile::default() };
+        let mut brain = BrainSimulator::new().with_forgetting(Forgetting::default()).with_profile(profile);
+        brain.register("Panic", |_, state| {
+            state.emotions.insert("fear".to_string(), 2.0);
+            Ok(())
+        });
+        brain.register("Calm", |_, state| {
+            state.emotions.insert("fear".to_string(), 0.0);
+            Ok(())
+        });
+        brain.execute(&program).unwrap();
+
+        // The frightening item outlasts the newer, duller one
+        assert_eq!(brain.state.working_memory, vec!["Heard: fire!", "Heard: b"]);
+    }
+
+    #[test]
+    fn test_distracted_brain_slips_and_loses_focus() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Receive", "target": "the lecture"},
+            {"actor": "you", "op": "Bind", "target": "total", "params": {"value": {"expr": {"op": "+", "left": 40, "right": 60}}}},
+            {"actor": "you", "op": "Bind", "target": "x", "params": {"value": 1}},
+            {"actor": "you", "op": "Bind", "target": "y", "params": {"value": 2}},
+            {"actor": "you", "op": "Receive", "target": "a"},
+            {"actor": "you", "op": "Receive", "target": "b"},
+            {"actor": "you", "op": "Receive", "target": "c"},
+            {"actor": "you", "op": "Receive", "target": "d"},
+            {"actor": "you", "op": "Receive", "target": "e"}
+        ]}"#).unwrap();
+
+        let mut focused = BrainSimulator::new().with_seed(7);
+        focused.execute(&program).unwrap();
+        assert_eq!(focused.state.beliefs["total"], serde_json::json!(100.0));
+        assert_eq!(focused.state.working_memory.len(), 6);
+        assert_eq!(focused.state.attention.as_deref(), Some("e"));
+
+        let mut distracted = BrainSimulator::new().with_seed(7).with_profile(CognitiveProfile::distracted());
+        distracted.execute(&program).unwrap();
+        let total = distracted.state.beliefs["total"].as_f64().unwrap();
+        assert!(total != 100.0 && (total - 100.0).abs() < 50.0, "{}", total);
+        assert_eq!(distracted.state.working_memory, vec!["Heard: b", "Heard: c", "Heard: d", "Heard: e"]);
+        // Three actions after hearing the lecture, the mind wanders; plain values aren't misremembered
+        assert!(distracted.state.thoughts.contains(&"Mind wandered from: the lecture".to_string()));
+        assert_eq!(distracted.state.beliefs["y"], serde_json::json!(2));
+        assert_eq!(distracted.state.attention.as_deref(), Some("e"));
+    }
+
+    #[test]
+    fn test_sleep_consolidates_and_dreams() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Receive", "target": "the news"},
+            {"actor": "you", "op": "Oblige", "target": "work", "params": {"duty": "call mum"}},
+            {"actor": "you", "op": "Bind", "target": "cat", "params": {"value": "black"}},
+            {"actor": "you", "op": "Bind", "target": "sky", "params": {"value": "blue"}},
+            {"actor": "you", "op": "Sleep", "target": "the day"},
+            {"actor": "you", "op": "Dream", "target": "the day", "params": {"count": 2}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new().with_seed(1);
+        brain.execute(&program).unwrap();
+        assert!(brain.state.working_memory.is_empty());
+        assert_eq!(brain.state.beliefs[sleep::LONG_TERM], serde_json::json!(["Heard: the news"]));
+        assert_eq!(brain.state.emotions["responsibility"], 0.25);
+        assert_eq!(brain.state.attention, None);
+        assert!(brain.state.thoughts.contains(&"Slept on the day, consolidating 1 memories".to_string()));
+        let dreams = brain.state.thoughts.iter().filter(|t| t.starts_with("Dreamt that")).count();
+        assert_eq!(dreams, 2);
+    }
+
+    #[test]
+    fn test_knowledge_graph_queries() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "StoreFact", "target": "cat", "params": {"chases": "mouse", "color": "black"}},
+            {"actor": "you", "op": "StoreFact", "target": "cat", "params": {"chases": "bird"}},
+            {"actor": "you", "op": "StoreFact", "target": "mouse", "params": {"eats": "cheese"}},
+            {"actor": "you", "op": "Read", "target": "*.chases", "params": {"into": "chasing"}},
+            {"actor": "you", "op": "Read", "target": "cat", "params": {"follow": "*", "into": "chain"}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        // The flat belief keeps only the latest chase; the graph keeps both
+        assert_eq!(brain.state.beliefs["cat.chases"], serde_json::json!("bird"));
+        let chased: Vec<&serde_json::Value> = brain.state.beliefs["chasing"].as_array().unwrap().iter().map(|t| &t["object"]).collect();
+        assert_eq!(chased, vec!["mouse", "bird"]);
+        let chain = brain.state.beliefs["chain"].as_array().unwrap();
+        assert!(chain.contains(&serde_json::json!("cheese")) && chain.contains(&serde_json::json!("black")), "{:?}", chain);
+        assert!(brain.state.display().contains("cat chases bird"));
+    }
+
+    #[test]
+    fn test_imagining_other_minds() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "StoreFact", "target": "cat", "params": {"color": "white"}},
+            {"actor": "you", "op": "Imagine", "target": "Alice", "body": [
+                {"actor": "Alice", "op": "StoreFact", "target": "cat", "params": {"color": "black"}},
+                {"actor": "Alice", "op": "Imagine", "target": "Bob", "body": [
+                    {"actor": "Bob", "op": "Bind", "target": "mood", "params": {"value": "grumpy"}}
+                ]}
+            ]},
+            {"actor": "you", "op": "Imagine", "target": "Alice", "params": {"ask": "cat.color", "into": "alice_thinks"}, "body": []},
+            {"actor": "you", "op": "Imagine", "target": "Alice", "params": {"ask": "cat.color"}, "body": []}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        // Alice's mind carries over between Imagines and doesn't leak into ours
+        assert_eq!(brain.state.beliefs["cat.color"], serde_json::json!("white"));
+        assert_eq!(brain.state.beliefs["alice_thinks"], serde_json::json!("black"));
+        assert_eq!(brain.state.beliefs["Alice.cat.color"], serde_json::json!("black"));
+        let colors: Vec<&serde_json::Value> = brain.state.knowledge.matching("cat.color").iter().map(|t| &t.object).collect();
+        assert_eq!(colors, vec![&serde_json::json!("white")]);
+        assert_eq!(brain.state.minds["Alice"].minds["Bob"].beliefs["mood"], serde_json::json!("grumpy"));
+        assert!(brain.state.display().contains("Alice believes: cat.color = \"black\""));
+
+        let failing = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Imagine", "target": "Carol", "body": [{"actor": "Carol", "op": "Throw", "target": "tantrum"}]}
+        ]}"#).unwrap();
+        let err = BrainSimulator::new().execute(&failing).unwrap_err();
+        assert!(err.to_string().contains("Imagining Carol's mind"), "{}", err);
+    }
+
+    #[test]
+    fn test_namespace_handler() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": {"Custom": "music:Arpeggiate"}, "target": "C", "params": {"chord": "Cmaj"}},
+            {"actor": "you", "op": {"Custom": "music:Fail"}, "target": "x"}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.register_namespace("music", |action, state| {
+            if action.op == Operation::Custom("music:Fail".into()) {
+                return Err(anyhow!("out of tune"));
+            }
+            state.output.push(format!("arpeggio {}", action.params.as_ref().unwrap()["chord"]));
+            Ok(())
+        });
+        let err = brain.execute(&program).unwrap_err();
+
+        assert_eq!(brain.state.output, vec!["arpeggio \"Cmaj\""]);
+        assert!(format!("{:#}", err).contains("music:Fail: out of tune"));
+        assert!(!brain.state.emotions.contains_key("confusion"));
+    }
+
+    #[test]
+    fn test_registered_handler() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": {"Custom": "Meditate"}, "target": "breath", "params": {"minutes": 5}},
+            {"actor": "you", "op": {"Custom": "music:Hum"}, "target": "tune"},
+            {"actor": "you", "op": {"Custom": "Juggle"}, "target": "balls"}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.register("Meditate", |action, state| {
+            state.emotions.insert("calm".to_string(), action.params.as_ref().unwrap()["minutes"].as_f64().unwrap() / 10.0);
+            Ok(())
+        });
+        brain.register_namespace("music", |_, state| {
+            state.output.push("namespace".to_string());
+            Ok(())
+        });
+        brain.register("music:Hum", |_, state| {
+            state.output.push("hmm".to_string());
+            Ok(())
+        });
+        brain.execute(&program).unwrap();
+
+        assert_eq!(brain.state.emotions["calm"], 0.5);
+        assert_eq!(brain.state.output, vec!["hmm", "I'm not sure what you mean..."]);
+        assert!(brain.state.emotions.contains_key("confusion"));
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "sum", "params": {"value": 0}},
+            {"actor": "you", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": 10, "body": [
+                {"actor": "you", "op": "If", "target": "even", "condition": {"type": "comparison", "op": "<", "left": {"expr": {"op": "%", "left": {"var": "i"}, "right": 2}}, "right": 1},
+                 "then": [{"actor": "you", "op": "Continue", "target": "loop"}]},
+                {"actor": "you", "op": "If", "target": "done", "condition": {"type": "comparison", "op": ">", "left": {"var": "i"}, "right": 6},
+                 "then": [{"actor": "you", "op": "Break", "target": "loop"}]},
+                {"actor": "you", "op": "Bind", "target": "sum", "params": {"value": {"expr": {"op": "+", "left": {"var": "sum"}, "right": {"var": "i"}}}}}
+            ]},
+            {"actor": "you", "op": "Bind", "target": "n", "params": {"value": 0}},
+            {"actor": "you", "op": "While", "target": "count", "condition": {"type": "comparison", "op": "<", "left": 0, "right": 1}, "body": [
+                {"actor": "you", "op": "Bind", "target": "n", "params": {"value": {"expr": {"op": "+", "left": {"var": "n"}, "right": 1}}}},
+                {"actor": "you", "op": "If", "target": "enough", "condition": {"type": "comparison", "op": ">=", "left": {"var": "n"}, "right": 3},
+                 "then": [{"actor": "you", "op": "Break", "target": "count"}]}
+            ]}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        // 1 + 3 + 5, stopping at 7
+        assert_eq!(brain.state.beliefs["sum"].as_f64(), Some(9.0));
+        assert_eq!(brain.state.beliefs["i"], serde_json::json!(7));
+        assert_eq!(brain.state.beliefs["n"].as_f64(), Some(3.0));
+
+        // A function body can't break the loop it's called from
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "DefineFunction", "target": "stop", "params": {"args": [], "body": [
+                {"actor": "you", "op": "Break", "target": "loop"}]}},
+            {"actor": "you", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": 3, "body": [
+                {"actor": "you", "op": "Bind", "target": "x", "params": {"value": {"call": "stop", "args": {}}}}
+            ]}
+        ]}"#).unwrap();
+        let err = BrainSimulator::new().execute(&program).unwrap_err();
+        assert!(format!("{:#}", err).contains("Break outside a loop"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_unary_and_truth_values() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "x", "params": {"value": 5}},
+            {"actor": "you", "op": "Bind", "target": "neg", "params": {"value": {"unary": {"op": "-", "operand": {"var": "x"}}}}},
+            {"actor": "you", "op": "Bind", "target": "big", "params": {"value": {"test": {"type": "comparison", "op": ">", "left": {"var": "x"}, "right": 3}}}},
+            {"actor": "you", "op": "Bind", "target": "small", "params": {"value": {"unary": {"op": "!", "operand": {"var": "big"}}}}},
+            {"actor": "you", "op": "If", "target": "check", "condition": {"type": "expression", "value": {"var": "big"}},
+             "then": [{"actor": "you", "op": "Bind", "target": "seen", "params": {"value": "big"}}]},
+            {"actor": "you", "op": "If", "target": "zero", "condition": {"type": "expression", "value": 0},
+             "then": [{"actor": "you", "op": "Bind", "target": "zero_is_true", "params": {"value": true}}]}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["neg"], serde_json::json!(-5));
+        assert_eq!(brain.state.beliefs["big"], serde_json::json!(true));
+        assert_eq!(brain.state.beliefs["small"], serde_json::json!(false));
+        assert_eq!(brain.state.beliefs["seen"], serde_json::json!("big"));
+        assert_eq!(brain.state.beliefs["zero_is_true"], serde_json::json!(true));
+    }
+
+    #[test]
+    fn test_actors_converse_over_channels() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "Alice", "op": "Bind", "target": "n", "params": {"value": 20}},
+            {"actor": "Alice", "op": "Emit", "target": "question", "params": {"content": {"var": "n"}}},
+            {"actor": "Alice", "op": "Receive", "target": "question"},
+            {"actor": "Bob", "op": "Receive", "target": "question", "params": {"into": "asked"}},
+            {"actor": "Bob", "op": "Emit", "target": "answer", "params": {"content": {"expr": {"op": "*", "left": {"var": "asked"}, "right": 2}}, "destination": "Alice"}},
+            {"actor": "Alice", "op": "Receive", "target": "answer"}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["asked"], serde_json::json!(20));
+        assert_eq!(brain.state.beliefs["answer"].as_f64(), Some(40.0));
+        assert!(brain.state.working_memory.contains(&"Heard from Bob: 40.0".to_string()), "{:?}", brain.state.working_memory);
+        assert!(brain.state.channels.is_empty());
+    }
+
+    #[test]
+    fn test_when_fires_on_registration_and_after_nested_actions() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "hungry", "params": {"value": true}},
+            {"actor": "you", "op": "When", "target": "eat", "condition": {"type": "expression", "value": {"var": "hungry"}},
+             "body": [{"actor": "you", "op": "Append", "target": "meals", "params": {"value": "snack"}}]},
+            {"actor": "you", "op": "When", "target": "bored", "condition": {"type": "exists", "var": "done"},
+             "body": [{"actor": "you", "op": "Bind", "target": "hungry", "params": {"value": false}}]},
+            {"actor": "you", "op": "If", "target": "check", "condition": {"type": "comparison", "op": ">", "left": 1, "right": 0},
+             "then": [{"actor": "you", "op": "Bind", "target": "done", "params": {"value": true}}]}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["meals"], serde_json::json!(["snack"]));
+        assert_eq!(brain.state.beliefs["hungry"], serde_json::json!(false));
+
+        let bad = Program::from_json(r#"{"actions": [{"actor": "you", "op": "When", "target": "x", "body": []}]}"#).unwrap();
+        assert!(brain.execute(&bad).is_err());
+    }
+
+    #[test]
+    fn test_exists_probes_without_reading() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "name", "params": {"value": "Ada"}},
+            {"actor": "you", "op": "If", "target": "greet", "condition": {"type": "exists", "var": "nickname"},
+             "then": [{"actor": "you", "op": "Bind", "target": "call", "params": {"value": {"var": "nickname"}}}],
+             "else": [{"actor": "you", "op": "Bind", "target": "call", "params": {"value": {"var": "name"}}}]}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["call"], serde_json::json!("Ada"));
+    }
+
+    #[test]
+    fn test_text_conditions() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "said", "params": {"value": "Hello, order #42 please"}},
+            {"actor": "you", "op": "If", "target": "order", "condition": {"type": "matches", "value": {"var": "said"}, "pattern": "order #[0-9]+"},
+             "then": [{"actor": "you", "op": "Bind", "target": "ordered", "params": {"value": true}}]},
+            {"actor": "you", "op": "If", "target": "greeting", "condition": {"type": "starts_with", "value": {"var": "said"}, "prefix": "Hello"},
+             "then": [{"actor": "you", "op": "Bind", "target": "greeted", "params": {"value": true}}]},
+            {"actor": "you", "op": "If", "target": "polite", "condition": {"type": "contains", "value": {"var": "said"}, "part": "thanks"},
+             "then": [{"actor": "you", "op": "Bind", "target": "thanked", "params": {"value": true}}]}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["ordered"], serde_json::json!(true));
+        assert_eq!(brain.state.beliefs["greeted"], serde_json::json!(true));
+        assert!(!brain.state.beliefs.contains_key("thanked"));
+
+        let bad = Program::from_json(r#"{"actions": [{"actor": "you", "op": "If", "target": "x",
+            "condition": {"type": "matches", "value": "a", "pattern": "("}, "then": []}]}"#).unwrap();
+        assert!(format!("{:#}", brain.execute(&bad).unwrap_err()).contains("Bad pattern"));
+    }
+
+    #[test]
+    fn test_string_functions_and_format() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "x", "params": {"value": 42}},
+            {"actor": "you", "op": "Bind", "target": "name", "params": {"value": "Tea"}},
+            {"actor": "you", "op": "Bind", "target": "shout", "params": {"value": {"string": {"op": "upper", "args": [{"var": "name"}]}}}},
+            {"actor": "you", "op": "Bind", "target": "label", "params": {"value": {"string": {"op": "concat", "args": [{"var": "shout"}, " #", {"var": "x"}]}}}},
+            {"actor": "you", "op": "Bind", "target": "hot", "params": {"value": {"string": {"op": "contains", "args": [{"var": "label"}, "TEA"]}}}},
+            {"actor": "you", "op": "Emit", "target": "answer", "params": {"content": {"format": "The answer is {x}, {name}"}}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["label"], serde_json::json!("TEA #42"));
+        assert_eq!(brain.state.beliefs["hot"], serde_json::json!(true));
+        assert_eq!(brain.state.output.last().unwrap(), "The answer is 42, Tea");
+
+        let missing = Program::from_json(r#"{"actions": [{"actor": "you", "op": "Emit", "target": "m", "params": {"content": {"format": "{nope}"}}}]}"#).unwrap();
+        assert!(format!("{:#}", brain.execute(&missing).unwrap_err()).contains("Variable not found: nope"));
+    }
+
+    #[test]
+    fn test_for_each_over_keys_and_state() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "ages", "params": {"value": {"bob": 40, "amy": 30}}},
+            {"actor": "you", "op": "ForEach", "target": "names", "variable": "name", "params": {"in": {"var": "ages"}}, "body": [
+                {"actor": "you", "op": "Append", "target": "people", "params": {"value": {"var": "name"}}}
+            ]},
+            {"actor": "you", "op": "Decide", "target": "plan", "params": {"goal": "rest"}},
+            {"actor": "you", "op": "ForEach", "target": "review", "variable": "goal", "params": {"over": "goals"}, "body": [
+                {"actor": "you", "op": "Append", "target": "reviewed", "params": {"value": {"var": "goal"}}}
+            ]}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["people"], serde_json::json!(["amy", "bob"]));
+        assert_eq!(brain.state.beliefs["reviewed"], serde_json::json!(["rest"]));
+
+        let bad = Program::from_json(r#"{"actions": [{"actor": "you", "op": "ForEach", "target": "x", "variable": "y", "params": {"over": "dreams"}, "body": []}]}"#).unwrap();
+        assert!(format!("{:#}", brain.execute(&bad).unwrap_err()).contains("the brain has beliefs, emotions"));
+    }
+
+    #[test]
+    fn test_lists() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "n", "params": {"value": 5}},
+            {"actor": "you", "op": "Bind", "target": "xs", "params": {"value": {"list": [1, {"var": "n"}, {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}]}}},
+            {"actor": "you", "op": "Append", "target": "xs", "params": {"value": {"length": {"var": "xs"}}}},
+            {"actor": "you", "op": "Bind", "target": "sum", "params": {"value": 0}},
+            {"actor": "you", "op": "ForEach", "target": "loop", "variable": "x", "params": {"in": {"var": "xs"}}, "body": [
+                {"actor": "you", "op": "Append", "target": "seen", "params": {"value": {"var": "x"}}},
+                {"actor": "you", "op": "Bind", "target": "sum", "params": {"value": {"expr": {"op": "+", "left": {"var": "sum"}, "right": {"var": "x"}}}}}
+            ]},
+            {"actor": "you", "op": "Bind", "target": "last", "params": {"value": {"index": {"var": "xs"}, "at": -1}}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["xs"], serde_json::json!([1, 5, 10.0, 3]));
+        assert_eq!(brain.state.beliefs["seen"], brain.state.beliefs["xs"]);
+        assert_eq!(brain.state.beliefs["sum"].as_f64(), Some(19.0));
+        assert_eq!(brain.state.beliefs["last"], serde_json::json!(3));
+
+        let bad = Program::from_json(r#"{"actions": [{"actor": "you", "op": "Bind", "target": "y", "params": {"value": {"index": {"var": "xs"}, "at": 9}}}]}"#).unwrap();
+        let err = brain.execute(&bad).unwrap_err();
+        assert!(format!("{:#}", err).contains("out of range"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_match_runs_first_matching_arm() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "For", "target": "loop", "variable": "n", "from": 1, "to": 4, "body": [
+                {"actor": "you", "op": "Match", "target": "kind", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}, "cases": [
+                    {"value": 2, "then": [{"actor": "you", "op": "Bind", "target": "two", "params": {"value": {"var": "n"}}}]},
+                    {"when": {"type": "comparison", "op": ">", "left": {"var": "n"}, "right": 2}, "then": [
+                        {"actor": "you", "op": "Bind", "target": "big", "params": {"value": {"var": "n"}}}]},
+                    {"value": 8, "then": [{"actor": "you", "op": "Bind", "target": "unreachable", "params": {"value": true}}]}
+                ]}, "else": [{"actor": "you", "op": "Bind", "target": "other", "params": {"value": {"var": "n"}}}]}
+            ]}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["two"], serde_json::json!(1));
+        assert_eq!(brain.state.beliefs["other"], serde_json::json!(2));
+        assert_eq!(brain.state.beliefs["big"], serde_json::json!(4));
+        assert!(!brain.state.beliefs.contains_key("unreachable"));
+
+        let missing = Program::from_json(r#"{"actions": [{"actor": "you", "op": "Match", "target": "kind"}]}"#).unwrap();
+        assert!(brain.execute(&missing).is_err());
+    }
+
+    #[test]
+    fn test_try_catches_throws_and_failures() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Try", "target": "risky", "body": [
+                {"actor": "you", "op": "Bind", "target": "before", "params": {"value": 1}},
+                {"actor": "you", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": 5, "body": [
+                    {"actor": "you", "op": "Throw", "target": "oops", "params": {"value": {"expr": {"op": "*", "left": {"var": "i"}, "right": 10}}}}
+                ]},
+                {"actor": "you", "op": "Bind", "target": "after", "params": {"value": 1}}
+            ], "catch": [
+                {"actor": "you", "op": "Bind", "target": "caught", "params": {"value": {"var": "error"}}}
+            ]},
+            {"actor": "you", "op": "Try", "target": "call", "params": {"error": "problem"}, "body": [
+                {"actor": "you", "op": "Bind", "target": "x", "params": {"value": {"call": "missing", "args": {}}}}
+            ], "catch": []}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["before"], serde_json::json!(1));
+        assert!(!brain.state.beliefs.contains_key("after"));
+        assert_eq!(brain.state.beliefs["caught"].as_f64(), Some(10.0));
+        assert!(brain.state.beliefs["problem"].as_str().unwrap().contains("missing"), "{}", brain.state.beliefs["problem"]);
+        assert_eq!(brain.recursion_depth, 0);
+        // The failed actions' path segments are gone by the time the catch runs
+        assert_eq!(brain.state.provenance.value("caught").unwrap().action, "0.catch[0]");
+
+        let uncaught = Program::from_json(r#"{"actions": [{"actor": "you", "op": "Throw", "target": "nope"}]}"#).unwrap();
+        let err = brain.execute(&uncaught).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(UclError::Thrown { value }) if value == "nope"));
+        assert_eq!(err.to_string(), "Uncaught throw: nope");
+    }
+
+    #[test]
+    fn test_function_calls_get_their_own_frames() {
+        // sum(n) adds up n, n-1, … 1 with a loop variable and a recursive call
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "DefineFunction", "target": "sum", "params": {"args": ["n"], "body": [
+                {"actor": "you", "op": "Bind", "target": "total", "params": {"value": 0}},
+                {"actor": "you", "op": "If", "target": "more", "condition": {"type": "comparison", "op": ">", "left": {"var": "n"}, "right": 0},
+                 "then": [
+                    {"actor": "you", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": 1,
+                     "body": [{"actor": "you", "op": "Bind", "target": "total", "params": {"value": {"var": "n"}}}]},
+                    {"actor": "you", "op": "Bind", "target": "rest", "params": {"value": {"call": "sum", "args": {"n": {"expr": {"op": "-", "left": {"var": "n"}, "right": 1}}}}}},
+                    {"actor": "you", "op": "Bind", "target": "total", "params": {"value": {"expr": {"op": "+", "left": {"var": "total"}, "right": {"var": "rest"}}}}},
+                    {"actor": "you", "op": "Append", "target": "seen", "params": {"value": {"var": "i"}}}
+                 ]},
+                {"actor": "you", "op": "Return", "target": "r", "params": {"value": {"var": "total"}}}]}},
+            {"actor": "you", "op": "Bind", "target": "i", "params": {"value": "global"}},
+            {"actor": "you", "op": "Bind", "target": "seen", "params": {"value": []}},
+            {"actor": "you", "op": "Bind", "target": "result", "params": {"value": {"call": "sum", "args": {"n": 3}}}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["result"].as_f64(), Some(6.0));
+        // The loop variable, arguments and locals stayed in their frames
+        assert_eq!(brain.state.beliefs["i"], serde_json::json!("global"));
+        for local in ["n", "total", "rest"] {
+            assert!(!brain.state.beliefs.contains_key(local), "{} leaked", local);
+        }
+        // Appending to a belief from inside a call still changes the belief
+        assert_eq!(brain.state.beliefs["seen"], serde_json::json!([1, 1, 1]));
+    }
+
+    #[test]
+    fn test_call_statements_run_defined_functions() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "greetings", "params": {"value": []}},
+            {"actor": "you", "op": "DefineFunction", "target": "greet", "params": {"args": ["name"], "body": [
+                {"actor": "you", "op": "Append", "target": "greetings", "params": {"value": {"var": "name"}}},
+                {"actor": "you", "op": "Return", "target": "r", "params": {"value": {"format": "hi {name}"}}}]}},
+            {"actor": "you", "op": "Bind", "target": "who", "params": {"value": "Ada"}},
+            {"actor": "you", "op": "Call", "target": "greet", "params": {"name": {"var": "who"}, "out": "said"}},
+            {"actor": "you", "op": "Call", "target": "greet", "params": {"name": "Bob"}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.bind_fn("greet", |_| Ok(serde_json::json!("host")));
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["greetings"], serde_json::json!(["Ada", "Bob"]));
+        assert_eq!(brain.state.beliefs["said"], serde_json::json!("hi Ada"));
+        assert!(!brain.state.beliefs.contains_key("name"));
+    }
+
+    #[test]
+    fn test_return_and_halt_from_nested_blocks() {
+        // find(xs, x) returns the first index of x from inside a loop
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "DefineFunction", "target": "find", "params": {"args": ["xs", "x"], "body": [
+                {"actor": "you", "op": "For", "target": "loop", "variable": "i", "from": 0, "to": 9, "body": [
+                    {"actor": "you", "op": "If", "target": "found",
+                     "condition": {"type": "comparison", "op": "==", "left": {"var": "i"}, "right": {"var": "x"}},
+                     "then": [{"actor": "you", "op": "Return", "target": "r", "params": {"value": {"var": "i"}}}]}]},
+                {"actor": "you", "op": "Return", "target": "r", "params": {"value": -1}}]}},
+            {"actor": "you", "op": "Bind", "target": "at", "params": {"value": {"call": "find", "args": {"xs": [], "x": 3}}}},
+            {"actor": "you", "op": "Bind", "target": "missing", "params": {"value": {"call": "find", "args": {"xs": [], "x": 42}}}},
+            {"actor": "you", "op": "While", "target": "forever", "condition": {"type": "comparison", "op": "==", "left": 1, "right": 1}, "body": [
+                {"actor": "you", "op": "Halt", "target": "stop", "params": {"status": 3}}]},
+            {"actor": "you", "op": "Bind", "target": "after", "params": {"value": true}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.beliefs["at"], serde_json::json!(3));
+        assert_eq!(brain.state.beliefs["missing"], serde_json::json!(-1));
+        assert_eq!(brain.state.halted, Some(3));
+        assert!(!brain.state.beliefs.contains_key("after"));
+        assert!(brain.state.display().contains("Halted with status 3"));
+
+        // Returning outside a function ends the program too, without a status
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Return", "target": "done"},
+            {"actor": "you", "op": "Bind", "target": "after", "params": {"value": true}}
+        ]}"#).unwrap();
+        brain.execute(&program).unwrap();
+        assert_eq!(brain.state.halted, None);
+        assert!(!brain.state.beliefs.contains_key("after"));
+    }
+
+    #[test]
+    fn test_bound_host_functions() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "x", "params": {"value": 4}},
+            {"actor": "you", "op": "Call", "target": "square", "params": {"n": {"var": "x"}, "out": "sq"}},
+            {"actor": "you", "op": "Bind", "target": "y", "params": {"value": {"call": "square", "args": {"n": 3}}}},
+            {"actor": "you", "op": "DefineFunction", "target": "square", "params": {"args": ["n"], "body": [
+                {"actor": "you", "op": "Return", "target": "r", "params": {"value": 0}}]}},
+            {"actor": "you", "op": "Bind", "target": "z", "params": {"value": {"call": "square", "args": {"n": 3}}}},
+            {"actor": "you", "op": "Call", "target": "fail", "params": {}}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.bind_fn("square", |args| {
+            let n = args["n"].as_f64().ok_or_else(|| anyhow!("n must be a number"))?;
+            Ok(serde_json::json!(n * n))
+        });
+        brain.bind_fn("fail", |_| Err(anyhow!("no network")));
+        let err = brain.execute(&program).unwrap_err();
+
+        assert_eq!(brain.state.beliefs["sq"], serde_json::json!(16.0));
+        assert_eq!(brain.state.beliefs["y"], serde_json::json!(9.0));
+        // The program's own definition wins once it exists
+        assert_eq!(brain.state.beliefs["z"], serde_json::json!(0));
+        assert!(format!("{:#}", err).contains("fail: no network"));
+    }
+
+    #[test]
+    fn test_provenance_tracks_nested_actions_and_inputs() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "x", "params": {"value": 2}},
+            {"actor": "you", "op": "Bind", "target": "seen", "params": {"value": []}},
+            {"actor": "you", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "you", "op": "Append", "target": "seen", "params": {"value": {"var": "n"}}},
+                {"actor": "you", "op": "Return", "target": "r", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}]}},
+            {"actor": "you", "op": "If", "target": "check",
+             "condition": {"type": "comparison", "op": ">", "left": {"var": "x"}, "right": 1},
+             "then": [{"actor": "you", "op": "Bind", "target": "y", "params": {"value": {"call": "double", "args": {"n": {"var": "x"}}}}}]},
+            {"actor": "you", "op": "Emit", "target": "y"}
+        ]}"#).unwrap();
+
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        let provenance = &brain.state.provenance;
+
+        assert_eq!(provenance.value("seen").unwrap().action, "3.then[0].double().body[0]");
+        assert_eq!(provenance.explain("y"), vec!["y ← 3.then[0] Bind(y)", "  x ← 0 Bind(x)"]);
+        assert_eq!(provenance.output(0).unwrap().inputs[0].name, "y");
+        assert!(brain.state.display().contains("x = 2  ← 0 Bind(x)"));
+    }
+
+    #[test]
+    fn test_seeded_random_ints_repeat() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "GenRandomInt", "target": "a", "params": {"min": 1, "max": 1000000}},
+            {"actor": "you", "op": "GenRandomInt", "target": "b", "params": {"min": 1, "max": 1000000}}
+        ]}"#).unwrap();
+        let draw = |seed| {
+            let mut brain = BrainSimulator::new().with_seed(seed);
+            brain.execute(&program).unwrap();
+            (brain.state.beliefs["a"].clone(), brain.state.beliefs["b"].clone())
+        };
+
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// On-disk cache of backend completions
+///
+/// Entries are keyed by instruction, model, temperature and the rendered
+/// prompt, so changing any of them (including a prompt template) misses.
+/// Each entry also stores all four, and a lookup only hits when they match,
+/// so two requests whose keys collide never share a completion.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    instruction: String,
+    model: String,
+    temperature: f64,
+    prompt: String,
+    completion: String,
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$UCL_AI_CACHE_DIR`, else `$XDG_CACHE_HOME/ucl/ai`, else `~/.cache/ucl/ai`
+    pub fn default_dir() -> PathBuf {
+        if let Ok(dir) = std::env::var("UCL_AI_CACHE_DIR") {
+            return PathBuf::from(dir);
+        }
+        let base = std::env::var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|_| std::env::var("HOME").map(|home| Path::new(&home).join(".cache")))
+            .unwrap_or_else(|_| std::env::temp_dir());
+        base.join("ucl").join("ai")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Name of the entry file for a request
+    pub fn key(instruction: &str, model: &str, temperature: f64, prompt: &str) -> String {
+        let material = format!("{}\u{0}{}\u{0}{}\u{0}{}", instruction, model, temperature, prompt);
+        format!("{:016x}", fnv1a(material.as_bytes()))
+    }
+
+    /// The completion cached for exactly this request, if any
+    pub fn get(&self, instruction: &str, model: &str, temperature: f64, prompt: &str) -> Option<String> {
+        let key = Self::key(instruction, model, temperature, prompt);
+        let content = fs::read_to_string(self.entry_path(&key)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+        let same = entry.instruction == instruction
+            && entry.model == model
+            && entry.temperature == temperature
+            && entry.prompt == prompt;
+        same.then_some(entry.completion)
+    }
+
+    pub fn put(&self, instruction: &str, model: &str, temperature: f64, prompt: &str, completion: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| anyhow!("Cannot create AI cache {}: {}", self.dir.display(), e))?;
+
+        let key = Self::key(instruction, model, temperature, prompt);
+        let entry = CacheEntry {
+            instruction: instruction.to_string(),
+            model: model.to_string(),
+            temperature,
+            prompt: prompt.to_string(),
+            completion: completion.to_string(),
+        };
+        fs::write(self.entry_path(&key), serde_json::to_string_pretty(&entry)?)?;
+        Ok(())
+    }
+
+    /// Delete every cached entry, returning how many were removed
+    pub fn clear(&self) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(Self::default_dir())
+    }
+}
+
+/// FNV-1a: stable across Rust versions, unlike `DefaultHasher`
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
@@ -0,0 +1,106 @@
+//! Messages actors send each other within one simulation
+//!
+//! Every `Emit` queues what it says on its channel; a `Receive` on that
+//! channel by another actor takes the oldest message it may have, so two
+//! actors in one program can actually exchange values.
+
+use crate::Action;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+
+/// Channel an Emit/Receive uses: its `channel` param, else its target
+pub fn channel(action: &Action) -> String {
+    param(action, "channel").unwrap_or(&action.target).to_string()
+}
+
+fn param<'a>(action: &'a Action, key: &str) -> Option<&'a str> {
+    action.params.as_ref().and_then(|p| p.get(key)).and_then(|v| v.as_str())
+}
+
+/// One emitted value waiting to be received
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub from: String,
+    /// The only actor that may receive it, from the Emit's `destination`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    pub content: Value,
+}
+
+/// Undelivered messages by channel, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Channels(HashMap<String, VecDeque<Message>>);
+
+impl Channels {
+    /// Queue `content` as said by `action`, an Emit
+    pub fn send(&mut self, action: &Action, content: Value) {
+        let message = Message {
+            from: action.actor.clone(),
+            to: param(action, "destination").map(str::to_string),
+            content,
+        };
+        self.0.entry(channel(action)).or_default().push_back(message);
+    }
+
+    /// Take the oldest message on `action`'s channel that its actor may
+    /// have: sent by another actor, to it or to anyone, and by the actor in
+    /// its `source` param if it has one
+    pub fn take(&mut self, action: &Action) -> Option<Message> {
+        let name = channel(action);
+        let queue = self.0.get_mut(&name)?;
+        let source = param(action, "source");
+        let position = queue.iter().position(|m| {
+            m.from != action.actor
+                && m.to.as_ref().is_none_or(|to| *to == action.actor)
+                && source.is_none_or(|source| m.from == source)
+        })?;
+        let message = queue.remove(position);
+        if queue.is_empty() {
+            self.0.remove(&name);
+        }
+        message
+    }
+
+    /// Move every message to `other`, after the ones already waiting there,
+    /// as when one simulator's messages reach another's
+    pub fn deliver_to(&mut self, other: &mut Channels) {
+        for (name, messages) in std::mem::take(&mut self.0) {
+            other.0.entry(name).or_default().extend(messages);
+        }
+    }
+
+    /// Messages waiting on each channel, channels in order
+    pub fn pending(&self) -> Vec<(&str, usize)> {
+        let mut pending: Vec<(&str, usize)> = self.0.iter().map(|(name, queue)| (name.as_str(), queue.len())).collect();
+        pending.sort();
+        pending
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+
+    #[test]
+    fn test_receivers_take_messages_meant_for_them() {
+        let action = |json: &str| Program::parse_action(json).unwrap();
+        let mut channels = Channels::default();
+        channels.send(&action(r#"{"actor": "Alice", "op": "Emit", "target": "chat", "params": {"destination": "Carol"}}"#), "for carol".into());
+        channels.send(&action(r#"{"actor": "Alice", "op": "Emit", "target": "chat"}"#), "hi".into());
+
+        // Alice doesn't hear herself, Bob skips Carol's message, and a source filter holds out
+        assert_eq!(channels.take(&action(r#"{"actor": "Alice", "op": "Receive", "target": "chat"}"#)), None);
+        assert_eq!(channels.take(&action(r#"{"actor": "Bob", "op": "Receive", "target": "chat", "params": {"source": "Dave"}}"#)), None);
+        assert_eq!(channels.take(&action(r#"{"actor": "Bob", "op": "Receive", "target": "chat"}"#)).unwrap().content, "hi");
+        assert_eq!(channels.pending(), vec![("chat", 1)]);
+        assert_eq!(channels.take(&action(r#"{"actor": "Carol", "op": "Receive", "target": "x", "params": {"channel": "chat"}}"#)).unwrap().from, "Alice");
+        assert!(channels.is_empty());
+    }
+}
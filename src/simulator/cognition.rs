@@ -0,0 +1,50 @@
+//! Cognitive limits of the brain simulator
+//!
+//! A [`CognitiveProfile`] says how many items working memory holds, how
+//! many actions attention stays on something before drifting, and how
+//! often mental arithmetic slips, so the same program can be run on a
+//! focused brain and a distracted one and the outcomes compared.
+
+/// How much a brain can hold and how reliably it works
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CognitiveProfile {
+    /// Items working memory holds before it drops one
+    pub working_memory: usize,
+    /// Actions attention stays on something before it drifts, or `None`
+    /// to stay until something else takes it
+    pub attention_span: Option<usize>,
+    /// Standard deviation of the relative error in computed numbers a
+    /// brain binds (0.1 = 10%)
+    pub noise: f64,
+}
+
+impl CognitiveProfile {
+    /// Names [`named`](Self::named) accepts
+    pub const NAMES: &'static [&'static str] = &["focused", "distracted"];
+
+    /// Seven items, unbroken attention, and exact arithmetic
+    pub fn focused() -> Self {
+        Self { working_memory: 7, attention_span: None, noise: 0.0 }
+    }
+
+    /// Four items, attention that drifts after three actions, and sums
+    /// off by around 10%
+    pub fn distracted() -> Self {
+        Self { working_memory: 4, attention_span: Some(3), noise: 0.1 }
+    }
+
+    /// The preset called `name`
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "focused" => Some(Self::focused()),
+            "distracted" => Some(Self::distracted()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for CognitiveProfile {
+    fn default() -> Self {
+        Self::focused()
+    }
+}
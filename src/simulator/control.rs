@@ -0,0 +1,86 @@
+//! Signals that cut the blocks around an action short, errors a `Try` can
+//! catch, what a `Match` arm matches, the triggers `When` registers and the
+//! native stack nested actions run on
+
+use crate::error::UclError;
+use crate::{Action, Condition};
+use serde_json::Value;
+
+/// Raised by an action and passed up through the enclosing blocks, each of
+/// which stops early, until the construct it's meant for handles it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Signal {
+    /// Leave the innermost loop
+    Break,
+    /// Skip to the innermost loop's next iteration
+    Continue,
+    /// Leave the running function with this value, or end the program
+    /// outside one
+    Return(Value),
+    /// End the whole program
+    Halt,
+}
+
+/// Whether a loop stops after an iteration that left `signal`, taking the
+/// `Break` or `Continue` meant for the loop itself
+pub(crate) fn ends_loop(signal: &mut Option<Signal>) -> bool {
+    match signal {
+        Some(Signal::Break | Signal::Continue) => signal.take() == Some(Signal::Break),
+        Some(Signal::Return(_) | Signal::Halt) => true,
+        None => false,
+    }
+}
+
+/// What a `Try`'s catch block sees as the error: a `Throw`'s payload, or the
+/// message of any other failure. Interrupts and runaway recursion can't be
+/// caught.
+pub(crate) fn caught(error: &anyhow::Error) -> Option<Value> {
+    match error.downcast_ref::<UclError>() {
+        Some(UclError::Interrupted | UclError::RecursionLimit { .. }) => None,
+        Some(UclError::Thrown { value }) => Some(value.clone()),
+        _ => Some(Value::String(format!("{:#}", error))),
+    }
+}
+
+/// How deeply blocks and function calls may nest unless a simulator is
+/// configured otherwise
+pub(crate) const DEFAULT_MAX_RECURSION_DEPTH: usize = 10_000;
+
+/// Room left on the native stack below which [`nested`] starts a new segment
+const RED_ZONE: usize = 128 * 1024;
+/// Size of each extra stack segment
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+/// Run `f`, one more level of a program's nesting, on a fresh heap-allocated
+/// stack segment if the current one is nearly used up, so only a simulator's
+/// recursion limit bounds how deep programs go
+pub(crate) fn nested<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, f)
+}
+
+/// Whether a Match arm's `value` matches, treating numbers as equal whatever
+/// their JSON type, so `3` matches a computed `3.0`
+pub(crate) fn same_value(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// A `When`'s condition and handler, and whether the condition held when it
+/// was last checked, so the handler runs each time it becomes true rather
+/// than after every action while it stays true
+#[derive(Debug, Clone)]
+pub(crate) struct Trigger {
+    pub condition: Condition,
+    pub body: Vec<Action>,
+    pub held: bool,
+}
+
+impl Trigger {
+    pub fn from_action(action: &Action) -> anyhow::Result<Self> {
+        let condition = action.condition.clone().ok_or_else(|| UclError::missing("When", "condition"))?;
+        let body = action.body_actions.clone().ok_or_else(|| UclError::missing("When", "body"))?;
+        Ok(Self { condition, body, held: false })
+    }
+}
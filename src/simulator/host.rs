@@ -0,0 +1,11 @@
+//! Native Rust functions that UCL programs running in a simulator can call
+//!
+//! An embedder binds a closure to a name with `bind_fn`; a `Call` action
+//! with that target, or a `{"call": name, "args": {...}}` expression, then
+//! runs it. Arguments arrive evaluated, as a JSON object keyed by name, and
+//! the closure's JSON result is the call's value.
+
+use serde_json::{Map, Value};
+
+/// A native function bound with `bind_fn`
+pub type HostFn = Box<dyn FnMut(&Map<String, Value>) -> anyhow::Result<Value> + Send>;
@@ -0,0 +1,328 @@
+//! Running a program on a person instead of a simulator
+//!
+//! `ucl brain --production` walks someone through a program one top-level
+//! action at a time: it shows the instruction and what to do with it,
+//! waits for them to do it, then asks what they're thinking, feeling and
+//! remembering. [`InteractiveBrainRunner`] works on any input and output,
+//! so the flow can be scripted in tests or driven from another front end,
+//! and returns the answers as a [`Session`].
+
+use crate::{Action, Operation, Program};
+use anyhow::Result;
+use serde::Serialize;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+/// What the person reported after one step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepReport {
+    /// 1-based step number
+    pub step: usize,
+    pub op: Operation,
+    pub target: String,
+    pub thought: String,
+    pub emotion: String,
+    pub memory: String,
+}
+
+impl StepReport {
+    pub fn display(&self) -> String {
+        format!(
+            "Step {}: {:?}({})\n  Thought: {}\n  Emotion: {}\n  Memory: {}",
+            self.step, self.op, self.target, self.thought, self.emotion, self.memory
+        )
+    }
+}
+
+/// Everything a run on a person produced
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Session {
+    /// They didn't agree to begin
+    pub aborted: bool,
+    /// Reports for the steps they finished, in order
+    pub steps: Vec<StepReport>,
+    /// Time from agreeing to begin to the last report
+    pub elapsed: Duration,
+}
+
+impl Session {
+    /// Whether every action of `program` was carried out
+    pub fn completed(&self, program: &Program) -> bool {
+        !self.aborted && self.steps.len() == program.actions.len()
+    }
+}
+
+/// Walks a person through a program over `input` and `output`
+pub struct InteractiveBrainRunner<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> InteractiveBrainRunner<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    /// The input and output back, e.g. to check what was written
+    pub fn into_parts(self) -> (R, W) {
+        (self.input, self.output)
+    }
+
+    /// Print `question` and read the answer, or `None` at the end of input
+    fn ask(&mut self, question: &str) -> Result<Option<String>> {
+        write!(self.output, "{}", question)?;
+        self.output.flush()?;
+
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim().to_string()))
+    }
+
+    /// Take the person through `program`, stopping early if the input ends
+    pub fn run(&mut self, program: &Program) -> Result<Session> {
+        let out = &mut self.output;
+        writeln!(out, "🧠💼 PRODUCTION MODE: Running on YOUR actual brain!")?;
+        writeln!(out, "{}", "=".repeat(60))?;
+        writeln!(out)?;
+        writeln!(out, "⚠️  WARNING: This will execute directly on human wetware.")?;
+        writeln!(out, "    No virtual machine. No sandbox. Just your neurons.")?;
+        writeln!(out)?;
+        writeln!(out, "Instructions:")?;
+        writeln!(out, "  - Read each operation carefully")?;
+        writeln!(out, "  - Execute it using your brain")?;
+        writeln!(out, "  - Report your internal state after each step")?;
+        writeln!(out)?;
+
+        let mut session = Session::default();
+        if !self.ask("Ready to begin? (y/n): ")?.is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+            writeln!(self.output, "\n❌ Aborted. Your brain remains in its current state.")?;
+            session.aborted = true;
+            return Ok(session);
+        }
+
+        writeln!(self.output, "\n🚀 Initiating brain program execution...\n")?;
+        let start = Instant::now();
+
+        for (i, action) in program.actions.iter().enumerate() {
+            self.show_step(i, program.actions.len(), action)?;
+
+            if self.ask("✅ Press ENTER when you've executed this operation...")?.is_none() {
+                break;
+            }
+
+            writeln!(self.output)?;
+            writeln!(self.output, "📊 Post-Execution Report:")?;
+            writeln!(self.output)?;
+            let (Some(thought), Some(emotion), Some(memory)) = (
+                self.ask("What are you thinking right now? ")?,
+                self.ask("How do you feel? (emotion): ")?,
+                self.ask("What do you remember? ")?,
+            ) else {
+                break;
+            };
+
+            session.steps.push(StepReport {
+                step: i + 1,
+                op: action.op.clone(),
+                target: action.target.clone(),
+                thought,
+                emotion,
+                memory,
+            });
+            writeln!(self.output, "\n✓ Step {} complete. Brain state updated.\n", i + 1)?;
+        }
+
+        session.elapsed = start.elapsed();
+        self.summarize(&session)?;
+        Ok(session)
+    }
+
+    fn show_step(&mut self, i: usize, count: usize, action: &Action) -> Result<()> {
+        let out = &mut self.output;
+        writeln!(out, "{}", "─".repeat(60))?;
+        writeln!(out, "STEP {}/{}: {:?} Operation", i + 1, count, action.op)?;
+        writeln!(out, "{}", "─".repeat(60))?;
+        writeln!(out)?;
+        writeln!(out, "📋 Instruction:")?;
+        writeln!(out, "   Actor:  {}", action.actor)?;
+        writeln!(out, "   Op:     {:?}", action.op)?;
+        writeln!(out, "   Target: {}", action.target)?;
+
+        if let Some(params) = &action.params {
+            writeln!(out, "   Params:")?;
+            for (key, value) in params {
+                writeln!(out, "     • {} = {}", key, value)?;
+            }
+        }
+
+        if let Some(effects) = &action.effects {
+            writeln!(out, "   Effects: [{}]", effects.join(", "))?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "🎯 Your Task:")?;
+        for line in instructions(action) {
+            writeln!(out, "   {}", line)?;
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    fn summarize(&mut self, session: &Session) -> Result<()> {
+        let out = &mut self.output;
+        let steps = session.steps.len();
+        writeln!(out, "\n")?;
+        writeln!(out, "🎉 PROGRAM EXECUTION COMPLETE 🎉")?;
+        writeln!(out, "{}", "=".repeat(60))?;
+        writeln!(out)?;
+        writeln!(out, "📈 Performance Metrics:")?;
+        writeln!(out, "   Total Operations: {}", steps)?;
+        writeln!(out, "   Execution Time: {:.2?}", session.elapsed)?;
+        writeln!(out, "   Avg Time/Op: {:.2?}", session.elapsed / steps.max(1) as u32)?;
+        writeln!(out)?;
+        writeln!(out, "🧠 Production Brain State Capture:")?;
+        writeln!(out, "{}", "─".repeat(60))?;
+
+        for step in &session.steps {
+            writeln!(out, "{}", step.display())?;
+            writeln!(out)?;
+        }
+
+        writeln!(out, "{}", "=".repeat(60))?;
+        writeln!(out)?;
+        writeln!(out, "💡 Insights:")?;
+        writeln!(out, "   • Your brain successfully executed {} UCL operations", steps)?;
+        writeln!(out, "   • Language literally ran as a program on your neurons")?;
+        writeln!(out, "   • You are now running UCL in production 🚀")?;
+        writeln!(out)?;
+        writeln!(out, "Thank you for being a biological runtime environment! 🧠✨")?;
+        Ok(())
+    }
+}
+
+/// What a person should do to carry out `action`, one line each
+pub fn instructions(action: &Action) -> Vec<String> {
+    let target = &action.target;
+    match action.op {
+        Operation::StoreFact => vec![
+            "→ Store this information in your memory".into(),
+            format!("→ Commit '{}' to long-term storage", target),
+        ],
+        Operation::Assert => vec![
+            "→ Assert this as a strong belief".into(),
+            "→ Make this a core part of your worldview".into(),
+        ],
+        Operation::Emit => vec![
+            "→ Generate and speak this output".into(),
+            "→ Say it out loud or in your mind".into(),
+        ],
+        Operation::Receive => vec![
+            "→ Process this input through your senses".into(),
+            format!("→ Pay attention to: {}", target),
+        ],
+        Operation::Decide => vec![
+            "→ Make this decision".into(),
+            format!("→ Commit to: {}", target),
+        ],
+        Operation::Measure => vec![
+            "→ Observe and measure".into(),
+            format!("→ Focus your attention on: {}", target),
+        ],
+        Operation::Create => vec![
+            "→ Create this new concept in your mind".into(),
+            format!("→ Imagine: {}", target),
+        ],
+        Operation::Bind => vec![
+            "→ Bind this concept to a mental variable".into(),
+            format!("→ Associate '{}' with a value", target),
+        ],
+        Operation::Write => match &action.params {
+            Some(params) if params.contains_key("lhs_register") && params.contains_key("rhs_register") => {
+                let lhs = params.get("lhs_register").and_then(|v| v.as_str()).unwrap_or("?");
+                let rhs = params.get("rhs_register").and_then(|v| v.as_str()).unwrap_or("?");
+                let symbol = match params.get("operation").and_then(|v| v.as_str()).unwrap_or("multiply") {
+                    "add" => "+",
+                    "subtract" => "-",
+                    "divide" => "÷",
+                    _ => "×",
+                };
+                vec![
+                    format!("→ Recall {} and {}", lhs, rhs),
+                    format!("→ Calculate: {} {} {}", lhs, symbol, rhs),
+                    format!("→ Store the answer in: {}", target),
+                ]
+            }
+            Some(_) => vec![format!("→ Update memory: {}", target), "→ Store a new value".into()],
+            None => vec![format!("→ Update memory: {}", target)],
+        },
+        Operation::Oblige => vec![
+            "→ Accept this obligation".into(),
+            "→ Add to your active goals".into(),
+        ],
+        Operation::Wait => vec![
+            format!("→ Wait and let {} seconds pass", action.dur.unwrap_or(1.0)),
+            "→ Be present in this moment".into(),
+        ],
+        Operation::Sleep => vec![
+            "→ Sleep on everything you're holding in mind".into(),
+            "→ Wake up calmer".into(),
+        ],
+        Operation::Dream => vec!["→ Let what you know drift together into a dream".into()],
+        Operation::Imagine => vec![
+            format!("→ Put yourself in {}'s shoes", target),
+            "→ Picture what they'd make of it".into(),
+        ],
+        Operation::ReadSensor => {
+            let sensor = action.params.as_ref().and_then(|p| p.get("sensor")).and_then(|v| v.as_str()).unwrap_or("temperature");
+            vec![format!("→ Check the {} of {}", sensor, target), "→ Remember the reading".into()]
+        }
+        Operation::Grasp => vec![format!("→ Pick up the {}", target), "→ Keep hold of it".into()],
+        Operation::Release => vec![format!("→ Put the {} down", target)],
+        Operation::GenRandomInt => match &action.params {
+            Some(params) => {
+                let min = params.get("min").and_then(|v| v.as_i64()).unwrap_or(0);
+                let max = params.get("max").and_then(|v| v.as_i64()).unwrap_or(9);
+                vec![format!("→ Think of a random number between {} and {}", min, max), format!("→ Remember it as '{}'", target)]
+            }
+            None => vec!["→ Generate a random number".into(), format!("→ Remember it as '{}'", target)],
+        },
+        _ => vec![
+            "⚠️  UNKNOWN OPERATION!".into(),
+            "→ Experience confusion".into(),
+            "→ Notice you don't understand".into(),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_session() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "StoreFact", "target": "cat", "params": {"color": "black"}},
+            {"actor": "you", "op": "Flurble", "target": "it"}
+        ]}"#).unwrap();
+
+        let script = "y\n\nthe cat\ncalm\na black cat\n\nhuh\nconfused\nnothing\n";
+        let mut runner = InteractiveBrainRunner::new(script.as_bytes(), Vec::new());
+        let session = runner.run(&program).unwrap();
+        assert!(session.completed(&program));
+        assert_eq!(session.steps[0].memory, "a black cat");
+        assert_eq!(session.steps[1].emotion, "confused");
+
+        let output = String::from_utf8(runner.into_parts().1).unwrap();
+        assert!(output.contains("→ Commit 'cat' to long-term storage"));
+        assert!(output.contains("⚠️  UNKNOWN OPERATION!"));
+        assert!(output.contains("Step 2: Flurble(it)\n  Thought: huh"));
+
+        // Declining, or running out of input partway, ends the session early
+        let session = InteractiveBrainRunner::new("n\n".as_bytes(), Vec::new()).run(&program).unwrap();
+        assert!(session.aborted && session.steps.is_empty());
+        let session = InteractiveBrainRunner::new("y\n\nthe cat\n".as_bytes(), Vec::new()).run(&program).unwrap();
+        assert!(!session.completed(&program) && session.steps.is_empty());
+    }
+}
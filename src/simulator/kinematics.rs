@@ -0,0 +1,55 @@
+//! How the robot's arm moves
+//!
+//! The arm's base sits at the origin. It moves in a straight line at up to
+//! [`ArmLimits::max_speed`], so each move takes time, and can't reach
+//! anything further from the base than [`ArmLimits::reach`].
+
+/// A point in the robot's workspace
+pub type Point = (f64, f64, f64);
+
+/// Straight-line distance between two points
+pub fn distance(a: Point, b: Point) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt()
+}
+
+/// Where the arm is mounted
+pub const BASE: Point = (0.0, 0.0, 0.0);
+
+/// How fast and how far the arm can move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArmLimits {
+    /// Workspace units per second
+    pub max_speed: f64,
+    /// Furthest the arm can reach from its base
+    pub reach: f64,
+}
+
+impl Default for ArmLimits {
+    fn default() -> Self {
+        Self { max_speed: 25.0, reach: 100.0 }
+    }
+}
+
+impl ArmLimits {
+    pub fn can_reach(&self, point: Point) -> bool {
+        distance(BASE, point) <= self.reach
+    }
+
+    /// Seconds a move from `from` to `to` takes at full speed
+    pub fn travel_time(&self, from: Point, to: Point) -> f64 {
+        distance(from, to) / self.max_speed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reach_and_travel_time() {
+        let arm = ArmLimits { max_speed: 10.0, reach: 50.0 };
+        assert!(arm.can_reach((30.0, 40.0, 0.0)));
+        assert!(!arm.can_reach((30.0, 40.0, 1.0)));
+        assert_eq!(arm.travel_time((0.0, 0.0, 0.0), (30.0, 40.0, 0.0)), 5.0);
+    }
+}
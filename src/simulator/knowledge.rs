@@ -0,0 +1,115 @@
+//! What the brain knows as a graph of subject–predicate–object facts
+//!
+//! Beliefs keep one value per dotted name, so a second `cat.chases`
+//! replaces the first. The graph keeps every fact `StoreFact` records
+//! alongside them, so "cat chases mouse" and "cat chases bird" can both be
+//! known, found with patterns like `cat.*` or `*.chases.mouse`, and
+//! followed from one entity to the next.
+
+use crate::simulator::values;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{HashSet, VecDeque};
+
+/// One fact: `subject` has `object` as its `predicate`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Triple {
+    pub subject: String,
+    pub predicate: String,
+    pub object: Value,
+}
+
+impl Triple {
+    /// The fact as a JSON object, for binding to a variable
+    pub fn to_value(&self) -> Value {
+        json!({"subject": self.subject, "predicate": self.predicate, "object": self.object})
+    }
+
+    /// "cat chases mouse"
+    pub fn sentence(&self) -> String {
+        format!("{} {} {}", self.subject, self.predicate, values::text(&self.object))
+    }
+}
+
+/// Every known fact, in the order learned
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KnowledgeGraph(Vec<Triple>);
+
+impl KnowledgeGraph {
+    /// Learn a fact, unless it's already known; whether it was new
+    pub fn insert(&mut self, subject: &str, predicate: &str, object: Value) -> bool {
+        let triple = Triple { subject: subject.to_string(), predicate: predicate.to_string(), object };
+        if self.0.contains(&triple) {
+            return false;
+        }
+        self.0.push(triple);
+        true
+    }
+
+    /// Facts matching `pattern`: `subject.predicate` or
+    /// `subject.predicate.object`, where `*` matches anything and a
+    /// missing part counts as `*`
+    pub fn matching(&self, pattern: &str) -> Vec<&Triple> {
+        let mut parts = pattern.splitn(3, '.');
+        let mut next = || parts.next().filter(|part| *part != "*");
+        let (subject, predicate, object) = (next(), next(), next());
+        self.0.iter()
+            .filter(|t| subject.is_none_or(|s| t.subject == s))
+            .filter(|t| predicate.is_none_or(|p| t.predicate == p))
+            .filter(|t| object.is_none_or(|o| values::text(&t.object) == o))
+            .collect()
+    }
+
+    /// Entities reachable from `start` by following facts whose objects
+    /// name other entities, only along `predicate` if given, nearest first
+    pub fn reachable(&self, start: &str, predicate: Option<&str>) -> Vec<String> {
+        let mut seen = HashSet::from([start.to_string()]);
+        let mut queue = VecDeque::from([start.to_string()]);
+        let mut found = Vec::new();
+        while let Some(entity) = queue.pop_front() {
+            let next = self.0.iter()
+                .filter(|t| t.subject == entity && predicate.is_none_or(|p| t.predicate == p))
+                .filter_map(|t| t.object.as_str());
+            for object in next {
+                if seen.insert(object.to_string()) {
+                    found.push(object.to_string());
+                    queue.push_back(object.to_string());
+                }
+            }
+        }
+        found
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Triple> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_and_traversal() {
+        let mut graph = KnowledgeGraph::default();
+        graph.insert("cat", "chases", json!("mouse"));
+        graph.insert("cat", "chases", json!("bird"));
+        graph.insert("cat", "color", json!("black"));
+        graph.insert("mouse", "eats", json!("cheese"));
+        graph.insert("cheese", "from", json!("cow"));
+        assert!(!graph.insert("cat", "color", json!("black")));
+
+        let sentences = |pattern: &str| graph.matching(pattern).iter().map(|t| t.sentence()).collect::<Vec<_>>();
+        assert_eq!(sentences("cat.*"), vec!["cat chases mouse", "cat chases bird", "cat color black"]);
+        assert_eq!(sentences("*.chases.mouse"), vec!["cat chases mouse"]);
+        assert_eq!(sentences("*.eats"), vec!["mouse eats cheese"]);
+
+        assert_eq!(graph.reachable("cat", Some("chases")), vec!["mouse", "bird"]);
+        assert_eq!(graph.reachable("cat", None), vec!["mouse", "bird", "black", "cheese", "cow"]);
+    }
+}
@@ -0,0 +1,80 @@
+//! Liquids in containers
+//!
+//! A container's [`Contents`] says how many millilitres of each liquid it
+//! holds. `Measure` with `into` fills one, `Pour` moves liquid from one to
+//! another, and `Mix` turns whatever is in one into a single composite.
+
+use std::collections::BTreeMap;
+
+/// Millilitres of each liquid in a container, by name
+pub type Contents = BTreeMap<String, f64>;
+
+/// `value` in `unit` as millilitres, if `unit` measures volume; no unit
+/// means millilitres
+pub fn millilitres(value: f64, unit: &str) -> Option<f64> {
+    let per_unit = match unit.trim().to_lowercase().as_str() {
+        "" | "ml" | "millilitre" | "millilitres" | "milliliter" | "milliliters" => 1.0,
+        "cl" => 10.0,
+        "dl" => 100.0,
+        "l" | "litre" | "litres" | "liter" | "liters" => 1000.0,
+        "tsp" | "teaspoon" | "teaspoons" => 5.0,
+        "tbsp" | "tablespoon" | "tablespoons" => 15.0,
+        "cup" | "cups" => 240.0,
+        "fl oz" => 29.57,
+        _ => return None,
+    };
+    Some(value * per_unit)
+}
+
+pub fn total(contents: &Contents) -> f64 {
+    // Not `sum()`, which makes an empty container hold -0ml
+    contents.values().fold(0.0, |total, volume| total + volume)
+}
+
+/// Take up to `amount` ml out of `contents`: of `liquid` if there's any,
+/// otherwise of everything in proportion. Returns what was taken.
+pub fn take(contents: &mut Contents, liquid: &str, amount: f64) -> Contents {
+    let mut taken = Contents::new();
+    if let Some(available) = contents.get_mut(liquid) {
+        let amount = amount.min(*available);
+        *available -= amount;
+        taken.insert(liquid.to_string(), amount);
+    } else {
+        let fraction = (amount / total(contents)).min(1.0);
+        for (name, volume) in contents.iter_mut() {
+            taken.insert(name.clone(), *volume * fraction);
+            *volume *= 1.0 - fraction;
+        }
+    }
+    contents.retain(|_, volume| *volume > 0.0);
+    taken
+}
+
+/// Add `more` to `contents`
+pub fn add(contents: &mut Contents, more: &Contents) {
+    for (name, volume) in more {
+        *contents.entry(name.clone()).or_insert(0.0) += volume;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_and_transfers() {
+        assert_eq!(millilitres(2.0, "cups"), Some(480.0));
+        assert_eq!(millilitres(0.5, "L"), Some(500.0));
+        assert_eq!(millilitres(100.0, "g"), None);
+
+        let mut pot = Contents::from([("tea".to_string(), 300.0), ("milk".to_string(), 100.0)]);
+        assert_eq!(take(&mut pot, "tea", 500.0), Contents::from([("tea".to_string(), 300.0)]));
+        assert_eq!(pot, Contents::from([("milk".to_string(), 100.0)]));
+
+        let mut bowl = Contents::from([("flour".to_string(), 300.0), ("milk".to_string(), 100.0)]);
+        let poured = take(&mut bowl, "batter", 200.0);
+        assert_eq!(poured, Contents::from([("flour".to_string(), 150.0), ("milk".to_string(), 50.0)]));
+        add(&mut pot, &poured);
+        assert_eq!(total(&pot), 300.0);
+    }
+}
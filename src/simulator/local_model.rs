@@ -0,0 +1,105 @@
+use crate::simulator::backend::{GenerationBackend, Prompt};
+use crate::error::UclError;
+use anyhow::Result;
+use candle_core::quantized::gguf_file;
+use candle_core::{Device, Tensor};
+use candle_transformers::generation::LogitsProcessor;
+use candle_transformers::models::quantized_llama::ModelWeights;
+use std::path::Path;
+use tokenizers::Tokenizer;
+
+/// Offline generation backend running a quantized GGUF model on the CPU
+///
+/// Only llama-architecture GGUF files are supported. The tokenizer is loaded
+/// from a Hugging Face `tokenizer.json`, by default the one next to the model.
+pub struct LocalModelBackend {
+    name: String,
+    model: ModelWeights,
+    tokenizer: Tokenizer,
+    device: Device,
+    max_tokens: usize,
+    seed: u64,
+}
+
+impl LocalModelBackend {
+    pub fn load(model_path: &Path, tokenizer_path: Option<&Path>) -> Result<Self> {
+        let device = Device::Cpu;
+
+        let mut file = std::fs::File::open(model_path)
+            .map_err(|e| UclError::model(format!("Cannot open model {}: {}", model_path.display(), e)))?;
+        let content = gguf_file::Content::read(&mut file)
+            .map_err(|e| UclError::model(format!("Invalid GGUF file {}: {}", model_path.display(), e)))?;
+        let model = ModelWeights::from_gguf(content, &mut file, &device)?;
+
+        let default_tokenizer = model_path.with_file_name("tokenizer.json");
+        let tokenizer_path = tokenizer_path.unwrap_or(&default_tokenizer);
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| UclError::model(format!("Cannot load tokenizer {}: {}", tokenizer_path.display(), e)))?;
+
+        let name = model_path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "local-model".to_string());
+
+        Ok(Self {
+            name,
+            model,
+            tokenizer,
+            device,
+            max_tokens: 512,
+            seed: 299792458,
+        })
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn eos_token(&self) -> Option<u32> {
+        ["</s>", "<|eot_id|>", "<|end_of_text|>", "<|im_end|>"]
+            .iter()
+            .find_map(|t| self.tokenizer.token_to_id(t))
+    }
+}
+
+impl GenerationBackend for LocalModelBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn complete(&mut self, prompt: &Prompt, temperature: f64) -> Result<String> {
+        let encoding = self.tokenizer.encode(prompt.to_text(), true)
+            .map_err(|e| UclError::model(format!("Tokenization failed: {}", e)))?;
+        let prompt_tokens = encoding.get_ids().to_vec();
+
+        // Temperature 0 means greedy decoding
+        let temperature = if temperature > 0.0 { Some(temperature) } else { None };
+        let mut sampler = LogitsProcessor::new(self.seed, temperature, None);
+        let eos = self.eos_token();
+
+        self.model.clear_kv_cache();
+        let input = Tensor::new(prompt_tokens.as_slice(), &self.device)?.unsqueeze(0)?;
+        let logits = self.model.forward(&input, 0)?.squeeze(0)?;
+        let mut next = sampler.sample(&logits)?;
+
+        let mut generated = Vec::new();
+        for index in 0..self.max_tokens {
+            if Some(next) == eos {
+                break;
+            }
+            generated.push(next);
+
+            let input = Tensor::new(&[next], &self.device)?.unsqueeze(0)?;
+            let logits = self.model.forward(&input, prompt_tokens.len() + index)?.squeeze(0)?;
+            next = sampler.sample(&logits)?;
+        }
+
+        Ok(self.tokenizer.decode(&generated, true)
+            .map_err(|e| UclError::model(format!("Detokenization failed: {}", e)))?)
+    }
+}
@@ -0,0 +1,115 @@
+//! Forgetting in the brain simulator
+//!
+//! With a [`Forgetting`] model, every belief has a [`Trace`]: it starts at
+//! strength 1, gets stronger each time an action reads or changes it, and
+//! fades by half every `half_life` simulated seconds it goes unused. Time
+//! passes with `Wait`s and with actions that take a `dur`; a belief that
+//! fades below `threshold` is forgotten. When working memory is full it
+//! drops the least salient item rather than the oldest, with items noted
+//! under strong emotion standing out longest.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How a brain forgets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Forgetting {
+    /// Simulated seconds for an unused memory's strength to halve
+    pub half_life: f64,
+    /// Strength below which a belief is forgotten
+    pub threshold: f64,
+    /// Strength each use of a belief adds
+    pub rehearsal: f64,
+}
+
+impl Default for Forgetting {
+    fn default() -> Self {
+        Self { half_life: 60.0, threshold: 0.25, rehearsal: 1.0 }
+    }
+}
+
+/// How strongly one thing is remembered
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    /// Strength when last used
+    pub strength: f64,
+    /// Simulated time of the last use
+    pub last_used: f64,
+}
+
+impl Trace {
+    /// Strength at `clock`, after fading since the last use
+    pub fn strength_at(&self, clock: f64, half_life: f64) -> f64 {
+        self.strength * 0.5f64.powf((clock - self.last_used).max(0.0) / half_life)
+    }
+}
+
+/// What the brain remembers, how well, and the simulated time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Memory {
+    /// Simulated seconds since the brain started
+    pub clock: f64,
+    /// Each belief's trace, by name
+    pub beliefs: HashMap<String, Trace>,
+    /// Salience of each working memory item, in the same order
+    pub working: Vec<Trace>,
+}
+
+impl Memory {
+    /// Note a use of belief `name` now, adding `rehearsal` to the strength
+    /// it has left
+    pub fn rehearse(&mut self, name: &str, model: &Forgetting) {
+        let clock = self.clock;
+        let strength = self.beliefs.get(name).map_or(0.0, |trace| trace.strength_at(clock, model.half_life));
+        let strength = (strength + model.rehearsal).max(1.0);
+        self.beliefs.insert(name.to_string(), Trace { strength, last_used: clock });
+    }
+
+    /// Let `seconds` pass, giving the beliefs that faded below the threshold
+    pub fn pass(&mut self, seconds: f64, model: &Forgetting) -> Vec<String> {
+        self.clock += seconds;
+        let clock = self.clock;
+        let mut faded: Vec<String> = self.beliefs.iter()
+            .filter(|(_, trace)| trace.strength_at(clock, model.half_life) < model.threshold)
+            .map(|(name, _)| name.clone())
+            .collect();
+        faded.sort();
+        for name in &faded {
+            self.beliefs.remove(name);
+        }
+        faded
+    }
+
+    /// Which of `count` working memory items to drop to make room: the least
+    /// salient now, the oldest among equals
+    pub fn least_salient(&self, count: usize, model: &Forgetting) -> usize {
+        let salience = |i: usize| self.working.get(i).map_or(0.0, |trace| trace.strength_at(self.clock, model.half_life));
+        (0..count).fold(0, |least, i| if salience(i) < salience(least) { i } else { least })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unused_beliefs_fade_and_rehearsed_ones_last() {
+        let model = Forgetting::default();
+        let mut memory = Memory::default();
+        memory.rehearse("used", &model);
+        memory.rehearse("unused", &model);
+
+        // Two half-lives leave both at 0.25, just hanging on
+        assert!(memory.pass(120.0, &model).is_empty());
+        memory.rehearse("used", &model);
+        assert_eq!(memory.pass(1.0, &model), vec!["unused"]);
+        assert!(memory.beliefs["used"].strength > 1.0);
+
+        memory.working = vec![
+            Trace { strength: 2.0, last_used: 0.0 },
+            Trace { strength: 1.0, last_used: 100.0 },
+            Trace { strength: 1.0, last_used: 121.0 },
+        ];
+        assert_eq!(memory.least_salient(3, &model), 0);
+    }
+}
@@ -1,8 +1,44 @@
 pub mod brain;
 pub mod robot;
 pub mod ai;
+pub mod backend;
+pub mod cache;
+pub mod channels;
+pub mod cognition;
+pub(crate) mod control;
+pub mod host;
+pub mod interactive;
+pub mod kinematics;
+pub mod knowledge;
+pub mod liquids;
+#[cfg(feature = "local-model")]
+pub mod local_model;
+pub mod memory;
+pub mod noise;
+pub mod provenance;
+pub mod query;
+pub mod sleep;
+pub mod substrate;
+pub mod workspace;
+pub(crate) mod values;
 
 pub use brain::{BrainSimulator, BrainState};
 pub use robot::{RobotSimulator, RobotState};
-pub use ai::{MockAISimulator, MockAIState};
+pub use ai::{MockAISimulator, MockAIState, GenerationFailure};
+pub use noise::{NoiseModel, MonteCarloReport};
+pub use kinematics::ArmLimits;
+pub use workspace::{Bounds, Violation, Workspace};
+pub use provenance::Provenance;
+pub use query::EmotionSummary;
+pub use substrate::{Simulator, SIMULATORS, by_name};
+pub use backend::{CallPolicy, GenerationBackend, GenerationError, Prompt, PromptTemplate};
+pub use cache::ResponseCache;
+pub use channels::{Channels, Message};
+pub use cognition::CognitiveProfile;
+pub use memory::{Forgetting, Memory};
+pub use host::HostFn;
+pub use interactive::{InteractiveBrainRunner, Session, StepReport};
+pub use knowledge::{KnowledgeGraph, Triple};
+#[cfg(feature = "local-model")]
+pub use local_model::LocalModelBackend;
 
@@ -0,0 +1,184 @@
+use crate::Program;
+use crate::simulator::RobotSimulator;
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Actuation noise applied to physical robot operations
+///
+/// Position noise is an absolute standard deviation in workspace units,
+/// amount noise is relative to the requested quantity (0.05 = 5%), and
+/// sensor noise is absolute in each sensor's units (°C, grams, workspace units).
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseModel {
+    pub position_sigma: f64,
+    pub amount_sigma: f64,
+
+    /// Largest placement error that still counts as success
+    pub position_tolerance: f64,
+
+    /// Largest relative amount error that still counts as success
+    pub amount_tolerance: f64,
+
+    /// Standard deviation of `ReadSensor` readings, in the sensor's units
+    pub sensor_sigma: f64,
+}
+
+impl NoiseModel {
+    pub fn new(position_sigma: f64, amount_sigma: f64) -> Self {
+        Self {
+            position_sigma,
+            amount_sigma,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_sensor_sigma(mut self, sigma: f64) -> Self {
+        self.sensor_sigma = sigma;
+        self
+    }
+
+    pub fn with_tolerances(mut self, position: f64, amount: f64) -> Self {
+        self.position_tolerance = position;
+        self.amount_tolerance = amount;
+        self
+    }
+}
+
+impl Default for NoiseModel {
+    fn default() -> Self {
+        Self {
+            position_sigma: 0.5,
+            amount_sigma: 0.02,
+            position_tolerance: 1.0,
+            amount_tolerance: 0.05,
+            sensor_sigma: 0.5,
+        }
+    }
+}
+
+/// Parse a quantity such as "250ml" or "1.5 cups" into a number and unit
+pub fn parse_quantity(text: &str) -> Option<(f64, String)> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(text.len());
+    let value = text[..split].parse::<f64>().ok()?;
+    Some((value, text[split..].trim().to_string()))
+}
+
+/// Outcome statistics for repeated noisy executions of a program
+#[derive(Debug, Clone)]
+pub struct MonteCarloReport {
+    pub runs: usize,
+    pub successes: usize,
+
+    /// How many runs hit each distinct error message
+    pub failure_counts: HashMap<String, usize>,
+}
+
+impl MonteCarloReport {
+    pub fn success_rate(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        self.successes as f64 / self.runs as f64
+    }
+
+    pub fn display(&self) -> String {
+        let mut output = String::new();
+
+        output.push_str("=== Monte Carlo Robustness ===\n\n");
+        output.push_str(&format!("Runs: {}\n", self.runs));
+        output.push_str(&format!("Successes: {} ({:.1}%)\n", self.successes, self.success_rate() * 100.0));
+        output.push_str(&format!("Failures: {}\n", self.runs - self.successes));
+
+        if !self.failure_counts.is_empty() {
+            output.push_str("\nFailure causes:\n");
+            let mut causes: Vec<_> = self.failure_counts.iter().collect();
+            causes.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+            for (cause, count) in causes {
+                output.push_str(&format!("  {}x {}\n", count, cause));
+            }
+        }
+
+        output
+    }
+}
+
+/// Run a program `runs` times under the given noise model
+///
+/// Run `i` is seeded with `seed + i`, so the whole experiment is reproducible.
+/// A run succeeds when execution returns Ok and the robot recorded no errors.
+pub fn monte_carlo(program: &Program, noise: &NoiseModel, runs: usize, seed: u64) -> Result<MonteCarloReport> {
+    let mut report = MonteCarloReport {
+        runs,
+        successes: 0,
+        failure_counts: HashMap::new(),
+    };
+
+    for i in 0..runs {
+        let mut simulator = RobotSimulator::new()
+            .with_noise(noise.clone())
+            .with_seed(seed.wrapping_add(i as u64));
+
+        let mut errors = match simulator.execute(program) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![e.to_string()],
+        };
+        errors.extend(simulator.state().errors.iter().cloned());
+
+        if errors.is_empty() {
+            report.successes += 1;
+        } else {
+            // Count each cause once per run; strip the measured magnitude so
+            // similar failures group together.
+            let mut causes: Vec<String> = errors.iter()
+                .map(|e| e.split(" by ").next().unwrap_or(e).to_string())
+                .collect();
+            causes.sort();
+            causes.dedup();
+            for cause in causes {
+                *report.failure_counts.entry(cause).or_insert(0) += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(parse_quantity("250ml"), Some((250.0, "ml".to_string())));
+        assert_eq!(parse_quantity("1.5 cups"), Some((1.5, "cups".to_string())));
+        assert_eq!(parse_quantity("some"), None);
+    }
+
+    #[test]
+    fn test_zero_noise_always_succeeds() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "cook", "op": "Gather", "target": "kit", "params": {"items": ["cup", "tea_bag"]}},
+            {"actor": "cook", "op": "Measure", "target": "water", "params": {"amount": "250ml"}},
+            {"actor": "cook", "op": "Place", "target": "tea_bag", "params": {"into": "cup"}}
+        ]}"#).unwrap();
+
+        let report = monte_carlo(&program, &NoiseModel::new(0.0, 0.0), 20, 1).unwrap();
+        assert_eq!(report.successes, 20);
+    }
+
+    #[test]
+    fn test_large_noise_causes_failures() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "cook", "op": "Gather", "target": "kit", "params": {"items": ["cup", "tea_bag"]}},
+            {"actor": "cook", "op": "Place", "target": "tea_bag", "params": {"into": "cup"}}
+        ]}"#).unwrap();
+
+        let report = monte_carlo(&program, &NoiseModel::new(5.0, 0.0), 50, 1).unwrap();
+        assert!(report.successes < 50);
+        let again = monte_carlo(&program, &NoiseModel::new(5.0, 0.0), 50, 1).unwrap();
+        assert_eq!(report.successes, again.successes);
+    }
+}
@@ -0,0 +1,411 @@
+//! Which action produced each named value and output line of a simulator,
+//! and which earlier values it was derived from
+
+use crate::Action;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Most records a [`Provenance`] holds; past it the oldest half is dropped
+pub const MAX_RECORDS: usize = 100_000;
+
+/// One action that changed the state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Origin {
+    /// Where the action sits: `3` is the fourth top-level action, `3.then[0]`
+    /// the first action of its then-branch, `5.fib().body[1]` the second
+    /// action of `fib` as called from action 5
+    pub action: String,
+    /// The action as it appears in the trace, e.g. `Bind(x)`
+    pub op: String,
+    /// Values the action read
+    pub inputs: Vec<Input>,
+    /// The action's `caused_by` ids, each with the record of the action it names
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub causes: Vec<Input>,
+}
+
+/// A value an action read, and the record that produced it (if any)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Input {
+    pub name: String,
+    pub from: Option<usize>,
+}
+
+/// Origins of a simulator's named values (beliefs or variables) and of each
+/// output (or log) line
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Every recorded origin, in execution order; the maps below index into it
+    pub records: Vec<Origin>,
+    pub values: HashMap<String, usize>,
+    pub output: Vec<usize>,
+    /// Record of each action that has an `id`
+    #[serde(default)]
+    pub ids: HashMap<String, usize>,
+    /// How many of the oldest records [`keep_last`](Self::keep_last) has
+    /// dropped; `records[0]` is record number `dropped`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub dropped: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+impl Provenance {
+    /// Record number `index`, unless it has been dropped
+    pub fn record_at(&self, index: usize) -> Option<&Origin> {
+        self.records.get(index.checked_sub(self.dropped)?)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&Origin> {
+        self.values.get(name).and_then(|&i| self.record_at(i))
+    }
+
+    /// Origin of output line `index`
+    pub fn output(&self, index: usize) -> Option<&Origin> {
+        self.output.get(index).and_then(|&i| self.record_at(i))
+    }
+
+    /// Record that `action`, at `path`, changed `values` and appended output
+    /// up to `output_len`, having read `reads`
+    ///
+    /// Values whose current origin is nested under `path` (set by a branch
+    /// or a function it called) keep that more precise origin. Actions with
+    /// an `id` are always recorded, so later actions can name them as causes.
+    pub(crate) fn record<'a>(
+        &mut self,
+        path: String,
+        action: &Action,
+        reads: Vec<String>,
+        values: impl IntoIterator<Item = &'a String>,
+        output_len: usize,
+    ) {
+        let nested = format!("{}.", path);
+        let changed: Vec<&String> = values.into_iter()
+            .filter(|name| self.value(name).is_none_or(|origin| !origin.action.starts_with(&nested)))
+            .collect();
+        if changed.is_empty() && self.output.len() >= output_len && action.id.is_none() {
+            return;
+        }
+
+        let mut seen = HashSet::new();
+        let inputs = reads.into_iter()
+            .filter(|name| seen.insert(name.clone()))
+            .map(|name| Input { from: self.values.get(&name).copied(), name })
+            .collect();
+
+        let causes = action.caused_by.iter().flatten()
+            .map(|id| Input { name: id.clone(), from: self.ids.get(id).copied() })
+            .collect();
+
+        let index = self.dropped + self.records.len();
+        self.records.push(Origin { action: path, op: format!("{:?}({})", action.op, action.target), inputs, causes });
+        if let Some(id) = &action.id {
+            self.ids.insert(id.clone(), index);
+        }
+        for name in changed {
+            self.values.insert(name.clone(), index);
+        }
+        self.output.truncate(output_len);
+        self.output.resize(output_len, index);
+        if self.records.len() > MAX_RECORDS {
+            self.keep_last(MAX_RECORDS / 2);
+        }
+    }
+
+    pub(crate) fn forget(&mut self, name: &str) {
+        self.values.remove(name);
+    }
+
+    /// Drop all but the newest `limit` records
+    ///
+    /// Values, ids and inputs that point at a dropped record lose their
+    /// origin, as if they'd never been recorded.
+    pub fn keep_last(&mut self, limit: usize) {
+        let excess = self.records.len().saturating_sub(limit);
+        if excess == 0 {
+            return;
+        }
+        self.records.drain(..excess);
+        self.dropped += excess;
+        let first = self.dropped;
+        self.values.retain(|_, index| *index >= first);
+        self.ids.retain(|_, index| *index >= first);
+    }
+
+    /// Forget the origins of the first `lines` output lines, after the
+    /// simulator dropped those lines
+    pub fn forget_output(&mut self, lines: usize) {
+        self.output.drain(..lines.min(self.output.len()));
+    }
+
+    /// Every record `name`'s value depends on: its origin, the origins of
+    /// the values it was derived from and of the actions it was caused by,
+    /// and so on, in execution order
+    pub fn why(&self, name: &str) -> Vec<&Origin> {
+        let mut pending: Vec<usize> = self.values.get(name).copied().into_iter().collect();
+        let mut found = HashSet::new();
+        while let Some(index) = pending.pop() {
+            if !found.insert(index) {
+                continue;
+            }
+            if let Some(origin) = self.record_at(index) {
+                pending.extend(origin.inputs.iter().chain(&origin.causes).filter_map(|input| input.from));
+            }
+        }
+        let mut found: Vec<usize> = found.into_iter().collect();
+        found.sort_unstable();
+        found.into_iter().filter_map(|index| self.record_at(index)).collect()
+    }
+
+    /// How `name` came to have its value: its origin, then the origins of
+    /// everything that was derived from and of the actions it was caused by,
+    /// one indented line each
+    pub fn explain(&self, name: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(&index) = self.values.get(name) {
+            self.explain_record(name, index, 0, &mut HashSet::new(), &mut lines);
+        }
+        lines
+    }
+
+    fn explain_record(&self, name: &str, index: usize, depth: usize, seen: &mut HashSet<usize>, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        let Some(origin) = self.record_at(index) else {
+            lines.push(format!("{}{} (forgotten)", indent, name));
+            return;
+        };
+        if !seen.insert(index) {
+            lines.push(format!("{}{} ← {} {} (see above)", indent, name, origin.action, origin.op));
+            return;
+        }
+        lines.push(format!("{}{} ← {} {}", indent, name, origin.action, origin.op));
+
+        for input in &origin.inputs {
+            match input.from {
+                Some(from) => self.explain_record(&input.name, from, depth + 1, seen, lines),
+                None => lines.push(format!("{}  {} (not set)", indent, input.name)),
+            }
+        }
+        for cause in &origin.causes {
+            let name = format!("caused by {}", cause.name);
+            match cause.from {
+                Some(from) => self.explain_record(&name, from, depth + 1, seen, lines),
+                None => lines.push(format!("{}  {} (not run)", indent, name)),
+            }
+        }
+    }
+}
+
+/// `  ← 3.then[0] Bind(x)` for state displays, or nothing without an origin
+pub fn origin_suffix(origin: Option<&Origin>) -> String {
+    origin.map(|o| format!("  ← {} {}", o.action, o.op)).unwrap_or_default()
+}
+
+/// The named values (beliefs or variables) the running actions wrote, each
+/// with the value it had before, so provenance can tell what an action
+/// changed without copying every value beforehand
+///
+/// Writes are kept from when the outermost running action
+/// [`begin`](Self::begin)s until it [`finish`](Self::finish)es.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WriteLog {
+    writes: Vec<(String, Option<Value>)>,
+    open: usize,
+}
+
+impl WriteLog {
+    /// Start an action, returning the mark to read its writes from
+    pub(crate) fn begin(&mut self) -> usize {
+        self.open += 1;
+        self.writes.len()
+    }
+
+    /// End the action started at `mark`, returning what [`since`](Self::since)
+    /// would
+    pub(crate) fn finish(&mut self, mark: usize) -> HashMap<String, Option<Value>> {
+        let before = self.since(mark);
+        self.open = self.open.saturating_sub(1);
+        if self.open == 0 {
+            self.writes.clear();
+        }
+        before
+    }
+
+    /// Each value written since `mark`, with the value it had at `mark`
+    /// (`None` if it wasn't set)
+    pub(crate) fn since(&self, mark: usize) -> HashMap<String, Option<Value>> {
+        let mut before = HashMap::new();
+        for (name, old) in &self.writes[mark.min(self.writes.len())..] {
+            before.entry(name.clone()).or_insert_with(|| old.clone());
+        }
+        before
+    }
+
+    /// Note that `name` is about to be changed in `values` by other means
+    pub(crate) fn touch(&mut self, values: &HashMap<String, Value>, name: &str) {
+        self.note(name.to_string(), values.get(name).cloned());
+    }
+
+    pub(crate) fn set(&mut self, values: &mut HashMap<String, Value>, name: String, value: Value) {
+        let old = values.insert(name.clone(), value);
+        self.note(name, old);
+    }
+
+    pub(crate) fn remove(&mut self, values: &mut HashMap<String, Value>, name: &str) -> Option<Value> {
+        let old = values.remove(name);
+        if old.is_some() {
+            self.note(name.to_string(), old.clone());
+        }
+        old
+    }
+
+    /// Writes outside any action have no action to record them against
+    fn note(&mut self, name: String, old: Option<Value>) {
+        if self.open > 0 {
+            self.writes.push((name, old));
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.writes.clear();
+        self.open = 0;
+    }
+}
+
+/// The position of the action being executed, as a stack of path segments
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ActionPath(Vec<String>);
+
+impl ActionPath {
+    pub(crate) fn push(&mut self, segment: impl Into<String>) {
+        self.0.push(segment.into());
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.0.pop();
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// How many segments deep the path is, to [`truncate`](Self::truncate)
+    /// back to after unwinding
+    pub(crate) fn depth(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn truncate(&mut self, depth: usize) {
+        self.0.truncate(depth);
+    }
+
+    pub(crate) fn current(&self) -> String {
+        self.0.join(".")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Operation;
+
+    #[test]
+    fn test_record_and_explain() {
+        let mut provenance = Provenance::default();
+        let x = "x".to_string();
+        let y = "y".to_string();
+
+        provenance.record("0".into(), &Action::new("VM", Operation::Bind, "x"), vec![], [&x], 0);
+        provenance.record("1".into(), &Action::new("VM", Operation::Write, "y"), vec!["x".into(), "x".into()], [&y], 0);
+        provenance.record("2".into(), &Action::new("VM", Operation::Emit, "y"), vec!["y".into(), "z".into()], [], 1);
+
+        assert_eq!(provenance.value("y").unwrap().inputs, vec![Input { name: "x".into(), from: Some(0) }]);
+        assert_eq!(provenance.output(0).unwrap().action, "2");
+        assert_eq!(provenance.explain("y"), vec!["y ← 1 Write(y)", "  x ← 0 Bind(x)"]);
+        assert_eq!(provenance.why("y").iter().map(|o| o.action.as_str()).collect::<Vec<_>>(), vec!["0", "1"]);
+
+        // Nothing changed: nothing recorded
+        provenance.record("3".into(), &Action::new("VM", Operation::Read, "x"), vec!["x".into()], [], 1);
+        assert_eq!(provenance.records.len(), 3);
+    }
+
+    #[test]
+    fn test_explains_explicit_causes() {
+        let mut provenance = Provenance::default();
+        let paid = "paid".to_string();
+        let shipped = "shipped".to_string();
+
+        let mut order = Action::new("Buyer", Operation::Emit, "order");
+        order.id = Some("order".into());
+        let mut pay = Action::new("Buyer", Operation::Write, "paid");
+        pay.id = Some("pay".into());
+        pay.caused_by = Some(vec!["order".into()]);
+        let mut ship = Action::new("Seller", Operation::Write, "shipped");
+        ship.caused_by = Some(vec!["pay".into(), "audit".into()]);
+
+        // Emit changes nothing, but its id keeps it on record
+        provenance.record("0".into(), &order, vec![], [], 0);
+        provenance.record("1".into(), &pay, vec![], [&paid], 0);
+        provenance.record("2".into(), &ship, vec![], [&shipped], 0);
+
+        assert_eq!(provenance.explain("shipped"), vec![
+            "shipped ← 2 Write(shipped)",
+            "  caused by pay ← 1 Write(paid)",
+            "    caused by order ← 0 Emit(order)",
+            "  caused by audit (not run)",
+        ]);
+    }
+
+    #[test]
+    fn test_keep_last_forgets_old_origins() {
+        let mut provenance = Provenance::default();
+        let x = "x".to_string();
+        let y = "y".to_string();
+
+        provenance.record("0".into(), &Action::new("VM", Operation::Bind, "x"), vec![], [&x], 0);
+        provenance.record("1".into(), &Action::new("VM", Operation::Write, "y"), vec!["x".into()], [&y], 0);
+        provenance.keep_last(1);
+
+        assert!(provenance.value("x").is_none());
+        assert_eq!(provenance.explain("y"), vec!["y ← 1 Write(y)", "  x (forgotten)"]);
+
+        provenance.record("2".into(), &Action::new("VM", Operation::Emit, "y"), vec!["y".into()], [], 1);
+        assert_eq!(provenance.output(0).unwrap().inputs, vec![Input { name: "y".into(), from: Some(1) }]);
+    }
+
+    #[test]
+    fn test_write_log_keeps_first_old_value() {
+        let mut values = HashMap::from([("x".to_string(), Value::from(1))]);
+        let mut writes = WriteLog::default();
+
+        let outer = writes.begin();
+        writes.set(&mut values, "x".into(), Value::from(2));
+        let inner = writes.begin();
+        writes.set(&mut values, "x".into(), Value::from(3));
+        writes.set(&mut values, "y".into(), Value::from(4));
+        assert_eq!(writes.finish(inner), HashMap::from([("x".to_string(), Some(Value::from(2))), ("y".to_string(), None)]));
+
+        writes.remove(&mut values, "y");
+        assert_eq!(writes.finish(outer), HashMap::from([("x".to_string(), Some(Value::from(1))), ("y".to_string(), None)]));
+        assert_eq!(writes.since(0), HashMap::new());
+    }
+
+    #[test]
+    fn test_nested_origin_is_kept() {
+        let mut provenance = Provenance::default();
+        let x = "x".to_string();
+
+        provenance.record("4.then[0]".into(), &Action::new("VM", Operation::Bind, "x"), vec![], [&x], 0);
+        provenance.record("4".into(), &Action::new("VM", Operation::If, "check"), vec![], [&x], 0);
+        assert_eq!(provenance.value("x").unwrap().action, "4.then[0]");
+
+        let mut path = ActionPath::default();
+        path.push("5");
+        path.push("fib()");
+        path.push("body[1]");
+        assert_eq!(path.current(), "5.fib().body[1]");
+    }
+}
@@ -0,0 +1,107 @@
+//! Asking a brain what it believes, wants and feels, and why
+//!
+//! Host applications call these instead of parsing
+//! [`BrainState::display`]. [`BrainState::query`] answers the same
+//! questions from a one-line string, as JSON.
+
+use crate::simulator::BrainState;
+use crate::simulator::provenance::Origin;
+use anyhow::{Result, bail};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+/// How a brain feels overall
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EmotionSummary {
+    /// The strongest emotion and its intensity, if the brain feels anything
+    pub dominant: Option<(String, f64)>,
+    /// Sum of every emotion's intensity
+    pub arousal: f64,
+    /// Every emotion, strongest first
+    pub ranked: Vec<(String, f64)>,
+}
+
+/// Questions [`BrainState::query`] understands
+pub const QUERIES: &[&str] = &["belief NAME", "beliefs PREFIX", "goals PREFIX", "emotions", "why NAME"];
+
+impl BrainState {
+    pub fn belief(&self, name: &str) -> Option<&Value> {
+        self.beliefs.get(name)
+    }
+
+    /// Beliefs whose names start with `prefix`, by name
+    pub fn beliefs_matching(&self, prefix: &str) -> Vec<(&str, &Value)> {
+        let mut found: Vec<(&str, &Value)> = self.beliefs.iter()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, value)| (name.as_str(), value))
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(b.0));
+        found
+    }
+
+    /// Goals starting with `prefix`, in the order they were set
+    pub fn goals_matching(&self, prefix: &str) -> Vec<&str> {
+        self.goals.iter().filter(|goal| goal.starts_with(prefix)).map(String::as_str).collect()
+    }
+
+    pub fn emotion_summary(&self) -> EmotionSummary {
+        let mut ranked: Vec<(String, f64)> = self.emotions.iter().map(|(name, &intensity)| (name.clone(), intensity)).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        EmotionSummary {
+            dominant: ranked.first().cloned(),
+            arousal: ranked.iter().map(|(_, intensity)| intensity).sum(),
+            ranked,
+        }
+    }
+
+    /// The actions that led to belief `name`, in the order they ran; empty
+    /// if no action set it
+    pub fn why(&self, name: &str) -> Vec<&Origin> {
+        self.provenance.why(name)
+    }
+
+    /// Answer `question`, one of [`QUERIES`] such as `belief cat.color` or
+    /// `goals Must:`, as JSON
+    pub fn query(&self, question: &str) -> Result<Value> {
+        let question = question.trim();
+        let (verb, arg) = question.split_once(' ').map_or((question, ""), |(verb, arg)| (verb, arg.trim()));
+        Ok(match verb {
+            "belief" => self.belief(arg).cloned().unwrap_or(Value::Null),
+            "beliefs" => Value::Object(self.beliefs_matching(arg).into_iter().map(|(name, value)| (name.to_string(), value.clone())).collect()),
+            "goals" => json!(self.goals_matching(arg)),
+            "emotions" => serde_json::to_value(self.emotion_summary())?,
+            "why" => serde_json::to_value(self.why(arg))?,
+            _ => bail!("Unknown query '{}'. Ask one of: {}", question, QUERIES.join(", ")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+    use crate::simulator::BrainSimulator;
+
+    #[test]
+    fn test_queries() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "you", "op": "StoreFact", "target": "cat", "params": {"color": "black", "age": 3}},
+            {"actor": "you", "op": "Bind", "target": "years", "params": {"value": {"var": "cat.age"}}},
+            {"actor": "you", "op": "Oblige", "target": "chores", "params": {"duty": "feed the cat"}}
+        ]}"#).unwrap();
+        let mut brain = BrainSimulator::new();
+        brain.execute(&program).unwrap();
+        let state = brain.state();
+
+        assert_eq!(state.query("belief cat.color").unwrap(), json!("black"));
+        assert_eq!(state.query("belief dog").unwrap(), Value::Null);
+        assert_eq!(state.query("beliefs cat.").unwrap(), json!({"cat.age": 3, "cat.color": "black"}));
+        assert_eq!(state.query("goals Must").unwrap(), json!(["Must: feed the cat"]));
+        assert_eq!(state.emotion_summary().dominant, Some(("responsibility".to_string(), 0.5)));
+
+        let why: Vec<&str> = state.why("years").iter().map(|origin| origin.op.as_str()).collect();
+        assert_eq!(why, vec!["StoreFact(cat)", "Bind(years)"]);
+        assert_eq!(state.query("why years").unwrap()[1]["action"], json!("1"));
+        assert!(state.query("feelings").is_err());
+    }
+}
@@ -1,16 +1,30 @@
-use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression};
+use crate::{Action, Operation, Program, Condition, ComparisonOp, Expression, template};
+use crate::rng::SeededRng;
+use crate::simulator::kinematics::{ArmLimits, BASE, Point, distance};
+use crate::simulator::liquids::{self, Contents, millilitres};
+use crate::simulator::noise::{NoiseModel, parse_quantity};
+use crate::simulator::{Channels, HostFn, Simulator};
+use crate::simulator::control::{DEFAULT_MAX_RECURSION_DEPTH, Signal, Trigger, caught, ends_loop, nested, same_value};
+use crate::simulator::values;
+use crate::simulator::workspace::{Bounds, Violation, Workspace};
+use crate::simulator::provenance::{ActionPath, Provenance, WriteLog, origin_suffix};
+use crate::simulator::substrate::keep_last;
 use anyhow::{Result, anyhow};
+use crate::error::UclError;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Represents a learned function in robot memory
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RobotFunctionDef {
     pub args: Vec<String>,
     pub body: Vec<Action>,
 }
 
 /// Represents the state of a simulated robot
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RobotState {
     /// Position of objects in 3D space
     pub objects: HashMap<String, ObjectState>,
@@ -18,6 +32,10 @@ pub struct RobotState {
     /// Robot arm position
     pub arm_position: (f64, f64, f64),
 
+    /// Simulated seconds spent moving the arm and waiting
+    #[serde(default)]
+    pub clock: f64,
+
     /// What the robot is currently holding
     pub gripper: Option<String>,
 
@@ -30,32 +48,93 @@ pub struct RobotState {
     /// Error state
     pub errors: Vec<String>,
 
+    /// Motions the robot refused to make, in order
+    #[serde(default)]
+    pub violations: Vec<Violation>,
+
     /// Variables/memory
     pub variables: HashMap<String, serde_json::Value>,
 
     /// Learned functions/procedures
     pub functions: HashMap<String, RobotFunctionDef>,
+
+    /// Emitted values no other actor has received yet
+    #[serde(default)]
+    pub channels: Channels,
+
+    /// Status the program ended with, if a `Halt` stopped it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub halted: Option<i64>,
+
+    /// Which action produced each variable and log line
+    #[serde(default)]
+    pub provenance: Provenance,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectState {
     pub position: (f64, f64, f64),
     pub container: Option<String>,
     pub temperature: f64,
+    pub volume: f64,    // ml, 0 for solids
     pub state: String,  // "solid", "liquid", "gas", "mixed", etc.
+    /// Width, depth and height of its bounding box, if known
+    #[serde(default)]
+    pub size: Option<Point>,
+    /// Most it holds, in ml, if it's a container of known size
+    #[serde(default)]
+    pub capacity: Option<f64>,
+    /// Liquids in it, for containers
+    #[serde(default)]
+    pub contents: Contents,
+}
+
+impl ObjectState {
+    /// A fresh object at the origin and room temperature
+    pub fn new() -> Self {
+        Self {
+            position: (0.0, 0.0, 0.0),
+            container: None,
+            temperature: ROOM_TEMPERATURE,
+            volume: 0.0,
+            state: "ready".to_string(),
+            size: None,
+            capacity: None,
+            contents: Contents::new(),
+        }
+    }
+}
+
+impl Default for ObjectState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+const ROOM_TEMPERATURE: f64 = 20.0;
+
+/// What `ReadSensor` can read
+pub const SENSORS: &[&str] = &["temperature", "weight", "position"];
+
+/// Degrees per second gained by a heated object
+const DEFAULT_HEATING_RATE: f64 = 1.0;
+
 impl RobotState {
     pub fn new() -> Self {
         Self {
             objects: HashMap::new(),
             arm_position: (0.0, 0.0, 0.0),
+            clock: 0.0,
             gripper: None,
             temperatures: HashMap::new(),
             log: Vec::new(),
             errors: Vec::new(),
+            violations: Vec::new(),
             variables: HashMap::new(),
             functions: HashMap::new(),
+            channels: Channels::default(),
+            halted: None,
+            provenance: Provenance::default(),
         }
     }
 
@@ -64,8 +143,15 @@ impl RobotState {
 
         output.push_str("=== Robot State ===\n\n");
 
+        if let Some(status) = self.halted {
+            output.push_str(&format!("Halted with status {}\n\n", status));
+        }
+
         output.push_str(&format!("Arm Position: ({:.2}, {:.2}, {:.2})\n",
             self.arm_position.0, self.arm_position.1, self.arm_position.2));
+        if self.clock > 0.0 {
+            output.push_str(&format!("Clock: {:.2}s\n", self.clock));
+        }
 
         if let Some(held) = &self.gripper {
             output.push_str(&format!("Gripper: Holding {}\n", held));
@@ -77,8 +163,19 @@ impl RobotState {
         if !self.objects.is_empty() {
             output.push_str("Objects:\n");
             for (name, obj) in &self.objects {
-                output.push_str(&format!("  {} - pos:({:.1}, {:.1}, {:.1}), temp:{:.0}°C, state:{}\n",
+                output.push_str(&format!("  {} - pos:({:.1}, {:.1}, {:.1}), temp:{:.0}°C, state:{}",
                     name, obj.position.0, obj.position.1, obj.position.2, obj.temperature, obj.state));
+                if obj.volume > 0.0 {
+                    output.push_str(&format!(", volume:{:.0}ml", obj.volume));
+                }
+                if let Some(capacity) = obj.capacity {
+                    output.push_str(&format!(", capacity:{:.0}ml", capacity));
+                }
+                if !obj.contents.is_empty() {
+                    let contents: Vec<String> = obj.contents.iter().map(|(liquid, ml)| format!("{} {:.0}ml", liquid, ml)).collect();
+                    output.push_str(&format!(", contents:[{}]", contents.join(", ")));
+                }
+                output.push('\n');
             }
             output.push('\n');
         }
@@ -94,7 +191,25 @@ impl RobotState {
         if !self.log.is_empty() {
             output.push_str("Execution Log:\n");
             for (i, entry) in self.log.iter().enumerate() {
-                output.push_str(&format!("  {}. {}\n", i + 1, entry));
+                output.push_str(&format!("  {}. {}{}\n", i + 1, entry, origin_suffix(self.provenance.output(i))));
+            }
+            output.push('\n');
+        }
+
+        if !self.channels.is_empty() {
+            output.push_str("Unreceived Messages:\n");
+            for (channel, count) in self.channels.pending() {
+                output.push_str(&format!("  {}: {}\n", channel, count));
+            }
+            output.push('\n');
+        }
+
+        if !self.variables.is_empty() {
+            output.push_str("Variables:\n");
+            let mut names: Vec<&String> = self.variables.keys().collect();
+            names.sort();
+            for name in names {
+                output.push_str(&format!("  {} = {}{}\n", name, self.variables[name], origin_suffix(self.provenance.value(name))));
             }
             output.push('\n');
         }
@@ -116,12 +231,35 @@ impl Default for RobotState {
     }
 }
 
+/// Runs a custom operation (`Meditate`, or a namespaced `music:Arpeggiate`)
+/// against the state
+pub type NamespaceHandler = Box<dyn FnMut(&Action, &mut RobotState) -> Result<()> + Send>;
+
 /// Simulates a robot as a VM that executes physical operations
 pub struct RobotSimulator {
     state: RobotState,
     verbose: bool,
     recursion_depth: usize,
     max_recursion_depth: usize,
+    interrupt: Option<Arc<AtomicBool>>,
+    interrupt_handlers: Vec<Vec<Action>>,
+    triggers: Vec<Trigger>,
+    /// A trigger's handler is running, so actions in it don't check triggers
+    firing: bool,
+    namespaces: HashMap<String, NamespaceHandler>,
+    handlers: HashMap<String, NamespaceHandler>,
+    host_fns: HashMap<String, HostFn>,
+    path: ActionPath,
+    writes: WriteLog,
+    /// Locals of the function calls under way, innermost last
+    frames: Vec<HashMap<String, serde_json::Value>>,
+    /// Loops the running action is inside, within the current function
+    loop_depth: usize,
+    signal: Option<Signal>,
+    noise: Option<NoiseModel>,
+    arm: ArmLimits,
+    workspace: Option<Workspace>,
+    rng: SeededRng,
 }
 
 impl RobotSimulator {
@@ -130,7 +268,23 @@ impl RobotSimulator {
             state: RobotState::new(),
             verbose: false,
             recursion_depth: 0,
-            max_recursion_depth: 1000,
+            max_recursion_depth: DEFAULT_MAX_RECURSION_DEPTH,
+            interrupt: None,
+            interrupt_handlers: Vec::new(),
+            triggers: Vec::new(),
+            firing: false,
+            namespaces: HashMap::new(),
+            handlers: HashMap::new(),
+            host_fns: HashMap::new(),
+            path: ActionPath::default(),
+            frames: Vec::new(),
+            writes: WriteLog::default(),
+            loop_depth: 0,
+            signal: None,
+            noise: None,
+            arm: ArmLimits::default(),
+            workspace: None,
+            rng: SeededRng::from_entropy(),
         }
     }
 
@@ -139,35 +293,288 @@ impl RobotSimulator {
         self
     }
 
+    /// How deeply blocks and function calls may nest before the run fails
+    /// with a recursion error; 10,000 by default
+    pub fn with_max_recursion_depth(mut self, depth: usize) -> Self {
+        self.max_recursion_depth = depth;
+        self
+    }
+
+    /// Stop at the next action once `flag` is set (e.g. by a Ctrl-C handler),
+    /// running any OnInterrupt handlers first
+    pub fn with_interrupt(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.interrupt = Some(flag);
+        self
+    }
+
+    /// Perturb physical operations with actuation noise
+    pub fn with_noise(mut self, noise: NoiseModel) -> Self {
+        self.noise = Some(noise);
+        self
+    }
+
+    /// How fast the arm moves and how far it reaches (see
+    /// [`kinematics`](crate::simulator::kinematics))
+    pub fn with_arm(mut self, arm: ArmLimits) -> Self {
+        self.arm = arm;
+        self
+    }
+
+    /// Keep the arm on `workspace`'s table and out of objects' way (see
+    /// [`workspace`](crate::simulator::workspace))
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Seed the random source used for noise
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SeededRng::new(seed);
+        self
+    }
+
+    /// Route every `Custom("<namespace>:...")` operation to `handler`
+    pub fn register_namespace(
+        &mut self,
+        namespace: &str,
+        handler: impl FnMut(&Action, &mut RobotState) -> Result<()> + Send + 'static,
+    ) {
+        self.namespaces.insert(namespace.to_string(), Box::new(handler));
+    }
+
+    /// Route `Custom("<name>")` operations to `handler` instead of treating
+    /// them as unknown; takes precedence over a namespace handler
+    pub fn register(
+        &mut self,
+        name: &str,
+        handler: impl FnMut(&Action, &mut RobotState) -> Result<()> + Send + 'static,
+    ) {
+        self.handlers.insert(name.to_string(), Box::new(handler));
+    }
+
+    /// Run `function` for `Call` actions targeting `name` and for `{"call":
+    /// name}` expressions, passing the evaluated arguments by name; a
+    /// function the program defines itself takes precedence
+    ///
+    /// A `Call`'s params are its arguments, except `out`, which names the
+    /// variable to store the result in.
+    pub fn bind_fn(
+        &mut self,
+        name: &str,
+        function: impl FnMut(&serde_json::Map<String, serde_json::Value>) -> Result<serde_json::Value> + Send + 'static,
+    ) {
+        self.host_fns.insert(name.to_string(), Box::new(function));
+    }
+
     pub fn state(&self) -> &RobotState {
         &self.state
     }
 
+    /// Replace the whole state, e.g. to roll back to an earlier snapshot
+    pub fn restore_state(&mut self, state: RobotState) {
+        self.state = state;
+    }
+
     pub fn execute(&mut self, program: &Program) -> Result<()> {
+        self.state.halted = None;
+        self.path.clear();
+        self.writes.clear();
+        self.frames.clear();
+        self.loop_depth = 0;
+        self.signal = None;
+        let result = self.execute_groups(program);
+        match &result {
+            Err(e) => self.run_interrupt_handlers(e),
+            // Handlers only cover the run that registered them
+            Ok(()) => self.interrupt_handlers.clear(),
+        }
+        result
+    }
+
+    fn execute_groups(&mut self, program: &Program) -> Result<()> {
         if self.verbose {
             println!("🤖 Starting robot execution...\n");
         }
 
-        for (i, action) in program.actions.iter().enumerate() {
+        for group in program.simultaneous_groups() {
+            if group.len() > 1 {
+                self.execute_simultaneous(&program.actions, group)?;
+                if self.signal.take().is_some() {
+                    break;
+                }
+                continue;
+            }
+
+            let (i, action) = (group.start, &program.actions[group.start]);
             if self.verbose {
                 println!("Step {}: {:?} - {} → {}",
                     i + 1, action.op, action.actor, action.target);
             }
 
-            self.execute_action(action)?;
+            self.path.push(i.to_string());
+            let result = self.execute_action(action);
+            self.path.pop();
+            result?;
 
             if self.verbose {
                 println!();
             }
+            // A Return outside any function, or a Halt, ends the program
+            if self.signal.take().is_some() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run registered OnInterrupt handlers, most recent first, after `error`
+    /// stopped the program
+    fn run_interrupt_handlers(&mut self, error: &anyhow::Error) {
+        let handlers = std::mem::take(&mut self.interrupt_handlers);
+        if handlers.is_empty() {
+            return;
+        }
+
+        if self.verbose {
+            println!("  🚨 {}: running {} interrupt handler(s)", error, handlers.len());
+        }
+        self.state.log.push(format!("OnInterrupt({})", error));
+        self.recursion_depth = 0;
+        self.path.clear();
+        self.frames.clear();
+        self.loop_depth = 0;
+        self.signal = None;
+
+        for (k, handler) in handlers.iter().enumerate().rev() {
+            for (j, action) in handler.iter().enumerate() {
+                self.path.push(format!("interrupt[{}].body[{}]", k, j));
+                let result = self.execute_action(action);
+                self.path.pop();
+                if let Err(e) = result {
+                    self.state.log.push(format!("OnInterrupt handler failed: {}", e));
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Run actions that share a timestamp as one atomic step: if any fails the
+    /// state is rolled back to before the group, and their log entries are
+    /// merged into one
+    fn execute_simultaneous(&mut self, actions: &[Action], group: std::ops::Range<usize>) -> Result<()> {
+        let t = actions[group.start].t.unwrap_or_default();
+        if self.verbose {
+            println!("Steps {}-{} (simultaneous at t={}):", group.start + 1, group.end, t);
+            for action in &actions[group.clone()] {
+                println!("  {:?} - {} → {}", action.op, action.actor, action.target);
+            }
+        }
+
+        let before = self.state.clone();
+        let handlers = self.interrupt_handlers.len();
+        for (i, action) in actions.iter().enumerate().take(group.end).skip(group.start) {
+            self.path.push(i.to_string());
+            let result = self.execute_action(action);
+            self.path.pop();
+            if let Err(e) = result {
+                self.state = before;
+                self.interrupt_handlers.truncate(handlers);
+                return Err(e.context(format!("in simultaneous group at t={}", t)));
+            }
         }
 
+        let entries: Vec<String> = self.state.log.drain(before.log.len()..).collect();
+        self.state.log.push(format!("t={} [{}]", t, entries.join(" ‖ ")));
+
+        if self.verbose {
+            println!();
+        }
         Ok(())
     }
 
     fn execute_action(&mut self, action: &Action) -> Result<()> {
+        nested(|| self.execute_nested(action))
+    }
+
+    fn execute_nested(&mut self, action: &Action) -> Result<()> {
+        let mark = self.writes.begin();
+        let result = self.dispatch_action(action);
+        let before = self.writes.finish(mark);
+        self.record_provenance(action, &before);
+        result?;
+        self.check_triggers()
+    }
+
+    /// Note which values and log lines `action` changed, and what it read
+    fn record_provenance(&mut self, action: &Action, before: &HashMap<String, Option<serde_json::Value>>) {
+        for name in before.keys().filter(|name| !self.state.variables.contains_key(*name)) {
+            self.state.provenance.forget(name);
+        }
+        let changed: Vec<&String> = before.iter()
+            .filter(|(name, old)| self.state.variables.get(*name).is_some_and(|value| Some(value) != old.as_ref()))
+            .map(|(name, _)| name)
+            .collect();
+        // An existing value the action named but didn't change was read (e.g. Emit)
+        let existed = before.get(&action.target)
+            .map_or(self.state.variables.contains_key(&action.target), Option::is_some);
+        let mut reads = crate::analysis::reads(action);
+        if existed && !changed.contains(&&action.target) {
+            reads.push(action.target.clone());
+        }
+        self.state.provenance.record(
+            self.path.current(),
+            action,
+            reads,
+            changed,
+            self.state.log.len(),
+        );
+    }
+
+    /// A variable's value: a local of the running function call, else a
+    /// variable of the program
+    fn lookup(&self, name: &str) -> Option<&serde_json::Value> {
+        match self.frames.last() {
+            Some(locals) if locals.contains_key(name) => locals.get(name),
+            _ => self.state.variables.get(name),
+        }
+    }
+
+    /// Bind a variable: a local inside a function call, else a variable of
+    /// the program
+    fn bind(&mut self, name: &str, value: serde_json::Value) {
+        match self.frames.last_mut() {
+            Some(locals) => {
+                locals.insert(name.to_string(), value);
+            }
+            None => self.writes.set(&mut self.state.variables, name.to_string(), value),
+        }
+    }
+
+    /// Run `actions` one level deeper, as the `label` branch of the current action
+    fn execute_block(&mut self, actions: &[Action], label: &str) -> Result<()> {
+        for (j, action) in actions.iter().enumerate() {
+            if self.signal.is_some() {
+                break;
+            }
+            self.path.push(format!("{}[{}]", label, j));
+            self.recursion_depth += 1;
+            let result = self.execute_action(action);
+            self.recursion_depth -= 1;
+            self.path.pop();
+            result?;
+        }
+        Ok(())
+    }
+
+    fn dispatch_action(&mut self, action: &Action) -> Result<()> {
         // Check recursion depth
         if self.recursion_depth >= self.max_recursion_depth {
-            return Err(anyhow!("Maximum recursion depth exceeded"));
+            return Err(UclError::RecursionLimit { depth: self.max_recursion_depth }.into());
+        }
+
+        if self.interrupt.as_ref().is_some_and(|flag| flag.swap(false, Ordering::SeqCst)) {
+            return Err(UclError::Interrupted.into());
         }
 
         match &action.op {
@@ -175,23 +582,47 @@ impl RobotSimulator {
             Operation::If => self.execute_if(action),
             Operation::While => self.execute_while(action),
             Operation::For => self.execute_for(action),
+            Operation::ForEach => self.execute_for_each(action),
+            Operation::Append => self.append(action),
             Operation::DefineFunction => self.execute_define_function(action),
+            Operation::Call if self.state.functions.contains_key(&action.target) || self.host_fns.contains_key(&action.target) => {
+                self.execute_call(action)
+            }
+            Operation::Break | Operation::Continue => self.raise_loop_signal(action),
+            Operation::Return => self.raise_return(action),
+            Operation::Halt => self.halt(action),
+            Operation::Match => self.execute_match(action),
+            Operation::Try => self.execute_try(action),
+            // Any interleaving will do, and program order is one
+            Operation::Parallel => self.execute_block(action.body_actions.as_deref().unwrap_or_default(), "body"),
+            Operation::Throw => self.throw(action),
+            Operation::OnInterrupt => self.register_interrupt_handler(action),
+            Operation::When => self.register_trigger(action),
             Operation::Bind => self.bind_variable(action),
-            Operation::Return => Ok(()), // Handled by function call
 
             // Physical operations
             Operation::Gather => self.gather(action),
             Operation::Measure => self.measure(action),
+            Operation::Scan => self.scan(action),
+            Operation::ReadSensor => self.read_sensor(action),
             Operation::Heat => self.heat(action),
             Operation::Pour => self.pour(action),
             Operation::Mix => self.mix(action),
             Operation::Stir => self.stir(action),
             Operation::Place => self.place(action),
             Operation::Remove => self.remove(action),
+            Operation::Grasp => self.grasp(action),
+            Operation::Release => self.release(action),
             Operation::Steep => self.steep(action),
             Operation::Serve => self.serve(action),
             Operation::Wait => self.wait(action),
             Operation::Emit => self.emit(action),
+            Operation::Receive => self.receive(action),
+
+            Operation::Custom(name) if self.handlers.contains_key(name) => self.execute_registered(action, name),
+            Operation::Custom(name) if crate::custom_ops::split(name).is_some_and(|(ns, _)| self.namespaces.contains_key(ns)) => {
+                self.execute_namespaced(action, name)
+            }
 
             _ => {
                 let error = format!("Unsupported operation: {:?}", action.op);
@@ -211,18 +642,37 @@ impl RobotSimulator {
             if let Some(items) = params.get("items").and_then(|v| v.as_array()) {
                 for item in items {
                     if let Some(item_name) = item.as_str() {
-                        self.state.objects.insert(
-                            item_name.to_string(),
-                            ObjectState {
-                                position: (0.0, 0.0, 0.0),
-                                container: None,
-                                temperature: 20.0,
-                                state: "ready".to_string(),
-                            }
-                        );
+                        self.state.objects.insert(item_name.to_string(), ObjectState::new());
                     }
                 }
             }
+            // Where each gathered item is, as `{"cup": [x, y, z]}` or the
+            // name of a workspace zone
+            if let Some(positions) = params.get("positions").and_then(|v| v.as_object()) {
+                for (name, position) in positions {
+                    let position = match position.as_str() {
+                        Some(zone) => self.locate(zone).unwrap_or(BASE),
+                        None => point(position),
+                    };
+                    self.state.objects.entry(name.clone()).or_default().position = position;
+                }
+            }
+            // How big each item is, as `{"cup": [width, depth, height]}`
+            if let Some(sizes) = params.get("sizes").and_then(|v| v.as_object()) {
+                for (name, size) in sizes {
+                    self.state.objects.entry(name.clone()).or_default().size = Some(point(size));
+                }
+            }
+            // How much each container holds, as `{"cup": 300}` ml or `{"cup": "1.5 cups"}`
+            if let Some(capacities) = params.get("capacities").and_then(|v| v.as_object()) {
+                for (name, capacity) in capacities {
+                    let capacity = capacity.as_f64().or_else(|| {
+                        let (value, unit) = parse_quantity(capacity.as_str()?)?;
+                        millilitres(value, &unit)
+                    });
+                    self.state.objects.entry(name.clone()).or_default().capacity = capacity;
+                }
+            }
         }
 
         let msg = format!("Gathered items for {}", action.target);
@@ -239,14 +689,113 @@ impl RobotSimulator {
         let amount = action.params
             .as_ref()
             .and_then(|p| p.get("amount"))
+            .and_then(|v| v.as_str());
+
+        if let Some(amount) = amount {
+            let (description, actual) = self.actuate_amount(&format!("Measured amount of {}", action.target), amount);
+            let into = action.params.as_ref().and_then(|p| p.get("into")).and_then(|v| v.as_str());
+
+            // Measuring out a quantity brings it into the workspace, in
+            // the container it's measured into
+            if let Some(volume) = actual {
+                let volume = as_millilitres(amount, volume).unwrap_or(volume);
+                let object = self.state.objects.entry(action.target.clone()).or_default();
+                object.volume = volume;
+                if let Some(into) = into {
+                    object.container = Some(into.to_string());
+                    self.fill(into, Contents::from([(action.target.clone(), volume)]), volume);
+                }
+            }
+
+            let msg = format!("Measured {} of {}", description, action.target);
+            self.state.log.push(msg.clone());
+
+            if self.verbose {
+                println!("  📏 {}", msg);
+            }
+        }
+
+        self.record_readings(action)
+    }
+
+    fn scan(&mut self, action: &Action) -> Result<()> {
+        if !self.state.objects.contains_key(&action.target) {
+            self.record_error(format!("Scan failed: no object named {}", action.target));
+            return Ok(());
+        }
+
+        self.record_readings(action)
+    }
+
+    /// Read one sensor on the target into `params.into` (default: the
+    /// target's name), off by the noise model's `sensor_sigma` if there is one
+    fn read_sensor(&mut self, action: &Action) -> Result<()> {
+        let param = |name: &str| action.params.as_ref().and_then(|p| p.get(name)).and_then(|v| v.as_str());
+        let sensor = param("sensor").unwrap_or("temperature");
+        let into = param("into").unwrap_or(&action.target).to_string();
+        let Some(obj) = self.state.objects.get(&action.target) else {
+            self.record_error(format!("ReadSensor failed: no object named {}", action.target));
+            return Ok(());
+        };
+
+        let sigma = self.noise.as_ref().map_or(0.0, |noise| noise.sensor_sigma);
+        let (temperature, position) = (obj.temperature, obj.position);
+        // Liquids weigh about a gram per ml
+        let weight = obj.volume;
+        let mut read = |value: f64| if sigma > 0.0 { self.rng.gaussian(value, sigma) } else { value };
+        let reading = match sensor {
+            "temperature" => serde_json::json!(read(temperature)),
+            "weight" => serde_json::json!(read(weight)),
+            "position" => serde_json::json!([read(position.0), read(position.1), read(position.2)]),
+            _ => return Err(UclError::invalid(format!("Unknown sensor: {}. Use one of: {}", sensor, SENSORS.join(", "))).into()),
+        };
+
+        if sensor == "temperature" {
+            self.state.temperatures.insert(action.target.clone(), reading.as_f64().unwrap_or(temperature));
+        }
+        if self.verbose {
+            println!("  📟 {} {} reads {}", action.target, sensor, reading);
+        }
+        self.bind(&into, reading);
+        Ok(())
+    }
+
+    /// Write the target's sensor readings into variables
+    ///
+    /// Every reading is stored as `<target>.temperature`, `<target>.volume` and
+    /// `<target>.position`; the quantity selected by the `quantity` param
+    /// (default temperature) is also stored under the bare target name so
+    /// conditions can compare against it directly.
+    fn record_readings(&mut self, action: &Action) -> Result<()> {
+        let Some(obj) = self.state.objects.get(&action.target) else {
+            return Ok(());
+        };
+
+        let readings = [
+            ("temperature", serde_json::json!(obj.temperature)),
+            ("volume", serde_json::json!(obj.volume)),
+            ("position", serde_json::json!([obj.position.0, obj.position.1, obj.position.2])),
+        ];
+
+        let quantity = action.params
+            .as_ref()
+            .and_then(|p| p.get("quantity"))
             .and_then(|v| v.as_str())
-            .unwrap_or("unknown");
+            .unwrap_or("temperature");
 
-        let msg = format!("Measured {} of {}", amount, action.target);
-        self.state.log.push(msg.clone());
+        let selected = readings.iter()
+            .find(|(name, _)| *name == quantity)
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| UclError::invalid(format!("Unknown sensor quantity: {}", quantity)))?;
+
+        self.state.temperatures.insert(action.target.clone(), obj.temperature);
+        for (name, value) in readings {
+            self.bind(&format!("{}.{}", action.target, name), value);
+        }
+        self.bind(&action.target, selected.clone());
 
         if self.verbose {
-            println!("  📏 {}", msg);
+            println!("  🌡️  Reading: {} {} = {}", action.target, quantity, selected);
         }
 
         Ok(())
@@ -259,9 +808,22 @@ impl RobotSimulator {
             .and_then(|v| v.as_str())
             .unwrap_or("100°C");
 
+        let setpoint = parse_quantity(temp).map(|(value, _)| value).unwrap_or(100.0);
+        let rate = action.params
+            .as_ref()
+            .and_then(|p| p.get("rate"))
+            .and_then(|v| v.as_f64())
+            .unwrap_or(DEFAULT_HEATING_RATE);
+
         if let Some(obj) = self.state.objects.get_mut(&action.target) {
-            obj.temperature = 100.0;
-            obj.state = "boiling".to_string();
+            // With a duration the object warms towards the setpoint at `rate`
+            // degrees per second; without one it reaches the setpoint at once.
+            obj.temperature = match action.dur {
+                Some(dur) if obj.temperature < setpoint => (obj.temperature + rate * dur).min(setpoint),
+                Some(_) => obj.temperature,
+                None => setpoint,
+            };
+            obj.state = if obj.temperature >= 100.0 { "boiling" } else { "heated" }.to_string();
         }
 
         let msg = format!("Heating {} to {}", action.target, temp);
@@ -279,8 +841,21 @@ impl RobotSimulator {
             let from = params.get("from").and_then(|v| v.as_str()).unwrap_or("?");
             let into = params.get("into").and_then(|v| v.as_str()).unwrap_or("?");
             let amount = params.get("amount").and_then(|v| v.as_str()).unwrap_or("?");
+            if from != "?" && self.state.gripper.as_deref() != Some(from) {
+                self.record_violation(Violation::NotHolding { action: "pour from".to_string(), object: from.to_string() });
+                return Ok(());
+            }
+            if !self.reach_for(from)? || !self.reach_for(into)? {
+                return Ok(());
+            }
+            let (mut description, actual) = self.actuate_amount(&format!("Poured amount of {}", action.target), amount);
+            if let Some(volume) = actual.and_then(|actual| as_millilitres(amount, actual)) {
+                if let Some(poured) = self.transfer(&action.target, from, into, volume) {
+                    description = format!("{:.0}ml of requested {}", poured, amount);
+                }
+            }
 
-            let msg = format!("Poured {} from {} into {} ({})", action.target, from, into, amount);
+            let msg = format!("Poured {} from {} into {} ({})", action.target, from, into, description);
             self.state.log.push(msg.clone());
 
             if self.verbose {
@@ -291,7 +866,74 @@ impl RobotSimulator {
         Ok(())
     }
 
+    /// Pour `volume` ml of `liquid` from container `from` into `into`,
+    /// as much as `from` has of it (or of whatever it holds). Returns how
+    /// much was poured when that fell short of `volume`.
+    fn transfer(&mut self, liquid: &str, from: &str, into: &str, volume: f64) -> Option<f64> {
+        let poured = match self.state.objects.get_mut(from) {
+            Some(source) => {
+                let poured = liquids::take(&mut source.contents, liquid, volume);
+                source.volume = liquids::total(&source.contents);
+                poured
+            }
+            // Somewhere the robot isn't keeping track of, like a tap
+            None => Contents::from([(liquid.to_string(), volume)]),
+        };
+
+        let amount = liquids::total(&poured);
+        let short = amount < volume * (1.0 - self.amount_tolerance());
+        if short {
+            self.record_error(format!("Only {:.0}ml of {} in {} to pour, not {:.0}ml", amount, liquid, from, volume));
+        }
+
+        // The liquid is wherever it was last poured, once none is left behind
+        let left_behind = self.state.objects.get(from).is_some_and(|source| source.contents.contains_key(liquid));
+        if !left_behind && self.state.objects.contains_key(into) {
+            if let Some(object) = self.state.objects.get_mut(liquid) {
+                object.container = Some(into.to_string());
+            }
+        }
+        self.fill(into, poured, volume);
+        short.then_some(amount)
+    }
+
+    /// Add `liquids` to container `name`, if the robot knows it, spilling
+    /// whatever doesn't fit; an overflow is an error unless it's within
+    /// the amount tolerance of `requested`
+    fn fill(&mut self, name: &str, liquids: Contents, requested: f64) {
+        let Some(container) = self.state.objects.get_mut(name) else {
+            return;
+        };
+        liquids::add(&mut container.contents, &liquids);
+        let total = liquids::total(&container.contents);
+        let spilled = container.capacity.map_or(0.0, |capacity| total - capacity);
+        if spilled > 0.0 {
+            for volume in container.contents.values_mut() {
+                *volume *= (total - spilled) / total;
+            }
+        }
+        container.volume = total - spilled.max(0.0);
+
+        if spilled > requested * self.amount_tolerance() {
+            self.record_error(format!("{} overflowed, spilling {:.0}ml", name, spilled));
+        }
+    }
+
+    /// Largest relative amount error that still counts as success
+    fn amount_tolerance(&self) -> f64 {
+        self.noise.as_ref().map_or(0.0, |noise| noise.amount_tolerance)
+    }
+
     fn mix(&mut self, action: &Action) -> Result<()> {
+        let contents = self.state.objects.get(&action.target).map(|o| o.contents.clone()).unwrap_or_default();
+        if contents.len() > 1 {
+            let into = action.params.as_ref()
+                .and_then(|p| p.get("into"))
+                .and_then(|v| v.as_str())
+                .map_or_else(|| format!("{}_mixture", action.target), str::to_string);
+            return self.combine(&action.target, contents, into);
+        }
+
         if let Some(obj) = self.state.objects.get_mut(&action.target) {
             obj.state = "mixed".to_string();
         }
@@ -306,6 +948,40 @@ impl RobotSimulator {
         Ok(())
     }
 
+    /// Turn the liquids in `container` into one composite object, `name`,
+    /// which remembers what went into it; the ingredients are used up
+    fn combine(&mut self, container: &str, ingredients: Contents, name: String) -> Result<()> {
+        let total = liquids::total(&ingredients);
+        let (position, temperature) = match self.state.objects.get(container) {
+            Some(container) => (container.position, container.temperature),
+            None => (BASE, ROOM_TEMPERATURE),
+        };
+        let temperature_of = |liquid: &str| self.state.objects.get(liquid).map_or(temperature, |o| o.temperature);
+        let mixed_temperature = ingredients.iter().map(|(liquid, ml)| temperature_of(liquid) * ml).sum::<f64>() / total;
+
+        self.state.objects.retain(|object, state| !(ingredients.contains_key(object) && state.container.as_deref() == Some(container)));
+        if let Some(container) = self.state.objects.get_mut(container) {
+            container.contents = Contents::from([(name.clone(), total)]);
+            container.state = "mixed".to_string();
+        }
+        let msg = format!("Mixed {} in {} into {}", ingredients.keys().cloned().collect::<Vec<_>>().join(", "), container, name);
+        self.state.objects.insert(name, ObjectState {
+            position,
+            container: Some(container.to_string()),
+            temperature: mixed_temperature,
+            volume: total,
+            state: "mixed".to_string(),
+            contents: ingredients,
+            ..ObjectState::new()
+        });
+
+        self.state.log.push(msg.clone());
+        if self.verbose {
+            println!("  🥄 {}", msg);
+        }
+        Ok(())
+    }
+
     fn stir(&mut self, action: &Action) -> Result<()> {
         let msg = format!("Stirred {}", action.target);
         self.state.log.push(msg.clone());
@@ -324,8 +1000,31 @@ impl RobotSimulator {
             .and_then(|v| v.as_str())
             .unwrap_or("?");
 
+        let destination = self.locate(into).unwrap_or(BASE);
+        if let Some(held) = self.state.gripper.clone().filter(|held| *held != action.target) {
+            self.record_violation(Violation::GripperFull { holding: held, wanted: action.target.clone() });
+            return Ok(());
+        }
+        if !self.reach_for(&action.target)? || !self.reach_for(into)? {
+            return Ok(());
+        }
+        if let Some(violation) = self.placement_violation(&action.target, into, destination) {
+            self.record_violation(violation);
+            return Ok(());
+        }
+        let actual = self.actuate_position(destination);
+
         if let Some(obj) = self.state.objects.get_mut(&action.target) {
             obj.container = Some(into.to_string());
+            obj.position = actual;
+        }
+        self.state.gripper = None;
+
+        let miss = distance(destination, actual);
+        if let Some(noise) = &self.noise {
+            if miss > noise.position_tolerance {
+                self.record_error(format!("Placement of {} missed {} by {:.2}", action.target, into, miss));
+            }
         }
 
         let msg = format!("Placed {} into {}", action.target, into);
@@ -345,8 +1044,8 @@ impl RobotSimulator {
             .and_then(|v| v.as_str())
             .unwrap_or("?");
 
-        if let Some(obj) = self.state.objects.get_mut(&action.target) {
-            obj.container = None;
+        if !self.pick_up(&action.target)? {
+            return Ok(());
         }
 
         let msg = format!("Removed {} from {}", action.target, from);
@@ -359,6 +1058,56 @@ impl RobotSimulator {
         Ok(())
     }
 
+    fn grasp(&mut self, action: &Action) -> Result<()> {
+        if self.state.gripper.as_deref() == Some(action.target.as_str()) || !self.pick_up(&action.target)? {
+            return Ok(());
+        }
+
+        let msg = format!("Grasped {}", action.target);
+        self.state.log.push(msg.clone());
+
+        if self.verbose {
+            println!("  🤏 {}", msg);
+        }
+
+        Ok(())
+    }
+
+    fn release(&mut self, action: &Action) -> Result<()> {
+        if self.state.gripper.as_deref() != Some(action.target.as_str()) {
+            self.record_violation(Violation::NotHolding { action: "release".to_string(), object: action.target.clone() });
+            return Ok(());
+        }
+        self.state.gripper = None;
+
+        let msg = format!("Released {}", action.target);
+        self.state.log.push(msg.clone());
+
+        if self.verbose {
+            println!("  🖐️  {}", msg);
+        }
+
+        Ok(())
+    }
+
+    /// Reach for `object` and pick it up out of whatever it's in, unless
+    /// the gripper holds something else; whether the robot now holds it
+    fn pick_up(&mut self, object: &str) -> Result<bool> {
+        if let Some(held) = self.state.gripper.clone().filter(|held| held != object) {
+            self.record_violation(Violation::GripperFull { holding: held, wanted: object.to_string() });
+            return Ok(false);
+        }
+        if !self.reach_for(object)? {
+            return Ok(false);
+        }
+
+        if let Some(obj) = self.state.objects.get_mut(object) {
+            obj.container = None;
+        }
+        self.state.gripper = Some(object.to_string());
+        Ok(true)
+    }
+
     fn steep(&mut self, action: &Action) -> Result<()> {
         let duration = action.params
             .as_ref()
@@ -389,6 +1138,7 @@ impl RobotSimulator {
 
     fn wait(&mut self, action: &Action) -> Result<()> {
         let duration = action.dur.unwrap_or(1.0);
+        self.state.clock += duration;
 
         let msg = format!("Waiting {:.0}s for {}", duration, action.target);
         self.state.log.push(msg.clone());
@@ -401,14 +1151,25 @@ impl RobotSimulator {
     }
 
     fn emit(&mut self, action: &Action) -> Result<()> {
-        let msg = action.params
-            .as_ref()
-            .and_then(|p| p.get("content"))
-            .and_then(|v| v.as_str())
-            .unwrap_or(&action.target);
+        let content = action.params.as_ref().and_then(|p| p.get("content"));
+        let (msg, value) = match content.map(|c| serde_json::from_value::<Expression>(c.clone())) {
+            // Expressions (`{"format": ...}`, `{"var": ...}`) say what they evaluate to
+            Some(Ok(expr)) if !matches!(expr, Expression::Value(_)) => {
+                let value = self.evaluate_expression(&expr)?;
+                (values::text(&value), value)
+            }
+            _ => match content {
+                Some(content) if !content.is_string() => (content.to_string(), content.clone()),
+                _ => {
+                    let msg = content.and_then(|v| v.as_str()).unwrap_or(&action.target).to_string();
+                    (msg.clone(), serde_json::Value::String(msg))
+                }
+            },
+        };
 
         let log_msg = format!("Output: {}", msg);
         self.state.log.push(log_msg);
+        self.state.channels.send(action, value);
 
         if self.verbose {
             println!("  📢 {}", msg);
@@ -417,70 +1178,339 @@ impl RobotSimulator {
         Ok(())
     }
 
-    fn bind_variable(&mut self, action: &Action) -> Result<()> {
-        if let Some(params) = &action.params {
-            if let Some(value) = params.get("value") {
-                self.state.variables.insert(action.target.clone(), value.clone());
-
-                if self.verbose {
-                    println!("  💾 Stored: {} = {}", action.target, value);
-                }
+    /// Take a message another actor emitted on this channel into the
+    /// variable `params.into`, else the target
+    fn receive(&mut self, action: &Action) -> Result<()> {
+        let msg = match self.state.channels.take(action) {
+            Some(message) => {
+                let name = action.params.as_ref()
+                    .and_then(|p| p.get("into"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(&action.target);
+                let msg = format!("Received {} from {}", values::text(&message.content), message.from);
+                self.bind(name, message.content);
+                msg
             }
+            None => format!("Nothing to receive on {}", crate::simulator::channels::channel(action)),
+        };
+        self.state.log.push(msg.clone());
+
+        if self.verbose {
+            println!("  📨 {}", msg);
         }
 
         Ok(())
     }
 
-    fn execute_if(&mut self, action: &Action) -> Result<()> {
-        let condition = action.condition.as_ref()
-            .ok_or_else(|| anyhow!("If requires condition"))?;
+    /// Apply amount noise to a requested quantity, returning a description
+    /// of what was actually dispensed and its numeric value if known
+    fn actuate_amount(&mut self, what: &str, requested: &str) -> (String, Option<f64>) {
+        let Some((value, unit)) = parse_quantity(requested) else {
+            return (requested.to_string(), None);
+        };
+        let Some(noise) = self.noise.clone() else {
+            return (requested.to_string(), Some(value));
+        };
 
-        let result = self.evaluate_condition(condition)?;
+        let actual = value * (1.0 + self.rng.gaussian(0.0, noise.amount_sigma));
+        let relative_error = if value != 0.0 { ((actual - value) / value).abs() } else { 0.0 };
 
-        if self.verbose {
-            println!("  🤔 Condition: {}", result);
+        if relative_error > noise.amount_tolerance {
+            self.record_error(format!("{} off by {:.1}%", what, relative_error * 100.0));
         }
 
-        if result {
-            if let Some(then_actions) = &action.then_actions {
-                for then_action in then_actions {
-                    self.recursion_depth += 1;
-                    self.execute_action(then_action)?;
-                    self.recursion_depth -= 1;
-                }
-            }
-        } else if let Some(else_actions) = &action.else_actions {
-            for else_action in else_actions {
-                self.recursion_depth += 1;
-                self.execute_action(else_action)?;
-                self.recursion_depth -= 1;
+        (format!("{:.1}{} of requested {}", actual, unit, requested), Some(actual))
+    }
+
+    fn actuate_position(&mut self, intended: (f64, f64, f64)) -> (f64, f64, f64) {
+        match &self.noise {
+            Some(noise) => {
+                let sigma = noise.position_sigma;
+                (
+                    self.rng.gaussian(intended.0, sigma),
+                    self.rng.gaussian(intended.1, sigma),
+                    self.rng.gaussian(intended.2, sigma),
+                )
             }
+            None => intended,
+        }
+    }
+
+    /// Where object or workspace zone `name` is
+    fn locate(&self, name: &str) -> Option<Point> {
+        self.state.objects.get(name).map(|o| o.position)
+            .or_else(|| self.workspace.as_ref()?.zone(name).map(Bounds::center))
+    }
+
+    /// Move the arm to object or zone `name`, if there is one; whether the
+    /// arm got there (or didn't need to move)
+    fn reach_for(&mut self, name: &str) -> Result<bool> {
+        match self.locate(name) {
+            Some(point) => self.move_arm(name, point),
+            None => Ok(true),
+        }
+    }
+
+    /// Move the arm in a straight line to `point`, where `name` is, taking
+    /// as long as the arm's top speed allows; fails if it's out of reach,
+    /// and refuses, returning false, if the move leaves the workspace
+    fn move_arm(&mut self, name: &str, point: Point) -> Result<bool> {
+        if !self.arm.can_reach(point) {
+            return Err(UclError::Unreachable { object: name.to_string(), distance: distance(BASE, point), reach: self.arm.reach }.into());
+        }
+        let seconds = self.arm.travel_time(self.state.arm_position, point);
+        if seconds == 0.0 {
+            return Ok(true);
+        }
+        if let Some(violation) = self.motion_violation(name, point) {
+            self.record_violation(violation);
+            return Ok(false);
         }
 
+        self.state.arm_position = point;
+        self.state.clock += seconds;
+        // Whatever the gripper holds comes along
+        if let Some(held) = self.state.gripper.as_ref().and_then(|held| self.state.objects.get_mut(held)) {
+            held.position = point;
+        }
+        let msg = format!("Moved arm to {} in {:.2}s", name, seconds);
+        self.state.log.push(msg.clone());
+        if self.verbose {
+            println!("  🦾 {}", msg);
+        }
+        Ok(true)
+    }
+
+    /// What's wrong with moving the arm straight to `point`, where `name`
+    /// is, if anything
+    ///
+    /// Objects around either end of the move, like the cup the arm reaches
+    /// into or the one it's holding, aren't in the way.
+    fn motion_violation(&self, name: &str, point: Point) -> Option<Violation> {
+        let workspace = self.workspace.as_ref()?;
+        if !workspace.table.contains(point) {
+            return Some(Violation::OffTable { target: name.to_string(), point });
+        }
+
+        let from = self.state.arm_position;
+        self.obstacles(name)
+            .find(|(_, bounds)| bounds.crosses(from, point) && !bounds.contains(from) && !bounds.contains(point))
+            .map(|(obstacle, _)| Violation::Collision { target: name.to_string(), obstacle: obstacle.to_string() })
+    }
+
+    /// What's wrong with setting `object` down at `destination`, in `into`,
+    /// if anything: it has to fit on the table without overlapping anything
+    /// but what it's going into
+    fn placement_violation(&self, object: &str, into: &str, destination: Point) -> Option<Violation> {
+        let workspace = self.workspace.as_ref()?;
+        let size = self.state.objects.get(object)?.size?;
+        let footprint = Bounds::around(destination, size);
+        let bad = |obstacle: Option<&str>| Violation::BadPlacement {
+            object: object.to_string(),
+            into: into.to_string(),
+            obstacle: obstacle.map(str::to_string),
+        };
+        if !workspace.table.encloses(&footprint) {
+            return Some(bad(None));
+        }
+
+        self.obstacles(object)
+            .find(|(name, bounds)| *name != into && !bounds.contains(destination) && bounds.overlaps(&footprint))
+            .map(|(obstacle, _)| bad(Some(obstacle)))
+    }
+
+    /// Bounding boxes of sized objects other than `except` and whatever the
+    /// gripper holds, by name
+    fn obstacles<'a>(&'a self, except: &'a str) -> impl Iterator<Item = (&'a str, Bounds)> + 'a {
+        let mut obstacles: Vec<(&str, Bounds)> = self.state.objects.iter()
+            .filter(|(name, _)| name.as_str() != except && self.state.gripper.as_deref() != Some(name.as_str()))
+            .filter_map(|(name, object)| Some((name.as_str(), Bounds::around(object.position, object.size?))))
+            .collect();
+        // Report the same obstacle whatever order the map iterates in
+        obstacles.sort_by(|a, b| a.0.cmp(b.0));
+        obstacles.into_iter()
+    }
+
+    fn record_violation(&mut self, violation: Violation) {
+        self.record_error(violation.to_string());
+        self.state.violations.push(violation);
+    }
+
+    fn record_error(&mut self, error: String) {
+        if self.verbose {
+            println!("  ⚠️  {}", error);
+        }
+        self.state.errors.push(error);
+    }
+
+    fn bind_variable(&mut self, action: &Action) -> Result<()> {
+        if let Some(params) = &action.params {
+            if let Some(value) = params.get("value") {
+                // Variables, calls and arithmetic are evaluated; anything else is stored as-is
+                let value = match serde_json::from_value::<Expression>(value.clone()) {
+                    Ok(Expression::Value(_)) | Err(_) => value.clone(),
+                    Ok(expr) => self.evaluate_expression(&expr)?,
+                };
+                self.bind(&action.target, value.clone());
+
+                if self.verbose {
+                    println!("  💾 Stored: {} = {}", action.target, value);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_if(&mut self, action: &Action) -> Result<()> {
+        let condition = action.condition.as_ref()
+            .ok_or_else(|| UclError::missing("If", "condition"))?;
+
+        let result = self.evaluate_condition(condition)?;
+
+        if self.verbose {
+            println!("  🤔 Condition: {}", result);
+        }
+
+        if result {
+            if let Some(then_actions) = &action.then_actions {
+                self.execute_block(then_actions, "then")?;
+            }
+        } else if let Some(else_actions) = &action.else_actions {
+            self.execute_block(else_actions, "else")?;
+        }
+
+        Ok(())
+    }
+
+    /// A `Call` statement: run the function the program defined, else the
+    /// host function, with the params as arguments
+    fn execute_call(&mut self, action: &Action) -> Result<()> {
+        let mut args = serde_json::Map::new();
+        let mut out = None;
+        for (key, value) in action.params.iter().flatten() {
+            if key == "out" {
+                out = value.as_str();
+                continue;
+            }
+            // Variables, calls and arithmetic are evaluated; anything else is passed as-is
+            let value = match serde_json::from_value::<Expression>(value.clone()) {
+                Ok(Expression::Value(_)) | Err(_) => value.clone(),
+                Ok(expr) => self.evaluate_expression(&expr)?,
+            };
+            args.insert(key.clone(), value);
+        }
+
+        let result = match self.state.functions.contains_key(&action.target) {
+            true => self.call_function(&action.target, args.into_iter().collect())?,
+            false => self.call_host(&action.target, &args)?,
+        };
+        if let Some(out) = out {
+            self.bind(out, result);
+        }
         Ok(())
     }
 
+    fn call_host(&mut self, name: &str, args: &serde_json::Map<String, serde_json::Value>) -> Result<serde_json::Value> {
+        if self.verbose {
+            println!("  🔌 {}({})", name, serde_json::Value::Object(args.clone()));
+        }
+        let function = self.host_fns.get_mut(name).expect("checked by caller");
+        function(args).map_err(|e| anyhow!("{}: {}", name, e))
+    }
+
+    fn execute_registered(&mut self, action: &Action, name: &str) -> Result<()> {
+        if self.verbose {
+            println!("  🧩 {} (registered handler)", name);
+        }
+        let handler = self.handlers.get_mut(name).expect("checked by caller");
+        handler(action, &mut self.state).map_err(|e| anyhow!("{}: {}", name, e))
+    }
+
+    fn execute_namespaced(&mut self, action: &Action, name: &str) -> Result<()> {
+        let (namespace, _) = crate::custom_ops::split(name).expect("checked by caller");
+        if self.verbose {
+            println!("  🧩 {} ({} handler)", name, namespace);
+        }
+        let handler = self.namespaces.get_mut(namespace).expect("checked by caller");
+        handler(action, &mut self.state).map_err(|e| anyhow!("{}: {}", name, e))
+    }
+
+    fn register_interrupt_handler(&mut self, action: &Action) -> Result<()> {
+        let handler = action.body_actions.clone().unwrap_or_default();
+        if self.verbose {
+            println!("  🛟 Registered interrupt handler ({} actions)", handler.len());
+        }
+        self.interrupt_handlers.push(handler);
+        Ok(())
+    }
+
+    fn register_trigger(&mut self, action: &Action) -> Result<()> {
+        let trigger = Trigger::from_action(action)?;
+        if self.verbose {
+            println!("  ⚡ Registered trigger for {} ({} actions)", action.target, trigger.body.len());
+        }
+        self.triggers.push(trigger);
+        Ok(())
+    }
+
+    /// Run the handler of every `When` whose condition has become true since
+    /// it was last checked; a condition that can't be evaluated yet (say, of
+    /// a variable not bound yet) doesn't hold
+    fn check_triggers(&mut self) -> Result<()> {
+        if self.firing || self.triggers.is_empty() || self.signal == Some(Signal::Halt) {
+            return Ok(());
+        }
+        self.firing = true;
+        // Triggers watch variables, whichever function call is running
+        let frames = std::mem::take(&mut self.frames);
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let mut signal = self.signal.take();
+        let mut result = Ok(());
+        for k in 0..self.triggers.len() {
+            let condition = self.triggers[k].condition.clone();
+            let holds = self.evaluate_condition(&condition).unwrap_or(false);
+            let rising = holds && !self.triggers[k].held;
+            self.triggers[k].held = holds;
+            if rising {
+                if self.verbose {
+                    println!("  ⚡ Trigger {} fired", k);
+                }
+                let body = self.triggers[k].body.clone();
+                result = self.execute_block(&body, &format!("trigger[{}].body", k));
+                // A Return only ends the handler, but a Halt ends the program
+                if self.signal.take() == Some(Signal::Halt) {
+                    signal = Some(Signal::Halt);
+                }
+                if result.is_err() || signal == Some(Signal::Halt) {
+                    break;
+                }
+            }
+        }
+        self.loop_depth = loop_depth;
+        self.frames = frames;
+        self.signal = signal;
+        self.firing = false;
+        result
+    }
+
     fn execute_while(&mut self, action: &Action) -> Result<()> {
         let condition = action.condition.as_ref()
-            .ok_or_else(|| anyhow!("While requires condition"))?;
+            .ok_or_else(|| UclError::missing("While", "condition"))?;
 
         let mut iterations = 0;
         const MAX_ITERATIONS: usize = 10000;
 
         while self.evaluate_condition(condition)? {
             if iterations >= MAX_ITERATIONS {
-                return Err(anyhow!("While loop exceeded maximum iterations"));
-            }
-
-            if let Some(body_actions) = &action.body_actions {
-                for body_action in body_actions {
-                    self.recursion_depth += 1;
-                    self.execute_action(body_action)?;
-                    self.recursion_depth -= 1;
-                }
+                return Err(UclError::LoopLimit { iterations: MAX_ITERATIONS }.into());
             }
 
+            self.execute_body(action)?;
             iterations += 1;
+            if ends_loop(&mut self.signal) {
+                break;
+            }
         }
 
         if self.verbose {
@@ -490,44 +1520,219 @@ impl RobotSimulator {
         Ok(())
     }
 
+    /// One iteration of a loop's body; a `Break` or `Continue` in it is left
+    /// in `signal` for the loop
+    fn execute_body(&mut self, action: &Action) -> Result<()> {
+        if let Some(body_actions) = &action.body_actions {
+            self.loop_depth += 1;
+            self.execute_block(body_actions, "body")?;
+            self.loop_depth -= 1;
+        }
+        Ok(())
+    }
+
+    fn raise_loop_signal(&mut self, action: &Action) -> Result<()> {
+        if self.loop_depth == 0 {
+            return Err(UclError::parse(format!("{:?} outside a loop", action.op)).into());
+        }
+        if self.verbose {
+            println!("  ⏭️  {:?}", action.op);
+        }
+        self.signal = Some(if action.op == Operation::Break { Signal::Break } else { Signal::Continue });
+        Ok(())
+    }
+
+    /// Leave the running function with `params.value`, evaluated (null
+    /// without one); outside a function this ends the program
+    fn raise_return(&mut self, action: &Action) -> Result<()> {
+        let value = match action.params.as_ref().and_then(|p| p.get("value")) {
+            Some(value) => self.evaluate_param(value)?,
+            None => serde_json::Value::Null,
+        };
+        if self.verbose {
+            println!("  ↩️  Return {}", value);
+        }
+        self.signal = Some(Signal::Return(value));
+        Ok(())
+    }
+
+    /// End the whole program with `params.status`, 0 by default
+    fn halt(&mut self, action: &Action) -> Result<()> {
+        let status = match action.params.as_ref().and_then(|p| p.get("status")) {
+            Some(status) => self.evaluate_param(status)?.as_i64()
+                .ok_or_else(|| UclError::invalid("Halt status must be an integer"))?,
+            None => 0,
+        };
+        if self.verbose {
+            println!("  🛑 Halt with status {}", status);
+        }
+        self.state.halted = Some(status);
+        self.signal = Some(Signal::Halt);
+        Ok(())
+    }
+
+    /// Run the body; if it fails, unwind to here, bind the error (`error`, or
+    /// the name in `params.error`) and run the catch block
+    fn execute_try(&mut self, action: &Action) -> Result<()> {
+        let (depth, path, loop_depth) = (self.recursion_depth, self.path.depth(), self.loop_depth);
+        let error = match self.execute_block(action.body_actions.as_deref().unwrap_or_default(), "body") {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        let Some(value) = caught(&error) else { return Err(error) };
+        self.recursion_depth = depth;
+        self.path.truncate(path);
+        self.loop_depth = loop_depth;
+
+        let name = action.params.as_ref()
+            .and_then(|p| p.get("error"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("error");
+        if self.verbose {
+            println!("  🪂 Caught: {}", value);
+        }
+        self.bind(name, value);
+        self.execute_block(action.catch_actions.as_deref().unwrap_or_default(), "catch")
+    }
+
+    /// Fail with `params.value` (evaluated), or the target's name without one
+    fn throw(&mut self, action: &Action) -> Result<()> {
+        let value = match action.params.as_ref().and_then(|p| p.get("value")) {
+            Some(value) => self.evaluate_param(value)?,
+            None => serde_json::json!(action.target),
+        };
+        Err(UclError::Thrown { value }.into())
+    }
+
+    /// Run the first arm whose `value` equals `params.value` (evaluated) or
+    /// whose `when` holds, or `else` if none does
+    fn execute_match(&mut self, action: &Action) -> Result<()> {
+        let value = action.params.as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Match", "'value' parameter"))?;
+        let value = self.evaluate_param(value)?;
+
+        let cases = action.match_cases().transpose()?.unwrap_or_default();
+        for (i, case) in cases.iter().enumerate() {
+            let hit = match (&case.value, &case.when) {
+                (Some(pattern), _) => same_value(&value, pattern),
+                (None, Some(condition)) => self.evaluate_condition(condition)?,
+                (None, None) => true,
+            };
+            if hit {
+                if self.verbose {
+                    println!("  🔀 {} matched case {}", value, i);
+                }
+                return self.execute_block(&case.then, &format!("params.cases[{}].then", i));
+            }
+        }
+        if let Some(else_actions) = &action.else_actions {
+            self.execute_block(else_actions, "else")?;
+        }
+        Ok(())
+    }
+
+    /// A param that may be an expression, evaluated; plain values as given
+    fn evaluate_param(&mut self, value: &serde_json::Value) -> Result<serde_json::Value> {
+        match serde_json::from_value::<Expression>(value.clone()) {
+            Ok(Expression::Value(_)) | Err(_) => Ok(value.clone()),
+            Ok(expr) => self.evaluate_expression(&expr),
+        }
+    }
+
     fn execute_for(&mut self, action: &Action) -> Result<()> {
         let loop_var = action.loop_var.as_ref()
-            .ok_or_else(|| anyhow!("For requires variable"))?;
+            .ok_or_else(|| UclError::missing("For", "variable"))?;
         let from_expr = action.from_expr.as_ref()
-            .ok_or_else(|| anyhow!("For requires from expression"))?;
+            .ok_or_else(|| UclError::missing("For", "from expression"))?;
         let to_expr = action.to_expr.as_ref()
-            .ok_or_else(|| anyhow!("For requires to expression"))?;
+            .ok_or_else(|| UclError::missing("For", "to expression"))?;
 
         let from_val = self.evaluate_expression(from_expr)?;
         let to_val = self.evaluate_expression(to_expr)?;
 
-        let from_i = from_val.as_i64().ok_or_else(|| anyhow!("For from must be integer"))?;
-        let to_i = to_val.as_i64().ok_or_else(|| anyhow!("For to must be integer"))?;
+        let from_i = from_val.as_i64().ok_or_else(|| UclError::invalid("For from must be integer"))?;
+        let to_i = to_val.as_i64().ok_or_else(|| UclError::invalid("For to must be integer"))?;
 
         for i in from_i..=to_i {
             // Set loop variable
-            self.state.variables.insert(loop_var.clone(), serde_json::json!(i));
+            self.bind(loop_var, serde_json::json!(i));
 
-            if let Some(body_actions) = &action.body_actions {
-                for body_action in body_actions {
-                    self.recursion_depth += 1;
-                    self.execute_action(body_action)?;
-                    self.recursion_depth -= 1;
-                }
+            self.execute_body(action)?;
+            if ends_loop(&mut self.signal) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Names of the state a `ForEach` can go `over`
+    pub const COLLECTIONS: &'static [&'static str] = &["objects", "variables", "temperatures"];
+
+    fn collection(&self, name: &str) -> Result<Vec<serde_json::Value>> {
+        Ok(match name {
+            "objects" => values::keys(&self.state.objects),
+            "variables" => values::keys(&self.state.variables),
+            "temperatures" => values::keys(&self.state.temperatures),
+            _ => return Err(UclError::invalid(format!("ForEach can't go over '{}'; the robot has {}", name, Self::COLLECTIONS.join(", "))).into()),
+        })
+    }
+
+    fn execute_for_each(&mut self, action: &Action) -> Result<()> {
+        let loop_var = action.loop_var.as_ref()
+            .ok_or_else(|| UclError::missing("ForEach", "variable"))?;
+        let params = action.params.as_ref();
+        let items = match params.and_then(|p| p.get("over")) {
+            Some(name) => self.collection(name.as_str().ok_or_else(|| UclError::invalid("ForEach 'over' must name a collection"))?)?,
+            None => {
+                let list = params.and_then(|p| p.get("in"))
+                    .ok_or_else(|| UclError::missing("ForEach", "'in' or 'over' parameter"))?;
+                values::items(self.evaluate_param(list)?)?
+            }
+        };
+
+        for item in items {
+            self.bind(loop_var, item);
+
+            self.execute_body(action)?;
+            if ends_loop(&mut self.signal) {
+                break;
             }
         }
 
         Ok(())
     }
 
+    fn append(&mut self, action: &Action) -> Result<()> {
+        let item = action.params.as_ref()
+            .and_then(|p| p.get("value"))
+            .ok_or_else(|| UclError::missing("Append", "'value' parameter"))?;
+        let item = self.evaluate_param(item)?;
+
+        // Inside a function this adds to a variable too, unless a local hides it
+        let global = self.frames.last().is_some_and(|locals| !locals.contains_key(&action.target))
+            && self.state.variables.contains_key(&action.target);
+        let list = values::append(self.lookup(&action.target).cloned(), item)?;
+        if self.verbose {
+            println!("  ➕ {} = {}", action.target, list);
+        }
+        if global {
+            self.writes.set(&mut self.state.variables, action.target.clone(), list);
+        } else {
+            self.bind(&action.target, list);
+        }
+        Ok(())
+    }
+
     fn execute_define_function(&mut self, action: &Action) -> Result<()> {
         let func_name = &action.target;
         let params = action.params.as_ref()
-            .ok_or_else(|| anyhow!("DefineFunction requires params"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "params"))?;
 
         let args = params.get("args")
             .and_then(|v| v.as_array())
-            .ok_or_else(|| anyhow!("DefineFunction requires args array"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "args array"))?;
 
         let arg_names: Vec<String> = args.iter()
             .filter_map(|v| v.as_str())
@@ -535,7 +1740,7 @@ impl RobotSimulator {
             .collect();
 
         let body_value = params.get("body")
-            .ok_or_else(|| anyhow!("DefineFunction requires body"))?;
+            .ok_or_else(|| UclError::missing("DefineFunction", "body"))?;
 
         let body_actions: Vec<Action> = serde_json::from_value(body_value.clone())?;
 
@@ -612,6 +1817,17 @@ impl RobotSimulator {
             Condition::Not { operand } => {
                 Ok(!self.evaluate_condition(operand)?)
             }
+            Condition::Expression { value } => Ok(values::truthy(&self.evaluate_expression(value)?)),
+            Condition::Matches { value, pattern } => values::matches(&self.evaluate_expression(value)?, pattern),
+            Condition::StartsWith { value, prefix } => {
+                let (value, prefix) = (self.evaluate_expression(value)?, self.evaluate_expression(prefix)?);
+                Ok(values::text(&value).starts_with(&values::text(&prefix)))
+            }
+            Condition::Contains { value, part } => {
+                let (value, part) = (self.evaluate_expression(value)?, self.evaluate_expression(part)?);
+                Ok(values::contains(&value, &part))
+            }
+            Condition::Exists { var } => Ok(self.lookup(var).is_some() || self.state.objects.contains_key(var)),
         }
     }
 
@@ -619,16 +1835,16 @@ impl RobotSimulator {
         match expr {
             Expression::Value(v) => Ok(v.clone()),
             Expression::Variable { var } => {
-                self.state.variables.get(var)
+                self.lookup(var)
                     .cloned()
-                    .ok_or_else(|| anyhow!("Variable not found: {}", var))
+                    .ok_or_else(|| UclError::UndefinedVariable { name: var.clone() }.into())
             }
             Expression::BinaryOp { expr: bin_op } => {
                 let left_val = self.evaluate_expression(&bin_op.left)?;
                 let right_val = self.evaluate_expression(&bin_op.right)?;
 
-                let left_num = left_val.as_f64().ok_or_else(|| anyhow!("Left operand must be number"))?;
-                let right_num = right_val.as_f64().ok_or_else(|| anyhow!("Right operand must be number"))?;
+                let left_num = left_val.as_f64().ok_or_else(|| UclError::invalid("Left operand must be number"))?;
+                let right_num = right_val.as_f64().ok_or_else(|| UclError::invalid("Right operand must be number"))?;
 
                 let result = match bin_op.op.as_str() {
                     "+" => left_num + right_num,
@@ -636,64 +1852,87 @@ impl RobotSimulator {
                     "*" => left_num * right_num,
                     "/" => {
                         if right_num == 0.0 {
-                            return Err(anyhow!("Division by zero"));
+                            return Err(UclError::invalid("Division by zero").into());
                         }
                         left_num / right_num
                     }
                     "%" => left_num % right_num,
-                    _ => return Err(anyhow!("Unknown operator: {}", bin_op.op)),
+                    _ => return Err(UclError::unsupported(format!("binary operator '{}'", bin_op.op), "robot").into()),
                 };
 
                 Ok(serde_json::json!(result))
             }
+            Expression::List { list } => {
+                let items = list.iter().map(|item| self.evaluate_expression(item)).collect::<Result<_>>()?;
+                Ok(serde_json::Value::Array(items))
+            }
+            Expression::Index { index, at } => {
+                let (list, at) = (self.evaluate_expression(index)?, self.evaluate_expression(at)?);
+                values::index(&list, &at)
+            }
+            Expression::Length { length } => values::length(&self.evaluate_expression(length)?),
+            Expression::StringOp { string } => {
+                let args = string.args.iter().map(|arg| self.evaluate_expression(arg)).collect::<Result<Vec<_>>>()?;
+                values::string_op(&string.op, &args)
+            }
+            Expression::Unary { unary } => {
+                let operand = self.evaluate_expression(&unary.operand)?;
+                values::unary(&unary.op, &operand)
+            }
+            Expression::Test { test } => Ok(serde_json::Value::Bool(self.evaluate_condition(test)?)),
+            Expression::Format { format } => {
+                let text = template::render(format, |name| {
+                    self.lookup(name)
+                        .map(values::text)
+                        .ok_or_else(|| UclError::UndefinedVariable { name: name.to_string() }.into())
+                })?;
+                Ok(serde_json::Value::String(text))
+            }
+            Expression::FunctionCall { call, args } if !self.state.functions.contains_key(call) && self.host_fns.contains_key(call) => {
+                let mut values = serde_json::Map::new();
+                for (name, expr) in args {
+                    values.insert(name.clone(), self.evaluate_expression(expr)?);
+                }
+                self.call_host(call, &values)
+            }
             Expression::FunctionCall { call, args } => {
-                // Get function definition
-                let func_def = self.state.functions.get(call)
-                    .ok_or_else(|| anyhow!("Function not defined: {}", call))?
-                    .clone();
-
-                // Save current variable state
-                let saved_vars: HashMap<String, serde_json::Value> = func_def.args.iter()
-                    .filter_map(|arg| self.state.variables.get(arg).map(|v| (arg.clone(), v.clone())))
-                    .collect();
-
-                // Bind arguments
+                // Arguments are evaluated in the caller and become the call's first locals
+                let mut locals = HashMap::new();
                 for (arg_name, arg_expr) in args {
-                    let arg_value = self.evaluate_expression(arg_expr)?;
-                    self.state.variables.insert(arg_name.clone(), arg_value);
+                    locals.insert(arg_name.clone(), self.evaluate_expression(arg_expr)?);
                 }
-
-                // Execute function body
-                let mut return_value = serde_json::Value::Null;
-                for action in &func_def.body {
-                    // Check for Return operation
-                    if matches!(action.op, Operation::Return) {
-                        if let Some(params) = &action.params {
-                            if let Some(value_expr) = params.get("value") {
-                                if let Ok(expr) = serde_json::from_value::<Expression>(value_expr.clone()) {
-                                    return_value = self.evaluate_expression(&expr)?;
-                                } else {
-                                    return_value = value_expr.clone();
-                                }
-                            }
-                        }
-                        break;
-                    }
-
-                    self.recursion_depth += 1;
-                    self.execute_action(action)?;
-                    self.recursion_depth -= 1;
-                }
-
-                // Restore saved variables
-                for (arg_name, saved_value) in saved_vars {
-                    self.state.variables.insert(arg_name, saved_value);
-                }
-
-                Ok(return_value)
+                self.call_function(call, locals)
             }
         }
     }
+
+    /// Run the program's function `name` in a new frame holding `locals`,
+    /// until it ends or a `Return` anywhere in it
+    fn call_function(&mut self, name: &str, locals: HashMap<String, serde_json::Value>) -> Result<serde_json::Value> {
+        let func_def = self.state.functions.get(name)
+            .ok_or_else(|| UclError::UndefinedFunction { name: name.to_string() })?
+            .clone();
+
+        // The body sees its own locals and the variables, not the caller's
+        // locals; loops around the call aren't the body's to break
+        self.frames.push(locals);
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.path.push(format!("{}()", name));
+        let result = self.execute_block(&func_def.body, "body");
+        self.path.pop();
+        self.loop_depth = loop_depth;
+        self.frames.pop();
+        let return_value = match self.signal.take() {
+            Some(Signal::Return(value)) => value,
+            // A Halt keeps going up to the program
+            signal => {
+                self.signal = signal;
+                serde_json::Value::Null
+            }
+        };
+
+        result.map(|_| return_value)
+    }
 }
 
 impl Default for RobotSimulator {
@@ -702,3 +1941,593 @@ impl Default for RobotSimulator {
     }
 }
 
+impl Simulator for RobotSimulator {
+    fn name(&self) -> &'static str {
+        "robot"
+    }
+
+    fn execute(&mut self, program: &Program) -> Result<()> {
+        RobotSimulator::execute(self, program)
+    }
+
+    fn snapshot(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(&self.state)?)
+    }
+
+    fn restore(&mut self, snapshot: serde_json::Value) -> Result<()> {
+        self.state = serde_json::from_value(snapshot)?;
+        Ok(())
+    }
+
+    fn value(&self, name: &str) -> Option<serde_json::Value> {
+        self.state.variables.get(name).cloned()
+    }
+
+    fn output(&self) -> &[String] {
+        &self.state.log
+    }
+
+    fn errors(&self) -> &[String] {
+        &self.state.errors
+    }
+
+    fn reset(&mut self) {
+        self.state = RobotState::new();
+        self.interrupt_handlers.clear();
+        self.triggers.clear();
+        self.recursion_depth = 0;
+        self.path.clear();
+        self.writes.clear();
+        self.frames.clear();
+        self.loop_depth = 0;
+        self.signal = None;
+    }
+
+    fn trim_history(&mut self, limit: usize) {
+        let state = &mut self.state;
+        let dropped = keep_last(&mut state.log, limit);
+        state.provenance.forget_output(dropped);
+        state.provenance.keep_last(limit);
+        keep_last(&mut state.errors, limit);
+        keep_last(&mut state.violations, limit);
+    }
+
+    fn display(&self) -> String {
+        self.state.display()
+    }
+}
+
+
+/// `value` of the unit `amount` was given in, like "250ml", in ml, if that
+/// unit measures volume
+fn as_millilitres(amount: &str, value: f64) -> Option<f64> {
+    let (_, unit) = parse_quantity(amount)?;
+    millilitres(value, &unit)
+}
+
+/// A point from a JSON `[x, y, z]` array, missing coordinates being 0
+fn point(value: &serde_json::Value) -> Point {
+    let coordinate = |i: usize| value.get(i).and_then(|v| v.as_f64()).unwrap_or(0.0);
+    (coordinate(0), coordinate(1), coordinate(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_writes_readings() {
+        let mut robot = RobotSimulator::new();
+        let mut params = HashMap::new();
+        params.insert("amount".to_string(), serde_json::json!("250ml"));
+        params.insert("quantity".to_string(), serde_json::json!("volume"));
+
+        let action = Action::new("cook", Operation::Measure, "water")
+            .with_params(params);
+
+        robot.execute_action(&action).unwrap();
+
+        assert_eq!(robot.state.variables.get("water").unwrap(), &serde_json::json!(250.0));
+        assert_eq!(robot.state.variables.get("water.temperature").unwrap(), &serde_json::json!(20.0));
+    }
+
+    #[test]
+    fn test_closed_loop_on_noisy_sensor() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "cook", "op": "Gather", "target": "kitchen", "params": {"items": ["kettle"]}},
+            {"actor": "cook", "op": "Measure", "target": "water", "params": {"amount": "500ml", "into": "kettle"}},
+            {"actor": "cook", "op": "ReadSensor", "target": "kettle", "params": {"sensor": "weight", "into": "grams"}},
+            {"actor": "cook", "op": "ReadSensor", "target": "water", "params": {"into": "reading"}},
+            {"actor": "cook", "op": "While", "target": "heating",
+             "condition": {"type": "comparison", "op": "<", "left": {"var": "reading"}, "right": 95},
+             "body": [
+                {"actor": "cook", "op": "Heat", "target": "water", "dur": 5.0, "params": {"temperature": "100°C", "rate": 2.0}},
+                {"actor": "cook", "op": "ReadSensor", "target": "water", "params": {"sensor": "temperature", "into": "reading"}}
+             ]}
+        ]}"#).unwrap();
+
+        let run = |seed: u64| {
+            let noise = NoiseModel::new(0.0, 0.0).with_sensor_sigma(3.0);
+            let mut robot = RobotSimulator::new().with_noise(noise).with_seed(seed);
+            robot.execute(&program).unwrap();
+            robot.state
+        };
+        let state = run(7);
+        let reading = state.variables["reading"].as_f64().unwrap();
+        assert!(reading >= 95.0);
+        // The loop stopped on the reading, not on the real temperature
+        assert_ne!(reading, state.objects["water"].temperature);
+        assert_eq!(run(7).variables["reading"], state.variables["reading"]);
+        assert_ne!(state.variables["grams"], serde_json::json!(500.0));
+
+        let mut exact = RobotSimulator::new();
+        exact.execute(&program).unwrap();
+        assert_eq!(exact.state.variables["grams"], serde_json::json!(500.0));
+        assert_eq!(exact.state.variables["reading"], serde_json::json!(100.0));
+
+        let wrong = Action::new("cook", Operation::ReadSensor, "kettle")
+            .with_params(HashMap::from([("sensor".to_string(), serde_json::json!("smell"))]));
+        assert!(exact.execute_action(&wrong).unwrap_err().to_string().contains("Unknown sensor: smell"));
+    }
+
+    #[test]
+    fn test_heat_until_hot_terminates() {
+        let content = std::fs::read_to_string("examples/boil_water.json").unwrap();
+        let program = Program::from_json(&content).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+
+        let temperature = robot.state.variables.get("water").unwrap().as_f64().unwrap();
+        assert!(temperature >= 95.0);
+        assert!(robot.state.errors.is_empty());
+    }
+
+    #[test]
+    fn test_simultaneous_group_logs_one_entry() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "left_arm", "op": "Gather", "target": "cup", "t": 0, "params": {"items": ["cup"]}},
+            {"actor": "right_arm", "op": "Gather", "target": "kettle", "t": 0, "params": {"items": ["kettle"]}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+
+        assert_eq!(robot.state.log.len(), 1);
+        assert!(robot.state.log[0].starts_with("t=0 ["));
+        assert!(robot.state.objects.contains_key("cup") && robot.state.objects.contains_key("kettle"));
+    }
+
+    #[test]
+    fn test_on_interrupt_runs_after_fatal_error() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "kitchen", "params": {"items": ["kettle"]}},
+            {"actor": "robot", "op": "OnInterrupt", "target": "heat",
+             "body": [{"actor": "robot", "op": "Heat", "target": "kettle", "params": {"temperature": "20°C"}}]},
+            {"actor": "robot", "op": "Heat", "target": "kettle", "params": {"temperature": "100°C"}},
+            {"actor": "robot", "op": "If", "target": "broken"}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        assert!(robot.execute(&program).is_err());
+
+        assert_eq!(robot.state.objects["kettle"].temperature, 20.0);
+        assert!(robot.state.log.iter().any(|l| l.starts_with("OnInterrupt(")));
+    }
+
+    #[test]
+    fn test_namespace_handler() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": {"Custom": "lab:Centrifuge"}, "target": "sample", "params": {"rpm": 3000}},
+            {"actor": "robot", "op": {"Custom": "kitchen:Flambe"}, "target": "pan"}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.register_namespace("lab", |action, state| {
+            state.log.push(format!("spun {} at {}", action.target, action.params.as_ref().unwrap()["rpm"]));
+            Ok(())
+        });
+        robot.execute(&program).unwrap();
+
+        assert_eq!(robot.state.log, vec!["spun sample at 3000"]);
+        // No handler for `kitchen`, so it falls back to the unsupported path
+        assert_eq!(robot.state.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_break_leaves_loop() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "For", "target": "stir", "variable": "turn", "from": 1, "to": 100, "body": [
+                {"actor": "robot", "op": "If", "target": "dizzy", "condition": {"type": "comparison", "op": "==", "left": {"var": "turn"}, "right": 4},
+                 "then": [{"actor": "robot", "op": "Break", "target": "stir"}]},
+                {"actor": "robot", "op": "Stir", "target": "tea"}
+            ]},
+            {"actor": "robot", "op": "Continue", "target": "nothing"}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        let err = robot.execute(&program).unwrap_err();
+
+        assert_eq!(robot.state.variables["turn"], serde_json::json!(4));
+        assert_eq!(robot.state.log.iter().filter(|l| l.contains("Stir")).count(), 3, "{:?}", robot.state.log);
+        assert_eq!(err.to_string(), "Continue outside a loop");
+    }
+
+    #[test]
+    fn test_emit_formats_message() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Bind", "target": "cups", "params": {"value": 3}},
+            {"actor": "robot", "op": "Emit", "target": "status", "params": {"content": {"format": "Poured {cups} cups"}}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.log.last().unwrap(), "Output: Poured 3 cups");
+    }
+
+    #[test]
+    fn test_for_each_over_list() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Grasp", "target": "pot"},
+            {"actor": "robot", "op": "Append", "target": "cups", "params": {"value": "red"}},
+            {"actor": "robot", "op": "Append", "target": "cups", "params": {"value": "blue"}},
+            {"actor": "robot", "op": "ForEach", "target": "fill", "variable": "cup", "params": {"in": {"var": "cups"}}, "body": [
+                {"actor": "robot", "op": "Pour", "target": "tea", "params": {"from": "pot", "into": {"var": "cup"}}}
+            ]}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+
+        assert_eq!(robot.state.variables["cups"], serde_json::json!(["red", "blue"]));
+        assert_eq!(robot.state.variables["cup"], serde_json::json!("blue"));
+        assert_eq!(robot.state.log.iter().filter(|l| l.contains("Pour")).count(), 2, "{:?}", robot.state.log);
+    }
+
+    #[test]
+    fn test_when_fires_each_time_condition_becomes_true() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "When", "target": "boiled", "condition": {"type": "comparison", "op": ">=", "left": {"var": "temperature"}, "right": 100},
+             "body": [{"actor": "robot", "op": "Append", "target": "pours", "params": {"value": {"var": "temperature"}}}]},
+            {"actor": "robot", "op": "For", "target": "heat", "variable": "temperature", "from": 98, "to": 101,
+             "body": [{"actor": "robot", "op": "Wait", "target": "kettle"}]},
+            {"actor": "robot", "op": "Bind", "target": "temperature", "params": {"value": 20}},
+            {"actor": "robot", "op": "Bind", "target": "temperature", "params": {"value": 100}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        // Once on the way up to 101, not again at 101, and again after cooling
+        assert_eq!(robot.state.variables["pours"], serde_json::json!([100, 100]));
+    }
+
+    #[test]
+    fn test_receive_takes_other_robots_messages() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "sensor", "op": "Emit", "target": "reading", "params": {"content": 71.5}},
+            {"actor": "arm", "op": "Receive", "target": "reading"},
+            {"actor": "arm", "op": "Receive", "target": "reading"}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.variables["reading"], serde_json::json!(71.5));
+        assert_eq!(robot.state.log[1..], ["Received 71.5 from sensor", "Nothing to receive on reading"]);
+    }
+
+    #[test]
+    fn test_parallel_runs_body() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Parallel", "target": "tea", "body": [
+                {"actor": "robot", "op": "Heat", "target": "kettle", "params": {"temperature": "100C"}},
+                {"actor": "robot", "op": "Place", "target": "cup"}
+            ]}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert!(robot.state.log.iter().any(|l| l.contains("kettle")), "{:?}", robot.state.log);
+        assert!(robot.state.log.iter().any(|l| l.contains("cup")), "{:?}", robot.state.log);
+    }
+
+    #[test]
+    fn test_for_each_over_gathered_objects() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "tea", "params": {"items": ["kettle", "cup"]}},
+            {"actor": "robot", "op": "ForEach", "target": "check", "variable": "item", "params": {"over": "objects"}, "body": [
+                {"actor": "robot", "op": "Append", "target": "checked", "params": {"value": {"var": "item"}}}
+            ]}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.variables["checked"], serde_json::json!(["cup", "kettle"]));
+    }
+
+    #[test]
+    fn test_exists_sees_objects_and_variables() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "tea", "params": {"items": ["kettle"]}},
+            {"actor": "robot", "op": "If", "target": "check", "condition": {"type": "and", "operands": [
+                {"type": "exists", "var": "kettle"}, {"type": "not", "operand": {"type": "exists", "var": "cup"}}
+             ]}, "then": [{"actor": "robot", "op": "Bind", "target": "ready", "params": {"value": true}}]}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.variables["ready"], serde_json::json!(true));
+    }
+
+    #[test]
+    fn test_match_picks_one_arm() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Bind", "target": "drink", "params": {"value": "tea"}},
+            {"actor": "robot", "op": "Match", "target": "order", "params": {"value": {"var": "drink"}, "cases": [
+                {"value": "coffee", "then": [{"actor": "robot", "op": "Pour", "target": "coffee"}]},
+                {"value": "tea", "then": [{"actor": "robot", "op": "Steep", "target": "tea"}]}
+            ]}, "else": [{"actor": "robot", "op": "Serve", "target": "water"}]}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+
+        assert!(robot.state.log.iter().any(|l| l.contains("Steep")), "{:?}", robot.state.log);
+        assert!(!robot.state.log.iter().any(|l| l.contains("Pour") || l.contains("Serve")), "{:?}", robot.state.log);
+    }
+
+    #[test]
+    fn test_try_recovers_from_throw() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Try", "target": "pour", "body": [
+                {"actor": "robot", "op": "Throw", "target": "spilled"},
+                {"actor": "robot", "op": "Stir", "target": "tea"}
+            ], "catch": [
+                {"actor": "robot", "op": "Remove", "target": "cup"}
+            ]}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+
+        assert_eq!(robot.state.variables["error"], serde_json::json!("spilled"));
+        assert!(!robot.state.log.iter().any(|l| l.contains("Stir")), "{:?}", robot.state.log);
+        assert!(robot.state.log.iter().any(|l| l.contains("Remove")), "{:?}", robot.state.log);
+    }
+
+    #[test]
+    fn test_bound_host_function() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Call", "target": "read_scale", "params": {"unit": "g", "out": "weight"}},
+            {"actor": "robot", "op": "Call", "target": "unbound"}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.bind_fn("read_scale", |args| Ok(serde_json::json!(format!("250{}", args["unit"].as_str().unwrap()))));
+        robot.execute(&program).unwrap();
+
+        assert_eq!(robot.state.variables["weight"], serde_json::json!("250g"));
+        assert_eq!(robot.state.errors, vec!["Unsupported operation: Call"]);
+    }
+
+    #[test]
+    fn test_call_statement_runs_defined_function() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "DefineFunction", "target": "brew", "params": {"args": ["drink", "minutes"], "body": [
+                {"actor": "robot", "op": "Steep", "target": "pot", "params": {"duration": {"var": "minutes"}}},
+                {"actor": "robot", "op": "Return", "target": "r", "params": {"value": {"var": "drink"}}}]}},
+            {"actor": "robot", "op": "Bind", "target": "drink", "params": {"value": "coffee"}},
+            {"actor": "robot", "op": "Call", "target": "brew", "params": {"drink": "tea", "minutes": 3, "out": "made"}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.variables["made"], serde_json::json!("tea"));
+        assert_eq!(robot.state.variables["drink"], serde_json::json!("coffee"));
+        assert!(robot.state.errors.is_empty(), "{:?}", robot.state.errors);
+    }
+
+    #[test]
+    fn test_function_calls_get_their_own_frames() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "DefineFunction", "target": "stir", "params": {"args": ["turns"], "body": [
+                {"actor": "robot", "op": "Bind", "target": "done", "params": {"value": 0}},
+                {"actor": "robot", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": {"var": "turns"}, "body": [
+                    {"actor": "robot", "op": "Bind", "target": "done", "params": {"value": {"var": "i"}}}
+                ]},
+                {"actor": "robot", "op": "Append", "target": "log", "params": {"value": {"var": "done"}}},
+                {"actor": "robot", "op": "Return", "target": "r", "params": {"value": {"var": "done"}}}]}},
+            {"actor": "robot", "op": "Bind", "target": "i", "params": {"value": 100}},
+            {"actor": "robot", "op": "Bind", "target": "log", "params": {"value": []}},
+            {"actor": "robot", "op": "Bind", "target": "stirred", "params": {"value": {"call": "stir", "args": {"turns": 8}}}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.variables["stirred"], serde_json::json!(8));
+        // The loop variable, arguments and locals stayed in their frame
+        assert_eq!(robot.state.variables["i"], serde_json::json!(100));
+        for local in ["turns", "done"] {
+            assert!(!robot.state.variables.contains_key(local), "{} leaked", local);
+        }
+        // Appending to a variable from inside a call still changes it
+        assert_eq!(robot.state.variables["log"], serde_json::json!([8]));
+    }
+
+    #[test]
+    fn test_return_and_halt_from_nested_blocks() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "DefineFunction", "target": "first_hot", "params": {"args": [], "body": [
+                {"actor": "robot", "op": "ForEach", "target": "cups", "variable": "cup", "params": {"in": ["a", "b", "c"]}, "body": [
+                    {"actor": "robot", "op": "Wait", "target": "cup", "dur": 1},
+                    {"actor": "robot", "op": "If", "target": "hot", "condition": {"type": "comparison", "op": "==", "left": {"var": "cup"}, "right": "b"},
+                     "then": [{"actor": "robot", "op": "Return", "target": "r", "params": {"value": {"var": "cup"}}}]}]}]}},
+            {"actor": "robot", "op": "Call", "target": "first_hot", "params": {"out": "hot"}},
+            {"actor": "robot", "op": "Try", "target": "attempt", "body": [{"actor": "robot", "op": "Halt", "target": "stop"}],
+             "catch": [{"actor": "robot", "op": "Bind", "target": "caught", "params": {"value": true}}]},
+            {"actor": "robot", "op": "Bind", "target": "after", "params": {"value": true}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.variables["hot"], serde_json::json!("b"));
+        assert_eq!(robot.state.halted, Some(0));
+        // A Halt isn't an error for Try to catch
+        assert!(!robot.state.variables.contains_key("caught"));
+        assert!(!robot.state.variables.contains_key("after"));
+    }
+
+    #[test]
+    fn test_deep_recursion_only_hits_the_configured_limit() {
+        // countdown(n) calls itself until n reaches 0
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "DefineFunction", "target": "countdown", "params": {"args": ["n"], "body": [
+                {"actor": "robot", "op": "If", "target": "more", "condition": {"type": "comparison", "op": ">", "left": {"var": "n"}, "right": 0},
+                 "then": [{"actor": "robot", "op": "Call", "target": "countdown", "params": {"n": {"expr": {"op": "-", "left": {"var": "n"}, "right": 1}}}}]}]}},
+            {"actor": "robot", "op": "Call", "target": "countdown", "params": {"n": 1500}}
+        ]}"#).unwrap();
+
+        RobotSimulator::new().execute(&program).unwrap();
+        let err = RobotSimulator::new().with_max_recursion_depth(1000).execute(&program).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(UclError::RecursionLimit { depth: 1000 })));
+    }
+
+    #[test]
+    fn test_provenance_of_variables_and_log() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "kitchen", "params": {"items": ["kettle"]}},
+            {"actor": "robot", "op": "For", "target": "loop", "variable": "i", "from": 1, "to": 2,
+             "body": [{"actor": "robot", "op": "Bind", "target": "last", "params": {"value": {"var": "i"}}}]}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        let provenance = &robot.state.provenance;
+
+        assert_eq!(provenance.output(0).unwrap().action, "0");
+        assert_eq!(provenance.value("last").unwrap().action, "1.body[0]");
+        assert_eq!(provenance.value("i").unwrap().op, "For(loop)");
+    }
+
+    #[test]
+    fn test_arm_moves_over_time_within_reach() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "kitchen",
+             "params": {"positions": {"cup": [30, 40, 0], "kettle": [30, 0, 0]}}},
+            {"actor": "robot", "op": "Grasp", "target": "kettle"},
+            {"actor": "robot", "op": "Pour", "target": "water", "params": {"from": "kettle", "into": "cup", "amount": "200ml"}},
+            {"actor": "robot", "op": "Wait", "target": "steep", "dur": 2}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new().with_arm(ArmLimits { max_speed: 10.0, reach: 60.0 });
+        robot.execute(&program).unwrap();
+        // 3s to the kettle, 4s on to the cup, then the wait
+        assert_eq!(robot.state.arm_position, (30.0, 40.0, 0.0));
+        assert_eq!(robot.state.clock, 9.0);
+        assert!(robot.state.log.contains(&"Moved arm to kettle in 3.00s".to_string()));
+
+        let far = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "kitchen", "params": {"positions": {"jar": [0, 80, 0]}}},
+            {"actor": "robot", "op": "Remove", "target": "jar", "params": {"from": "shelf"}}
+        ]}"#).unwrap();
+        let err = RobotSimulator::new().with_arm(ArmLimits { max_speed: 10.0, reach: 60.0 }).execute(&far).unwrap_err();
+        match err.downcast_ref() {
+            Some(UclError::Unreachable { object, distance, reach }) => {
+                assert_eq!((object.as_str(), *distance, *reach), ("jar", 80.0, 60.0));
+            }
+            _ => panic!("expected Unreachable, got {}", err),
+        }
+    }
+
+    #[test]
+    fn test_pouring_moves_liquid_between_containers() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "kitchen", "params": {"items": ["jug", "cup", "bowl"], "capacities": {"cup": 250, "bowl": "2 cups"}}},
+            {"actor": "robot", "op": "Measure", "target": "milk", "params": {"amount": "300ml", "into": "jug"}},
+            {"actor": "robot", "op": "Grasp", "target": "jug"},
+            {"actor": "robot", "op": "Pour", "target": "milk", "params": {"from": "jug", "into": "cup", "amount": "200ml"}},
+            {"actor": "robot", "op": "Pour", "target": "milk", "params": {"from": "jug", "into": "bowl", "amount": "0.25 l"}},
+            {"actor": "robot", "op": "Release", "target": "jug"},
+            {"actor": "robot", "op": "Grasp", "target": "tap"},
+            {"actor": "robot", "op": "Pour", "target": "water", "params": {"from": "tap", "into": "cup", "amount": "100ml"}},
+            {"actor": "robot", "op": "Pour", "target": "water", "params": {"from": "tap", "into": "bowl", "amount": "100ml"}},
+            {"actor": "robot", "op": "Mix", "target": "bowl", "params": {"into": "watery_milk"}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        let objects = &robot.state.objects;
+        assert!(objects["jug"].contents.is_empty());
+        assert_eq!(objects["cup"].volume, 250.0);
+        assert_eq!(robot.state.errors, vec![
+            "Only 100ml of milk in jug to pour, not 250ml".to_string(),
+            "cup overflowed, spilling 50ml".to_string(),
+        ]);
+        assert!(robot.state.log.contains(&"Poured milk from jug into bowl (100ml of requested 0.25 l)".to_string()), "{:?}", robot.state.log);
+
+        // The milk and water in the bowl became one new thing
+        assert_eq!(objects["bowl"].contents, Contents::from([("watery_milk".to_string(), 200.0)]));
+        let mixture = &objects["watery_milk"];
+        assert_eq!((mixture.volume, mixture.container.as_deref()), (200.0, Some("bowl")));
+        assert_eq!(mixture.contents.keys().collect::<Vec<_>>(), vec!["milk", "water"]);
+        assert!(!objects.contains_key("milk"));
+    }
+
+    #[test]
+    fn test_gripper_holds_one_thing_at_a_time() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "kitchen",
+             "params": {"positions": {"kettle": [20, 0, 0], "cup": [0, 20, 0], "spoon": [10, 10, 0]}}},
+            {"actor": "robot", "op": "Pour", "target": "water", "params": {"from": "kettle", "into": "cup", "amount": "200ml"}},
+            {"actor": "robot", "op": "Grasp", "target": "kettle"},
+            {"actor": "robot", "op": "Grasp", "target": "spoon"},
+            {"actor": "robot", "op": "Place", "target": "spoon", "params": {"into": "cup"}},
+            {"actor": "robot", "op": "Pour", "target": "water", "params": {"from": "kettle", "into": "cup", "amount": "200ml"}},
+            {"actor": "robot", "op": "Release", "target": "kettle"},
+            {"actor": "robot", "op": "Release", "target": "kettle"},
+            {"actor": "robot", "op": "Remove", "target": "spoon", "params": {"from": "drawer"}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new();
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.violations, vec![
+            Violation::NotHolding { action: "pour from".into(), object: "kettle".into() },
+            Violation::GripperFull { holding: "kettle".into(), wanted: "spoon".into() },
+            Violation::GripperFull { holding: "kettle".into(), wanted: "spoon".into() },
+            Violation::NotHolding { action: "release".into(), object: "kettle".into() },
+        ]);
+        assert_eq!(robot.state.log.iter().filter(|line| line.starts_with("Poured")).count(), 1);
+
+        // The kettle went where the arm took it, and stayed there when let go
+        assert_eq!(robot.state.objects["kettle"].position, (0.0, 20.0, 0.0));
+        assert_eq!(robot.state.gripper.as_deref(), Some("spoon"));
+    }
+
+    #[test]
+    fn test_workspace_refuses_collisions_and_leaving_the_table() {
+        let workspace = Workspace::new(Bounds { min: (-50.0, -50.0, 0.0), max: (50.0, 50.0, 40.0) })
+            .with_zone("tray", Bounds { min: (-40.0, 10.0, 0.0), max: (-10.0, 40.0, 6.0) });
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "robot", "op": "Gather", "target": "kitchen", "params": {
+                "positions": {"kettle": [20, 0, 5], "teapot": [40, 0, 5], "cup": "tray", "bowl": [-30, -30, 5], "plate": [-17, 25, 3], "jar": [0, 60, 5]},
+                "sizes": {"kettle": [10, 10, 10], "teapot": [10, 10, 10], "cup": [6, 6, 8], "bowl": [12, 12, 6], "plate": [6, 6, 6]}}},
+            {"actor": "robot", "op": "Grasp", "target": "teapot"},
+            {"actor": "robot", "op": "Remove", "target": "jar", "params": {"from": "shelf"}},
+            {"actor": "robot", "op": "Place", "target": "cup", "params": {"into": "bowl"}},
+            {"actor": "robot", "op": "Place", "target": "bowl", "params": {"into": "tray"}}
+        ]}"#).unwrap();
+
+        let mut robot = RobotSimulator::new().with_workspace(workspace);
+        robot.execute(&program).unwrap();
+        assert_eq!(robot.state.violations, vec![
+            Violation::Collision { target: "teapot".into(), obstacle: "kettle".into() },
+            Violation::OffTable { target: "jar".into(), point: (0.0, 60.0, 5.0) },
+            Violation::BadPlacement { object: "bowl".into(), into: "tray".into(), obstacle: Some("plate".into()) },
+        ]);
+        assert_eq!(robot.state.errors.len(), 3);
+        assert_eq!(robot.state.gripper, None);
+
+        // The cup went into the bowl, but the bowl never moved to the tray
+        assert_eq!(robot.state.objects["cup"].container.as_deref(), Some("bowl"));
+        assert_eq!(robot.state.objects["bowl"].position, (-30.0, -30.0, 5.0));
+    }
+}
@@ -0,0 +1,99 @@
+//! Sleep in the brain simulator
+//!
+//! `Sleep` consolidates working memory into the [`LONG_TERM`] belief and
+//! lets emotions settle; `Dream` recombines stored facts into dream
+//! thoughts. The rules are plain functions of the state so they can be
+//! checked on their own.
+
+use crate::rng::SeededRng;
+use crate::simulator::{BrainState, values};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Belief holding every consolidated working memory item, as a list
+pub const LONG_TERM: &str = "long_term_memory";
+
+/// Share of each emotion's intensity left after a sleep
+pub const EMOTION_RETAINED: f64 = 0.5;
+
+/// Intensity below which a settled emotion is gone
+pub const EMOTION_FLOOR: f64 = 0.05;
+
+/// Move working memory into [`LONG_TERM`], storing each item once, and
+/// give the items that weren't stored already
+pub fn consolidate(state: &mut BrainState) -> Vec<String> {
+    let mut stored = match state.beliefs.remove(LONG_TERM) {
+        Some(Value::Array(items)) => items,
+        Some(other) => vec![other],
+        None => Vec::new(),
+    };
+    let mut added = Vec::new();
+    for item in state.working_memory.drain(..) {
+        let value = Value::String(item.clone());
+        if !stored.contains(&value) {
+            stored.push(value);
+            added.push(item);
+        }
+    }
+    state.memory.working.clear();
+    if !stored.is_empty() {
+        state.beliefs.insert(LONG_TERM.to_string(), Value::Array(stored));
+    }
+    added
+}
+
+/// Scale every emotion by [`EMOTION_RETAINED`], dropping those that fall
+/// below [`EMOTION_FLOOR`]
+pub fn settle(emotions: &mut HashMap<String, f64>) {
+    for intensity in emotions.values_mut() {
+        *intensity *= EMOTION_RETAINED;
+    }
+    emotions.retain(|_, intensity| intensity.abs() >= EMOTION_FLOOR);
+}
+
+/// A dream pairing one fact's name with another fact's value, or `None`
+/// with fewer than two facts to recombine
+///
+/// Facts are beliefs with a single value; lists such as [`LONG_TERM`]
+/// aren't facts.
+pub fn dream(beliefs: &HashMap<String, Value>, rng: &mut SeededRng) -> Option<String> {
+    let mut facts: Vec<(&String, &Value)> = beliefs.iter().filter(|(_, value)| !value.is_array() && !value.is_object()).collect();
+    if facts.len() < 2 {
+        return None;
+    }
+    facts.sort_by(|a, b| a.0.cmp(b.0));
+
+    let last = facts.len() as i64 - 1;
+    let subject = rng.gen_range_i64(0, last) as usize;
+    // Any fact but the subject's own
+    let other = (subject + 1 + rng.gen_range_i64(0, last - 1) as usize) % facts.len();
+    Some(format!("Dreamt that {} was {}", facts[subject].0, values::text(facts[other].1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sleep_consolidates_settles_and_recombines() {
+        let mut state = BrainState::new();
+        state.beliefs.insert(LONG_TERM.to_string(), json!(["Heard: a"]));
+        state.working_memory = vec!["Heard: a".to_string(), "Heard: b".to_string()];
+        assert_eq!(consolidate(&mut state), vec!["Heard: b"]);
+        assert!(state.working_memory.is_empty());
+        assert_eq!(state.beliefs[LONG_TERM], json!(["Heard: a", "Heard: b"]));
+
+        let mut emotions = HashMap::from([("fear".to_string(), 1.0), ("joy".to_string(), 0.08)]);
+        settle(&mut emotions);
+        assert_eq!(emotions, HashMap::from([("fear".to_string(), 0.5)]));
+
+        let mut rng = SeededRng::new(3);
+        assert_eq!(dream(&state.beliefs, &mut rng), None);
+        state.beliefs.insert("cat".to_string(), json!("black"));
+        state.beliefs.insert("sky".to_string(), json!("blue"));
+        let dreams: Vec<String> = (0..10).filter_map(|_| dream(&state.beliefs, &mut rng)).collect();
+        assert_eq!(dreams.len(), 10);
+        assert!(dreams.iter().all(|d| d == "Dreamt that cat was blue" || d == "Dreamt that sky was black"), "{:?}", dreams);
+    }
+}
@@ -0,0 +1,104 @@
+//! The [`Simulator`] trait every simulated substrate implements, and
+//! [`by_name`] to pick one from the command line
+
+use crate::simulator::{BrainSimulator, MockAISimulator, RobotSimulator};
+use crate::{Action, Program};
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+/// What every simulated substrate can do, so callers can drive one without
+/// knowing which it is
+///
+/// States are snapshotted as JSON, which keeps the trait object-safe; each
+/// simulator's own `state()` still gives the typed state.
+pub trait Simulator: Send {
+    /// `brain`, `robot` or `ai`
+    fn name(&self) -> &'static str;
+
+    /// Run every action of `program` against the current state
+    fn execute(&mut self, program: &Program) -> Result<()>;
+
+    /// Run one action against the current state, as a one-action program
+    fn execute_action(&mut self, action: &Action) -> Result<()> {
+        self.execute(&Program { metadata: None, actions: vec![action.clone()] })
+    }
+
+    /// The whole state as JSON
+    fn snapshot(&self) -> Result<Value>;
+
+    /// Replace the state with an earlier [`snapshot`](Self::snapshot)
+    fn restore(&mut self, snapshot: Value) -> Result<()>;
+
+    /// The value held under `name`: a brain's belief, a robot's variable,
+    /// or the actions the AI generated for that target
+    fn value(&self, name: &str) -> Option<Value>;
+
+    /// What the simulator has said so far, oldest first: a brain's output,
+    /// a robot's log or the AI's responses
+    fn output(&self) -> &[String];
+
+    /// Problems recorded without failing the action, oldest first
+    fn errors(&self) -> &[String] {
+        &[]
+    }
+
+    /// Forget everything executed so far, keeping configuration such as
+    /// verbosity, namespaces and backends
+    fn reset(&mut self);
+
+    /// Keep only the newest `limit` entries of each record that grows with
+    /// every action (output, logs, traces, provenance), so a long run holds
+    /// a bounded amount of history
+    fn trim_history(&mut self, limit: usize);
+
+    /// The state, formatted for the terminal
+    fn display(&self) -> String;
+}
+
+/// Drop all but the last `limit` items, returning how many were dropped
+pub(crate) fn keep_last<T>(items: &mut Vec<T>, limit: usize) -> usize {
+    let excess = items.len().saturating_sub(limit);
+    items.drain(..excess);
+    excess
+}
+
+/// Names [`by_name`] accepts
+pub const SIMULATORS: &[&str] = &["brain", "robot", "ai"];
+
+/// A fresh simulator for `name`
+pub fn by_name(name: &str) -> Result<Box<dyn Simulator>> {
+    Ok(match name {
+        "brain" => Box::new(BrainSimulator::new()),
+        "robot" => Box::new(RobotSimulator::new()),
+        "ai" => Box::new(MockAISimulator::new()),
+        _ => bail!("Unsupported target: {}. Use one of: {}", name, SIMULATORS.join(", ")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_simulator_snapshots_and_resets() {
+        let emit = Program::parse_action(r#"{"actor": "you", "op": "Emit", "target": "greeting", "params": {"content": "hi"}}"#).unwrap();
+        let generate = Program::parse_action(r#"{"actor": "user", "op": "Generate", "target": "code", "params": {"instruction": "factorial"}}"#).unwrap();
+        for name in SIMULATORS {
+            let mut simulator = by_name(name).unwrap();
+            assert_eq!(simulator.name(), *name);
+
+            let fresh = simulator.snapshot().unwrap();
+            simulator.execute_action(if *name == "ai" { &generate } else { &emit }).unwrap();
+            let after = simulator.snapshot().unwrap();
+            assert_ne!(after, fresh, "{} state didn't change", name);
+            assert_eq!(simulator.output().len(), 1, "{} said nothing", name);
+            assert_eq!(simulator.value(if *name == "ai" { "code" } else { "nothing" }).is_some(), *name == "ai");
+
+            simulator.reset();
+            assert_eq!(simulator.snapshot().unwrap(), fresh, "{} didn't reset", name);
+            simulator.restore(after.clone()).unwrap();
+            assert_eq!(simulator.snapshot().unwrap(), after, "{} didn't restore", name);
+        }
+        assert!(by_name("toaster").is_err());
+    }
+}
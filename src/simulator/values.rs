@@ -0,0 +1,195 @@
+//! What list and string expressions do to the JSON values the simulators
+//! hold
+
+use crate::simulator::control::same_value;
+use crate::error::UclError;
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// `list[at]`, counting negative positions from the end, or `object[at]`
+pub(crate) fn index(value: &Value, at: &Value) -> Result<Value> {
+    let found = match value {
+        Value::Array(items) => {
+            let position = at.as_f64()
+                .filter(|p| p.fract() == 0.0)
+                .ok_or_else(|| UclError::invalid(format!("List index must be an integer, got {}", at)))?;
+            let resolved = if position < 0.0 { items.len() as f64 + position } else { position };
+            items.get(resolved as usize)
+                .filter(|_| resolved >= 0.0)
+                .cloned()
+                .ok_or_else(|| UclError::invalid(format!("Index {} is out of range for a list of {}", position, items.len())))
+        }
+        Value::Object(map) => {
+            let key = at.as_str().ok_or_else(|| UclError::invalid(format!("Object key must be a string, got {}", at)))?;
+            map.get(key).cloned().ok_or_else(|| UclError::invalid(format!("No key '{}' in {}", key, value)))
+        }
+        other => Err(UclError::invalid(format!("Can't index into {}", other))),
+    };
+    Ok(found?)
+}
+
+/// Items in a list or object, characters in a string
+pub(crate) fn length(value: &Value) -> Result<Value> {
+    let length = match value {
+        Value::Array(items) => items.len(),
+        Value::Object(map) => map.len(),
+        Value::String(s) => s.chars().count(),
+        other => return Err(UclError::invalid(format!("{} has no length", other)).into()),
+    };
+    Ok(Value::from(length))
+}
+
+/// `value` with `item` on the end; no value yet starts a new list
+pub(crate) fn append(value: Option<Value>, item: Value) -> Result<Value> {
+    match value {
+        Some(Value::Array(mut items)) => {
+            items.push(item);
+            Ok(Value::Array(items))
+        }
+        None | Some(Value::Null) => Ok(Value::Array(vec![item])),
+        Some(other) => Err(UclError::invalid(format!("Can't append to {}, it isn't a list", other)).into()),
+    }
+}
+
+/// The items a `ForEach` visits: a list's items or an object's keys
+pub(crate) fn items(value: Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Array(items) => Ok(items),
+        Value::Object(map) => Ok(map.into_iter().map(|(key, _)| Value::String(key)).collect()),
+        other => Err(UclError::invalid(format!("ForEach needs a list or an object, got {}", other)).into()),
+    }
+}
+
+/// A simulator map's keys in order, for a `ForEach` over a state collection
+pub(crate) fn keys<V>(map: &HashMap<String, V>) -> Vec<Value> {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    keys.into_iter().map(|key| Value::String(key.clone())).collect()
+}
+
+/// Whether a value counts as true: anything but `false` and `null`, as in Ruby
+pub(crate) fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+/// `-value` or `!value`
+pub(crate) fn unary(op: &str, value: &Value) -> Result<Value> {
+    match op {
+        "-" => {
+            let number = value.as_f64().ok_or_else(|| UclError::invalid(format!("Can't negate {}", value)))?;
+            Ok(match value.as_i64() {
+                Some(integer) if value.is_i64() => Value::from(-integer),
+                _ => serde_json::json!(-number),
+            })
+        }
+        "!" => Ok(Value::Bool(!truthy(value))),
+        other => Err(UclError::unsupported(format!("unary operator '{}'", other), "simulator").into()),
+    }
+}
+
+/// How a value reads in a message: strings without their quotes
+pub(crate) fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Whether a list has `part` as an item, or anything else contains it as text
+pub(crate) fn contains(value: &Value, part: &Value) -> bool {
+    match value {
+        Value::Array(items) => items.iter().any(|item| same_value(item, part)),
+        value => text(value).contains(&text(part)),
+    }
+}
+
+/// Whether a value, as text, matches a regular expression
+pub(crate) fn matches(value: &Value, pattern: &str) -> Result<bool> {
+    let regex = regex::Regex::new(pattern).map_err(|e| UclError::parse(format!("Bad pattern /{}/: {}", pattern, e)))?;
+    Ok(regex.is_match(&text(value)))
+}
+
+/// A string function applied to its evaluated arguments
+pub(crate) fn string_op(op: &str, args: &[Value]) -> Result<Value> {
+    let arity = |n: usize| -> Result<()> {
+        if args.len() != n {
+            return Err(UclError::parse(format!("{} takes {} argument{}, got {}", op, n, if n == 1 { "" } else { "s" }, args.len())).into());
+        }
+        Ok(())
+    };
+    Ok(match op {
+        "concat" => Value::String(args.iter().map(text).collect()),
+        "upper" => {
+            arity(1)?;
+            Value::String(text(&args[0]).to_uppercase())
+        }
+        "lower" => {
+            arity(1)?;
+            Value::String(text(&args[0]).to_lowercase())
+        }
+        "contains" => {
+            arity(2)?;
+            Value::Bool(contains(&args[0], &args[1]))
+        }
+        other => return Err(UclError::unsupported(format!("string function '{}'", other), "simulator").into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_index_length_and_append() {
+        let list = json!([10, 20, 30]);
+        assert_eq!(index(&list, &json!(0)).unwrap(), json!(10));
+        assert_eq!(index(&list, &json!(-1)).unwrap(), json!(30));
+        assert_eq!(index(&list, &json!(2.0)).unwrap(), json!(30));
+        assert!(index(&list, &json!(3)).is_err());
+        assert!(index(&list, &json!(-4)).is_err());
+        assert!(index(&list, &json!(0.5)).is_err());
+        assert_eq!(index(&json!({"a": 1}), &json!("a")).unwrap(), json!(1));
+
+        assert_eq!(length(&list).unwrap(), json!(3));
+        assert_eq!(length(&json!("héllo")).unwrap(), json!(5));
+        assert!(length(&json!(5)).is_err());
+
+        assert_eq!(append(None, json!(1)).unwrap(), json!([1]));
+        assert_eq!(append(Some(list), json!(40)).unwrap(), json!([10, 20, 30, 40]));
+        assert!(append(Some(json!("text")), json!(1)).is_err());
+    }
+
+    #[test]
+    fn test_unary_and_truthiness() {
+        assert_eq!(unary("-", &json!(3)).unwrap(), json!(-3));
+        assert_eq!(unary("-", &json!(2.5)).unwrap(), json!(-2.5));
+        assert!(unary("-", &json!("x")).is_err());
+        assert_eq!(unary("!", &json!(0)).unwrap(), json!(false));
+        assert_eq!(unary("!", &json!(null)).unwrap(), json!(true));
+        assert!(!truthy(&json!(false)));
+        assert!(truthy(&json!("")));
+    }
+
+    #[test]
+    fn test_text_conditions() {
+        assert!(matches(&json!("order #42"), r"#\d+$").unwrap());
+        assert!(!matches(&json!("order"), r"\d").unwrap());
+        assert!(matches(&json!(42), "^4").unwrap());
+        assert!(matches(&json!("x"), "(").is_err());
+        assert!(contains(&json!([1, 2.0]), &json!(2)));
+        assert!(contains(&json!("teapot"), &json!("pot")));
+    }
+
+    #[test]
+    fn test_string_ops() {
+        assert_eq!(string_op("concat", &[json!("x = "), json!(3), json!(true)]).unwrap(), json!("x = 3true"));
+        assert_eq!(string_op("upper", &[json!("Tea")]).unwrap(), json!("TEA"));
+        assert_eq!(string_op("lower", &[json!("Tea")]).unwrap(), json!("tea"));
+        assert_eq!(string_op("contains", &[json!("teapot"), json!("pot")]).unwrap(), json!(true));
+        assert_eq!(string_op("contains", &[json!([1, 2]), json!(3)]).unwrap(), json!(false));
+        assert_eq!(string_op("upper", &[]).unwrap_err().to_string(), "upper takes 1 argument, got 0");
+        assert!(string_op("reverse", &[json!("x")]).is_err());
+    }
+}
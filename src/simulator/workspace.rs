@@ -0,0 +1,178 @@
+//! Where the robot may move and what's in its way
+//!
+//! A [`Workspace`] is the table the robot works over plus named zones on
+//! it, like "counter" or "sink". With one set, the robot refuses to move
+//! its arm off the table or through an object's bounding box, and to set an
+//! object down where it would stick out of the table or overlap another,
+//! and records a [`Violation`] instead. It records one too when the gripper
+//! can't do what's asked, workspace or not.
+
+use crate::simulator::kinematics::Point;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// An axis-aligned box, from its lowest corner to its highest
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Bounds {
+    /// A box of `size` centred on `center`
+    pub fn around(center: Point, size: Point) -> Self {
+        let (x, y, z) = (size.0 / 2.0, size.1 / 2.0, size.2 / 2.0);
+        Self {
+            min: (center.0 - x, center.1 - y, center.2 - z),
+            max: (center.0 + x, center.1 + y, center.2 + z),
+        }
+    }
+
+    pub fn center(&self) -> Point {
+        ((self.min.0 + self.max.0) / 2.0, (self.min.1 + self.max.1) / 2.0, (self.min.2 + self.max.2) / 2.0)
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        (self.min.0..=self.max.0).contains(&point.0)
+            && (self.min.1..=self.max.1).contains(&point.1)
+            && (self.min.2..=self.max.2).contains(&point.2)
+    }
+
+    /// Whether all of `other` is inside this box
+    pub fn encloses(&self, other: &Bounds) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    /// Whether the boxes share any volume; touching faces don't count
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        self.min.0 < other.max.0 && other.min.0 < self.max.0
+            && self.min.1 < other.max.1 && other.min.1 < self.max.1
+            && self.min.2 < other.max.2 && other.min.2 < self.max.2
+    }
+
+    /// Whether the straight line from `from` to `to` passes through the box
+    pub fn crosses(&self, from: Point, to: Point) -> bool {
+        let axes = [
+            (from.0, to.0, self.min.0, self.max.0),
+            (from.1, to.1, self.min.1, self.max.1),
+            (from.2, to.2, self.min.2, self.max.2),
+        ];
+        // Narrow the part of the line, 0 at `from` to 1 at `to`, inside
+        // the box one axis at a time
+        let (mut enter, mut exit) = (0.0_f64, 1.0_f64);
+        for (start, end, low, high) in axes {
+            let step = end - start;
+            if step == 0.0 {
+                if start < low || start > high {
+                    return false;
+                }
+                continue;
+            }
+            let (a, b) = ((low - start) / step, (high - start) / step);
+            enter = enter.max(a.min(b));
+            exit = exit.min(a.max(b));
+            if enter > exit {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The table the robot works over and the named zones on it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workspace {
+    pub table: Bounds,
+    #[serde(default)]
+    pub zones: BTreeMap<String, Bounds>,
+}
+
+impl Workspace {
+    pub fn new(table: Bounds) -> Self {
+        Self { table, zones: BTreeMap::new() }
+    }
+
+    pub fn with_zone(mut self, name: &str, bounds: Bounds) -> Self {
+        self.zones.insert(name.to_string(), bounds);
+        self
+    }
+
+    /// Parse a workspace from JSON like
+    /// `{"table": {"min": [-50, -50, 0], "max": [50, 50, 40]}, "zones": {...}}`
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn zone(&self, name: &str) -> Option<&Bounds> {
+        self.zones.get(name)
+    }
+}
+
+/// A motion the robot refused to make
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Violation {
+    /// Moving the arm to `target` would leave the table
+    OffTable { target: String, point: Point },
+    /// Moving the arm to `target` would go through `obstacle`
+    Collision { target: String, obstacle: String },
+    /// Setting `object` down in `into` would overlap `obstacle`, or stick
+    /// out of the table when there's no obstacle
+    BadPlacement { object: String, into: String, obstacle: Option<String> },
+    /// Picking up `wanted` while the gripper already holds `holding`
+    GripperFull { holding: String, wanted: String },
+    /// An action that needs `object` in the gripper, when it isn't, such as
+    /// pouring from it
+    NotHolding { action: String, object: String },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::OffTable { target, point } => {
+                write!(f, "Refused to move to {}: ({:.2}, {:.2}, {:.2}) is off the table", target, point.0, point.1, point.2)
+            }
+            Violation::Collision { target, obstacle } => {
+                write!(f, "Refused to move to {}: the arm would hit {}", target, obstacle)
+            }
+            Violation::BadPlacement { object, into, obstacle: Some(obstacle) } => {
+                write!(f, "Refused to place {} into {}: it would hit {}", object, into, obstacle)
+            }
+            Violation::BadPlacement { object, into, obstacle: None } => {
+                write!(f, "Refused to place {} into {}: it wouldn't fit on the table", object, into)
+            }
+            Violation::GripperFull { holding, wanted } => {
+                write!(f, "Refused to pick up {}: already holding {}", wanted, holding)
+            }
+            Violation::NotHolding { action, object } => {
+                write!(f, "Refused to {} {}: not holding it", action, object)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box_geometry() {
+        let cup = Bounds::around((10.0, 0.0, 5.0), (4.0, 4.0, 10.0));
+        assert_eq!(cup.min, (8.0, -2.0, 0.0));
+        assert_eq!(cup.center(), (10.0, 0.0, 5.0));
+
+        assert!(cup.crosses((0.0, 0.0, 5.0), (20.0, 0.0, 5.0)));
+        assert!(!cup.crosses((0.0, 0.0, 5.0), (7.0, 0.0, 5.0)));
+        assert!(!cup.crosses((0.0, 0.0, 20.0), (20.0, 0.0, 20.0)));
+
+        let saucer = Bounds::around((12.0, 0.0, 1.0), (6.0, 6.0, 2.0));
+        assert!(cup.overlaps(&saucer));
+        assert!(!cup.overlaps(&Bounds::around((14.0, 0.0, 5.0), (4.0, 4.0, 10.0))));
+
+        let workspace = Workspace::from_json(r#"{"table": {"min": [-50, -50, 0], "max": [50, 50, 40]},
+            "zones": {"sink": {"min": [20, 20, 0], "max": [40, 40, 10]}}}"#).unwrap();
+        assert_eq!(workspace.zone("sink").unwrap().center(), (30.0, 30.0, 5.0));
+        assert!(workspace.table.encloses(&cup));
+    }
+}
@@ -0,0 +1,92 @@
+//! Extracting the part of a program one substrate or domain cares about
+
+use crate::analysis::infer_effects;
+use crate::{Action, Program};
+
+/// Which actions a slice keeps: each list given must match (any one of its
+/// entries will do), and an empty filter keeps everything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SliceFilter {
+    pub actors: Vec<String>,
+    /// Effect tags, matched case-insensitively against an action's declared
+    /// `effects`, or the inferred ones if it declares none
+    pub effects: Vec<String>,
+    pub targets: Vec<String>,
+}
+
+impl SliceFilter {
+    pub fn is_empty(&self) -> bool {
+        self.actors.is_empty() && self.effects.is_empty() && self.targets.is_empty()
+    }
+
+    pub fn matches(&self, action: &Action) -> bool {
+        let effects = match &action.effects {
+            Some(declared) if !declared.is_empty() => declared.clone(),
+            _ => infer_effects(action),
+        };
+        (self.actors.is_empty() || self.actors.contains(&action.actor))
+            && (self.targets.is_empty() || self.targets.contains(&action.target))
+            && (self.effects.is_empty() || self.effects.iter().any(|tag| effects.iter().any(|e| e.eq_ignore_ascii_case(tag))))
+    }
+}
+
+/// The actions `filter` matches, in order, keeping the `If`s, loops and
+/// functions around them: an action that doesn't match stays only if
+/// something nested in it does, with its nested lists sliced the same way
+pub fn slice(program: &Program, filter: &SliceFilter) -> Program {
+    Program {
+        metadata: program.metadata.clone(),
+        actions: slice_actions(&program.actions, filter),
+    }
+}
+
+fn slice_actions(actions: &[Action], filter: &SliceFilter) -> Vec<Action> {
+    actions.iter()
+        .filter_map(|action| {
+            let mut sliced = action.clone();
+            let mut kept_nested = false;
+            sliced.visit_children_mut(&mut |_, nested| {
+                *nested = slice_actions(nested, filter);
+                kept_nested |= !nested.is_empty();
+            });
+            if sliced.else_actions.as_ref().is_some_and(Vec::is_empty) {
+                sliced.else_actions = None;
+            }
+            (kept_nested || filter.matches(action)).then_some(sliced)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(actions: &[Action]) -> Vec<&str> {
+        actions.iter().map(|a| a.target.as_str()).collect()
+    }
+
+    #[test]
+    fn test_slice_keeps_structure() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "x", "params": {"value": 1}},
+            {"actor": "RobotVM", "op": "Heat", "target": "kettle"},
+            {"actor": "Coordinator", "op": "If", "target": "ready",
+             "condition": {"type": "comparison", "op": "==", "left": {"var": "x"}, "right": 1},
+             "then": [{"actor": "BrainVM", "op": "Emit", "target": "hello"}, {"actor": "RobotVM", "op": "Pour", "target": "cup"}],
+             "else": [{"actor": "RobotVM", "op": "Wait", "target": "kettle"}]},
+            {"actor": "Seller", "op": "Oblige", "target": "Seller", "effects": ["Legal"]}
+        ]}"#).unwrap();
+
+        let brain = slice(&program, &SliceFilter { actors: vec!["BrainVM".into()], ..Default::default() });
+        assert_eq!(targets(&brain.actions), vec!["x", "ready"]);
+        assert_eq!(targets(brain.actions[1].then_actions.as_ref().unwrap()), vec!["hello"]);
+        assert!(brain.actions[1].else_actions.is_none());
+
+        let physical = slice(&program, &SliceFilter { effects: vec!["physical".into()], ..Default::default() });
+        assert_eq!(targets(&physical.actions), vec!["kettle", "ready"]);
+        assert_eq!(targets(physical.actions[1].then_actions.as_ref().unwrap()), vec!["cup"]);
+
+        let legal = slice(&program, &SliceFilter { effects: vec!["Legal".into()], actors: vec!["Buyer".into()], ..Default::default() });
+        assert!(legal.actions.is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+//! Long-lived execution for `ucl exec --stream`: actions arrive one JSON
+//! line at a time and run against a simulator that keeps its state
+//!
+//! [`ActionStream`] reads NDJSON traces lazily, so `ucl exec --file` can run
+//! traces far larger than a whole `Program` would fit in memory.
+
+use crate::simulator::{self, Simulator};
+use crate::{Action, Program};
+use anyhow::Result;
+use serde_json::{Map, Value, json};
+use std::io::{BufRead, Write};
+
+/// A simulator, with its state as JSON
+pub(crate) struct Engine(Box<dyn Simulator>);
+
+impl Engine {
+    /// `brain`, `robot` or `ai`
+    pub(crate) fn new(target: &str) -> Result<Self> {
+        Ok(Engine(simulator::by_name(target)?))
+    }
+
+    /// The state without its provenance: it indexes into the whole run's
+    /// history, so it can't be diffed and replayed like the rest
+    pub(crate) fn state(&self) -> Result<Value> {
+        let mut state = self.0.snapshot()?;
+        if let Value::Object(fields) = &mut state {
+            fields.remove("provenance");
+        }
+        Ok(state)
+    }
+
+    pub(crate) fn restore(&mut self, state: Value) -> Result<()> {
+        self.0.restore(state)
+    }
+
+    pub(crate) fn execute(&mut self, program: &Program) -> Result<()> {
+        self.0.execute(program)
+    }
+
+    pub(crate) fn trim_history(&mut self, limit: usize) {
+        self.0.trim_history(limit)
+    }
+
+    pub(crate) fn display(&self) -> String {
+        self.0.display()
+    }
+}
+
+/// How one state field changed
+#[derive(Debug, Clone, PartialEq)]
+enum FieldChange {
+    /// A map with keys set and removed
+    Keys { set: Map<String, Value>, removed: Vec<String> },
+    /// A list that only grew
+    Append(Vec<Value>),
+    /// Anything else
+    Replace(Value),
+}
+
+/// The difference between two simulator states (JSON objects), which can
+/// be replayed onto another state
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatePatch {
+    fields: Vec<(String, FieldChange)>,
+}
+
+impl StatePatch {
+    pub fn between(before: &Value, after: &Value) -> Self {
+        let empty = Map::new();
+        let before = before.as_object().unwrap_or(&empty);
+        let after = after.as_object().unwrap_or(&empty);
+
+        let mut fields = Vec::new();
+        for (field, new) in after {
+            let old = before.get(field).unwrap_or(&Value::Null);
+            if old == new {
+                continue;
+            }
+
+            let change = match (old, new) {
+                (Value::Object(old), Value::Object(new)) => FieldChange::Keys {
+                    set: new.iter()
+                        .filter(|(key, value)| old.get(*key) != Some(*value))
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect(),
+                    removed: old.keys().filter(|k| !new.contains_key(*k)).cloned().collect(),
+                },
+                (Value::Array(old), Value::Array(new)) if new.starts_with(old) => {
+                    FieldChange::Append(new[old.len()..].to_vec())
+                }
+                _ => FieldChange::Replace(new.clone()),
+            };
+            fields.push((field.clone(), change));
+        }
+        Self { fields }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Make the same changes to `state`
+    pub fn apply(&self, state: &mut Value) {
+        let Some(object) = state.as_object_mut() else { return };
+        for (field, change) in &self.fields {
+            let slot = object.entry(field.clone()).or_insert(Value::Null);
+
+            match change {
+                FieldChange::Keys { set, removed } => {
+                    if !slot.is_object() {
+                        *slot = Value::Object(Map::new());
+                    }
+                    if let Value::Object(map) = slot {
+                        map.extend(set.clone());
+                        removed.iter().for_each(|key| { map.remove(key); });
+                    }
+                }
+                FieldChange::Append(items) => match slot {
+                    Value::Array(list) => list.extend(items.iter().cloned()),
+                    other => *other = Value::Array(items.clone()),
+                },
+                FieldChange::Replace(value) => *slot = value.clone(),
+            }
+        }
+    }
+
+    /// Changed fields as JSON, with removed keys as `null`
+    pub fn to_json(&self) -> Value {
+        let mut changes = Map::new();
+        for (field, change) in &self.fields {
+            let value = match change {
+                FieldChange::Keys { set, removed } => {
+                    let mut keys = set.clone();
+                    keys.extend(removed.iter().map(|k| (k.clone(), Value::Null)));
+                    Value::Object(keys)
+                }
+                FieldChange::Append(items) => Value::Array(items.clone()),
+                FieldChange::Replace(value) => value.clone(),
+            };
+            changes.insert(field.clone(), value);
+        }
+        Value::Object(changes)
+    }
+}
+
+/// Actions read lazily from NDJSON, one per line, skipping blank lines
+pub struct ActionStream<R> {
+    lines: std::io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> ActionStream<R> {
+    pub fn new(input: R) -> Self {
+        Self { lines: input.lines(), line: 0 }
+    }
+
+    /// The line number of the last action read
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl<R: BufRead> Iterator for ActionStream<R> {
+    type Item = Result<Action>;
+
+    fn next(&mut self) -> Option<Result<Action>> {
+        loop {
+            self.line += 1;
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let action = serde_json::from_str(&line)
+                .map_err(|e| anyhow::anyhow!("line {}: {}", self.line, e));
+            return Some(action);
+        }
+    }
+}
+
+/// A persistent simulator fed by NDJSON lines
+pub struct StreamExecutor {
+    engine: Engine,
+    steps: usize,
+    history_limit: Option<usize>,
+}
+
+impl StreamExecutor {
+    /// `brain`, `robot` or `ai`
+    pub fn new(target: &str) -> Result<Self> {
+        Ok(Self { engine: Engine::new(target)?, steps: 0, history_limit: None })
+    }
+
+    /// Keep only the newest `limit` output lines, trace entries and
+    /// provenance records while [`run_actions`](Self::run_actions) runs, so
+    /// a long trace doesn't keep all of its history in memory
+    pub fn with_history_limit(mut self, limit: usize) -> Self {
+        self.history_limit = Some(limit);
+        self
+    }
+
+    /// Run one line, either a single action or a whole program, and return
+    /// what changed in the simulator state
+    pub fn execute_line(&mut self, line: &str) -> Result<Value> {
+        let value: Value = serde_json::from_str(line)?;
+        let program = if value.get("actions").is_some() {
+            serde_json::from_value::<Program>(value)?
+        } else {
+            Program { metadata: None, actions: vec![serde_json::from_value::<Action>(value)?] }
+        };
+
+        let before = self.engine.state()?;
+        let result = self.engine.execute(&program);
+        let changes = delta(&before, &self.engine.state()?);
+        result.map(|_| changes)
+    }
+
+    /// Run every action of an NDJSON trace in order, holding one action in
+    /// memory at a time, and return how many ran
+    ///
+    /// Unlike [`run`](Self::run) this computes no deltas and stops at the
+    /// first error. Each action runs on its own, so actions sharing a `t`
+    /// run one after another rather than simultaneously.
+    pub fn run_actions<R: BufRead>(&mut self, input: R) -> Result<usize> {
+        let mut actions = ActionStream::new(input);
+        while let Some(action) = actions.next() {
+            let program = Program { metadata: None, actions: vec![action?] };
+            self.engine.execute(&program)
+                .map_err(|e| anyhow::anyhow!("line {}: {}", actions.line(), e))?;
+            if let Some(limit) = self.history_limit {
+                self.engine.trim_history(limit);
+            }
+            self.steps += 1;
+        }
+        Ok(self.steps)
+    }
+
+    /// The simulator state, formatted for the terminal
+    pub fn display(&self) -> String {
+        self.engine.display()
+    }
+
+    /// Read lines until end of input, writing one JSON result per non-blank
+    /// line: `{"step": n, "delta": {...}}` or `{"step": n, "error": "..."}`
+    ///
+    /// A bad line is reported and skipped; it doesn't end the stream.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> Result<usize> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            self.steps += 1;
+            let reply = match self.execute_line(&line) {
+                Ok(changes) => json!({"step": self.steps, "delta": changes}),
+                Err(e) => json!({"step": self.steps, "error": e.to_string()}),
+            };
+            writeln!(output, "{}", reply)?;
+            output.flush()?;
+        }
+        Ok(self.steps)
+    }
+}
+
+/// Fields of `after` that differ from `before`
+///
+/// Maps report changed keys (removed keys as `null`), lists that only grew
+/// report the new items, anything else reports its new value.
+pub fn delta(before: &Value, after: &Value) -> Value {
+    StatePatch::between(before, after).to_json()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_keeps_state_between_lines() {
+        let input = r#"{"actor": "you", "op": "Bind", "target": "x", "params": {"value": 2}}
+
+{"actor": "you", "op": "Write", "target": "y", "params": {"operation": "multiply", "lhs_register": "x", "rhs": 5}}
+not json
+{"actions": [{"actor": "you", "op": "Emit", "target": "y"}]}
+"#;
+        let mut output = Vec::new();
+        let steps = StreamExecutor::new("brain").unwrap().run(input.as_bytes(), &mut output).unwrap();
+        let replies: Vec<Value> = String::from_utf8(output).unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(steps, 4);
+        assert_eq!(replies[0]["delta"]["beliefs"], json!({"x": 2}));
+        assert_eq!(replies[1]["delta"]["beliefs"], json!({"y": 10.0}));
+        assert!(replies[2]["error"].is_string());
+        assert_eq!(replies[3]["delta"]["output"], json!(["10.0"]));
+    }
+
+    #[test]
+    fn test_run_actions_from_ndjson() {
+        let input = r#"{"actor": "you", "op": "Bind", "target": "x", "params": {"value": 2}}
+
+{"actor": "you", "op": "Write", "target": "y", "params": {"operation": "multiply", "lhs_register": "x", "rhs": 5}}
+{"actor": "you", "op": "Emit", "target": "y"}
+"#;
+        let mut executor = StreamExecutor::new("brain").unwrap();
+        assert_eq!(executor.run_actions(input.as_bytes()).unwrap(), 3);
+
+        let state = executor.engine.state().unwrap();
+        assert_eq!(state["beliefs"]["y"], json!(10.0));
+        assert_eq!(state["output"], json!(["10.0"]));
+    }
+
+    #[test]
+    fn test_run_actions_keeps_bounded_history() {
+        let input: String = (0..20)
+            .map(|i| format!("{{\"actor\": \"you\", \"op\": \"Emit\", \"target\": \"line\", \"params\": {{\"content\": \"{}\"}}}}\n", i))
+            .collect();
+        let mut executor = StreamExecutor::new("brain").unwrap().with_history_limit(3);
+        assert_eq!(executor.run_actions(input.as_bytes()).unwrap(), 20);
+
+        let state = executor.engine.0.snapshot().unwrap();
+        assert_eq!(state["output"], json!(["17", "18", "19"]));
+        assert!(state["trace"].as_array().unwrap().len() <= 3);
+        assert!(state["provenance"]["records"].as_array().unwrap().len() <= 3);
+        assert_eq!(state["provenance"]["output"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_action_stream_names_the_bad_line() {
+        let input = "{\"actor\": \"you\", \"op\": \"Emit\", \"target\": \"x\"}\n\nnot json\n";
+        let actions: Vec<Result<Action>> = ActionStream::new(input.as_bytes()).collect();
+
+        assert_eq!(actions.len(), 2);
+        assert!(actions[0].is_ok());
+        assert!(actions[1].as_ref().unwrap_err().to_string().starts_with("line 3:"));
+
+        let error = StreamExecutor::new("brain").unwrap().run_actions(input.as_bytes()).unwrap_err();
+        assert!(error.to_string().starts_with("line 3:"));
+    }
+
+    #[test]
+    fn test_delta() {
+        let before = json!({"beliefs": {"a": 1, "b": 2}, "log": ["x"], "arm": [0, 0], "same": 1});
+        let after = json!({"beliefs": {"a": 1, "c": 3}, "log": ["x", "y"], "arm": [1, 0], "same": 1});
+
+        assert_eq!(delta(&before, &after), json!({
+            "beliefs": {"b": null, "c": 3},
+            "log": ["y"],
+            "arm": [1, 0]
+        }));
+
+        let mut replayed = before.clone();
+        StatePatch::between(&before, &after).apply(&mut replayed);
+        assert_eq!(replayed, after);
+    }
+}
@@ -0,0 +1,90 @@
+//! `{name}` templates, as in `{"format": "The answer is {x}"}`
+//!
+//! A name in braces is replaced by that variable's value; `{{` and `}}` are
+//! literal braces.
+
+use anyhow::{Result, bail};
+
+/// A piece of a template
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    Text(String),
+    Var(String),
+}
+
+/// Split a template into literal text and the variables it names
+pub fn parse(template: &str) -> Result<Vec<Piece>> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                let name = name.trim();
+                if !closed || name.is_empty() || name.contains('{') {
+                    bail!("Bad placeholder in template {:?}", template);
+                }
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Var(name.to_string()));
+            }
+            '}' => bail!("Unmatched '}}' in template {:?}", template),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// The template with each placeholder replaced by what `lookup` gives
+pub fn render(template: &str, mut lookup: impl FnMut(&str) -> Result<String>) -> Result<String> {
+    let mut output = String::new();
+    for piece in parse(template)? {
+        match piece {
+            Piece::Text(text) => output.push_str(&text),
+            Piece::Var(name) => output.push_str(&lookup(&name)?),
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_render() {
+        assert_eq!(parse("The answer is {x}, {{really}}").unwrap(), vec![
+            Piece::Text("The answer is ".into()),
+            Piece::Var("x".into()),
+            Piece::Text(", {really}".into()),
+        ]);
+        assert!(parse("{}").is_err());
+        assert!(parse("{x").is_err());
+        assert!(parse("x}").is_err());
+
+        let rendered = render("{a} + {b}", |name| Ok(name.to_uppercase())).unwrap();
+        assert_eq!(rendered, "A + B");
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{Action, BinaryOpExpr, ComparisonOp, Condition, Expression, MatchCase, Operation, Program, StringOpExpr, UnaryOpExpr};
+use proptest::arbitrary::Arbitrary;
+use proptest::collection::{hash_map, vec};
+use proptest::option;
+use proptest::prelude::*;
+use serde_json::{Value, json};
+
+/// Every operation with no payload
+const UNIT_OPERATIONS: &[Operation] = &[
+    Operation::Create, Operation::Read, Operation::Write, Operation::Delete,
+    Operation::Bind, Operation::Unbind, Operation::Emit, Operation::Receive, Operation::Vote,
+    Operation::Measure, Operation::Scan, Operation::ReadSensor, Operation::Decide, Operation::Wait, Operation::Sleep, Operation::Dream, Operation::Imagine,
+    Operation::Assert, Operation::StoreFact, Operation::Oblige, Operation::Permit, Operation::Remedy,
+    Operation::Transcribe, Operation::Translate, Operation::Express,
+    Operation::Call, Operation::Assign, Operation::Return, Operation::Append, Operation::GenRandomInt,
+    Operation::Gather, Operation::Heat, Operation::Pour, Operation::Mix, Operation::Stir,
+    Operation::Place, Operation::Remove, Operation::Grasp, Operation::Release, Operation::Steep, Operation::Serve,
+    Operation::If, Operation::Match, Operation::While, Operation::For, Operation::ForEach, Operation::DefineFunction, Operation::OnInterrupt,
+    Operation::Break, Operation::Continue, Operation::Halt, Operation::Try, Operation::Throw, Operation::Parallel, Operation::When,
+    Operation::DefineMacro, Operation::Expand,
+    Operation::Generate, Operation::Parse, Operation::Execute,
+    Operation::Flurble, Operation::Grok, Operation::Defenestrate,
+];
+
+/// Short identifiers, so generated programs reuse the same names
+fn name() -> impl Strategy<Value = String> {
+    prop::sample::select(&["x", "y", "n", "result", "water", "cup", "memory", "VM"][..]).prop_map(String::from)
+}
+
+/// Scalar JSON values (objects could be mistaken for expressions)
+fn scalar() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        (-20i64..20).prop_map(|n| json!(n)),
+        (-100.0f64..100.0).prop_map(|f| json!(f)),
+        any::<bool>().prop_map(|b| json!(b)),
+        "[a-z ]{0,12}".prop_map(|s| json!(s)),
+    ]
+}
+
+/// Format templates, mostly with a placeholder but sometimes with stray braces
+fn template() -> impl Strategy<Value = String> {
+    prop_oneof![
+        ("[a-z ]{0,6}", name(), "[a-z ]{0,6}").prop_map(|(before, var, after)| format!("{}{{{}}}{}", before, var, after)),
+        "[a-z {}]{0,12}",
+    ]
+}
+
+pub fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        9 => prop::sample::select(UNIT_OPERATIONS),
+        1 => "[A-Z][a-z]{1,8}".prop_map(Operation::Custom),
+    ]
+}
+
+pub fn expression() -> impl Strategy<Value = Expression> {
+    let leaf = prop_oneof![
+        name().prop_map(|var| Expression::Variable { var }),
+        scalar().prop_map(Expression::Value),
+        template().prop_map(|format| Expression::Format { format }),
+    ];
+
+    leaf.prop_recursive(3, 12, 2, |inner| {
+        prop_oneof![
+            (prop::sample::select(&["+", "-", "*", "/"][..]), inner.clone(), inner.clone()).prop_map(|(op, left, right)| {
+                Expression::BinaryOp {
+                    expr: BinaryOpExpr { op: op.to_string(), left: Box::new(left), right: Box::new(right) },
+                }
+            }),
+            (name(), hash_map(name(), inner.clone(), 0..2)).prop_map(|(call, args)| Expression::FunctionCall { call, args }),
+            vec(inner.clone(), 0..3).prop_map(|list| Expression::List { list }),
+            (inner.clone(), inner.clone()).prop_map(|(index, at)| Expression::Index { index: Box::new(index), at: Box::new(at) }),
+            inner.clone().prop_map(|length| Expression::Length { length: Box::new(length) }),
+            (prop::sample::select(&["concat", "upper", "lower", "contains"][..]), vec(inner.clone(), 0..3)).prop_map(|(op, args)| {
+                Expression::StringOp { string: StringOpExpr { op: op.to_string(), args } }
+            }),
+            (prop::sample::select(&["-", "!"][..]), inner.clone()).prop_map(|(op, operand)| {
+                Expression::Unary { unary: UnaryOpExpr { op: op.to_string(), operand: Box::new(operand) } }
+            }),
+            // Only comparisons, as condition() is built from expressions
+            (comparison_op(), inner.clone(), inner).prop_map(|(op, left, right)| {
+                Expression::Test { test: Box::new(Condition::Comparison { op, left, right }) }
+            }),
+        ]
+    })
+}
+
+fn comparison_op() -> impl Strategy<Value = ComparisonOp> {
+    prop::sample::select(&[
+        ComparisonOp::Equal, ComparisonOp::NotEqual, ComparisonOp::LessThan,
+        ComparisonOp::LessThanOrEqual, ComparisonOp::GreaterThan, ComparisonOp::GreaterThanOrEqual,
+    ][..])
+}
+
+pub fn condition() -> impl Strategy<Value = Condition> {
+    let leaf = prop_oneof![
+        (comparison_op(), expression(), expression()).prop_map(|(op, left, right)| Condition::Comparison { op, left, right }),
+        expression().prop_map(|value| Condition::Expression { value }),
+        // Patterns may not compile
+        (expression(), "[a-z.*^$(\\[]{0,6}").prop_map(|(value, pattern)| Condition::Matches { value, pattern }),
+        (expression(), expression()).prop_map(|(value, prefix)| Condition::StartsWith { value, prefix }),
+        (expression(), expression()).prop_map(|(value, part)| Condition::Contains { value, part }),
+        name().prop_map(|var| Condition::Exists { var }),
+    ];
+
+    leaf.prop_recursive(2, 6, 2, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 1..3).prop_map(|operands| Condition::And { operands }),
+            vec(inner.clone(), 1..3).prop_map(|operands| Condition::Or { operands }),
+            inner.prop_map(|operand| Condition::Not { operand: Box::new(operand) }),
+        ]
+    })
+}
+
+/// A straight-line action: any operation, optional timing, params and effects
+fn simple_action() -> impl Strategy<Value = Action> {
+    (
+        name(),
+        operation(),
+        name(),
+        option::of(0.0f64..100.0),
+        option::of(0.0f64..10.0),
+        option::of(hash_map("[a-z]{1,8}", scalar(), 0..3)),
+        option::of(vec("[A-Z][a-z]{2,8}", 1..3)),
+    )
+        .prop_map(|(actor, op, target, t, dur, params, effects)| {
+            let mut action = Action::new(actor, op, target);
+            action.t = t;
+            action.dur = dur;
+            action.params = params;
+            action.effects = effects;
+            action
+        })
+}
+
+pub fn action() -> impl Strategy<Value = Action> {
+    simple_action().prop_recursive(3, 24, 3, |inner| {
+        let block = vec(inner, 0..3);
+        let case = (prop_oneof![
+            scalar().prop_map(|value| (Some(value), None)),
+            condition().prop_map(|when| (None, Some(when))),
+        ], block.clone())
+            .prop_map(|((value, when), then)| MatchCase { value, when, then });
+        prop_oneof![
+            (name(), condition(), block.clone(), option::of(block.clone())).prop_map(|(actor, condition, then, otherwise)| {
+                let mut action = Action::new(actor, Operation::If, "branch");
+                action.condition = Some(condition);
+                action.then_actions = Some(then);
+                action.else_actions = otherwise;
+                action
+            }),
+            (name(), condition(), block.clone()).prop_map(|(actor, condition, body)| {
+                let mut action = Action::new(actor, Operation::While, "loop");
+                action.condition = Some(condition);
+                action.body_actions = Some(body);
+                action
+            }),
+            (name(), name(), vec(expression(), 0..4), block.clone()).prop_map(|(actor, var, list, body)| {
+                let mut action = Action::new(actor, Operation::ForEach, "loop");
+                action.loop_var = Some(var);
+                action.params = Some([("in".to_string(), serde_json::to_value(Expression::List { list }).unwrap())].into());
+                action.body_actions = Some(body);
+                action
+            }),
+            (name(), name(), block.clone(), block.clone()).prop_map(|(actor, error, body, catch)| {
+                let mut action = Action::new(actor, Operation::Try, "attempt");
+                action.params = Some([("error".to_string(), json!(error))].into());
+                action.body_actions = Some(body);
+                action.catch_actions = Some(catch);
+                action
+            }),
+            (name(), expression(), vec(case, 0..3), option::of(block.clone())).prop_map(|(actor, value, cases, otherwise)| {
+                let mut action = Action::new(actor, Operation::Match, "choice");
+                action.params = Some([
+                    ("value".to_string(), serde_json::to_value(value).unwrap()),
+                    ("cases".to_string(), serde_json::to_value(cases).unwrap()),
+                ].into());
+                action.else_actions = otherwise;
+                action
+            }),
+            (name(), block.clone()).prop_map(|(actor, body)| {
+                let mut action = Action::new(actor, Operation::Parallel, "branches");
+                action.body_actions = Some(body);
+                action
+            }),
+            (name(), condition(), block.clone()).prop_map(|(actor, condition, body)| {
+                let mut action = Action::new(actor, Operation::When, "trigger");
+                action.condition = Some(condition);
+                action.body_actions = Some(body);
+                action
+            }),
+            (name(), name(), -3i64..3, -3i64..5, block).prop_map(|(actor, var, from, to, body)| {
+                let mut action = Action::new(actor, Operation::For, "loop");
+                action.loop_var = Some(var);
+                action.from_expr = Some(Expression::Value(json!(from)));
+                action.to_expr = Some(Expression::Value(json!(to)));
+                action.body_actions = Some(body);
+                action
+            }),
+        ]
+    })
+}
+
+pub fn program() -> impl Strategy<Value = Program> {
+    (
+        option::of(hash_map("[a-z]{1,8}", scalar(), 0..3)),
+        vec(action(), 0..8),
+    )
+        .prop_map(|(metadata, actions)| Program { metadata, actions })
+}
+
+macro_rules! impl_arbitrary {
+    ($($ty:ty => $strategy:ident),* $(,)?) => {
+        $(
+            impl Arbitrary for $ty {
+                type Parameters = ();
+                type Strategy = BoxedStrategy<Self>;
+
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    $strategy().boxed()
+                }
+            }
+        )*
+    };
+}
+
+impl_arbitrary! {
+    Operation => operation,
+    Expression => expression,
+    Condition => condition,
+    Action => action,
+    Program => program,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{analysis, macros};
+    use crate::compiler::RubyCompiler;
+    use crate::simulator::{BrainSimulator, RobotSimulator};
+    use crate::Program;
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn json_roundtrips(program: Program) {
+            // Compare as values: params are HashMaps, so key order varies
+            let json = program.to_json().unwrap();
+            let reparsed = Program::from_json(&json).unwrap();
+            prop_assert_eq!(serde_json::to_value(&reparsed).unwrap(), serde_json::to_value(&program).unwrap());
+        }
+
+        #[test]
+        fn compilers_and_simulators_do_not_panic(program: Program) {
+            let _ = macros::expand(&mut program.clone());
+            let _ = RubyCompiler::new().compile(&program);
+            let _ = BrainSimulator::new().execute(&program);
+            let _ = RobotSimulator::new().execute(&program);
+        }
+
+        #[test]
+        fn analyses_do_not_panic(program: Program) {
+            analysis::program_stats(&program);
+            analysis::critical_path(&program);
+            analysis::actor_network(&program);
+            analysis::information_flows(&program, &analysis::TaintPolicy::default());
+            analysis::idioms::detect(&program);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Test support for UCL programs (`testing` feature)
+//!
+//! `arbitrary` provides proptest strategies and `Arbitrary` impls for the core
+//! types, for fuzzing parsers, compilers and simulators. `snapshot` pins a
+//! program's simulated result to a golden file.
+
+pub mod arbitrary;
+pub mod snapshot;
+
+pub use arbitrary::{action, condition, expression, operation, program};
+pub use snapshot::{SnapshotTarget, assert_program_snapshot, assert_snapshot, check_snapshot};
@@ -0,0 +1,214 @@
+use crate::compiler::RubyCompiler;
+use crate::simulator::{BrainSimulator, BrainState, RobotSimulator, RobotState};
+use crate::Program;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// Set to `1` to rewrite snapshot files instead of comparing against them
+pub const UPDATE_ENV: &str = "UCL_UPDATE_SNAPSHOTS";
+
+/// What to snapshot a program on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotTarget {
+    /// Final brain state and trace
+    Brain,
+    /// Final robot state and log
+    Robot,
+    /// Generated Ruby source
+    Ruby,
+}
+
+/// Run `program` and render the result as stable, diffable text
+///
+/// Simulator errors are part of the snapshot rather than a failure, so
+/// failing programs can be pinned too.
+pub fn snapshot(program: &Program, target: SnapshotTarget) -> Result<String> {
+    Ok(match target {
+        SnapshotTarget::Brain => {
+            let mut brain = BrainSimulator::new();
+            let outcome = brain.execute(program);
+            let mut text = render_brain(brain.state());
+            if let Err(e) = outcome {
+                let _ = writeln!(text, "\n[error]\n{}", e);
+            }
+            text
+        }
+        SnapshotTarget::Robot => {
+            let mut robot = RobotSimulator::new();
+            let outcome = robot.execute(program);
+            let mut text = render_robot(robot.state());
+            if let Err(e) = outcome {
+                let _ = writeln!(text, "\n[error]\n{}", e);
+            }
+            text
+        }
+        SnapshotTarget::Ruby => RubyCompiler::new().compile(program)?,
+    })
+}
+
+fn section(out: &mut String, title: &str, lines: impl IntoIterator<Item = String>) {
+    let lines: Vec<String> = lines.into_iter().collect();
+    if lines.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "[{}]", title);
+    for line in lines {
+        let _ = writeln!(out, "{}", line);
+    }
+    out.push('\n');
+}
+
+/// Brain state with every map sorted by key
+pub fn render_brain(state: &BrainState) -> String {
+    let mut out = String::new();
+    let beliefs: BTreeMap<_, _> = state.beliefs.iter().collect();
+    let emotions: BTreeMap<_, _> = state.emotions.iter().collect();
+    let mut functions: Vec<_> = state.functions.keys().cloned().collect();
+    functions.sort();
+
+    section(&mut out, "beliefs", beliefs.iter().map(|(k, v)| format!("{} = {}", k, v)));
+    section(&mut out, "emotions", emotions.iter().map(|(k, v)| format!("{} = {:.2}", k, v)));
+    section(&mut out, "output", state.output.iter().cloned());
+    section(&mut out, "goals", state.goals.iter().cloned());
+    section(&mut out, "functions", functions);
+    section(&mut out, "attention", state.attention.iter().cloned());
+    section(&mut out, "trace", state.trace.iter().cloned());
+    out.trim_end().to_string() + "\n"
+}
+
+/// Robot state with every map sorted by key
+pub fn render_robot(state: &RobotState) -> String {
+    let mut out = String::new();
+    let objects: BTreeMap<_, _> = state.objects.iter().collect();
+    let temperatures: BTreeMap<_, _> = state.temperatures.iter().collect();
+    let variables: BTreeMap<_, _> = state.variables.iter().collect();
+
+    let (x, y, z) = state.arm_position;
+    section(&mut out, "arm", [
+        format!("position = ({:.2}, {:.2}, {:.2})", x, y, z),
+        format!("gripper = {}", state.gripper.as_deref().unwrap_or("empty")),
+    ]);
+    section(&mut out, "objects", objects.iter().map(|(name, o)| format!(
+        "{}: pos=({:.1}, {:.1}, {:.1}) temp={:.1} volume={:.1} state={} container={}",
+        name, o.position.0, o.position.1, o.position.2, o.temperature, o.volume, o.state,
+        o.container.as_deref().unwrap_or("-"),
+    )));
+    section(&mut out, "temperatures", temperatures.iter().map(|(k, v)| format!("{} = {:.1}", k, v)));
+    section(&mut out, "variables", variables.iter().map(|(k, v)| format!("{} = {}", k, v)));
+    section(&mut out, "log", state.log.iter().cloned());
+    section(&mut out, "errors", state.errors.iter().cloned());
+    out.trim_end().to_string() + "\n"
+}
+
+/// Line diff between the stored and actual snapshot, `-` for removed and `+` for added
+pub fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence table, filled from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            let _ = writeln!(out, "  {}", old[i]);
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(out, "- {}", old[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+ {}", new[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+/// Compare `actual` with the snapshot stored at `path`
+///
+/// A missing snapshot is written and accepted. With `UCL_UPDATE_SNAPSHOTS=1`
+/// every snapshot is rewritten. Otherwise a mismatch returns the diff.
+pub fn check_snapshot(path: impl AsRef<Path>, actual: &str) -> Result<(), String> {
+    let path = path.as_ref();
+    let update = std::env::var(UPDATE_ENV).is_ok_and(|v| v == "1");
+
+    match fs::read_to_string(path) {
+        Ok(expected) if !update => {
+            if expected == actual {
+                Ok(())
+            } else {
+                Err(format!(
+                    "snapshot {} does not match (rerun with {}=1 to accept):\n{}",
+                    path.display(), UPDATE_ENV, diff(&expected, actual)
+                ))
+            }
+        }
+        _ => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            fs::write(path, actual).map_err(|e| format!("writing {}: {}", path.display(), e))
+        }
+    }
+}
+
+/// Panic with a readable diff if `actual` differs from the stored snapshot
+#[track_caller]
+pub fn assert_snapshot(path: impl AsRef<Path>, actual: &str) {
+    if let Err(message) = check_snapshot(path, actual) {
+        panic!("{}", message);
+    }
+}
+
+/// Run `program` on `target` and compare against the snapshot at `path`
+#[track_caller]
+pub fn assert_program_snapshot(path: impl AsRef<Path>, program: &Program, target: SnapshotTarget) {
+    match snapshot(program, target) {
+        Ok(actual) => assert_snapshot(path, &actual),
+        Err(e) => panic!("could not snapshot program: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn root() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn example(name: &str) -> Program {
+        Program::from_json(&fs::read_to_string(root().join("examples").join(name)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_example_snapshots() {
+        let snapshots = root().join("tests/snapshots");
+        assert_program_snapshot(snapshots.join("hello_world.brain.snap"), &example("hello_world.json"), SnapshotTarget::Brain);
+        assert_program_snapshot(snapshots.join("hello_world.rb.snap"), &example("hello_world.json"), SnapshotTarget::Ruby);
+        assert_program_snapshot(snapshots.join("recipe_tea.robot.snap"), &example("recipe_tea.json"), SnapshotTarget::Robot);
+    }
+
+    #[test]
+    fn test_mismatch_reports_diff() {
+        let path = std::env::temp_dir().join(format!("ucl_snapshot_{}.snap", std::process::id()));
+        fs::write(&path, "a\nb\nc\n").unwrap();
+
+        let message = check_snapshot(&path, "a\nB\nc\n").unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        assert!(message.contains("- b\n+ B\n"));
+        assert!(message.contains("  a\n"));
+    }
+}
@@ -0,0 +1,1148 @@
+//! UCL text syntax: a hand-writable alternative to JSON
+//!
+//! One action per line, as `actor Op target` followed by optional clauses:
+//!
+//! ```text
+//! metadata {
+//!   description: "Greeting",
+//! }
+//!
+//! brain StoreFact cat { color: "black", age: 3 } at 0.5 for 2 effects [Memory] id cat
+//! Seller Oblige Seller { duty: "Deliver" } pre "Order confirmed" post "Delivered" because [cat]
+//! VM For loop var i from 0 to 10 step 2 do {
+//!   VM If check when i % 4 == 0 && !(i > 6) then {
+//!     VM Emit output { content: (i) }
+//!   } else {
+//!     VM Wait pause for 0.1
+//!   }
+//! }
+//! VM DefineFunction square { args: ["x"] } do {
+//!   VM Return result { value: (x * x) }
+//! }
+//! ```
+//!
+//! Params and metadata are JSON values whose keys may be left unquoted.
+//! Names that aren't plain words (letters, digits, `_` and `.`) are quoted.
+//! `when` takes a condition and `from`/`to`/`step` an expression, written as
+//! in most languages: bare words are variables and calls name their
+//! arguments, `f(n: n - 1)`. Lists are `[a, b]`, indexed as `xs[0]`, and
+//! built-ins such as `length(xs)`, `format("{x}!")` and `string.upper(s)`
+//! take their arguments unnamed, so functions can't share their names.
+//! Besides comparisons, conditions include `name matches "^[A-Z]"`,
+//! `name starts_with "Dr"`, `xs contains x` and `exists(name)`.
+//! Where an expression is expected, a comparison is its truth value and
+//! `test(...)` holds any other condition; where a condition is expected, any
+//! expression is a truth value, with `not(x)` negating the value rather than
+//! the condition. In params, an expression in parentheses stands for its
+//! JSON form, so `(x * x)` is `{"expr": {"op": "*", ...}}`. A
+//! `DefineFunction`'s `do` block is its `body` param, and a `Try`'s `catch`
+//! block follows its `do` block. `#` starts a comment.
+
+use crate::{Action, BinaryOpExpr, Condition, Expression, Operation, Program, StringOpExpr, UnaryOpExpr};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+/// Symbols, longest first so `**` isn't read as two `*`
+const SYMBOLS: &[&str] = &[
+    "**", "==", "!=", "<=", ">=", "&&", "||",
+    "{", "}", "[", "]", "(", ")", ",", ":", "+", "-", "*", "/", "%", "<", ">", "!",
+];
+
+/// Binary operators an expression can use
+const ARITHMETIC: &[&str] = &["+", "-", "*", "/", "%", "**"];
+
+/// Conditions written as infix operators
+const INFIX_WORDS: &[&str] = &["matches", "starts_with", "contains"];
+
+/// Built-in expressions, written like calls but with unnamed arguments, so
+/// functions can't have these names in text. String operations are
+/// `string.` and the operation, as in `string.upper(name)`.
+const BUILTINS: &[&str] = &["list", "length", "format", "test", "not", "exists"];
+
+const INDENT: &str = "  ";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Number(serde_json::Number),
+    Symbol(&'static str),
+    Newline,
+}
+
+/// A parsed `when`/`from`/`to`/`step` expression, before it is split into
+/// UCL conditions and expressions
+#[derive(Debug, Clone)]
+enum Node {
+    Value(serde_json::Value),
+    Var(String),
+    Call(String, Vec<(String, Node)>),
+    /// A built-in like `length(xs)`, whose arguments aren't named
+    Builtin(String, Vec<Node>),
+    /// `[a, b]`
+    List(Vec<Node>),
+    /// `xs[0]`
+    Index(Box<Node>, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    /// `-x`
+    Neg(Box<Node>),
+    /// Parenthesized, so `(a && b) && c` stays nested
+    Group(Box<Node>),
+}
+
+/// Parse a program written in UCL text syntax
+pub fn parse(source: &str) -> Result<Program> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    parser.program()
+}
+
+/// Write a program in UCL text syntax; `parse` gives the same program back
+pub fn to_text(program: &Program) -> Result<String> {
+    let mut output = String::new();
+
+    if let Some(metadata) = &program.metadata {
+        output.push_str("metadata {\n");
+        for (key, value) in sorted(metadata) {
+            output.push_str(&format!("{}{}: {},\n", INDENT, name_text(key), value_text(value)));
+        }
+        output.push_str("}\n\n");
+    }
+
+    for action in &program.actions {
+        write_action(&mut output, action, 0)?;
+    }
+
+    Ok(output)
+}
+
+fn write_action(output: &mut String, action: &Action, depth: usize) -> Result<()> {
+    let indent = INDENT.repeat(depth);
+    let mut params = action.params.clone();
+    let mut body = action.body_actions.clone();
+
+    // A function's body param is written as its `do` block
+    if action.op == Operation::DefineFunction && body.is_none() {
+        if let Some(value) = params.as_ref().and_then(|p| p.get("body")) {
+            if let Ok(actions) = serde_json::from_value::<Vec<Action>>(value.clone()) {
+                if serde_json::to_value(&actions)? == *value {
+                    if let Some(params) = params.as_mut() {
+                        params.remove("body");
+                    }
+                    body = Some(actions);
+                }
+            }
+        }
+    }
+
+    output.push_str(&format!("{}{} {} {}", indent, name_text(&action.actor), op_text(&action.op)?, name_text(&action.target)));
+    if let Some(params) = &params {
+        output.push(' ');
+        output.push_str(&object_text(params));
+    }
+    if let Some(t) = action.t {
+        output.push_str(&format!(" at {}", serde_json::to_string(&t)?));
+    }
+    if let Some(dur) = action.dur {
+        output.push_str(&format!(" for {}", serde_json::to_string(&dur)?));
+    }
+    if let Some(pre) = &action.pre {
+        output.push_str(&format!(" pre {}", string_text(pre)));
+    }
+    if let Some(post) = &action.post {
+        output.push_str(&format!(" post {}", string_text(post)));
+    }
+    if let Some(effects) = &action.effects {
+        output.push_str(&format!(" effects [{}]", effects.iter().map(|e| name_text(e)).collect::<Vec<_>>().join(", ")));
+    }
+    if let Some(id) = &action.id {
+        output.push_str(&format!(" id {}", name_text(id)));
+    }
+    if let Some(causes) = &action.caused_by {
+        output.push_str(&format!(" because [{}]", causes.iter().map(|c| name_text(c)).collect::<Vec<_>>().join(", ")));
+    }
+    if let Some(condition) = &action.condition {
+        output.push_str(&format!(" when {}", condition_text(condition)?));
+    }
+    if let Some(var) = &action.loop_var {
+        output.push_str(&format!(" var {}", name_text(var)));
+    }
+    for (keyword, expr) in [("from", &action.from_expr), ("to", &action.to_expr), ("step", &action.step_expr)] {
+        if let Some(expr) = expr {
+            output.push_str(&format!(" {} {}", keyword, expression_text(expr)?));
+        }
+    }
+    for (keyword, actions) in [("then", &action.then_actions), ("else", &action.else_actions), ("do", &body), ("catch", &action.catch_actions)] {
+        let Some(actions) = actions else { continue };
+        if actions.is_empty() {
+            output.push_str(&format!(" {} {{}}", keyword));
+            continue;
+        }
+        output.push_str(&format!(" {} {{\n", keyword));
+        for nested in actions {
+            write_action(output, nested, depth + 1)?;
+        }
+        output.push_str(&format!("{}}}", indent));
+    }
+    output.push('\n');
+    Ok(())
+}
+
+fn op_text(op: &Operation) -> Result<String> {
+    match op {
+        Operation::Custom(name) => Ok(name_text(name)),
+        op => Ok(serde_json::to_value(op)?.as_str().unwrap_or_default().to_string()),
+    }
+}
+
+fn is_word(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name) || name.starts_with("string.")
+}
+
+fn name_text(name: &str) -> String {
+    if is_word(name) { name.to_string() } else { string_text(name) }
+}
+
+fn string_text(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+fn sorted(map: &HashMap<String, serde_json::Value>) -> Vec<(&String, &serde_json::Value)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+fn object_text(map: &HashMap<String, serde_json::Value>) -> String {
+    if map.is_empty() {
+        return "{}".to_string();
+    }
+    let entries: Vec<String> = sorted(map).into_iter()
+        .map(|(key, value)| format!("{}: {}", name_text(key), value_text(value)))
+        .collect();
+    format!("{{ {} }}", entries.join(", "))
+}
+
+fn value_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Array(items) => {
+            format!("[{}]", items.iter().map(value_text).collect::<Vec<_>>().join(", "))
+        }
+        serde_json::Value::Object(map) => {
+            if let Some(text) = inline_expression(value) {
+                return format!("({})", text);
+            }
+            if map.is_empty() {
+                return "{}".to_string();
+            }
+            let entries: Vec<String> = map.iter()
+                .map(|(key, value)| format!("{}: {}", name_text(key), value_text(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        scalar => scalar.to_string(),
+    }
+}
+
+/// A param that is a variable, arithmetic or call, in expression syntax, if
+/// writing it that way loses nothing
+fn inline_expression(value: &serde_json::Value) -> Option<String> {
+    let expr: Expression = serde_json::from_value(value.clone()).ok()?;
+    if matches!(expr, Expression::Value(_)) || serde_json::to_value(&expr).ok()? != *value {
+        return None;
+    }
+    expression_text(&expr).ok()
+}
+
+fn condition_text(condition: &Condition) -> Result<String> {
+    let operand = |c: &Condition| -> Result<String> {
+        match c {
+            Condition::And { .. } | Condition::Or { .. } => Ok(format!("({})", condition_text(c)?)),
+            _ => condition_text(c),
+        }
+    };
+    match condition {
+        Condition::Comparison { op, left, right } => {
+            let op = serde_json::to_value(op)?;
+            Ok(format!("{} {} {}", test_operand_text(left)?, op.as_str().unwrap_or_default(), test_operand_text(right)?))
+        }
+        Condition::And { operands } | Condition::Or { operands } if operands.len() < 2 => {
+            bail!("and/or conditions need at least two operands to be written as text")
+        }
+        Condition::And { operands } => Ok(operands.iter().map(operand).collect::<Result<Vec<_>>>()?.join(" && ")),
+        Condition::Or { operands } => Ok(operands.iter().map(operand).collect::<Result<Vec<_>>>()?.join(" || ")),
+        Condition::Not { operand } => Ok(format!("!({})", condition_text(operand)?)),
+        // Bare, these would read back as the condition itself
+        Condition::Expression { value: Expression::Unary { unary } } if unary.op == "!" => {
+            Ok(format!("not({})", expression_text(&unary.operand)?))
+        }
+        Condition::Expression { value: Expression::Test { test } } => Ok(format!("test({})", condition_text(test)?)),
+        Condition::Expression { value } => expression_text(value),
+        Condition::Matches { value, pattern } => Ok(format!("{} matches {}", test_operand_text(value)?, string_text(pattern))),
+        Condition::StartsWith { value, prefix } => {
+            Ok(format!("{} starts_with {}", test_operand_text(value)?, test_operand_text(prefix)?))
+        }
+        Condition::Contains { value, part } => Ok(format!("{} contains {}", test_operand_text(value)?, test_operand_text(part)?)),
+        Condition::Exists { var } if is_word(var) && !["true", "false", "null"].contains(&var.as_str()) => {
+            Ok(format!("exists({})", var))
+        }
+        Condition::Exists { var } => Ok(format!("exists({})", string_text(var))),
+    }
+}
+
+fn expression_text(expr: &Expression) -> Result<String> {
+    match expr {
+        Expression::Value(value) => Ok(value_text(value)),
+        Expression::Variable { var } => {
+            if !is_word(var) || ["true", "false", "null"].contains(&var.as_str()) {
+                bail!("variable {:?} can't be written as text", var);
+            }
+            Ok(var.clone())
+        }
+        Expression::BinaryOp { expr } => {
+            let Some(op) = ARITHMETIC.iter().find(|op| **op == expr.op) else {
+                bail!("operator {:?} can't be written as text", expr.op);
+            };
+            Ok(format!("{} {} {}", operand_text(&expr.left)?, op, operand_text(&expr.right)?))
+        }
+        Expression::FunctionCall { call, args } => {
+            if !is_word(call) || is_builtin(call) {
+                bail!("function {:?} can't be called in text", call);
+            }
+            let mut args: Vec<_> = args.iter().collect();
+            args.sort_by_key(|(name, _)| *name);
+            let args = args.into_iter()
+                .map(|(name, arg)| Ok(format!("{}: {}", name_text(name), expression_text(arg)?)))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(format!("{}({})", call, args.join(", ")))
+        }
+        // All-literal items would read back as one literal list
+        Expression::List { list } if list.iter().all(|item| matches!(item, Expression::Value(_))) => {
+            Ok(format!("list({})", list.iter().map(expression_text).collect::<Result<Vec<_>>>()?.join(", ")))
+        }
+        Expression::List { list } => Ok(format!("[{}]", list.iter().map(expression_text).collect::<Result<Vec<_>>>()?.join(", "))),
+        Expression::Index { index, at } => Ok(format!("{}[{}]", operand_text(index)?, expression_text(at)?)),
+        Expression::Length { length } => Ok(format!("length({})", expression_text(length)?)),
+        Expression::StringOp { string } => {
+            if !is_word(&string.op) {
+                bail!("string operation {:?} can't be written as text", string.op);
+            }
+            let args = string.args.iter().map(expression_text).collect::<Result<Vec<_>>>()?;
+            Ok(format!("string.{}({})", string.op, args.join(", ")))
+        }
+        Expression::Format { format } => Ok(format!("format({})", string_text(format))),
+        Expression::Unary { unary } => {
+            if unary.op != "-" && unary.op != "!" {
+                bail!("unary operator {:?} can't be written as text", unary.op);
+            }
+            match &*unary.operand {
+                // `-1` would read back as a number
+                operand @ Expression::Value(serde_json::Value::Number(_)) if unary.op == "-" => {
+                    Ok(format!("-({})", expression_text(operand)?))
+                }
+                operand => Ok(format!("{}{}", unary.op, operand_text(operand)?)),
+            }
+        }
+        Expression::Test { test } if is_infix(test) || matches!(**test, Condition::Exists { .. }) => condition_text(test),
+        Expression::Test { test } => Ok(format!("test({})", condition_text(test)?)),
+    }
+}
+
+/// Whether a condition is written with an infix operator, so it reads back
+/// as a test where an expression is expected
+fn is_infix(condition: &Condition) -> bool {
+    matches!(
+        condition,
+        Condition::Comparison { .. }
+            | Condition::And { .. }
+            | Condition::Or { .. }
+            | Condition::Matches { .. }
+            | Condition::StartsWith { .. }
+            | Condition::Contains { .. }
+    )
+}
+
+/// An expression compared in a condition, in parentheses if it's itself a
+/// comparison or logic
+fn test_operand_text(expr: &Expression) -> Result<String> {
+    match expr {
+        Expression::Test { test } if is_infix(test) => Ok(format!("({})", expression_text(expr)?)),
+        _ => expression_text(expr),
+    }
+}
+
+/// An expression as the operand of an operator or index, in parentheses
+/// unless it binds tighter than any of them
+fn operand_text(expr: &Expression) -> Result<String> {
+    match expr {
+        Expression::BinaryOp { .. } | Expression::Unary { .. } => Ok(format!("({})", expression_text(expr)?)),
+        Expression::Test { test } if is_infix(test) => Ok(format!("({})", expression_text(expr)?)),
+        _ => expression_text(expr),
+    }
+}
+
+/// Tokens with their line numbers
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let mut line = 1;
+    let mut rest = source;
+
+    while let Some(c) = rest.chars().next() {
+        let operand_before = matches!(tokens.last(),
+            Some((Token::Word(_) | Token::Str(_) | Token::Number(_) | Token::Symbol(")" | "]" | "}"), _)));
+
+        if c == '\n' {
+            tokens.push((Token::Newline, line));
+            line += 1;
+            rest = &rest[1..];
+        } else if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c == '#' {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+        } else if c == '"' {
+            // Find the closing quote, skipping escaped characters
+            let mut end = None;
+            let mut escaped = false;
+            for (i, ch) in rest.char_indices().skip(1) {
+                match ch {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => { end = Some(i); break; }
+                    '\n' => break,
+                    _ => {}
+                }
+            }
+            let end = end.ok_or_else(|| anyhow!("line {}: unterminated string", line))?;
+            let value: String = serde_json::from_str(&rest[..=end]).map_err(|e| anyhow!("line {}: {}", line, e))?;
+            tokens.push((Token::Str(value), line));
+            rest = &rest[end + 1..];
+        } else if c.is_ascii_digit() || (c == '-' && !operand_before && rest[1..].starts_with(|d: char| d.is_ascii_digit())) {
+            let mut end = 1;
+            let bytes = rest.as_bytes();
+            while end < bytes.len() && (bytes[end].is_ascii_digit() || b".eE".contains(&bytes[end])
+                || (b"+-".contains(&bytes[end]) && b"eE".contains(&bytes[end - 1]))) {
+                end += 1;
+            }
+            let number: serde_json::Number = serde_json::from_str(&rest[..end])
+                .map_err(|_| anyhow!("line {}: invalid number {}", line, &rest[..end]))?;
+            tokens.push((Token::Number(number), line));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')).unwrap_or(rest.len());
+            tokens.push((Token::Word(rest[..end].to_string()), line));
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS.iter().find(|s| rest.starts_with(**s))
+                .ok_or_else(|| anyhow!("line {}: unexpected character '{}'", line, c))?;
+            tokens.push((Token::Symbol(symbol), line));
+            rest = &rest[symbol.len()..];
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos).or(self.tokens.last()).map(|(_, line)| *line).unwrap_or(1)
+    }
+
+    fn error(&self, expected: &str) -> anyhow::Error {
+        let found = match self.peek() {
+            Some(Token::Word(word)) => format!("'{}'", word),
+            Some(Token::Str(s)) => format!("{:?}", s),
+            Some(Token::Number(n)) => n.to_string(),
+            Some(Token::Symbol(s)) => format!("'{}'", s),
+            Some(Token::Newline) => "end of line".to_string(),
+            None => "end of file".to_string(),
+        };
+        anyhow!("line {}: expected {}, found {}", self.line(), expected, found)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if !self.eat(symbol) {
+            return Err(self.error(&format!("'{}'", symbol)));
+        }
+        Ok(())
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    fn program(&mut self) -> Result<Program> {
+        let mut program = Program::new();
+        self.skip_newlines();
+
+        let is_metadata = matches!(self.peek(), Some(Token::Word(w)) if w == "metadata")
+            && matches!(self.tokens.get(self.pos + 1), Some((Token::Symbol("{"), _)));
+        if is_metadata {
+            self.pos += 1;
+            program.metadata = Some(self.object()?);
+        }
+
+        loop {
+            self.skip_newlines();
+            if self.peek().is_none() {
+                return Ok(program);
+            }
+            program.actions.push(self.action()?);
+        }
+    }
+
+    /// A word or quoted string
+    fn name(&mut self, what: &str) -> Result<String> {
+        match self.peek() {
+            Some(Token::Word(word)) => { let word = word.clone(); self.pos += 1; Ok(word) }
+            Some(Token::Str(s)) => { let s = s.clone(); self.pos += 1; Ok(s) }
+            _ => Err(self.error(what)),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some(Token::Number(n)) => { let n = n.as_f64().unwrap_or_default(); self.pos += 1; Ok(n) }
+            _ => Err(self.error("a number")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Str(s)) => { let s = s.clone(); self.pos += 1; Ok(s) }
+            _ => Err(self.error("a string")),
+        }
+    }
+
+    /// `[name, ...]`
+    fn names(&mut self, what: &str) -> Result<Vec<String>> {
+        self.expect("[")?;
+        let mut names = Vec::new();
+        while !self.eat("]") {
+            names.push(self.name(what)?);
+            if !self.eat(",") {
+                self.expect("]")?;
+                break;
+            }
+        }
+        Ok(names)
+    }
+
+    fn action(&mut self) -> Result<Action> {
+        let actor = self.name("an actor")?;
+        let op_name = self.name("an operation")?;
+        let op = Operation::named(&op_name);
+        let target = self.name("a target")?;
+        let mut action = Action::new(actor, op, target);
+
+        loop {
+            let keyword = match self.peek() {
+                None | Some(Token::Newline) | Some(Token::Symbol("}")) => break,
+                Some(Token::Symbol("{")) if action.params.is_none() => {
+                    action.params = Some(self.object()?);
+                    continue;
+                }
+                Some(Token::Word(word)) => word.clone(),
+                _ => return Err(self.error("a clause (params, at, for, pre, post, effects, id, because, when, var, from, to, step, then, else, do or catch)")),
+            };
+            self.pos += 1;
+            match keyword.as_str() {
+                "at" if action.t.is_none() => action.t = Some(self.number()?),
+                "for" if action.dur.is_none() => action.dur = Some(self.number()?),
+                "pre" if action.pre.is_none() => action.pre = Some(self.string()?),
+                "post" if action.post.is_none() => action.post = Some(self.string()?),
+                "effects" if action.effects.is_none() => action.effects = Some(self.names("an effect")?),
+                "id" if action.id.is_none() => action.id = Some(self.name("an id")?),
+                "because" if action.caused_by.is_none() => action.caused_by = Some(self.names("an id")?),
+                "when" if action.condition.is_none() => {
+                    let node = self.expression(0)?;
+                    action.condition = Some(condition(node).map_err(|e| anyhow!("line {}: {}", self.line(), e))?);
+                }
+                "var" if action.loop_var.is_none() => action.loop_var = Some(self.name("a loop variable")?),
+                "from" if action.from_expr.is_none() => action.from_expr = Some(self.typed_expression()?),
+                "to" if action.to_expr.is_none() => action.to_expr = Some(self.typed_expression()?),
+                "step" if action.step_expr.is_none() => action.step_expr = Some(self.typed_expression()?),
+                "then" if action.then_actions.is_none() => action.then_actions = Some(self.block()?),
+                "else" if action.else_actions.is_none() => action.else_actions = Some(self.block()?),
+                "catch" if action.catch_actions.is_none() => action.catch_actions = Some(self.block()?),
+                "do" if action.body_actions.is_none() => {
+                    let body = self.block()?;
+                    if action.op == Operation::DefineFunction {
+                        let params = action.params.get_or_insert_with(HashMap::new);
+                        if params.contains_key("body") {
+                            bail!("line {}: function {} has both a body param and a do block", self.line(), action.target);
+                        }
+                        params.insert("body".to_string(), serde_json::to_value(&body)?);
+                    } else {
+                        action.body_actions = Some(body);
+                    }
+                }
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("a clause that isn't already given"));
+                }
+            }
+
+            // `else` may start the line after a `then` block closes, and `catch` after `do`
+            let follower = match keyword.as_str() {
+                "then" => Some("else"),
+                "do" => Some("catch"),
+                _ => None,
+            };
+            if let Some(follower) = follower {
+                let mut lookahead = self.pos;
+                while matches!(self.tokens.get(lookahead), Some((Token::Newline, _))) {
+                    lookahead += 1;
+                }
+                if matches!(self.tokens.get(lookahead), Some((Token::Word(w), _)) if w == follower) {
+                    self.pos = lookahead;
+                }
+            }
+        }
+
+        Ok(action)
+    }
+
+    /// `{`, actions one per line, `}`
+    fn block(&mut self) -> Result<Vec<Action>> {
+        self.expect("{")?;
+        let mut actions = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.eat("}") {
+                return Ok(actions);
+            }
+            if self.peek().is_none() {
+                return Err(self.error("'}'"));
+            }
+            actions.push(self.action()?);
+        }
+    }
+
+    fn object(&mut self) -> Result<HashMap<String, serde_json::Value>> {
+        self.expect("{")?;
+        let mut map = HashMap::new();
+        loop {
+            self.skip_newlines();
+            if self.eat("}") {
+                return Ok(map);
+            }
+            let key = self.name("a key")?;
+            self.expect(":")?;
+            self.skip_newlines();
+            let value = self.value()?;
+            map.insert(key, value);
+            self.skip_newlines();
+            if !self.eat(",") {
+                self.skip_newlines();
+                self.expect("}")?;
+                return Ok(map);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<serde_json::Value> {
+        match self.peek() {
+            Some(Token::Str(s)) => { let s = s.clone(); self.pos += 1; Ok(s.into()) }
+            Some(Token::Number(n)) => { let n = n.clone(); self.pos += 1; Ok(n.into()) }
+            Some(Token::Word(w)) if w == "true" || w == "false" || w == "null" => {
+                let value = match w.as_str() { "true" => true.into(), "false" => false.into(), _ => serde_json::Value::Null };
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(Token::Symbol("{")) => Ok(self.object()?.into_iter().collect::<serde_json::Map<_, _>>().into()),
+            Some(Token::Symbol("(")) => {
+                self.pos += 1;
+                let expr = self.typed_expression()?;
+                self.expect(")")?;
+                Ok(serde_json::to_value(expr)?)
+            }
+            Some(Token::Symbol("[")) => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_newlines();
+                    if self.eat("]") {
+                        return Ok(items.into());
+                    }
+                    items.push(self.value()?);
+                    self.skip_newlines();
+                    if !self.eat(",") {
+                        self.skip_newlines();
+                        self.expect("]")?;
+                        return Ok(items.into());
+                    }
+                }
+            }
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn typed_expression(&mut self) -> Result<Expression> {
+        let node = self.expression(0)?;
+        expression(node).map_err(|e| anyhow!("line {}: {}", self.line(), e))
+    }
+
+    fn expression(&mut self, min_power: u8) -> Result<Node> {
+        let mut left = self.operand()?;
+        while self.eat("[") {
+            let at = self.expression(0)?;
+            self.expect("]")?;
+            left = Node::Index(Box::new(left), Box::new(at));
+        }
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op)) => *op,
+                Some(Token::Word(word)) => match INFIX_WORDS.iter().find(|w| *w == word) {
+                    Some(op) => *op,
+                    None => break,
+                },
+                _ => break,
+            };
+            let Some((left_power, right_power)) = infix_power(op) else { break };
+            if left_power < min_power {
+                break;
+            }
+            self.pos += 1;
+            let right = self.expression(right_power)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<Node> {
+        match self.peek().cloned() {
+            Some(Token::Word(word)) if !["true", "false", "null"].contains(&word.as_str()) => {
+                self.pos += 1;
+                if !self.eat("(") {
+                    return Ok(Node::Var(word));
+                }
+                if is_builtin(&word) {
+                    return Ok(Node::Builtin(word, self.items(")")?));
+                }
+                let mut args = Vec::new();
+                while !self.eat(")") {
+                    let name = self.name("an argument name")?;
+                    self.expect(":")?;
+                    args.push((name, self.expression(0)?));
+                    if !self.eat(",") {
+                        self.expect(")")?;
+                        break;
+                    }
+                }
+                Ok(Node::Call(word, args))
+            }
+            Some(Token::Symbol("(")) => {
+                self.pos += 1;
+                let node = self.expression(0)?;
+                self.expect(")")?;
+                Ok(Node::Group(Box::new(node)))
+            }
+            Some(Token::Symbol("[")) => {
+                self.pos += 1;
+                Ok(Node::List(self.items("]")?))
+            }
+            Some(Token::Symbol("!")) => {
+                self.pos += 1;
+                Ok(Node::Not(Box::new(self.expression(15)?)))
+            }
+            Some(Token::Symbol("-")) => {
+                self.pos += 1;
+                // After a word like `to`, `-1` is read as `-` and `1`
+                if let Some(Token::Number(n)) = self.peek() {
+                    if let Ok(negative) = serde_json::from_str::<serde_json::Number>(&format!("-{}", n)) {
+                        self.pos += 1;
+                        return Ok(Node::Value(negative.into()));
+                    }
+                }
+                Ok(Node::Neg(Box::new(self.expression(13)?)))
+            }
+            Some(_) => self.value().map(Node::Value).map_err(|_| self.error("an expression")),
+            None => Err(self.error("an expression")),
+        }
+    }
+
+    /// Expressions separated by commas, up to `close`
+    fn items(&mut self, close: &str) -> Result<Vec<Node>> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.eat(close) {
+                return Ok(items);
+            }
+            items.push(self.expression(0)?);
+            self.skip_newlines();
+            if !self.eat(",") {
+                self.skip_newlines();
+                self.expect(close)?;
+                return Ok(items);
+            }
+        }
+    }
+}
+
+/// Left and right binding power of an infix operator; `**` is right
+/// associative
+fn infix_power(op: &str) -> Option<(u8, u8)> {
+    match op {
+        "||" => Some((1, 2)),
+        "&&" => Some((3, 4)),
+        "==" | "!=" => Some((5, 6)),
+        "<" | "<=" | ">" | ">=" | "matches" | "starts_with" | "contains" => Some((7, 8)),
+        "+" | "-" => Some((9, 10)),
+        "*" | "/" | "%" => Some((11, 12)),
+        "**" => Some((14, 13)),
+        _ => None,
+    }
+}
+
+fn expression(node: Node) -> Result<Expression> {
+    match node {
+        Node::Value(value) => Ok(Expression::Value(value)),
+        Node::Var(var) => Ok(Expression::Variable { var }),
+        Node::Group(inner) => expression(*inner),
+        Node::Call(call, args) => Ok(Expression::FunctionCall {
+            call,
+            args: args.into_iter().map(|(name, arg)| Ok((name, expression(arg)?))).collect::<Result<_>>()?,
+        }),
+        Node::Binary(op, left, right) if ARITHMETIC.contains(&op) => Ok(Expression::BinaryOp {
+            expr: BinaryOpExpr {
+                op: op.to_string(),
+                left: Box::new(expression(*left)?),
+                right: Box::new(expression(*right)?),
+            },
+        }),
+        Node::List(items) => match items.iter().map(literal).collect::<Option<Vec<_>>>() {
+            Some(values) => Ok(Expression::Value(values.into())),
+            None => Ok(Expression::List { list: items.into_iter().map(expression).collect::<Result<_>>()? }),
+        },
+        Node::Index(index, at) => Ok(Expression::Index { index: Box::new(expression(*index)?), at: Box::new(expression(*at)?) }),
+        Node::Builtin(name, args) => builtin(&name, args),
+        Node::Neg(operand) => unary("-", *operand),
+        Node::Not(operand) => unary("!", *operand),
+        // A comparison or logic is a test of the condition
+        node @ Node::Binary(..) => Ok(Expression::Test { test: Box::new(condition(node)?) }),
+    }
+}
+
+/// The value of a literal, or a list of only literals
+fn literal(node: &Node) -> Option<serde_json::Value> {
+    match node {
+        Node::Value(value) => Some(value.clone()),
+        Node::List(items) => items.iter().map(literal).collect::<Option<Vec<_>>>().map(Into::into),
+        _ => None,
+    }
+}
+
+fn unary(op: &str, operand: Node) -> Result<Expression> {
+    Ok(Expression::Unary { unary: UnaryOpExpr { op: op.to_string(), operand: Box::new(expression(operand)?) } })
+}
+
+fn builtin(name: &str, args: Vec<Node>) -> Result<Expression> {
+    let count = args.len();
+    let one = |args: Vec<Node>| -> Result<Node> {
+        <[Node; 1]>::try_from(args).map(|[arg]| arg).map_err(|_| anyhow!("{}() takes 1 argument, got {}", name, count))
+    };
+    match name {
+        "list" => Ok(Expression::List { list: args.into_iter().map(expression).collect::<Result<_>>()? }),
+        "length" => Ok(Expression::Length { length: Box::new(expression(one(args)?)?) }),
+        "format" => match one(args)? {
+            Node::Value(serde_json::Value::String(format)) => Ok(Expression::Format { format }),
+            _ => bail!("format() takes a string"),
+        },
+        "test" => Ok(Expression::Test { test: Box::new(condition(one(args)?)?) }),
+        "not" => unary("!", one(args)?),
+        "exists" => Ok(Expression::Test { test: Box::new(condition(Node::Builtin(name.to_string(), args))?) }),
+        _ if name.starts_with("string.") => Ok(Expression::StringOp {
+            string: StringOpExpr {
+                op: name["string.".len()..].to_string(),
+                args: args.into_iter().map(expression).collect::<Result<_>>()?,
+            },
+        }),
+        _ => unreachable!("{} is not a built-in", name),
+    }
+}
+
+fn condition(node: Node) -> Result<Condition> {
+    match node {
+        Node::Group(inner) => condition(*inner),
+        Node::Binary(op @ ("&&" | "||"), left, right) => {
+            let mut operands = Vec::new();
+            for side in [*left, *right] {
+                // An unparenthesized chain like `a && b && c` is one list
+                match (op, side) {
+                    ("&&", Node::Binary("&&", l, r)) | ("||", Node::Binary("||", l, r)) => {
+                        match condition(Node::Binary(op, l, r))? {
+                            Condition::And { operands: inner } | Condition::Or { operands: inner } => operands.extend(inner),
+                            other => operands.push(other),
+                        }
+                    }
+                    (_, side) => operands.push(condition(side)?),
+                }
+            }
+            Ok(if op == "&&" { Condition::And { operands } } else { Condition::Or { operands } })
+        }
+        Node::Binary("matches", value, pattern) => match *pattern {
+            Node::Value(serde_json::Value::String(pattern)) => Ok(Condition::Matches { value: expression(*value)?, pattern }),
+            _ => bail!("matches takes a pattern string"),
+        },
+        Node::Binary("starts_with", value, prefix) => {
+            Ok(Condition::StartsWith { value: expression(*value)?, prefix: expression(*prefix)? })
+        }
+        Node::Binary("contains", value, part) => Ok(Condition::Contains { value: expression(*value)?, part: expression(*part)? }),
+        Node::Binary(op, left, right) if !ARITHMETIC.contains(&op) => Ok(Condition::Comparison {
+            op: serde_json::from_value(op.into())?,
+            left: expression(*left)?,
+            right: expression(*right)?,
+        }),
+        Node::Not(operand) => Ok(Condition::Not { operand: Box::new(condition(*operand)?) }),
+        Node::Builtin(name, args) if name == "exists" => match <[Node; 1]>::try_from(args) {
+            Ok([Node::Var(var) | Node::Value(serde_json::Value::String(var))]) => Ok(Condition::Exists { var }),
+            _ => bail!("exists() takes a variable name"),
+        },
+        // Any other expression is a truth value
+        node => Ok(Condition::Expression { value: expression(node)? }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text() {
+        let program = parse(r#"
+            # A counter
+            metadata {
+              description: "Count to ten",
+              tags: ["demo"],
+            }
+
+            brain StoreFact cat { color: "black", "full name": "Tom" } at 0.5 for 2 effects [Memory] id cat
+            VM For loop var i from 0 to n * 2 do {
+              VM If check when i % 4 == 0 && !(i > 6 || i < 1) then {
+                VM Emit output { content: (i), label: { var: "raw" } }
+              }
+              else {}
+            }
+            VM DefineFunction square { args: ["x"] } do {
+              VM Return result { value: { var: "x" } }
+            }
+            robot Flurble gadget because [cat, "the dog"]
+        "#).unwrap();
+
+        assert_eq!(program.meta().get("description").unwrap(), "Count to ten");
+        let fact = &program.actions[0];
+        assert_eq!(fact.params.as_ref().unwrap()["full name"], "Tom");
+        assert_eq!((fact.t, fact.dur), (Some(0.5), Some(2.0)));
+        assert_eq!(fact.effects, Some(vec!["Memory".to_string()]));
+        assert_eq!(fact.id.as_deref(), Some("cat"));
+        assert_eq!(program.actions[3].caused_by, Some(vec!["cat".to_string(), "the dog".to_string()]));
+
+        let json = serde_json::to_value(&program.actions[1]).unwrap();
+        assert_eq!(json["to"], serde_json::json!({"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}));
+        assert_eq!(json["body"][0]["condition"]["operands"][1], serde_json::json!({"type": "not", "operand": {"type": "or", "operands": [
+            {"type": "comparison", "op": ">", "left": {"var": "i"}, "right": 6},
+            {"type": "comparison", "op": "<", "left": {"var": "i"}, "right": 1}
+        ]}}));
+        assert_eq!(json["body"][0]["else"], serde_json::json!([]));
+        assert_eq!(json["body"][0]["then"][0]["params"], serde_json::json!({"content": {"var": "i"}, "label": {"var": "raw"}}));
+
+        assert_eq!(program.actions[2].params.as_ref().unwrap()["body"][0]["op"], "Return");
+        assert_eq!(program.actions[3].op, Operation::Flurble);
+    }
+
+    #[test]
+    fn test_round_trips_examples() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let program = Program::from_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let text = to_text(&program).unwrap();
+            let parsed = parse(&text).unwrap_or_else(|e| panic!("{}: {}\n{}", path.display(), e, text));
+            assert_eq!(parsed.to_canonical_json().unwrap(), program.to_canonical_json().unwrap(), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_try_catch_round_trip() {
+        let program = parse(r#"
+            VM Try risky { error: "problem" } do {
+              VM Throw oops { value: (x + 1) }
+            }
+            catch {
+              VM Emit log { content: { var: "problem" } }
+            }
+        "#).unwrap();
+
+        let json = serde_json::to_value(&program.actions[0]).unwrap();
+        assert_eq!(json["body"][0]["op"], "Throw");
+        assert_eq!(json["catch"][0]["params"]["content"], serde_json::json!({"var": "problem"}));
+        let reparsed = parse(&to_text(&program).unwrap()).unwrap();
+        assert_eq!(reparsed.to_canonical_json().unwrap(), program.to_canonical_json().unwrap());
+    }
+
+    #[test]
+    fn test_truth_value_conditions() {
+        let program = parse("VM While loop when !done && ready() do {}").unwrap();
+        let json = serde_json::to_value(&program.actions[0].condition).unwrap();
+        assert_eq!(json, serde_json::json!({"type": "and", "operands": [
+            {"type": "not", "operand": {"type": "expression", "value": {"var": "done"}}},
+            {"type": "expression", "value": {"call": "ready", "args": {}}}
+        ]}));
+        let reparsed = parse(&to_text(&program).unwrap()).unwrap();
+        assert_eq!(reparsed.to_canonical_json().unwrap(), program.to_canonical_json().unwrap());
+    }
+
+    #[test]
+    fn test_list_expressions_round_trip() {
+        let program = parse(r#"
+            VM ForEach loop var x from [a, 1] to [1, [2]] do {}
+            VM If check when length(xs) > xs[i + 1][0] then {}
+        "#).unwrap();
+
+        let json = serde_json::to_value(&program.actions).unwrap();
+        assert_eq!(json[0]["from"], serde_json::json!({"list": [{"var": "a"}, 1]}));
+        assert_eq!(json[0]["to"], serde_json::json!([1, [2]]));
+        assert_eq!(json[1]["condition"]["left"], serde_json::json!({"length": {"var": "xs"}}));
+        assert_eq!(json[1]["condition"]["right"], serde_json::json!({"index": {
+            "index": {"var": "xs"},
+            "at": {"expr": {"op": "+", "left": {"var": "i"}, "right": 1}}
+        }, "at": 0}));
+        let reparsed = parse(&to_text(&program).unwrap()).unwrap();
+        assert_eq!(reparsed.to_canonical_json().unwrap(), program.to_canonical_json().unwrap());
+
+        // A list expression of literals isn't a literal list
+        let mut literal = program.clone();
+        literal.actions[0].from_expr = Some(Expression::List { list: vec![Expression::Value(1.into())] });
+        let text = to_text(&literal).unwrap();
+        assert!(text.contains("from list(1)"), "{}", text);
+        assert_eq!(parse(&text).unwrap().to_canonical_json().unwrap(), literal.to_canonical_json().unwrap());
+    }
+
+    #[test]
+    fn test_string_expressions_round_trip() {
+        let program = parse(r#"VM Emit greeting { content: (string.concat(format("Hi {name}"), string.upper(x))) }"#).unwrap();
+
+        let json = serde_json::to_value(&program.actions[0]).unwrap();
+        assert_eq!(json["params"]["content"], serde_json::json!({"string": {"op": "concat", "args": [
+            {"format": "Hi {name}"},
+            {"string": {"op": "upper", "args": [{"var": "x"}]}}
+        ]}}));
+        let reparsed = parse(&to_text(&program).unwrap()).unwrap();
+        assert_eq!(reparsed.to_canonical_json().unwrap(), program.to_canonical_json().unwrap());
+
+        let error = parse("VM Emit greeting { content: (format(name)) }").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: format() takes a string");
+    }
+
+    #[test]
+    fn test_unary_and_test_expressions_round_trip() {
+        let program = parse(r#"
+            VM Assign flag { value: (!(x > -y) == test(!ready)) }
+            VM If check when not(x) || test(a == b) || length(xs) - 1 then {}
+        "#).unwrap();
+
+        let json = serde_json::to_value(&program.actions).unwrap();
+        let negative = serde_json::json!({"unary": {"op": "-", "operand": {"var": "y"}}});
+        assert_eq!(json[0]["params"]["value"], serde_json::json!({"test": {"type": "comparison", "op": "==",
+            "left": {"unary": {"op": "!", "operand": {"test": {"type": "comparison", "op": ">", "left": {"var": "x"}, "right": negative}}}},
+            "right": {"test": {"type": "not", "operand": {"type": "expression", "value": {"var": "ready"}}}}
+        }}));
+        assert_eq!(json[1]["condition"]["operands"], serde_json::json!([
+            {"type": "expression", "value": {"unary": {"op": "!", "operand": {"var": "x"}}}},
+            {"type": "expression", "value": {"test": {"type": "comparison", "op": "==", "left": {"var": "a"}, "right": {"var": "b"}}}},
+            {"type": "expression", "value": {"expr": {"op": "-", "left": {"length": {"var": "xs"}}, "right": 1}}}
+        ]));
+        let reparsed = parse(&to_text(&program).unwrap()).unwrap();
+        assert_eq!(reparsed.to_canonical_json().unwrap(), program.to_canonical_json().unwrap());
+
+        // Negating a number isn't a negative number
+        let mut negated = program.clone();
+        negated.actions[1].condition = Some(Condition::Expression {
+            value: Expression::Unary { unary: UnaryOpExpr { op: "-".to_string(), operand: Box::new(Expression::Value(1.into())) } },
+        });
+        let text = to_text(&negated).unwrap();
+        assert!(text.contains("when -(1) then"), "{}", text);
+        assert_eq!(parse(&text).unwrap().to_canonical_json().unwrap(), negated.to_canonical_json().unwrap());
+        let program = parse("VM For loop var i from 0 to -1 do {}").unwrap();
+        assert_eq!(program.actions[0].to_expr, Some(Expression::Value((-1).into())));
+    }
+
+    #[test]
+    fn test_text_conditions_round_trip() {
+        let program = parse(r#"
+            VM If check when name matches "^[A-Z]" && name starts_with prefix + "-" then {}
+            VM Assign found { value: (xs contains x == (x contains "y")) }
+        "#).unwrap();
+
+        let json = serde_json::to_value(&program.actions).unwrap();
+        assert_eq!(json[0]["condition"]["operands"], serde_json::json!([
+            {"type": "matches", "value": {"var": "name"}, "pattern": "^[A-Z]"},
+            {"type": "starts_with", "value": {"var": "name"}, "prefix": {"expr": {"op": "+", "left": {"var": "prefix"}, "right": "-"}}}
+        ]));
+        assert_eq!(json[1]["params"]["value"], serde_json::json!({"test": {"type": "comparison", "op": "==",
+            "left": {"test": {"type": "contains", "value": {"var": "xs"}, "part": {"var": "x"}}},
+            "right": {"test": {"type": "contains", "value": {"var": "x"}, "part": "y"}}
+        }}));
+        let reparsed = parse(&to_text(&program).unwrap()).unwrap();
+        assert_eq!(reparsed.to_canonical_json().unwrap(), program.to_canonical_json().unwrap());
+
+        let error = parse("VM If check when name matches pattern then {}").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: matches takes a pattern string");
+    }
+
+    #[test]
+    fn test_exists_round_trip() {
+        let program = parse(r#"
+            VM If check when !exists(cache) || exists("the cache") then {}
+            VM Assign known { value: (exists(x)) }
+        "#).unwrap();
+
+        let json = serde_json::to_value(&program.actions).unwrap();
+        assert_eq!(json[0]["condition"]["operands"], serde_json::json!([
+            {"type": "not", "operand": {"type": "exists", "var": "cache"}},
+            {"type": "exists", "var": "the cache"}
+        ]));
+        assert_eq!(json[1]["params"]["value"], serde_json::json!({"test": {"type": "exists", "var": "x"}}));
+        let reparsed = parse(&to_text(&program).unwrap()).unwrap();
+        assert_eq!(reparsed.to_canonical_json().unwrap(), program.to_canonical_json().unwrap());
+
+        let error = parse("VM If check when exists(a.b + 1) then {}").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: exists() takes a variable name");
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let error = parse("a Emit b\nc Emit d { x: }").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: expected a value, found '}'");
+
+        let error = parse("VM If x when (1 + ) then {}").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: expected an expression, found ')'");
+    }
+}
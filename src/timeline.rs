@@ -0,0 +1,170 @@
+//! Laying a program's actions out in time by their `t` and `dur`
+//!
+//! An action with `t` starts then; one without starts when the action before
+//! it finishes, so untimed programs keep running in order. Actions that
+//! overlap run concurrently, each in its own lane, and two that change the
+//! same target while they overlap are reported as a [`Conflict`].
+
+use crate::{Action, Operation, Program};
+use serde::Serialize;
+
+/// Operations that change their target other than by writing a value to it
+/// (see [`Operation::writes_target`])
+const MUTATING: &[Operation] = &[
+    Operation::Delete,
+    Operation::Unbind,
+    Operation::Heat,
+    Operation::Pour,
+    Operation::Mix,
+    Operation::Stir,
+    Operation::Place,
+    Operation::Remove,
+    Operation::Grasp,
+    Operation::Release,
+    Operation::Steep,
+    Operation::Serve,
+];
+
+/// When one top-level action runs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Slot {
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+    /// Which of the concurrently running actions this is, from 0
+    pub lane: usize,
+    /// Whether `start` came from the action's own `t`
+    pub timed: bool,
+}
+
+impl Slot {
+    /// Whether the two run at the same time; instantaneous actions only
+    /// coincide if both were placed there by `t`
+    fn overlaps(&self, other: &Slot) -> bool {
+        (self.start < other.end && other.start < self.end)
+            || (self.timed && other.timed && self.start == other.start)
+    }
+}
+
+/// Two actions changing the same target while both are running
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Conflict {
+    pub first: usize,
+    pub second: usize,
+    pub target: String,
+    /// When the overlap begins
+    pub at: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Timeline {
+    /// In start order; actions starting together keep program order
+    pub slots: Vec<Slot>,
+    pub conflicts: Vec<Conflict>,
+    /// Time the last action finishes
+    pub end: f64,
+    /// Most actions running at once
+    pub lanes: usize,
+}
+
+/// Place every top-level action on the timeline
+pub fn timeline(program: &Program) -> Timeline {
+    let mut slots: Vec<Slot> = Vec::with_capacity(program.actions.len());
+    let mut previous_end = 0.0;
+    for (index, action) in program.actions.iter().enumerate() {
+        let start = action.t.unwrap_or(previous_end);
+        let end = start + action.dur.unwrap_or(0.0).max(0.0);
+        slots.push(Slot { index, start, end, lane: 0, timed: action.t.is_some() });
+        previous_end = end;
+    }
+    slots.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.index.cmp(&b.index)));
+
+    // Each action takes the lowest lane nothing still running holds
+    let mut lane_ends: Vec<Option<Slot>> = Vec::new();
+    for slot in &mut slots {
+        let free = lane_ends.iter().position(|busy| busy.as_ref().is_none_or(|busy| !busy.overlaps(slot)));
+        slot.lane = free.unwrap_or(lane_ends.len());
+        if slot.lane == lane_ends.len() {
+            lane_ends.push(None);
+        }
+        lane_ends[slot.lane] = Some(slot.clone());
+    }
+
+    let mut conflicts = Vec::new();
+    for (i, a) in slots.iter().enumerate() {
+        for b in slots[i + 1..].iter().take_while(|b| b.start <= a.end) {
+            let (first, second) = (&program.actions[a.index], &program.actions[b.index]);
+            if a.overlaps(b) && mutates(first) && mutates(second) && first.target == second.target {
+                conflicts.push(Conflict { first: a.index.min(b.index), second: a.index.max(b.index), target: first.target.clone(), at: b.start });
+            }
+        }
+    }
+
+    Timeline {
+        end: slots.iter().map(|s| s.end).fold(0.0, f64::max),
+        lanes: lane_ends.len(),
+        slots,
+        conflicts,
+    }
+}
+
+fn mutates(action: &Action) -> bool {
+    action.op.writes_target() || MUTATING.contains(&action.op)
+}
+
+impl Timeline {
+    /// The program with its top-level actions in the order they start, for
+    /// simulators that run actions one after another
+    pub fn ordered(&self, program: &Program) -> Program {
+        Program {
+            metadata: program.metadata.clone(),
+            actions: self.slots.iter().map(|slot| program.actions[slot.index].clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        Program::from_json(r#"{"actions": [
+            {"actor": "cook", "op": "Heat", "target": "water", "t": 0, "dur": 180},
+            {"actor": "cook", "op": "Place", "target": "tea_bag", "params": {"into": "cup"}},
+            {"actor": "timer", "op": "Emit", "target": "alarm", "t": 170},
+            {"actor": "helper", "op": "Pour", "target": "water", "t": 120, "dur": 10, "params": {"from": "jug", "into": "kettle"}},
+            {"actor": "cook", "op": "Bind", "target": "done", "params": {"value": true}},
+            {"actor": "cook", "op": "Assign", "target": "done", "params": {"value": false}}
+        ]}"#).unwrap()
+    }
+
+    #[test]
+    fn test_orders_by_time_and_assigns_lanes() {
+        let timeline = timeline(&program());
+        let placed: Vec<(usize, f64, f64, usize)> = timeline.slots.iter().map(|s| (s.index, s.start, s.end, s.lane)).collect();
+        assert_eq!(placed, vec![
+            (0, 0.0, 180.0, 0),
+            (3, 120.0, 130.0, 1),
+            (4, 130.0, 130.0, 1),
+            (5, 130.0, 130.0, 1),
+            (2, 170.0, 170.0, 1),
+            (1, 180.0, 180.0, 0),
+        ]);
+        assert_eq!((timeline.end, timeline.lanes), (180.0, 2));
+
+        let order: Vec<String> = timeline.ordered(&program()).actions.iter().map(|a| a.target.clone()).collect();
+        assert_eq!(order, vec!["water", "water", "done", "done", "alarm", "tea_bag"]);
+    }
+
+    #[test]
+    fn test_detects_overlapping_mutations() {
+        let timeline = timeline(&program());
+        assert_eq!(timeline.conflicts, vec![Conflict { first: 0, second: 3, target: "water".to_string(), at: 120.0 }]);
+
+        let chord = Program::from_json(r#"{"actions": [
+            {"actor": "A", "op": "Write", "target": "cell", "t": 1, "params": {"value": 1}},
+            {"actor": "B", "op": "Write", "target": "cell", "t": 1, "params": {"value": 2}}
+        ]}"#).unwrap();
+        assert_eq!(super::timeline(&chord).conflicts.len(), 1);
+    }
+}
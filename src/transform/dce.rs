@@ -0,0 +1,208 @@
+//! Dead action elimination
+
+use super::Pass;
+use super::fold::constant_condition;
+use super::inline::has_call;
+use crate::analysis::reads;
+use crate::{Action, Operation, Program};
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Remove stores to variables nothing reads, functions nothing calls, and
+/// `else` branches behind a constantly true condition, until none are left
+///
+/// A name counts as read if any action reads it, targets it with anything
+/// but a store, or mentions it as a string param (an `Emit` of `"x"` prints
+/// the variable `x`). Stores whose value calls a function are kept, since
+/// the call may have effects. Removed stores no longer show up in the
+/// simulators' final state.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeadCode;
+
+/// Something [`eliminate`] removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Removal {
+    pub op: Operation,
+    pub target: String,
+    pub reason: &'static str,
+}
+
+impl Removal {
+    fn of(action: &Action, reason: &'static str) -> Self {
+        Removal { op: action.op.clone(), target: action.target.clone(), reason }
+    }
+}
+
+impl Pass for DeadCode {
+    fn name(&self) -> &str {
+        "dce"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<bool> {
+        Ok(!eliminate(program).is_empty())
+    }
+}
+
+/// Remove dead actions, repeating while removals expose more
+pub fn eliminate(program: &mut Program) -> Vec<Removal> {
+    let mut removed = Vec::new();
+    loop {
+        let usage = Usage::of(program);
+        let before = removed.len();
+        sweep(&mut program.actions, &usage, &mut removed);
+        if removed.len() == before {
+            return removed;
+        }
+    }
+}
+
+/// What a program reads and calls
+struct Usage {
+    read: HashSet<String>,
+    /// Functions reachable from code outside any function
+    live_functions: HashSet<String>,
+}
+
+impl Usage {
+    fn of(program: &Program) -> Self {
+        let mut usage = Usage { read: HashSet::new(), live_functions: HashSet::new() };
+        // Calls made from the top level (None) and from each function's body
+        let mut calls: HashMap<Option<String>, HashSet<String>> = HashMap::new();
+        usage.collect(&program.actions, None, &mut calls);
+
+        let mut pending: Vec<String> = calls.get(&None).into_iter().flatten().cloned().collect();
+        while let Some(function) = pending.pop() {
+            if usage.live_functions.insert(function.clone()) {
+                pending.extend(calls.get(&Some(function)).into_iter().flatten().cloned());
+            }
+        }
+        usage
+    }
+
+    fn collect(&mut self, actions: &[Action], function: Option<&str>, calls: &mut HashMap<Option<String>, HashSet<String>>) {
+        for action in actions {
+            self.read.extend(reads(action));
+            if !action.op.is_assignment() && action.op != Operation::DefineFunction {
+                self.read.insert(action.target.clone());
+            }
+
+            let called = calls.entry(function.map(str::to_string)).or_default();
+            if action.op == Operation::Call {
+                called.insert(action.target.clone());
+            }
+            for (key, value) in action.params.iter().flatten() {
+                if action.op == Operation::DefineFunction && key == "body" {
+                    continue;
+                }
+                strings_and_calls(value, &mut self.read, called);
+            }
+            for expression in [&action.from_expr, &action.to_expr, &action.step_expr].into_iter().flatten() {
+                if let Ok(value) = serde_json::to_value(expression) {
+                    strings_and_calls(&value, &mut self.read, called);
+                }
+            }
+            if let Some(value) = action.condition.as_ref().and_then(|c| serde_json::to_value(c).ok()) {
+                strings_and_calls(&value, &mut self.read, called);
+            }
+
+            action.visit_children(&mut |name, nested| {
+                let scope = if name == "params.body" { Some(action.target.as_str()) } else { function };
+                self.collect(nested, scope, calls);
+            });
+        }
+    }
+}
+
+/// Every string in `value`, and the functions its `{"call": ...}`s name
+fn strings_and_calls(value: &Value, strings: &mut HashSet<String>, calls: &mut HashSet<String>) {
+    match value {
+        Value::String(s) => {
+            strings.insert(s.clone());
+        }
+        Value::Object(map) => {
+            if let Some(name) = map.get("call").and_then(|c| c.as_str()) {
+                calls.insert(name.to_string());
+            }
+            map.values().for_each(|v| strings_and_calls(v, strings, calls));
+        }
+        Value::Array(items) => items.iter().for_each(|v| strings_and_calls(v, strings, calls)),
+        _ => {}
+    }
+}
+
+fn sweep(actions: &mut Vec<Action>, usage: &Usage, removed: &mut Vec<Removal>) {
+    actions.retain(|action| {
+        let value_calls = action.params.as_ref().is_some_and(|p| p.values().any(has_call));
+        if action.op.is_assignment() && !usage.read.contains(&action.target) && !value_calls {
+            removed.push(Removal::of(action, "never read"));
+            false
+        } else if action.op == Operation::DefineFunction && !usage.live_functions.contains(&action.target) {
+            removed.push(Removal::of(action, "never called"));
+            false
+        } else {
+            true
+        }
+    });
+
+    for action in actions.iter_mut() {
+        let always = action.op == Operation::If && action.condition.as_ref().and_then(constant_condition) == Some(true);
+        if always && action.else_actions.take().is_some() {
+            removed.push(Removal::of(action, "else branch unreachable"));
+        }
+        action.visit_children_mut(&mut |_, nested| sweep(nested, usage, removed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removes_dead_stores_functions_and_branches() {
+        let mut program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "unused", "params": {"args": [], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"call": "helper", "args": {}}}}
+            ]}},
+            {"actor": "VM", "op": "DefineFunction", "target": "helper", "params": {"args": [], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": 1}}
+            ]}},
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Bind", "target": "a", "params": {"value": 1}},
+            {"actor": "VM", "op": "Bind", "target": "b", "params": {"value": {"var": "a"}}},
+            {"actor": "VM", "op": "Bind", "target": "c", "params": {"value": 3}},
+            {"actor": "VM", "op": "Bind", "target": "d", "params": {"value": {"call": "double", "args": {"n": 4}}}},
+            {"actor": "VM", "op": "If", "target": "check",
+             "condition": {"type": "comparison", "op": "==", "left": 1, "right": 1},
+             "then": [{"actor": "VM", "op": "Emit", "target": "out", "params": {"content": "c"}}],
+             "else": [{"actor": "VM", "op": "Emit", "target": "never"}]}
+        ]}"#).unwrap();
+
+        let removed = eliminate(&mut program);
+        let summary: Vec<String> = removed.iter().map(|r| format!("{} {}", r.target, r.reason)).collect();
+        assert_eq!(summary, vec![
+            "unused never called",
+            "helper never called",
+            "b never read",
+            "check else branch unreachable",
+            "a never read",
+        ]);
+
+        let targets: Vec<&str> = program.actions.iter().map(|a| a.target.as_str()).collect();
+        assert_eq!(targets, vec!["double", "c", "d", "check"]);
+        assert!(!DeadCode.run(&mut program).unwrap());
+    }
+
+    #[test]
+    fn test_keeps_stores_read_by_format_templates() {
+        let mut program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "a", "params": {"value": 1}},
+            {"actor": "VM", "op": "Emit", "target": "out", "params": {"content": {"format": "a={a}"}}}
+        ]}"#).unwrap();
+
+        assert!(eliminate(&mut program).is_empty());
+        assert_eq!(program.actions.len(), 2);
+    }
+}
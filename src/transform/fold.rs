@@ -0,0 +1,228 @@
+//! Constant folding: arithmetic on literals and `If`s with constant conditions
+
+use super::Pass;
+use crate::{Action, ComparisonOp, Condition, Expression, Operation, Program};
+use anyhow::Result;
+use serde_json::{Number, Value};
+
+/// Evaluate `{"expr": ...}` arithmetic whose operands are literals, replace
+/// an `If` whose condition is constant with the branch it would take, and
+/// drop a `While` whose condition is constantly false
+///
+/// Results are floats, as the simulators compute them, and integer division
+/// isn't folded, since targets disagree on it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fold;
+
+impl Pass for Fold {
+    fn name(&self) -> &str {
+        "fold"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<bool> {
+        Ok(fold_actions(&mut program.actions) > 0)
+    }
+}
+
+/// Fold a list in place; returns how many folds were made
+fn fold_actions(actions: &mut Vec<Action>) -> usize {
+    let mut folds = 0;
+    let mut folded = Vec::with_capacity(actions.len());
+
+    for mut action in std::mem::take(actions) {
+        folds += fold_fields(&mut action);
+        action.visit_children_mut(&mut |_, nested| folds += fold_actions(nested));
+
+        let constant = action.condition.as_ref().and_then(constant_condition);
+        match (&action.op, constant) {
+            (Operation::If, Some(taken)) => {
+                let branch = if taken { action.then_actions } else { action.else_actions };
+                folded.extend(branch.unwrap_or_default());
+                folds += 1;
+            }
+            (Operation::While, Some(false)) => folds += 1,
+            _ => folded.push(action),
+        }
+    }
+
+    *actions = folded;
+    folds
+}
+
+/// Fold the expressions in an action's params, condition and loop bounds
+fn fold_fields(action: &mut Action) -> usize {
+    let mut folds = 0;
+    let function = action.op == Operation::DefineFunction;
+    for (key, value) in action.params.iter_mut().flatten() {
+        // A function body is a list of actions, folded as one
+        if !(function && key == "body") {
+            folds += fold_value(value);
+        }
+    }
+
+    if let Some(condition) = &mut action.condition {
+        folds += fold_typed(condition);
+    }
+    for bound in [&mut action.from_expr, &mut action.to_expr, &mut action.step_expr].into_iter().flatten() {
+        folds += fold_typed::<Expression>(bound);
+    }
+    folds
+}
+
+/// Fold the expressions inside a condition or expression through its JSON
+fn fold_typed<T: serde::Serialize + serde::de::DeserializeOwned>(typed: &mut T) -> usize {
+    let Ok(mut value) = serde_json::to_value(&*typed) else { return 0 };
+    let folds = fold_value(&mut value);
+    if folds > 0 {
+        if let Ok(folded) = serde_json::from_value(value) {
+            *typed = folded;
+            return folds;
+        }
+    }
+    0
+}
+
+/// Replace `{"expr": {"op", "left", "right"}}` with its value wherever both
+/// operands are (or fold to) numbers
+fn fold_value(value: &mut Value) -> usize {
+    let mut folds = match value {
+        Value::Object(map) => map.values_mut().map(fold_value).sum(),
+        Value::Array(items) => items.iter_mut().map(fold_value).sum(),
+        _ => 0,
+    };
+
+    let result = value.as_object()
+        .filter(|map| map.len() == 1)
+        .and_then(|map| map.get("expr"))
+        .and_then(|expr| arithmetic(expr.get("op")?.as_str()?, expr.get("left")?.as_number()?, expr.get("right")?.as_number()?));
+    if let Some(result) = result {
+        *value = result;
+        folds += 1;
+    }
+    folds
+}
+
+fn arithmetic(op: &str, left: &Number, right: &Number) -> Option<Value> {
+    let integers = !left.is_f64() && !right.is_f64();
+    let (l, r) = (left.as_f64()?, right.as_f64()?);
+    let result = match op {
+        "+" => l + r,
+        "-" => l - r,
+        "*" => l * r,
+        "/" if !integers && r != 0.0 => l / r,
+        // Floored and truncated remainders agree when both are positive
+        "%" if l >= 0.0 && r > 0.0 => l % r,
+        _ => return None,
+    };
+    Number::from_f64(result).map(Value::Number)
+}
+
+/// The value of a condition that reads no variables, if it has one
+pub(super) fn constant_condition(condition: &Condition) -> Option<bool> {
+    match condition {
+        Condition::Comparison { op, left, right } => {
+            let (Expression::Value(left), Expression::Value(right)) = (left, right) else { return None };
+            let numbers = left.as_f64().zip(right.as_f64());
+            match op {
+                ComparisonOp::Equal => Some(numbers.map_or(left == right, |(l, r)| l == r)),
+                ComparisonOp::NotEqual => Some(numbers.map_or(left != right, |(l, r)| l != r)),
+                ComparisonOp::LessThan => numbers.map(|(l, r)| l < r),
+                ComparisonOp::LessThanOrEqual => numbers.map(|(l, r)| l <= r),
+                ComparisonOp::GreaterThan => numbers.map(|(l, r)| l > r),
+                ComparisonOp::GreaterThanOrEqual => numbers.map(|(l, r)| l >= r),
+            }
+        }
+        Condition::And { operands } => {
+            let values: Vec<Option<bool>> = operands.iter().map(constant_condition).collect();
+            if values.contains(&Some(false)) {
+                Some(false)
+            } else {
+                values.iter().all(|v| *v == Some(true)).then_some(true)
+            }
+        }
+        Condition::Or { operands } => {
+            let values: Vec<Option<bool>> = operands.iter().map(constant_condition).collect();
+            if values.contains(&Some(true)) {
+                Some(true)
+            } else {
+                values.iter().all(|v| *v == Some(false)).then_some(false)
+            }
+        }
+        Condition::Not { operand } => constant_condition(operand).map(|v| !v),
+        Condition::Expression { value: Expression::Value(value) } => Some(!matches!(value, Value::Null | Value::Bool(false))),
+        Condition::Expression { .. } | Condition::Matches { .. } | Condition::StartsWith { .. } | Condition::Contains { .. } | Condition::Exists { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::BrainSimulator;
+    use serde_json::json;
+
+    fn folded(json: &str) -> Program {
+        let mut program = Program::from_json(json).unwrap();
+        Fold.run(&mut program).unwrap();
+        program
+    }
+
+    #[test]
+    fn test_folds_literal_arithmetic() {
+        let program = folded(r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "a", "params": {"value": {"expr": {"op": "*", "left": {"expr": {"op": "+", "left": 2, "right": 3}}, "right": 4}}}},
+            {"actor": "VM", "op": "Bind", "target": "b", "params": {"value": {"expr": {"op": "/", "left": 7, "right": 2}}}},
+            {"actor": "VM", "op": "Bind", "target": "c", "params": {"value": {"expr": {"op": "/", "left": 7.0, "right": 2}}}},
+            {"actor": "VM", "op": "Bind", "target": "d", "params": {"value": {"expr": {"op": "+", "left": {"var": "x"}, "right": {"expr": {"op": "-", "left": 1, "right": 1}}}}}}
+        ]}"#);
+        let value = |i: usize| program.actions[i].params.as_ref().unwrap()["value"].clone();
+
+        assert_eq!(value(0), json!(20.0));
+        assert_eq!(value(1), json!({"expr": {"op": "/", "left": 7, "right": 2}}));
+        assert_eq!(value(2), json!(3.5));
+        assert_eq!(value(3), json!({"expr": {"op": "+", "left": {"var": "x"}, "right": 0.0}}));
+    }
+
+    #[test]
+    fn test_brain_output_unchanged_for_examples() {
+        let run = |program: &Program| {
+            let mut brain = BrainSimulator::new().with_seed(7);
+            let result = brain.execute(program).map_err(|e| e.to_string());
+            (result, brain.state().output.clone(), serde_json::to_value(&brain.state().beliefs).unwrap())
+        };
+
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let program = Program::load(&path).unwrap();
+            let mut folded = program.clone();
+            Fold.run(&mut folded).unwrap();
+            assert_eq!(run(&folded), run(&program), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_prunes_constant_branches() {
+        let program = folded(r#"{"actions": [
+            {"actor": "VM", "op": "If", "target": "debug",
+             "condition": {"type": "comparison", "op": ">", "left": {"expr": {"op": "*", "left": 2, "right": 2}}, "right": 3},
+             "then": [{"actor": "VM", "op": "Emit", "target": "yes"}, {"actor": "VM", "op": "Emit", "target": "again"}],
+             "else": [{"actor": "VM", "op": "Emit", "target": "no"}]},
+            {"actor": "VM", "op": "If", "target": "off",
+             "condition": {"type": "and", "operands": [
+                 {"type": "comparison", "op": "==", "left": {"var": "x"}, "right": 1},
+                 {"type": "comparison", "op": "==", "left": "a", "right": "b"}
+             ]},
+             "then": [{"actor": "VM", "op": "Emit", "target": "never"}]},
+            {"actor": "VM", "op": "While", "target": "loop",
+             "condition": {"type": "comparison", "op": "<", "left": {"var": "i"}, "right": 3}, "body": [
+                {"actor": "VM", "op": "While", "target": "dead", "condition": {"type": "comparison", "op": "!=", "left": 1, "right": 1.0}}
+             ]}
+        ]}"#);
+
+        let targets: Vec<&str> = program.actions.iter().map(|a| a.target.as_str()).collect();
+        assert_eq!(targets, vec!["yes", "again", "loop"]);
+        assert_eq!(program.actions[2].body_actions.as_deref().map(<[Action]>::len), Some(0));
+    }
+}
@@ -0,0 +1,187 @@
+//! Inlining calls to single-expression functions
+
+use super::Pass;
+use crate::analysis::collect_vars;
+use crate::{Operation, Program};
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Replace `{"call": f, "args": {...}}` with `f`'s return expression, for
+/// functions whose whole body is one `Return` that reads only its args and
+/// calls nothing, and has no `{"format": ...}` template, whose placeholders
+/// can't be substituted. An argument that makes a call is only substituted where
+/// its parameter is used exactly once, so the call still runs once. The
+/// definitions stay, so other callers still work.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Inline;
+
+/// An inlinable function's arg names and returned expression
+type Template = (Vec<String>, Value);
+
+impl Pass for Inline {
+    fn name(&self) -> &str {
+        "inline"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<bool> {
+        let templates = inlinable(program);
+        if templates.is_empty() {
+            return Ok(false);
+        }
+
+        let mut value = serde_json::to_value(&*program)?;
+        let inlined = rewrite(&mut value, &templates);
+        if inlined > 0 {
+            *program = serde_json::from_value(value)?;
+        }
+        Ok(inlined > 0)
+    }
+}
+
+/// Functions defined exactly once whose body is a single simple `Return`
+fn inlinable(program: &Program) -> HashMap<String, Template> {
+    let mut definitions: HashMap<String, Option<Template>> = HashMap::new();
+
+    program.walk(&mut |action, _| {
+        if action.op != Operation::DefineFunction {
+            return;
+        }
+        let template = action.params.as_ref().and_then(|params| {
+            let args: Vec<String> = params.get("args")?.as_array()?.iter()
+                .map(|a| a.as_str().map(str::to_string))
+                .collect::<Option<_>>()?;
+            let [body] = params.get("body")?.as_array()?.as_slice() else { return None };
+            if body.get("op")?.as_str()? != "Return" {
+                return None;
+            }
+            let expression = body.get("params")?.get("value")?.clone();
+
+            let mut vars = Vec::new();
+            collect_vars(&expression, &mut vars);
+            (!has_call(&expression) && !has_format(&expression) && vars.iter().all(|v| args.contains(v))).then_some((args, expression))
+        });
+        // A second definition makes calls ambiguous
+        definitions.entry(action.target.clone())
+            .and_modify(|existing| *existing = None)
+            .or_insert(template);
+    });
+
+    definitions.into_iter().filter_map(|(name, template)| Some((name, template?))).collect()
+}
+
+pub(super) fn has_call(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.contains_key("call") || map.values().any(has_call),
+        Value::Array(items) => items.iter().any(has_call),
+        _ => false,
+    }
+}
+
+fn has_format(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.get("format").is_some_and(Value::is_string) || map.values().any(has_format),
+        Value::Array(items) => items.iter().any(has_format),
+        _ => false,
+    }
+}
+
+/// Inline every call to a template, innermost first; returns how many
+fn rewrite(value: &mut Value, templates: &HashMap<String, Template>) -> usize {
+    let mut count = match value {
+        Value::Object(map) => map.values_mut().map(|v| rewrite(v, templates)).sum(),
+        Value::Array(items) => items.iter_mut().map(|v| rewrite(v, templates)).sum(),
+        _ => 0,
+    };
+
+    if let Some(inlined) = inline_call(value, templates) {
+        *value = inlined;
+        count += 1;
+    }
+    count
+}
+
+fn inline_call(value: &Value, templates: &HashMap<String, Template>) -> Option<Value> {
+    let call = value.as_object().filter(|map| map.len() == 2)?;
+    let (names, expression) = templates.get(call.get("call")?.as_str()?)?;
+    let args = call.get("args")?.as_object()?;
+    if !names.iter().all(|name| args.contains_key(name)) {
+        return None;
+    }
+
+    let mut uses = Vec::new();
+    collect_vars(expression, &mut uses);
+    let runs_once = |name: &String| uses.iter().filter(|used| *used == name).count() == 1;
+    if !names.iter().all(|name| !has_call(&args[name]) || runs_once(name)) {
+        return None;
+    }
+
+    let mut inlined = expression.clone();
+    substitute(&mut inlined, args);
+    Some(inlined)
+}
+
+/// Replace each `{"var": name}` with the argument passed for `name`
+fn substitute(value: &mut Value, args: &Map<String, Value>) {
+    let replacement = value.as_object()
+        .filter(|map| map.len() == 1)
+        .and_then(|map| map.get("var")?.as_str())
+        .and_then(|name| args.get(name));
+    if let Some(arg) = replacement {
+        *value = arg.clone();
+        return;
+    }
+
+    match value {
+        Value::Object(map) => map.values_mut().for_each(|v| substitute(v, args)),
+        Value::Array(items) => items.iter_mut().for_each(|v| substitute(v, args)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_inlines_only_simple_functions() {
+        let mut program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "square", "params": {"args": ["x"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "x"}, "right": {"var": "x"}}}}}
+            ]}},
+            {"actor": "VM", "op": "DefineFunction", "target": "fact", "params": {"args": ["n"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"call": "fact", "args": {"n": {"var": "n"}}}}}
+            ]}},
+            {"actor": "VM", "op": "Bind", "target": "a", "params": {"value": {"call": "square", "args": {"x": {"call": "square", "args": {"x": {"var": "y"}}}}}}},
+            {"actor": "VM", "op": "Bind", "target": "b", "params": {"value": {"call": "fact", "args": {"n": 3}}}},
+            {"actor": "VM", "op": "Bind", "target": "c", "params": {"value": {"call": "square", "args": {"x": {"call": "noisy", "args": {}}}}}}
+        ]}"#).unwrap();
+
+        assert!(Inline.run(&mut program).unwrap());
+
+        let square_y = json!({"expr": {"op": "*", "left": {"var": "y"}, "right": {"var": "y"}}});
+        assert_eq!(program.actions[2].params.as_ref().unwrap()["value"], json!({"expr": {"op": "*", "left": square_y, "right": square_y}}));
+        assert_eq!(program.actions[3].params.as_ref().unwrap()["value"], json!({"call": "fact", "args": {"n": 3}}));
+        // Inlining would call noisy() twice
+        assert_eq!(program.actions[4].params.as_ref().unwrap()["value"], json!({"call": "square", "args": {"x": {"call": "noisy", "args": {}}}}));
+        assert_eq!(program.actions.len(), 5);
+
+        assert!(!Inline.run(&mut program).unwrap());
+    }
+
+    #[test]
+    fn test_leaves_format_templates_alone() {
+        let mut program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "greet", "params": {"args": ["name"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"format": "hi {name}"}}}
+            ]}},
+            {"actor": "VM", "op": "Bind", "target": "name", "params": {"value": "global"}},
+            {"actor": "VM", "op": "Bind", "target": "a", "params": {"value": {"call": "greet", "args": {"name": "Bob"}}}}
+        ]}"#).unwrap();
+
+        // Inlined, the template would read the caller's `name`
+        assert!(!Inline.run(&mut program).unwrap());
+        assert_eq!(program.actions[2].params.as_ref().unwrap()["value"], json!({"call": "greet", "args": {"name": "Bob"}}));
+    }
+}
@@ -0,0 +1,122 @@
+//! Program rewrites that compose: each [`Pass`] changes a program in place,
+//! and a [`PassManager`] runs a list of them in order
+
+pub mod dce;
+pub mod fold;
+pub mod inline;
+pub mod rename;
+
+pub use dce::DeadCode;
+pub use fold::Fold;
+pub use inline::Inline;
+pub use rename::RenameActors;
+
+use crate::Program;
+use anyhow::{Result, bail};
+
+/// One rewrite of a program
+pub trait Pass {
+    /// The name `ucl transform --pass` knows it by
+    fn name(&self) -> &str;
+
+    /// Rewrite `program` in place, returning whether anything changed
+    fn run(&self, program: &mut Program) -> Result<bool>;
+}
+
+/// Passes by name, with their arguments after a colon
+pub const PASS_NAMES: &[&str] = &["inline", "fold", "dce", "rename-actors:OLD=NEW,..."];
+
+/// The pass for a `--pass` spec such as `inline` or `rename-actors:VM=BrainVM`
+pub fn pass_named(spec: &str) -> Result<Box<dyn Pass>> {
+    let (name, args) = match spec.split_once(':') {
+        Some((name, args)) => (name, Some(args)),
+        None => (spec, None),
+    };
+    Ok(match (name, args) {
+        ("inline", None) => Box::new(Inline),
+        ("fold", None) => Box::new(Fold),
+        ("dce", None) => Box::new(DeadCode),
+        ("rename-actors", Some(args)) => Box::new(RenameActors::parse(args)?),
+        ("rename-actors", None) => bail!("rename-actors needs OLD=NEW pairs, e.g. rename-actors:VM=BrainVM"),
+        _ => bail!("Unknown pass: {}. Available: {}", spec, PASS_NAMES.join(", ")),
+    })
+}
+
+/// What one pass did
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassReport {
+    pub name: String,
+    pub changed: bool,
+}
+
+/// Runs passes in the order they were added
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Passes from `--pass` specs, in order
+    pub fn from_specs(specs: &[String]) -> Result<Self> {
+        let mut manager = Self::new();
+        for spec in specs {
+            manager.passes.push(pass_named(spec)?);
+        }
+        Ok(manager)
+    }
+
+    pub fn with_pass(mut self, pass: impl Pass + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Run every pass once, stopping at the first that fails
+    pub fn run(&self, program: &mut Program) -> Result<Vec<PassReport>> {
+        self.passes.iter()
+            .map(|pass| {
+                let changed = pass.run(program)
+                    .map_err(|e| anyhow::anyhow!("{} pass: {}", pass.name(), e))?;
+                Ok(PassReport { name: pass.name().to_string(), changed })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passes_run_in_order() {
+        let mut program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "DefineFunction", "target": "double", "params": {"args": ["x"], "body": [
+                {"actor": "VM", "op": "Return", "target": "result", "params": {"value": {"expr": {"op": "*", "left": {"var": "x"}, "right": 2}}}}
+            ]}},
+            {"actor": "VM", "op": "Bind", "target": "y", "params": {"value": {"call": "double", "args": {"x": 21}}}}
+        ]}"#).unwrap();
+
+        let manager = PassManager::from_specs(&["inline".into(), "rename-actors:VM=BrainVM".into()]).unwrap();
+        let reports = manager.run(&mut program).unwrap();
+
+        assert_eq!(reports, vec![
+            PassReport { name: "inline".into(), changed: true },
+            PassReport { name: "rename-actors".into(), changed: true },
+        ]);
+        assert!(program.actions.iter().all(|a| a.actor == "BrainVM"));
+        assert_eq!(
+            program.actions[1].params.as_ref().unwrap()["value"],
+            serde_json::json!({"expr": {"op": "*", "left": 21, "right": 2}})
+        );
+    }
+
+    #[test]
+    fn test_unknown_pass() {
+        let error = pass_named("unroll").err().unwrap();
+        assert!(error.to_string().starts_with("Unknown pass: unroll. Available: inline"));
+        assert!(pass_named("rename-actors").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Renaming actors, e.g. to move a program onto another substrate
+
+use super::Pass;
+use crate::Program;
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+
+/// Give actors new names, in nested actions and function bodies too
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenameActors {
+    pub renames: BTreeMap<String, String>,
+}
+
+impl RenameActors {
+    /// `VM=BrainVM,robot=RobotVM`
+    pub fn parse(args: &str) -> Result<Self> {
+        let mut renames = BTreeMap::new();
+        for pair in args.split(',') {
+            match pair.split_once('=') {
+                Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+                    renames.insert(from.trim().to_string(), to.trim().to_string());
+                }
+                _ => bail!("expected OLD=NEW, got '{}'", pair),
+            }
+        }
+        Ok(Self { renames })
+    }
+}
+
+impl Pass for RenameActors {
+    fn name(&self) -> &str {
+        "rename-actors"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<bool> {
+        let mut changed = false;
+        program.walk_mut(&mut |action, _| {
+            if let Some(to) = self.renames.get(&action.actor) {
+                action.actor = to.clone();
+                changed = true;
+            }
+        });
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let pass = RenameActors::parse("VM=BrainVM, robot = RobotVM").unwrap();
+        assert_eq!(pass.renames["VM"], "BrainVM");
+        assert_eq!(pass.renames["robot"], "RobotVM");
+        assert!(RenameActors::parse("VM").is_err());
+        assert!(RenameActors::parse("VM=").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::coordinator::MultiSubstrateCoordinator;
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Substrate panes, left to right
+const PANES: &[(&str, &str)] = &[
+    ("RubyVM", "💎 Ruby VM"),
+    ("BrainVM", "🧠 Brain VM"),
+    ("RobotVM", "🦾 Robot VM"),
+    ("Coordinator", "🌐 Coordinator"),
+];
+
+/// Live view of a `MultiSubstrateCoordinator` run
+///
+/// Takes over the terminal on `start` and gives it back when dropped.
+pub struct Dashboard {
+    terminal: DefaultTerminal,
+    title: String,
+}
+
+impl Dashboard {
+    pub fn start(title: impl Into<String>) -> Result<Self> {
+        Ok(Self {
+            terminal: ratatui::try_init()?,
+            title: title.into(),
+        })
+    }
+
+    /// Redraw after a step
+    pub fn update(&mut self, coordinator: &MultiSubstrateCoordinator, done: usize, total: usize) -> Result<()> {
+        let title = self.title.as_str();
+        self.terminal.draw(|frame| render(frame, title, coordinator, done, total, "running…"))?;
+        Ok(())
+    }
+
+    /// Show the final state until the user presses q or Esc
+    pub fn finish(&mut self, coordinator: &MultiSubstrateCoordinator, outcome: &Result<()>) -> Result<()> {
+        let status = match outcome {
+            Ok(()) => "✨ finished — press q to quit".to_string(),
+            Err(e) => format!("❌ {} — press q to quit", e),
+        };
+        let title = self.title.as_str();
+
+        loop {
+            self.terminal.draw(|frame| render(frame, title, coordinator, 1, 1, &status))?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// Draw one frame: progress, a pane per substrate, then shared memory
+pub fn render(
+    frame: &mut Frame,
+    title: &str,
+    coordinator: &MultiSubstrateCoordinator,
+    done: usize,
+    total: usize,
+    status: &str,
+) {
+    let [header, panes, memory] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(8),
+        Constraint::Length(8),
+    ]).areas(frame.area());
+
+    let ratio = if total == 0 { 1.0 } else { done as f64 / total as f64 };
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::bordered().title(format!(" {} ", title)))
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .ratio(ratio.min(1.0))
+            .label(format!("step {}/{} · {}", done, total, status)),
+        header,
+    );
+
+    let columns = Layout::horizontal(PANES.iter().map(|_| Constraint::Ratio(1, PANES.len() as u32)))
+        .split(panes);
+    for ((substrate, label), area) in PANES.iter().zip(columns.iter()) {
+        render_substrate(frame, *area, coordinator, substrate, label);
+    }
+
+    let mut entries: Vec<_> = coordinator.shared_memory().iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    let lines: Vec<Line> = entries.iter()
+        .map(|(key, value)| Line::from(format!("{} = {}", key, value)))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Shared Memory ")),
+        memory,
+    );
+}
+
+fn render_substrate(frame: &mut Frame, area: Rect, coordinator: &MultiSubstrateCoordinator, substrate: &str, label: &str) {
+    let activity = coordinator.activity(substrate).cloned().unwrap_or_default();
+
+    let title = if activity.errors > 0 {
+        Line::from(format!(" {} · {} errors ", label, activity.errors)).red()
+    } else {
+        Line::from(format!(" {} ", label))
+    };
+
+    let mut lines = vec![
+        Line::from(format!("▶ {}", activity.current.as_deref().unwrap_or("idle"))).bold(),
+        Line::from(format!("{} actions", activity.actions)).dim(),
+        Line::from(""),
+    ];
+
+    // Newest output at the bottom, trimmed to what fits
+    let room = (area.height as usize).saturating_sub(2 + lines.len());
+    let skip = activity.output.len().saturating_sub(room);
+    lines.extend(activity.output.iter().skip(skip).map(|line| Line::from(line.as_str())));
+
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn test_render_shows_panes_and_memory() {
+        let json = r#"{"actions": [
+            {"actor": "BrainVM", "op": "Bind", "target": "x", "params": {"value": 3}},
+            {"actor": "Coordinator", "op": "Receive", "target": "x", "params": {"source": "BrainVM"}}
+        ]}"#;
+        let mut coordinator = MultiSubstrateCoordinator::new();
+        coordinator.execute(&Program::from_json(json).unwrap()).unwrap();
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 24)).unwrap();
+        terminal.draw(|frame| render(frame, "test", &coordinator, 2, 2, "done")).unwrap();
+
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("Brain VM"));
+        assert!(screen.contains("Shared Memory"));
+        assert!(screen.contains("x = 3"));
+    }
+}
@@ -0,0 +1,584 @@
+use crate::{Action, Operation, Program};
+use anyhow::{Result, anyhow};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+use std::fs;
+use std::path::PathBuf;
+
+const HELP: &str = "j/k move · J/K reorder · a add · n add nested · d delete · \
+r actor · o op · t target · p params · c condition · s save · q quit";
+
+/// Which list an action lives in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Root,
+    Then,
+    Else,
+    Body,
+    Catch,
+}
+
+impl Slot {
+    fn name(&self) -> &'static str {
+        match self {
+            Slot::Root => "actions",
+            Slot::Then => "then",
+            Slot::Else => "else",
+            Slot::Body => "body",
+            Slot::Catch => "catch",
+        }
+    }
+
+    fn of_mut(self, action: &mut Action) -> &mut Option<Vec<Action>> {
+        match self {
+            Slot::Root => unreachable!("root actions have no parent"),
+            Slot::Then => &mut action.then_actions,
+            Slot::Else => &mut action.else_actions,
+            Slot::Body => &mut action.body_actions,
+            Slot::Catch => &mut action.catch_actions,
+        }
+    }
+}
+
+/// Route to an action: `(Slot::Root, i)` first, then one step per nesting level
+pub type ActionPath = Vec<(Slot, usize)>;
+
+/// One line of the action tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub path: ActionPath,
+    pub depth: usize,
+    pub text: String,
+}
+
+/// Field being edited in the input line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Actor,
+    Op,
+    Target,
+    Params,
+    Condition,
+}
+
+impl Field {
+    fn label(&self) -> &'static str {
+        match self {
+            Field::Actor => "actor",
+            Field::Op => "op",
+            Field::Target => "target",
+            Field::Params => "params (JSON)",
+            Field::Condition => "condition (JSON)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Browse,
+    Input { field: Field, buffer: String },
+}
+
+/// Structural editor state, independent of the terminal so it can be tested
+pub struct Editor {
+    pub program: Program,
+    path: PathBuf,
+    cursor: usize,
+    mode: Mode,
+    status: String,
+    dirty: bool,
+    confirm_quit: bool,
+}
+
+impl Editor {
+    pub fn new(program: Program, path: PathBuf) -> Self {
+        Self {
+            program,
+            path,
+            cursor: 0,
+            mode: Mode::Browse,
+            status: HELP.to_string(),
+            dirty: false,
+            confirm_quit: false,
+        }
+    }
+
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let program = Program::load(&path)?;
+        Ok(Self::new(program, path))
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    /// The action tree flattened into display rows
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        collect_rows(&self.program.actions, Slot::Root, &Vec::new(), &mut rows);
+        rows
+    }
+
+    pub fn selected(&self) -> Option<ActionPath> {
+        self.rows().get(self.cursor).map(|row| row.path.clone())
+    }
+
+    fn select(&mut self, path: &ActionPath) {
+        if let Some(index) = self.rows().iter().position(|row| &row.path == path) {
+            self.cursor = index;
+        }
+    }
+
+    pub fn action(&self, path: &ActionPath) -> Option<&Action> {
+        let ((_, first), rest) = path.split_first()?;
+        let mut action = self.program.actions.get(*first)?;
+        for &(slot, index) in rest {
+            let nested = match slot {
+                Slot::Root => return None,
+                Slot::Then => &action.then_actions,
+                Slot::Else => &action.else_actions,
+                Slot::Body => &action.body_actions,
+                Slot::Catch => &action.catch_actions,
+            };
+            action = nested.as_ref()?.get(index)?;
+        }
+        Some(action)
+    }
+
+    fn action_mut(&mut self, path: &ActionPath) -> Option<&mut Action> {
+        let (&(_, index), parent) = path.split_last()?;
+        siblings_mut(&mut self.program.actions, parent, path.last()?.0)?.get_mut(index)
+    }
+
+    fn changed(&mut self, status: impl Into<String>) {
+        self.dirty = true;
+        self.confirm_quit = false;
+        self.status = status.into();
+    }
+
+    pub fn move_cursor(&mut self, delta: isize) {
+        let len = self.rows().len();
+        if len > 0 {
+            self.cursor = self.cursor.saturating_add_signed(delta).min(len - 1);
+        }
+    }
+
+    /// Swap the selected action with its previous/next sibling
+    pub fn move_selected(&mut self, delta: isize) {
+        let Some(mut path) = self.selected() else { return };
+        let (slot, index) = path.pop().expect("rows have non-empty paths");
+
+        let Some(list) = siblings_mut(&mut self.program.actions, &path, slot) else { return };
+        let Some(other) = index.checked_add_signed(delta).filter(|&i| i < list.len()) else { return };
+        list.swap(index, other);
+
+        path.push((slot, other));
+        self.select(&path);
+        self.changed("Moved action");
+    }
+
+    /// Add a new action after the selection (or as the first action)
+    pub fn add_after(&mut self) {
+        let selected = self.selected();
+        let actor = selected.as_ref()
+            .and_then(|p| self.action(p).map(|a| a.actor.clone()))
+            .unwrap_or_else(|| "actor".to_string());
+        let action = Action::new(actor, Operation::Emit, "new");
+
+        let mut path = selected.unwrap_or_else(|| vec![(Slot::Root, usize::MAX)]);
+        let (slot, index) = path.pop().expect("non-empty path");
+        let Some(list) = siblings_mut(&mut self.program.actions, &path, slot) else { return };
+        let at = index.saturating_add(1).min(list.len());
+        list.insert(at, action);
+
+        path.push((slot, at));
+        self.select(&path);
+        self.changed("Added action");
+    }
+
+    /// Add a child to the selected If (then) or While/For (body)
+    pub fn add_nested(&mut self) {
+        let Some(path) = self.selected() else { return };
+        let Some(action) = self.action_mut(&path) else { return };
+
+        let slot = match action.op {
+            Operation::If => Slot::Then,
+            Operation::While | Operation::For | Operation::OnInterrupt | Operation::Try | Operation::Parallel | Operation::When
+            | Operation::Imagine => Slot::Body,
+            _ => {
+                self.status = "Only If, While, For, OnInterrupt, Try, Parallel, When and Imagine actions have nested blocks".to_string();
+                return;
+            }
+        };
+
+        let child = Action::new(action.actor.clone(), Operation::Emit, "new");
+        slot.of_mut(action).get_or_insert_with(Vec::new).push(child);
+        let index = slot.of_mut(action).as_ref().map_or(0, |l| l.len() - 1);
+
+        let mut child_path = path;
+        child_path.push((slot, index));
+        self.select(&child_path);
+        self.changed("Added nested action");
+    }
+
+    pub fn delete_selected(&mut self) {
+        let Some(mut path) = self.selected() else { return };
+        let (slot, index) = path.pop().expect("non-empty path");
+        let Some(list) = siblings_mut(&mut self.program.actions, &path, slot) else { return };
+        list.remove(index);
+
+        self.move_cursor(0);
+        self.changed("Deleted action");
+    }
+
+    /// Current value of a field, as shown in the input line
+    fn field_value(&self, field: Field) -> String {
+        let Some(action) = self.selected().and_then(|p| self.action(&p).cloned()) else {
+            return String::new();
+        };
+        match field {
+            Field::Actor => action.actor,
+            Field::Op => op_name(&action.op),
+            Field::Target => action.target,
+            Field::Params => action.params.map(|p| serde_json::to_string(&p).unwrap_or_default()).unwrap_or_default(),
+            Field::Condition => action.condition.map(|c| serde_json::to_string(&c).unwrap_or_default()).unwrap_or_default(),
+        }
+    }
+
+    /// Set a field on the selected action from its text form
+    pub fn set_field(&mut self, field: Field, text: &str) -> Result<()> {
+        let path = self.selected().ok_or_else(|| anyhow!("No action selected"))?;
+        let action = self.action_mut(&path).ok_or_else(|| anyhow!("No action selected"))?;
+        let text = text.trim();
+
+        match field {
+            Field::Actor => action.actor = text.to_string(),
+            Field::Target => action.target = text.to_string(),
+            Field::Op => action.op = parse_op(text)?,
+            Field::Params if text.is_empty() => action.params = None,
+            Field::Params => action.params = Some(serde_json::from_str(text)?),
+            Field::Condition if text.is_empty() => action.condition = None,
+            Field::Condition => action.condition = Some(serde_json::from_str(text)?),
+        }
+
+        self.changed(format!("Set {}", field.label()));
+        Ok(())
+    }
+
+    /// Validate and write the program back to its file
+    pub fn save(&mut self) -> Result<()> {
+        let problems = validate(&self.program);
+        if let Some(problem) = problems.first() {
+            return Err(anyhow!("{} (and {} more)", problem, problems.len() - 1));
+        }
+
+        let json = self.program.to_json()?;
+        Program::from_json(&json)?;
+        // In the format the file was opened in, as `Program::load` reads it
+        let content = match self.path.extension().and_then(|ext| ext.to_str()) {
+            Some("ucl") => self.program.to_text()?,
+            Some("yaml" | "yml") => self.program.to_yaml()?,
+            _ => json,
+        };
+        fs::write(&self.path, content)?;
+
+        self.dirty = false;
+        self.status = format!("Saved {}", self.path.display());
+        Ok(())
+    }
+
+    /// Handle a key press; returns false when the editor should close
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if let Mode::Input { field, buffer } = &mut self.mode {
+            match key.code {
+                KeyCode::Esc => {
+                    self.mode = Mode::Browse;
+                    self.status = HELP.to_string();
+                }
+                KeyCode::Enter => {
+                    let (field, text) = (*field, buffer.clone());
+                    self.mode = Mode::Browse;
+                    if let Err(e) = self.set_field(field, &text) {
+                        self.status = format!("Invalid {}: {}", field.label(), e);
+                    }
+                }
+                KeyCode::Backspace => {
+                    buffer.pop();
+                }
+                KeyCode::Char(c) => buffer.push(c),
+                _ => {}
+            }
+            return true;
+        }
+
+        let field = match key.code {
+            KeyCode::Char('r') => Some(Field::Actor),
+            KeyCode::Char('o') => Some(Field::Op),
+            KeyCode::Char('t') => Some(Field::Target),
+            KeyCode::Char('p') => Some(Field::Params),
+            KeyCode::Char('c') => Some(Field::Condition),
+            _ => None,
+        };
+        if let Some(field) = field {
+            if self.selected().is_some() {
+                let buffer = self.field_value(field);
+                self.mode = Mode::Input { field, buffer };
+            }
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => self.move_cursor(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Char('J') => self.move_selected(1),
+            KeyCode::Char('K') => self.move_selected(-1),
+            KeyCode::Char('a') => self.add_after(),
+            KeyCode::Char('n') => self.add_nested(),
+            KeyCode::Char('d') => self.delete_selected(),
+            KeyCode::Char('s') => {
+                if let Err(e) = self.save() {
+                    self.status = format!("Not saved: {}", e);
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if !self.dirty || self.confirm_quit {
+                    return false;
+                }
+                self.confirm_quit = true;
+                self.status = "Unsaved changes — press q again to discard them".to_string();
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+fn collect_rows(actions: &[Action], slot: Slot, parent: &ActionPath, rows: &mut Vec<Row>) {
+    for (index, action) in actions.iter().enumerate() {
+        let mut path = parent.clone();
+        path.push((slot, index));
+
+        let label = if slot == Slot::Root { String::new() } else { format!("{}: ", slot.name()) };
+        rows.push(Row {
+            depth: parent.len(),
+            text: format!("{}{} {} → {}", label, op_name(&action.op), action.actor, action.target),
+            path: path.clone(),
+        });
+
+        for (nested_slot, nested) in [
+            (Slot::Then, &action.then_actions),
+            (Slot::Else, &action.else_actions),
+            (Slot::Body, &action.body_actions),
+            (Slot::Catch, &action.catch_actions),
+        ] {
+            if let Some(nested) = nested {
+                collect_rows(nested, nested_slot, &path, rows);
+            }
+        }
+    }
+}
+
+/// The list containing the action at `parent + (slot, _)`
+fn siblings_mut<'a>(actions: &'a mut Vec<Action>, parent: &[(Slot, usize)], slot: Slot) -> Option<&'a mut Vec<Action>> {
+    let Some((&(_, first), rest)) = parent.split_first() else {
+        return (slot == Slot::Root).then_some(actions);
+    };
+
+    let mut action = actions.get_mut(first)?;
+    for &(step, index) in rest {
+        action = step.of_mut(action).as_mut()?.get_mut(index)?;
+    }
+    slot.of_mut(action).as_mut()
+}
+
+fn op_name(op: &Operation) -> String {
+    match op {
+        Operation::Custom(name) => name.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Operation from its name; unknown names become `Custom`
+fn parse_op(name: &str) -> Result<Operation> {
+    if name.is_empty() {
+        return Err(anyhow!("operation name is empty"));
+    }
+    Ok(serde_json::from_value(serde_json::json!(name)).unwrap_or_else(|_| Operation::Custom(name.to_string())))
+}
+
+/// Structural problems that would make the program fail to run
+pub fn validate(program: &Program) -> Vec<String> {
+    let mut problems = Vec::new();
+    program.walk(&mut |action, _| {
+        let describe = || format!("{} {} → {}", op_name(&action.op), action.actor, action.target);
+        if action.actor.trim().is_empty() || action.target.trim().is_empty() {
+            problems.push(format!("{}: actor and target are required", describe()));
+        }
+        match action.op {
+            Operation::If | Operation::While if action.condition.is_none() => {
+                problems.push(format!("{}: needs a condition", describe()));
+            }
+            Operation::For if action.loop_var.is_none() || action.from_expr.is_none() || action.to_expr.is_none() => {
+                problems.push(format!("{}: needs variable, from and to", describe()));
+            }
+            _ => {}
+        }
+    });
+    problems
+}
+
+/// Draw the tree, the selected action's JSON and the status/input line
+pub fn render(frame: &mut Frame, editor: &Editor) {
+    let [main, footer] = Layout::vertical([Constraint::Min(5), Constraint::Length(3)]).areas(frame.area());
+    let [tree, detail] = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(main);
+
+    let rows = editor.rows();
+    let items: Vec<ListItem> = rows.iter()
+        .map(|row| ListItem::new(format!("{}{}", "  ".repeat(row.depth), row.text)))
+        .collect();
+    let title = format!(" {}{} ", editor.path.display(), if editor.dirty { " *" } else { "" });
+    let mut state = ListState::default().with_selected((!rows.is_empty()).then_some(editor.cursor));
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan)),
+        tree,
+        &mut state,
+    );
+
+    let json = rows.get(editor.cursor)
+        .and_then(|row| editor.action(&row.path))
+        .map(|action| serde_json::to_string_pretty(&action).unwrap_or_default())
+        .unwrap_or_else(|| "No actions — press a to add one".to_string());
+    frame.render_widget(
+        Paragraph::new(json).wrap(Wrap { trim: false }).block(Block::bordered().title(" Action ")),
+        detail,
+    );
+
+    let line = match &editor.mode {
+        Mode::Browse => Line::from(editor.status.as_str()).dim(),
+        Mode::Input { field, buffer } => Line::from(format!("{}: {}▏", field.label(), buffer)).bold(),
+    };
+    frame.render_widget(Paragraph::new(line).block(Block::bordered()), footer);
+}
+
+/// Run the editor on `path` until the user quits
+pub fn run(path: PathBuf) -> Result<()> {
+    let mut editor = Editor::open(path)?;
+    let mut terminal = ratatui::try_init()?;
+
+    let outcome = (|| -> Result<()> {
+        loop {
+            terminal.draw(|frame| render(frame, &editor))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !editor.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    })();
+
+    ratatui::restore();
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    fn editor() -> Editor {
+        let json = r#"{"actions": [
+            {"actor": "VM", "op": "Bind", "target": "x", "params": {"value": 1}},
+            {"actor": "VM", "op": "If", "target": "check",
+             "condition": {"type": "comparison", "op": "==", "left": {"var": "x"}, "right": 1},
+             "then": [{"actor": "VM", "op": "Emit", "target": "one"}]}
+        ]}"#;
+        Editor::new(Program::from_json(json).unwrap(), std::env::temp_dir().join("ucl_editor_test.json"))
+    }
+
+    fn press(editor: &mut Editor, keys: &str) {
+        for c in keys.chars() {
+            let code = if c == '\n' { KeyCode::Enter } else { KeyCode::Char(c) };
+            editor.handle_key(KeyEvent::from(code));
+        }
+    }
+
+    #[test]
+    fn test_tree_includes_nested_actions() {
+        let rows = editor().rows();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2].depth, 1);
+        assert_eq!(rows[2].text, "then: Emit VM → one");
+    }
+
+    #[test]
+    fn test_add_reorder_edit_and_delete() {
+        let mut editor = editor();
+
+        // Add after the Bind, rename it, and move it to the top
+        press(&mut editor, "a");
+        editor.set_field(Field::Target, "hello").unwrap();
+        press(&mut editor, "K");
+        assert_eq!(editor.program.actions[0].target, "hello");
+
+        // Nested child under the If, then change its op through the input line
+        press(&mut editor, "jjn");
+        press(&mut editor, "o");
+        if let Mode::Input { buffer, .. } = &mut editor.mode {
+            buffer.clear();
+        }
+        press(&mut editor, "Wait\n");
+        let then = editor.program.actions[2].then_actions.as_ref().unwrap();
+        assert_eq!(then.len(), 2);
+        assert_eq!(then[1].op, Operation::Wait);
+
+        press(&mut editor, "d");
+        assert_eq!(editor.program.actions[2].then_actions.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_save_validates() {
+        let mut editor = editor();
+        press(&mut editor, "j");
+        editor.set_field(Field::Condition, "").unwrap();
+        assert!(editor.save().unwrap_err().to_string().contains("needs a condition"));
+    }
+
+    #[test]
+    fn test_opens_and_saves_yaml() {
+        let path = std::env::temp_dir().join(format!("ucl_editor_test_{}.yaml", std::process::id()));
+        fs::write(&path, "actions:\n- actor: VM\n  op: Emit\n  target: hi\n").unwrap();
+
+        let mut editor = Editor::open(path.clone()).unwrap();
+        editor.set_field(Field::Target, "hello").unwrap();
+        editor.save().unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Program::from_yaml(&saved).unwrap().actions[0].target, "hello");
+    }
+
+    #[test]
+    fn test_quit_asks_before_discarding() {
+        let mut editor = editor();
+        press(&mut editor, "a");
+        assert!(editor.handle_key(KeyEvent::from(KeyCode::Char('q'))));
+        assert!(!editor.handle_key(KeyEvent::from(KeyCode::Char('q'))));
+    }
+
+    #[test]
+    fn test_render_shows_tree_and_json() {
+        let editor = editor();
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| render(frame, &editor)).unwrap();
+
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("Bind VM → x"));
+        assert!(screen.contains("\"target\": \"x\""));
+    }
+}
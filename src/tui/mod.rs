@@ -0,0 +1,7 @@
+//! Terminal user interfaces (`tui` feature)
+
+pub mod dashboard;
+pub mod editor;
+
+pub use dashboard::Dashboard;
+pub use editor::Editor;
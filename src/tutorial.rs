@@ -0,0 +1,280 @@
+//! Interactive walkthrough behind `ucl learn`
+//!
+//! Each lesson introduces one operation family with a small program. The
+//! learner predicts part of the final brain state, steps through the program
+//! on the brain simulator, and sees whether the prediction held.
+
+use crate::simulator::{BrainSimulator, BrainState};
+use crate::{Action, Program};
+use anyhow::{Result, anyhow};
+use std::io::{BufRead, Write};
+
+/// What the learner is asked to predict
+#[derive(Debug, Clone, Copy)]
+pub enum Quiz {
+    /// Final value of a belief (variable)
+    Belief(&'static str),
+    /// A line the brain emits, by position
+    Output(usize),
+}
+
+pub struct Lesson {
+    pub title: &'static str,
+    pub explanation: &'static str,
+    pub program: &'static str,
+    pub question: &'static str,
+    pub quiz: Quiz,
+}
+
+pub const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "Communication: Emit",
+        explanation: "Every UCL action is actor → op → target. Emit makes the actor say something:\n\
+                      the `content` param, or the target's value if the target is a known variable.",
+        program: r#"{"actions": [
+            {"actor": "you", "op": "Emit", "target": "greeting", "params": {"content": "Hello"}},
+            {"actor": "you", "op": "Emit", "target": "farewell", "params": {"content": "Goodbye"}}
+        ]}"#,
+        question: "What is the second line the brain says?",
+        quiz: Quiz::Output(1),
+    },
+    Lesson {
+        title: "Binding and memory: Bind, Write",
+        explanation: "Bind stores a value under a name. Write with an `operation` computes from\n\
+                      registers (`lhs_register`) or literals (`rhs`) and stores the result in the target.",
+        program: r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "x", "params": {"value": 3}},
+            {"actor": "you", "op": "Write", "target": "y", "params": {"operation": "multiply", "lhs_register": "x", "rhs": 4}},
+            {"actor": "you", "op": "Write", "target": "y", "params": {"operation": "subtract", "lhs_register": "y", "rhs": 2}}
+        ]}"#,
+        question: "What is y at the end?",
+        quiz: Quiz::Belief("y"),
+    },
+    Lesson {
+        title: "Knowledge: StoreFact",
+        explanation: "StoreFact records properties of an entity. Each param becomes a belief named\n\
+                      `entity.property`; a later fact about the same property replaces the earlier one.",
+        program: r#"{"actions": [
+            {"actor": "you", "op": "StoreFact", "target": "memory", "params": {"entity": "cat", "color": "black", "legs": 4}},
+            {"actor": "you", "op": "StoreFact", "target": "memory", "params": {"entity": "cat", "color": "white"}}
+        ]}"#,
+        question: "What is cat.color at the end?",
+        quiz: Quiz::Belief("cat.color"),
+    },
+    Lesson {
+        title: "Decisions: If",
+        explanation: "If evaluates its `condition` and runs the `then` actions when it holds,\n\
+                      otherwise the `else` actions. Conditions compare expressions such as {\"var\": \"x\"}.",
+        program: r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "temperature", "params": {"value": 18}},
+            {"actor": "you", "op": "If", "target": "weather",
+             "condition": {"type": "comparison", "op": ">", "left": {"var": "temperature"}, "right": 20},
+             "then": [{"actor": "you", "op": "Emit", "target": "t-shirt", "params": {"content": "Wear a t-shirt"}}],
+             "else": [{"actor": "you", "op": "Emit", "target": "jumper", "params": {"content": "Wear a jumper"}}]}
+        ]}"#,
+        question: "What does the brain say?",
+        quiz: Quiz::Output(0),
+    },
+    Lesson {
+        title: "Loops: For",
+        explanation: "For binds `variable` to each value from `from` to `to` (inclusive) and runs\n\
+                      the `body` each time.",
+        program: r#"{"actions": [
+            {"actor": "you", "op": "Bind", "target": "sum", "params": {"value": 0}},
+            {"actor": "you", "op": "For", "target": "count", "variable": "i", "from": 1, "to": 4,
+             "body": [{"actor": "you", "op": "Write", "target": "sum", "params": {"operation": "add", "lhs_register": "sum", "rhs_register": "i"}}]}
+        ]}"#,
+        question: "What is sum at the end?",
+        quiz: Quiz::Belief("sum"),
+    },
+    Lesson {
+        title: "Functions: DefineFunction",
+        explanation: "DefineFunction teaches the brain a procedure with named args and a body that\n\
+                      Returns a value. A {\"call\": name, \"args\": {...}} expression runs it.",
+        program: r#"{"actions": [
+            {"actor": "you", "op": "DefineFunction", "target": "double",
+             "params": {"args": ["n"], "body": [
+                {"actor": "you", "op": "Return", "target": "result",
+                 "params": {"value": {"expr": {"op": "*", "left": {"var": "n"}, "right": 2}}}}
+             ]}},
+            {"actor": "you", "op": "Bind", "target": "answer", "params": {"value": {"call": "double", "args": {"n": 21}}}}
+        ]}"#,
+        question: "What is answer at the end?",
+        quiz: Quiz::Belief("answer"),
+    },
+];
+
+/// Correct answers and lessons completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Score {
+    pub correct: usize,
+    pub completed: usize,
+}
+
+impl Lesson {
+    pub fn program(&self) -> Program {
+        Program::from_json(self.program).expect("tutorial programs are valid")
+    }
+
+    /// The expected answer, found by running the program
+    pub fn answer(&self) -> Result<String> {
+        let mut brain = BrainSimulator::new();
+        brain.execute(&self.program())?;
+        answer_in(brain.state(), self.quiz)
+    }
+}
+
+fn answer_in(state: &BrainState, quiz: Quiz) -> Result<String> {
+    match quiz {
+        Quiz::Belief(name) => state.beliefs.get(name)
+            .map(display_value)
+            .ok_or_else(|| anyhow!("{} was never set", name)),
+        Quiz::Output(index) => state.output.get(index)
+            .cloned()
+            .ok_or_else(|| anyhow!("the brain said fewer than {} things", index + 1)),
+    }
+}
+
+/// `12.0` → `12`, `"white"` → `white`
+fn display_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", f as i64),
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+/// Whether a typed guess matches, ignoring case, quotes and `12` vs `12.0`
+pub fn matches(guess: &str, answer: &str) -> bool {
+    let clean = |s: &str| s.trim().trim_matches('"').to_lowercase();
+    let (guess, answer) = (clean(guess), clean(answer));
+    match (guess.parse::<f64>(), answer.parse::<f64>()) {
+        (Ok(g), Ok(a)) => (g - a).abs() < 1e-9,
+        _ => guess == answer,
+    }
+}
+
+fn describe(action: &Action) -> String {
+    let mut line = format!("{} {:?} {}", action.actor, action.op, action.target);
+    if let Some(params) = &action.params {
+        let mut keys: Vec<_> = params.iter().collect();
+        keys.sort_by(|a, b| a.0.cmp(b.0));
+        let shown: Vec<String> = keys.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        line.push_str(&format!("  {{{}}}", shown.join(", ")));
+    }
+    line
+}
+
+/// Reads lines from the learner, treating end of input or `q` as quitting
+struct Prompt<'a, R, W> {
+    input: &'a mut R,
+    output: &'a mut W,
+}
+
+impl<R: BufRead, W: Write> Prompt<'_, R, W> {
+    fn ask(&mut self, question: &str) -> Result<Option<String>> {
+        write!(self.output, "{}", question)?;
+        self.output.flush()?;
+
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 || line.trim() == "q" {
+            return Ok(None);
+        }
+        Ok(Some(line.trim().to_string()))
+    }
+}
+
+/// Run lessons from `start` (0-based), returning the score; `q` or end of
+/// input stops early
+pub fn run<R: BufRead, W: Write>(input: &mut R, output: &mut W, start: usize) -> Result<Score> {
+    let mut score = Score::default();
+    let mut prompt = Prompt { input, output };
+
+    for (number, lesson) in LESSONS.iter().enumerate().skip(start) {
+        let program = lesson.program();
+        let answer = lesson.answer()?;
+
+        writeln!(prompt.output, "\n━━━ Lesson {}/{}: {} ━━━\n", number + 1, LESSONS.len(), lesson.title)?;
+        writeln!(prompt.output, "{}\n", lesson.explanation)?;
+        writeln!(prompt.output, "Program:")?;
+        for (i, action) in program.actions.iter().enumerate() {
+            writeln!(prompt.output, "  {}. {}", i + 1, describe(action))?;
+        }
+
+        let Some(guess) = prompt.ask(&format!("\n🤔 {} ", lesson.question))? else {
+            return Ok(score);
+        };
+
+        // Step through on the brain, one top-level action at a time
+        let mut brain = BrainSimulator::new();
+        for (i, action) in program.actions.iter().enumerate() {
+            if prompt.ask(&format!("   ⏎ step {}: {} ", i + 1, describe(action)))?.is_none() {
+                return Ok(score);
+            }
+
+            let before = brain.state().clone();
+            brain.execute(&Program { metadata: None, actions: vec![action.clone()] })?;
+            let after = brain.state();
+
+            let mut changed: Vec<_> = after.beliefs.iter()
+                .filter(|(k, v)| before.beliefs.get(*k) != Some(*v))
+                .collect();
+            changed.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, value) in changed {
+                writeln!(prompt.output, "      🧠 {} = {}", name, display_value(value))?;
+            }
+            for said in &after.output[before.output.len()..] {
+                writeln!(prompt.output, "      💬 {}", said)?;
+            }
+        }
+
+        score.completed += 1;
+        if matches(&guess, &answer) {
+            score.correct += 1;
+            writeln!(prompt.output, "\n✅ Correct: {}", answer)?;
+        } else {
+            writeln!(prompt.output, "\n❌ You said {}, the answer is {}", guess, answer)?;
+        }
+    }
+
+    writeln!(prompt.output, "\n🎓 Done! {}/{} predictions correct.", score.correct, score.completed)?;
+    writeln!(prompt.output, "   Next: browse examples/ and try `ucl brain <file> --verbose`.")?;
+    Ok(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lesson_answers() {
+        let answers: Vec<String> = LESSONS.iter().map(|l| l.answer().unwrap()).collect();
+        assert_eq!(answers, vec!["Goodbye", "10", "white", "Wear a jumper", "10", "42"]);
+    }
+
+    #[test]
+    fn test_run_scores_predictions() {
+        // Lesson 1: right answer; lesson 2: wrong; then quit
+        let steps = |n: usize| "\n".repeat(n);
+        let script = format!("Goodbye\n{}11\n{}q\n", steps(2), steps(3));
+
+        let mut output = Vec::new();
+        let score = run(&mut script.as_bytes(), &mut output, 0).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(score, Score { correct: 1, completed: 2 });
+        assert!(output.contains("💬 Goodbye"));
+        assert!(output.contains("the answer is 10"));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(" 12 ", "12"));
+        assert!(matches("12", "12.0"));
+        assert!(matches("\"White\"", "white"));
+        assert!(!matches("11", "12"));
+    }
+}
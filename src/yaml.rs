@@ -0,0 +1,134 @@
+//! YAML reading and writing for programs, through `serde_yaml`
+//!
+//! Documents are read into JSON values first, so a YAML program goes through
+//! exactly the checks a JSON one does. Writing serializes the program itself,
+//! so actions keep their field order, then writes enum variants as the
+//! one-key maps JSON uses rather than YAML tags.
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Write a value as a YAML document
+pub fn to_yaml<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Ok(serde_yaml::to_string(&untag(serde_yaml::to_value(value)?))?)
+}
+
+/// `value` with every tagged value, like `!Custom Splice`, as a map from
+/// the tag to the value, like `{Custom: Splice}`
+fn untag(value: serde_yaml::Value) -> serde_yaml::Value {
+    use serde_yaml::Value as Yaml;
+    match value {
+        Yaml::Tagged(tagged) => {
+            let mut map = serde_yaml::Mapping::new();
+            let tag = tagged.tag.to_string();
+            map.insert(Yaml::String(tag.trim_start_matches('!').to_string()), untag(tagged.value));
+            Yaml::Mapping(map)
+        }
+        Yaml::Sequence(items) => Yaml::Sequence(items.into_iter().map(untag).collect()),
+        Yaml::Mapping(map) => Yaml::Mapping(map.into_iter().map(|(k, v)| (untag(k), untag(v))).collect()),
+        other => other,
+    }
+}
+
+/// Parse a YAML document into a JSON value
+pub fn from_yaml(source: &str) -> Result<Value> {
+    // Read as YAML's own value first, which rejects duplicate keys
+    let value: serde_yaml::Value = serde_yaml::from_str(source)?;
+    Ok(serde_json::to_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+
+    #[test]
+    fn test_parse_yaml() {
+        let value = from_yaml(r#"
+# A program
+metadata:
+  description: "Greeting: hello"   # quoted because of the colon
+  tags: [demo, 'it''s', {level: 1}]
+  notes: |
+    line one
+    line two
+actions:
+- actor: VM
+  op: Emit
+  target: greeting
+  t: 1.5
+  params:
+    content: Hello, World!
+    empty: {}
+    none: ~
+  effects:
+    - CPU
+"#).unwrap();
+
+        assert_eq!(value, serde_json::json!({
+            "metadata": {
+                "description": "Greeting: hello",
+                "tags": ["demo", "it's", {"level": 1}],
+                "notes": "line one\nline two\n"
+            },
+            "actions": [{
+                "actor": "VM", "op": "Emit", "target": "greeting", "t": 1.5,
+                "params": {"content": "Hello, World!", "empty": {}, "none": null},
+                "effects": ["CPU"]
+            }]
+        }));
+    }
+
+    #[test]
+    fn test_writes_actions_in_field_order() {
+        let program = Program::from_json(r#"{"actions": [
+            {"actor": "VM", "op": "If", "target": "check",
+             "condition": {"type": "comparison", "op": ">", "left": {"var": "x"}, "right": 1},
+             "then": [{"actor": "VM", "op": "Emit", "target": "out", "params": {"content": "yes: 100%", "n": 10}}]}
+        ]}"#).unwrap();
+
+        let yaml = to_yaml(&program).unwrap();
+        assert_eq!(yaml, "\
+actions:
+- actor: VM
+  op: If
+  target: check
+  condition:
+    type: comparison
+    op: '>'
+    left:
+      var: x
+    right: 1
+  then:
+  - actor: VM
+    op: Emit
+    target: out
+    params:
+      content: 'yes: 100%'
+      n: 10
+");
+        assert_eq!(serde_json::to_value(Program::from_yaml(&yaml).unwrap()).unwrap(), serde_json::to_value(&program).unwrap());
+    }
+
+    #[test]
+    fn test_round_trips_examples() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let program = Program::from_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let yaml = to_yaml(&program).unwrap();
+            let parsed = Program::from_yaml(&yaml).unwrap_or_else(|e| panic!("{}: {}\n{}", path.display(), e, yaml));
+            assert_eq!(serde_json::to_value(parsed).unwrap(), serde_json::to_value(&program).unwrap(), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_documents() {
+        let error = from_yaml("a:\n  b: 1\n    c: 2").unwrap_err().to_string();
+        assert!(error.contains("line 3"), "{}", error);
+        assert!(from_yaml("a: 1\na: 2").unwrap_err().to_string().contains("duplicate"));
+    }
+}
@@ -62,6 +62,16 @@ fn test_music_example() {
     }
 }
 
+#[test]
+fn test_music_macro_expands_to_music_example() {
+    let mut expanded = Program::load(std::path::Path::new("examples/music_macro.json")).unwrap();
+    assert_eq!(expanded.expand_macros().unwrap(), 8);
+    let written = Program::load(std::path::Path::new("examples/music.json")).unwrap();
+
+    let actions = |program: &Program| serde_json::to_value(&program.actions).unwrap();
+    assert_eq!(actions(&expanded), actions(&written));
+}
+
 #[test]
 fn test_legal_contract_example() {
     let content = fs::read_to_string("examples/legal_contract.json")
@@ -117,10 +127,7 @@ fn test_fibonacci_example() {
 fn test_fibonacci_compiles_and_runs() {
     use std::process::Command;
 
-    let content = fs::read_to_string("examples/fibonacci.json")
-        .expect("Failed to read fibonacci.json");
-    let program = Program::from_json(&content)
-        .expect("Failed to parse fibonacci.json");
+    let program = fibonacci_program();
 
     // Compile to Ruby
     let mut compiler = ucl::compiler::RubyCompiler::new();
@@ -154,6 +161,72 @@ fn test_fibonacci_compiles_and_runs() {
     assert!(stdout.contains("55"));
 }
 
+/// examples/fibonacci.json, parsed
+fn fibonacci_program() -> Program {
+    let content = fs::read_to_string("examples/fibonacci.json")
+        .expect("Failed to read fibonacci.json");
+    Program::from_json(&content).expect("Failed to parse fibonacci.json")
+}
+
+/// Run `command`, expecting it to print the sequence fibonacci.json prints
+fn assert_prints_fibonacci(command: &mut std::process::Command) {
+    let output = command.output().unwrap_or_else(|e| panic!("Failed to run {:?}: {}", command.get_program(), e));
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines, vec!["Fibonacci sequence:", "0", "1", "1", "2", "3", "5", "8", "13", "21", "34", "55"]);
+}
+
+#[test]
+fn test_fibonacci_compiles_to_javascript_and_runs() {
+    let js_code = ucl::compiler::JavaScriptCompiler::new().compile(&fibonacci_program())
+        .expect("Failed to compile fibonacci");
+    assert!(js_code.contains("function fibonacci(n)"));
+    assert!(js_code.contains("fibonacci((n - 1))"));
+
+    assert_prints_fibonacci(std::process::Command::new("node").arg("-e").arg(&js_code));
+}
+
+#[test]
+fn test_fibonacci_compiles_to_shell_and_runs() {
+    let script = ucl::compiler::ShellCompiler::new().compile(&fibonacci_program())
+        .expect("Failed to compile fibonacci");
+
+    assert_prints_fibonacci(std::process::Command::new("sh").arg("-c").arg(&script));
+}
+
+#[test]
+fn test_fibonacci_compiles_to_wasm_and_runs() {
+    let module = ucl::compiler::WasmCompiler::new().compile_binary(&fibonacci_program())
+        .expect("Failed to compile fibonacci");
+
+    assert_prints_fibonacci(std::process::Command::new("node").arg("-e").arg(ucl::compiler::wasm::node_runner(&module)));
+}
+
+#[test]
+fn test_fibonacci_compiles_to_native_rust_and_runs() {
+    use std::process::Command;
+
+    let rust_code = ucl::compiler::RustCompiler::new().compile(&fibonacci_program())
+        .expect("Failed to compile fibonacci");
+
+    let dir = std::env::temp_dir().join(format!("ucl_rust_fib_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.rs"), &rust_code).unwrap();
+
+    let build = Command::new("rustc")
+        .args(["--edition", "2021", "-o"])
+        .arg(dir.join("fib"))
+        .arg(dir.join("main.rs"))
+        .output()
+        .expect("Failed to run rustc");
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+
+    assert_prints_fibonacci(&mut Command::new(dir.join("fib")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
 #[test]
 fn test_ai_generate_factorial() {
     let content = fs::read_to_string("examples/ai_generate_factorial.json")
@@ -229,3 +302,32 @@ fn test_all_examples_roundtrip() {
     }
 }
 
+
+#[test]
+fn test_all_examples_pass_strict_validation() {
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+
+        // What `ucl validate --strict` checks
+        let (mut program, _) = Program::load_checked(&path, true)
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        program.expand_macros().unwrap();
+        let problems: Vec<String> = ucl::links::check(&program).iter().map(|p| p.display()).collect();
+        assert!(problems.is_empty(), "{}: {:?}", path.display(), problems);
+        let problems: Vec<String> = ucl::schema::check(&program).iter().map(|p| p.display()).collect();
+        assert!(problems.is_empty(), "{}: {:?}", path.display(), problems);
+    }
+}
+
+#[test]
+fn test_legal_contract_reads_as_english() {
+    let content = fs::read_to_string("examples/legal_contract.json").unwrap();
+    let program = Program::from_json(&content).unwrap();
+
+    let text = ucl::compiler::EnglishCompiler::new().compile(&program).unwrap();
+    assert!(text.contains("First, Buyer must pay by Delivery+5d"), "{}", text);
+    assert!(text.contains("Then, Buyer may return if defective or not as described within 30 days."), "{}", text);
+}